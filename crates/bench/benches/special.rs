@@ -20,6 +20,8 @@ fn custom_module_benchmarks(c: &mut Criterion) {
     let config = Config {
         storage: Storage::Memory,
         fsync: spacetimedb::db::FsyncPolicy::Never,
+        snapshot_interval: None,
+        compact_log: false,
     };
     let module = runtime.block_on(async { BENCHMARKS_MODULE.load_module(config).await });
 
@@ -70,6 +70,8 @@ impl BenchDatabase for SpacetimeModule {
                 FsyncPolicy::Never
             },
             storage: if in_memory { Storage::Memory } else { Storage::Disk },
+            snapshot_interval: None,
+            compact_log: false,
         };
         let module = runtime.block_on(async { BENCHMARKS_MODULE.load_module(config).await });
 
@@ -36,6 +36,9 @@ mod sym {
     /// Matches `crate`.
     pub const CRATE: Symbol = Symbol("crate");
 
+    /// Matches `default`.
+    pub const DEFAULT: Symbol = Symbol("default");
+
     /// Matches `name`.
     pub const NAME: Symbol = Symbol("name");
 
@@ -75,13 +78,72 @@ mod sym {
 /// The macro takes this `input`, which defines what the attribute does,
 /// and it is structured roughly like so:
 /// ```ignore
-/// input = table | init | connect | disconnect | migrate
-///       | reducer [, repeat = Duration]
+/// input = table [, filter = string] | init | connect | disconnect | migrate
+///       | reducer [, repeat = Duration | string [, misfire = skip | fire_once | fire_all]] [, allow = owner | path]
 ///       | index(btree | hash [, name = string] [, field_name:ident]*)
 /// ```
 ///
 /// For description of the field attributes on `#[spacetimedb(table)]` structs,
 /// see [`TableType`](spacetimedb_tabletype).
+///
+/// When an update to the module changes the columns of a table in a way that
+/// can't be migrated automatically, the `#[spacetimedb(migrate)]` reducer is
+/// called with the name of the table and its rows in the shape of the old columns,
+/// and is expected to insert them in the new shape:
+/// ```ignore
+/// #[spacetimedb(migrate)]
+/// pub fn migrate(table: String, rows: OldRows) -> Result<(), String> {
+///     let rows: Vec<PersonV1> = rows.decode().map_err(|e| e.to_string())?;
+///     ..
+/// }
+/// ```
+/// where `PersonV1` is a `SpacetimeType` with the old columns, as fields in the same order.
+/// The update is rolled back, leaving the old module in place, if it fails.
+///
+/// The other changes are migrated by the host: integer columns may be widened,
+/// and columns may be appended to a table, filled in the existing rows
+/// with the value declared by `#[default(expr)]` on the field,
+/// or else with the zero value of their type: `0`, `false`, `""`, `None`, or a struct of such values:
+/// ```ignore
+/// #[spacetimedb(table)]
+/// pub struct Person {
+///     name: String,
+///     #[default(18)]
+///     age: u32,
+/// }
+/// ```
+/// The `#[spacetimedb(update)]` reducer runs in the same transaction and may set the new columns too.
+/// The update is rolled back if that reducer fails.
+///
+/// A reducer with `allow = owner` may only be called by the owner of the database,
+/// and one with `allow = path` only if the guard function at `path` returns true:
+/// ```ignore
+/// fn is_admin(ctx: &ReducerContext) -> bool { .. }
+///
+/// #[spacetimedb(reducer, allow = is_admin)]
+/// pub fn ban(ctx: ReducerContext, player: Identity) { .. }
+/// ```
+/// Denied calls are rejected by the host before the reducer runs.
+/// Scheduled reducers are always allowed.
+///
+/// A reducer with `repeat = Duration` reschedules itself after each run,
+/// once something has first scheduled it.
+/// One with `repeat = "every <interval>"` or `repeat = "cron <expression>"`,
+/// which must not take any arguments, is scheduled by the host when the module is published,
+/// and the runs it misses while the host is down are handled per `misfire`, `fire_once` by default:
+/// ```ignore
+/// #[spacetimedb(reducer, repeat = "cron 0 4 * * *", misfire = skip)]
+/// pub fn nightly_cleanup(ctx: ReducerContext) { .. }
+/// ```
+///
+/// A table with a `filter` only shows a client other than the owner of the database
+/// the rows matching it, in subscriptions and SQL queries alike.
+/// The filter is a SQL condition on the columns of the table,
+/// where `:sender` is the `Identity` of the client:
+/// ```ignore
+/// #[spacetimedb(table, filter = "owner = :sender")]
+/// pub struct Message { owner: Identity, text: String }
+/// ```
 #[proc_macro_attribute]
 pub fn spacetimedb(macro_args: proc_macro::TokenStream, item: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let item: TokenStream = item.into();
@@ -100,9 +162,9 @@ pub fn spacetimedb(macro_args: proc_macro::TokenStream, item: proc_macro::TokenS
 /// On `item`, route the macro `input` to the various interpretations.
 fn route_input(input: MacroInput, item: TokenStream) -> syn::Result<TokenStream> {
     match input {
-        MacroInput::Table => spacetimedb_table(item),
+        MacroInput::Table { filter } => spacetimedb_table(filter, item),
         MacroInput::Init => spacetimedb_init(item),
-        MacroInput::Reducer { repeat } => spacetimedb_reducer(repeat, item),
+        MacroInput::Reducer { repeat, misfire, allow } => spacetimedb_reducer(repeat, misfire, allow, item),
         MacroInput::Connect => spacetimedb_special_reducer("__identity_connected__", item),
         MacroInput::Disconnect => spacetimedb_special_reducer("__identity_disconnected__", item),
         MacroInput::Migrate => spacetimedb_special_reducer("__migrate__", item),
@@ -122,10 +184,14 @@ fn duration_totokens(dur: Duration) -> TokenStream {
 
 /// Defines the input space of the `spacetimedb` macro.
 enum MacroInput {
-    Table,
+    Table {
+        filter: Option<String>,
+    },
     Init,
     Reducer {
-        repeat: Option<Duration>,
+        repeat: Option<RepeatSpec>,
+        misfire: Option<Ident>,
+        allow: Option<ReducerAuth>,
     },
     Connect,
     Disconnect,
@@ -174,23 +240,59 @@ fn check_duplicate_meta<T>(x: &Option<T>, meta: &syn::meta::ParseNestedMeta<'_>)
 impl syn::parse::Parse for MacroInput {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         Ok(match_tok!(match input {
-            kw::table => Self::Table,
+            kw::table => {
+                // Eat an optional comma, and then if anything follows,
+                // it has to be `filter = string`.
+                let mut filter = None;
+                comma_then_comma_delimited(input, || {
+                    match_tok!(match input {
+                        tok @ kw::filter => {
+                            check_duplicate(&filter, tok.span)?;
+                            input.parse::<Token![=]>()?;
+                            filter = Some(input.parse::<syn::LitStr>()?.value());
+                        }
+                    });
+                    Ok(())
+                })?;
+                Self::Table { filter }
+            }
             kw::init => Self::Init,
             kw::reducer => {
                 // Eat an optional comma, and then if anything follows,
-                // it has to be `repeat = Duration`.
+                // it has to be `repeat = Duration | string`, `misfire = policy` or `allow = owner | path`.
                 let mut repeat = None;
+                let mut misfire = None;
+                let mut allow = None;
                 comma_then_comma_delimited(input, || {
                     match_tok!(match input {
                         tok @ kw::repeat => {
                             check_duplicate(&repeat, tok.span)?;
                             input.parse::<Token![=]>()?;
-                            repeat = Some(input.call(parse_duration)?);
+                            repeat = Some(input.call(parse_repeat)?);
+                        }
+                        tok @ kw::misfire => {
+                            check_duplicate(&misfire, tok.span)?;
+                            input.parse::<Token![=]>()?;
+                            misfire = Some(match_tok!(match input {
+                                tok @ kw::skip => Ident::new("Skip", tok.span),
+                                tok @ kw::fire_once => Ident::new("FireOnce", tok.span),
+                                tok @ kw::fire_all => Ident::new("FireAll", tok.span),
+                            }));
+                        }
+                        tok @ kw::allow => {
+                            check_duplicate(&allow, tok.span)?;
+                            input.parse::<Token![=]>()?;
+                            let path = input.parse::<syn::Path>()?;
+                            allow = Some(if path.is_ident("owner") {
+                                ReducerAuth::Owner
+                            } else {
+                                ReducerAuth::Guard(path)
+                            });
                         }
                     });
                     Ok(())
                 })?;
-                Self::Reducer { repeat }
+                Self::Reducer { repeat, misfire, allow }
             }
             kw::connect => Self::Connect,
             kw::disconnect => Self::Disconnect,
@@ -259,12 +361,47 @@ mod kw {
     syn::custom_keyword!(hash);
     syn::custom_keyword!(name);
     syn::custom_keyword!(repeat);
+    syn::custom_keyword!(misfire);
+    syn::custom_keyword!(skip);
+    syn::custom_keyword!(fire_once);
+    syn::custom_keyword!(fire_all);
     syn::custom_keyword!(update);
+    syn::custom_keyword!(allow);
+    syn::custom_keyword!(filter);
+}
+
+/// Who may call a reducer, per `allow = ..`.
+enum ReducerAuth {
+    Owner,
+    Guard(syn::Path),
+}
+
+/// How a reducer repeats, per `repeat = ..`.
+enum RepeatSpec {
+    /// The reducer reschedules itself this long after each run.
+    Interval(Duration),
+    /// The host schedules the reducer as this `every ..` or `cron ..` string says.
+    Schedule(syn::LitStr),
 }
 
 /// Generates a reducer in place of `item`.
-fn spacetimedb_reducer(repeat: Option<Duration>, item: TokenStream) -> syn::Result<TokenStream> {
-    let repeat_dur = repeat.map_or(ReducerExtra::Schedule, ReducerExtra::Repeat);
+fn spacetimedb_reducer(
+    repeat: Option<RepeatSpec>,
+    misfire: Option<Ident>,
+    allow: Option<ReducerAuth>,
+    item: TokenStream,
+) -> syn::Result<TokenStream> {
+    let (extra, schedule) = match repeat {
+        None => (ReducerExtra::Schedule, None),
+        Some(RepeatSpec::Interval(dur)) => (ReducerExtra::Repeat(dur), None),
+        Some(RepeatSpec::Schedule(repeat)) => (ReducerExtra::Schedule, Some(repeat)),
+    };
+    if let (Some(misfire), None) = (&misfire, &schedule) {
+        return Err(syn::Error::new(
+            misfire.span(),
+            "`misfire` needs `repeat = \"every <interval>\"` or `repeat = \"cron <expression>\"`",
+        ));
+    }
     let original_function = syn::parse2::<ItemFn>(item)?;
 
     // Extract reducer name, making sure it's not `__XXX__` as that's the form we reserve for special reducers.
@@ -276,7 +413,77 @@ fn spacetimedb_reducer(repeat: Option<Duration>, item: TokenStream) -> syn::Resu
         ));
     }
 
-    gen_reducer(original_function, &reducer_name, repeat_dur)
+    let func_name = original_function.sig.ident.clone();
+    let mut output = gen_reducer(original_function, &reducer_name, extra)?;
+    if let Some(repeat) = schedule {
+        let misfire = misfire.unwrap_or_else(|| Ident::new("FireOnce", Span::call_site()));
+        output.extend(gen_repeating_reducer(&func_name, &reducer_name, repeat, misfire));
+    }
+    if let Some(allow) = allow {
+        output.extend(gen_reducer_auth(&func_name, &reducer_name, allow));
+    }
+    Ok(output)
+}
+
+/// Generates the registration of the authorization rule `allow` of a reducer,
+/// and for a guard, the `__guard__` export that the host calls to check a call.
+fn gen_reducer_auth(func_name: &Ident, reducer_name: &str, allow: ReducerAuth) -> TokenStream {
+    let register_auth_symbol = format!("__preinit__20_register_reducer_auth_{reducer_name}");
+
+    let (guard_name, guard_export) = match allow {
+        ReducerAuth::Owner => (quote!(None), TokenStream::new()),
+        ReducerAuth::Guard(guard) => {
+            let guard_name = guard
+                .segments
+                .iter()
+                .map(|seg| seg.ident.to_string())
+                .collect::<Vec<_>>()
+                .join("::");
+            let guard_symbol = format!("__guard__{reducer_name}");
+            let guard_export = quote! {
+                #[export_name = #guard_symbol]
+                pub extern "C" fn __guard(
+                    __sender: spacetimedb::sys::Buffer,
+                    __caller_address: spacetimedb::sys::Buffer,
+                    __timestamp: u64,
+                ) -> u32 {
+                    spacetimedb::rt::invoke_guard(#guard, __sender, __caller_address, __timestamp)
+                }
+            };
+            (quote!(Some(#guard_name)), guard_export)
+        }
+    };
+
+    quote! {
+        const _: () = {
+            #[export_name = #register_auth_symbol]
+            pub extern "C" fn __register_reducer_auth() {
+                spacetimedb::rt::register_reducer_auth::<#func_name>()
+            }
+            #guard_export
+        };
+        impl spacetimedb::rt::AuthInfo for #func_name {
+            const GUARD: Option<&'static str> = #guard_name;
+        }
+    }
+}
+
+/// Generates the registration of the schedule of a reducer with `repeat = "every .." | "cron .."`,
+/// which the host stores when the module is published.
+fn gen_repeating_reducer(func_name: &Ident, reducer_name: &str, repeat: syn::LitStr, misfire: Ident) -> TokenStream {
+    let register_repeat_symbol = format!("__preinit__20_register_repeating_reducer_{reducer_name}");
+    quote! {
+        const _: () = {
+            #[export_name = #register_repeat_symbol]
+            pub extern "C" fn __register_repeating_reducer() {
+                spacetimedb::rt::register_repeating_reducer::<_, #func_name>(#func_name)
+            }
+        };
+        impl spacetimedb::rt::RepeatInfo for #func_name {
+            const REPEAT: &'static str = #repeat;
+            const MISFIRE: spacetimedb::MisfirePolicy = spacetimedb::MisfirePolicy::#misfire;
+        }
+    }
 }
 
 /// Generates the special `__init__` "reducer" in place of `item`.
@@ -365,6 +572,13 @@ fn gen_reducer(original_function: ItemFn, reducer_name: &str, extra: ReducerExtr
             pub fn schedule(__time: spacetimedb::Timestamp #(, #arg_names: #arg_tys)*) -> spacetimedb::ScheduleToken<#func_name> {
                 spacetimedb::rt::schedule(__time, (#(#arg_names,)*))
             }
+            pub fn schedule_repeating(
+                __repeat: spacetimedb::Repeat,
+                __misfire: spacetimedb::MisfirePolicy
+                #(, #arg_names: #arg_tys)*
+            ) -> spacetimedb::ScheduleToken<#func_name> {
+                spacetimedb::rt::schedule_repeating(__repeat, __misfire, (#(#arg_names,)*))
+            }
         }));
     }
 
@@ -455,10 +669,30 @@ bitflags! {
     }
 }
 
-fn spacetimedb_table(item: TokenStream) -> syn::Result<TokenStream> {
+fn spacetimedb_table(filter: Option<String>, item: TokenStream) -> syn::Result<TokenStream> {
+    let row_filter = match filter {
+        Some(filter) => {
+            let ident = syn::parse2::<syn::DeriveInput>(item.clone())?.ident;
+            let register_row_filter_symbol = format!("__preinit__20_register_row_filter_{ident}");
+            quote! {
+                const _: () = {
+                    #[export_name = #register_row_filter_symbol]
+                    extern "C" fn __register_row_filter() {
+                        spacetimedb::rt::register_row_filter::<#ident>()
+                    }
+                };
+                impl spacetimedb::rt::RowFilterInfo for #ident {
+                    const ROW_FILTER: &'static str = #filter;
+                }
+            }
+        }
+        None => TokenStream::new(),
+    };
+
     Ok(quote! {
         #[derive(spacetimedb::TableType)]
         #item
+        #row_filter
     })
 }
 
@@ -487,7 +721,13 @@ fn spacetimedb_table(item: TokenStream) -> syn::Result<TokenStream> {
 /// * `#[primarykey]`
 ///
 ///    Similar to `#[unique]`, but generates additional CRUD methods.
-#[proc_macro_derive(TableType, attributes(sats, unique, autoinc, primarykey))]
+///
+/// * `#[default(expr)]`
+///
+///    Fills the annotated field with `expr` in the existing rows
+///    when an update of the module appends it to the table.
+///    A new field without a default is filled with the zero value of its type, if it has one.
+#[proc_macro_derive(TableType, attributes(sats, unique, autoinc, primarykey, default))]
 pub fn spacetimedb_tabletype(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let item = syn::parse_macro_input!(item as syn::DeriveInput);
     spacetimedb_tabletype_impl(item)
@@ -499,6 +739,7 @@ enum ColumnAttr {
     Unique(Span),
     Autoinc(Span),
     Primarykey(Span),
+    Default(Span, Expr),
 }
 
 impl ColumnAttr {
@@ -515,6 +756,8 @@ impl ColumnAttr {
         } else if ident == sym::PRIMARYKEY {
             attr.meta.require_path_only()?;
             Some(ColumnAttr::Primarykey(ident.span()))
+        } else if ident == sym::DEFAULT {
+            Some(ColumnAttr::Default(ident.span(), attr.parse_args()?))
         } else {
             None
         })
@@ -531,6 +774,7 @@ fn spacetimedb_tabletype_impl(item: syn::DeriveInput) -> syn::Result<TokenStream
     };
 
     let mut columns = Vec::<Column>::new();
+    let mut column_defaults = Vec::new();
 
     let get_table_id_func = quote! {
         fn table_id() -> u32 {
@@ -547,6 +791,7 @@ fn spacetimedb_tabletype_impl(item: syn::DeriveInput) -> syn::Result<TokenStream
             .map_err(|_| syn::Error::new_spanned(field.ident, "too many columns; the most a table can have is 256"))?;
 
         let mut col_attr = ColumnIndexAttribute::UNSET;
+        let mut default = None;
         for attr in field.original_attrs {
             let Some(attr) = ColumnAttr::parse(attr)? else { continue };
             let duplicate = |span| syn::Error::new(span, "duplicate attribute");
@@ -554,6 +799,12 @@ fn spacetimedb_tabletype_impl(item: syn::DeriveInput) -> syn::Result<TokenStream
                 ColumnAttr::Unique(span) => (ColumnIndexAttribute::UNIQUE, span),
                 ColumnAttr::Autoinc(span) => (ColumnIndexAttribute::AUTO_INC, span),
                 ColumnAttr::Primarykey(span) => (ColumnIndexAttribute::PRIMARY_KEY, span),
+                ColumnAttr::Default(span, expr) => {
+                    if default.replace(expr).is_some() {
+                        return Err(duplicate(span));
+                    }
+                    continue;
+                }
             };
             // do those attributes intersect (not counting the INDEXED bit which is present in all attributes)?
             // this will check that no two attributes both have UNIQUE, AUTOINC or PRIMARY_KEY bits set
@@ -581,6 +832,17 @@ fn spacetimedb_tabletype_impl(item: syn::DeriveInput) -> syn::Result<TokenStream
             }
         }
 
+        if let Some(default) = default {
+            let col_name = field.name.as_deref().unwrap();
+            let col_type = field.ty;
+            column_defaults.push(quote! {
+                (#col_name, {
+                    let value: #col_type = #default;
+                    spacetimedb::spacetimedb_lib::bsatn::to_vec(&value).unwrap()
+                })
+            });
+        }
+
         let column = Column {
             index: col_num,
             field,
@@ -722,6 +984,13 @@ fn spacetimedb_tabletype_impl(item: syn::DeriveInput) -> syn::Result<TokenStream
             Span::call_site(),
         )
     });
+    let column_defaults_func = (!column_defaults.is_empty()).then(|| {
+        quote! {
+            fn column_defaults() -> Vec<(&'static str, Vec<u8>)> {
+                vec![#(#column_defaults),*]
+            }
+        }
+    });
     let tabletype_impl = quote! {
         impl spacetimedb::TableType for #original_struct_ident {
             const TABLE_NAME: &'static str = #table_name;
@@ -731,6 +1000,7 @@ fn spacetimedb_tabletype_impl(item: syn::DeriveInput) -> syn::Result<TokenStream
             const INDEXES: &'static [spacetimedb::IndexDef<'static>] = &[#(#indexes),*];
             type InsertResult = #insert_result;
             #get_table_id_func
+            #column_defaults_func
         }
     };
 
@@ -870,6 +1140,18 @@ pub fn duration(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     duration_totokens(dur).into()
 }
 
+/// Parses the `repeat` of a reducer, either a duration,
+/// or a string `every <interval>` or `cron <expression>` in the textual form of a `Repeat`.
+fn parse_repeat(input: ParseStream) -> syn::Result<RepeatSpec> {
+    if let Ok(s) = input.fork().parse::<syn::LitStr>() {
+        if s.value().starts_with("every ") || s.value().starts_with("cron ") {
+            input.parse::<syn::LitStr>()?;
+            return Ok(RepeatSpec::Schedule(s));
+        }
+    }
+    input.call(parse_duration).map(RepeatSpec::Interval)
+}
+
 fn parse_duration(input: ParseStream) -> syn::Result<Duration> {
     let (s, span) = match_tok!(match input {
         s @ syn::LitStr => (s.value(), s.span()),
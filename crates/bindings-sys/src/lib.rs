@@ -221,6 +221,9 @@ pub mod raw {
         /// A generated schedule id is assigned to the reducer.
         /// This id is written to the pointer `out`.
         ///
+        /// The reducer is stored in the system table `st_scheduled`,
+        /// and is only scheduled if the transaction of the calling reducer commits.
+        ///
         /// Traps if
         /// - the `time` delay exceeds `64^6 - 1` milliseconds from now
         /// - `name` does not point to valid UTF-8
@@ -234,9 +237,36 @@ pub mod raw {
             out: *mut u64,
         );
 
+        /// Schedules a reducer to be called asynchronously at `time`,
+        /// and then repeatedly, as described by the valid UTF-8 slice `(repeat, repeat_len)`,
+        /// e.g. `every 1mo` or `cron 0 9 * * mon-fri`.
+        ///
+        /// The runs missed while the host is down are handled on restart according to `misfire`,
+        /// which is `0` to skip them, `1` to run once in their place, or `2` to run them all.
+        ///
+        /// Otherwise, this is the same as `_schedule_reducer`,
+        /// and the reducer keeps its id from one run to the next until it's cancelled.
+        ///
+        /// Traps if
+        /// - `repeat` is not a valid repeat, see `spacetimedb_lib::schedule::Repeat`
+        /// - `misfire` is not `0`, `1` or `2`
+        /// - any of the conditions of `_schedule_reducer` happen
+        pub fn _schedule_reducer_repeating(
+            name: *const u8,
+            name_len: usize,
+            args: *const u8,
+            args_len: usize,
+            time: u64,
+            repeat: *const u8,
+            repeat_len: usize,
+            misfire: u8,
+            out: *mut u64,
+        );
+
         /// Unschedule a reducer using the same `id` generated as when it was scheduled.
         ///
         /// This assumes that the reducer hasn't already been executed.
+        /// The reducer is only unscheduled if the transaction of the calling reducer commits.
         pub fn _cancel_reducer(id: u64);
 
         /// Returns the length (number of bytes) of buffer `bufh` without
@@ -696,6 +726,31 @@ pub fn schedule(name: &str, args: &[u8], time: u64) -> u64 {
     out
 }
 
+/// Schedule a reducer to be called asynchronously at `time`,
+/// and then repeatedly, as described by `repeat`, with missed runs handled according to `misfire`.
+///
+/// The reducer is assigned `name` and is provided `args` as its argument.
+///
+/// A generated schedule id is assigned to the reducer which is returned.
+#[inline]
+pub fn schedule_repeating(name: &str, args: &[u8], time: u64, repeat: &str, misfire: u8) -> u64 {
+    let mut out = 0;
+    unsafe {
+        raw::_schedule_reducer_repeating(
+            name.as_ptr(),
+            name.len(),
+            args.as_ptr(),
+            args.len(),
+            time,
+            repeat.as_ptr(),
+            repeat.len(),
+            misfire,
+            &mut out,
+        )
+    }
+    out
+}
+
 /// Unschedule a reducer using the same `id` generated as when it was scheduled.
 ///
 /// This assumes that the reducer hasn't already been executed.
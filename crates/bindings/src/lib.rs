@@ -24,6 +24,7 @@ pub use spacetimedb_bindings_macro::{duration, query, spacetimedb, TableType};
 pub use sats::SpacetimeType;
 pub use spacetimedb_lib;
 pub use spacetimedb_lib::sats;
+pub use spacetimedb_lib::schedule::{MisfirePolicy, Repeat};
 pub use spacetimedb_lib::Address;
 pub use spacetimedb_lib::AlgebraicValue;
 pub use spacetimedb_lib::Identity;
@@ -66,6 +67,46 @@ impl ReducerContext {
     }
 }
 
+/// The rows of a table as they were before an update of the module,
+/// which the `#[spacetimedb(migrate)]` reducer receives to insert them in their new shape.
+///
+/// The rows have the old columns of the table, and are decoded by a type declaring them,
+/// such as the table struct of the previous version of the module:
+/// ```ignore
+/// #[derive(SpacetimeType)]
+/// pub struct PersonV1 {
+///     name: String,
+///     age: u32,
+/// }
+///
+/// #[spacetimedb(migrate)]
+/// pub fn migrate(table: String, rows: OldRows) -> Result<(), String> {
+///     if table == "Person" {
+///         for PersonV1 { name, age } in rows.decode().map_err(|e| e.to_string())? {
+///             Person::insert(Person { name, age: age.to_string() });
+///         }
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct OldRows(Vec<Vec<u8>>);
+
+impl_serialize!([] OldRows, (self, ser) => self.0.serialize(ser));
+impl_deserialize!([] OldRows, de => Vec::<Vec<u8>>::deserialize(de).map(Self));
+impl_st!([] OldRows, ts => Vec::<Vec<u8>>::make_type(ts));
+
+impl OldRows {
+    /// Decodes the rows as values of `T`, whose fields must be the old columns, in order.
+    pub fn decode<T: DeserializeOwned>(&self) -> Result<Vec<T>, DecodeError> {
+        self.0.iter().map(|row| bsatn::from_slice(row)).collect()
+    }
+
+    /// Returns the rows, each encoded in BSATN.
+    pub fn into_bytes(self) -> Vec<Vec<u8>> {
+        self.0
+    }
+}
+
 // #[cfg(target_arch = "wasm32")]
 // #[global_allocator]
 // static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
@@ -320,7 +361,7 @@ trait BufferDeserialize {
 //     }
 // }
 
-/// Deserialize bsatn values to a particular `T` where `T: TableType`.
+/// Deserialize bsatn values to a particular `T`, e.g. where `T: TableType`.
 struct TableTypeBufferDeserialize<T> {
     _marker: PhantomData<T>,
 }
@@ -331,7 +372,7 @@ impl<T> TableTypeBufferDeserialize<T> {
     }
 }
 
-impl<T: TableType> BufferDeserialize for TableTypeBufferDeserialize<T> {
+impl<T: DeserializeOwned> BufferDeserialize for TableTypeBufferDeserialize<T> {
     type Item = T;
 
     fn deserialize<'de>(&mut self, mut reader: impl BufReader<'de>) -> Self::Item {
@@ -432,6 +473,12 @@ pub trait TableType: SpacetimeType + DeserializeOwned + Serialize {
     /// Returns the ID of this table.
     fn table_id() -> u32;
 
+    /// Returns the columns declared with `#[default(..)]`, with their value encoded in BSATN.
+    #[doc(hidden)]
+    fn column_defaults() -> Vec<(&'static str, Vec<u8>)> {
+        Vec::new()
+    }
+
     /// Insert `ins` as a row in this table.
     fn insert(ins: Self) -> Self::InsertResult {
         insert(Self::table_id(), ins)
@@ -657,33 +704,42 @@ macro_rules! schedule {
         $crate::schedule!($crate::duration!($dur), $($args)*)
     };
     ($dur:expr, $($args:tt)*) => {
-        $crate::__schedule_impl!($crate::rt::schedule_in($dur), [] [$($args)*])
+        $crate::__schedule_impl!(schedule [$crate::rt::schedule_in($dur)], [] [$($args)*])
     };
 }
 #[macro_export]
 macro_rules! schedule_at {
     ($time:expr, $($args:tt)*) => {
-        $crate::__schedule_impl!($time, [] [$($args)*])
+        $crate::__schedule_impl!(schedule [$time], [] [$($args)*])
+    };
+}
+/// Schedules a reducer to run at each of the times of a [`Repeat`],
+/// with the runs missed while the host is down handled according to a [`MisfirePolicy`], e.g.
+/// `schedule_repeating!(Repeat::cron("0 4 * * *")?, MisfirePolicy::Skip, cleanup(_))`.
+#[macro_export]
+macro_rules! schedule_repeating {
+    ($repeat:expr, $misfire:expr, $($args:tt)*) => {
+        $crate::__schedule_impl!(schedule_repeating [$repeat, $misfire], [] [$($args)*])
     };
 }
 
 #[doc(hidden)]
 #[macro_export]
 macro_rules! __schedule_impl {
-    ($time:expr, [$repeater:path] [($($args:tt)*)]) => {
-        $crate::__schedule_impl!(@process_args $time, $repeater, ($($args)*))
+    (@process_args $method:ident [$($time:expr),*], $repeater:path, (_$(, $args:expr)* $(,)?)) => {
+        $crate::__schedule_impl!(@call $method [$($time),*], $repeater, $crate::ReducerContext::__dummy(), ($($args),*))
     };
-    ($time:expr, [$($cur:tt)*] [$next:tt $($rest:tt)*]) => {
-        $crate::__schedule_impl!($time, [$($cur)* $next] [$($rest)*])
+    (@process_args $method:ident [$($time:expr),*], $repeater:path, ($($args:expr),* $(,)?)) => {
+        $crate::__schedule_impl!(@call $method [$($time),*], $repeater, , ($($args),*))
     };
-    (@process_args $time:expr, $repeater:path, (_$(, $args:expr)* $(,)?)) => {
-        $crate::__schedule_impl!(@call $time, $repeater, $crate::ReducerContext::__dummy(), ($($args),*))
+    (@call $method:ident [$($time:expr),*], $repeater:path, $($ctx:expr)?, ($($args:expr),*)) => {
+        <$repeater>::$method($($time,)* $($ctx,)? $($args),*);
     };
-    (@process_args $time:expr, $repeater:path, ($($args:expr),* $(,)?)) => {
-        $crate::__schedule_impl!(@call $time, $repeater, , ($($args),*))
+    ($method:ident [$($time:expr),*], [$repeater:path] [($($args:tt)*)]) => {
+        $crate::__schedule_impl!(@process_args $method [$($time),*], $repeater, ($($args)*))
     };
-    (@call $time:expr, $repeater:path, $($ctx:expr)?, ($($args:expr),*)) => {
-        <$repeater>::schedule($time, $($ctx,)? $($args),*);
+    ($method:ident [$($time:expr),*], [$($cur:tt)*] [$next:tt $($rest:tt)*]) => {
+        $crate::__schedule_impl!($method [$($time),*], [$($cur)* $next] [$($rest)*])
     };
 }
 
@@ -722,7 +778,8 @@ impl<R> ScheduleToken<R> {
         ScheduleToken::new(self.id)
     }
 
-    /// Cancel this scheduled reducer.
+    /// Cancel this scheduled reducer,
+    /// provided the transaction of the current reducer commits.
     ///
     /// Cancelling the same ID again has no effect.
     #[inline]
@@ -731,6 +788,32 @@ impl<R> ScheduleToken<R> {
     }
 }
 
+/// A reducer scheduled to run later, which hasn't run yet, nor been cancelled,
+/// as stored in the system table `st_scheduled`.
+#[derive(Deserialize)]
+#[sats(crate = spacetimedb_lib)]
+pub struct ScheduledReducer {
+    /// The token with which the reducer was scheduled.
+    pub token: ScheduleToken,
+    /// The name of the reducer.
+    pub reducer: String,
+    /// The BSATN-encoded arguments the reducer is called with.
+    pub args: Vec<u8>,
+    /// When the reducer runs next.
+    pub at: Timestamp,
+    /// How the reducer repeats, as a [`Repeat`], or empty if it runs once.
+    pub repeat: String,
+    /// What happens to the runs missed while the host is down.
+    pub misfire: MisfirePolicy,
+}
+
+/// Returns an iterator over the reducers scheduled to run later,
+/// including those scheduled by the current reducer.
+pub fn scheduled_reducers() -> impl Iterator<Item = ScheduledReducer> {
+    let (iter, _schema) = buffer_table_iter(get_table_id("st_scheduled"), None).unwrap();
+    RawTableIter::new(iter, TableTypeBufferDeserialize::new())
+}
+
 /// An erased reducer.
 pub struct AnyReducer {
     _never: std::convert::Infallible,
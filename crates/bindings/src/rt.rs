@@ -13,8 +13,12 @@ use spacetimedb_lib::auth::{StAccess, StTableType};
 use spacetimedb_lib::de::{self, Deserialize, SeqProductAccess};
 use spacetimedb_lib::sats::typespace::TypespaceBuilder;
 use spacetimedb_lib::sats::{impl_deserialize, impl_serialize, AlgebraicType, AlgebraicTypeRef, ProductTypeElement};
+use spacetimedb_lib::schedule::{MisfirePolicy, Repeat};
 use spacetimedb_lib::ser::{Serialize, SerializeSeqProduct};
-use spacetimedb_lib::{bsatn, Address, Identity, MiscModuleExport, ModuleDef, ReducerDef, TableDef, TypeAlias};
+use spacetimedb_lib::{
+    bsatn, Address, ColumnDefault, Identity, MiscModuleExport, ModuleDef, ReducerAuth, ReducerAuthRule, ReducerDef,
+    RepeatingReducer, RowFilter, TableDef, TypeAlias,
+};
 use sys::Buffer;
 
 pub use once_cell::sync::{Lazy, OnceCell};
@@ -50,6 +54,15 @@ pub fn invoke_reducer<'a, A: Args<'a>, T>(
     cvt_result(res)
 }
 
+/// The `sender` asks whether it may call a reducer at `timestamp`,
+/// which the `guard` answers with the context of the call.
+///
+/// Returns nonzero if the call is allowed.
+pub fn invoke_guard(guard: fn(&ReducerContext) -> bool, sender: Buffer, client_address: Buffer, timestamp: u64) -> u32 {
+    let ctx = assemble_context(sender, timestamp, client_address);
+    with_timestamp_set(ctx.timestamp, || guard(&ctx)) as u32
+}
+
 /// Creates an index with the name `index_name` and type `index_type`,
 /// on a product of the given columns ids in `col_ids`,
 /// identifying columns in the table identified by `table_id`.
@@ -136,6 +149,29 @@ pub trait RepeaterInfo: ReducerInfo {
     const REPEAT_INTERVAL: Duration;
 }
 
+/// A trait for reducer types that the host schedules to repeat from when the module is published.
+pub trait RepeatInfo: ReducerInfo {
+    /// How the reducer repeats, in the textual form of a [`Repeat`],
+    /// e.g. `every 1h` or `cron 0 4 * * *`.
+    const REPEAT: &'static str;
+    /// What happens to the runs missed while the host is down.
+    const MISFIRE: MisfirePolicy;
+}
+
+/// A trait for reducer types knowing who may call them.
+pub trait AuthInfo: ReducerInfo {
+    /// The name of the guard deciding who may call this reducer,
+    /// or `None` if only the owner of the database may.
+    const GUARD: Option<&'static str>;
+}
+
+/// A trait for table types knowing which of their rows a client may read.
+pub trait RowFilterInfo: TableType {
+    /// The SQL condition on the columns of the table that the rows read by a client must satisfy,
+    /// where `:sender` is the `Identity` of the client.
+    const ROW_FILTER: &'static str;
+}
+
 /// A trait of types representing the arguments of a reducer.
 pub trait Args<'de>: Sized {
     /// How many arguments does the reducer accept?
@@ -356,6 +392,29 @@ pub fn schedule<'de, R: ReducerInfo>(time: Timestamp, args: impl ScheduleArgs<'d
     ScheduleToken::new(id)
 }
 
+/// Schedule reducer `R` to be executed async with arguments `args`
+/// at each of the times of `repeat` from now on,
+/// with the runs missed while the host is down handled according to `misfire`.
+///
+/// Returns a token for the schedule that can be used to cancel it.
+#[track_caller]
+pub fn schedule_repeating<'de, R: ReducerInfo>(
+    repeat: Repeat,
+    misfire: MisfirePolicy,
+    args: impl ScheduleArgs<'de>,
+) -> ScheduleToken<R> {
+    let time = repeat
+        .next(Timestamp::now().micros_since_epoch)
+        .unwrap_or_else(|| panic!("`{repeat}` never runs"));
+
+    // bsatn serialize the arguments into a vector.
+    let arg_bytes = bsatn::to_vec(&SerDeArgs(args.into_args())).unwrap();
+
+    // Schedule the reducer.
+    let id = sys::schedule_repeating(R::NAME, &arg_bytes, time, &repeat.to_string(), misfire.to_u8());
+    ScheduleToken::new(id)
+}
+
 /// Schedule a repeating `_reducer` `I` with repeater args `A`.
 pub fn schedule_repeater<A: RepeaterArgs, T, I: RepeaterInfo>(_reducer: impl for<'de> Reducer<'de, A, T>) {
     // First time to schedule reducer at.
@@ -408,7 +467,18 @@ pub fn register_table<T: TableType>() {
             table_type: StTableType::User,
             table_access: StAccess::for_name(T::TABLE_NAME),
         };
-        module.module.tables.push(schema)
+        module.module.tables.push(schema);
+        for (column, value) in T::column_defaults() {
+            let default = ColumnDefault {
+                table: T::TABLE_NAME.into(),
+                column: column.into(),
+                value,
+            };
+            module
+                .module
+                .misc_exports
+                .push(MiscModuleExport::ColumnDefault(default));
+        }
     })
 }
 
@@ -431,6 +501,51 @@ pub fn register_reducer<'a, A: Args<'a>, T, I: ReducerInfo>(_: impl Reducer<'a,
     })
 }
 
+/// Registers a describer for the authorization rule of the reducer `I`.
+pub fn register_reducer_auth<I: AuthInfo>() {
+    register_describer(|module| {
+        let rule = match I::GUARD {
+            Some(guard) => ReducerAuthRule::Guard(guard.to_owned()),
+            None => ReducerAuthRule::Owner,
+        };
+        let auth = ReducerAuth {
+            reducer: I::NAME.to_owned(),
+            rule,
+        };
+        module.module.misc_exports.push(MiscModuleExport::ReducerAuth(auth));
+    })
+}
+
+/// Registers a describer for the schedule of the repeating `_reducer` `I`,
+/// which must not take any arguments, as the host schedules it without any.
+pub fn register_repeating_reducer<T, I: RepeatInfo>(_reducer: impl for<'de> Reducer<'de, (), T>) {
+    register_describer(|module| {
+        let repeat = I::REPEAT
+            .parse::<Repeat>()
+            .unwrap_or_else(|e| panic!("invalid `repeat` of reducer `{}`: {e}", I::NAME));
+        let repeating = RepeatingReducer {
+            reducer: I::NAME.to_owned(),
+            repeat,
+            misfire: I::MISFIRE,
+        };
+        module
+            .module
+            .misc_exports
+            .push(MiscModuleExport::RepeatingReducer(repeating));
+    })
+}
+
+/// Registers a describer for the row filter of the `TableType` `T`.
+pub fn register_row_filter<T: RowFilterInfo>() {
+    register_describer(|module| {
+        let filter = RowFilter {
+            table: T::TABLE_NAME.to_owned(),
+            filter: T::ROW_FILTER.to_owned(),
+        };
+        module.module.misc_exports.push(MiscModuleExport::RowFilter(filter));
+    })
+}
+
 /// A builder for a module.
 #[derive(Default)]
 struct ModuleBuilder {
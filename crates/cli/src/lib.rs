@@ -19,7 +19,9 @@ pub fn get_subcommands() -> Vec<Command> {
         version::cli(),
         publish::cli(),
         delete::cli(),
+        restore::cli(),
         logs::cli(),
+        log::cli(),
         call::cli(),
         describe::cli(),
         identity::cli(),
@@ -45,7 +47,9 @@ pub async fn exec_subcommand(config: Config, cmd: &str, args: &ArgMatches) -> Re
         "energy" => energy::exec(config, args).await,
         "publish" => publish::exec(config, args).await,
         "delete" => delete::exec(config, args).await,
+        "restore" => restore::exec(config, args).await,
         "logs" => logs::exec(config, args).await,
+        "log" => log::exec(config, args).await,
         "sql" => sql::exec(config, args).await,
         "dns" => dns::exec(config, args).await,
         "generate" => generate::exec(args),
@@ -183,9 +183,13 @@ pub fn extract_from_moduledef(module: ModuleDef) -> (GenCtx, impl Iterator<Item
     let mut names = vec![None; typespace.types.len()];
     let name_info = itertools::chain!(
         tables.iter().map(|t| (t.data, &t.name)),
-        misc_exports
-            .iter()
-            .map(|MiscModuleExport::TypeAlias(a)| (a.ty, &a.name)),
+        misc_exports.iter().filter_map(|export| match export {
+            MiscModuleExport::TypeAlias(a) => Some((a.ty, &a.name)),
+            MiscModuleExport::ReducerAuth(_)
+            | MiscModuleExport::RowFilter(_)
+            | MiscModuleExport::RepeatingReducer(_)
+            | MiscModuleExport::ColumnDefault(_) => None,
+        }),
     );
     for (typeref, name) in name_info {
         names[typeref.idx()] = Some(name.clone())
@@ -193,7 +197,7 @@ pub fn extract_from_moduledef(module: ModuleDef) -> (GenCtx, impl Iterator<Item
 
     let ctx = GenCtx { typespace, names };
     let iter = itertools::chain!(
-        misc_exports.into_iter().map(GenItem::from_misc_export),
+        misc_exports.into_iter().filter_map(GenItem::from_misc_export),
         tables.into_iter().map(GenItem::Table),
         reducers
             .into_iter()
@@ -210,9 +214,14 @@ pub enum GenItem {
 }
 
 impl GenItem {
-    fn from_misc_export(exp: MiscModuleExport) -> Self {
+    fn from_misc_export(exp: MiscModuleExport) -> Option<Self> {
         match exp {
-            MiscModuleExport::TypeAlias(a) => Self::TypeAlias(a),
+            MiscModuleExport::TypeAlias(a) => Some(Self::TypeAlias(a)),
+            // Clients don't need to know who may call a reducer, read a row, or when a reducer repeats.
+            MiscModuleExport::ReducerAuth(_)
+            | MiscModuleExport::RowFilter(_)
+            | MiscModuleExport::RepeatingReducer(_)
+            | MiscModuleExport::ColumnDefault(_) => None,
         }
     }
 
@@ -763,6 +763,10 @@ pub fn autogen_rust_reducer(ctx: &GenCtx, reducer: &ReducerDef) -> String {
 ///    to connect to a remote database, and passes the `handle_row_update`
 ///    and `handle_event` functions so the `BackgroundDbConnection` can spawn workers
 ///    which use those functions to dispatch on the content of messages.
+///
+/// 5. `fn connect_with`, which does the same for a
+///    `spacetimedb_sdk::db_connection::DbConnection`
+///    rather than for the global connection.
 pub fn autogen_rust_globals(ctx: &GenCtx, items: &[GenItem]) -> Vec<Vec<(String, String)>> {
     let mut output = CodeIndenter::new(String::new());
     let out = &mut output;
@@ -800,6 +804,11 @@ pub fn autogen_rust_globals(ctx: &GenCtx, items: &[GenItem]) -> Vec<Vec<(String,
     // Define `fn connect`.
     print_connect_defn(out);
 
+    out.newline();
+
+    // Define `fn connect_with`.
+    print_connect_with_defn(out);
+
     vec![vec![("mod.rs".to_string(), output.into_inner())]]
 }
 
@@ -807,6 +816,7 @@ pub fn autogen_rust_globals(ctx: &GenCtx, items: &[GenItem]) -> Vec<Vec<(String,
 const DISPATCH_IMPORTS: &[&str] = &[
     "use spacetimedb_sdk::client_api_messages::{TableUpdate, Event};",
     "use spacetimedb_sdk::client_cache::{ClientCache, RowCallbackReminders};",
+    "use spacetimedb_sdk::db_connection::DbConnection;",
     "use spacetimedb_sdk::identity::Credentials;",
     "use spacetimedb_sdk::callbacks::{DbCallbacks, ReducerCallbacks};",
     "use spacetimedb_sdk::reducer::AnyReducerEvent;",
@@ -1064,6 +1074,46 @@ where
     );
 }
 
+const CONNECT_WITH_DOCSTRING: &[&str] = &[
+    "/// Connect `connection` to a database named `db_name` accessible over the internet at the URI `spacetimedb_uri`.",
+    "///",
+    "/// Unlike `connect`, this does not use the global connection,",
+    "/// so several `DbConnection`s can be connected at once,",
+    "/// to different databases or with different `credentials`.",
+    "///",
+    "/// If `credentials` are supplied, they will be passed to the new connection to",
+    "/// identify and authenticate the user. Otherwise, a set of `Credentials` will be",
+    "/// generated by the server.",
+];
+
+/// Define the `connect_with` wrapper,
+/// which passes the autogenerated `Module` to `DbConnection::connect`.
+fn print_connect_with_defn(out: &mut Indenter) {
+    print_lines(out, CONNECT_WITH_DOCSTRING);
+    // Clients which only use the global connection never call `connect_with`.
+    writeln!(out, "{}", ALLOW_UNUSED).unwrap();
+    out.delimited_block(
+        "pub fn connect_with<IntoUri>(
+\tconnection: &DbConnection,
+\tspacetimedb_uri: IntoUri,
+\tdb_name: &str,
+\tcredentials: Option<Credentials>,
+) -> Result<()>
+where
+\tIntoUri: TryInto<spacetimedb_sdk::http::Uri>,
+\t<IntoUri as TryInto<spacetimedb_sdk::http::Uri>>::Error: std::error::Error + Send + Sync + 'static,
+{",
+        |out| {
+            writeln!(
+                out,
+                "connection.connect(spacetimedb_uri, db_name, credentials, Arc::new(Module))"
+            )
+            .unwrap()
+        },
+        "}\n",
+    );
+}
+
 fn print_reducer_event_defn(out: &mut Indenter, items: &[GenItem]) {
     writeln!(out, "{}", ALLOW_UNUSED).unwrap();
 
@@ -0,0 +1,297 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use spacetimedb::db::commit_log::{self, Corruption};
+use spacetimedb::db::inspect::{Catalog, RowWrite};
+use spacetimedb::db::message_log::Segments;
+use spacetimedb::db::messages::write::Operation;
+use spacetimedb::db::ostorage::sled_object_db::SledObjectDB;
+use spacetimedb::db::ostorage::ObjectDB;
+use spacetimedb::db::snapshot::SnapshotStore;
+use spacetimedb_lib::sats::satn::Satn;
+use spacetimedb_lib::sats::ser::serde::SerializeWrapper;
+use spacetimedb_lib::sats::{ProductValue, Typespace, ValueWithType, WithTypespace};
+
+use crate::config::Config;
+
+pub fn cli() -> Command {
+    Command::new("log")
+        .args_conflicts_with_subcommands(true)
+        .subcommand_required(true)
+        .subcommands(get_subcommands())
+        .about("Inspect the commit log of a database on disk")
+}
+
+pub async fn exec(config: Config, args: &ArgMatches) -> Result<(), anyhow::Error> {
+    let (cmd, subcommand_args) = args.subcommand().expect("Subcommand required");
+    exec_subcommand(config, cmd, subcommand_args).await
+}
+
+fn get_subcommands() -> Vec<Command> {
+    vec![
+        Command::new("verify")
+            .about("Verifies the checksums and the hash chain of a commit log")
+            .long_about(
+                "Verifies the checksums and the hash chain of a commit log, reporting the first corrupt commit. \
+                 The log is left as is, so this is safe to run on the log of a running database.",
+            )
+            .arg(
+                Arg::new("path")
+                    .required(true)
+                    .value_parser(clap::value_parser!(PathBuf))
+                    .help("The path to the commit log, or to the database directory holding it"),
+            )
+            .after_help("Run `spacetime log verify --help` for more detailed information.\n"),
+        Command::new("inspect")
+            .about("Prints the transactions of a commit log")
+            .long_about(
+                "Prints the transactions of a commit log, one commit at a time, with the rows inserted and deleted \
+                 by each of them decoded against the tables defined by the log itself. \
+                 The log is left as is, so this is safe to run on the log of a running database.",
+            )
+            .arg(
+                Arg::new("path")
+                    .required(true)
+                    .value_parser(clap::value_parser!(PathBuf))
+                    .help("The path to the commit log, or to the database directory holding it"),
+            )
+            .arg(
+                Arg::new("format")
+                    .long("format")
+                    .value_parser(["json", "satn"])
+                    .default_value("json")
+                    .help("The output format: one JSON object per commit, or SATN for human consumption"),
+            )
+            .arg(
+                Arg::new("table")
+                    .long("table")
+                    .action(ArgAction::Append)
+                    .help("Only print the writes to this table, may be given more than once"),
+            )
+            .arg(
+                Arg::new("from")
+                    .long("from")
+                    .value_parser(clap::value_parser!(u64))
+                    .help("The offset of the first commit to print"),
+            )
+            .arg(
+                Arg::new("to")
+                    .long("to")
+                    .value_parser(clap::value_parser!(u64))
+                    .help("The offset of the last commit to print"),
+            )
+            .after_help("Run `spacetime log inspect --help` for more detailed information.\n"),
+    ]
+}
+
+async fn exec_subcommand(config: Config, cmd: &str, args: &ArgMatches) -> Result<(), anyhow::Error> {
+    match cmd {
+        "verify" => exec_verify(config, args).await,
+        "inspect" => exec_inspect(config, args).await,
+        unknown => Err(anyhow::anyhow!("Invalid subcommand: {}", unknown)),
+    }
+}
+
+/// Returns the path of the message log at or under `path`.
+fn message_log_path(path: &Path) -> PathBuf {
+    let mlog = path.join("mlog");
+    if mlog.is_dir() {
+        mlog
+    } else {
+        path.to_owned()
+    }
+}
+
+async fn exec_verify(_config: Config, args: &ArgMatches) -> Result<(), anyhow::Error> {
+    let path = message_log_path(args.get_one::<PathBuf>("path").unwrap());
+    let segments = Segments::read_dir(&path).with_context(|| format!("could not read {}", path.display()))?;
+    let verification = commit_log::verify(segments)?;
+
+    match verification.corruption {
+        None => {
+            println!("Verified {} commits in {}", verification.commits, path.display());
+            Ok(())
+        }
+        Some(Corruption {
+            segment_offset,
+            byte_offset,
+            commit_offset,
+            reason,
+        }) => {
+            let commit = match commit_offset {
+                Some(offset) => format!("commit {offset}"),
+                None => "the first commit".to_owned(),
+            };
+            Err(anyhow::anyhow!(
+                "Found {} valid commits, then {} is corrupt: {}\n\
+                 at byte {} of segment {:0>20}.log in {}",
+                verification.commits,
+                commit,
+                reason,
+                byte_offset,
+                segment_offset,
+                path.display()
+            ))
+        }
+    }
+}
+
+async fn exec_inspect(_config: Config, args: &ArgMatches) -> Result<(), anyhow::Error> {
+    let path = args.get_one::<PathBuf>("path").unwrap();
+    let json = args.get_one::<String>("format").unwrap() == "json";
+    let tables = args
+        .get_many::<String>("table")
+        .map(|tables| tables.collect::<Vec<_>>());
+    let from = args.get_one::<u64>("from").copied().unwrap_or(0);
+    let to = args.get_one::<u64>("to").copied().unwrap_or(u64::MAX);
+
+    let mlog = message_log_path(path);
+    let db_dir = if mlog == *path {
+        path.parent()
+    } else {
+        Some(path.as_path())
+    };
+    let segments = Segments::read_dir(&mlog).with_context(|| format!("could not read {}", mlog.display()))?;
+
+    // The rows larger than a data key are in the object db next to the log.
+    // It is read through a copy, for sled to neither write to nor lock the odb of a running database.
+    let scratch = tempfile::tempdir()?;
+    let odb = match db_dir.map(|dir| dir.join("odb")).filter(|odb| odb.is_dir()) {
+        Some(path) => match SledObjectDB::open_copy(&path, scratch.path().join("odb")) {
+            Ok(odb) => Some(odb),
+            Err(e) => {
+                eprintln!(
+                    "Warning: could not open {}, large rows won't be decoded: {e}",
+                    path.display()
+                );
+                None
+            }
+        },
+        None => None,
+    };
+
+    let mut catalog = Catalog::new();
+    let mut commits = commit_log::Iter::from(segments).peekable();
+    // The tables created by the commits compacted away are in the snapshot covering them.
+    if let Some(Ok(first)) = commits.peek() {
+        let snapshots = db_dir.map(|dir| dir.join("snapshots")).filter(|dir| dir.is_dir());
+        if let (true, Some(snapshots)) = (first.commit_offset > 0, snapshots) {
+            let store = SnapshotStore::open(snapshots)?;
+            let offset = store
+                .offsets()?
+                .into_iter()
+                .find(|&offset| offset >= first.commit_offset);
+            match offset {
+                Some(offset) => catalog.restore_snapshot(&store.read(offset)?)?,
+                None => eprintln!(
+                    "Warning: no snapshot covers the commits before offset {}, some tables won't be decoded",
+                    first.commit_offset
+                ),
+            }
+        }
+    }
+
+    for commit in commits {
+        let commit = commit?;
+        if commit.commit_offset > to {
+            break;
+        }
+        let mut transactions = Vec::with_capacity(commit.transactions.len());
+        for (i, tx) in commit.transactions.iter().enumerate() {
+            // Every write is decoded, for the catalog to follow the schema changes.
+            let mut writes = Vec::with_capacity(tx.writes.len());
+            for write in &tx.writes {
+                let write = catalog.decode_write(write, odb.as_ref().map(|odb| odb as &dyn ObjectDB))?;
+                let name = catalog.table(write.table_id).map(|table| table.table_name.as_str());
+                if tables
+                    .as_ref()
+                    .map_or(true, |tables| tables.iter().any(|t| Some(t.as_str()) == name))
+                {
+                    writes.push(write);
+                }
+            }
+            transactions.push((commit.min_tx_offset + i as u64, writes));
+        }
+        if commit.commit_offset < from {
+            continue;
+        }
+
+        let timestamp = commit
+            .timestamp
+            .map(|t| humantime::format_rfc3339_micros(t.to_systemtime()));
+        for (tx_offset, writes) in transactions {
+            if tables.is_some() && writes.is_empty() {
+                continue;
+            }
+            if json {
+                let writes = writes
+                    .iter()
+                    .map(|write| write_json(&catalog, write))
+                    .collect::<Vec<_>>();
+                let tx = serde_json::json!({
+                    "commit_offset": commit.commit_offset,
+                    "tx_offset": tx_offset,
+                    "timestamp": timestamp.as_ref().map(|t| t.to_string()),
+                    "writes": writes,
+                });
+                println!("{tx}");
+            } else {
+                match &timestamp {
+                    Some(timestamp) => println!("commit {}, tx {tx_offset}, {timestamp}", commit.commit_offset),
+                    None => println!("commit {}, tx {tx_offset}", commit.commit_offset),
+                }
+                for write in &writes {
+                    println!("{}", write_satn(&catalog, write));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn table_name(catalog: &Catalog, write: &RowWrite) -> String {
+    catalog.table(write.table_id).map_or_else(
+        || format!("<table {}>", write.table_id),
+        |table| table.table_name.clone(),
+    )
+}
+
+/// Calls `f` with the row of `write` typed by the columns of its table.
+fn with_row<R>(catalog: &Catalog, write: &RowWrite, f: impl FnOnce(Option<ValueWithType<'_, ProductValue>>) -> R) -> R {
+    let row_type = catalog.table(write.table_id).map(|table| table.row_type());
+    let typespace = Typespace::default();
+    match (&row_type, &write.row) {
+        (Some(row_type), Some(row)) => f(Some(WithTypespace::new(&typespace, row_type).with_value(row))),
+        _ => f(None),
+    }
+}
+
+fn write_json(catalog: &Catalog, write: &RowWrite) -> serde_json::Value {
+    let row = with_row(catalog, write, |row| {
+        row.map_or(Ok(serde_json::Value::Null), |row| {
+            serde_json::to_value(SerializeWrapper::from_ref(&row))
+        })
+    })
+    .unwrap_or(serde_json::Value::Null);
+    serde_json::json!({
+        "op": match write.operation {
+            Operation::Insert => "insert",
+            Operation::Delete => "delete",
+        },
+        "table": table_name(catalog, write),
+        "table_id": write.table_id,
+        "row": row,
+    })
+}
+
+fn write_satn(catalog: &Catalog, write: &RowWrite) -> String {
+    let op = match write.operation {
+        Operation::Insert => '+',
+        Operation::Delete => '-',
+    };
+    let row = with_row(catalog, write, |row| {
+        row.map_or_else(|| "<undecodable row>".to_owned(), |row| row.to_satn())
+    });
+    format!("  {op} {} {row}", table_name(catalog, write))
+}
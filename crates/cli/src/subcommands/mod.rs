@@ -8,9 +8,11 @@ pub mod generate;
 pub mod identity;
 pub mod init;
 pub mod list;
+pub mod log;
 pub mod logs;
 pub mod publish;
 pub mod repl;
+pub mod restore;
 pub mod server;
 pub mod sql;
 pub mod version;
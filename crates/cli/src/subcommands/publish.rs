@@ -4,7 +4,7 @@ use clap::ArgAction::SetTrue;
 use clap::ArgMatches;
 use reqwest::{StatusCode, Url};
 use spacetimedb_lib::name::PublishOp;
-use spacetimedb_lib::name::{is_address, parse_domain_name, PublishResult};
+use spacetimedb_lib::name::{is_address, parse_domain_name, PublishResult, SchemaDiff};
 use std::fs;
 use std::path::PathBuf;
 
@@ -83,6 +83,41 @@ pub fn cli() -> clap::Command {
                 .action(SetTrue)
                 .help("Builds the module using debug instead of release (intended to speed up local iteration, not recommended for CI)"),
         )
+        .arg(
+            Arg::new("dry_run")
+                .long("dry-run")
+                .action(SetTrue)
+                .help("Report the changes publishing would make to the schema of the database, without making them"),
+        )
+        .arg(
+            Arg::new("wal_fsync")
+                .long("wal-fsync")
+                .action(SetTrue)
+                .help("The database will fsync on each commit, whatever the server does by default. Like the other --wal options, this applies to a running database right away"),
+        )
+        .arg(
+            Arg::new("wal_host_default")
+                .long("wal-host-default")
+                .action(SetTrue)
+                .conflicts_with_all(["wal_fsync", "wal_group_commit"])
+                .help("The database will fsync as the server does by default, undoing an earlier --wal-fsync or --wal-group-commit"),
+        )
+        .arg(
+            Arg::new("wal_group_commit")
+                .long("wal-group-commit")
+                .value_name("MILLIS")
+                .value_parser(clap::value_parser!(u64))
+                .conflicts_with("wal_fsync")
+                .help("The database will batch the commits made within this many milliseconds, and fsync them once for the whole batch"),
+        )
+        .arg(
+            Arg::new("wal_group_commit_bytes")
+                .long("wal-group-commit-bytes")
+                .value_name("BYTES")
+                .value_parser(clap::value_parser!(usize))
+                .requires("wal_group_commit")
+                .help("The size of a batch of commits at which it's fsync'd without waiting for its window to elapse"),
+        )
         .arg(
             Arg::new("name|address")
                 .help("A valid domain or address for this database"),
@@ -108,6 +143,11 @@ pub async fn exec(mut config: Config, args: &ArgMatches) -> Result<(), anyhow::E
     let anon_identity = args.get_flag("anon_identity");
     let skip_clippy = args.get_flag("skip_clippy");
     let build_debug = args.get_flag("debug");
+    let dry_run = args.get_flag("dry_run");
+    let wal_fsync = args.get_flag("wal_fsync");
+    let wal_host_default = args.get_flag("wal_host_default");
+    let wal_group_commit = args.get_one::<u64>("wal_group_commit").map(u64::to_string);
+    let wal_group_commit_bytes = args.get_one::<usize>("wal_group_commit_bytes").map(usize::to_string);
 
     let mut query_params = Vec::<(&str, &str)>::new();
     query_params.push(("host_type", host_type.as_str()));
@@ -137,6 +177,23 @@ pub async fn exec(mut config: Config, args: &ArgMatches) -> Result<(), anyhow::E
         query_params.push(("trace_log", "true"));
     }
 
+    if dry_run {
+        query_params.push(("dry_run", "true"));
+    }
+
+    if wal_fsync {
+        query_params.push(("wal_fsync", "true"));
+    }
+    if wal_host_default {
+        query_params.push(("wal_fsync", "default"));
+    }
+    if let Some(window) = &wal_group_commit {
+        query_params.push(("wal_group_commit", window));
+    }
+    if let Some(max_bytes) = &wal_group_commit_bytes {
+        query_params.push(("wal_group_commit_bytes", max_bytes));
+    }
+
     let path_to_wasm = crate::tasks::build(path_to_project, skip_clippy, build_debug)?;
     let program_bytes = fs::read(path_to_wasm)?;
 
@@ -194,6 +251,18 @@ pub async fn exec(mut config: Config, args: &ArgMatches) -> Result<(), anyhow::E
                 println!("{} database with address: {}", op, address);
             }
         }
+        PublishResult::DryRun { op, diff } => match diff {
+            Some(diff) => {
+                print_schema_diff(&diff);
+                if !diff.tainted_tables.is_empty() {
+                    bail!("The database update would be rejected due to incompatible schema changes");
+                }
+            }
+            None => match op {
+                PublishOp::Created => println!("Dry run: the database would be created"),
+                PublishOp::Updated => println!("Dry run: the database would be cleared and created anew"),
+            },
+        },
         PublishResult::TldNotRegistered { domain } => {
             return Err(anyhow::anyhow!(
                 "The top level domain that you provided is not registered.\n\
@@ -238,3 +307,31 @@ pub async fn exec(mut config: Config, args: &ArgMatches) -> Result<(), anyhow::E
 
     Ok(())
 }
+
+/// Prints the changes a dry-run publish reported for the schema of the database.
+fn print_schema_diff(diff: &SchemaDiff) {
+    println!("Dry run: the database would be updated");
+    let sections = [
+        ("Tables to create", &diff.tables_to_create),
+        ("Tables to migrate", &diff.tables_to_migrate),
+        ("Indexes to drop", &diff.indexes_to_drop),
+        ("Indexes to create", &diff.indexes_to_create),
+    ];
+    for (title, names) in sections {
+        if !names.is_empty() {
+            println!("{}:", title);
+            for name in names {
+                println!("  {}", name);
+            }
+        }
+    }
+    if !diff.tainted_tables.is_empty() {
+        println!("Incompatible changes:");
+        for tainted in &diff.tainted_tables {
+            println!("  {}:", tainted.table);
+            for reason in &tainted.reasons {
+                println!("    - {}", reason);
+            }
+        }
+    }
+}
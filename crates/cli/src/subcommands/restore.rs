@@ -0,0 +1,112 @@
+use anyhow::{bail, Context};
+use clap::{Arg, ArgMatches};
+use reqwest::Url;
+use spacetimedb_lib::name::PublishResult;
+use std::time::UNIX_EPOCH;
+
+use crate::config::Config;
+use crate::util::{add_auth_header_opt, database_address, get_auth_header_only};
+
+pub fn cli() -> clap::Command {
+    clap::Command::new("restore")
+        .about("Restores a SpacetimeDB database as of a point in its history into a new database")
+        .arg(
+            Arg::new("database")
+                .required(true)
+                .help("The domain or address of the database to restore"),
+        )
+        .arg(
+            Arg::new("at")
+                .long("at")
+                .value_name("OFFSET|TIME")
+                .help("The commit offset or time to restore the database at")
+                .long_help(
+                    "The commit offset or time to restore the database at. \
+                     A number is the offset of the last commit to restore, \
+                     anything else an RFC 3339 time such as `2023-09-01 12:00:00`, in UTC. \
+                     If not provided, the database is forked in its current state.",
+                ),
+        )
+        .arg(
+            Arg::new("into")
+                .long("into")
+                .value_name("NAME")
+                .help("The domain name of the new database")
+                .long_help("The domain name of the new database. If not provided, the new database only has an address."),
+        )
+        .arg(
+            Arg::new("identity")
+                .long("identity")
+                .short('i')
+                .help("The identity to use for restoring this database")
+                .long_help("The identity to use for restoring this database. If no identity is provided, the default one will be used."),
+        )
+        .arg(
+            Arg::new("server")
+                .long("server")
+                .short('s')
+                .help("The nickname, host name or URL of the server hosting the database"),
+        )
+        .after_help("Run `spacetime help restore` for more detailed information.\n")
+}
+
+pub async fn exec(mut config: Config, args: &ArgMatches) -> Result<(), anyhow::Error> {
+    let server = args.get_one::<String>("server").map(|s| s.as_ref());
+    let database = args.get_one::<String>("database").unwrap();
+    let at = args.get_one::<String>("at");
+    let into = args.get_one::<String>("into");
+    let identity_or_name = args.get_one::<String>("identity");
+
+    let address = database_address(&config, database, server).await?;
+
+    let mut query_params = Vec::new();
+    if let Some(at) = at {
+        query_params.push(parse_restore_point(at)?);
+    }
+    if let Some(into) = into {
+        query_params.push(("into", into.clone()));
+    }
+
+    let builder = reqwest::Client::new().post(Url::parse_with_params(
+        &format!("{}/database/restore/{}", config.get_host_url(server)?, address),
+        query_params,
+    )?);
+    let auth_header = get_auth_header_only(&mut config, false, identity_or_name, server).await;
+    let builder = add_auth_header_opt(builder, &auth_header);
+
+    let res = builder.send().await?;
+    if res.status().is_client_error() || res.status().is_server_error() {
+        let err = res.text().await?;
+        bail!(err)
+    }
+    let bytes = res.bytes().await?;
+    match serde_json::from_slice(&bytes[..])? {
+        PublishResult::Success {
+            domain: Some(domain),
+            address,
+            ..
+        } => println!(
+            "Restored {} into database with domain: {}, address: {}",
+            database, domain, address
+        ),
+        PublishResult::Success { address, .. } => {
+            println!("Restored {} into database with address: {}", database, address)
+        }
+        PublishResult::TldNotRegistered { domain } => bail!("The top level domain {} is not registered", domain.tld()),
+        PublishResult::PermissionDenied { domain } => bail!("Permission denied for domain {}", domain),
+        PublishResult::DryRun { .. } => bail!("Unexpected dry-run response from the server"),
+    }
+
+    Ok(())
+}
+
+/// Parses `--at` into the query parameter of the restore endpoint.
+fn parse_restore_point(at: &str) -> anyhow::Result<(&'static str, String)> {
+    if let Ok(offset) = at.parse::<u64>() {
+        return Ok(("at_offset", offset.to_string()));
+    }
+    let time = humantime::parse_rfc3339_weak(at)
+        .with_context(|| format!("`{at}` is neither a commit offset nor an RFC 3339 time"))?;
+    let micros = time.duration_since(UNIX_EPOCH)?.as_micros();
+    Ok(("at_time", micros.to_string()))
+}
@@ -8,13 +8,15 @@ use spacetimedb::address::Address;
 use spacetimedb::auth::identity::{DecodingKey, EncodingKey};
 use spacetimedb::client::ClientActorIndex;
 use spacetimedb::database_instance_context_controller::DatabaseInstanceContextController;
+use spacetimedb::db::commit_log::RestorePoint;
+use spacetimedb::db::FsyncPolicy;
 use spacetimedb::host::UpdateDatabaseResult;
 use spacetimedb::host::{EnergyQuanta, HostController};
 use spacetimedb::identity::Identity;
 use spacetimedb::messages::control_db::{Database, DatabaseInstance, IdentityEmail, Node};
 use spacetimedb::module_host_context::ModuleHostContext;
 use spacetimedb::sendgrid_controller::SendGridController;
-use spacetimedb_lib::name::{DomainName, InsertDomainResult, RegisterTldResult, Tld};
+use spacetimedb_lib::name::{DomainName, InsertDomainResult, RegisterTldResult, SchemaDiff, Tld};
 use spacetimedb_lib::recovery::RecoveryCode;
 
 pub mod auth;
@@ -68,6 +70,22 @@ pub struct DatabaseDef {
     pub program_bytes: Vec<u8>,
     /// The desired number of replicas the database shall have.
     pub num_replicas: u32,
+    /// How the [`FsyncPolicy`] of the database's commit log changes.
+    ///
+    /// It applies from the next time the commit log is opened.
+    pub fsync: FsyncPolicyUpdate,
+}
+
+/// How publishing a database changes the [`FsyncPolicy`] of its commit log.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FsyncPolicyUpdate {
+    /// The database keeps its policy, which is the host's for a new database.
+    #[default]
+    Keep,
+    /// The database follows the host's policy again.
+    HostDefault,
+    /// The database has this policy, whatever the host's.
+    Set(FsyncPolicy),
 }
 
 /// API of the SpacetimeDB control plane.
@@ -142,8 +160,32 @@ pub trait ControlStateWriteAccess: Send + Sync {
         spec: DatabaseDef,
     ) -> spacetimedb::control_db::Result<Option<UpdateDatabaseResult>>;
 
+    /// Compute the changes publishing a database acc. to [`DatabaseDef`] would
+    /// make to the schema of the existing database with the given address,
+    /// without changing anything.
+    ///
+    /// `None` is returned if no database with the given address exists.
+    async fn publish_database_dry_run(
+        &self,
+        identity: &Identity,
+        spec: DatabaseDef,
+    ) -> spacetimedb::control_db::Result<Option<SchemaDiff>>;
+
     async fn delete_database(&self, identity: &Identity, address: &Address) -> spacetimedb::control_db::Result<()>;
 
+    /// Create the database at `address` as a fork of the database at `source`,
+    /// in its state as of `point`, and register it under `domain` if given.
+    ///
+    /// If the restore fails, nothing of the new database is left behind.
+    async fn restore_database(
+        &self,
+        identity: &Identity,
+        source: &Address,
+        point: RestorePoint,
+        address: &Address,
+        domain: Option<&DomainName>,
+    ) -> spacetimedb::control_db::Result<()>;
+
     // Identities
     async fn create_identity(&self) -> spacetimedb::control_db::Result<Identity>;
     async fn add_email(&self, identity: &Identity, email: &str) -> spacetimedb::control_db::Result<()>;
@@ -247,10 +289,29 @@ impl<T: ControlStateWriteAccess + ?Sized> ControlStateWriteAccess for ArcEnv<T>
         self.0.publish_database(identity, publisher_address, spec).await
     }
 
+    async fn publish_database_dry_run(
+        &self,
+        identity: &Identity,
+        spec: DatabaseDef,
+    ) -> spacetimedb::control_db::Result<Option<SchemaDiff>> {
+        self.0.publish_database_dry_run(identity, spec).await
+    }
+
     async fn delete_database(&self, identity: &Identity, address: &Address) -> spacetimedb::control_db::Result<()> {
         self.0.delete_database(identity, address).await
     }
 
+    async fn restore_database(
+        &self,
+        identity: &Identity,
+        source: &Address,
+        point: RestorePoint,
+        address: &Address,
+        domain: Option<&DomainName>,
+    ) -> spacetimedb::control_db::Result<()> {
+        self.0.restore_database(identity, source, point, address, domain).await
+    }
+
     async fn create_identity(&self) -> spacetimedb::control_db::Result<Identity> {
         self.0.create_identity().await
     }
@@ -400,10 +461,31 @@ impl<T: ControlStateWriteAccess + ?Sized> ControlStateWriteAccess for Arc<T> {
         (**self).publish_database(identity, publisher_address, spec).await
     }
 
+    async fn publish_database_dry_run(
+        &self,
+        identity: &Identity,
+        spec: DatabaseDef,
+    ) -> spacetimedb::control_db::Result<Option<SchemaDiff>> {
+        (**self).publish_database_dry_run(identity, spec).await
+    }
+
     async fn delete_database(&self, identity: &Identity, address: &Address) -> spacetimedb::control_db::Result<()> {
         (**self).delete_database(identity, address).await
     }
 
+    async fn restore_database(
+        &self,
+        identity: &Identity,
+        source: &Address,
+        point: RestorePoint,
+        address: &Address,
+        domain: Option<&DomainName>,
+    ) -> spacetimedb::control_db::Result<()> {
+        (**self)
+            .restore_database(identity, source, point, address, domain)
+            .await
+    }
+
     async fn create_identity(&self) -> spacetimedb::control_db::Result<Identity> {
         (**self).create_identity().await
     }
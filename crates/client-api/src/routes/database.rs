@@ -11,12 +11,14 @@ use serde_json::{json, Value};
 use spacetimedb::address::Address;
 use spacetimedb::auth::identity::encode_token;
 use spacetimedb::database_logger::DatabaseLogger;
+use spacetimedb::db::commit_log::RestorePoint;
+use spacetimedb::db::{FsyncPolicy, DEFAULT_GROUP_COMMIT_BYTES};
 use spacetimedb::host::DescribedEntityType;
 use spacetimedb::host::EntityDef;
 use spacetimedb::host::ReducerArgs;
 use spacetimedb::host::ReducerCallError;
 use spacetimedb::host::ReducerOutcome;
-use spacetimedb::host::UpdateDatabaseSuccess;
+use spacetimedb::host::Timestamp;
 use spacetimedb::identity::Identity;
 use spacetimedb::json::client_api::StmtResultJson;
 use spacetimedb::messages::control_db::{Database, DatabaseInstance, HostType};
@@ -28,6 +30,7 @@ use spacetimedb_lib::recovery::{RecoveryCode, RecoveryCodeResponse};
 use spacetimedb_lib::sats::WithTypespace;
 use std::collections::HashMap;
 use std::convert::From;
+use std::time::Duration;
 
 use super::identity::IdentityForUrl;
 use crate::auth::{
@@ -36,7 +39,7 @@ use crate::auth::{
 };
 use crate::routes::subscribe::generate_random_address;
 use crate::util::{ByteStringBody, NameOrAddress};
-use crate::{log_and_500, ControlStateDelegate, DatabaseDef, NodeDelegate};
+use crate::{log_and_500, ControlStateDelegate, DatabaseDef, FsyncPolicyUpdate, NodeDelegate};
 
 #[derive(derive_more::From)]
 pub(crate) struct DomainParsingRejection(pub(crate) DomainParsingError);
@@ -114,7 +117,7 @@ pub async fn call<S: ControlStateDelegate + NodeDelegate>(
         return Err((StatusCode::NOT_FOUND, format!("{:#}", anyhow::anyhow!(e))).into());
     }
     let result = match module
-        .call_reducer(caller_identity, Some(client_address), None, &reducer, args)
+        .call_reducer(caller_identity, Some(client_address), None, None, &reducer, args)
         .await
     {
         Ok(rcr) => Ok(rcr),
@@ -177,6 +180,7 @@ fn reducer_outcome_response(identity: &Identity, reducer: &str, outcome: Reducer
                 "Module energy budget exhausted.".to_owned(),
             )
         }
+        ReducerOutcome::Unauthorized(errmsg) => (StatusCode::FORBIDDEN, errmsg),
     }
 }
 
@@ -551,14 +555,14 @@ where
     let instance_id = database_instance.id;
 
     let host = worker_ctx.host_controller();
-    match host.get_module_host(instance_id) {
-        Ok(_) => {}
+    let module = match host.get_module_host(instance_id) {
+        Ok(module) => module,
         Err(_) => {
             let dbic = worker_ctx
                 .load_module_host_context(database, instance_id)
                 .await
                 .map_err(log_and_500)?;
-            host.spawn_module_host(dbic).await.map_err(log_and_500)?;
+            host.spawn_module_host(dbic).await.map_err(log_and_500)?
         }
     };
 
@@ -567,6 +571,7 @@ where
         instance_id,
         body,
         auth,
+        &module.info().row_filters,
     ) {
         Ok(results) => results,
         Err(err) => {
@@ -771,6 +776,65 @@ pub struct PublishDatabaseQueryParams {
     clear: bool,
     name_or_address: Option<NameOrAddress>,
     client_address: Option<AddressForUrl>,
+    /// Report what publishing would do, without doing it.
+    #[serde(default)]
+    dry_run: bool,
+    /// Whether the database fsyncs each commit, rather than following the host's [`FsyncPolicy`],
+    /// or `default` to follow the host's policy again.
+    ///
+    /// Like the other `wal_` parameters, this applies to the commits made from the publish on,
+    /// those of a running database included.
+    wal_fsync: Option<WalFsync>,
+    /// Batch the commits of the database made within this many milliseconds,
+    /// and fsync them once for the whole batch.
+    wal_group_commit: Option<u64>,
+    /// The size of a batch of commits under `wal_group_commit`,
+    /// at which it's fsync'd without waiting for its window to elapse.
+    wal_group_commit_bytes: Option<usize>,
+}
+
+/// The values of the `wal_fsync` query parameter.
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+enum WalFsync {
+    True,
+    False,
+    Default,
+}
+
+impl PublishDatabaseQueryParams {
+    /// How the [`FsyncPolicy`] of the database changes.
+    fn fsync_policy(&self) -> Result<FsyncPolicyUpdate, ErrorResponse> {
+        let policy = match (self.wal_fsync, self.wal_group_commit, self.wal_group_commit_bytes) {
+            (None, None, None) => FsyncPolicyUpdate::Keep,
+            (Some(WalFsync::Default), None, None) => FsyncPolicyUpdate::HostDefault,
+            (Some(WalFsync::True), None, None) => FsyncPolicyUpdate::Set(FsyncPolicy::EveryTx),
+            (Some(WalFsync::False), None, None) => FsyncPolicyUpdate::Set(FsyncPolicy::Never),
+            (None, Some(window), max_bytes) => FsyncPolicyUpdate::Set(FsyncPolicy::GroupCommit {
+                window: Duration::from_millis(window),
+                max_bytes: max_bytes.unwrap_or(DEFAULT_GROUP_COMMIT_BYTES),
+            }),
+            (Some(_), Some(_), _) => {
+                return Err((StatusCode::BAD_REQUEST, "`wal_fsync` conflicts with `wal_group_commit`").into())
+            }
+            (_, None, Some(_)) => {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    "`wal_group_commit_bytes` requires `wal_group_commit`",
+                )
+                    .into())
+            }
+        };
+        Ok(policy)
+    }
+}
+
+/// Maps an error publishing a database to a response, i.e. `403` if the caller doesn't own the database.
+fn publish_error(err: spacetimedb::control_db::Error) -> ErrorResponse {
+    match err {
+        spacetimedb::control_db::Error::PermissionDenied { .. } => (StatusCode::FORBIDDEN, err.to_string()).into(),
+        _ => log_and_500(err),
+    }
 }
 
 pub async fn publish<S: NodeDelegate + ControlStateDelegate>(
@@ -780,10 +844,13 @@ pub async fn publish<S: NodeDelegate + ControlStateDelegate>(
     auth: SpacetimeAuthHeader,
     body: Bytes,
 ) -> axum::response::Result<axum::Json<PublishResult>> {
+    let fsync = query_params.fsync_policy()?;
     let PublishDatabaseQueryParams {
         name_or_address,
         clear,
         client_address,
+        dry_run,
+        ..
     } = query_params;
 
     let client_address = client_address.map(Address::from);
@@ -792,6 +859,41 @@ pub async fn publish<S: NodeDelegate + ControlStateDelegate>(
     // so, unless you are the owner, this will fail.
     let auth = auth_or_unauth(auth)?;
 
+    if dry_run {
+        // Unlike below, no address nor DNS record is created
+        // for a name which doesn't resolve yet.
+        let db_addr = match name_or_address {
+            Some(noa) => noa.try_resolve(&ctx).await?.ok().map(Address::from),
+            None => None,
+        };
+        let existing = match db_addr {
+            Some(addr) => ctx.get_database_by_address(&addr).map_err(log_and_500)?.map(|_| addr),
+            None => None,
+        };
+        let op = if existing.is_some() {
+            PublishOp::Updated
+        } else {
+            PublishOp::Created
+        };
+        let diff = match existing {
+            // A cleared database is created anew.
+            Some(address) if !clear => ctx
+                .publish_database_dry_run(
+                    &auth.identity,
+                    DatabaseDef {
+                        address,
+                        program_bytes: body.into(),
+                        num_replicas: 1,
+                        fsync,
+                    },
+                )
+                .await
+                .map_err(publish_error)?,
+            _ => None,
+        };
+        return Ok(axum::Json(PublishResult::DryRun { op, diff }));
+    }
+
     let (db_addr, db_name) = match name_or_address {
         Some(noa) => match noa.try_resolve(&ctx).await? {
             Ok(resolved) => resolved.into(),
@@ -837,29 +939,15 @@ pub async fn publish<S: NodeDelegate + ControlStateDelegate>(
                 address: db_addr,
                 program_bytes: body.into(),
                 num_replicas: 1,
+                fsync,
             },
         )
         .await
-        .map_err(log_and_500)?;
+        .map_err(publish_error)?;
 
-    if let Some(updated) = maybe_updated {
-        match updated {
-            Ok(success) => {
-                if let UpdateDatabaseSuccess {
-                    // An update reducer was defined, and it was run
-                    update_result: Some(update_result),
-                    // Not yet implemented
-                    migrate_results: _,
-                } = success
-                {
-                    let ror = reducer_outcome_response(&auth.identity, "update", update_result.outcome);
-                    if !matches!(ror, (StatusCode::OK, _)) {
-                        return Err(ror.into());
-                    }
-                }
-            }
-            Err(e) => return Err((StatusCode::BAD_REQUEST, format!("Database update rejected: {e}")).into()),
-        }
+    // A failed migration or `update` reducer rejects the whole update.
+    if let Some(Err(e)) = maybe_updated {
+        return Err((StatusCode::BAD_REQUEST, format!("Database update rejected: {e}")).into());
     }
 
     Ok(axum::Json(PublishResult::Success {
@@ -890,6 +978,85 @@ pub async fn delete_database<S: ControlStateDelegate>(
     Ok(())
 }
 
+#[derive(Deserialize)]
+pub struct RestoreDatabaseParams {
+    name_or_address: NameOrAddress,
+}
+
+#[derive(Deserialize)]
+pub struct RestoreDatabaseQueryParams {
+    /// Restore up to and including the commit at this offset.
+    at_offset: Option<u64>,
+    /// Restore up to the last commit at or before this time, in microseconds since the Unix epoch.
+    at_time: Option<u64>,
+    into: Option<NameOrAddress>,
+}
+
+/// Maps an error restoring a database to a response, i.e. `403` if the caller doesn't own the source database,
+/// and `400` if the restore point is outside of its commit log.
+fn restore_error(err: spacetimedb::control_db::Error) -> ErrorResponse {
+    match err {
+        spacetimedb::control_db::Error::PermissionDenied { .. } => (StatusCode::FORBIDDEN, err.to_string()).into(),
+        spacetimedb::control_db::Error::InvalidRestorePoint(_) => (StatusCode::BAD_REQUEST, err.to_string()).into(),
+        // Another request registered the name since it was checked.
+        spacetimedb::control_db::Error::RecordAlreadyExists(_) => {
+            (StatusCode::CONFLICT, "The database to restore into already exists.").into()
+        }
+        _ => log_and_500(err),
+    }
+}
+
+pub async fn restore<S: ControlStateDelegate>(
+    State(ctx): State<S>,
+    Path(RestoreDatabaseParams { name_or_address }): Path<RestoreDatabaseParams>,
+    Query(query_params): Query<RestoreDatabaseQueryParams>,
+    auth: SpacetimeAuthHeader,
+) -> axum::response::Result<axum::Json<PublishResult>> {
+    let RestoreDatabaseQueryParams {
+        at_offset,
+        at_time,
+        into,
+    } = query_params;
+    let auth = auth_or_unauth(auth)?;
+
+    let point = match (at_offset, at_time) {
+        (None, None) => RestorePoint::Latest,
+        (Some(offset), None) => RestorePoint::Offset(offset),
+        (None, Some(micros)) => RestorePoint::Time(Timestamp(micros)),
+        (Some(_), Some(_)) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Only one of `at_offset` and `at_time` can be given",
+            )
+                .into())
+        }
+    };
+
+    let source: Address = name_or_address.resolve(&ctx).await?.into();
+
+    // The database is restored into a fresh address, under the new name if any.
+    let db_name = match into {
+        Some(noa) => match noa.try_resolve(&ctx).await? {
+            Ok(_) => return Err((StatusCode::CONFLICT, "The database to restore into already exists.").into()),
+            Err(domain) => Some(domain),
+        },
+        None => None,
+    };
+    let db_addr = ctx.create_address().await.map_err(log_and_500)?;
+
+    log::trace!("Restoring {} into the address: {}", source.to_hex(), db_addr.to_hex());
+
+    ctx.restore_database(&auth.identity, &source, point, &db_addr, db_name.as_ref())
+        .await
+        .map_err(restore_error)?;
+
+    Ok(axum::Json(PublishResult::Success {
+        domain: db_name.as_ref().map(ToString::to_string),
+        address: db_addr.to_hex(),
+        op: PublishOp::Created,
+    }))
+}
+
 #[derive(Deserialize)]
 pub struct SetNameQueryParams {
     domain: String,
@@ -947,6 +1114,7 @@ where
         .route("/confirm_recovery_code", get(confirm_recovery_code::<S>))
         .route("/publish", post(publish::<S>).layer(DefaultBodyLimit::disable()))
         .route("/delete/:address", post(delete_database::<S>))
+        .route("/restore/:name_or_address", post(restore::<S>))
 }
 
 pub fn worker_routes<S>() -> axum::Router<S>
@@ -966,3 +1134,82 @@ where
         .route("/logs/:name_or_address", get(logs::<S>))
         .route("/sql/:name_or_address", post(sql::<S>))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reducer_outcome_status() {
+        let identity = Identity::from_hashing_bytes("owner");
+        let status = |outcome| reducer_outcome_response(&identity, "add", outcome).0;
+        assert_eq!(status(ReducerOutcome::Committed), StatusCode::OK);
+        assert_eq!(status(ReducerOutcome::Failed("oops".into())).as_u16(), 530);
+        assert_eq!(status(ReducerOutcome::BudgetExceeded), StatusCode::PAYMENT_REQUIRED);
+
+        let (status, body) = reducer_outcome_response(&identity, "add", ReducerOutcome::Unauthorized("denied".into()));
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body, "denied");
+    }
+
+    #[test]
+    fn test_publish_fsync_policy() {
+        let policy = |wal_fsync, wal_group_commit, wal_group_commit_bytes| {
+            PublishDatabaseQueryParams {
+                clear: false,
+                name_or_address: None,
+                client_address: None,
+                dry_run: false,
+                wal_fsync,
+                wal_group_commit,
+                wal_group_commit_bytes,
+            }
+            .fsync_policy()
+            .ok()
+        };
+        assert_eq!(policy(None, None, None), Some(FsyncPolicyUpdate::Keep));
+        assert_eq!(
+            policy(Some(WalFsync::True), None, None),
+            Some(FsyncPolicyUpdate::Set(FsyncPolicy::EveryTx))
+        );
+        assert_eq!(
+            policy(Some(WalFsync::Default), None, None),
+            Some(FsyncPolicyUpdate::HostDefault)
+        );
+        assert_eq!(
+            policy(None, Some(10), Some(1024)),
+            Some(FsyncPolicyUpdate::Set(FsyncPolicy::GroupCommit {
+                window: Duration::from_millis(10),
+                max_bytes: 1024,
+            }))
+        );
+        assert_eq!(policy(Some(WalFsync::Default), Some(10), None), None);
+        assert_eq!(policy(None, None, Some(1024)), None);
+
+        // The host's policy is asked for with `wal_fsync=default`.
+        assert!(matches!(
+            serde_json::from_str::<WalFsync>("\"default\""),
+            Ok(WalFsync::Default)
+        ));
+    }
+
+    #[test]
+    fn test_restore_error_status() {
+        use spacetimedb::control_db::Error;
+
+        let status = |err| restore_error(err).into_response().status();
+        let denied = Error::PermissionDenied {
+            identity: Identity::from_hashing_bytes("other"),
+            address: Address::zero(),
+        };
+        assert_eq!(status(denied), StatusCode::FORBIDDEN);
+        assert_eq!(
+            status(Error::InvalidRestorePoint("past the end".into())),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(anyhow::anyhow!("disk full").into()),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+}
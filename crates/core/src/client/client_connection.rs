@@ -1,7 +1,7 @@
 use std::ops::Deref;
 
 use crate::host::{ModuleHost, NoSuchModule, ReducerArgs, ReducerCallError, ReducerCallResult};
-use crate::protobuf::client_api::Subscribe;
+use crate::protobuf::client_api::{Subscribe, SubscribeAdd, Unsubscribe};
 use crate::worker_metrics::{CONNECTED_CLIENTS, WEBSOCKET_SENT, WEBSOCKET_SENT_MSG_SIZE};
 use derive_more::From;
 use futures::prelude::*;
@@ -29,8 +29,13 @@ pub struct ClientClosed;
 
 impl ClientConnectionSender {
     pub fn dummy(id: ClientActorId, protocol: Protocol) -> Self {
-        let (sendtx, _) = mpsc::channel(1);
-        Self { id, protocol, sendtx }
+        Self::dummy_with_channel(id, protocol).0
+    }
+
+    /// Like [`Self::dummy`], but also returns the receiving end of the messages sent to the client.
+    pub fn dummy_with_channel(id: ClientActorId, protocol: Protocol) -> (Self, mpsc::Receiver<DataMessage>) {
+        let (sendtx, rx) = mpsc::channel(1);
+        (Self { id, protocol, sendtx }, rx)
     }
 
     pub fn send_message(&self, message: impl ServerMessage) -> impl Future<Output = Result<(), ClientClosed>> + '_ {
@@ -150,12 +155,18 @@ impl ClientConnection {
         message_handlers::handle(self, message.into())
     }
 
-    pub async fn call_reducer(&self, reducer: &str, args: ReducerArgs) -> Result<ReducerCallResult, ReducerCallError> {
+    pub async fn call_reducer(
+        &self,
+        reducer: &str,
+        args: ReducerArgs,
+        request_id: Option<u32>,
+    ) -> Result<ReducerCallResult, ReducerCallError> {
         self.module
             .call_reducer(
                 self.id.identity,
                 Some(self.id.address),
                 Some(self.sender()),
+                request_id,
                 reducer,
                 args,
             )
@@ -166,6 +177,14 @@ impl ClientConnection {
         self.module.subscription().add_subscriber(self.sender(), subscription)
     }
 
+    pub fn subscribe_add(&self, subscribe_add: SubscribeAdd) -> Result<(), NoSuchModule> {
+        self.module.subscription().add_query(self.sender(), subscribe_add)
+    }
+
+    pub fn unsubscribe(&self, unsubscribe: Unsubscribe) -> Result<(), NoSuchModule> {
+        self.module.subscription().remove_query(self.sender(), unsubscribe)
+    }
+
     pub async fn one_off_query(&self, query: &str, message_id: &[u8]) -> Result<(), anyhow::Error> {
         let result = self.module.one_off_query(self.id.identity, query.to_owned()).await;
         let message_id = message_id.to_owned();
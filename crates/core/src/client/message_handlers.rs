@@ -1,9 +1,12 @@
+use std::collections::HashMap;
 use std::time::Duration;
 
 use crate::host::module_host::{EventStatus, ModuleEvent, ModuleFunctionCall};
 use crate::host::{EnergyDiff, ReducerArgs, Timestamp};
 use crate::identity::Identity;
-use crate::protobuf::client_api::{message, FunctionCall, Message, Subscribe};
+use crate::protobuf::client_api::{
+    message, FunctionCall, Message, ParameterizedQuery, QueryParam, ResumeFrom, Subscribe, SubscribeAdd, Unsubscribe,
+};
 use crate::worker_metrics::{WEBSOCKET_REQUESTS, WEBSOCKET_REQUEST_MSG_SIZE};
 use base64::Engine;
 use bytes::Bytes;
@@ -52,11 +55,22 @@ pub async fn handle(client: &ClientConnection, message: DataMessage) -> Result<(
 async fn handle_binary(client: &ClientConnection, message_buf: Vec<u8>) -> Result<(), MessageHandleError> {
     let message = Message::decode(Bytes::from(message_buf))?;
     let message = match message.r#type {
-        Some(message::Type::FunctionCall(FunctionCall { ref reducer, arg_bytes })) => {
+        Some(message::Type::FunctionCall(FunctionCall {
+            ref reducer,
+            arg_bytes,
+            request_id,
+        })) => {
             let args = ReducerArgs::Bsatn(arg_bytes.into());
-            DecodedMessage::Call { reducer, args }
+            let request_id = (request_id != 0).then_some(request_id);
+            DecodedMessage::Call {
+                reducer,
+                args,
+                request_id,
+            }
         }
         Some(message::Type::Subscribe(subscription)) => DecodedMessage::Subscribe(subscription),
+        Some(message::Type::SubscribeAdd(subscribe_add)) => DecodedMessage::SubscribeAdd(subscribe_add),
+        Some(message::Type::Unsubscribe(unsubscribe)) => DecodedMessage::Unsubscribe(unsubscribe),
         Some(message::Type::OneOffQuery(ref oneoff)) => DecodedMessage::OneOffQuery {
             query_string: &oneoff.query_string[..],
             message_id: &oneoff.message_id[..],
@@ -76,9 +90,23 @@ enum RawJsonMessage<'a> {
         #[serde(borrow, rename = "fn")]
         func: std::borrow::Cow<'a, str>,
         args: &'a serde_json::value::RawValue,
+        #[serde(default)]
+        request_id: Option<u32>,
     },
     #[serde(rename = "subscribe")]
-    Subscribe { query_strings: Vec<String> },
+    Subscribe {
+        query_strings: Vec<String>,
+        #[serde(default)]
+        parameterized_queries: Vec<RawJsonParameterizedQuery>,
+        #[serde(default)]
+        added_queries: Vec<RawJsonAddedQuery>,
+        #[serde(default)]
+        resume_from: Option<RawJsonResumeFrom>,
+    },
+    #[serde(rename = "subscribe_add")]
+    SubscribeAdd { query_id: u32, query_string: String },
+    #[serde(rename = "unsubscribe")]
+    Unsubscribe { query_id: u32 },
     #[serde(rename = "one_off_query")]
     OneOffQuery {
         #[serde(borrow)]
@@ -90,16 +118,85 @@ enum RawJsonMessage<'a> {
     },
 }
 
+/// A `ParameterizedQuery` in a text message,
+/// in which the value of each parameter is a base64-encoded string of BSATN.
+#[derive(serde::Deserialize)]
+struct RawJsonParameterizedQuery {
+    template: String,
+    params: HashMap<String, String>,
+}
+
+/// A `SubscribeAdd` among the `added_queries` of a `subscribe` text message.
+#[derive(serde::Deserialize)]
+struct RawJsonAddedQuery {
+    query_id: u32,
+    query_string: String,
+}
+
+/// The `ResumeFrom` of a `subscribe` text message.
+#[derive(serde::Deserialize)]
+struct RawJsonResumeFrom {
+    history_id: u64,
+    tx_offset: u64,
+}
+
 async fn handle_text(client: &ClientConnection, message: String) -> Result<(), MessageHandleError> {
     let message = ByteString::from(message);
     let msg = serde_json::from_str::<RawJsonMessage>(&message)?;
     let mut message_id_ = Vec::new();
     let msg = match msg {
-        RawJsonMessage::Call { ref func, args } => {
+        RawJsonMessage::Call {
+            ref func,
+            args,
+            request_id,
+        } => {
             let args = ReducerArgs::Json(message.slice_ref(args.get()));
-            DecodedMessage::Call { reducer: func, args }
+            DecodedMessage::Call {
+                reducer: func,
+                args,
+                request_id,
+            }
+        }
+        RawJsonMessage::Subscribe {
+            query_strings,
+            parameterized_queries,
+            added_queries,
+            resume_from,
+        } => {
+            let parameterized_queries = parameterized_queries
+                .into_iter()
+                .map(|query| {
+                    let params = query
+                        .params
+                        .into_iter()
+                        .map(|(name, value)| {
+                            let value = base64::engine::general_purpose::STANDARD.decode(value)?;
+                            Ok(QueryParam { name, value })
+                        })
+                        .collect::<Result<_, base64::DecodeError>>()?;
+                    Ok(ParameterizedQuery {
+                        template: query.template,
+                        params,
+                    })
+                })
+                .collect::<Result<_, base64::DecodeError>>()?;
+            let added_queries = added_queries
+                .into_iter()
+                .map(|RawJsonAddedQuery { query_id, query_string }| SubscribeAdd { query_id, query_string })
+                .collect();
+            let resume_from =
+                resume_from.map(|RawJsonResumeFrom { history_id, tx_offset }| ResumeFrom { history_id, tx_offset });
+            DecodedMessage::Subscribe(Subscribe {
+                query_strings,
+                parameterized_queries,
+                added_queries,
+                resume_from,
+            })
         }
-        RawJsonMessage::Subscribe { query_strings } => DecodedMessage::Subscribe(Subscribe { query_strings }),
+        RawJsonMessage::SubscribeAdd { query_id, query_string } => {
+            DecodedMessage::SubscribeAdd(SubscribeAdd { query_id, query_string })
+        }
+        RawJsonMessage::Unsubscribe { query_id } => DecodedMessage::Unsubscribe(Unsubscribe { query_id }),
         RawJsonMessage::OneOffQuery {
             query_string: ref query,
             message_id,
@@ -124,8 +221,11 @@ enum DecodedMessage<'a> {
     Call {
         reducer: &'a str,
         args: ReducerArgs,
+        request_id: Option<u32>,
     },
     Subscribe(Subscribe),
+    SubscribeAdd(SubscribeAdd),
+    Unsubscribe(Unsubscribe),
     OneOffQuery {
         query_string: &'a str,
         message_id: &'a [u8],
@@ -134,12 +234,20 @@ enum DecodedMessage<'a> {
 
 impl DecodedMessage<'_> {
     async fn handle(self, client: &ClientConnection) -> Result<(), MessageExecutionError> {
+        let request_id = match self {
+            DecodedMessage::Call { request_id, .. } => request_id,
+            _ => None,
+        };
         let res = match self {
-            DecodedMessage::Call { reducer, args } => {
-                let res = client.call_reducer(reducer, args).await;
+            DecodedMessage::Call { reducer, args, .. } => {
+                let res = client.call_reducer(reducer, args, request_id).await;
                 res.map(drop).map_err(|e| (Some(reducer), e.into()))
             }
             DecodedMessage::Subscribe(subscription) => client.subscribe(subscription).map_err(|e| (None, e.into())),
+            DecodedMessage::SubscribeAdd(subscribe_add) => {
+                client.subscribe_add(subscribe_add).map_err(|e| (None, e.into()))
+            }
+            DecodedMessage::Unsubscribe(unsubscribe) => client.unsubscribe(unsubscribe).map_err(|e| (None, e.into())),
             DecodedMessage::OneOffQuery {
                 query_string: query,
                 message_id,
@@ -149,6 +257,7 @@ impl DecodedMessage<'_> {
             reducer: reducer.map(str::to_owned),
             caller_identity: client.id.identity,
             caller_address: Some(client.id.address),
+            request_id,
             err,
         })
     }
@@ -161,6 +270,7 @@ pub struct MessageExecutionError {
     pub reducer: Option<String>,
     pub caller_identity: Identity,
     pub caller_address: Option<Address>,
+    pub request_id: Option<u32>,
     #[source]
     pub err: anyhow::Error,
 }
@@ -171,6 +281,7 @@ impl MessageExecutionError {
             timestamp: Timestamp::now(),
             caller_identity: self.caller_identity,
             caller_address: self.caller_address,
+            request_id: self.request_id,
             function_call: ModuleFunctionCall {
                 reducer: self.reducer.unwrap_or_else(|| "<none>".to_owned()),
                 args: Default::default(),
@@ -187,6 +298,7 @@ impl ServerMessage for MessageExecutionError {
         TransactionUpdateMessage {
             event: &mut self.into_event(),
             database_update: Default::default(),
+            tx_offset: 0,
         }
         .serialize_text()
     }
@@ -195,6 +307,7 @@ impl ServerMessage for MessageExecutionError {
         TransactionUpdateMessage {
             event: &mut self.into_event(),
             database_update: Default::default(),
+            tx_offset: 0,
         }
         .serialize_binary()
     }
@@ -220,4 +333,45 @@ mod tests {
             panic!("wrong variant")
         }
     }
+
+    #[test]
+    fn parse_subscribe_add_and_unsubscribe() {
+        let message = r#"{ "subscribe_add": { "query_id": 3, "query_string": "SELECT * FROM User" } }"#;
+        let parsed = serde_json::from_str::<RawJsonMessage>(message).unwrap();
+        assert!(
+            matches!(parsed, RawJsonMessage::SubscribeAdd { query_id: 3, ref query_string } if query_string == "SELECT * FROM User")
+        );
+
+        let message = r#"{ "unsubscribe": { "query_id": 3 } }"#;
+        let parsed = serde_json::from_str::<RawJsonMessage>(message).unwrap();
+        assert!(matches!(parsed, RawJsonMessage::Unsubscribe { query_id: 3 }));
+    }
+
+    #[test]
+    fn parse_call_with_request_id() {
+        let message = r#"{ "call": { "fn": "say_hello", "args": [], "request_id": 7 } }"#;
+        let parsed = serde_json::from_str::<RawJsonMessage>(message).unwrap();
+        assert!(matches!(parsed, RawJsonMessage::Call { ref func, request_id: Some(7), .. } if func == "say_hello"));
+
+        let message = r#"{ "call": { "fn": "say_hello", "args": [] } }"#;
+        let parsed = serde_json::from_str::<RawJsonMessage>(message).unwrap();
+        assert!(matches!(parsed, RawJsonMessage::Call { request_id: None, .. }));
+    }
+
+    #[test]
+    fn parse_subscribe_with_added_queries() {
+        let message = r#"{ "subscribe": { "query_strings": ["SELECT * FROM A"],
+            "added_queries": [{ "query_id": 3, "query_string": "SELECT * FROM B" }] } }"#;
+        let parsed = serde_json::from_str::<RawJsonMessage>(message).unwrap();
+        let RawJsonMessage::Subscribe { added_queries, .. } = parsed else {
+            panic!("expected a subscribe message");
+        };
+        assert_eq!(added_queries.len(), 1);
+        assert_eq!(added_queries[0].query_id, 3);
+        assert_eq!(added_queries[0].query_string, "SELECT * FROM B");
+
+        let message = r#"{ "subscribe": { "query_strings": [] } }"#;
+        let parsed = serde_json::from_str::<RawJsonMessage>(message).unwrap();
+        assert!(matches!(parsed, RawJsonMessage::Subscribe { added_queries, .. } if added_queries.is_empty()));
+    }
 }
@@ -7,9 +7,11 @@ use crate::host::module_host::{DatabaseUpdate, EventStatus, ModuleEvent};
 use crate::identity::Identity;
 use crate::json::client_api::{
     EventJson, FunctionCallJson, IdentityTokenJson, MessageJson, OneOffQueryResponseJson, OneOffTableJson,
-    TransactionUpdateJson,
+    QueryUpdateJson, SubscriptionUpdateJson, TransactionUpdateJson,
+};
+use crate::protobuf::client_api::{
+    event, message, Event, FunctionCall, IdentityToken, Message, QueryUpdate, SubscriptionUpdate, TransactionUpdate,
 };
-use crate::protobuf::client_api::{event, message, Event, FunctionCall, IdentityToken, Message, TransactionUpdate};
 
 use super::{DataMessage, Protocol};
 
@@ -54,15 +56,23 @@ impl ServerMessage for IdentityTokenMessage {
 pub struct TransactionUpdateMessage<'a> {
     pub event: &'a mut ModuleEvent,
     pub database_update: DatabaseUpdate,
+    /// The offset of the transaction of a committed event, numbered by the subscription actor, and 0 otherwise.
+    pub tx_offset: u64,
 }
 
 impl ServerMessage for TransactionUpdateMessage<'_> {
     fn serialize_text(self) -> MessageJson {
-        let Self { event, database_update } = self;
+        let Self {
+            event,
+            database_update,
+            tx_offset,
+        } = self;
+        let request_id = event.request_id;
         let (status_str, errmsg) = match &event.status {
             EventStatus::Committed(_) => ("committed", String::new()),
             EventStatus::Failed(errmsg) => ("failed", errmsg.clone()),
             EventStatus::OutOfEnergy => ("out_of_energy", String::new()),
+            EventStatus::Unauthorized(errmsg) => ("unauthorized", errmsg.clone()),
         };
 
         let event = EventJson {
@@ -82,15 +92,23 @@ impl ServerMessage for TransactionUpdateMessage<'_> {
         MessageJson::TransactionUpdate(TransactionUpdateJson {
             event,
             subscription_update,
+            request_id,
+            tx_offset: (tx_offset != 0).then_some(tx_offset),
         })
     }
 
     fn serialize_binary(self) -> Message {
-        let Self { event, database_update } = self;
+        let Self {
+            event,
+            database_update,
+            tx_offset,
+        } = self;
+        let request_id = event.request_id;
         let (status, errmsg) = match &event.status {
             EventStatus::Committed(_) => (event::Status::Committed, String::new()),
             EventStatus::Failed(errmsg) => (event::Status::Failed, errmsg.clone()),
             EventStatus::OutOfEnergy => (event::Status::OutOfEnergy, String::new()),
+            EventStatus::Unauthorized(errmsg) => (event::Status::Unauthorized, errmsg.clone()),
         };
 
         let event = Event {
@@ -100,6 +118,7 @@ impl ServerMessage for TransactionUpdateMessage<'_> {
             function_call: Some(FunctionCall {
                 reducer: event.function_call.reducer.to_owned(),
                 arg_bytes: event.function_call.args.get_bsatn().clone().into(),
+                request_id: 0,
             }),
             message: errmsg,
             energy_quanta_used: event.energy_quanta_used.0 as i64,
@@ -112,6 +131,8 @@ impl ServerMessage for TransactionUpdateMessage<'_> {
         let tx_update = TransactionUpdate {
             event: Some(event),
             subscription_update: Some(subscription_update),
+            request_id: request_id.unwrap_or(0),
+            tx_offset,
         };
 
         Message {
@@ -125,6 +146,7 @@ impl ServerMessage for &mut TransactionUpdateMessage<'_> {
         TransactionUpdateMessage {
             event: &mut *self.event,
             database_update: self.database_update.clone(),
+            tx_offset: self.tx_offset,
         }
         .serialize_text()
     }
@@ -132,6 +154,7 @@ impl ServerMessage for &mut TransactionUpdateMessage<'_> {
         TransactionUpdateMessage {
             event: &mut *self.event,
             database_update: self.database_update.clone(),
+            tx_offset: self.tx_offset,
         }
         .serialize_binary()
     }
@@ -139,16 +162,56 @@ impl ServerMessage for &mut TransactionUpdateMessage<'_> {
 
 pub struct SubscriptionUpdateMessage {
     pub database_update: DatabaseUpdate,
+    /// The offset of the last transaction whose changes `database_update` contains.
+    pub tx_offset: u64,
+    /// Identifies the numbering of `tx_offset`.
+    pub history_id: u64,
+    /// Whether `database_update` holds only the changes since the offset the client resumed from.
+    pub resumed: bool,
 }
 
 impl ServerMessage for SubscriptionUpdateMessage {
     fn serialize_text(self) -> MessageJson {
-        MessageJson::SubscriptionUpdate(self.database_update.into_json())
+        MessageJson::SubscriptionUpdate(SubscriptionUpdateJson {
+            tx_offset: Some(self.tx_offset),
+            history_id: Some(self.history_id),
+            resumed: self.resumed,
+            ..self.database_update.into_json()
+        })
     }
 
     fn serialize_binary(self) -> Message {
         Message {
-            r#type: Some(message::Type::SubscriptionUpdate(self.database_update.into_protobuf())),
+            r#type: Some(message::Type::SubscriptionUpdate(SubscriptionUpdate {
+                tx_offset: self.tx_offset,
+                history_id: self.history_id,
+                resumed: self.resumed,
+                ..self.database_update.into_protobuf()
+            })),
+        }
+    }
+}
+
+/// The rows added to or removed from the subscribed rows of a client by a `SubscribeAdd` or an `Unsubscribe`.
+pub struct QueryUpdateMessage {
+    pub query_id: u32,
+    pub database_update: DatabaseUpdate,
+}
+
+impl ServerMessage for QueryUpdateMessage {
+    fn serialize_text(self) -> MessageJson {
+        MessageJson::QueryUpdate(QueryUpdateJson {
+            query_id: self.query_id,
+            subscription_update: self.database_update.into_json(),
+        })
+    }
+
+    fn serialize_binary(self) -> Message {
+        Message {
+            r#type: Some(message::Type::QueryUpdate(QueryUpdate {
+                query_id: self.query_id,
+                subscription_update: Some(self.database_update.into_protobuf()),
+            })),
         }
     }
 }
@@ -4,6 +4,7 @@ use anyhow::{anyhow, Context};
 
 use crate::address::Address;
 
+use crate::db::FsyncPolicy;
 use crate::hash::hash_bytes;
 use crate::host::EnergyQuanta;
 use crate::identity::Identity;
@@ -33,6 +34,10 @@ pub enum Error {
     RecordAlreadyExists(DomainName),
     #[error("database with address {0} already exists")]
     DatabaseAlreadyExists(String),
+    #[error("Permission denied: `{}` does not own database `{}`", .identity.to_hex(), .address.to_abbreviated_hex())]
+    PermissionDenied { identity: Identity, address: Address },
+    #[error("{0}")]
+    InvalidRestorePoint(String),
     #[error("failed to register {0} domain")]
     DomainRegistrationFailure(DomainName),
     #[error("failed to decode data")]
@@ -380,12 +385,31 @@ impl ControlDb {
 
             tree_by_address.remove(key.as_bytes())?;
             tree.remove(id.to_be_bytes())?;
+            self.set_fsync_policy(id, None)?;
             return Ok(Some(id));
         }
 
         Ok(None)
     }
 
+    /// The [FsyncPolicy] the database of id `database_id` was published with, if any.
+    pub fn get_fsync_policy(&self, database_id: u64) -> Result<Option<FsyncPolicy>> {
+        let tree = self.db.open_tree("fsync_policy")?;
+        let value = tree.get(database_id.to_be_bytes())?;
+        Ok(value.map(|value| serde_json::from_slice(&value[..])).transpose()?)
+    }
+
+    /// Sets the [FsyncPolicy] of the database of id `database_id`,
+    /// overriding that of the host, or resets it to the host's if `None`.
+    pub fn set_fsync_policy(&self, database_id: u64, policy: Option<FsyncPolicy>) -> Result<()> {
+        let tree = self.db.open_tree("fsync_policy")?;
+        match policy {
+            Some(policy) => tree.insert(database_id.to_be_bytes(), serde_json::to_vec(&policy)?)?,
+            None => tree.remove(database_id.to_be_bytes())?,
+        };
+        Ok(())
+    }
+
     pub fn get_database_instances(&self) -> Result<Vec<DatabaseInstance>> {
         let tree = self.db.open_tree("database_instance")?;
         let mut database_instances = Vec::new();
@@ -1,9 +1,11 @@
 use std::str::FromStr;
+use std::time::Duration;
 
 use once_cell::sync::Lazy;
 use tempdir::TempDir;
 
 use super::*;
+use crate::messages::control_db::HostType;
 
 static ALICE: Lazy<Identity> = Lazy::new(|| Identity::from_hashing_bytes("alice"));
 static BOB: Lazy<Identity> = Lazy::new(|| Identity::from_hashing_bytes("bob"));
@@ -82,3 +84,36 @@ fn test_domain() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_fsync_policy() -> anyhow::Result<()> {
+    let tmp = TempDir::new("fsync-policy")?;
+    let cdb = ControlDb::at(tmp.path())?;
+
+    let id = cdb.insert_database(Database {
+        id: 0,
+        address: Address::zero(),
+        identity: *ALICE,
+        host_type: HostType::Wasmer,
+        num_replicas: 1,
+        program_bytes_address: hash_bytes(b"program"),
+        publisher_address: None,
+    })?;
+    assert_eq!(cdb.get_fsync_policy(id)?, None);
+
+    let policy = FsyncPolicy::GroupCommit {
+        window: Duration::from_millis(10),
+        max_bytes: 4096,
+    };
+    cdb.set_fsync_policy(id, Some(policy))?;
+    assert_eq!(cdb.get_fsync_policy(id)?, Some(policy));
+    cdb.set_fsync_policy(id, Some(FsyncPolicy::EveryTx))?;
+    assert_eq!(cdb.get_fsync_policy(id)?, Some(FsyncPolicy::EveryTx));
+
+    // The policy goes away with its database.
+    cdb.delete_database(id)?;
+    assert_eq!(cdb.get_fsync_policy(id)?, None);
+    let _ = tmp.close().ok(); // force tmp to not be dropped until here
+
+    Ok(())
+}
@@ -1,11 +1,13 @@
 use super::database_logger::DatabaseLogger;
 use crate::address::Address;
+use crate::db::commit_log::RestorePoint;
 use crate::db::message_log::MessageLog;
 use crate::db::ostorage::memory_object_db::MemoryObjectDB;
 use crate::db::ostorage::sled_object_db::SledObjectDB;
 use crate::db::ostorage::ObjectDB;
 use crate::db::relational_db::RelationalDB;
-use crate::db::{Config, FsyncPolicy, Storage};
+use crate::db::{Config, Storage};
+use crate::error::DBError;
 use crate::identity::Identity;
 use crate::messages::control_db::Database;
 use std::path::{Path, PathBuf};
@@ -24,10 +26,7 @@ pub struct DatabaseInstanceContext {
 
 impl DatabaseInstanceContext {
     pub fn from_database(config: Config, database: &Database, instance_id: u64, root_db_path: PathBuf) -> Arc<Self> {
-        let mut db_path = root_db_path;
-        db_path.extend([database.address.to_hex(), instance_id.to_string()]);
-        db_path.push("database");
-
+        let db_path = Self::db_path(&database.address, instance_id, root_db_path);
         let log_path = DatabaseLogger::filepath(&database.address, instance_id);
 
         Self::new(
@@ -42,6 +41,30 @@ impl DatabaseInstanceContext {
         )
     }
 
+    /// Creates the instance of `database` as a fork of the `source` database
+    /// in its state as of `point`.
+    pub fn restore_from(
+        config: Config,
+        database: &Database,
+        instance_id: u64,
+        root_db_path: PathBuf,
+        source: &RelationalDB,
+        point: RestorePoint,
+    ) -> Result<Arc<Self>, DBError> {
+        if let Storage::Memory = config.storage {
+            return Err(anyhow::anyhow!("Can't restore a database into memory").into());
+        }
+        let db_path = Self::db_path(&database.address, instance_id, root_db_path.clone());
+        let mut message_log = MessageLog::open(db_path.join("mlog"))?;
+        let mut odb = Self::make_default_ostorage(db_path.join("odb"));
+        source.restore_into(point, &db_path, &mut message_log, &mut *odb)?;
+        drop((message_log, odb));
+
+        Ok(Self::from_database(config, database, instance_id, root_db_path))
+    }
+
+    /// The path of the sled tree in which the scheduled reducers of the instance were stored
+    /// before `st_scheduled`, see [`crate::host::scheduler::import_legacy_schedules`].
     pub fn scheduler_db_path(&self, root_db_path: PathBuf) -> PathBuf {
         let mut scheduler_db_path = root_db_path;
         scheduler_db_path.extend([self.address.to_hex(), self.database_instance_id.to_string()]);
@@ -49,6 +72,13 @@ impl DatabaseInstanceContext {
         scheduler_db_path
     }
 
+    fn db_path(address: &Address, instance_id: u64, root_db_path: PathBuf) -> PathBuf {
+        let mut db_path = root_db_path;
+        db_path.extend([address.to_hex(), instance_id.to_string()]);
+        db_path.push("database");
+        db_path
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         config: Config,
@@ -84,7 +114,16 @@ impl DatabaseInstanceContext {
             address,
             logger: Arc::new(Mutex::new(DatabaseLogger::open(log_path))),
             relational_db: Arc::new(
-                RelationalDB::open(db_path, message_log, odb, address, config.fsync != FsyncPolicy::Never).unwrap(),
+                RelationalDB::open(
+                    db_path,
+                    message_log,
+                    odb,
+                    address,
+                    config.fsync,
+                    config.snapshot_interval,
+                    config.compact_log,
+                )
+                .unwrap(),
             ),
             publisher_address,
         })
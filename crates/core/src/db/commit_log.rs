@@ -3,6 +3,7 @@ use super::{
     message_log::{self, MessageLog},
     messages::commit::Commit,
     ostorage::ObjectDB,
+    FsyncPolicy,
 };
 use crate::{
     db::{
@@ -13,6 +14,7 @@ use crate::{
         },
     },
     error::DBError,
+    host::Timestamp,
 };
 
 use spacetimedb_lib::{
@@ -20,16 +22,36 @@ use spacetimedb_lib::{
     DataKey,
 };
 
+use anyhow::anyhow;
 use std::io;
-use std::sync::Arc;
-use std::sync::Mutex;
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
 #[derive(Clone)]
 pub struct CommitLog {
     mlog: Option<Arc<Mutex<MessageLog>>>,
     odb: Arc<Mutex<Box<dyn ObjectDB + Send>>>,
     unwritten_commit: Arc<Mutex<Commit>>,
-    fsync: bool,
+    /// Shared by the clones of the log, so that [CommitLog::set_fsync] applies to all of them.
+    fsync: Arc<Mutex<FsyncPolicy>>,
+    batches: Arc<Batches>,
+}
+
+/// The batch of transactions being accumulated under [FsyncPolicy::GroupCommit].
+#[derive(Default)]
+struct Batch {
+    /// The size in bytes of the transactions in the batch.
+    bytes: usize,
+    /// The outcome of writing the batch, shared by its transactions,
+    /// or `None` when no batch is being accumulated.
+    result: Option<Arc<OnceLock<Result<(), String>>>>,
+}
+
+/// Coordinates the transactions waiting for their [Batch] to be written.
+#[derive(Default)]
+struct Batches {
+    current: Mutex<Batch>,
+    written: Condvar,
 }
 
 impl CommitLog {
@@ -37,38 +59,165 @@ impl CommitLog {
         mlog: Option<Arc<Mutex<MessageLog>>>,
         odb: Arc<Mutex<Box<dyn ObjectDB + Send>>>,
         unwritten_commit: Commit,
-        fsync: bool,
+        fsync: FsyncPolicy,
     ) -> Self {
         Self {
             mlog,
             odb,
             unwritten_commit: Arc::new(Mutex::new(unwritten_commit)),
-            fsync,
+            fsync: Arc::new(Mutex::new(fsync)),
+            batches: Arc::new(Batches::default()),
+        }
+    }
+
+    fn fsync(&self) -> FsyncPolicy {
+        *self.fsync.lock().unwrap()
+    }
+
+    /// Changes the [FsyncPolicy] of the transactions appended from now on.
+    ///
+    /// The batch being accumulated under [FsyncPolicy::GroupCommit], if any,
+    /// is written under the old policy first.
+    pub fn set_fsync(&self, fsync: FsyncPolicy) {
+        let mut batch = self.batches.current.lock().unwrap();
+        while batch.result.is_some() {
+            batch = self.batches.written.wait(batch).unwrap();
         }
+        *self.fsync.lock().unwrap() = fsync;
     }
 
     /// Persist to disk the [Tx] result into the [MessageLog].
     ///
     /// Returns `Some(n_bytes_written)` if `commit_result` was persisted, `None` if it doesn't have bytes to write.
+    /// Under [FsyncPolicy::GroupCommit], this waits until the batch of the transaction is durable,
+    /// and `n_bytes_written` is the size of the transaction in the batch.
     #[tracing::instrument(skip_all)]
     pub fn append_tx<D>(&self, tx_data: &TxData, datastore: &D) -> Result<Option<usize>, DBError>
     where
         D: MutTxDatastore<RowId = RowId>,
     {
-        if let Some(bytes) = self.generate_commit(tx_data, datastore) {
-            self.append_commit_bytes(&bytes).map(Some)
-        } else {
-            Ok(None)
+        match self.fsync() {
+            FsyncPolicy::GroupCommit { window, max_bytes } if self.mlog.is_some() => {
+                self.append_tx_to_batch(tx_data, window, max_bytes)
+            }
+            _ => {
+                if let Some(bytes) = self.generate_commit(tx_data, datastore) {
+                    self.append_commit_bytes(&bytes).map(Some)
+                } else {
+                    Ok(None)
+                }
+            }
         }
     }
 
+    /// Adds the transaction to the current [Batch] and waits until the batch is durable.
+    ///
+    /// The first transaction of a batch leads it: it waits for the `window` to elapse,
+    /// or for the batch to take `max_bytes`, then writes the batch as a single commit.
+    fn append_tx_to_batch(
+        &self,
+        tx_data: &TxData,
+        window: Duration,
+        max_bytes: usize,
+    ) -> Result<Option<usize>, DBError> {
+        if tx_data.records.is_empty() {
+            return Ok(None);
+        }
+
+        let mut batch = self.batches.current.lock().unwrap();
+        // The policy may have changed since the caller read it,
+        // in which case the transaction is written on its own, see [Self::set_fsync].
+        if !matches!(self.fsync(), FsyncPolicy::GroupCommit { .. }) {
+            drop(batch);
+            let bytes = {
+                let mut unwritten_commit = self.unwritten_commit.lock().unwrap();
+                self.add_transaction(&mut unwritten_commit, tx_data);
+                Self::encode_commit(&mut unwritten_commit)
+            };
+            return self.append_commit_bytes(&bytes).map(Some);
+        }
+        let tx_len = self.add_transaction(&mut self.unwritten_commit.lock().unwrap(), tx_data);
+        batch.bytes += tx_len;
+
+        if let Some(result) = batch.result.clone() {
+            if batch.bytes >= max_bytes {
+                // Wake up the leader to write the full batch.
+                self.batches.written.notify_all();
+            }
+            while result.get().is_none() {
+                batch = self.batches.written.wait(batch).unwrap();
+            }
+            return match result.get().unwrap() {
+                Ok(()) => Ok(Some(tx_len)),
+                Err(err) => Err(anyhow!("Failed to write the batch of the transaction: {err}").into()),
+            };
+        }
+
+        let result = Arc::new(OnceLock::new());
+        batch.result = Some(result.clone());
+        let deadline = Instant::now() + window;
+        while batch.bytes < max_bytes {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if timeout.is_zero() {
+                break;
+            }
+            batch = self.batches.written.wait_timeout(batch, timeout).unwrap().0;
+        }
+
+        // Write while holding the lock, so the next batch starts after this one is durable.
+        let bytes = Self::encode_commit(&mut self.unwritten_commit.lock().unwrap());
+        let written = self.append_commit_bytes(&bytes);
+        *batch = Batch::default();
+        result
+            .set(written.as_ref().map(drop).map_err(|err| err.to_string()))
+            .unwrap();
+        self.batches.written.notify_all();
+
+        written.map(|_| Some(tx_len))
+    }
+
+    /// Returns the header of the next commit to be written,
+    /// i.e. the position in the log after all the commits written so far.
+    pub fn next_commit(&self) -> Commit {
+        let unwritten_commit = self.unwritten_commit.lock().unwrap();
+        Commit {
+            parent_commit_hash: unwritten_commit.parent_commit_hash,
+            commit_offset: unwritten_commit.commit_offset,
+            min_tx_offset: unwritten_commit.min_tx_offset,
+            timestamp: None,
+            transactions: Vec::new(),
+        }
+    }
+
+    /// Physically persists the commits written so far, regardless of the [FsyncPolicy].
+    pub fn sync_all(&self) -> Result<(), DBError> {
+        if let Some(mlog) = &self.mlog {
+            mlog.lock().unwrap().sync_all()?;
+            self.odb.lock().unwrap().sync_all()?;
+        }
+        Ok(())
+    }
+
+    /// Removes the segments of the [MessageLog] holding only commits before `commit_offset`,
+    /// i.e. the segments covered by a snapshot at `commit_offset`.
+    ///
+    /// Returns the number of segments removed.
+    pub fn compact(&self, commit_offset: u64) -> Result<usize, DBError> {
+        let Some(mlog) = &self.mlog else {
+            return Ok(0);
+        };
+        let mut mlog = mlog.lock().unwrap();
+        let offset = segment_offset_of_commit(&mlog, commit_offset)?;
+        mlog.remove_segments_before(offset)
+    }
+
     // For testing -- doesn't require a `MutTxDatastore`, which is currently
     // unused anyway.
     fn append_commit_bytes(&self, commit: &[u8]) -> Result<usize, DBError> {
         if let Some(mlog) = &self.mlog {
             let mut mlog = mlog.lock().unwrap();
             mlog.append(commit)?;
-            if self.fsync {
+            if self.fsync() != FsyncPolicy::Never {
                 mlog.sync_all()?;
                 let mut odb = self.odb.lock().unwrap();
                 odb.sync_all()?;
@@ -89,6 +238,15 @@ impl CommitLog {
         }
 
         let mut unwritten_commit = self.unwritten_commit.lock().unwrap();
+        self.add_transaction(&mut unwritten_commit, tx_data);
+        Some(Self::encode_commit(&mut unwritten_commit))
+    }
+
+    /// Adds the transaction of `tx_data` to the `unwritten_commit`,
+    /// and its large objects to the [ObjectDB].
+    ///
+    /// Returns the encoded length of the transaction.
+    fn add_transaction(&self, unwritten_commit: &mut Commit, tx_data: &TxData) -> usize {
         let writes = tx_data
             .records
             .iter()
@@ -102,36 +260,145 @@ impl CommitLog {
             })
             .collect();
         let transaction = Transaction { writes };
+        let len = transaction.encoded_len();
         unwritten_commit.transactions.push(Arc::new(transaction));
 
-        const COMMIT_SIZE: usize = 1;
-
-        if unwritten_commit.transactions.len() >= COMMIT_SIZE {
-            {
-                let mut guard = self.odb.lock().unwrap();
-                for record in &tx_data.records {
-                    match &record.op {
-                        TxOp::Insert(bytes) => {
-                            guard.add(Vec::clone(bytes));
-                        }
-                        TxOp::Delete => continue,
-                    }
+        let mut guard = self.odb.lock().unwrap();
+        for record in &tx_data.records {
+            match &record.op {
+                TxOp::Insert(bytes) => {
+                    guard.add(Vec::clone(bytes));
                 }
+                TxOp::Delete => continue,
             }
+        }
+        len
+    }
 
-            let mut bytes = Vec::new();
-            unwritten_commit.encode(&mut bytes);
+    /// Encodes the `unwritten_commit` and starts the next one.
+    fn encode_commit(unwritten_commit: &mut Commit) -> Vec<u8> {
+        unwritten_commit.timestamp = Some(Timestamp::now());
+        let mut bytes = Vec::new();
+        unwritten_commit.encode(&mut bytes);
 
-            unwritten_commit.parent_commit_hash = Some(hash_bytes(&bytes));
-            unwritten_commit.commit_offset += 1;
-            unwritten_commit.min_tx_offset += unwritten_commit.transactions.len() as u64;
-            unwritten_commit.transactions.clear();
+        unwritten_commit.parent_commit_hash = Some(hash_bytes(&bytes));
+        unwritten_commit.commit_offset += 1;
+        unwritten_commit.min_tx_offset += unwritten_commit.transactions.len() as u64;
+        unwritten_commit.transactions.clear();
 
-            Some(bytes)
-        } else {
-            None
+        bytes
+    }
+}
+
+/// The point in the history of a database, as recorded by its [CommitLog],
+/// to restore it to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestorePoint {
+    /// After the last commit, i.e. a fork of the database as it is.
+    Latest,
+    /// After the commit at this offset.
+    Offset(u64),
+    /// After the last commit written at or before this time.
+    Time(Timestamp),
+}
+
+/// Returns the offset of the segment of `mlog` holding the commit at `commit_offset`,
+/// or of the first segment if it starts after `commit_offset`.
+///
+/// The segment is found by the first commit of each segment, as the offsets
+/// of the messages in the [MessageLog] don't necessarily match the offsets of the commits.
+pub fn segment_offset_of_commit(mlog: &MessageLog, commit_offset: u64) -> Result<u64, DBError> {
+    let mut found = 0;
+    for segment in mlog.segments() {
+        let offset = segment.offset();
+        let Some(bytes) = segment.try_into_iter()?.next().transpose()? else {
+            break;
+        };
+        let (commit, _) = Commit::decode(bytes);
+        if commit.commit_offset > commit_offset {
+            break;
+        }
+        found = offset;
+    }
+    Ok(found)
+}
+
+/// The first corrupt message of a [MessageLog], as found by [verify].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Corruption {
+    /// The offset of the segment holding the message.
+    pub segment_offset: u64,
+    /// The position in bytes of the message in its segment.
+    pub byte_offset: u64,
+    /// The offset of the commit expected in the message, `None` if it is the first one.
+    pub commit_offset: Option<u64>,
+    pub reason: String,
+}
+
+/// The outcome of [verify].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verification {
+    /// The number of valid commits before the corruption, if any.
+    pub commits: u64,
+    pub corruption: Option<Corruption>,
+}
+
+/// Checks the checksum of every message in the `segments`, and that they hold
+/// contiguous commits forming a hash chain, stopping at the first corrupt one.
+///
+/// Unlike opening the [MessageLog], this leaves a torn write at the end of the
+/// log in place, reporting it as a corruption.
+pub fn verify(segments: message_log::Segments) -> Result<Verification, DBError> {
+    let mut commits = 0;
+    let mut parent = None;
+    let mut next_offset = None;
+    for segment in segments {
+        let (segment_offset, size) = (segment.offset(), segment.size());
+        let mut messages = segment.try_into_iter()?;
+        loop {
+            let reason = match messages.next() {
+                None if messages.position() < size => "incomplete message".to_owned(),
+                None => break,
+                Some(Err(e)) if e.kind() == io::ErrorKind::InvalidData => e.to_string(),
+                Some(Err(e)) => return Err(e.into()),
+                Some(Ok(bytes)) => {
+                    let hash = hash_bytes(&bytes);
+                    let (commit, _) = Commit::decode(bytes);
+                    let checksummed = messages.is_checksummed();
+                    let checked = match next_offset {
+                        Some(offset) if offset != commit.commit_offset => Err(format!(
+                            "commit {} out of order, expected commit {offset}",
+                            commit.commit_offset
+                        )),
+                        _ if checksummed => check_parent(&commit, parent).map_err(|e| e.to_string()),
+                        _ => Ok(()),
+                    };
+                    match checked {
+                        Ok(()) => {
+                            commits += 1;
+                            next_offset = Some(commit.commit_offset + 1);
+                            parent = checksummed.then_some(hash);
+                            continue;
+                        }
+                        Err(reason) => reason,
+                    }
+                }
+            };
+            return Ok(Verification {
+                commits,
+                corruption: Some(Corruption {
+                    segment_offset,
+                    byte_offset: messages.position(),
+                    commit_offset: next_offset,
+                    reason,
+                }),
+            });
         }
     }
+    Ok(Verification {
+        commits,
+        corruption: None,
+    })
 }
 
 /// A read-only view of a [`CommitLog`].
@@ -181,6 +448,22 @@ impl CommitLogView {
         self.message_log_segments_from(offset).into()
     }
 
+    /// Obtain an iterator over the [`Commit`]s in the log, starting at the
+    /// segment holding the commit at `commit_offset`.
+    ///
+    /// Unlike [`Self::iter_from`], this looks up the segment by the offsets of
+    /// the commits rather than of the messages, which may differ.
+    /// [`Commit`]s with an offset _smaller_ than `commit_offset` may still be
+    /// yielded, from the start of the segment.
+    pub fn iter_from_commit(&self, commit_offset: u64) -> Result<Iter, DBError> {
+        let Some(mlog) = &self.mlog else {
+            return Ok(message_log::Segments::empty().into());
+        };
+        let mlog = mlog.lock().unwrap();
+        let offset = segment_offset_of_commit(&mlog, commit_offset)?;
+        Ok(mlog.segments_from(offset).into())
+    }
+
     /// Obtain an iterator over the large objects in [`Commit`], if any.
     ///
     /// Large objects are stored in the [`ObjectDB`], and are referenced from
@@ -227,26 +510,53 @@ struct IterSegment {
 }
 
 impl Iterator for IterSegment {
-    type Item = io::Result<Commit>;
+    type Item = io::Result<(Commit, Hash)>;
 
     fn next(&mut self) -> Option<Self::Item> {
         let next = self.inner.next()?;
         Some(next.map(|bytes| {
+            let hash = hash_bytes(&bytes);
             // It seems very improbable that `decode` is infallible...
             let (commit, _) = Commit::decode(bytes);
-            commit
+            (commit, hash)
         }))
     }
 }
 
+/// Checks that `commit` follows the commit of hash `parent`, if known.
+fn check_parent(commit: &Commit, parent: Option<Hash>) -> io::Result<()> {
+    match parent {
+        Some(parent) if commit.parent_commit_hash != Some(parent) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("commit {} doesn't follow its parent commit", commit.commit_offset),
+        )),
+        _ => Ok(()),
+    }
+}
+
 /// Iterator over a [`CommitLogView`], yielding [`Commit`]s.
 ///
 /// Created by [`CommitLogView::iter`] and [`CommitLogView::iter_from`]
 /// respectively.
+///
+/// The `parent_commit_hash` of each commit is checked against the hash of the
+/// commit before it, yielding an [`io::ErrorKind::InvalidData`] error if they
+/// differ. The commits in segments without checksums are not checked,
+/// as they were written before the hash chain was maintained across restarts.
 #[must_use = "iterators are lazy and do nothing unless consumed"]
 pub struct Iter {
     commits: Option<IterSegment>,
     segments: message_log::Segments,
+    /// The hash of the last commit yielded, if it is to be checked.
+    parent: Option<Hash>,
+    last_hash: Option<Hash>,
+}
+
+impl Iter {
+    /// The hash of the last commit yielded, i.e. the parent of the next one.
+    pub fn last_hash(&self) -> Option<Hash> {
+        self.last_hash
+    }
 }
 
 impl Iterator for Iter {
@@ -256,8 +566,16 @@ impl Iterator for Iter {
         loop {
             if let Some(mut commits) = self.commits.take() {
                 if let Some(commit) = commits.next() {
+                    let checksummed = commits.inner.is_checksummed();
                     self.commits = Some(commits);
-                    return Some(commit);
+                    return Some(commit.and_then(|(commit, hash)| {
+                        if checksummed {
+                            check_parent(&commit, self.parent)?;
+                        }
+                        self.parent = checksummed.then_some(hash);
+                        self.last_hash = Some(hash);
+                        Ok(commit)
+                    }));
                 }
             }
 
@@ -277,6 +595,8 @@ impl From<message_log::Segments> for Iter {
         Self {
             commits: None,
             segments,
+            parent: None,
+            last_hash: None,
         }
     }
 }
@@ -286,10 +606,179 @@ mod tests {
     use super::*;
 
     use spacetimedb_lib::data_key::InlineData;
+    use spacetimedb_sats::product;
     use tempdir::TempDir;
 
+    use crate::db::datastore::traits::{TableId, TxRecord};
     use crate::db::ostorage::memory_object_db::MemoryObjectDB;
 
+    fn open_log(path: &std::path::Path, fsync: FsyncPolicy) -> CommitLog {
+        let mlog = MessageLog::open(path).unwrap();
+        let odb = MemoryObjectDB::default();
+        CommitLog::new(
+            Some(Arc::new(Mutex::new(mlog))),
+            Arc::new(Mutex::new(Box::new(odb))),
+            Commit {
+                parent_commit_hash: None,
+                commit_offset: 0,
+                min_tx_offset: 0,
+                timestamp: None,
+                transactions: Vec::new(),
+            },
+            fsync,
+        )
+    }
+
+    fn insert_tx(n: u8) -> TxData {
+        TxData {
+            records: vec![TxRecord {
+                op: TxOp::Insert(Arc::new(vec![n])),
+                table_id: TableId(42),
+                key: DataKey::Data(InlineData::from_bytes(&[n]).unwrap()),
+                product_value: product![n],
+            }],
+        }
+    }
+
+    fn append_tx(log: &CommitLog, n: u8) {
+        let mut commit = log.unwritten_commit.lock().unwrap();
+        log.add_transaction(&mut commit, &insert_tx(n));
+        let bytes = CommitLog::encode_commit(&mut commit);
+        log.append_commit_bytes(&bytes).unwrap();
+    }
+
+    #[test]
+    fn test_verify() {
+        let tmp = TempDir::new("commit_log_test").unwrap();
+        let log = open_log(tmp.path(), FsyncPolicy::EveryTx);
+        for n in 0..10 {
+            append_tx(&log, n);
+        }
+        let verify = || verify(message_log::Segments::read_dir(tmp.path()).unwrap()).unwrap();
+        assert_eq!(
+            verify(),
+            Verification {
+                commits: 10,
+                corruption: None
+            }
+        );
+
+        let segment = CommitLogView::from(&log).message_log_segments().next().unwrap();
+        let path = tmp.path().join(format!("{:0>20}.log", segment.offset()));
+        let mut messages = segment.try_into_iter().unwrap();
+        let mut positions = Vec::new();
+        while messages.next().is_some() {
+            positions.push(messages.position());
+        }
+        let bytes = std::fs::read(&path).unwrap();
+
+        // A flipped bit is caught by the checksum.
+        let mut corrupt = bytes.clone();
+        corrupt[positions[4] as usize + message_log::HEADER_SIZE + 1] ^= 1;
+        std::fs::write(&path, &corrupt).unwrap();
+        let corruption = verify().corruption.unwrap();
+        assert_eq!(corruption.byte_offset, positions[4]);
+        assert_eq!(corruption.commit_offset, Some(4));
+        assert_eq!(corruption.reason, "message checksum mismatch");
+        assert!(CommitLogView::from(&log).iter().any(|commit| commit.is_err()));
+
+        // A torn write is reported, until opening the log truncates it.
+        std::fs::write(&path, &bytes[..bytes.len() - 3]).unwrap();
+        let verification = verify();
+        assert_eq!(verification.commits, 9);
+        assert_eq!(verification.corruption.unwrap().commit_offset, Some(9));
+        drop(log);
+        let log = open_log(tmp.path(), FsyncPolicy::EveryTx);
+        assert_eq!(
+            verify(),
+            Verification {
+                commits: 9,
+                corruption: None
+            }
+        );
+
+        // A commit not following its parent breaks the hash chain.
+        let mut orphan = Vec::new();
+        Commit {
+            parent_commit_hash: None,
+            commit_offset: 9,
+            min_tx_offset: 9,
+            timestamp: None,
+            transactions: Vec::new(),
+        }
+        .encode(&mut orphan);
+        log.append_commit_bytes(&orphan).unwrap();
+        let verification = verify();
+        assert_eq!(verification.commits, 9);
+        assert_eq!(
+            verification.corruption.unwrap().reason,
+            "commit 9 doesn't follow its parent commit"
+        );
+    }
+
+    #[test]
+    fn test_group_commit() {
+        let tmp = TempDir::new("commit_log_test").unwrap();
+        let window = Duration::from_millis(200);
+        let max_bytes = usize::MAX;
+        let log = open_log(tmp.path(), FsyncPolicy::GroupCommit { window, max_bytes });
+
+        const TXS: u8 = 8;
+        std::thread::scope(|s| {
+            for n in 0..TXS {
+                let log = &log;
+                s.spawn(move || log.append_tx_to_batch(&insert_tx(n), window, max_bytes).unwrap());
+            }
+        });
+
+        // Every tx is durable once acknowledged, in fewer commits than txs.
+        let commits = CommitLogView::from(&log).iter().map(Result::unwrap).collect::<Vec<_>>();
+        let txs = commits.iter().map(|commit| commit.transactions.len()).sum::<usize>();
+        assert_eq!(txs, TXS as usize);
+        assert!(commits.len() < TXS as usize, "{} commits for {TXS} txs", commits.len());
+        for (offset, commit) in commits.iter().enumerate() {
+            assert_eq!(commit.commit_offset, offset as u64);
+        }
+    }
+
+    #[test]
+    fn test_group_commit_max_bytes() {
+        let tmp = TempDir::new("commit_log_test").unwrap();
+        // A full batch doesn't wait for its window to elapse.
+        let window = Duration::from_secs(3600);
+        let max_bytes = 1;
+        let log = open_log(tmp.path(), FsyncPolicy::GroupCommit { window, max_bytes });
+
+        for n in 0..3 {
+            assert!(log
+                .append_tx_to_batch(&insert_tx(n), window, max_bytes)
+                .unwrap()
+                .is_some());
+        }
+        assert_eq!(CommitLogView::from(&log).iter().count(), 3);
+    }
+
+    #[test]
+    fn test_set_fsync() {
+        let tmp = TempDir::new("commit_log_test").unwrap();
+        let window = Duration::from_secs(3600);
+        let max_bytes = usize::MAX;
+        let log = open_log(tmp.path(), FsyncPolicy::GroupCommit { window, max_bytes });
+
+        // A clone of the log shares its policy.
+        log.clone().set_fsync(FsyncPolicy::EveryTx);
+        assert_eq!(log.fsync(), FsyncPolicy::EveryTx);
+
+        // A transaction which read the old policy doesn't wait for the window of a batch.
+        for n in 0..3 {
+            assert!(log
+                .append_tx_to_batch(&insert_tx(n), window, max_bytes)
+                .unwrap()
+                .is_some());
+        }
+        assert_eq!(CommitLogView::from(&log).iter().count(), 3);
+    }
+
     #[test]
     fn test_iter_commits() {
         let tmp = TempDir::new("commit_log_test").unwrap();
@@ -310,20 +799,28 @@ mod tests {
             ],
         };
 
-        // The iterator doesn't verify integrity of commits, so we can just
-        // write the same one repeatedly.
-        let commit = Commit {
-            parent_commit_hash: None,
+        // The iterator verifies the hash chain, so each commit follows the
+        // previous one. They all have a parent, so they have the same size.
+        let mut commit = Commit {
+            parent_commit_hash: Some(hash_bytes(b"genesis")),
             commit_offset: 0,
             min_tx_offset: 0,
+            timestamp: None,
             transactions: vec![Arc::new(tx)],
         };
-        let mut commit_bytes = Vec::new();
-        commit.encode(&mut commit_bytes);
+        let mut next_commit_bytes = || {
+            let mut bytes = Vec::new();
+            commit.encode(&mut bytes);
+            commit.parent_commit_hash = Some(hash_bytes(&bytes));
+            commit.commit_offset += 1;
+            bytes
+        };
+        let commit_len = next_commit_bytes().len();
 
         const COMMITS_PER_SEGMENT: usize = 10_000;
         const TOTAL_MESSAGES: usize = (COMMITS_PER_SEGMENT * 3) - 1;
-        let segment_size: usize = COMMITS_PER_SEGMENT * (commit_bytes.len() + 4);
+        let segment_size: usize =
+            message_log::SEGMENT_HEADER_SIZE + COMMITS_PER_SEGMENT * (commit_len + message_log::HEADER_SIZE);
 
         let mlog = message_log::MessageLog::options()
             .max_segment_size(segment_size as u64)
@@ -338,13 +835,14 @@ mod tests {
                 parent_commit_hash: None,
                 commit_offset: 0,
                 min_tx_offset: 0,
+                timestamp: None,
                 transactions: Vec::new(),
             },
-            true, // fsync
+            FsyncPolicy::EveryTx,
         );
 
         for _ in 0..TOTAL_MESSAGES {
-            log.append_commit_bytes(&commit_bytes).unwrap();
+            log.append_commit_bytes(&next_commit_bytes()).unwrap();
         }
 
         let view = CommitLogView::from(&log);
@@ -3,13 +3,11 @@ use crate::{
     db::datastore::traits::{IndexId, IndexSchema},
     error::DBError,
 };
+use im::{ordset, OrdSet};
 use nonempty::NonEmpty;
-use spacetimedb_lib::{data_key::ToDataKey, DataKey};
+use spacetimedb_lib::{data_key::ToDataKey, DataKey, IndexType};
 use spacetimedb_sats::{AlgebraicValue, ProductValue};
-use std::{
-    collections::{btree_set, BTreeSet},
-    ops::{Bound, RangeBounds},
-};
+use std::ops::{Bound, RangeBounds};
 
 /// ## Index Key Composition
 ///
@@ -48,7 +46,7 @@ impl IndexKey {
 }
 
 pub struct BTreeIndexIter<'a> {
-    iter: btree_set::Iter<'a, IndexKey>,
+    iter: ordset::Iter<'a, IndexKey>,
 }
 
 impl Iterator for BTreeIndexIter<'_> {
@@ -63,7 +61,7 @@ impl Iterator for BTreeIndexIter<'_> {
 /// An iterator for the rows that match a value [AlgebraicValue] on the
 /// [BTreeIndex]
 pub struct BTreeIndexRangeIter<'a> {
-    range_iter: btree_set::Range<'a, IndexKey>,
+    range_iter: ordset::RangedIter<'a, IndexKey>,
 }
 
 impl Iterator for BTreeIndexRangeIter<'_> {
@@ -75,13 +73,19 @@ impl Iterator for BTreeIndexRangeIter<'_> {
     }
 }
 
+/// An index over the values of `cols`.
+///
+/// The keys are kept in a persistent [OrdSet], so cloning the index for a snapshot is cheap.
+#[derive(Clone)]
 pub(crate) struct BTreeIndex {
     pub(crate) index_id: IndexId,
     pub(crate) table_id: u32,
     pub(crate) cols: NonEmpty<u32>,
     pub(crate) name: String,
     pub(crate) is_unique: bool,
-    idx: BTreeSet<IndexKey>,
+    idx: OrdSet<IndexKey>,
+    /// The number of distinct values in `idx`.
+    distinct_values: usize,
 }
 
 impl BTreeIndex {
@@ -92,7 +96,8 @@ impl BTreeIndex {
             cols,
             name,
             is_unique,
-            idx: BTreeSet::new(),
+            idx: OrdSet::new(),
+            distinct_values: 0,
         }
     }
 
@@ -105,14 +110,24 @@ impl BTreeIndex {
     pub(crate) fn insert(&mut self, row: &ProductValue) -> Result<(), DBError> {
         let col_value = self.get_fields(row)?;
         let key = IndexKey::from_row(&col_value, row.to_data_key());
-        self.idx.insert(key);
+        let is_new_value = !self.contains_any(&col_value);
+        if self.idx.insert(key).is_none() && is_new_value {
+            self.distinct_values += 1;
+        }
         Ok(())
     }
 
     #[tracing::instrument(skip_all)]
     pub(crate) fn delete(&mut self, col_value: &AlgebraicValue, row_id: &RowId) {
         let key = IndexKey::from_row(col_value, row_id.0);
-        self.idx.remove(&key);
+        if self.idx.remove(&key).is_some() && !self.contains_any(col_value) {
+            self.distinct_values -= 1;
+        }
+    }
+
+    /// Returns the number of distinct values in the [BTreeIndex].
+    pub(crate) fn distinct_values(&self) -> usize {
+        self.distinct_values
     }
 
     #[tracing::instrument(skip_all)]
@@ -148,13 +163,15 @@ impl BTreeIndex {
     /// that fall within the specified `range`.
     #[tracing::instrument(skip_all)]
     pub(crate) fn seek<'a>(&'a self, range: &impl RangeBounds<AlgebraicValue>) -> BTreeIndexRangeIter<'a> {
-        let map = |bound, datakey| match bound {
-            Bound::Included(x) => Bound::Included(IndexKey::from_row(x, datakey)),
-            Bound::Excluded(x) => Bound::Excluded(IndexKey::from_row(x, datakey)),
+        // The keys for a `value` span from `(value, min_datakey)` to `(value, max_datakey)`,
+        // so an excluded bound must skip past all of them.
+        let map = |bound, included, excluded| match bound {
+            Bound::Included(x) => Bound::Included(IndexKey::from_row(x, included)),
+            Bound::Excluded(x) => Bound::Excluded(IndexKey::from_row(x, excluded)),
             Bound::Unbounded => Bound::Unbounded,
         };
-        let start = map(range.start_bound(), DataKey::min_datakey());
-        let end = map(range.end_bound(), DataKey::max_datakey());
+        let start = map(range.start_bound(), DataKey::min_datakey(), DataKey::max_datakey());
+        let end = map(range.end_bound(), DataKey::max_datakey(), DataKey::min_datakey());
         BTreeIndexRangeIter {
             range_iter: self.idx.range((start, end)),
         }
@@ -178,6 +195,7 @@ impl From<&BTreeIndex> for IndexSchema {
             cols: x.cols.clone(),
             is_unique: x.is_unique,
             index_name: x.name.clone(),
+            index_type: IndexType::BTree,
         }
     }
 }
@@ -0,0 +1,133 @@
+use super::RowId;
+use crate::{
+    db::datastore::traits::{IndexId, IndexSchema},
+    error::DBError,
+};
+use im::{ordset, HashMap, OrdSet};
+use nonempty::NonEmpty;
+use spacetimedb_lib::{data_key::ToDataKey, IndexType};
+use spacetimedb_sats::{AlgebraicValue, ProductValue};
+
+/// An iterator for the rows that match a value [AlgebraicValue] on the
+/// [HashIndex]
+pub struct HashIndexIter<'a> {
+    iter: Option<ordset::Iter<'a, RowId>>,
+}
+
+impl Iterator for HashIndexIter<'_> {
+    type Item = RowId;
+
+    #[tracing::instrument(skip_all)]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.as_mut()?.next().copied()
+    }
+}
+
+/// An index over the values of `cols`, which only answers equality lookups.
+///
+/// The `RowId`s of a value are yielded in ascending order, as by a
+/// [BTreeIndex](super::btree_index::BTreeIndex) seeking that value.
+/// Both maps are persistent, so cloning the index for a snapshot is cheap.
+#[derive(Clone)]
+pub(crate) struct HashIndex {
+    pub(crate) index_id: IndexId,
+    pub(crate) table_id: u32,
+    pub(crate) cols: NonEmpty<u32>,
+    pub(crate) name: String,
+    pub(crate) is_unique: bool,
+    idx: HashMap<AlgebraicValue, OrdSet<RowId>>,
+}
+
+impl HashIndex {
+    pub(crate) fn new(index_id: IndexId, table_id: u32, cols: NonEmpty<u32>, name: String, is_unique: bool) -> Self {
+        Self {
+            index_id,
+            table_id,
+            cols,
+            name,
+            is_unique,
+            idx: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn get_fields(&self, row: &ProductValue) -> Result<AlgebraicValue, DBError> {
+        let fields = row.project_not_empty(&self.cols)?;
+        Ok(fields)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn insert(&mut self, row: &ProductValue) -> Result<(), DBError> {
+        let col_value = self.get_fields(row)?;
+        self.idx.entry(col_value).or_default().insert(RowId(row.to_data_key()));
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn delete(&mut self, col_value: &AlgebraicValue, row_id: &RowId) {
+        if let Some(row_ids) = self.idx.get_mut(col_value) {
+            row_ids.remove(row_id);
+            if row_ids.is_empty() {
+                self.idx.remove(col_value);
+            }
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn violates_unique_constraint(&self, row: &ProductValue) -> bool {
+        if self.is_unique {
+            let col_value = self.get_fields(row).unwrap();
+            return self.contains_any(&col_value);
+        }
+        false
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn get_rows_that_violate_unique_constraint<'a>(
+        &'a self,
+        row: &'a AlgebraicValue,
+    ) -> Option<HashIndexIter<'a>> {
+        self.is_unique.then(|| self.seek(row))
+    }
+
+    /// Returns `true` if the [HashIndex] contains a value for the specified `value`.
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn contains_any(&self, value: &AlgebraicValue) -> bool {
+        self.idx.contains_key(value)
+    }
+
+    /// Returns the number of distinct values in the [HashIndex].
+    pub(crate) fn distinct_values(&self) -> usize {
+        self.idx.len()
+    }
+
+    /// Returns an iterator over the [HashIndex] that yields all the `RowId`s
+    /// that match the specified `value`.
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn seek<'a>(&'a self, value: &AlgebraicValue) -> HashIndexIter<'a> {
+        HashIndexIter {
+            iter: self.idx.get(value).map(|row_ids| row_ids.iter()),
+        }
+    }
+
+    /// Construct the [HashIndex] from the rows.
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn build_from_rows<'a>(&mut self, rows: impl Iterator<Item = &'a ProductValue>) -> Result<(), DBError> {
+        for row in rows {
+            self.insert(row)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<&HashIndex> for IndexSchema {
+    fn from(x: &HashIndex) -> Self {
+        IndexSchema {
+            index_id: x.index_id.0,
+            table_id: x.table_id,
+            cols: x.cols.clone(),
+            is_unique: x.is_unique,
+            index_name: x.name.clone(),
+            index_type: IndexType::Hash,
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use super::{
+    btree_index::{BTreeIndex, BTreeIndexRangeIter},
+    hash_index::{HashIndex, HashIndexIter},
+    RowId,
+};
+use crate::{
+    db::datastore::traits::{IndexId, IndexSchema},
+    error::DBError,
+};
+use nonempty::NonEmpty;
+use spacetimedb_lib::IndexType;
+use spacetimedb_sats::{AlgebraicValue, ProductValue};
+use std::ops::{Bound, RangeBounds};
+
+/// An iterator over the `RowId`s yielded by a [TableIndex].
+pub enum TableIndexIter<'a> {
+    BTree(BTreeIndexRangeIter<'a>),
+    Hash(HashIndexIter<'a>),
+}
+
+impl Iterator for TableIndexIter<'_> {
+    type Item = RowId;
+
+    #[tracing::instrument(skip_all)]
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::BTree(iter) => iter.next(),
+            Self::Hash(iter) => iter.next(),
+        }
+    }
+}
+
+/// An index of a table, of one of the [IndexType]s.
+#[derive(Clone)]
+pub(crate) enum TableIndex {
+    BTree(BTreeIndex),
+    Hash(HashIndex),
+}
+
+impl TableIndex {
+    pub(crate) fn new(
+        index_type: IndexType,
+        index_id: IndexId,
+        table_id: u32,
+        cols: NonEmpty<u32>,
+        name: String,
+        is_unique: bool,
+    ) -> Self {
+        match index_type {
+            IndexType::BTree => Self::BTree(BTreeIndex::new(index_id, table_id, cols, name, is_unique)),
+            IndexType::Hash => Self::Hash(HashIndex::new(index_id, table_id, cols, name, is_unique)),
+        }
+    }
+
+    /// Returns an empty index with the same definition as this one.
+    pub(crate) fn new_empty(&self) -> Self {
+        Self::new(
+            self.index_type(),
+            self.index_id(),
+            self.table_id(),
+            self.cols().clone(),
+            self.name().to_owned(),
+            self.is_unique(),
+        )
+    }
+
+    pub(crate) fn index_type(&self) -> IndexType {
+        match self {
+            Self::BTree(_) => IndexType::BTree,
+            Self::Hash(_) => IndexType::Hash,
+        }
+    }
+
+    pub(crate) fn index_id(&self) -> IndexId {
+        match self {
+            Self::BTree(index) => index.index_id,
+            Self::Hash(index) => index.index_id,
+        }
+    }
+
+    pub(crate) fn table_id(&self) -> u32 {
+        match self {
+            Self::BTree(index) => index.table_id,
+            Self::Hash(index) => index.table_id,
+        }
+    }
+
+    pub(crate) fn cols(&self) -> &NonEmpty<u32> {
+        match self {
+            Self::BTree(index) => &index.cols,
+            Self::Hash(index) => &index.cols,
+        }
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        match self {
+            Self::BTree(index) => &index.name,
+            Self::Hash(index) => &index.name,
+        }
+    }
+
+    pub(crate) fn is_unique(&self) -> bool {
+        match self {
+            Self::BTree(index) => index.is_unique,
+            Self::Hash(index) => index.is_unique,
+        }
+    }
+
+    pub(crate) fn distinct_values(&self) -> usize {
+        match self {
+            Self::BTree(index) => index.distinct_values(),
+            Self::Hash(index) => index.distinct_values(),
+        }
+    }
+
+    pub(crate) fn get_fields(&self, row: &ProductValue) -> Result<AlgebraicValue, DBError> {
+        match self {
+            Self::BTree(index) => index.get_fields(row),
+            Self::Hash(index) => index.get_fields(row),
+        }
+    }
+
+    pub(crate) fn insert(&mut self, row: &ProductValue) -> Result<(), DBError> {
+        match self {
+            Self::BTree(index) => index.insert(row),
+            Self::Hash(index) => index.insert(row),
+        }
+    }
+
+    pub(crate) fn delete(&mut self, col_value: &AlgebraicValue, row_id: &RowId) {
+        match self {
+            Self::BTree(index) => index.delete(col_value, row_id),
+            Self::Hash(index) => index.delete(col_value, row_id),
+        }
+    }
+
+    pub(crate) fn violates_unique_constraint(&self, row: &ProductValue) -> bool {
+        match self {
+            Self::BTree(index) => index.violates_unique_constraint(row),
+            Self::Hash(index) => index.violates_unique_constraint(row),
+        }
+    }
+
+    pub(crate) fn get_rows_that_violate_unique_constraint<'a>(
+        &'a self,
+        row: &'a AlgebraicValue,
+    ) -> Option<TableIndexIter<'a>> {
+        match self {
+            Self::BTree(index) => index
+                .get_rows_that_violate_unique_constraint(row)
+                .map(TableIndexIter::BTree),
+            Self::Hash(index) => index
+                .get_rows_that_violate_unique_constraint(row)
+                .map(TableIndexIter::Hash),
+        }
+    }
+
+    /// Returns an iterator over the index that yields all the `RowId`s
+    /// that fall within the specified `range`,
+    /// or `None` if the index can't answer it, i.e. a hash index for anything but a single value.
+    pub(crate) fn seek<'a>(&'a self, range: &impl RangeBounds<AlgebraicValue>) -> Option<TableIndexIter<'a>> {
+        match self {
+            Self::BTree(index) => Some(TableIndexIter::BTree(index.seek(range))),
+            Self::Hash(index) => match (range.start_bound(), range.end_bound()) {
+                (Bound::Included(start), Bound::Included(end)) if start == end => {
+                    Some(TableIndexIter::Hash(index.seek(start)))
+                }
+                _ => None,
+            },
+        }
+    }
+
+    pub(crate) fn build_from_rows<'a>(&mut self, rows: impl Iterator<Item = &'a ProductValue>) -> Result<(), DBError> {
+        match self {
+            Self::BTree(index) => index.build_from_rows(rows),
+            Self::Hash(index) => index.build_from_rows(rows),
+        }
+    }
+}
+
+impl From<&TableIndex> for IndexSchema {
+    fn from(x: &TableIndex) -> Self {
+        match x {
+            TableIndex::BTree(index) => index.into(),
+            TableIndex::Hash(index) => index.into(),
+        }
+    }
+}
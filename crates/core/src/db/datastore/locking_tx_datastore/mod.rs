@@ -1,58 +1,64 @@
 mod btree_index;
+mod hash_index;
+mod index;
 mod sequence;
 mod table;
 
 use self::{
-    btree_index::{BTreeIndex, BTreeIndexRangeIter},
+    index::{TableIndex, TableIndexIter},
     sequence::Sequence,
     table::Table,
 };
+use im::OrdMap;
 use nonempty::NonEmpty;
 use std::{
     borrow::Cow,
     collections::{BTreeMap, BTreeSet, HashMap},
-    ops::RangeBounds,
+    ops::{Deref, DerefMut, RangeBounds},
     sync::Arc,
     vec,
 };
 
 use super::{
     system_tables::{
-        self, StColumnRow, StIndexRow, StModuleRow, StSequenceRow, StTableRow, INDEX_ID_SEQUENCE_ID,
-        SEQUENCE_ID_SEQUENCE_ID, ST_COLUMNS_ID, ST_COLUMNS_ROW_TYPE, ST_INDEXES_ID, ST_INDEX_ROW_TYPE, ST_MODULE_ID,
-        ST_SEQUENCES_ID, ST_SEQUENCE_ROW_TYPE, ST_TABLES_ID, ST_TABLE_ROW_TYPE, TABLE_ID_SEQUENCE_ID, WASM_MODULE,
+        self, decode_st_index_row, st_index_legacy_data_key, StColumnRow, StIndexRow, StModuleRow, StSequenceRow, StTableRow,
+        INDEX_ID_SEQUENCE_ID, SEQUENCE_ID_SEQUENCE_ID, ST_COLUMNS_ID, ST_COLUMNS_ROW_TYPE, ST_INDEXES_ID,
+        ST_INDEX_ROW_TYPE, ST_MODULE_ID, ST_SEQUENCES_ID, ST_SEQUENCE_ROW_TYPE, ST_TABLES_ID, ST_TABLE_ROW_TYPE,
+        TABLE_ID_SEQUENCE_ID, WASM_MODULE,
     },
     traits::{
         self, ColId, DataRow, IndexDef, IndexId, IndexSchema, MutTx, MutTxDatastore, SequenceDef, SequenceId, TableDef,
-        TableId, TableSchema, TxData, TxDatastore,
+        TableId, TableSchema, TableStats, TxData, TxDatastore,
     },
 };
 
 use crate::db::datastore::system_tables::{
-    st_constraints_schema, st_module_schema, table_name_is_system, StConstraintRow, SystemTables,
-    CONSTRAINT_ID_SEQUENCE_ID, ST_CONSTRAINTS_ID, ST_CONSTRAINT_ROW_TYPE, ST_MODULE_ROW_TYPE,
+    st_constraints_schema, st_module_schema, st_scheduled_schema, table_name_is_system, StConstraintRow, SystemTables,
+    CONSTRAINT_ID_SEQUENCE_ID, SCHEDULED_ID_SEQUENCE_ID, ST_CONSTRAINTS_ID, ST_CONSTRAINT_ROW_TYPE, ST_MODULE_ROW_TYPE,
+    ST_SCHEDULED_ID, ST_SCHEDULED_ROW_TYPE,
 };
 use crate::{
     db::datastore::traits::{TxOp, TxRecord},
     db::{
         datastore::{
             system_tables::{st_columns_schema, st_indexes_schema, st_sequences_schema, st_table_schema},
-            traits::ColumnSchema,
+            traits::{ColumnDef, ColumnSchema},
         },
         messages::{transaction::Transaction, write::Operation},
         ostorage::ObjectDB,
+        snapshot::SnapshotTable,
     },
     error::{DBError, IndexError, TableError},
 };
 
 use anyhow::anyhow;
 use derive_more::Into;
-use parking_lot::{lock_api::ArcMutexGuard, Mutex, RawMutex};
+use parking_lot::{lock_api::ArcMutexGuard, Mutex, RawMutex, RwLock};
 use spacetimedb_lib::{
     auth::{StAccess, StTableType},
     data_key::ToDataKey,
     relation::RelValue,
-    DataKey, Hash,
+    DataKey, Hash, IndexType,
 };
 use spacetimedb_sats::{AlgebraicType, AlgebraicValue, ProductType, ProductValue};
 use thiserror::Error;
@@ -124,23 +130,69 @@ impl From<DataRef> for RelValue {
 }
 
 pub struct MutTxId {
-    lock: ArcMutexGuard<RawMutex, Inner>,
+    lock: TxLock,
 }
 
+impl MutTxId {
+    /// Returns `true` if this is a read-only transaction over a snapshot of the committed state.
+    pub fn is_read_only(&self) -> bool {
+        matches!(self.lock, TxLock::Read(_))
+    }
+}
+
+/// The state a transaction operates on.
+enum TxLock {
+    /// A mutable transaction holds the lock over the datastore
+    /// until it's committed or rolled back.
+    Mut(ArcMutexGuard<RawMutex, Inner>),
+    /// A read-only transaction owns a snapshot of the committed state,
+    /// so it doesn't block, nor is blocked by, the mutable transactions.
+    ///
+    /// Writes made to it are never committed and are discarded when it's released.
+    Read(Box<Inner>),
+}
+
+impl Deref for TxLock {
+    type Target = Inner;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            TxLock::Mut(inner) => inner,
+            TxLock::Read(inner) => inner,
+        }
+    }
+}
+
+impl DerefMut for TxLock {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            TxLock::Mut(inner) => inner,
+            TxLock::Read(inner) => inner,
+        }
+    }
+}
+
+/// The committed tables of the database.
+///
+/// The tables are persistent data structures,
+/// so cloning the `CommittedState` to take a snapshot of it is cheap.
+#[derive(Clone)]
 struct CommittedState {
-    tables: HashMap<TableId, Table>,
+    tables: im::HashMap<TableId, Table>,
 }
 
 impl CommittedState {
     fn new() -> Self {
-        Self { tables: HashMap::new() }
+        Self {
+            tables: im::HashMap::new(),
+        }
     }
 
     fn get_or_create_table(&mut self, table_id: TableId, row_type: &ProductType, schema: &TableSchema) -> &mut Table {
         self.tables.entry(table_id).or_insert_with(|| Table {
             row_type: row_type.clone(),
             schema: schema.clone(),
-            rows: BTreeMap::new(),
+            rows: OrdMap::new(),
             indexes: HashMap::new(),
         })
     }
@@ -151,6 +203,7 @@ impl CommittedState {
 
     fn merge(&mut self, tx_state: TxState, memory: BTreeMap<DataKey, Arc<Vec<u8>>>) -> TxData {
         let mut tx_data = TxData { records: vec![] };
+        let mut new_indexes = Vec::new();
         for (table_id, table) in tx_state.insert_tables {
             let commit_table = self.get_or_create_table(table_id, &table.row_type, &table.schema);
             // The schema may have been modified in the transaction.
@@ -172,12 +225,7 @@ impl CommittedState {
                 }
             }));
 
-            // Add all newly created indexes to the committed state
-            for (_, index) in table.indexes {
-                if !commit_table.indexes.contains_key(&index.cols.clone().map(ColId)) {
-                    commit_table.insert_index(index);
-                }
-            }
+            new_indexes.extend(table.indexes.into_values().map(|index| (table_id, index)));
         }
         for (table_id, row_ids) in tx_state.delete_tables {
             // NOTE: it is possible that the delete_tables contain a row in a table
@@ -202,6 +250,14 @@ impl CommittedState {
                 }
             }
         }
+        // Add all newly created indexes to the committed state.
+        // This is done after the deletes, as the deleted rows may not fit the altered table.
+        for (table_id, index) in new_indexes {
+            let commit_table = self.get_table(&table_id).unwrap();
+            if !commit_table.indexes.contains_key(&index.cols().clone().map(ColId)) {
+                commit_table.insert_index(index);
+            }
+        }
         tx_data
     }
 
@@ -210,7 +266,7 @@ impl CommittedState {
         table_id: &TableId,
         cols: &NonEmpty<ColId>,
         range: &impl RangeBounds<AlgebraicValue>,
-    ) -> Option<BTreeIndexRangeIter<'a>> {
+    ) -> Option<TableIndexIter<'a>> {
         if let Some(table) = self.tables.get(table_id) {
             table.index_seek(cols, range)
         } else {
@@ -326,7 +382,7 @@ impl TxState {
         table_id: &TableId,
         cols: &NonEmpty<ColId>,
         range: &impl RangeBounds<AlgebraicValue>,
-    ) -> Option<BTreeIndexRangeIter<'a>> {
+    ) -> Option<TableIndexIter<'a>> {
         self.insert_tables.get(table_id)?.index_seek(cols, range)
     }
 }
@@ -417,6 +473,7 @@ impl Inner {
                     ),
                     ST_SEQUENCES_ID => (SystemTables::total_sequences() as i128, SEQUENCE_ID_SEQUENCE_ID),
                     ST_CONSTRAINTS_ID => (SystemTables::total_constraints() as i128, CONSTRAINT_ID_SEQUENCE_ID),
+                    ST_SCHEDULED_ID => (1, SCHEDULED_ID_SEQUENCE_ID),
                     _ => unreachable!(),
                 };
                 let st_sequences = self.committed_state.get_or_create_table(
@@ -473,6 +530,7 @@ impl Inner {
                     cols: NonEmpty::new(col_id.col_id),
                     index_name: format!("idx_{}", &constraint.constraint_name),
                     is_unique: true,
+                    index_type: IndexType::BTree,
                 },
                 x if x.is_indexed() => IndexSchema {
                     index_id: constraint.constraint_id,
@@ -480,6 +538,7 @@ impl Inner {
                     cols: NonEmpty::new(col_id.col_id),
                     index_name: format!("idx_{}", &constraint.constraint_name),
                     is_unique: false,
+                    index_type: IndexType::BTree,
                 },
                 x => {
                     panic!("Adding constraint of kind `{x:?}` is not supported yet.")
@@ -499,6 +558,7 @@ impl Inner {
                 cols: index.cols.clone(),
                 index_name: &index.index_name,
                 is_unique: index.is_unique,
+                index_type: index.index_type,
             };
             let row = ProductValue::from(&row);
             let data_key = row.to_data_key();
@@ -515,11 +575,14 @@ impl Inner {
             let sequence = StSequenceRow::try_from(&row)?;
             // TODO: The system tables have initialized their value already, but this is wrong:
             // If we exceed  `SEQUENCE_PREALLOCATION_AMOUNT` we will get a unique violation
-            let is_system_table = self
-                .committed_state
-                .tables
-                .get(&TableId(sequence.table_id))
-                .map_or(false, |x| x.schema.table_type == StTableType::System);
+            // The ids of the scheduled reducers are only known from the rows persisted
+            // in `st_scheduled`, so, as for a user table, its sequence resumes after its allocation.
+            let is_system_table = TableId(sequence.table_id) != ST_SCHEDULED_ID
+                && self
+                    .committed_state
+                    .tables
+                    .get(&TableId(sequence.table_id))
+                    .map_or(false, |x| x.schema.table_type == StTableType::System);
 
             let schema = (&sequence).into();
 
@@ -542,7 +605,8 @@ impl Inner {
         for row in rows {
             let index_row = StIndexRow::try_from(&row)?;
             let table = self.committed_state.get_table(&TableId(index_row.table_id)).unwrap();
-            let mut index = BTreeIndex::new(
+            let mut index = TableIndex::new(
+                index_row.index_type,
                 IndexId(index_row.index_id),
                 index_row.table_id,
                 index_row.cols.clone(),
@@ -573,7 +637,7 @@ impl Inner {
                         row_type,
                         schema,
                         indexes: HashMap::new(),
-                        rows: BTreeMap::new(),
+                        rows: OrdMap::new(),
                     },
                 );
             }
@@ -785,7 +849,7 @@ impl Inner {
                 row_type,
                 schema,
                 indexes: HashMap::new(),
-                rows: BTreeMap::new(),
+                rows: OrdMap::new(),
             },
         );
         Ok(())
@@ -879,6 +943,7 @@ impl Inner {
                 index_name: el.index_name.into(),
                 is_unique: el.is_unique,
                 index_id: el.index_id,
+                index_type: el.index_type,
             };
             indexes.push(index_schema);
         }
@@ -924,8 +989,9 @@ impl Inner {
         self.drop_table_from_st_tables(table_id)?;
 
         // Delete the table and its rows and indexes from memory.
-        // TODO: This needs to not remove it from the committed state, because it can still be rolled back.
-        // We will have to store the deletion in the TxState and then apply it to the CommittedState in commit.
+        // This removes it from the committed state, which is restored
+        // if the transaction is rolled back, see `Locking::rollback_mut_tx`.
+        // TODO: Store the deletion in the TxState and apply it to the CommittedState in commit instead.
         self.committed_state.tables.remove(&table_id);
         Ok(())
     }
@@ -946,6 +1012,78 @@ impl Inner {
         Ok(())
     }
 
+    fn alter_table(&mut self, table_id: TableId, columns: Vec<ColumnDef>) -> super::Result<Vec<ProductValue>> {
+        let table_name = &self.schema_for_table(table_id)?.table_name;
+        if table_name_is_system(table_name) {
+            return Err(TableError::System(table_name.clone()).into());
+        }
+
+        // Delete the rows, which no longer fit the table.
+        let rows = self
+            .iter(&table_id)?
+            .map(|row| row.view().to_owned())
+            .collect::<Vec<_>>();
+        self.delete_by_rel(&table_id, rows.iter().cloned())?;
+
+        // Replace the table's columns in st_columns.
+        // The unchanged columns are deleted and reinserted, which leaves them in place.
+        const ST_COLUMNS_TABLE_ID_COL: ColId = ColId(0);
+        let old_columns = self
+            .iter_by_col_eq(&ST_COLUMNS_ID, ST_COLUMNS_TABLE_ID_COL, table_id.into())?
+            .map(|row| row.view().to_owned())
+            .collect::<Vec<_>>();
+        self.delete_by_rel(&ST_COLUMNS_ID, old_columns)?;
+        for (i, col) in columns.iter().enumerate() {
+            let row = StColumnRow {
+                table_id: table_id.0,
+                col_id: i as u32,
+                col_name: &col.col_name,
+                col_type: col.col_type.clone(),
+                is_autoinc: col.is_autoinc,
+            };
+            self.insert(ST_COLUMNS_ID, (&row).into())?;
+        }
+
+        // Swap the columns of the in memory representation of the table.
+        // As for a new index, this is done in the insert table,
+        // which replaces the schema of the committed table on commit.
+        if self.tx_state.as_ref().unwrap().get_insert_table(&table_id).is_none() {
+            let committed_table = self
+                .committed_state
+                .tables
+                .get(&table_id)
+                .ok_or(TableError::IdNotFound(table_id.0))?;
+            let table = Table {
+                row_type: committed_table.row_type.clone(),
+                schema: committed_table.get_schema().clone(),
+                indexes: committed_table
+                    .indexes
+                    .iter()
+                    .map(|(cols, index)| (cols.clone(), index.new_empty()))
+                    .collect::<HashMap<_, _>>(),
+                rows: OrdMap::new(),
+            };
+            self.tx_state.as_mut().unwrap().insert_tables.insert(table_id, table);
+        }
+        let insert_table = self.tx_state.as_mut().unwrap().get_insert_table_mut(&table_id).unwrap();
+        insert_table.row_type = ProductType {
+            elements: columns.iter().map(|col| col.col_type.clone().into()).collect(),
+        };
+        insert_table.schema.columns = columns
+            .into_iter()
+            .enumerate()
+            .map(|(i, col)| ColumnSchema {
+                table_id: table_id.0,
+                col_id: i as u32,
+                col_name: col.col_name,
+                col_type: col.col_type,
+                is_autoinc: col.is_autoinc,
+            })
+            .collect();
+
+        Ok(rows)
+    }
+
     fn table_id_from_name(&self, table_name: &str) -> super::Result<Option<TableId>> {
         let table_name_col: ColId = ColId(1);
         self.iter_by_col_eq(
@@ -968,6 +1106,39 @@ impl Inner {
             })
     }
 
+    /// Applies the replayed insert or delete of a `row` of st_columns
+    /// to the in memory schema of its table, if there is one,
+    /// as the columns of a table with rows may have been altered by [`Self::alter_table`].
+    ///
+    /// The insert of an altered column is replayed before the delete of the column it replaces,
+    /// so a deleted column is only removed if it is still the one in the schema.
+    fn replay_column_write(&mut self, row: &ProductValue, is_insert: bool) -> super::Result<()> {
+        let el = StColumnRow::try_from(row)?;
+        let Some(table) = self.committed_state.tables.get_mut(&TableId(el.table_id)) else {
+            return Ok(());
+        };
+        let column = ColumnSchema {
+            table_id: el.table_id,
+            col_id: el.col_id,
+            col_name: el.col_name.into(),
+            col_type: el.col_type,
+            is_autoinc: el.is_autoinc,
+        };
+        let columns = &mut table.schema.columns;
+        match columns.binary_search_by_key(&column.col_id, |col| col.col_id) {
+            Ok(i) if is_insert => columns[i] = column,
+            Err(i) if is_insert => columns.insert(i, column),
+            Ok(i) if columns[i] == column => {
+                columns.remove(i);
+            }
+            _ => return Ok(()),
+        }
+        table.row_type = ProductType {
+            elements: columns.iter().map(|col| col.col_type.clone().into()).collect(),
+        };
+        Ok(())
+    }
+
     fn create_index(&mut self, index: IndexDef) -> super::Result<IndexId> {
         log::trace!(
             "INDEX CREATING: {} for table: {} and col(s): {:?}",
@@ -985,6 +1156,7 @@ impl Inner {
             cols: index.cols.clone(),
             index_name: &index.name,
             is_unique: index.is_unique,
+            index_type: index.index_type,
         };
         let index_id = StIndexRow::try_from(&self.insert(ST_INDEXES_ID, (&row).into())?)?.index_id;
 
@@ -1004,14 +1176,13 @@ impl Inner {
     }
 
     fn create_index_internal(&mut self, index_id: IndexId, index: &IndexDef) -> super::Result<()> {
-        let insert_table = if let Some(insert_table) = self
+        if self
             .tx_state
-            .as_mut()
+            .as_ref()
             .unwrap()
-            .get_insert_table_mut(&TableId(index.table_id))
+            .get_insert_table(&TableId(index.table_id))
+            .is_none()
         {
-            insert_table
-        } else {
             let row_type = self.row_type_for_table(TableId(index.table_id))?.into_owned();
             let schema = self.schema_for_table(TableId(index.table_id))?.into_owned();
             self.tx_state.as_mut().unwrap().insert_tables.insert(
@@ -1020,17 +1191,15 @@ impl Inner {
                     row_type,
                     schema,
                     indexes: HashMap::new(),
-                    rows: BTreeMap::new(),
+                    rows: OrdMap::new(),
                 },
             );
-            self.tx_state
-                .as_mut()
-                .unwrap()
-                .get_insert_table_mut(&TableId(index.table_id))
-                .unwrap()
-        };
+        }
+        let tx_state = self.tx_state.as_mut().unwrap();
+        let insert_table = tx_state.insert_tables.get_mut(&TableId(index.table_id)).unwrap();
 
-        let mut insert_index = BTreeIndex::new(
+        let mut insert_index = TableIndex::new(
+            index.index_type,
             index_id,
             index.table_id,
             index.cols.clone(),
@@ -1039,9 +1208,17 @@ impl Inner {
         );
         insert_index.build_from_rows(insert_table.scan_rows())?;
 
-        // NOTE: Also add all the rows in the already committed table to the index.
+        // NOTE: Also add all the rows in the already committed table to the index,
+        // except for those deleted in this transaction, which may not even fit the table anymore.
         if let Some(committed_table) = self.committed_state.get_table(&TableId(index.table_id)) {
-            insert_index.build_from_rows(committed_table.scan_rows())?;
+            let deleted = tx_state.delete_tables.get(&TableId(index.table_id));
+            insert_index.build_from_rows(
+                committed_table
+                    .rows
+                    .iter()
+                    .filter(|(row_id, _)| deleted.map_or(true, |deleted| !deleted.contains(row_id)))
+                    .map(|(_, row)| row),
+            )?;
         }
 
         insert_table.schema.indexes.push(IndexSchema {
@@ -1050,6 +1227,7 @@ impl Inner {
             index_name: index.name.to_string(),
             is_unique: index.is_unique,
             index_id: index_id.0,
+            index_type: index.index_type,
         });
 
         insert_table.indexes.insert(index.cols.clone().map(ColId), insert_index);
@@ -1079,8 +1257,8 @@ impl Inner {
         for (_, table) in self.committed_state.tables.iter_mut() {
             let mut cols = vec![];
             for index in table.indexes.values_mut() {
-                if index.index_id == *index_id {
-                    cols.push(index.cols.clone());
+                if index.index_id() == *index_id {
+                    cols.push(index.cols().clone());
                 }
             }
             for col in cols {
@@ -1088,16 +1266,11 @@ impl Inner {
                 table.schema.indexes.retain(|x| x.cols != col);
             }
         }
-        if let Some(insert_table) = self
-            .tx_state
-            .as_mut()
-            .unwrap()
-            .get_insert_table_mut(&TableId(index_id.0))
-        {
+        for insert_table in self.tx_state.as_mut().unwrap().insert_tables.values_mut() {
             let mut cols = vec![];
             for index in insert_table.indexes.values_mut() {
-                if index.index_id == *index_id {
-                    cols.push(index.cols.clone());
+                if index.index_id() == *index_id {
+                    cols.push(index.cols().clone());
                 }
             }
             for col in cols {
@@ -1146,6 +1319,43 @@ impl Inner {
             || self.committed_state.tables.contains_key(table_id)
     }
 
+    /// Returns the statistics of the table, counting the rows inserted and deleted by the transaction.
+    ///
+    /// The distinct values of an index are counted by the committed and the inserted rows separately,
+    /// so they are an upper bound when both have the same value.
+    fn table_stats(&self, table_id: &TableId) -> super::Result<TableStats> {
+        if !self.table_exists(table_id) {
+            return Err(TableError::IdNotFound(table_id.0).into());
+        }
+        let tx_state = self.tx_state.as_ref();
+        let tables = self
+            .committed_state
+            .tables
+            .get(table_id)
+            .into_iter()
+            .chain(tx_state.and_then(|tx_state| tx_state.get_insert_table(table_id)));
+        let deleted = tx_state
+            .and_then(|tx_state| tx_state.delete_tables.get(table_id))
+            .map_or(0, |rows| rows.len());
+
+        let mut row_count = 0;
+        let mut distinct_values = HashMap::<_, u64>::new();
+        for table in tables {
+            row_count += table.rows.len();
+            for (cols, index) in &table.indexes {
+                *distinct_values.entry(cols.clone().map(|col| col.0)).or_default() += index.distinct_values() as u64;
+            }
+        }
+        let row_count = row_count.saturating_sub(deleted) as u64;
+        for distinct in distinct_values.values_mut() {
+            *distinct = (*distinct).min(row_count);
+        }
+        Ok(TableStats {
+            row_count,
+            distinct_values,
+        })
+    }
+
     fn algebraic_type_is_numeric(ty: &AlgebraicType) -> bool {
         matches!(*ty, |AlgebraicType::I8| AlgebraicType::U8
             | AlgebraicType::I16
@@ -1242,20 +1452,9 @@ impl Inner {
                 indexes: committed_table
                     .indexes
                     .iter()
-                    .map(|(cols, index)| {
-                        (
-                            cols.clone(),
-                            BTreeIndex::new(
-                                index.index_id,
-                                index.table_id,
-                                index.cols.clone(),
-                                index.name.clone(),
-                                index.is_unique,
-                            ),
-                        )
-                    })
+                    .map(|(cols, index)| (cols.clone(), index.new_empty()))
                     .collect::<HashMap<_, _>>(),
-                rows: BTreeMap::new(),
+                rows: OrdMap::new(),
             };
             self.tx_state.as_mut().unwrap().insert_tables.insert(table_id, table);
             self.tx_state.as_ref().unwrap().get_insert_table(&table_id).unwrap()
@@ -1264,12 +1463,12 @@ impl Inner {
         // Check unique constraints
         for index in insert_table.indexes.values() {
             if index.violates_unique_constraint(&row) {
-                let value = row.project_not_empty(&index.cols).unwrap();
+                let value = row.project_not_empty(index.cols()).unwrap();
                 return Err(IndexError::UniqueConstraintViolation {
-                    constraint_name: index.name.clone(),
+                    constraint_name: index.name().to_owned(),
                     table_name: insert_table.schema.table_name.clone(),
                     col_names: index
-                        .cols
+                        .cols()
                         .iter()
                         .map(|&x| insert_table.schema.columns[x as usize].col_name.clone())
                         .collect(),
@@ -1278,7 +1477,7 @@ impl Inner {
                 .into());
             }
         }
-        if let Some(table) = self.committed_state.tables.get_mut(&table_id) {
+        if let Some(table) = self.committed_state.tables.get(&table_id) {
             for index in table.indexes.values() {
                 let value = index.get_fields(&row)?;
                 let Some(violators) = index.get_rows_that_violate_unique_constraint(&value) else {
@@ -1287,12 +1486,12 @@ impl Inner {
                 for row_id in violators {
                     if let Some(delete_table) = self.tx_state.as_ref().unwrap().delete_tables.get(&table_id) {
                         if !delete_table.contains(&row_id) {
-                            let value = row.project_not_empty(&index.cols)?;
+                            let value = row.project_not_empty(index.cols())?;
                             return Err(IndexError::UniqueConstraintViolation {
-                                constraint_name: index.name.clone(),
+                                constraint_name: index.name().to_owned(),
                                 table_name: table.schema.table_name.clone(),
                                 col_names: index
-                                    .cols
+                                    .cols()
                                     .iter()
                                     .map(|&x| insert_table.schema.columns[x as usize].col_name.clone())
                                     .collect(),
@@ -1301,12 +1500,12 @@ impl Inner {
                             .into());
                         }
                     } else {
-                        let value = row.project_not_empty(&index.cols)?;
+                        let value = row.project_not_empty(index.cols())?;
                         return Err(IndexError::UniqueConstraintViolation {
-                            constraint_name: index.name.clone(),
+                            constraint_name: index.name().to_owned(),
                             table_name: table.schema.table_name.clone(),
                             col_names: index
-                                .cols
+                                .cols()
                                 .iter()
                                 .map(|&x| insert_table.schema.columns[x as usize].col_name.clone())
                                 .collect(),
@@ -1512,10 +1711,13 @@ impl Inner {
             let tx_state = self.tx_state.as_ref().unwrap();
             Ok(IterByColRange::Index(IndexSeekIterInner {
                 table_id: *table_id,
+                cols: NonEmpty::collect(cols.iter().map(|col| col.0)).unwrap(),
                 tx_state,
                 inserted_rows,
                 committed_rows: self.committed_state.index_seek(table_id, &cols, &range),
                 committed_state: &self.committed_state,
+                next_inserted: None,
+                next_committed: None,
             }))
         } else {
             // Either the current transaction has not modified this table, or the table is not
@@ -1555,11 +1757,34 @@ impl Inner {
         self.tx_state = None;
         // TODO: Check that no sequences exceed their allocation after the rollback.
     }
+
+    /// Recreates the sequences of `st_sequences` missing from the sequence state,
+    /// i.e. those dropped by a transaction which was then rolled back.
+    fn restore_dropped_sequences(&mut self) -> super::Result<()> {
+        let st_sequences = self.committed_state.tables.get(&ST_SEQUENCES_ID).unwrap();
+        for row in st_sequences.scan_rows() {
+            let sequence = StSequenceRow::try_from(row)?;
+            let seq_id = SequenceId(sequence.sequence_id);
+            if self.sequence_state.sequences.contains_key(&seq_id) {
+                continue;
+            }
+            let mut seq = Sequence::new((&sequence).into());
+            // The values up to the last allocation may have been handed out already.
+            if seq.value < sequence.allocated + 1 {
+                seq.value = sequence.allocated + 1;
+            }
+            self.sequence_state.sequences.insert(seq_id, seq);
+        }
+        Ok(())
+    }
 }
 
 #[derive(Clone)]
 pub struct Locking {
     inner: Arc<Mutex<Inner>>,
+    /// The state of the database as of the last committed transaction,
+    /// which the read-only transactions take a snapshot of.
+    committed_state: Arc<RwLock<CommittedState>>,
 }
 
 impl Locking {
@@ -1594,6 +1819,11 @@ impl Locking {
             .committed_state
             .get_or_create_table(ST_MODULE_ID, &ST_MODULE_ROW_TYPE, &st_module_schema());
         datastore.bootstrap_system_table(st_module_schema())?;
+        // Likewise for ST_SCHEDULED.
+        datastore
+            .committed_state
+            .get_or_create_table(ST_SCHEDULED_ID, &ST_SCHEDULED_ROW_TYPE, &st_scheduled_schema());
+        datastore.bootstrap_system_table(st_scheduled_schema())?;
 
         // The database tables are now initialized with the correct data.
         // Now we have to build our in memory structures.
@@ -1603,10 +1833,16 @@ impl Locking {
         log::trace!("DATABASE:BOOTSTRAPPING SYSTEM TABLES DONE");
 
         Ok(Locking {
+            committed_state: Arc::new(RwLock::new(datastore.committed_state.clone())),
             inner: Arc::new(Mutex::new(datastore)),
         })
     }
 
+    /// Makes the committed state of `inner` visible to the read-only transactions that begin afterward.
+    fn publish(&self, inner: &Inner) {
+        *self.committed_state.write() = inner.committed_state.clone();
+    }
+
     /// The purpose of this is to rebuild the state of the datastore
     /// after having inserted all of rows from the message log.
     /// This is necessary because, for example, inserting a row into `st_table`
@@ -1621,6 +1857,7 @@ impl Locking {
         inner.build_missing_tables()?;
         inner.build_indexes()?;
         inner.build_sequence_state()?;
+        self.publish(&inner);
 
         Ok(())
     }
@@ -1630,7 +1867,7 @@ impl Locking {
         table_id: TableId,
         schema: TableSchema,
         row_type: ProductType,
-    ) -> &mut BTreeMap<RowId, ProductValue> {
+    ) -> &mut OrdMap<RowId, ProductValue> {
         &mut inner
             .committed_state
             .tables
@@ -1639,7 +1876,7 @@ impl Locking {
                 row_type,
                 schema,
                 indexes: HashMap::new(),
-                rows: BTreeMap::new(),
+                rows: OrdMap::new(),
             })
             .rows
     }
@@ -1656,27 +1893,98 @@ impl Locking {
             let row_type = inner.row_type_for_table(table_id)?.into_owned();
             match write.operation {
                 Operation::Delete => {
-                    Self::table_rows(&mut inner, table_id, schema, row_type).remove(&RowId(write.data_key));
+                    let rows = Self::table_rows(&mut inner, table_id, schema, row_type);
+                    let mut row = rows.remove(&RowId(write.data_key));
+                    if row.is_none() && table_id == ST_INDEXES_ID {
+                        // A row written before `index_type` was added is keyed by its padded form,
+                        // so find the row the legacy key was computed from.
+                        let row_id = rows
+                            .iter()
+                            .find(|(_, row)| st_index_legacy_data_key(row) == write.data_key)
+                            .map(|(row_id, _)| *row_id);
+                        row = row_id.and_then(|row_id| rows.remove(&row_id));
+                    }
+                    if let Some(row) = row.filter(|_| table_id == ST_COLUMNS_ID) {
+                        inner.replay_column_write(&row, false)?;
+                    }
                 }
                 Operation::Insert => {
-                    let product_value = match write.data_key {
-                        DataKey::Data(data) => ProductValue::decode(&row_type, &mut &data[..]).unwrap_or_else(|_| {
+                    let decode = |data: &[u8]| {
+                        let row = if table_id == ST_INDEXES_ID {
+                            decode_st_index_row(data)
+                        } else {
+                            ProductValue::decode(&row_type, &mut &data[..])
+                        };
+                        row.unwrap_or_else(|_| {
                             panic!("Couldn't decode product value to {:?} from message log", row_type)
-                        }),
-                        DataKey::Hash(hash) => {
-                            let data = odb.lock().unwrap().get(hash).unwrap();
-                            ProductValue::decode(&row_type, &mut &data[..]).unwrap_or_else(|_| {
-                                panic!("Couldn't decode product value to {:?} from message log", row_type)
-                            })
-                        }
+                        })
+                    };
+                    let product_value = match write.data_key {
+                        DataKey::Data(data) => decode(&data),
+                        DataKey::Hash(hash) => decode(&odb.lock().unwrap().get(hash).unwrap()),
+                    };
+                    if table_id == ST_COLUMNS_ID {
+                        inner.replay_column_write(&product_value, true)?;
+                    }
+                    // Key the rows of `st_indexes` by their current shape, as `drop_index` does,
+                    // even when they were written before `index_type` was added.
+                    let row_id = if table_id == ST_INDEXES_ID {
+                        RowId(product_value.to_data_key())
+                    } else {
+                        RowId(write.data_key)
                     };
-                    Self::table_rows(&mut inner, table_id, schema, row_type)
-                        .insert(RowId(write.data_key), product_value);
+                    Self::table_rows(&mut inner, table_id, schema, row_type).insert(row_id, product_value);
                 }
             }
         }
         Ok(())
     }
+
+    /// Returns the rows of the committed tables seen by the read-only `tx`,
+    /// in ascending order of table id, for a [`Snapshot`](crate::db::snapshot::Snapshot).
+    pub fn snapshot_tables(&self, tx: &MutTxId) -> Vec<SnapshotTable> {
+        let inner = &*tx.lock;
+        let mut tables = inner
+            .committed_state
+            .tables
+            .iter()
+            // Skip the leftovers of dropped tables, which have no schema to decode their rows with.
+            .filter(|(table_id, _)| inner.schema_for_table(**table_id).is_ok())
+            .map(|(table_id, table)| SnapshotTable {
+                table_id: *table_id,
+                rows: table
+                    .rows
+                    .values()
+                    .map(|row| {
+                        let mut bytes = Vec::new();
+                        row.encode(&mut bytes);
+                        bytes
+                    })
+                    .collect(),
+            })
+            .collect::<Vec<_>>();
+        tables.sort_unstable_by_key(|table| table.table_id);
+        tables
+    }
+
+    /// Inserts the rows of a [`Snapshot`](crate::db::snapshot::Snapshot) into the committed state,
+    /// as [`Self::replay_transaction`] does with the rows of a transaction.
+    ///
+    /// The `tables` must be in ascending order of table id,
+    /// so the schema of each table is restored before its rows.
+    pub fn restore_snapshot(&self, tables: &[SnapshotTable]) -> Result<(), DBError> {
+        let mut inner = self.inner.lock();
+        for table in tables {
+            let schema = inner.schema_for_table(table.table_id)?.into_owned();
+            let row_type = inner.row_type_for_table(table.table_id)?.into_owned();
+            let rows = Self::table_rows(&mut inner, table.table_id, schema, row_type.clone());
+            for bytes in &table.rows {
+                let row = ProductValue::decode(&row_type, &mut &bytes[..])?;
+                rows.insert(RowId(row.to_data_key()), row);
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
@@ -1695,8 +2003,18 @@ impl DataRow for Locking {
 impl traits::Tx for Locking {
     type TxId = MutTxId;
 
+    /// Begins a read-only transaction over a snapshot of the last committed state.
+    ///
+    /// Unlike [`MutTx::begin_mut_tx`], this doesn't take the lock over the datastore,
+    /// so it runs concurrently with the mutable transactions,
+    /// and doesn't see the changes they commit after it began.
     fn begin_tx(&self) -> Self::TxId {
-        self.begin_mut_tx()
+        let mut inner = Inner::new();
+        inner.committed_state = self.committed_state.read().clone();
+        inner.tx_state = Some(TxState::new());
+        MutTxId {
+            lock: TxLock::Read(Box::new(inner)),
+        }
     }
 
     fn release_tx(&self, tx: Self::TxId) {
@@ -1723,10 +2041,10 @@ impl<'a> Iter<'a> {
 enum ScanStage<'a> {
     Start,
     CurrentTx {
-        iter: std::collections::btree_map::Iter<'a, RowId, ProductValue>,
+        iter: im::ordmap::Iter<'a, RowId, ProductValue>,
     },
     Committed {
-        iter: std::collections::btree_map::Iter<'a, RowId, ProductValue>,
+        iter: im::ordmap::Iter<'a, RowId, ProductValue>,
     },
 }
 
@@ -1796,27 +2114,30 @@ impl Iterator for Iter<'_> {
     }
 }
 
+/// An iterator over the rows of an index range, in index order,
+/// merging the rows inserted by the current transaction with the committed ones.
 pub struct IndexSeekIterInner<'a> {
     table_id: TableId,
+    cols: NonEmpty<u32>,
     tx_state: &'a TxState,
     committed_state: &'a CommittedState,
-    inserted_rows: BTreeIndexRangeIter<'a>,
-    committed_rows: Option<BTreeIndexRangeIter<'a>>,
+    inserted_rows: TableIndexIter<'a>,
+    committed_rows: Option<TableIndexIter<'a>>,
+    next_inserted: Option<DataRef>,
+    next_committed: Option<DataRef>,
 }
 
-impl Iterator for IndexSeekIterInner<'_> {
-    type Item = DataRef;
-
-    #[tracing::instrument(skip_all)]
-    fn next(&mut self) -> Option<Self::Item> {
-        if let Some(row_id) = self.inserted_rows.next() {
-            return Some(DataRef::new(
-                row_id.0,
-                self.tx_state.get_row(&self.table_id, &row_id).unwrap().clone(),
-            ));
-        }
+impl IndexSeekIterInner<'_> {
+    fn next_inserted(&mut self) -> Option<DataRef> {
+        let row_id = self.inserted_rows.next()?;
+        Some(DataRef::new(
+            row_id.0,
+            self.tx_state.get_row(&self.table_id, &row_id).unwrap().clone(),
+        ))
+    }
 
-        if let Some(row_id) = self.committed_rows.as_mut().and_then(|i| {
+    fn next_committed(&mut self) -> Option<DataRef> {
+        let row_id = self.committed_rows.as_mut().and_then(|i| {
             i.find(|row_id| {
                 !self
                     .tx_state
@@ -1824,11 +2145,39 @@ impl Iterator for IndexSeekIterInner<'_> {
                     .get(&self.table_id)
                     .map_or(false, |table| table.contains(row_id))
             })
-        }) {
-            return Some(get_committed_row(self.committed_state, &self.table_id, &row_id));
+        })?;
+        Some(get_committed_row(self.committed_state, &self.table_id, &row_id))
+    }
+
+    /// The key of `row` in the index, which orders by column value first and [RowId] second.
+    fn index_key(&self, row: &DataRef) -> (AlgebraicValue, DataKey) {
+        (row.view().project_not_empty(&self.cols).unwrap(), row.id)
+    }
+}
+
+impl Iterator for IndexSeekIterInner<'_> {
+    type Item = DataRef;
+
+    #[tracing::instrument(skip_all)]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next_inserted.is_none() {
+            self.next_inserted = self.next_inserted();
+        }
+        if self.next_committed.is_none() {
+            self.next_committed = self.next_committed();
         }
 
-        None
+        match (&self.next_inserted, &self.next_committed) {
+            (Some(inserted), Some(committed)) => {
+                if self.index_key(inserted) <= self.index_key(committed) {
+                    self.next_inserted.take()
+                } else {
+                    self.next_committed.take()
+                }
+            }
+            (Some(_), None) => self.next_inserted.take(),
+            (None, _) => self.next_committed.take(),
+        }
     }
 }
 
@@ -1836,7 +2185,7 @@ pub struct CommittedIndexIter<'a> {
     table_id: TableId,
     tx_state: &'a TxState,
     committed_state: &'a CommittedState,
-    committed_rows: BTreeIndexRangeIter<'a>,
+    committed_rows: TableIndexIter<'a>,
 }
 
 impl Iterator for CommittedIndexIter<'_> {
@@ -1968,15 +2317,31 @@ impl traits::MutTx for Locking {
             panic!("The previous transaction was not properly rolled back or committed.");
         }
         inner.tx_state = Some(TxState::new());
-        MutTxId { lock: inner }
+        MutTxId {
+            lock: TxLock::Mut(inner),
+        }
     }
 
     fn rollback_mut_tx(&self, mut tx: Self::MutTxId) {
         tx.lock.rollback();
+        if !tx.is_read_only() {
+            // Dropping a table or an index changes the committed state in place,
+            // so it's restored from the one published by the last commit.
+            tx.lock.committed_state = self.committed_state.read().clone();
+            if let Err(e) = tx.lock.restore_dropped_sequences() {
+                log::error!("failed to restore the sequences after a rollback: {e}");
+            }
+        }
     }
 
     fn commit_mut_tx(&self, mut tx: Self::MutTxId) -> super::Result<Option<TxData>> {
-        tx.lock.commit()
+        if tx.is_read_only() {
+            return Err(anyhow!("Cannot commit a read-only transaction").into());
+        }
+        let tx_data = tx.lock.commit()?;
+        // Publish while still holding the lock, so the snapshots follow the order of the commits.
+        self.publish(&tx.lock);
+        Ok(tx_data)
     }
 }
 
@@ -2032,6 +2397,15 @@ impl MutTxDatastore for Locking {
         tx.lock.table_exists(table_id)
     }
 
+    fn alter_table_mut_tx(
+        &self,
+        tx: &mut Self::MutTxId,
+        table_id: TableId,
+        columns: Vec<ColumnDef>,
+    ) -> super::Result<Vec<ProductValue>> {
+        tx.lock.alter_table(table_id, columns)
+    }
+
     fn table_id_from_name_mut_tx(&self, tx: &Self::MutTxId, table_name: &str) -> super::Result<Option<TableId>> {
         tx.lock.table_id_from_name(table_name)
     }
@@ -2040,6 +2414,10 @@ impl MutTxDatastore for Locking {
         tx.lock.table_name_from_id(table_id)
     }
 
+    fn table_stats_mut_tx(&self, tx: &Self::MutTxId, table_id: TableId) -> super::Result<TableStats> {
+        tx.lock.table_stats(&table_id)
+    }
+
     fn create_index_mut_tx(&self, tx: &mut Self::MutTxId, index: IndexDef) -> super::Result<IndexId> {
         tx.lock.create_index(index)
     }
@@ -2182,6 +2560,7 @@ mod tests {
             },
             traits::{
                 ColumnDef, ColumnSchema, DataRow, IndexDef, IndexSchema, MutTx, MutTxDatastore, TableDef, TableSchema,
+                Tx, TxDatastore,
             },
         },
         error::{DBError, IndexError},
@@ -2191,7 +2570,7 @@ mod tests {
     use spacetimedb_lib::{
         auth::{StAccess, StTableType},
         error::ResultTest,
-        ColumnIndexAttribute,
+        ColumnIndexAttribute, IndexType,
     };
     use spacetimedb_sats::{product, AlgebraicType, AlgebraicValue, ProductValue};
 
@@ -2210,6 +2589,7 @@ mod tests {
             cols: NonEmpty::new(col_id),
             index_name: name.into(),
             is_unique,
+            index_type: IndexType::BTree,
         }
     }
 
@@ -2260,6 +2640,7 @@ mod tests {
             cols: NonEmpty::new(col_id),
             index_name: name.to_string(),
             is_unique,
+            index_type: IndexType::BTree,
         }
     }
 
@@ -2289,12 +2670,14 @@ mod tests {
                     cols: NonEmpty::new(0),
                     name: "id_idx".into(),
                     is_unique: true,
+                    index_type: IndexType::BTree,
                 },
                 IndexDef {
                     table_id: 0, // Ignored
                     cols: NonEmpty::new(1),
                     name: "name_idx".into(),
                     is_unique: true,
+                    index_type: IndexType::BTree,
                 },
             ],
             table_type: StTableType::User,
@@ -2338,6 +2721,7 @@ mod tests {
                 table_row(3, "st_indexes", StTableType::System, StAccess::Public),
                 table_row(4, "st_constraints", StTableType::System, StAccess::Public),
                 table_row(5, "st_module", StTableType::System, StAccess::Public),
+                table_row(u32::MAX, "st_scheduled", StTableType::System, StAccess::Public),
             ]
         );
         let column_rows = datastore
@@ -2376,6 +2760,7 @@ mod tests {
                 column_row(3, 2, "cols", AlgebraicType::array(AlgebraicType::U32), false),
                 column_row(3, 3, "index_name", AlgebraicType::String, false),
                 column_row(3, 4, "is_unique", AlgebraicType::Bool, false),
+                column_row(3, 5, "index_type", AlgebraicType::String, false),
 
                 column_row(4, 0, "constraint_id", AlgebraicType::U32, true),
                 column_row(4, 1, "constraint_name", AlgebraicType::String, false),
@@ -2386,6 +2771,13 @@ mod tests {
                 column_row(5, 0, "program_hash", AlgebraicType::array(AlgebraicType::U8), false),
                 column_row(5, 1, "kind", AlgebraicType::U8, false),
                 column_row(5, 2, "epoch", AlgebraicType::U128, false),
+
+                column_row(u32::MAX, 0, "scheduled_id", AlgebraicType::U64, true),
+                column_row(u32::MAX, 1, "reducer", AlgebraicType::String, false),
+                column_row(u32::MAX, 2, "args", AlgebraicType::bytes(), false),
+                column_row(u32::MAX, 3, "scheduled_at", AlgebraicType::U64, false),
+                column_row(u32::MAX, 4, "repeat", AlgebraicType::String, false),
+                column_row(u32::MAX, 5, "misfire", AlgebraicType::String, false),
             ]
         );
         let index_rows = datastore
@@ -2404,6 +2796,7 @@ mod tests {
                 index_row(3, 0, 1, "table_name_idx", true),
                 index_row(4, 4, 0, "constraint_id_idx", true),
                 index_row(5, 1, 0, "idx_ct_columns_table_id", false),
+                index_row(u32::MAX, u32::MAX, 0, "scheduled_id_idx", true),
             ]
         );
         let sequence_rows = datastore
@@ -2419,6 +2812,7 @@ mod tests {
                 StSequenceRow { sequence_id: 1, sequence_name: "sequence_id_seq".to_string(), table_id: 2, col_id: 0, increment: 1, start: 4, min_value: 1, max_value: 4294967295, allocated: 4096 },
                 StSequenceRow { sequence_id: 2, sequence_name: "index_id_seq".to_string(), table_id: 3, col_id: 0, increment: 1, start: 6, min_value: 1, max_value: 4294967295, allocated: 4096 },
                 StSequenceRow { sequence_id: 3, sequence_name: "constraint_id_seq".to_string(), table_id: 4, col_id: 0, increment: 1, start: 1, min_value: 1, max_value: 4294967295, allocated: 4096 },
+                StSequenceRow { sequence_id: u32::MAX, sequence_name: "scheduled_id_seq".to_string(), table_id: u32::MAX, col_id: 0, increment: 1, start: 1, min_value: 1, max_value: 4294967295, allocated: 4096 },
             ]
         );
         let constraints_rows = datastore
@@ -2612,6 +3006,7 @@ mod tests {
                 cols: NonEmpty::new(0),
                 name: "id_idx".into(),
                 is_unique: true,
+                index_type: IndexType::BTree,
             },
         )?;
 
@@ -2801,6 +3196,7 @@ mod tests {
             name: "age_idx".to_string(),
             is_unique: true,
             table_id: table_id.0,
+            index_type: IndexType::BTree,
         };
         datastore.create_index_mut_tx(&mut tx, index_def)?;
         let index_rows = datastore
@@ -2820,6 +3216,7 @@ mod tests {
             index_row(6, 6, 0, "id_idx", true),
             index_row(7, 6, 1, "name_idx", true),
             index_row(8, 6, 2, "age_idx", true),
+            index_row(u32::MAX, u32::MAX, 0, "scheduled_id_idx", true),
         ]);
         let row = u32_str_u32(0, "Bar", 18); // 0 will be ignored.
         let result = datastore.insert_mut_tx(&mut tx, table_id, row);
@@ -2849,6 +3246,7 @@ mod tests {
             cols: NonEmpty::new(2),
             name: "age_idx".to_string(),
             is_unique: true,
+            index_type: IndexType::BTree,
         };
         datastore.create_index_mut_tx(&mut tx, index_def)?;
         datastore.commit_mut_tx(tx)?;
@@ -2870,6 +3268,7 @@ mod tests {
             index_row(6, 6, 0, "id_idx", true),
             index_row(7, 6, 1, "name_idx", true),
             index_row(8, 6, 2, "age_idx", true),
+            index_row(u32::MAX, u32::MAX, 0, "scheduled_id_idx", true),
         ]);
 
         let row = u32_str_u32(0, "Bar", 18); // 0 will be ignored.
@@ -2900,6 +3299,7 @@ mod tests {
             name: "age_idx".to_string(),
             is_unique: true,
             table_id: table_id.0,
+            index_type: IndexType::BTree,
         };
         datastore.create_index_mut_tx(&mut tx, index_def)?;
         datastore.rollback_mut_tx(tx);
@@ -2920,6 +3320,7 @@ mod tests {
             index_row(5, 1, 0, "idx_ct_columns_table_id", false),
             index_row(6, 6, 0, "id_idx", true),
             index_row(7, 6, 1, "name_idx", true),
+            index_row(u32::MAX, u32::MAX, 0, "scheduled_id_idx", true),
         ]);
         let row = u32_str_u32(0, "Bar", 18); // 0 will be ignored.
         datastore.insert_mut_tx(&mut tx, table_id, row)?;
@@ -2931,6 +3332,117 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_drop_post_rollback() -> ResultTest<()> {
+        let (datastore, mut tx, table_id) = setup_table()?;
+        datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, "Foo", 18))?;
+        datastore.commit_mut_tx(tx)?;
+        let mut tx = datastore.begin_mut_tx();
+        let index_id = datastore.index_id_from_name_mut_tx(&tx, "name_idx")?.unwrap();
+        datastore.drop_index_mut_tx(&mut tx, index_id)?;
+        datastore.drop_table_mut_tx(&mut tx, table_id)?;
+        datastore.rollback_mut_tx(tx);
+        // The table, its index and its sequence are back,
+        // the latter resuming after the values it had allocated.
+        let mut tx = datastore.begin_mut_tx();
+        datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, "Bar", 20))?;
+        assert!(datastore
+            .insert_mut_tx(&mut tx, table_id, u32_str_u32(0, "Foo", 20))
+            .is_err());
+        #[rustfmt::skip]
+        assert_eq!(all_rows(&datastore, &tx, table_id), vec![
+            u32_str_u32(1, "Foo", 18),
+            u32_str_u32(4099, "Bar", 20),
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_hash_index() -> ResultTest<()> {
+        let (datastore, mut tx, table_id) = setup_table()?;
+        datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, "Foo", 18))?;
+        datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, "Bar", 20))?;
+        datastore.commit_mut_tx(tx)?;
+        let mut tx = datastore.begin_mut_tx();
+        let index_def = IndexDef::new("age_idx".into(), table_id.0, 2, false).with_index_type(IndexType::Hash);
+        let index_id = datastore.create_index_mut_tx(&mut tx, index_def)?;
+        datastore.commit_mut_tx(tx)?;
+        let mut tx = datastore.begin_mut_tx();
+        datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, "Baz", 18))?;
+
+        let by_age = |tx: &MutTxId, age: u32| {
+            datastore
+                .iter_by_col_eq_mut_tx(tx, table_id, ColId(2), age.into())
+                .unwrap()
+                .map(|r| r.view().clone())
+                .sorted()
+                .collect::<Vec<_>>()
+        };
+        // The rows inserted by the transaction are merged with the committed ones.
+        assert_eq!(
+            by_age(&tx, 18),
+            vec![u32_str_u32(1, "Foo", 18), u32_str_u32(3, "Baz", 18)]
+        );
+        datastore.commit_mut_tx(tx)?;
+        let tx = datastore.begin_mut_tx();
+        assert_eq!(
+            by_age(&tx, 18),
+            vec![u32_str_u32(1, "Foo", 18), u32_str_u32(3, "Baz", 18)]
+        );
+        assert_eq!(by_age(&tx, 19), vec![]);
+
+        // A range can't be answered by the hash index, so it is scanned.
+        let in_range = datastore
+            .iter_by_col_range_mut_tx(&tx, table_id, ColId(2), AlgebraicValue::U32(19)..)?
+            .map(|r| r.view().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(in_range, vec![u32_str_u32(2, "Bar", 20)]);
+
+        let index = datastore
+            .schema_for_table_mut_tx(&tx, table_id)?
+            .indexes
+            .iter()
+            .find(|index| index.index_id == index_id.0)
+            .cloned()
+            .unwrap();
+        assert_eq!(index.index_type, IndexType::Hash);
+        Ok(())
+    }
+
+    #[test]
+    fn test_table_stats() -> ResultTest<()> {
+        let (datastore, mut tx, table_id) = setup_table()?;
+        let index_def = IndexDef::new("age_idx".into(), table_id.0, 2, false).with_index_type(IndexType::Hash);
+        datastore.create_index_mut_tx(&mut tx, index_def)?;
+        datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, "Foo", 18))?;
+        datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, "Bar", 20))?;
+        datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, "Baz", 18))?;
+        datastore.commit_mut_tx(tx)?;
+
+        let stats = |tx: &MutTxId| datastore.table_stats_mut_tx(tx, table_id).unwrap();
+        let tx = datastore.begin_mut_tx();
+        let committed = stats(&tx);
+        assert_eq!(committed.row_count, 3);
+        assert_eq!(committed.distinct_values[&NonEmpty::new(0)], 3);
+        assert_eq!(committed.distinct_values[&NonEmpty::new(2)], 2);
+        datastore.rollback_mut_tx(tx);
+
+        // The rows inserted and deleted by the transaction are counted.
+        let mut tx = datastore.begin_mut_tx();
+        datastore.delete_by_rel_mut_tx(&mut tx, table_id, [u32_str_u32(2, "Bar", 20)])?;
+        datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, "Qux", 18))?;
+        datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, "Quux", 18))?;
+        assert_eq!(stats(&tx).row_count, 4);
+        datastore.commit_mut_tx(tx)?;
+
+        let tx = datastore.begin_mut_tx();
+        let committed = stats(&tx);
+        assert_eq!(committed.row_count, 4);
+        assert_eq!(committed.distinct_values[&NonEmpty::new(0)], 4);
+        assert_eq!(committed.distinct_values[&NonEmpty::new(2)], 1);
+        Ok(())
+    }
+
     #[test]
     fn test_update_reinsert() -> ResultTest<()> {
         let (datastore, mut tx, table_id) = setup_table()?;
@@ -2979,6 +3491,61 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_read_tx_snapshot() -> ResultTest<()> {
+        let (datastore, mut tx, table_id) = setup_table()?;
+        datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, "Foo", 18))?;
+        datastore.commit_mut_tx(tx)?;
+
+        // A read-only tx doesn't wait for the mutable tx holding the lock,
+        // and only sees the committed rows.
+        let mut tx = datastore.begin_mut_tx();
+        datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, "Bar", 18))?;
+        let read_tx = datastore.begin_tx();
+        #[rustfmt::skip]
+        assert_eq!(all_rows(&datastore, &read_tx, table_id), vec![u32_str_u32(1, "Foo", 18)]);
+
+        // Nor does it see the changes committed after it began.
+        datastore.commit_mut_tx(tx)?;
+        #[rustfmt::skip]
+        assert_eq!(all_rows(&datastore, &read_tx, table_id), vec![u32_str_u32(1, "Foo", 18)]);
+        let rows = datastore
+            .iter_by_col_eq_tx(
+                &read_tx,
+                table_id,
+                ColId(1).into(),
+                AlgebraicValue::String("Bar".into()),
+            )?
+            .count();
+        assert_eq!(rows, 0);
+        datastore.release_tx(read_tx);
+
+        let read_tx = datastore.begin_tx();
+        #[rustfmt::skip]
+        assert_eq!(all_rows(&datastore, &read_tx, table_id), vec![u32_str_u32(1, "Foo", 18), u32_str_u32(2, "Bar", 18)]);
+        datastore.release_tx(read_tx);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_tx_discards_writes() -> ResultTest<()> {
+        let (datastore, tx, table_id) = setup_table()?;
+        datastore.commit_mut_tx(tx)?;
+
+        let mut read_tx = datastore.begin_tx();
+        assert!(read_tx.is_read_only());
+        datastore
+            .create_table_mut_tx(&mut read_tx, basic_table_schema())
+            .unwrap_err();
+        assert!(datastore.commit_mut_tx(read_tx).is_err());
+
+        let tx = datastore.begin_mut_tx();
+        assert!(!tx.is_read_only());
+        #[rustfmt::skip]
+        assert_eq!(all_rows(&datastore, &tx, table_id), vec![]);
+        Ok(())
+    }
+
     // TODO: Add the following tests
     // - Create index with unique constraint and immediately insert a row that violates the constraint before committing.
     // - Create a tx that inserts 2000 rows with an autoinc column
@@ -1,26 +1,29 @@
 use super::{
-    btree_index::{BTreeIndex, BTreeIndexRangeIter},
+    index::{TableIndex, TableIndexIter},
     RowId,
 };
 use crate::db::datastore::traits::{ColId, TableSchema};
+use im::OrdMap;
 use nonempty::NonEmpty;
 use spacetimedb_sats::{AlgebraicValue, ProductType, ProductValue};
-use std::{
-    collections::{BTreeMap, HashMap},
-    ops::RangeBounds,
-};
+use std::{collections::HashMap, ops::RangeBounds};
 
+/// The rows of a table and its indexes.
+///
+/// The rows are kept in a persistent [OrdMap], so a clone shares them with the original
+/// and a snapshot of the committed state doesn't copy the rows.
+#[derive(Clone)]
 pub(crate) struct Table {
     pub(crate) row_type: ProductType,
     pub(crate) schema: TableSchema,
-    pub(crate) indexes: HashMap<NonEmpty<ColId>, BTreeIndex>,
-    pub(crate) rows: BTreeMap<RowId, ProductValue>,
+    pub(crate) indexes: HashMap<NonEmpty<ColId>, TableIndex>,
+    pub(crate) rows: OrdMap<RowId, ProductValue>,
 }
 
 impl Table {
-    pub(crate) fn insert_index(&mut self, mut index: BTreeIndex) {
+    pub(crate) fn insert_index(&mut self, mut index: TableIndex) {
         index.build_from_rows(self.scan_rows()).unwrap();
-        self.indexes.insert(index.cols.clone().map(ColId), index);
+        self.indexes.insert(index.cols().clone().map(ColId), index);
     }
 
     pub(crate) fn insert(&mut self, row_id: RowId, row: ProductValue) {
@@ -56,8 +59,8 @@ impl Table {
         self.rows.values()
     }
 
-    /// When there's an index for `cols`,
-    /// returns an iterator over the [`BTreeIndex`] that yields all the `RowId`s
+    /// When there's an index for `cols` that can answer `range`,
+    /// returns an iterator over the [`TableIndex`] that yields all the `RowId`s
     /// that match the specified `range` in the indexed column.
     ///
     /// Matching is defined by `Ord for AlgebraicValue`.
@@ -65,7 +68,7 @@ impl Table {
         &self,
         cols: &NonEmpty<ColId>,
         range: &impl RangeBounds<AlgebraicValue>,
-    ) -> Option<BTreeIndexRangeIter<'_>> {
-        self.indexes.get(cols).map(|index| index.seek(range))
+    ) -> Option<TableIndexIter<'_>> {
+        self.indexes.get(cols).and_then(|index| index.seek(range))
     }
 }
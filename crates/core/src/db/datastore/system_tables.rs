@@ -5,7 +5,10 @@ use core::fmt;
 use nonempty::NonEmpty;
 use once_cell::sync::Lazy;
 use spacetimedb_lib::auth::{StAccess, StTableType};
-use spacetimedb_lib::{ColumnIndexAttribute, Hash};
+use spacetimedb_lib::buffer::DecodeError;
+use spacetimedb_lib::data_key::ToDataKey;
+use spacetimedb_lib::schedule::{MisfirePolicy, ParseRepeatError, Repeat};
+use spacetimedb_lib::{ColumnIndexAttribute, DataKey, Hash, IndexType};
 use spacetimedb_sats::{
     impl_deserialize, impl_serialize, product, product_value::InvalidFieldError, AlgebraicType, AlgebraicValue,
     ArrayValue, ProductType, ProductValue,
@@ -24,6 +27,14 @@ pub(crate) const ST_CONSTRAINTS_ID: TableId = TableId(4);
 /// The static ID of the table that defines the stdb module associated with
 /// the database
 pub(crate) const ST_MODULE_ID: TableId = TableId(5);
+/// The static ID of the table that defines the reducers scheduled to run later
+///
+/// The system tables are bootstrapped rather than logged, so the databases created
+/// before a system table was added have user tables, indexes and sequences
+/// with the ids following those of the [original system tables](SystemTables::original_tables).
+/// The system tables added since then take their ids from the top of each range instead,
+/// which the sequences generating ids would only reach after four billion of them.
+pub(crate) const ST_SCHEDULED_ID: TableId = TableId(u32::MAX);
 
 pub(crate) const ST_TABLES_NAME: &str = "st_table";
 pub(crate) const ST_COLUMNS_NAME: &str = "st_columns";
@@ -31,11 +42,13 @@ pub(crate) const ST_SEQUENCES_NAME: &str = "st_sequence";
 pub(crate) const ST_INDEXES_NAME: &str = "st_indexes";
 pub(crate) const ST_CONSTRAINTS_NAME: &str = "st_constraints";
 pub(crate) const ST_MODULE_NAME: &str = "st_module";
+pub(crate) const ST_SCHEDULED_NAME: &str = "st_scheduled";
 
 pub(crate) const TABLE_ID_SEQUENCE_ID: SequenceId = SequenceId(0);
 pub(crate) const SEQUENCE_ID_SEQUENCE_ID: SequenceId = SequenceId(1);
 pub(crate) const INDEX_ID_SEQUENCE_ID: SequenceId = SequenceId(2);
 pub(crate) const CONSTRAINT_ID_SEQUENCE_ID: SequenceId = SequenceId(3);
+pub(crate) const SCHEDULED_ID_SEQUENCE_ID: SequenceId = SequenceId(u32::MAX);
 
 pub(crate) const ST_TABLE_ID_INDEX_ID: u32 = 0;
 pub(crate) const ST_TABLE_NAME_INDEX_ID: u32 = 3;
@@ -43,10 +56,29 @@ pub(crate) const ST_INDEX_ID_INDEX_ID: u32 = 1;
 pub(crate) const ST_SEQUENCE_ID_INDEX_ID: u32 = 2;
 pub(crate) const ST_CONSTRAINT_ID_INDEX_ID: u32 = 4;
 pub(crate) const ST_CONSTRAINT_ID_INDEX_HACK: u32 = 5;
+pub(crate) const ST_SCHEDULED_ID_INDEX_ID: u32 = u32::MAX;
 pub(crate) struct SystemTables {}
 
 impl SystemTables {
-    pub(crate) fn tables() -> [TableSchema; 6] {
+    pub(crate) fn tables() -> [TableSchema; 7] {
+        let [st_table, st_columns, st_sequences, st_indexes, st_constraints, st_module] = Self::original_tables();
+        [
+            st_table,
+            st_columns,
+            st_sequences,
+            st_indexes,
+            st_constraints,
+            st_module,
+            st_scheduled_schema(),
+        ]
+    }
+
+    /// The system tables which databases have had from the start.
+    ///
+    /// The `total_` counts, at which the sequences of ids start, only count these,
+    /// so that they stay the same for existing databases when a system table is added.
+    /// WARNING: Don't add tables here, see [ST_SCHEDULED_ID].
+    pub(crate) fn original_tables() -> [TableSchema; 6] {
         [
             st_table_schema(),
             st_columns_schema(),
@@ -58,29 +90,32 @@ impl SystemTables {
     }
 
     pub(crate) fn total_tables() -> usize {
-        Self::tables().len()
+        Self::original_tables().len()
     }
 
     pub(crate) fn total_indexes() -> usize {
-        Self::tables().iter().flat_map(|x| x.indexes.iter()).count()
+        Self::original_tables().iter().flat_map(|x| x.indexes.iter()).count()
     }
 
     pub(crate) fn total_constraints_indexes() -> usize {
-        Self::tables()
+        Self::original_tables()
             .iter()
             .flat_map(|x| x.constraints.iter().filter(|x| x.kind != ColumnIndexAttribute::UNSET))
             .count()
     }
 
     pub(crate) fn total_sequences() -> usize {
-        Self::tables()
+        Self::original_tables()
             .iter()
             .flat_map(|x| x.columns.iter().filter(|x| x.is_autoinc))
             .count()
     }
 
     pub(crate) fn total_constraints() -> usize {
-        Self::tables().iter().flat_map(|x| x.constraints.iter()).count()
+        Self::original_tables()
+            .iter()
+            .flat_map(|x| x.constraints.iter())
+            .count()
     }
 }
 
@@ -136,6 +171,7 @@ pub enum StIndexFields {
     Cols = 2,
     IndexName = 3,
     IsUnique = 4,
+    IndexType = 5,
 }
 
 impl StIndexFields {
@@ -147,6 +183,7 @@ impl StIndexFields {
             StIndexFields::Cols => "cols",
             StIndexFields::IndexName => "index_name",
             StIndexFields::IsUnique => "is_unique",
+            StIndexFields::IndexType => "index_type",
         }
     }
 }
@@ -223,6 +260,31 @@ impl StModuleFields {
     }
 }
 
+// WARNING: In order to keep a stable schema, don't change the discriminant of the fields
+#[derive(Debug)]
+pub enum StScheduledFields {
+    ScheduledId = 0,
+    Reducer = 1,
+    Args = 2,
+    ScheduledAt = 3,
+    Repeat = 4,
+    Misfire = 5,
+}
+
+impl StScheduledFields {
+    pub fn name(&self) -> &'static str {
+        // WARNING: Don't change the name of the fields
+        match self {
+            Self::ScheduledId => "scheduled_id",
+            Self::Reducer => "reducer",
+            Self::Args => "args",
+            Self::ScheduledAt => "scheduled_at",
+            Self::Repeat => "repeat",
+            Self::Misfire => "misfire",
+        }
+    }
+}
+
 /// System Table [ST_TABLES_NAME]
 ///
 /// | table_id: u32 | table_name: String | table_type: String | table_access: String |
@@ -239,6 +301,7 @@ pub fn st_table_schema() -> TableSchema {
                 cols: NonEmpty::new(StTableFields::TableId as u32),
                 index_name: "table_id_idx".into(),
                 is_unique: true,
+                index_type: IndexType::BTree,
             },
             IndexSchema {
                 index_id: ST_TABLE_NAME_INDEX_ID,
@@ -246,6 +309,7 @@ pub fn st_table_schema() -> TableSchema {
                 cols: NonEmpty::new(StTableFields::TableName as u32),
                 index_name: "table_name_idx".into(),
                 is_unique: true,
+                index_type: IndexType::BTree,
             },
         ],
         columns: vec![
@@ -353,9 +417,9 @@ pub static ST_COLUMNS_ROW_TYPE: Lazy<ProductType> =
 
 /// System Table [ST_INDEXES]
 ///
-/// | index_id: u32 | table_id: u32 | cols: NonEmpty<u32> | index_name: String | is_unique: bool      |
-/// |---------------|---------------|---------------------|--------------------|----------------------|
-/// | 1             | 1             | [1]                 | "ix_sample"        | 0                    |
+/// | index_id: u32 | table_id: u32 | cols: NonEmpty<u32> | index_name: String | is_unique: bool      | index_type: String |
+/// |---------------|---------------|---------------------|--------------------|----------------------|--------------------|
+/// | 1             | 1             | [1]                 | "ix_sample"        | 0                    | "btree"            |
 pub fn st_indexes_schema() -> TableSchema {
     TableSchema {
         table_id: ST_INDEXES_ID.0,
@@ -367,6 +431,7 @@ pub fn st_indexes_schema() -> TableSchema {
             cols: NonEmpty::new(0),
             index_name: "index_id_idx".into(),
             is_unique: true,
+            index_type: IndexType::BTree,
         }],
         columns: vec![
             ColumnSchema {
@@ -404,6 +469,13 @@ pub fn st_indexes_schema() -> TableSchema {
                 col_type: AlgebraicType::Bool,
                 is_autoinc: false,
             },
+            ColumnSchema {
+                table_id: ST_INDEXES_ID.0,
+                col_id: 5,
+                col_name: "index_type".into(),
+                col_type: AlgebraicType::String,
+                is_autoinc: false,
+            },
         ],
         constraints: vec![],
         table_type: StTableType::System,
@@ -414,6 +486,39 @@ pub fn st_indexes_schema() -> TableSchema {
 pub static ST_INDEX_ROW_TYPE: Lazy<ProductType> =
     Lazy::new(|| ProductType::from_iter(st_indexes_schema().columns.iter().map(|c| c.col_type.clone())));
 
+/// The row type of [ST_INDEXES_NAME] before its `index_type` column,
+/// in which the rows of the indexes created before it are found in the message log.
+static ST_INDEX_LEGACY_ROW_TYPE: Lazy<ProductType> = Lazy::new(|| {
+    ProductType::from_iter(
+        st_indexes_schema().columns[..StIndexFields::IndexType as usize]
+            .iter()
+            .map(|c| c.col_type.clone()),
+    )
+});
+
+/// Decodes a row of [ST_INDEXES_NAME] from the message log.
+///
+/// A row written before the `index_type` column was added is a btree index.
+pub(crate) fn decode_st_index_row(bytes: &[u8]) -> Result<ProductValue, DecodeError> {
+    ProductValue::decode(&ST_INDEX_ROW_TYPE, &mut &bytes[..]).or_else(|e| {
+        let reader = &mut &bytes[..];
+        let mut row = ProductValue::decode(&ST_INDEX_LEGACY_ROW_TYPE, reader)?;
+        if !reader.is_empty() {
+            return Err(e);
+        }
+        row.elements
+            .push(AlgebraicValue::String(IndexType::BTree.as_str().into()));
+        Ok(row)
+    })
+}
+
+/// The [DataKey] under which `row` of [ST_INDEXES_NAME] was written to the message log
+/// before the `index_type` column was added.
+pub(crate) fn st_index_legacy_data_key(row: &ProductValue) -> DataKey {
+    let legacy = ProductValue::from_iter(row.elements[..StIndexFields::IndexType as usize].iter().cloned());
+    legacy.to_data_key()
+}
+
 /// System Table [ST_SEQUENCES]
 ///
 /// | sequence_id | sequence_name     | increment | start | min_value | max_value | table_id | col_id | allocated |
@@ -430,6 +535,7 @@ pub(crate) fn st_sequences_schema() -> TableSchema {
             cols: NonEmpty::new(0),
             index_name: "sequences_id_idx".into(),
             is_unique: true,
+            index_type: IndexType::BTree,
         }],
         columns: vec![
             ColumnSchema {
@@ -521,6 +627,7 @@ pub(crate) fn st_constraints_schema() -> TableSchema {
             cols: NonEmpty::new(0),
             index_name: "constraint_id_idx".into(),
             is_unique: true,
+            index_type: IndexType::BTree,
         }],
         columns: vec![
             ColumnSchema {
@@ -617,6 +724,86 @@ pub(crate) fn st_module_schema() -> TableSchema {
 pub static ST_MODULE_ROW_TYPE: Lazy<ProductType> =
     Lazy::new(|| ProductType::from_iter(st_module_schema().columns.iter().map(|c| c.col_type.clone())));
 
+/// System table [ST_SCHEDULED_NAME]
+///
+/// This table holds the reducers scheduled to run later, which are yet to run:
+///
+/// * `scheduled_id` identifies the schedule, e.g. to cancel it.
+/// * `reducer` is the name of the reducer.
+/// * `args` are the BSATN-encoded arguments the reducer is called with.
+/// * `scheduled_at` is the time, in microseconds since the Unix epoch, at which it runs next.
+/// * `repeat` is how it repeats after running, as a [Repeat], or empty if it runs once.
+/// * `misfire` is the [MisfirePolicy] of the runs missed while the host was down.
+///
+/// | scheduled_id | reducer      | args         | scheduled_at     | repeat            | misfire     |
+/// |--------------|--------------|--------------|------------------|-------------------|-------------|
+/// | 4097         | "send_email" | [5, 0, ...]  | 1697529600000000 | ""                | "fire_once" |
+/// | 4098         | "cleanup"    | []           | 1697587200000000 | "cron 0 4 * * *"  | "skip"      |
+pub(crate) fn st_scheduled_schema() -> TableSchema {
+    TableSchema {
+        table_id: ST_SCHEDULED_ID.0,
+        table_name: ST_SCHEDULED_NAME.into(),
+        indexes: vec![IndexSchema {
+            index_id: ST_SCHEDULED_ID_INDEX_ID,
+            table_id: ST_SCHEDULED_ID.0,
+            cols: NonEmpty::new(StScheduledFields::ScheduledId as u32),
+            index_name: "scheduled_id_idx".into(),
+            is_unique: true,
+            index_type: IndexType::BTree,
+        }],
+        columns: vec![
+            ColumnSchema {
+                table_id: ST_SCHEDULED_ID.0,
+                col_id: StScheduledFields::ScheduledId as u32,
+                col_name: StScheduledFields::ScheduledId.name().into(),
+                col_type: AlgebraicType::U64,
+                is_autoinc: true,
+            },
+            ColumnSchema {
+                table_id: ST_SCHEDULED_ID.0,
+                col_id: StScheduledFields::Reducer as u32,
+                col_name: StScheduledFields::Reducer.name().into(),
+                col_type: AlgebraicType::String,
+                is_autoinc: false,
+            },
+            ColumnSchema {
+                table_id: ST_SCHEDULED_ID.0,
+                col_id: StScheduledFields::Args as u32,
+                col_name: StScheduledFields::Args.name().into(),
+                col_type: AlgebraicType::bytes(),
+                is_autoinc: false,
+            },
+            ColumnSchema {
+                table_id: ST_SCHEDULED_ID.0,
+                col_id: StScheduledFields::ScheduledAt as u32,
+                col_name: StScheduledFields::ScheduledAt.name().into(),
+                col_type: AlgebraicType::U64,
+                is_autoinc: false,
+            },
+            ColumnSchema {
+                table_id: ST_SCHEDULED_ID.0,
+                col_id: StScheduledFields::Repeat as u32,
+                col_name: StScheduledFields::Repeat.name().into(),
+                col_type: AlgebraicType::String,
+                is_autoinc: false,
+            },
+            ColumnSchema {
+                table_id: ST_SCHEDULED_ID.0,
+                col_id: StScheduledFields::Misfire as u32,
+                col_name: StScheduledFields::Misfire.name().into(),
+                col_type: AlgebraicType::String,
+                is_autoinc: false,
+            },
+        ],
+        constraints: vec![],
+        table_type: StTableType::System,
+        table_access: StAccess::Public,
+    }
+}
+
+pub static ST_SCHEDULED_ROW_TYPE: Lazy<ProductType> =
+    Lazy::new(|| ProductType::from_iter(st_scheduled_schema().columns.iter().map(|c| c.col_type.clone())));
+
 pub(crate) fn table_name_is_system(table_name: &str) -> bool {
     table_name.starts_with("st_")
 }
@@ -751,6 +938,7 @@ pub struct StIndexRow<Name: AsRef<str>> {
     pub(crate) cols: NonEmpty<u32>,
     pub(crate) index_name: Name,
     pub(crate) is_unique: bool,
+    pub(crate) index_type: IndexType,
 }
 
 impl StIndexRow<&str> {
@@ -761,6 +949,7 @@ impl StIndexRow<&str> {
             cols: self.cols.clone(),
             index_name: self.index_name.to_owned(),
             is_unique: self.is_unique,
+            index_type: self.index_type,
         }
     }
 }
@@ -783,12 +972,22 @@ impl<'a> TryFrom<&'a ProductValue> for StIndexRow<&'a str> {
 
         let index_name = row.field_as_str(StIndexFields::IndexName as usize, None)?;
         let is_unique = row.field_as_bool(StIndexFields::IsUnique as usize, None)?;
+        let index_type = row
+            .field_as_str(StIndexFields::IndexType as usize, None)?
+            .try_into()
+            .map_err(|x: &str| TableError::DecodeField {
+                table: ST_INDEXES_NAME.into(),
+                field: StIndexFields::IndexType.name().into(),
+                expect: format!("`{}` or `{}`", IndexType::BTree.as_str(), IndexType::Hash.as_str()),
+                found: x.to_string(),
+            })?;
         Ok(StIndexRow {
             index_id,
             table_id,
             cols,
             index_name,
             is_unique,
+            index_type,
         })
     }
 }
@@ -800,7 +999,8 @@ impl<Name: AsRef<str>> From<&StIndexRow<Name>> for ProductValue {
             x.table_id,
             ArrayValue::from(x.cols.clone()),
             AlgebraicValue::String(x.index_name.as_ref().to_string()),
-            x.is_unique
+            x.is_unique,
+            AlgebraicValue::String(x.index_type.as_str().into())
         ]
     }
 }
@@ -1032,3 +1232,89 @@ impl From<&StModuleRow> for ProductValue {
         ]
     }
 }
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StScheduledRow {
+    pub(crate) scheduled_id: u64,
+    pub(crate) reducer: String,
+    pub(crate) args: Vec<u8>,
+    pub(crate) scheduled_at: u64,
+    pub(crate) repeat: Option<Repeat>,
+    pub(crate) misfire: MisfirePolicy,
+}
+
+impl TryFrom<&ProductValue> for StScheduledRow {
+    type Error = DBError;
+
+    fn try_from(row: &ProductValue) -> Result<Self, Self::Error> {
+        let scheduled_id = row.field_as_u64(
+            StScheduledFields::ScheduledId as usize,
+            Some(StScheduledFields::ScheduledId.name()),
+        )?;
+        let reducer = row
+            .field_as_str(
+                StScheduledFields::Reducer as usize,
+                Some(StScheduledFields::Reducer.name()),
+            )?
+            .to_owned();
+        let args = row
+            .field_as_bytes(StScheduledFields::Args as usize, Some(StScheduledFields::Args.name()))?
+            .to_owned();
+        let scheduled_at = row.field_as_u64(
+            StScheduledFields::ScheduledAt as usize,
+            Some(StScheduledFields::ScheduledAt.name()),
+        )?;
+        let repeat = row.field_as_str(
+            StScheduledFields::Repeat as usize,
+            Some(StScheduledFields::Repeat.name()),
+        )?;
+        let repeat = match repeat {
+            "" => None,
+            repeat => Some(repeat.parse().map_err(|e: ParseRepeatError| TableError::DecodeField {
+                table: ST_SCHEDULED_NAME.into(),
+                field: StScheduledFields::Repeat.name().into(),
+                expect: e.to_string(),
+                found: repeat.to_string(),
+            })?),
+        };
+        let misfire = row
+            .field_as_str(
+                StScheduledFields::Misfire as usize,
+                Some(StScheduledFields::Misfire.name()),
+            )?
+            .try_into()
+            .map_err(|x: &str| TableError::DecodeField {
+                table: ST_SCHEDULED_NAME.into(),
+                field: StScheduledFields::Misfire.name().into(),
+                expect: format!(
+                    "`{}`, `{}` or `{}`",
+                    MisfirePolicy::Skip.as_str(),
+                    MisfirePolicy::FireOnce.as_str(),
+                    MisfirePolicy::FireAll.as_str()
+                ),
+                found: x.to_string(),
+            })?;
+
+        Ok(Self {
+            scheduled_id,
+            reducer,
+            args,
+            scheduled_at,
+            repeat,
+            misfire,
+        })
+    }
+}
+
+impl From<&StScheduledRow> for ProductValue {
+    fn from(x: &StScheduledRow) -> Self {
+        product![
+            AlgebraicValue::U64(x.scheduled_id),
+            AlgebraicValue::String(x.reducer.clone()),
+            AlgebraicValue::Bytes(x.args.clone()),
+            AlgebraicValue::U64(x.scheduled_at),
+            AlgebraicValue::String(x.repeat.as_ref().map_or_else(String::new, |repeat| repeat.to_string())),
+            AlgebraicValue::String(x.misfire.as_str().into()),
+        ]
+    }
+}
@@ -4,11 +4,11 @@ use derive_more::From;
 use nonempty::NonEmpty;
 use spacetimedb_lib::auth::{StAccess, StTableType};
 use spacetimedb_lib::relation::{DbTable, FieldName, FieldOnly, Header, TableField};
-use spacetimedb_lib::{ColumnIndexAttribute, DataKey, Hash};
+use spacetimedb_lib::{ColumnIndexAttribute, DataKey, Hash, IndexType};
 use spacetimedb_sats::product_value::InvalidFieldError;
 use spacetimedb_sats::{AlgebraicType, AlgebraicValue, ProductType, ProductTypeElement, ProductValue};
 use spacetimedb_vm::expr::SourceExpr;
-use std::{borrow::Cow, ops::RangeBounds, sync::Arc};
+use std::{borrow::Cow, collections::HashMap, ops::RangeBounds, sync::Arc};
 
 use super::{system_tables::StTableRow, Result};
 
@@ -92,6 +92,7 @@ pub struct IndexSchema {
     pub(crate) index_name: String,
     pub(crate) is_unique: bool,
     pub(crate) cols: NonEmpty<u32>,
+    pub(crate) index_type: IndexType,
 }
 
 /// This type is just the [IndexSchema] without the autoinc fields
@@ -101,17 +102,24 @@ pub struct IndexDef {
     pub(crate) cols: NonEmpty<u32>,
     pub(crate) name: String,
     pub(crate) is_unique: bool,
+    pub(crate) index_type: IndexType,
 }
 
 impl IndexDef {
+    /// A btree index over `col_id`.
     pub fn new(name: String, table_id: u32, col_id: u32, is_unique: bool) -> Self {
         Self {
             cols: NonEmpty::new(col_id),
             name,
             is_unique,
             table_id,
+            index_type: IndexType::BTree,
         }
     }
+
+    pub fn with_index_type(self, index_type: IndexType) -> Self {
+        Self { index_type, ..self }
+    }
 }
 
 impl From<IndexSchema> for IndexDef {
@@ -121,6 +129,7 @@ impl From<IndexSchema> for IndexDef {
             cols: value.cols,
             name: value.index_name,
             is_unique: value.is_unique,
+            index_type: value.index_type,
         }
     }
 }
@@ -384,6 +393,16 @@ impl From<TableSchema> for TableDef {
     }
 }
 
+/// The statistics of the rows of a table, as seen by a transaction,
+/// used to estimate the cost of the plans of a query.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TableStats {
+    /// The number of rows in the table.
+    pub row_count: u64,
+    /// The number of distinct values of each index of the table, by its columns.
+    pub distinct_values: HashMap<NonEmpty<u32>, u64>,
+}
+
 /// Operations in a transaction are either Inserts or Deletes.
 /// Inserts report the byte objects they inserted, to be persisted
 /// later in an object store.
@@ -490,9 +509,16 @@ pub trait MutTxDatastore: TxDatastore + MutTx {
     fn schema_for_table_mut_tx<'tx>(&self, tx: &'tx Self::MutTxId, table_id: TableId) -> Result<Cow<'tx, TableSchema>>;
     fn drop_table_mut_tx(&self, tx: &mut Self::MutTxId, table_id: TableId) -> Result<()>;
     fn rename_table_mut_tx(&self, tx: &mut Self::MutTxId, table_id: TableId, new_name: &str) -> Result<()>;
+    fn alter_table_mut_tx(
+        &self,
+        tx: &mut Self::MutTxId,
+        table_id: TableId,
+        columns: Vec<ColumnDef>,
+    ) -> Result<Vec<ProductValue>>;
     fn table_id_exists(&self, tx: &Self::MutTxId, table_id: &TableId) -> bool;
     fn table_id_from_name_mut_tx(&self, tx: &Self::MutTxId, table_name: &str) -> Result<Option<TableId>>;
     fn table_name_from_id_mut_tx(&self, tx: &Self::MutTxId, table_id: TableId) -> Result<Option<String>>;
+    fn table_stats_mut_tx(&self, tx: &Self::MutTxId, table_id: TableId) -> Result<TableStats>;
     fn get_all_tables_mut_tx<'tx>(&self, tx: &'tx Self::MutTxId) -> super::Result<Vec<Cow<'tx, TableSchema>>> {
         let mut tables = Vec::new();
         let table_rows = self.iter_mut_tx(tx, TableId(ST_TABLES_ID))?.collect::<Vec<_>>();
@@ -0,0 +1,246 @@
+//! Decoding the writes of the [`Commit`](super::messages::commit::Commit)s in a
+//! [`MessageLog`](super::message_log::MessageLog) offline, against the schemas
+//! recorded in `st_table` and `st_columns` by the log itself.
+
+use std::collections::BTreeMap;
+
+use spacetimedb_lib::DataKey;
+use spacetimedb_sats::{ProductType, ProductTypeElement, ProductValue};
+
+use super::datastore::system_tables::{
+    decode_st_index_row, StColumnRow, StTableRow, SystemTables, ST_COLUMNS_ID, ST_INDEXES_ID, ST_TABLES_ID,
+};
+use super::datastore::traits::TableId;
+use super::messages::write::{Operation, Write};
+use super::ostorage::ObjectDB;
+use super::snapshot::Snapshot;
+use crate::error::DBError;
+
+/// The name and columns of a table, as known to a [`Catalog`].
+#[derive(Debug, Clone)]
+pub struct TableInfo {
+    pub table_name: String,
+    columns: BTreeMap<u32, ProductTypeElement>,
+}
+
+impl TableInfo {
+    /// The type of the rows of the table, with its columns in order.
+    pub fn row_type(&self) -> ProductType {
+        self.columns.values().cloned().collect::<Vec<_>>().into()
+    }
+}
+
+/// A write of a transaction, with its row decoded.
+#[derive(Debug, Clone)]
+pub struct RowWrite {
+    pub table_id: u32,
+    pub operation: Operation,
+    /// The row, unless it couldn't be decoded, e.g. as its table is unknown
+    /// or its large object is missing from the [`ObjectDB`].
+    pub row: Option<ProductValue>,
+}
+
+/// The schemas of the tables of a database, as of some commit in its log.
+///
+/// Feeding the writes of the commits in order to [`Catalog::decode_write`]
+/// keeps track of the tables created and altered along the way.
+#[derive(Debug, Clone)]
+pub struct Catalog {
+    tables: BTreeMap<TableId, TableInfo>,
+}
+
+impl Default for Catalog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Catalog {
+    /// The catalog of an empty database, which only has the system tables.
+    pub fn new() -> Self {
+        let tables = SystemTables::tables()
+            .into_iter()
+            .map(|schema| {
+                let columns = schema
+                    .columns
+                    .iter()
+                    .map(|col| (col.col_id, ProductTypeElement::from(col)))
+                    .collect();
+                let table = TableInfo {
+                    table_name: schema.table_name,
+                    columns,
+                };
+                (TableId(schema.table_id), table)
+            })
+            .collect();
+        Self { tables }
+    }
+
+    /// Adds the tables of the database as of the `snapshot`.
+    pub fn restore_snapshot(&mut self, snapshot: &Snapshot) -> Result<(), DBError> {
+        for table_id in [ST_TABLES_ID, ST_COLUMNS_ID] {
+            let row_type = self.tables[&table_id].row_type();
+            let rows = snapshot.tables.iter().filter(|table| table.table_id == table_id);
+            for row in rows.flat_map(|table| &table.rows) {
+                let row = ProductValue::decode(&row_type, &mut &row[..])?;
+                self.apply(table_id, Operation::Insert, &row)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn table(&self, table_id: u32) -> Option<&TableInfo> {
+        self.tables.get(&TableId(table_id))
+    }
+
+    /// Decodes the row of `write`, reading it from the `odb` unless it is inline,
+    /// then applies it to the catalog if it changes the schema of a table.
+    pub fn decode_write(&mut self, write: &Write, odb: Option<&dyn ObjectDB>) -> Result<RowWrite, DBError> {
+        let table_id = TableId(write.set_id);
+        let bytes = match write.data_key {
+            DataKey::Data(data) => Some(data.to_vec()),
+            DataKey::Hash(hash) => odb.and_then(|odb| odb.get(hash)).map(|bytes| bytes.to_vec()),
+        };
+        let row = match (self.tables.get(&table_id), bytes) {
+            (Some(_), Some(bytes)) if table_id == ST_INDEXES_ID => decode_st_index_row(&bytes).ok(),
+            (Some(table), Some(bytes)) => ProductValue::decode(&table.row_type(), &mut &bytes[..]).ok(),
+            _ => None,
+        };
+        if let Some(row) = &row {
+            self.apply(table_id, write.operation, row)?;
+        }
+
+        Ok(RowWrite {
+            table_id: write.set_id,
+            operation: write.operation,
+            row,
+        })
+    }
+
+    fn apply(&mut self, table_id: TableId, operation: Operation, row: &ProductValue) -> Result<(), DBError> {
+        match (table_id, operation) {
+            (ST_TABLES_ID, Operation::Insert) => {
+                let row = StTableRow::try_from(row)?;
+                self.tables
+                    .entry(TableId(row.table_id))
+                    .or_insert_with(|| TableInfo {
+                        table_name: String::new(),
+                        columns: BTreeMap::new(),
+                    })
+                    .table_name = row.table_name.to_owned();
+            }
+            (ST_COLUMNS_ID, Operation::Insert) => {
+                let row = StColumnRow::try_from(row)?;
+                if let Some(table) = self.tables.get_mut(&TableId(row.table_id)) {
+                    let column = ProductTypeElement::new_named(row.col_type, row.col_name);
+                    table.columns.insert(row.col_id, column);
+                }
+            }
+            // Only drop a column if it is still the one deleted,
+            // as the insert of its replacement may come first.
+            (ST_COLUMNS_ID, Operation::Delete) => {
+                let row = StColumnRow::try_from(row)?;
+                if let Some(table) = self.tables.get_mut(&TableId(row.table_id)) {
+                    let column = ProductTypeElement::new_named(row.col_type, row.col_name);
+                    if table.columns.get(&row.col_id) == Some(&column) {
+                        table.columns.remove(&row.col_id);
+                    }
+                }
+            }
+            // The tables dropped are kept, to decode the deletes of their rows.
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address::Address;
+    use crate::database_instance_context::DatabaseInstanceContext;
+    use crate::db::commit_log;
+    use crate::db::datastore::traits::TableDef;
+    use crate::db::message_log::{MessageLog, Segments};
+    use crate::db::ostorage::sled_object_db::SledObjectDB;
+    use crate::db::relational_db::RelationalDB;
+    use crate::db::FsyncPolicy;
+    use spacetimedb_lib::error::ResultTest;
+    use spacetimedb_sats::{product, AlgebraicType, AlgebraicValue};
+    use std::sync::{Arc, Mutex};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_decode_writes() -> ResultTest<()> {
+        // Laid out as by the host, with the large rows in a sled odb.
+        let tmp_dir = TempDir::new("stdb_test")?;
+        let mlog = MessageLog::open(tmp_dir.path().join("mlog"))?;
+        let odb = DatabaseInstanceContext::make_default_ostorage(tmp_dir.path().join("odb"));
+        let stdb = RelationalDB::open(
+            tmp_dir.path(),
+            Some(Arc::new(Mutex::new(mlog))),
+            Arc::new(Mutex::new(odb)),
+            Address::zero(),
+            FsyncPolicy::EveryTx,
+            None,
+            false,
+        )?;
+        let long_name = "Bob".repeat(20);
+
+        let mut tx = stdb.begin_tx();
+        let mut schema = TableDef::from(ProductType::from([("name", AlgebraicType::String)]));
+        schema.table_name = "Person".to_string();
+        let table_id = stdb.create_table(&mut tx, schema)?;
+        stdb.insert(&mut tx, table_id, product![AlgebraicValue::String("Alice".into())])?;
+        stdb.commit_tx(tx)?;
+
+        let mut tx = stdb.begin_tx();
+        let alice = product![AlgebraicValue::String("Alice".into())];
+        stdb.delete_by_rel(&mut tx, table_id, vec![alice.clone()])?;
+        stdb.commit_tx(tx)?;
+
+        let mut tx = stdb.begin_tx();
+        stdb.insert(&mut tx, table_id, product![AlgebraicValue::String(long_name.clone())])?;
+        stdb.commit_tx(tx)?;
+
+        // The rows larger than a `DataKey` are in the object db,
+        // which is read through a copy while the database is still running.
+        let scratch = TempDir::new("stdb_test")?;
+        let odb = SledObjectDB::open_copy(tmp_dir.path().join("odb"), scratch.path().join("odb"))?;
+        let mut catalog = Catalog::new();
+        let mut writes = Vec::new();
+        for commit in commit_log::Iter::from(Segments::read_dir(tmp_dir.path().join("mlog"))?) {
+            for tx in commit?.transactions {
+                for write in &tx.writes {
+                    writes.push(catalog.decode_write(write, Some(&odb))?);
+                }
+            }
+        }
+
+        let table = catalog.table(table_id).unwrap();
+        assert_eq!(table.table_name, "Person");
+        assert_eq!(table.row_type(), ProductType::from([("name", AlgebraicType::String)]));
+
+        let person_writes = writes
+            .iter()
+            .filter(|write| write.table_id == table_id)
+            .map(|write| (write.operation.to_u8(), write.row.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            person_writes,
+            [
+                (Operation::Insert.to_u8(), Some(alice.clone())),
+                (Operation::Delete.to_u8(), Some(alice)),
+                (
+                    Operation::Insert.to_u8(),
+                    Some(product![AlgebraicValue::String(long_name)])
+                )
+            ]
+        );
+        // The schema changes are decoded too.
+        assert!(writes
+            .iter()
+            .any(|write| write.table_id == ST_TABLES_ID.0 && write.row.is_some()));
+        Ok(())
+    }
+}
@@ -1,19 +1,25 @@
 use std::{
     fs::{self, File},
-    io::{self, BufReader, BufWriter, Read, Write},
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
     path::{Path, PathBuf},
 };
 
-#[cfg(target_family = "unix")]
-use std::os::unix::fs::FileExt;
-
 use anyhow::{anyhow, Context};
 
 use crate::error::DBError;
-#[cfg(target_family = "windows")]
-use std::os::windows::fs::FileExt;
 
-const HEADER_SIZE: usize = 4;
+/// The header of a segment written with checksums, followed by its version.
+const SEGMENT_MAGIC: &[u8; 7] = b"STDBLOG";
+const SEGMENT_VERSION: u8 = 1;
+pub(crate) const SEGMENT_HEADER_SIZE: usize = SEGMENT_MAGIC.len() + 1;
+
+// message: <message_len(4)><crc32(4)><message(message_len)>
+//
+// The CRC covers the length and the message.
+// The segments written before checksums were introduced lack the segment header,
+// and their messages the CRC.
+pub(crate) const HEADER_SIZE: usize = 8;
+const LEGACY_HEADER_SIZE: usize = 4;
 
 /// Options for opening a [`MessageLog`], similar to [`fs::OpenOptions`].
 #[derive(Clone, Copy, Debug)]
@@ -37,73 +43,119 @@ impl OpenOptions {
         let root = path.as_ref();
         fs::create_dir_all(root).with_context(|| format!("could not create root directory: {}", root.display()))?;
 
-        let mut segments = Vec::new();
-        let mut total_size = 0;
-        for file in fs::read_dir(root).with_context(|| format!("unable to read root directory: {}", root.display()))? {
-            let dir_entry = file?;
-            let path = dir_entry.path();
-            if let Some(ext) = path.extension() {
-                if ext != "log" {
-                    continue;
-                }
-                let file_stem = path
-                    .file_stem()
-                    .map(|os| os.to_string_lossy())
-                    .ok_or_else(|| anyhow!("unexpected .log file: {}", path.display()))?;
-                let offset = file_stem
-                    .parse::<u64>()
-                    .with_context(|| format!("could not parse log offset from: {}", path.display()))?;
-                let size = dir_entry.metadata()?.len();
-
-                total_size += size;
-                segments.push(Segment {
-                    min_offset: offset,
-                    size,
-                });
-            }
-        }
-
-        segments.sort_unstable_by_key(|s| s.min_offset);
-
+        let mut segments = read_segments(root)?;
         if segments.is_empty() {
             segments.push(Segment { min_offset: 0, size: 0 });
         }
+        let mut total_size = segments.iter().map(|s| s.size).sum();
 
-        let last_segment = segments.last().unwrap();
+        let last_segment = *segments.last().unwrap();
         let last_segment_path = root.join(last_segment.name() + ".log");
-        let file = fs::OpenOptions::new()
+        let mut file = fs::OpenOptions::new()
             .read(true)
             .append(true)
             .create(true)
             .open(&last_segment_path)?;
+        if last_segment.size == 0 {
+            write_segment_header(&mut file)?;
+            segments.last_mut().unwrap().size = SEGMENT_HEADER_SIZE as u64;
+            total_size += SEGMENT_HEADER_SIZE as u64;
+        }
 
+        // Find the end of the last valid message, dropping a torn write after it.
+        let mut messages = IterSegment::new(File::open(&last_segment_path)?)?;
         let mut max_offset = last_segment.min_offset;
-        let mut cursor: u64 = 0;
-        while cursor < last_segment.size {
-            let mut buf = [0; HEADER_SIZE];
-            #[cfg(target_family = "windows")]
-            file.seek_read(&mut buf, cursor)?;
-            #[cfg(target_family = "unix")]
-            file.read_exact_at(&mut buf, cursor)?;
-            let message_len = u32::from_le_bytes(buf);
-
-            max_offset += 1;
-            cursor += HEADER_SIZE as u64 + message_len as u64;
+        while let Some(message) = messages.next() {
+            match message {
+                Ok(_) => max_offset += 1,
+                // A bad checksum before the last message is not a torn write.
+                Err(e) if e.kind() == io::ErrorKind::InvalidData && messages.end < messages.size => {
+                    return Err(anyhow!(
+                        "{e} at byte {} of segment {}, run `spacetime log verify` on {}",
+                        messages.start,
+                        last_segment_path.display(),
+                        root.display()
+                    )
+                    .into());
+                }
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let end = messages.start;
+        let checksummed = messages.checksummed;
+        let open_segment = segments.last_mut().unwrap();
+        if end < open_segment.size {
+            log::warn!(
+                "Truncating torn write of {} bytes at the end of segment {}",
+                open_segment.size - end,
+                last_segment_path.display()
+            );
+            file.set_len(end)?;
+            file.sync_all()?;
+            total_size -= open_segment.size - end;
+            open_segment.size = end;
         }
 
-        let file = BufWriter::new(file);
-
-        log::debug!("Initialized with offset {}", max_offset);
-
-        Ok(MessageLog {
+        let mut mlog = MessageLog {
             root: root.to_owned(),
             options: *self,
             segments,
             total_size,
-            open_segment_file: file,
+            open_segment_file: BufWriter::new(file),
             open_segment_max_offset: max_offset,
-        })
+        };
+        // Only append messages with checksums, to a new segment if need be.
+        if !checksummed {
+            mlog.start_segment()?;
+        }
+
+        log::debug!("Initialized with offset {}", max_offset);
+
+        Ok(mlog)
+    }
+}
+
+/// Lists the segments of the message log at `root`, in ascending order of their offset.
+fn read_segments(root: &Path) -> Result<Vec<Segment>, DBError> {
+    let mut segments = Vec::new();
+    for file in fs::read_dir(root).with_context(|| format!("unable to read root directory: {}", root.display()))? {
+        let dir_entry = file?;
+        let path = dir_entry.path();
+        if let Some(ext) = path.extension() {
+            if ext != "log" {
+                continue;
+            }
+            let file_stem = path
+                .file_stem()
+                .map(|os| os.to_string_lossy())
+                .ok_or_else(|| anyhow!("unexpected .log file: {}", path.display()))?;
+            let offset = file_stem
+                .parse::<u64>()
+                .with_context(|| format!("could not parse log offset from: {}", path.display()))?;
+            let size = dir_entry.metadata()?.len();
+
+            segments.push(Segment {
+                min_offset: offset,
+                size,
+            });
+        }
     }
+
+    segments.sort_unstable_by_key(|s| s.min_offset);
+    Ok(segments)
+}
+
+fn write_segment_header(file: &mut File) -> io::Result<()> {
+    file.write_all(SEGMENT_MAGIC)?;
+    file.write_all(&[SEGMENT_VERSION])
+}
+
+fn checksum(message_len: [u8; 4], message: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&message_len);
+    hasher.update(message);
+    hasher.finalize()
 }
 
 impl Default for OpenOptions {
@@ -174,25 +226,13 @@ impl MessageLog {
 
         let end_size = self.open_segment().size + size as u64;
         if end_size > self.options.max_segment_size {
-            self.flush()?;
-            self.segments.push(Segment {
-                min_offset: self.open_segment_max_offset + 1,
-                size: 0,
-            });
-
-            let last_segment = self.segments.last().unwrap();
-            let last_segment_path = self.root.join(last_segment.name() + ".log");
-
-            let file = fs::OpenOptions::new()
-                .append(true)
-                .create_new(true)
-                .open(last_segment_path)?;
-            let file = BufWriter::new(file);
-
-            self.open_segment_file = file;
+            self.start_segment()?;
         }
 
-        self.open_segment_file.write_all(&mess_size.to_le_bytes())?;
+        let message_len = mess_size.to_le_bytes();
+        self.open_segment_file.write_all(&message_len)?;
+        self.open_segment_file
+            .write_all(&checksum(message_len, message).to_le_bytes())?;
         self.open_segment_file.write_all(message)?;
 
         self.open_segment_mut().size += size as u64;
@@ -202,6 +242,24 @@ impl MessageLog {
         Ok(())
     }
 
+    /// Closes the open segment and starts a new one, after the last message.
+    fn start_segment(&mut self) -> Result<(), DBError> {
+        self.flush()?;
+        let segment = Segment {
+            min_offset: self.open_segment_max_offset + 1,
+            size: SEGMENT_HEADER_SIZE as u64,
+        };
+        let path = self.root.join(segment.name() + ".log");
+
+        let mut file = fs::OpenOptions::new().append(true).create_new(true).open(path)?;
+        write_segment_header(&mut file)?;
+
+        self.segments.push(segment);
+        self.total_size += segment.size;
+        self.open_segment_file = BufWriter::new(file);
+        Ok(())
+    }
+
     // NOTE: Flushing a `File` does nothing (just returns Ok(())), but flushing a BufWriter will
     // write the current buffer to the `File` by calling write. All `File` writes are atomic
     // so if you want to do an atomic action, make sure it all fits within the BufWriter buffer.
@@ -275,11 +333,8 @@ impl MessageLog {
         }
 
         let root = self.get_root();
-        let pos = self
-            .segments
-            .iter()
-            .rposition(|s| s.min_offset <= offset)
-            .expect("a segment with offset 0 must exist");
+        // The segments before `offset` may have been removed by [`MessageLog::remove_segments_before`].
+        let pos = self.segments.iter().rposition(|s| s.min_offset <= offset).unwrap_or(0);
 
         Segments {
             root,
@@ -287,6 +342,25 @@ impl MessageLog {
         }
     }
 
+    /// Removes the segments preceding the segment starting at `offset`,
+    /// typically because a snapshot covers all of their messages.
+    ///
+    /// The open segment is never removed.
+    /// Returns the number of segments removed.
+    #[tracing::instrument(skip(self))]
+    pub fn remove_segments_before(&mut self, offset: u64) -> Result<usize, DBError> {
+        let count = self.segments[..self.segments.len() - 1]
+            .iter()
+            .take_while(|s| s.min_offset < offset)
+            .count();
+        for segment in self.segments.drain(..count) {
+            let path = self.root.join(segment.name() + ".log");
+            fs::remove_file(&path).with_context(|| format!("could not remove segment: {}", path.display()))?;
+            self.total_size -= segment.size;
+        }
+        Ok(count)
+    }
+
     fn open_segment(&self) -> &Segment {
         self.segments.last().expect("at least one segment must exist")
     }
@@ -335,9 +409,7 @@ impl TryFrom<SegmentView> for IterSegment {
     type Error = io::Error;
 
     fn try_from(view: SegmentView) -> Result<Self, Self::Error> {
-        File::try_from(view)
-            .map(BufReader::new)
-            .map(|file| IterSegment { file })
+        File::try_from(view).and_then(IterSegment::new)
     }
 }
 
@@ -352,18 +424,64 @@ impl TryFrom<SegmentView> for File {
 /// Iterator over a [`SegmentView`], yielding individual messages.
 ///
 /// Created by [`SegmentView::try_iter`].
+///
+/// A message failing its checksum yields an [`io::ErrorKind::InvalidData`] error.
+/// The iteration ends before an incomplete message, i.e. a torn write or one
+/// not yet flushed to the segment.
 #[must_use = "iterators are lazy and do nothing unless consumed"]
 pub struct IterSegment {
     file: BufReader<File>,
+    /// The size of the segment when it was opened.
+    size: u64,
+    /// The position of the last message read, or of the end of the iteration.
+    start: u64,
+    /// The position after the last message read.
+    end: u64,
+    checksummed: bool,
 }
 
 impl IterSegment {
-    fn read_exact_or_none(&mut self, buf: &mut [u8]) -> Option<io::Result<()>> {
-        match self.file.read_exact(buf) {
-            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => None,
-            Err(e) => Some(Err(e)),
-            Ok(()) => Some(Ok(())),
-        }
+    fn new(file: File) -> io::Result<Self> {
+        let size = file.metadata()?.len();
+        let mut file = BufReader::new(file);
+
+        let mut header = [0; SEGMENT_HEADER_SIZE];
+        let checksummed = size >= SEGMENT_HEADER_SIZE as u64 && {
+            file.read_exact(&mut header)?;
+            header.starts_with(SEGMENT_MAGIC)
+        };
+        let start = if checksummed {
+            if header[SEGMENT_MAGIC.len()] != SEGMENT_VERSION {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unsupported segment version {}", header[SEGMENT_MAGIC.len()]),
+                ));
+            }
+            SEGMENT_HEADER_SIZE as u64
+        } else {
+            file.seek(SeekFrom::Start(0))?
+        };
+
+        Ok(Self {
+            file,
+            size,
+            start,
+            end: start,
+            checksummed,
+        })
+    }
+
+    /// The position in bytes in the segment of the last message read,
+    /// or of the first byte not read once the iteration has ended.
+    pub fn position(&self) -> u64 {
+        self.start
+    }
+
+    /// Whether the messages of the segment carry checksums.
+    ///
+    /// Only the segments written before checksums were introduced lack them.
+    pub fn is_checksummed(&self) -> bool {
+        self.checksummed
     }
 }
 
@@ -371,16 +489,38 @@ impl Iterator for IterSegment {
     type Item = io::Result<Vec<u8>>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let mut buf = [0; HEADER_SIZE];
-        if let Err(e) = self.read_exact_or_none(&mut buf)? {
+        self.start = self.end;
+        let header_size = if self.checksummed {
+            HEADER_SIZE
+        } else {
+            LEGACY_HEADER_SIZE
+        };
+        if self.start + header_size as u64 > self.size {
+            return None;
+        }
+
+        let mut header = [0; HEADER_SIZE];
+        if let Err(e) = self.file.read_exact(&mut header[..header_size]) {
             return Some(Err(e));
         }
+        let message_len: [u8; 4] = header[..4].try_into().unwrap();
+        let end = self.start + header_size as u64 + u32::from_le_bytes(message_len) as u64;
+        if end > self.size {
+            return None;
+        }
 
-        let message_len = u32::from_le_bytes(buf);
-        let mut buf = vec![0; message_len as usize];
-        if let Err(e) = self.read_exact_or_none(&mut buf)? {
+        let mut buf = vec![0; (end - self.start) as usize - header_size];
+        if let Err(e) = self.file.read_exact(&mut buf) {
             return Some(Err(e));
         }
+        self.end = end;
+
+        if self.checksummed && checksum(message_len, &buf).to_le_bytes() != header[4..] {
+            return Some(Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "message checksum mismatch",
+            )));
+        }
 
         Some(Ok(buf))
     }
@@ -401,6 +541,16 @@ impl Segments {
             inner: vec![].into_iter(),
         }
     }
+
+    /// Lists the segments of the message log at `path` without opening it,
+    /// which would truncate a torn write, e.g. to inspect a log as is.
+    pub fn read_dir(path: impl AsRef<Path>) -> Result<Self, DBError> {
+        let root = path.as_ref();
+        Ok(Self {
+            root: root.to_owned(),
+            inner: read_segments(root)?.into_iter(),
+        })
+    }
 }
 
 impl Iterator for Segments {
@@ -418,8 +568,10 @@ impl Iterator for Segments {
 mod tests {
     #![allow(clippy::disallowed_macros)]
 
-    use super::MessageLog;
+    use super::{MessageLog, Segments};
     use spacetimedb_lib::error::ResultTest;
+    use std::fs;
+    use std::io::{self, Write};
     use tempdir::{self, TempDir};
 
     #[test]
@@ -469,7 +621,7 @@ mod tests {
         drop(message_log);
 
         let message_log = MessageLog::open(path)?;
-        assert!(message_log.size() == 2_000_000);
+        assert!(message_log.size() == 2_400_008);
 
         Ok(())
     }
@@ -481,7 +633,8 @@ mod tests {
 
         const MESSAGE: &[u8] = b"fee fi fo fum";
         const MESSAGES_PER_SEGMENT: usize = 10_000;
-        const SEGMENT_SIZE: usize = MESSAGES_PER_SEGMENT * (MESSAGE.len() + super::HEADER_SIZE);
+        const SEGMENT_SIZE: usize =
+            super::SEGMENT_HEADER_SIZE + MESSAGES_PER_SEGMENT * (MESSAGE.len() + super::HEADER_SIZE);
         const TOTAL_MESSAGES: usize = (MESSAGES_PER_SEGMENT * 3) - 1;
 
         let mut message_log = MessageLog::options().max_segment_size(SEGMENT_SIZE as u64).open(path)?;
@@ -508,6 +661,134 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_remove_segments_before() -> ResultTest<()> {
+        let tmp = TempDir::new("message_log_test")?;
+        let path = tmp.path();
+
+        const MESSAGE: &[u8] = b"fee fi fo fum";
+        const MESSAGES_PER_SEGMENT: usize = 10_000;
+        const SEGMENT_SIZE: usize =
+            super::SEGMENT_HEADER_SIZE + MESSAGES_PER_SEGMENT * (MESSAGE.len() + super::HEADER_SIZE);
+        const TOTAL_MESSAGES: usize = (MESSAGES_PER_SEGMENT * 3) - 1;
+
+        let mut message_log = MessageLog::options().max_segment_size(SEGMENT_SIZE as u64).open(path)?;
+        for _ in 0..TOTAL_MESSAGES {
+            message_log.append(MESSAGE)?;
+        }
+        message_log.sync_all()?;
+        let offsets = message_log.segments().map(|s| s.offset()).collect::<Vec<_>>();
+
+        // The open segment is never removed.
+        assert_eq!(2, message_log.remove_segments_before(u64::MAX)?);
+        assert_eq!(0, message_log.remove_segments_before(u64::MAX)?);
+        assert_eq!(1, message_log.segments_from(0).count());
+        assert_eq!(offsets[2], message_log.segments().next().unwrap().offset());
+        assert_eq!(
+            super::SEGMENT_HEADER_SIZE + (MESSAGES_PER_SEGMENT - 1) * (MESSAGE.len() + super::HEADER_SIZE),
+            message_log.size() as usize
+        );
+        drop(message_log);
+
+        let message_log = MessageLog::open(path)?;
+        assert_eq!(1, message_log.segments().count());
+        let messages = message_log
+            .segments()
+            .map(|s| s.try_into_iter().unwrap().count())
+            .sum::<usize>();
+        assert_eq!(MESSAGES_PER_SEGMENT - 1, messages);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncate_torn_write() -> ResultTest<()> {
+        let tmp = TempDir::new("message_log_test")?;
+        let path = tmp.path();
+
+        const MESSAGE: &[u8] = b"fee fi fo fum";
+        let mut message_log = MessageLog::open(path)?;
+        for _ in 0..10 {
+            message_log.append(MESSAGE)?;
+        }
+        message_log.sync_all()?;
+        let size = message_log.size();
+        drop(message_log);
+
+        // Half a message made it to disk.
+        let segment_path = path.join(format!("{:0>20}.log", 0));
+        let mut file = fs::OpenOptions::new().append(true).open(&segment_path)?;
+        file.write_all(&[MESSAGE.len() as u8, 0, 0, 0, 42, 42])?;
+        drop(file);
+
+        let mut message_log = MessageLog::open(path)?;
+        assert_eq!(size, message_log.size());
+        assert_eq!(size, fs::metadata(&segment_path)?.len());
+        message_log.append(MESSAGE)?;
+        message_log.sync_all()?;
+
+        let messages = message_log.segments().next().unwrap().try_into_iter()?;
+        assert_eq!(11, messages.map(Result::unwrap).count());
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupt_message() -> ResultTest<()> {
+        let tmp = TempDir::new("message_log_test")?;
+        let path = tmp.path();
+
+        let mut message_log = MessageLog::open(path)?;
+        for _ in 0..10 {
+            message_log.append(b"fee fi fo fum")?;
+        }
+        message_log.sync_all()?;
+        drop(message_log);
+
+        let segment_path = path.join(format!("{:0>20}.log", 0));
+        let mut bytes = fs::read(&segment_path)?;
+        bytes[super::SEGMENT_HEADER_SIZE + super::HEADER_SIZE] ^= 1;
+        fs::write(&segment_path, bytes)?;
+
+        // Corruption in the middle of a segment is not mistaken for a torn write.
+        assert!(MessageLog::open(path).is_err());
+        let mut messages = Segments::read_dir(path)?.next().unwrap().try_into_iter()?;
+        let err = messages.next().unwrap().unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        assert_eq!(super::SEGMENT_HEADER_SIZE as u64, messages.position());
+        Ok(())
+    }
+
+    #[test]
+    fn test_legacy_segment() -> ResultTest<()> {
+        let tmp = TempDir::new("message_log_test")?;
+        let path = tmp.path();
+
+        // A segment written before checksums, with messages prefixed by their length only.
+        const MESSAGE: &[u8] = b"fee fi fo fum";
+        let mut bytes = Vec::new();
+        for _ in 0..10 {
+            bytes.extend((MESSAGE.len() as u32).to_le_bytes());
+            bytes.extend(MESSAGE);
+        }
+        fs::write(path.join(format!("{:0>20}.log", 0)), bytes)?;
+
+        // New messages go to a new segment, with checksums.
+        let mut message_log = MessageLog::open(path)?;
+        message_log.append(MESSAGE)?;
+        message_log.sync_all()?;
+
+        let segments = message_log
+            .segments()
+            .map(|segment| {
+                let mut messages = segment.try_into_iter().unwrap();
+                let count = messages.by_ref().map(Result::unwrap).count();
+                (count, messages.is_checksummed())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(vec![(10, false), (1, true)], segments);
+        Ok(())
+    }
+
     #[test]
     fn test_segment_iter() -> ResultTest<()> {
         let tmp = TempDir::new("message_log_test")?;
@@ -515,7 +796,8 @@ mod tests {
 
         const MESSAGE: &[u8] = b"fee fi fo fum";
         const MESSAGES_PER_SEGMENT: usize = 10_000;
-        const SEGMENT_SIZE: usize = MESSAGES_PER_SEGMENT * (MESSAGE.len() + super::HEADER_SIZE);
+        const SEGMENT_SIZE: usize =
+            super::SEGMENT_HEADER_SIZE + MESSAGES_PER_SEGMENT * (MESSAGE.len() + super::HEADER_SIZE);
         const TOTAL_MESSAGES: usize = (MESSAGES_PER_SEGMENT * 3) - 1;
 
         let mut message_log = MessageLog::options().max_segment_size(SEGMENT_SIZE as u64).open(path)?;
@@ -1,5 +1,6 @@
 use super::transaction::Transaction;
 use crate::hash::Hash;
+use crate::host::Timestamp;
 use std::sync::Arc;
 
 // aka "Block" from blockchain, aka RecordBatch, aka TxBatch
@@ -8,11 +9,18 @@ pub struct Commit {
     pub parent_commit_hash: Option<Hash>,
     pub commit_offset: u64,
     pub min_tx_offset: u64,
+    /// When the commit was written, absent from the commits written before it was recorded.
+    pub timestamp: Option<Timestamp>,
     pub transactions: Vec<Arc<Transaction>>,
 }
 
+/// Set in the flags of a [`Commit`] with a `parent_commit_hash`.
+const HAS_PARENT_COMMIT_HASH: u8 = 1;
+/// Set in the flags of a [`Commit`] with a `timestamp`.
+const HAS_TIMESTAMP: u8 = 1 << 1;
+
 // TODO: Maybe a transaction buffer hash?
-// commit: <parent_commit_hash(32)><commit_offset(8)><min_tx_offset(8)>[<transaction>...]*
+// commit: <flags(1)><parent_commit_hash(32)>?<commit_offset(8)><min_tx_offset(8)><timestamp(8)>?[<transaction>...]*
 impl Commit {
     pub fn decode(bytes: impl AsRef<[u8]>) -> (Self, usize) {
         let bytes = &mut bytes.as_ref();
@@ -22,21 +30,21 @@ impl Commit {
                     parent_commit_hash: None,
                     commit_offset: 0,
                     min_tx_offset: 0,
+                    timestamp: None,
                     transactions: Vec::new(),
                 },
                 0,
             );
         }
 
-        let mut read_count = 0;
+        let flags = bytes[0];
+        let mut read_count = 1;
 
-        let parent_commit_hash = if bytes[read_count] != 0 {
-            read_count += 1;
+        let parent_commit_hash = if flags & HAS_PARENT_COMMIT_HASH != 0 {
             let parent_commit_hash = Hash::from_slice(&bytes[read_count..read_count + 32]);
             read_count += 32;
             Some(parent_commit_hash)
         } else {
-            read_count += 1;
             None
         };
 
@@ -50,6 +58,15 @@ impl Commit {
         let min_tx_offset = u64::from_le_bytes(dst);
         read_count += 8;
 
+        let timestamp = if flags & HAS_TIMESTAMP != 0 {
+            let mut dst = [0u8; 8];
+            dst.copy_from_slice(&bytes[read_count..read_count + 8]);
+            read_count += 8;
+            Some(Timestamp(u64::from_le_bytes(dst)))
+        } else {
+            None
+        };
+
         let mut transactions: Vec<Arc<Transaction>> = Vec::new();
         while read_count < bytes.len() {
             let (tx, read) = Transaction::decode(&bytes[read_count..]);
@@ -62,6 +79,7 @@ impl Commit {
                 parent_commit_hash,
                 commit_offset,
                 min_tx_offset,
+                timestamp,
                 transactions,
             },
             read_count,
@@ -84,6 +102,10 @@ impl Commit {
         // 8 for min_tx_offset
         count += 8;
 
+        if self.timestamp.is_some() {
+            count += 8;
+        }
+
         for tx in &self.transactions {
             count += tx.encoded_len();
         }
@@ -94,18 +116,67 @@ impl Commit {
     pub fn encode(&self, bytes: &mut Vec<u8>) {
         bytes.reserve(self.encoded_len());
 
-        if self.parent_commit_hash.is_none() {
-            bytes.push(0);
-        } else {
-            bytes.push(1);
-            bytes.extend(self.parent_commit_hash.unwrap().data);
+        let mut flags = 0;
+        if self.parent_commit_hash.is_some() {
+            flags |= HAS_PARENT_COMMIT_HASH;
+        }
+        if self.timestamp.is_some() {
+            flags |= HAS_TIMESTAMP;
+        }
+        bytes.push(flags);
+        if let Some(parent_commit_hash) = self.parent_commit_hash {
+            bytes.extend(parent_commit_hash.data);
         }
 
         bytes.extend(self.commit_offset.to_le_bytes());
         bytes.extend(self.min_tx_offset.to_le_bytes());
+        if let Some(timestamp) = self.timestamp {
+            bytes.extend(timestamp.0.to_le_bytes());
+        }
 
         for tx in &self.transactions {
             tx.encode(bytes);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::hash_bytes;
+
+    #[test]
+    fn test_commit_roundtrip() {
+        let commit = Commit {
+            parent_commit_hash: Some(hash_bytes(b"parent")),
+            commit_offset: 7,
+            min_tx_offset: 42,
+            timestamp: Some(Timestamp(1_690_000_000_000_000)),
+            transactions: Vec::new(),
+        };
+        let mut bytes = Vec::new();
+        commit.encode(&mut bytes);
+        assert_eq!(bytes.len(), commit.encoded_len());
+
+        let (decoded, read) = Commit::decode(&bytes);
+        assert_eq!(read, bytes.len());
+        assert_eq!(decoded.parent_commit_hash, commit.parent_commit_hash);
+        assert_eq!(decoded.commit_offset, commit.commit_offset);
+        assert_eq!(decoded.min_tx_offset, commit.min_tx_offset);
+        assert_eq!(decoded.timestamp, commit.timestamp);
+    }
+
+    #[test]
+    fn test_decode_commit_without_timestamp() {
+        // A commit written before the timestamp was recorded.
+        let mut bytes = vec![0];
+        bytes.extend(3u64.to_le_bytes());
+        bytes.extend(5u64.to_le_bytes());
+
+        let (commit, _) = Commit::decode(&bytes);
+        assert_eq!(commit.parent_commit_hash, None);
+        assert_eq!(commit.commit_offset, 3);
+        assert_eq!(commit.min_tx_offset, 5);
+        assert_eq!(commit.timestamp, None);
+    }
+}
@@ -0,0 +1,323 @@
+//! Automatic migrations of the rows of a table to new columns,
+//! for the changes to its schema which don't need the help of the module,
+//! i.e. appending columns filled with a default value and widening integer columns.
+use std::collections::HashMap;
+
+use spacetimedb_sats::algebraic_type::fmt::fmt_algebraic_type;
+use spacetimedb_sats::{AlgebraicType, AlgebraicValue, BuiltinType, ProductValue};
+
+use crate::db::datastore::traits::ColumnDef;
+
+/// Why the rows of a table can't be migrated automatically.
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum MigrationError {
+    #[error("column `{name}` was removed, renamed or moved")]
+    ColumnRemoved { name: String },
+    #[error("column `{name}` changed type from {} to {}", fmt_algebraic_type(.from), fmt_algebraic_type(.to))]
+    ColumnTypeChanged {
+        name: String,
+        from: AlgebraicType,
+        to: AlgebraicType,
+    },
+    #[error("column `{name}` changed whether it is autoinc")]
+    AutoIncChanged { name: String },
+    #[error("new column `{name}` of type {} has no default value", fmt_algebraic_type(.ty))]
+    NoDefaultValue { name: String, ty: AlgebraicType },
+}
+
+/// How the value of a column of a migrated row is obtained.
+#[derive(Debug, Clone, PartialEq)]
+enum ColumnMigration {
+    /// The value of the old column is kept as it is.
+    Keep,
+    /// The value of the old column is converted to a wider integer type.
+    Widen(AlgebraicType),
+    /// The column is new, and all the rows get this value.
+    Add(AlgebraicValue),
+}
+
+/// The migration of the rows of a table from its old columns to new ones.
+///
+/// The old columns must keep their name and position,
+/// their type may only be widened to a larger integer type, see [`can_widen`],
+/// and the new columns are appended, filled with the default declared for them by the module,
+/// or else the [`default_value`] of their type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableMigration {
+    columns: Vec<ColumnMigration>,
+}
+
+impl TableMigration {
+    /// Returns the migration from the `old` columns to the `new` ones,
+    /// or why, column by column, it can't be done automatically.
+    ///
+    /// `defaults` are the values declared for the new columns, by name,
+    /// which must be of the type of their column.
+    pub fn new(
+        old: &[ColumnDef],
+        new: &[ColumnDef],
+        defaults: &HashMap<String, AlgebraicValue>,
+    ) -> Result<Self, Vec<MigrationError>> {
+        let mut columns = Vec::with_capacity(new.len());
+        let mut errors = Vec::new();
+        for (i, old_col) in old.iter().enumerate() {
+            let Some(new_col) = new.get(i).filter(|new_col| new_col.col_name == old_col.col_name) else {
+                errors.push(MigrationError::ColumnRemoved {
+                    name: old_col.col_name.clone(),
+                });
+                continue;
+            };
+            if new_col.is_autoinc != old_col.is_autoinc {
+                errors.push(MigrationError::AutoIncChanged {
+                    name: new_col.col_name.clone(),
+                });
+            }
+            if new_col.col_type == old_col.col_type {
+                columns.push(ColumnMigration::Keep);
+            } else if can_widen(&old_col.col_type, &new_col.col_type) {
+                columns.push(ColumnMigration::Widen(new_col.col_type.clone()));
+            } else {
+                errors.push(MigrationError::ColumnTypeChanged {
+                    name: new_col.col_name.clone(),
+                    from: old_col.col_type.clone(),
+                    to: new_col.col_type.clone(),
+                });
+            }
+        }
+        for new_col in &new[old.len().min(new.len())..] {
+            if new_col.is_autoinc {
+                errors.push(MigrationError::AutoIncChanged {
+                    name: new_col.col_name.clone(),
+                });
+            }
+            let declared = defaults.get(&new_col.col_name).cloned();
+            match declared.or_else(|| default_value(&new_col.col_type)) {
+                Some(value) => columns.push(ColumnMigration::Add(value)),
+                None => errors.push(MigrationError::NoDefaultValue {
+                    name: new_col.col_name.clone(),
+                    ty: new_col.col_type.clone(),
+                }),
+            }
+        }
+        if errors.is_empty() {
+            Ok(Self { columns })
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Returns whether the rows are left as they are, i.e. the columns are unchanged.
+    pub fn is_identity(&self) -> bool {
+        self.columns.iter().all(|col| *col == ColumnMigration::Keep)
+    }
+
+    /// Converts a `row` in the shape of the old columns to the shape of the new ones.
+    pub fn migrate_row(&self, row: ProductValue) -> ProductValue {
+        let mut old_values = row.elements.into_iter();
+        self.columns
+            .iter()
+            .map(|col| match col {
+                ColumnMigration::Keep => old_values.next().unwrap(),
+                ColumnMigration::Widen(ty) => widen(old_values.next().unwrap(), ty),
+                ColumnMigration::Add(value) => value.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Returns the value of a new column of type `ty` in the existing rows,
+/// if there is one and the module doesn't declare another.
+///
+/// That is zero for a number, `false`, the empty string, `none` for an option,
+/// and a product of such values.
+/// A new column of any other type, e.g. an array or an enum, without a declared default taints the table,
+/// whose rows are then left to the `migrate` reducer, if the module defines one.
+pub fn default_value(ty: &AlgebraicType) -> Option<AlgebraicValue> {
+    Some(match ty {
+        AlgebraicType::Sum(sum) => {
+            sum.as_option()?;
+            AlgebraicValue::OptionNone()
+        }
+        AlgebraicType::Product(product) => AlgebraicValue::Product(
+            product
+                .elements
+                .iter()
+                .map(|element| default_value(&element.algebraic_type))
+                .collect::<Option<_>>()?,
+        ),
+        AlgebraicType::Builtin(builtin) => match builtin {
+            BuiltinType::Bool => false.into(),
+            BuiltinType::I8 => 0i8.into(),
+            BuiltinType::U8 => 0u8.into(),
+            BuiltinType::I16 => 0i16.into(),
+            BuiltinType::U16 => 0u16.into(),
+            BuiltinType::I32 => 0i32.into(),
+            BuiltinType::U32 => 0u32.into(),
+            BuiltinType::I64 => 0i64.into(),
+            BuiltinType::U64 => 0u64.into(),
+            BuiltinType::I128 => 0i128.into(),
+            BuiltinType::U128 => 0u128.into(),
+            BuiltinType::F32 => 0f32.into(),
+            BuiltinType::F64 => 0f64.into(),
+            BuiltinType::String => String::new().into(),
+            BuiltinType::Array(_) | BuiltinType::Map(_) => return None,
+        },
+        AlgebraicType::Ref(_) => return None,
+    })
+}
+
+/// Returns whether `ty` is signed and its number of bits, if it is an integer type.
+fn integer_type(ty: &AlgebraicType) -> Option<(bool, u32)> {
+    let AlgebraicType::Builtin(builtin) = ty else {
+        return None;
+    };
+    Some(match builtin {
+        BuiltinType::I8 => (true, 8),
+        BuiltinType::U8 => (false, 8),
+        BuiltinType::I16 => (true, 16),
+        BuiltinType::U16 => (false, 16),
+        BuiltinType::I32 => (true, 32),
+        BuiltinType::U32 => (false, 32),
+        BuiltinType::I64 => (true, 64),
+        BuiltinType::U64 => (false, 64),
+        BuiltinType::I128 => (true, 128),
+        BuiltinType::U128 => (false, 128),
+        _ => return None,
+    })
+}
+
+/// Returns whether all the values of the integer type `from`
+/// can be represented by the larger integer type `to`,
+/// e.g. `u8` to `u32` or `i32`, but not `i8` to `u32`.
+pub fn can_widen(from: &AlgebraicType, to: &AlgebraicType) -> bool {
+    match (integer_type(from), integer_type(to)) {
+        (Some((from_signed, from_bits)), Some((to_signed, to_bits))) => {
+            to_bits > from_bits && (from_signed == to_signed || to_signed)
+        }
+        _ => false,
+    }
+}
+
+/// Converts the integer `value` to the type `ty`, to which it [can be widened](can_widen).
+fn widen(value: AlgebraicValue, ty: &AlgebraicType) -> AlgebraicValue {
+    // A `u128` can't be widened, so any value fits in an `i128`.
+    let value = match value {
+        AlgebraicValue::I8(v) => v as i128,
+        AlgebraicValue::U8(v) => v as i128,
+        AlgebraicValue::I16(v) => v as i128,
+        AlgebraicValue::U16(v) => v as i128,
+        AlgebraicValue::I32(v) => v as i128,
+        AlgebraicValue::U32(v) => v as i128,
+        AlgebraicValue::I64(v) => v as i128,
+        AlgebraicValue::U64(v) => v as i128,
+        value => unreachable!("{value:?} can't be widened"),
+    };
+    match *ty {
+        AlgebraicType::I16 => (value as i16).into(),
+        AlgebraicType::U16 => (value as u16).into(),
+        AlgebraicType::I32 => (value as i32).into(),
+        AlgebraicType::U32 => (value as u32).into(),
+        AlgebraicType::I64 => (value as i64).into(),
+        AlgebraicType::U64 => (value as u64).into(),
+        AlgebraicType::I128 => value.into(),
+        AlgebraicType::U128 => (value as u128).into(),
+        _ => unreachable!("can't widen to {ty:?}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spacetimedb_sats::{product, ArrayValue};
+
+    fn col(name: &str, ty: AlgebraicType) -> ColumnDef {
+        ColumnDef {
+            col_name: name.to_string(),
+            col_type: ty,
+            is_autoinc: false,
+        }
+    }
+
+    #[test]
+    fn test_migrate_row() {
+        let old = [col("id", AlgebraicType::U32), col("score", AlgebraicType::I8)];
+        let new = [
+            col("id", AlgebraicType::U32),
+            col("score", AlgebraicType::I64),
+            col("name", AlgebraicType::String),
+            col("nick", AlgebraicType::option(AlgebraicType::String)),
+        ];
+        let migration = TableMigration::new(&old, &new, &HashMap::new()).unwrap();
+        assert!(!migration.is_identity());
+        assert_eq!(
+            migration.migrate_row(product![1u32, -3i8]),
+            product![1u32, -3i64, "", AlgebraicValue::OptionNone()]
+        );
+
+        // A declared default replaces the zero value.
+        let defaults = HashMap::from([("name".to_string(), AlgebraicValue::String("anonymous".into()))]);
+        let migration = TableMigration::new(&old, &new, &defaults).unwrap();
+        assert_eq!(
+            migration.migrate_row(product![1u32, -3i8]),
+            product![1u32, -3i64, "anonymous", AlgebraicValue::OptionNone()]
+        );
+
+        assert!(TableMigration::new(&old, &old, &HashMap::new()).unwrap().is_identity());
+    }
+
+    #[test]
+    fn test_migration_errors() {
+        let old = [col("id", AlgebraicType::U32), col("score", AlgebraicType::I8)];
+
+        let new = [col("id", AlgebraicType::U32)];
+        assert_eq!(
+            TableMigration::new(&old, &new, &HashMap::new()),
+            Err(vec![MigrationError::ColumnRemoved { name: "score".into() }])
+        );
+
+        let new = [col("score", AlgebraicType::I8), col("id", AlgebraicType::U32)];
+        assert_eq!(
+            TableMigration::new(&old, &new, &HashMap::new()),
+            Err(vec![
+                MigrationError::ColumnRemoved { name: "id".into() },
+                MigrationError::ColumnRemoved { name: "score".into() },
+            ])
+        );
+
+        let new = [col("id", AlgebraicType::U32), col("score", AlgebraicType::U64)];
+        assert_eq!(
+            TableMigration::new(&old, &new, &HashMap::new()),
+            Err(vec![MigrationError::ColumnTypeChanged {
+                name: "score".into(),
+                from: AlgebraicType::I8,
+                to: AlgebraicType::U64
+            }])
+        );
+
+        let mut new = old.to_vec();
+        new.push(col("tags", AlgebraicType::array(AlgebraicType::String)));
+        assert_eq!(
+            TableMigration::new(&old, &new, &HashMap::new()),
+            Err(vec![MigrationError::NoDefaultValue {
+                name: "tags".into(),
+                ty: AlgebraicType::array(AlgebraicType::String)
+            }])
+        );
+        // Unless the module declares one.
+        let tags = AlgebraicValue::Array(ArrayValue::String(vec!["new".into()]));
+        let defaults = HashMap::from([("tags".to_string(), tags.clone())]);
+        let migration = TableMigration::new(&old, &new, &defaults).unwrap();
+        assert_eq!(migration.migrate_row(product![1u32, -3i8]), product![1u32, -3i8, tags]);
+    }
+
+    #[test]
+    fn test_can_widen() {
+        assert!(can_widen(&AlgebraicType::U8, &AlgebraicType::U16));
+        assert!(can_widen(&AlgebraicType::U32, &AlgebraicType::I64));
+        assert!(can_widen(&AlgebraicType::I64, &AlgebraicType::I128));
+        assert!(!can_widen(&AlgebraicType::I8, &AlgebraicType::U32));
+        assert!(!can_widen(&AlgebraicType::U32, &AlgebraicType::I32));
+        assert!(!can_widen(&AlgebraicType::U64, &AlgebraicType::U32));
+        assert!(!can_widen(&AlgebraicType::F32, &AlgebraicType::F64));
+    }
+}
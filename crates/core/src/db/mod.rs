@@ -2,13 +2,18 @@ pub mod commit_log;
 pub mod cursor;
 pub mod datastore;
 pub mod db_metrics;
+pub mod inspect;
 pub mod message_log;
 pub mod messages;
+pub mod migration;
 pub mod ostorage;
 pub mod relational_db;
 mod relational_operators;
+pub mod snapshot;
 
+use serde::{Deserialize, Serialize};
 pub use spacetimedb_lib::{AlgebraicType, AlgebraicValue, ProductType, ProductValue};
+use std::time::Duration;
 
 /// Whether SpacetimeDB is run in memory, or persists objects and
 /// a message log to disk.
@@ -21,13 +26,25 @@ pub enum Storage {
     Disk,
 }
 
+/// The default size of a batch of commits under [FsyncPolicy::GroupCommit].
+pub const DEFAULT_GROUP_COMMIT_BYTES: usize = 1024 * 1024;
+
 /// How often Txn messages are physically persisted to the WAL.
-#[derive(Clone, Copy, PartialEq)]
+///
+/// The policy given in [Config] is the default of the host,
+/// which a database may override with its own.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum FsyncPolicy {
     /// Flush WAL writes to OS buffers and let OS schedule the write to disk.
     Never,
     /// Every Txn should be fsync'd to disk.
     EveryTx,
+    /// Txns are batched into a single commit, fsync'd to disk once for the whole batch.
+    ///
+    /// A batch is written when `window` has elapsed since its first Txn,
+    /// or as soon as its Txns take `max_bytes`.
+    /// Each Txn is acknowledged only once its batch is durable.
+    GroupCommit { window: Duration, max_bytes: usize },
 }
 
 /// Internal database config parameters
@@ -37,4 +54,12 @@ pub struct Config {
     pub fsync: FsyncPolicy,
     /// Specifies the object storage model.
     pub storage: Storage,
+    /// Specifies after how many commits a snapshot of the database is taken,
+    /// if at all.
+    pub snapshot_interval: Option<u64>,
+    /// Specifies whether taking a snapshot removes the segments of the commit log it covers.
+    ///
+    /// This bounds the size of the log, but the history before the retained snapshots
+    /// can then no longer be restored, forked or inspected.
+    pub compact_log: bool,
 }
@@ -5,6 +5,7 @@ use bytes::Bytes;
 use sled;
 use sled::Mode::HighThroughput;
 use std::path::Path;
+use std::{fs, io};
 
 pub struct SledObjectDB {
     db: sled::Db,
@@ -20,6 +21,35 @@ impl SledObjectDB {
 
         Ok(Self { db })
     }
+
+    /// Opens a copy of the object db at `path`, made in the empty directory `scratch`.
+    ///
+    /// sled has no read-only mode: opening a db writes to it and locks it,
+    /// so the odb of a running database can only be read through a copy.
+    /// Nothing is created at `path`, which must hold a sled db.
+    pub fn open_copy(path: impl AsRef<Path>, scratch: impl AsRef<Path>) -> Result<Self, DBError> {
+        let path = path.as_ref();
+        if !path.join("db").is_file() {
+            let msg = format!("no object db in {}", path.display());
+            return Err(io::Error::new(io::ErrorKind::NotFound, msg).into());
+        }
+        copy_dir(path, scratch.as_ref())?;
+        Self::open(scratch)
+    }
+}
+
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let to = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &to)?;
+        } else {
+            fs::copy(entry.path(), to)?;
+        }
+    }
+    Ok(())
 }
 
 impl ObjectDB for SledObjectDB {
@@ -113,4 +143,22 @@ mod tests {
 
         assert!(result.is_none());
     }
+
+    #[test]
+    fn test_open_copy() -> Result<(), DBError> {
+        let tmp_dir = TempDir::new(TEST_DB_DIR_PREFIX)?;
+        let scratch = TempDir::new(TEST_DB_DIR_PREFIX)?;
+        let mut db = SledObjectDB::open(tmp_dir.path().join("odb"))?;
+        let hash = db.add(TEST_DATA1.to_vec());
+        db.flush()?;
+
+        // The original is still open, and locked.
+        let copy = SledObjectDB::open_copy(tmp_dir.path().join("odb"), scratch.path().join("odb"))?;
+        assert_eq!(TEST_DATA1, copy.get(hash).unwrap().to_vec().as_slice());
+
+        // Nothing is created where there is no db.
+        assert!(SledObjectDB::open_copy(tmp_dir.path().join("missing"), scratch.path().join("missing")).is_err());
+        assert!(!tmp_dir.path().join("missing").exists());
+        Ok(())
+    }
 }
@@ -1,8 +1,8 @@
-use super::commit_log::{CommitLog, CommitLogView};
+use super::commit_log::{CommitLog, CommitLogView, RestorePoint};
 use super::datastore::locking_tx_datastore::{Data, DataRef, Iter, IterByColEq, IterByColRange, MutTxId, RowId};
 use super::datastore::traits::{
-    ColId, DataRow, IndexDef, IndexId, MutProgrammable, MutTx, MutTxDatastore, Programmable, SequenceDef, SequenceId,
-    TableDef, TableId, TableSchema, TxData,
+    ColId, ColumnDef, DataRow, IndexDef, IndexId, MutProgrammable, MutTx, MutTxDatastore, Programmable, SequenceDef,
+    SequenceId, TableDef, TableId, TableSchema, TableStats, Tx, TxData,
 };
 use super::message_log::MessageLog;
 use super::ostorage::memory_object_db::MemoryObjectDB;
@@ -13,11 +13,15 @@ use crate::db::db_metrics::{RDB_DELETE_BY_REL_TIME, RDB_DROP_TABLE_TIME, RDB_INS
 use crate::db::messages::commit::Commit;
 use crate::db::ostorage::hashmap_object_db::HashMapObjectDB;
 use crate::db::ostorage::ObjectDB;
+use crate::db::snapshot::{Snapshot, SnapshotStore};
+use crate::db::FsyncPolicy;
 use crate::error::{DBError, DatabaseError, IndexError, TableError};
 use crate::hash::Hash;
 use crate::util::prometheus_handle::HistogramVecHandle;
+use anyhow::anyhow;
 use fs2::FileExt;
 use nonempty::NonEmpty;
+use parking_lot::RwLock;
 use prometheus::HistogramVec;
 use spacetimedb_lib::ColumnIndexAttribute;
 use spacetimedb_lib::{data_key::ToDataKey, PrimaryKey};
@@ -26,7 +30,9 @@ use std::borrow::Cow;
 use std::fs::{create_dir_all, File};
 use std::ops::RangeBounds;
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
 
 use super::datastore::locking_tx_datastore::Locking;
 
@@ -35,6 +41,9 @@ fn measure(hist: &'static HistogramVec, table_id: u32) {
     HistogramVecHandle::new(hist, vec![format!("{}", table_id)]).start();
 }
 
+/// The directory of the [`Snapshot`]s of a database, under its root.
+const SNAPSHOTS_DIR: &str = "snapshots";
+
 pub const ST_TABLES_NAME: &str = "st_table";
 pub const ST_COLUMNS_NAME: &str = "st_columns";
 pub const ST_SEQUENCES_NAME: &str = "st_sequence";
@@ -52,9 +61,28 @@ pub struct RelationalDB {
     // TODO(cloutiertyler): This should not be public
     pub(crate) inner: Locking,
     commit_log: CommitLog,
+    snapshots: Option<Snapshots>,
     _lock: Arc<File>,
 }
 
+/// The [`Snapshot`]s of a [`RelationalDB`] whose [`CommitLog`] is persisted to disk.
+#[derive(Clone)]
+struct Snapshots {
+    store: SnapshotStore,
+    /// Held shared by the commits being written to the [`CommitLog`],
+    /// and exclusively by a snapshot, so it covers exactly the commits in the log.
+    commits: Arc<RwLock<()>>,
+    /// The number of commits after which a snapshot is taken, if at all.
+    interval: Option<u64>,
+    /// Whether taking a periodic snapshot also removes the segments of the [`CommitLog`]
+    /// that the retained snapshots cover.
+    compact_log: bool,
+    /// The commit offset of the latest snapshot.
+    last_offset: Arc<AtomicU64>,
+    /// The thread taking the latest periodic snapshot, off the path of the commits.
+    worker: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
 impl DataRow for RelationalDB {
     type RowId = RowId;
     type Data = Data;
@@ -77,7 +105,9 @@ impl RelationalDB {
         message_log: Option<Arc<Mutex<MessageLog>>>,
         odb: Arc<Mutex<Box<dyn ObjectDB + Send>>>,
         address: Address,
-        fsync: bool,
+        fsync: FsyncPolicy,
+        snapshot_interval: Option<u64>,
+        compact_log: bool,
     ) -> Result<Self, DBError> {
         let address = address.to_hex();
         log::debug!("[{}] DATABASE: OPENING", address);
@@ -93,22 +123,53 @@ impl RelationalDB {
         lock.try_lock_exclusive()
             .map_err(|err| DatabaseError::DatabasedOpened(root.to_path_buf(), err.into()))?;
 
+        let snapshots = message_log
+            .as_ref()
+            .map(|_| SnapshotStore::open(root.join(SNAPSHOTS_DIR)))
+            .transpose()?;
+
         let datastore = Locking::bootstrap()?;
-        log::debug!("[{}] Replaying transaction log.", address);
         let mut segment_index = 0;
         let mut last_logged_percentage = 0;
+        let mut snapshot_offset = 0;
         let unwritten_commit = {
             let mut transaction_offset = 0;
             let mut last_commit_offset = None;
             let mut last_hash: Option<Hash> = None;
             if let Some(message_log) = &message_log {
+                if let Some(snapshot) = snapshots.as_ref().map(SnapshotStore::latest).transpose()?.flatten() {
+                    log::debug!(
+                        "[{}] Restoring snapshot at commit offset {}.",
+                        address,
+                        snapshot.commit_offset
+                    );
+                    datastore.restore_snapshot(&snapshot.tables)?;
+                    snapshot_offset = snapshot.commit_offset;
+                    transaction_offset = snapshot.tx_offset;
+                    last_commit_offset = snapshot.commit_offset.checked_sub(1);
+                    last_hash = snapshot.last_commit_hash;
+                }
+
+                log::debug!("[{}] Replaying transaction log.", address);
                 let message_log = message_log.lock().unwrap();
                 let max_offset = message_log.open_segment_max_offset;
-                for commit in commit_log::Iter::from(message_log.segments()) {
+                let segment_offset = commit_log::segment_offset_of_commit(&message_log, snapshot_offset)?;
+                let mut commits = commit_log::Iter::from(message_log.segments_from(segment_offset));
+                for commit in commits.by_ref() {
                     let commit = commit?;
+                    // The commits before the snapshot are already part of it.
+                    if commit.commit_offset < snapshot_offset {
+                        continue;
+                    }
+                    if last_commit_offset.map_or(0, |offset| offset + 1) < commit.commit_offset {
+                        return Err(anyhow!(
+                            "Missing commits before offset {} in the message log",
+                            commit.commit_offset
+                        )
+                        .into());
+                    }
 
                     segment_index += 1;
-                    last_hash = commit.parent_commit_hash;
                     last_commit_offset = Some(commit.commit_offset);
                     for transaction in commit.transactions {
                         transaction_offset += 1;
@@ -133,6 +194,11 @@ impl RelationalDB {
                     }
                 }
 
+                // The next commit follows the last one in the log, if any.
+                if let Some(hash) = commits.last_hash() {
+                    last_hash = Some(hash);
+                }
+
                 // The purpose of this is to rebuild the state of the datastore
                 // after having inserted all of rows from the message log.
                 // This is necessary because, for example, inserting a row into `st_table`
@@ -158,16 +224,26 @@ impl RelationalDB {
                 parent_commit_hash: last_hash,
                 commit_offset,
                 min_tx_offset: transaction_offset,
+                timestamp: None,
                 transactions: Vec::new(),
             }
         };
         let commit_log = CommitLog::new(message_log, odb.clone(), unwritten_commit, fsync);
+        let snapshots = snapshots.map(|store| Snapshots {
+            store,
+            commits: Arc::default(),
+            interval: snapshot_interval,
+            compact_log,
+            last_offset: Arc::new(AtomicU64::new(snapshot_offset)),
+            worker: Arc::default(),
+        });
 
         // i.e. essentially bootstrap the creation of the schema
         // tables by hard coding the schema of the schema tables
         let db = Self {
             inner: datastore,
             commit_log,
+            snapshots,
             _lock: Arc::new(lock),
         };
 
@@ -180,6 +256,225 @@ impl RelationalDB {
         CommitLogView::from(&self.commit_log)
     }
 
+    /// Changes how the transactions committed from now on are persisted, see [`CommitLog::set_fsync`].
+    pub fn set_fsync_policy(&self, fsync: FsyncPolicy) {
+        self.commit_log.set_fsync(fsync)
+    }
+
+    /// Persist a [`Snapshot`] of the committed tables, so that opening the
+    /// database replays only the commits written after it.
+    ///
+    /// Returns the commit offset covered by the snapshot, or `None` if the
+    /// database doesn't persist a [`CommitLog`].
+    #[tracing::instrument(skip_all)]
+    pub fn take_snapshot(&self) -> Result<Option<u64>, DBError> {
+        let Some(snapshots) = &self.snapshots else {
+            return Ok(None);
+        };
+        let (tx, next_commit) = {
+            // Wait for the commits in flight to be written, and block new ones,
+            // so the committed state matches the commits in the log.
+            let _commits = snapshots.commits.write();
+            // The snapshot must never cover commits lost from the log.
+            self.commit_log.sync_all()?;
+            (self.begin_read_tx(), self.commit_log.next_commit())
+        };
+        let tables = self.inner.snapshot_tables(&tx);
+        self.release_tx(tx);
+
+        let snapshot = Snapshot {
+            commit_offset: next_commit.commit_offset,
+            tx_offset: next_commit.min_tx_offset,
+            last_commit_hash: next_commit.parent_commit_hash,
+            tables,
+        };
+        snapshots.store.write(&snapshot)?;
+        snapshots
+            .last_offset
+            .fetch_max(snapshot.commit_offset, Ordering::SeqCst);
+        log::debug!("Took snapshot at commit offset {}", snapshot.commit_offset);
+
+        Ok(Some(snapshot.commit_offset))
+    }
+
+    /// Starts taking a snapshot, then compacting the commit log if enabled, in the background,
+    /// if the snapshot interval has elapsed since the latest one.
+    ///
+    /// Does nothing while the previous snapshot is still being taken:
+    /// the next commit tries again.
+    fn maybe_take_snapshot(&self) {
+        let Some(snapshots) = &self.snapshots else {
+            return;
+        };
+        let Some(interval) = snapshots.interval else {
+            return;
+        };
+        let last_offset = snapshots.last_offset.load(Ordering::SeqCst);
+        let commit_offset = self.commit_log.next_commit().commit_offset;
+        if commit_offset < last_offset.saturating_add(interval) {
+            return;
+        }
+        // Only one of the concurrent commits starts the snapshot.
+        let Ok(mut worker) = snapshots.worker.try_lock() else {
+            return;
+        };
+        if worker.as_ref().map_or(false, |worker| !worker.is_finished()) {
+            return;
+        }
+        let db = self.clone();
+        let spawned = std::thread::Builder::new()
+            .name("snapshot".into())
+            .spawn(move || db.take_snapshot_and_compact());
+        match spawned {
+            Ok(handle) => *worker = Some(handle),
+            Err(e) => log::error!("Failed to start a snapshot at commit offset {}: {}", commit_offset, e),
+        }
+    }
+
+    /// Takes a snapshot, and if it succeeds and compaction is enabled,
+    /// removes the segments of the commit log the retained snapshots cover.
+    fn take_snapshot_and_compact(&self) {
+        let commit_offset = match self.take_snapshot() {
+            Ok(Some(commit_offset)) => commit_offset,
+            Ok(None) => return,
+            Err(e) => {
+                log::error!("Failed to take a snapshot: {}", e);
+                return;
+            }
+        };
+        if !self.snapshots.as_ref().map_or(false, |s| s.compact_log) {
+            return;
+        }
+        match self.compact_commit_log() {
+            Ok(removed) => log::debug!(
+                "Removed {} segments of the commit log before offset {}",
+                removed,
+                commit_offset
+            ),
+            Err(e) => log::error!(
+                "Failed to compact the commit log before offset {}: {}",
+                commit_offset,
+                e
+            ),
+        }
+    }
+
+    /// Waits for the periodic snapshot being taken, if any.
+    #[cfg(test)]
+    fn wait_for_snapshot(&self) {
+        let worker = self.snapshots.as_ref().and_then(|s| s.worker.lock().unwrap().take());
+        if let Some(worker) = worker {
+            worker.join().unwrap();
+        }
+    }
+
+    /// Write the history of this database up to `point` into the `message_log`,
+    /// `odb` and snapshots under `root` of another database, which is then
+    /// opened as a fork of this database in its state as of `point`.
+    ///
+    /// Returns the offset of the first commit not restored.
+    #[tracing::instrument(skip_all, fields(?point))]
+    pub fn restore_into(
+        &self,
+        point: RestorePoint,
+        root: &Path,
+        message_log: &mut MessageLog,
+        odb: &mut dyn ObjectDB,
+    ) -> Result<u64, DBError> {
+        let Some(snapshots) = &self.snapshots else {
+            return Err(anyhow!("Can't restore a database which doesn't persist its commit log").into());
+        };
+        // Make sure the commits written so far can be read back.
+        self.commit_log.sync_all()?;
+        let log = self.commit_log();
+        let next_offset = self.commit_log.next_commit().commit_offset;
+
+        let end = match point {
+            RestorePoint::Latest => next_offset,
+            RestorePoint::Offset(offset) if offset < next_offset => offset + 1,
+            RestorePoint::Offset(offset) => {
+                return Err(DBError::InvalidRestorePoint(format!(
+                    "commit offset {offset} is past the end of the commit log, at {next_offset}"
+                )))
+            }
+            RestorePoint::Time(time) => {
+                let mut end = next_offset;
+                for commit in log.iter() {
+                    let commit = commit?;
+                    if commit.timestamp.map_or(false, |timestamp| timestamp.0 > time.0) {
+                        end = commit.commit_offset;
+                        break;
+                    }
+                }
+                if end == 0 {
+                    return Err(DBError::InvalidRestorePoint(format!(
+                        "no commit was made at or before {} microseconds since the epoch",
+                        time.0
+                    )));
+                }
+                end
+            }
+        };
+
+        // Start from the latest snapshot before `end`, unless the log has all the commits before it.
+        let first_offset = log
+            .iter()
+            .next()
+            .transpose()?
+            .map_or(next_offset, |commit| commit.commit_offset);
+        let snapshot = snapshots
+            .store
+            .offsets()?
+            .into_iter()
+            .rev()
+            .find(|&offset| offset == end || (first_offset..end).contains(&offset))
+            .map(|offset| snapshots.store.read(offset))
+            .transpose()?;
+        let start = snapshot.as_ref().map_or(0, |snapshot| snapshot.commit_offset);
+        if start < first_offset && start < end {
+            return Err(DBError::InvalidRestorePoint(format!(
+                "the commits before offset {first_offset} were compacted, and no snapshot covers offset {end}"
+            )));
+        }
+        if let Some(snapshot) = &snapshot {
+            SnapshotStore::open(root.join(SNAPSHOTS_DIR))?.write(snapshot)?;
+        }
+
+        for commit in log.iter_from_commit(start)? {
+            let commit = commit?;
+            if commit.commit_offset < start {
+                continue;
+            }
+            if commit.commit_offset >= end {
+                break;
+            }
+            for object in log.commit_objects(&commit) {
+                odb.add(object?.to_vec());
+            }
+            let mut bytes = Vec::with_capacity(commit.encoded_len());
+            commit.encode(&mut bytes);
+            message_log.append(bytes)?;
+        }
+        message_log.sync_all()?;
+        odb.sync_all()?;
+
+        log::debug!("Restored commits {start}..{end} into {}", root.display());
+        Ok(end)
+    }
+
+    /// Remove the segments of the message log covered by the oldest retained snapshot,
+    /// which opening the database no longer replays,
+    /// even if it has to fall back to that snapshot because a later one is corrupt.
+    ///
+    /// Returns the number of segments removed.
+    pub fn compact_commit_log(&self) -> Result<usize, DBError> {
+        let offsets = self.snapshots.as_ref().map(|s| s.store.offsets()).transpose()?;
+        match offsets.as_deref().and_then(<[u64]>::first) {
+            Some(&commit_offset) => self.commit_log.compact(commit_offset),
+            None => Ok(0),
+        }
+    }
+
     // pub fn reset_hard(&mut self, message_log: Arc<Mutex<MessageLog>>) -> Result<(), DBError> {
     //     log::warn!("DATABASE: RESET");
 
@@ -265,6 +560,26 @@ impl RelationalDB {
         self.inner.begin_mut_tx()
     }
 
+    /// Begin a read-only transaction over a snapshot of the committed state.
+    ///
+    /// It doesn't take the lock held by the transactions begun with [`Self::begin_tx`],
+    /// so it neither blocks nor waits for them, and doesn't see the changes they commit after it began.
+    ///
+    /// **Note**: this call **must** be paired with [`Self::release_tx`].
+    /// See also [`Self::with_read_only`].
+    #[tracing::instrument(skip_all)]
+    pub fn begin_read_tx(&self) -> MutTxId {
+        log::trace!("BEGIN READ TX");
+        self.inner.begin_tx()
+    }
+
+    /// Release a read-only transaction, discarding any writes made to it.
+    #[tracing::instrument(skip_all)]
+    pub fn release_tx(&self, tx: MutTxId) {
+        log::trace!("RELEASE TX");
+        self.inner.release_tx(tx)
+    }
+
     #[tracing::instrument(skip_all)]
     pub fn rollback_tx(&self, tx: MutTxId) {
         log::trace!("ROLLBACK TX");
@@ -274,11 +589,20 @@ impl RelationalDB {
     #[tracing::instrument(skip_all)]
     pub fn commit_tx(&self, tx: MutTxId) -> Result<Option<(TxData, Option<usize>)>, DBError> {
         log::trace!("COMMIT TX");
-        if let Some(tx_data) = self.inner.commit_mut_tx(tx)? {
-            let bytes_written = self.commit_log.append_tx(&tx_data, &self.inner)?;
-            return Ok(Some((tx_data, bytes_written)));
+        let committed = {
+            let _commits = self.snapshots.as_ref().map(|snapshots| snapshots.commits.read());
+            match self.inner.commit_mut_tx(tx)? {
+                Some(tx_data) => {
+                    let bytes_written = self.commit_log.append_tx(&tx_data, &self.inner)?;
+                    Some((tx_data, bytes_written))
+                }
+                None => None,
+            }
+        };
+        if committed.is_some() {
+            self.maybe_take_snapshot();
         }
-        Ok(None)
+        Ok(committed)
     }
 
     /// Run a fallible function in a transaction.
@@ -334,13 +658,12 @@ impl RelationalDB {
         self.rollback_on_err(tx, res)
     }
 
-    /// Run a fallible function in a transaction.
+    /// Run a fallible function in a read-only transaction.
     ///
-    /// This is similar to `with_auto_commit`, but regardless of the return value of
-    /// the fallible function, the transaction will ALWAYS be rolled back. This can be used to
-    /// emulate a read-only transaction.
+    /// This is similar to `with_auto_commit`, but the function runs over a snapshot
+    /// of the committed state, see [`Self::begin_read_tx`], and regardless of its return value
+    /// the transaction will ALWAYS be released, discarding any writes.
     ///
-    /// TODO(jgilles): when we support actual read-only transactions, use those here instead.
     /// TODO(jgilles, kim): get this merged with the above function (two people had similar ideas
     /// at the same time)
     pub fn with_read_only<F, A, E>(&self, f: F) -> Result<A, E>
@@ -348,9 +671,9 @@ impl RelationalDB {
         F: FnOnce(&mut MutTxId) -> Result<A, E>,
         E: From<DBError>,
     {
-        let mut tx = self.begin_tx();
+        let mut tx = self.begin_read_tx();
         let res = f(&mut tx);
-        self.rollback_tx(tx);
+        self.release_tx(tx);
         res
     }
 
@@ -408,6 +731,22 @@ impl RelationalDB {
         self.inner.rename_table_mut_tx(tx, TableId(table_id), new_name)
     }
 
+    /// Replace the columns of a table.
+    ///
+    /// All the rows of the table are deleted and returned, as they may not fit the new columns.
+    /// It is up to the caller to insert them back, in the shape of the new columns.
+    /// The indexes and sequences of the table are left as they are.
+    ///
+    /// If the table is not found or is a system table, an error is returned.
+    pub fn alter_table(
+        &self,
+        tx: &mut MutTxId,
+        table_id: u32,
+        columns: Vec<ColumnDef>,
+    ) -> Result<Vec<ProductValue>, DBError> {
+        self.inner.alter_table_mut_tx(tx, TableId(table_id), columns)
+    }
+
     #[tracing::instrument(skip_all)]
     pub fn table_id_from_name(&self, tx: &MutTxId, table_name: &str) -> Result<Option<u32>, DBError> {
         self.inner
@@ -420,6 +759,12 @@ impl RelationalDB {
         self.inner.table_name_from_id_mut_tx(tx, TableId(table_id))
     }
 
+    /// Returns the [TableStats] of the table, as seen by `tx`, used to plan the queries over it.
+    #[tracing::instrument(skip_all)]
+    pub fn table_stats(&self, tx: &MutTxId, table_id: u32) -> Result<TableStats, DBError> {
+        self.inner.table_stats_mut_tx(tx, TableId(table_id))
+    }
+
     #[tracing::instrument(skip_all)]
     pub fn column_attrs(
         &self,
@@ -627,7 +972,12 @@ pub fn open_db(path: impl AsRef<Path>, in_memory: bool, fsync: bool) -> Result<R
         Some(Arc::new(Mutex::new(MessageLog::open(path.join("mlog"))?)))
     };
     let odb = Arc::new(Mutex::new(make_default_ostorage(in_memory, path.join("odb"))?));
-    let stdb = RelationalDB::open(path, mlog, odb, Address::zero(), fsync)?;
+    let fsync = if fsync {
+        FsyncPolicy::EveryTx
+    } else {
+        FsyncPolicy::Never
+    };
+    let stdb = RelationalDB::open(path, mlog, odb, Address::zero(), fsync, None, false)?;
 
     Ok(stdb)
 }
@@ -657,31 +1007,42 @@ mod tests {
     #![allow(clippy::disallowed_macros)]
 
     use nonempty::NonEmpty;
+    use std::collections::HashMap;
     use std::sync::{Arc, Mutex};
 
     use crate::address::Address;
+    use crate::db::commit_log::RestorePoint;
     use crate::db::datastore::locking_tx_datastore::IterByColEq;
     use crate::db::datastore::system_tables::StIndexRow;
     use crate::db::datastore::system_tables::StSequenceRow;
     use crate::db::datastore::system_tables::StTableRow;
     use crate::db::datastore::system_tables::ST_INDEXES_ID;
+    use crate::db::datastore::system_tables::ST_INDEX_ROW_TYPE;
     use crate::db::datastore::system_tables::ST_SEQUENCES_ID;
     use crate::db::datastore::traits::ColId;
     use crate::db::datastore::traits::ColumnDef;
     use crate::db::datastore::traits::IndexDef;
     use crate::db::datastore::traits::TableDef;
     use crate::db::message_log::MessageLog;
+    use crate::db::messages::write::Operation;
+    use crate::db::migration::TableMigration;
     use crate::db::relational_db::{open_db, ST_TABLES_ID};
+    use crate::db::snapshot::SnapshotStore;
+    use crate::db::FsyncPolicy;
 
     use super::RelationalDB;
     use crate::db::relational_db::make_default_ostorage;
     use crate::db::relational_db::tests_utils::make_test_db;
     use crate::error::{DBError, DatabaseError, IndexError};
+    use crate::host::Timestamp;
     use spacetimedb_lib::auth::StAccess;
     use spacetimedb_lib::auth::StTableType;
+    use spacetimedb_lib::data_key::DataKey;
     use spacetimedb_lib::error::ResultTest;
-    use spacetimedb_lib::{AlgebraicType, AlgebraicValue, ProductType};
+    use spacetimedb_lib::hash::hash_bytes;
+    use spacetimedb_lib::{AlgebraicType, AlgebraicValue, IndexType, ProductType, ProductValue};
     use spacetimedb_sats::product;
+    use tempdir::TempDir;
 
     fn column(name: &str, ty: AlgebraicType) -> ColumnDef {
         ColumnDef {
@@ -697,6 +1058,7 @@ mod tests {
             cols: NonEmpty::collect(cols.iter().copied()).unwrap(),
             name: name.to_string(),
             is_unique: false,
+            index_type: IndexType::BTree,
         }
     }
 
@@ -742,7 +1104,7 @@ mod tests {
             tmp_dir.path().join("odb"),
         )?));
 
-        match RelationalDB::open(tmp_dir.path(), mlog, odb, Address::zero(), true) {
+        match RelationalDB::open(tmp_dir.path(), mlog, odb, Address::zero(), FsyncPolicy::EveryTx, None, false) {
             Ok(_) => {
                 panic!("Allowed to open database twice")
             }
@@ -757,6 +1119,224 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_read_only_concurrent_with_tx() -> ResultTest<()> {
+        let (stdb, _tmp_dir) = make_test_db()?;
+
+        let mut tx = stdb.begin_tx();
+        let mut schema = TableDef::from(ProductType::from([("my_col", AlgebraicType::I32)]));
+        schema.table_name = "MyTable".to_string();
+        let table_id = stdb.create_table(&mut tx, schema)?;
+        stdb.insert(&mut tx, table_id, product![AlgebraicValue::I32(1)])?;
+        stdb.commit_tx(tx)?;
+
+        // Hold the lock of a mutable tx, while reading the committed state in another thread.
+        let mut tx = stdb.begin_tx();
+        stdb.insert(&mut tx, table_id, product![AlgebraicValue::I32(2)])?;
+        let rows = std::thread::scope(|s| {
+            s.spawn(|| stdb.with_read_only(|tx| Ok::<_, DBError>(stdb.iter(tx, table_id)?.count())))
+                .join()
+                .unwrap()
+        })?;
+        assert_eq!(rows, 1);
+        stdb.commit_tx(tx)?;
+
+        let rows = stdb.with_read_only(|tx| Ok::<_, DBError>(stdb.iter(tx, table_id)?.count()))?;
+        assert_eq!(rows, 2);
+        Ok(())
+    }
+
+    fn open_with_segment_size(
+        path: &std::path::Path,
+        max_segment_size: u64,
+        snapshot_interval: Option<u64>,
+        compact_log: bool,
+    ) -> Result<RelationalDB, DBError> {
+        let mlog = MessageLog::options()
+            .max_segment_size(max_segment_size)
+            .open(path.join("mlog"))?;
+        let odb = make_default_ostorage(false, path.join("odb"))?;
+        RelationalDB::open(
+            path,
+            Some(Arc::new(Mutex::new(mlog))),
+            Arc::new(Mutex::new(odb)),
+            Address::zero(),
+            FsyncPolicy::Never,
+            snapshot_interval,
+            compact_log,
+        )
+    }
+
+    fn my_table(stdb: &RelationalDB) -> ResultTest<u32> {
+        let mut tx = stdb.begin_tx();
+        let mut schema = TableDef::from(ProductType::from([("my_col", AlgebraicType::I32)]));
+        schema.table_name = "MyTable".to_string();
+        let table_id = stdb.create_table(&mut tx, schema)?;
+        stdb.commit_tx(tx)?;
+        Ok(table_id)
+    }
+
+    fn insert_i32(stdb: &RelationalDB, table_id: u32, value: i32) -> ResultTest<()> {
+        let mut tx = stdb.begin_tx();
+        stdb.insert(&mut tx, table_id, product![AlgebraicValue::I32(value)])?;
+        stdb.commit_tx(tx)?;
+        Ok(())
+    }
+
+    fn i32_rows(stdb: &RelationalDB, table_id: u32) -> ResultTest<Vec<i32>> {
+        let mut rows = stdb.with_read_only(|tx| {
+            Ok::<_, DBError>(
+                stdb.iter(tx, table_id)?
+                    .map(|r| *r.view().elements[0].as_i32().unwrap())
+                    .collect::<Vec<_>>(),
+            )
+        })?;
+        rows.sort();
+        Ok(rows)
+    }
+
+    #[test]
+    fn test_snapshot_reopen() -> ResultTest<()> {
+        let (stdb, tmp_dir) = make_test_db()?;
+        let table_id = my_table(&stdb)?;
+        insert_i32(&stdb, table_id, 1)?;
+
+        assert_eq!(stdb.take_snapshot()?, Some(2));
+        insert_i32(&stdb, table_id, 2)?;
+        drop(stdb);
+
+        let stdb = open_db(&tmp_dir, false, true)?;
+        assert_eq!(i32_rows(&stdb, table_id)?, [1, 2]);
+
+        // The commits after the snapshot continue the log.
+        insert_i32(&stdb, table_id, 3)?;
+        let offsets = stdb
+            .commit_log()
+            .iter()
+            .map(|commit| commit.map(|commit| commit.commit_offset))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(offsets, [0, 1, 2, 3]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_in_memory() -> ResultTest<()> {
+        let tmp_dir = TempDir::new("stdb_test")?;
+        let stdb = open_db(&tmp_dir, true, false)?;
+        assert_eq!(stdb.take_snapshot()?, None);
+        assert_eq!(stdb.compact_commit_log()?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_interval_and_compaction() -> ResultTest<()> {
+        let tmp_dir = TempDir::new("stdb_test")?;
+        let stdb = open_with_segment_size(tmp_dir.path(), 512, Some(10), true)?;
+        let table_id = my_table(&stdb)?;
+        for value in 0..50 {
+            insert_i32(&stdb, table_id, value)?;
+            stdb.wait_for_snapshot();
+        }
+
+        // A snapshot is taken every 10 commits, and the latest two are kept.
+        let snapshots = SnapshotStore::open(tmp_dir.path().join("snapshots"))?;
+        assert_eq!(snapshots.offsets()?, [40, 50]);
+
+        // Each snapshot compacted the log the oldest retained one covers.
+        let first_commit = stdb.commit_log().iter().next().unwrap()?;
+        assert!(first_commit.commit_offset > 0 && first_commit.commit_offset <= 40);
+        assert_eq!(stdb.compact_commit_log()?, 0);
+        drop(stdb);
+
+        // Opening restores the snapshot, then replays what's left of the log.
+        let stdb = open_with_segment_size(tmp_dir.path(), 512, None, false)?;
+        insert_i32(&stdb, table_id, 50)?;
+        assert_eq!(i32_rows(&stdb, table_id)?, (0..=50).collect::<Vec<_>>());
+        drop(stdb);
+
+        // If the latest snapshot is corrupt, the older one still has the log it needs.
+        let latest = tmp_dir.path().join("snapshots").join(format!("{:0>20}.snapshot", 50));
+        std::fs::write(latest, b"corrupt")?;
+        let stdb = open_with_segment_size(tmp_dir.path(), 512, None, false)?;
+        assert_eq!(i32_rows(&stdb, table_id)?, (0..=50).collect::<Vec<_>>());
+        drop(stdb);
+
+        // Without the snapshots, the compacted log can't be replayed.
+        std::fs::remove_dir_all(tmp_dir.path().join("snapshots"))?;
+        assert!(open_with_segment_size(tmp_dir.path(), 512, None, false).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_interval_without_compaction() -> ResultTest<()> {
+        let tmp_dir = TempDir::new("stdb_test")?;
+        let stdb = open_with_segment_size(tmp_dir.path(), 512, Some(10), false)?;
+        let table_id = my_table(&stdb)?;
+        for value in 0..50 {
+            insert_i32(&stdb, table_id, value)?;
+            stdb.wait_for_snapshot();
+        }
+        let snapshots = SnapshotStore::open(tmp_dir.path().join("snapshots"))?;
+        assert_eq!(snapshots.offsets()?, [40, 50]);
+
+        // The whole history is still in the log.
+        let first_commit = stdb.commit_log().iter().next().unwrap()?;
+        assert_eq!(first_commit.commit_offset, 0);
+        Ok(())
+    }
+
+    fn restore(stdb: &RelationalDB, point: RestorePoint, path: &std::path::Path) -> ResultTest<(u64, RelationalDB)> {
+        let mut mlog = MessageLog::open(path.join("mlog"))?;
+        let mut odb = make_default_ostorage(false, path.join("odb"))?;
+        let end = stdb.restore_into(point, path, &mut mlog, &mut *odb)?;
+        drop((mlog, odb));
+        Ok((end, open_db(path, false, false)?))
+    }
+
+    #[test]
+    fn test_restore() -> ResultTest<()> {
+        let (stdb, _tmp_dir) = make_test_db()?;
+        // The table is created at commit offset 0, and `value` inserted at `value + 1`.
+        let table_id = my_table(&stdb)?;
+        for value in 0..5 {
+            insert_i32(&stdb, table_id, value)?;
+        }
+        assert_eq!(stdb.take_snapshot()?, Some(6));
+        for value in 5..8 {
+            insert_i32(&stdb, table_id, value)?;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        let time = Timestamp::now();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        insert_i32(&stdb, table_id, 8)?;
+
+        let tmp_dir = TempDir::new("stdb_restore")?;
+        let (end, fork) = restore(&stdb, RestorePoint::Offset(3), &tmp_dir.path().join("offset"))?;
+        assert_eq!(end, 4);
+        assert_eq!(i32_rows(&fork, table_id)?, [0, 1, 2]);
+
+        // The snapshot is used for the commits it covers.
+        let path = tmp_dir.path().join("snapshot");
+        let (end, fork) = restore(&stdb, RestorePoint::Time(time), &path)?;
+        assert_eq!(end, 9);
+        assert_eq!(i32_rows(&fork, table_id)?, (0..8).collect::<Vec<_>>());
+        assert_eq!(SnapshotStore::open(path.join("snapshots"))?.offsets()?, [6]);
+
+        // A fork goes its own way.
+        let (end, fork) = restore(&stdb, RestorePoint::Latest, &tmp_dir.path().join("latest"))?;
+        assert_eq!(end, 10);
+        insert_i32(&fork, table_id, 100)?;
+        assert_eq!(i32_rows(&fork, table_id)?, [0, 1, 2, 3, 4, 5, 6, 7, 8, 100]);
+        assert_eq!(i32_rows(&stdb, table_id)?, (0..9).collect::<Vec<_>>());
+
+        let path = tmp_dir.path().join("past");
+        let mut mlog = MessageLog::open(path.join("mlog"))?;
+        let mut odb = make_default_ostorage(false, path.join("odb"))?;
+        let past = stdb.restore_into(RestorePoint::Offset(10), &path, &mut mlog, &mut *odb);
+        assert!(matches!(past, Err(DBError::InvalidRestorePoint(_))));
+        Ok(())
+    }
+
     #[test]
     fn test_table_name() -> ResultTest<()> {
         let (stdb, _tmp_dir) = make_test_db()?;
@@ -1083,6 +1663,7 @@ mod tests {
                 cols: NonEmpty::new(0),
                 name: "MyTable_my_col_idx".to_string(),
                 is_unique: false,
+                index_type: IndexType::BTree,
             }],
             table_type: StTableType::User,
             table_access: StAccess::Public,
@@ -1108,6 +1689,127 @@ mod tests {
         Ok(())
     }
 
+    /// Creates `MyTable` with an index on its only column and one row,
+    /// then rewrites the log as it was before `st_indexes` had its `index_type` column.
+    fn make_legacy_st_indexes_db() -> ResultTest<(TempDir, u32)> {
+        let (stdb, tmp_dir) = make_test_db()?;
+
+        let mut tx = stdb.begin_tx();
+        let schema = TableDef {
+            table_name: "MyTable".to_string(),
+            columns: vec![column("my_col", AlgebraicType::I64)],
+            indexes: vec![index("MyTable_my_col_idx", &[0])],
+            table_type: StTableType::User,
+            table_access: StAccess::Public,
+        };
+        let table_id = stdb.create_table(&mut tx, schema)?;
+        stdb.insert(&mut tx, table_id, product![AlgebraicValue::I64(1)])?;
+        stdb.commit_tx(tx)?;
+        let commits = stdb.commit_log().iter().collect::<Result<Vec<_>, _>>()?;
+        drop(stdb);
+
+        let path = tmp_dir.path();
+        let mut odb = make_default_ostorage(false, path.join("odb"))?;
+        std::fs::remove_dir_all(path.join("mlog"))?;
+        let mut mlog = MessageLog::open(path.join("mlog"))?;
+        let mut parent = None;
+        let mut legacy_rows = 0;
+        for mut commit in commits {
+            for tx in &mut commit.transactions {
+                for write in &mut Arc::make_mut(tx).writes {
+                    if write.set_id != ST_INDEXES_ID.0 || !matches!(write.operation, Operation::Insert) {
+                        continue;
+                    }
+                    let bytes = match write.data_key {
+                        DataKey::Data(data) => data.to_vec(),
+                        DataKey::Hash(hash) => odb.get(hash).unwrap().to_vec(),
+                    };
+                    let mut row = ProductValue::decode(&ST_INDEX_ROW_TYPE, &mut &bytes[..]).unwrap();
+                    row.elements.pop();
+                    let mut bytes = Vec::new();
+                    row.encode(&mut bytes);
+                    write.data_key = DataKey::from_data(&bytes);
+                    if let DataKey::Hash(_) = write.data_key {
+                        odb.add(bytes);
+                    }
+                    legacy_rows += 1;
+                }
+            }
+            commit.parent_commit_hash = parent;
+            let mut bytes = Vec::new();
+            commit.encode(&mut bytes);
+            parent = Some(hash_bytes(&bytes));
+            mlog.append(bytes)?;
+        }
+        assert_eq!(legacy_rows, 1);
+        mlog.sync_all()?;
+        Ok((tmp_dir, table_id))
+    }
+
+    #[test]
+    fn test_replay_st_indexes_rows_without_index_type() -> ResultTest<()> {
+        let (tmp_dir, table_id) = make_legacy_st_indexes_db()?;
+        let path = tmp_dir.path();
+
+        let stdb = open_db(path, false, false)?;
+        let mut tx = stdb.begin_tx();
+        let index_id = stdb.index_id_from_name(&tx, "MyTable_my_col_idx")?;
+        assert!(index_id.is_some(), "Index not replayed");
+        let schema = stdb.schema_for_table(&tx, table_id)?;
+        assert_eq!(schema.indexes.len(), 1);
+        assert_eq!(schema.indexes[0].index_type, IndexType::BTree);
+        stdb.insert(&mut tx, table_id, product![AlgebraicValue::I64(2)])?;
+        let rows = stdb
+            .iter_by_col_range(&tx, table_id, ColId(0), AlgebraicValue::I64(0)..)?
+            .map(|r| *r.view().elements[0].as_i64().unwrap())
+            .collect::<Vec<i64>>();
+        assert_eq!(rows, vec![1, 2]);
+        stdb.commit_tx(tx)?;
+        drop(stdb);
+
+        // The log now holds rows of both shapes.
+        let stdb = open_db(path, false, false)?;
+        let tx = stdb.begin_tx();
+        assert!(stdb.index_id_from_name(&tx, "MyTable_my_col_idx")?.is_some());
+        assert_eq!(stdb.iter(&tx, table_id)?.count(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_drop_replayed_st_indexes_rows_without_index_type() -> ResultTest<()> {
+        let (tmp_dir, table_id) = make_legacy_st_indexes_db()?;
+        let path = tmp_dir.path();
+
+        let stdb = open_db(path, false, false)?;
+        let mut tx = stdb.begin_tx();
+        let index_id = stdb.index_id_from_name(&tx, "MyTable_my_col_idx")?.unwrap();
+        stdb.drop_index(&mut tx, index_id)?;
+        stdb.commit_tx(tx)?;
+        drop(stdb);
+
+        let stdb = open_db(path, false, false)?;
+        let tx = stdb.begin_tx();
+        assert!(stdb.index_id_from_name(&tx, "MyTable_my_col_idx")?.is_none());
+        assert!(stdb.schema_for_table(&tx, table_id)?.indexes.is_empty());
+        stdb.rollback_tx(tx);
+        drop(stdb);
+
+        // Indexes dropped with their table stay dropped too.
+        let (tmp_dir, table_id) = make_legacy_st_indexes_db()?;
+        let path = tmp_dir.path();
+        let stdb = open_db(path, false, false)?;
+        let mut tx = stdb.begin_tx();
+        stdb.drop_table(&mut tx, table_id)?;
+        stdb.commit_tx(tx)?;
+        drop(stdb);
+
+        let stdb = open_db(path, false, false)?;
+        let tx = stdb.begin_tx();
+        assert!(stdb.index_id_from_name(&tx, "MyTable_my_col_idx")?.is_none());
+        assert!(stdb.table_id_from_name(&tx, "MyTable")?.is_none());
+        Ok(())
+    }
+
     #[test]
     fn test_unique() -> ResultTest<()> {
         let (stdb, _tmp_dir) = make_test_db()?;
@@ -1125,6 +1827,7 @@ mod tests {
                 cols: NonEmpty::new(0),
                 name: "MyTable_my_col_idx".to_string(),
                 is_unique: true,
+                index_type: IndexType::BTree,
             }],
             table_type: StTableType::User,
             table_access: StAccess::Public,
@@ -1155,6 +1858,46 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_unique_hash_reload() -> ResultTest<()> {
+        let (stdb, tmp_dir) = make_test_db()?;
+
+        let mut tx = stdb.begin_tx();
+        let schema = TableDef {
+            table_name: "MyTable".to_string(),
+            columns: vec![ColumnDef {
+                col_name: "my_col".to_string(),
+                col_type: AlgebraicType::I64,
+                is_autoinc: false,
+            }],
+            indexes: vec![IndexDef::new("MyTable_my_col_idx".to_string(), 0, 0, true).with_index_type(IndexType::Hash)],
+            table_type: StTableType::User,
+            table_access: StAccess::Public,
+        };
+        let table_id = stdb.create_table(&mut tx, schema)?;
+        stdb.insert(&mut tx, table_id, product![AlgebraicValue::I64(1)])?;
+        stdb.commit_tx(tx)?;
+        drop(stdb);
+
+        // The index type is restored from `st_indexes`.
+        let stdb = open_db(&tmp_dir, false, true)?;
+        let mut tx = stdb.begin_tx();
+        let schema = stdb.schema_for_table(&tx, table_id)?;
+        assert_eq!(schema.indexes[0].index_type, IndexType::Hash);
+
+        let rows = stdb
+            .iter_by_col_eq(&tx, table_id, ColId(0), AlgebraicValue::I64(1))?
+            .map(|r| r.view().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(rows, vec![product![AlgebraicValue::I64(1)]]);
+
+        match stdb.insert(&mut tx, table_id, product![AlgebraicValue::I64(1)]) {
+            Err(DBError::Index(IndexError::UniqueConstraintViolation { .. })) => {}
+            res => panic!("Expected error `UniqueConstraintViolation`, got {res:?}"),
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_identity() -> ResultTest<()> {
         let (stdb, _tmp_dir) = make_test_db()?;
@@ -1172,6 +1915,7 @@ mod tests {
                 cols: NonEmpty::new(0),
                 name: "MyTable_my_col_idx".to_string(),
                 is_unique: true,
+                index_type: IndexType::BTree,
             }],
             table_type: StTableType::User,
             table_access: StAccess::Public,
@@ -1235,18 +1979,21 @@ mod tests {
                     cols: NonEmpty::new(0),
                     name: "MyTable_col1_idx".to_string(),
                     is_unique: true,
+                    index_type: IndexType::BTree,
                 },
                 IndexDef {
                     table_id: 0,
                     cols: NonEmpty::new(2),
                     name: "MyTable_col3_idx".to_string(),
                     is_unique: false,
+                    index_type: IndexType::BTree,
                 },
                 IndexDef {
                     table_id: 0,
                     cols: NonEmpty::new(3),
                     name: "MyTable_col4_idx".to_string(),
                     is_unique: true,
+                    index_type: IndexType::BTree,
                 },
             ],
             table_type: StTableType::User,
@@ -1305,6 +2052,7 @@ mod tests {
                 cols: NonEmpty::new(0),
                 name: "MyTable_my_col_idx".to_string(),
                 is_unique: true,
+                index_type: IndexType::BTree,
             }],
             table_type: StTableType::User,
             table_access: StAccess::Public,
@@ -1327,6 +2075,86 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_alter_table_reload() -> ResultTest<()> {
+        let (stdb, tmp_dir) = make_test_db()?;
+
+        let mut tx = stdb.begin_tx();
+        let mut id_index = index("MyTable_id_unique", &[0]);
+        id_index.is_unique = true;
+        let old_columns = vec![column("id", AlgebraicType::U32), column("score", AlgebraicType::I8)];
+        let table_id = stdb.create_table(&mut tx, table("MyTable", old_columns.clone(), vec![id_index]))?;
+        stdb.insert(&mut tx, table_id, product![1u32, -3i8])?;
+        stdb.insert(&mut tx, table_id, product![2u32, 5i8])?;
+        stdb.commit_tx(tx)?;
+
+        let new_columns = vec![
+            column("id", AlgebraicType::U32),
+            column("score", AlgebraicType::I64),
+            column("name", AlgebraicType::String),
+        ];
+        let migration = TableMigration::new(&old_columns, &new_columns, &HashMap::new()).unwrap();
+        let mut tx = stdb.begin_tx();
+        for row in stdb.alter_table(&mut tx, table_id, new_columns.clone())? {
+            stdb.insert(&mut tx, table_id, migration.migrate_row(row))?;
+        }
+        // An index on a new column is built from the migrated rows only.
+        let mut name_index = index("MyTable_name_idx", &[2]);
+        name_index.table_id = table_id;
+        stdb.create_index(&mut tx, name_index)?;
+        stdb.commit_tx(tx)?;
+
+        let expected = vec![product![1u32, -3i64, ""], product![2u32, 5i64, ""]];
+        let tx = stdb.begin_tx();
+        let mut rows = stdb.iter(&tx, table_id)?.map(|r| r.view().clone()).collect::<Vec<_>>();
+        rows.sort();
+        assert_eq!(rows, expected);
+        stdb.rollback_tx(tx);
+        drop(stdb);
+
+        // The rows of the altered table are replayed from the log with its new columns.
+        let stdb = open_db(&tmp_dir, false, true)?;
+        let mut tx = stdb.begin_tx();
+        let schema = stdb.schema_for_table(&tx, table_id)?;
+        assert_eq!(
+            schema.columns.iter().cloned().map(ColumnDef::from).collect::<Vec<_>>(),
+            new_columns
+        );
+        let mut rows = stdb
+            .iter_by_col_eq(&tx, table_id, ColId(2), AlgebraicValue::String("".into()))?
+            .map(|r| r.view().clone())
+            .collect::<Vec<_>>();
+        rows.sort();
+        assert_eq!(rows, expected);
+
+        match stdb.insert(&mut tx, table_id, product![1u32, 0i64, "a"]) {
+            Err(DBError::Index(IndexError::UniqueConstraintViolation { .. })) => {}
+            res => panic!("Expected error `UniqueConstraintViolation`, got {res:?}"),
+        }
+        stdb.rollback_tx(tx);
+
+        // An index dropped after altering the table is dropped from its new rows as well.
+        let mut tx = stdb.begin_tx();
+        let mut newer_columns = new_columns.clone();
+        newer_columns.push(column("level", AlgebraicType::U8));
+        let migration = TableMigration::new(&new_columns, &newer_columns, &HashMap::new()).unwrap();
+        for row in stdb.alter_table(&mut tx, table_id, newer_columns)? {
+            stdb.insert(&mut tx, table_id, migration.migrate_row(row))?;
+        }
+        let index_id = stdb.index_id_from_name(&tx, "MyTable_name_idx")?.unwrap();
+        stdb.drop_index(&mut tx, index_id)?;
+        stdb.commit_tx(tx)?;
+
+        let tx = stdb.begin_tx();
+        let indexed_cols = stdb
+            .table_stats(&tx, table_id)?
+            .distinct_values
+            .into_keys()
+            .collect::<Vec<_>>();
+        assert_eq!(indexed_cols, [NonEmpty::new(0)]);
+        Ok(())
+    }
+
     #[test]
     fn test_multi_column_index() -> ResultTest<()> {
         let (stdb, _tmp_dir) = make_test_db()?;
@@ -0,0 +1,270 @@
+//! Snapshots of the committed tables, so that opening a database replays only
+//! the suffix of the [`MessageLog`](super::message_log::MessageLog) after the
+//! latest snapshot.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context};
+
+use crate::db::datastore::traits::TableId;
+use crate::error::DBError;
+use crate::hash::{hash_bytes, Hash, HASH_SIZE};
+
+const MAGIC: &[u8; 8] = b"STDBSNAP";
+const EXTENSION: &str = "snapshot";
+
+/// The rows of a committed table, as of a [`Snapshot`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotTable {
+    pub table_id: TableId,
+    /// The BSATN encoded rows of the table.
+    pub rows: Vec<Vec<u8>>,
+}
+
+/// The state of the committed tables after all the commits before `commit_offset`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// The offset of the first commit not covered by the snapshot.
+    pub commit_offset: u64,
+    /// The offset of the first transaction not covered by the snapshot.
+    pub tx_offset: u64,
+    /// The hash of the last commit covered by the snapshot.
+    pub last_commit_hash: Option<Hash>,
+    /// The tables, in ascending order of their id, so the system tables come first.
+    pub tables: Vec<SnapshotTable>,
+}
+
+// snapshot: <magic(8)><commit_offset(8)><tx_offset(8)><last_commit_hash(1|33)><n_tables(4)>[<table>...]*<hash(32)>
+// table: <table_id(4)><n_rows(8)>[<row_len(4)><row>...]*
+//
+// The trailing hash covers all the preceding bytes, so a torn write is detected.
+impl Snapshot {
+    pub fn encode(&self, bytes: &mut Vec<u8>) {
+        let start = bytes.len();
+        bytes.extend(MAGIC);
+        bytes.extend(self.commit_offset.to_le_bytes());
+        bytes.extend(self.tx_offset.to_le_bytes());
+        match self.last_commit_hash {
+            None => bytes.push(0),
+            Some(hash) => {
+                bytes.push(1);
+                bytes.extend(hash.data);
+            }
+        }
+
+        bytes.extend((self.tables.len() as u32).to_le_bytes());
+        for table in &self.tables {
+            bytes.extend(table.table_id.0.to_le_bytes());
+            bytes.extend((table.rows.len() as u64).to_le_bytes());
+            for row in &table.rows {
+                bytes.extend((row.len() as u32).to_le_bytes());
+                bytes.extend(row);
+            }
+        }
+
+        let hash = hash_bytes(&bytes[start..]);
+        bytes.extend(hash.data);
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, DBError> {
+        let Some(len) = bytes.len().checked_sub(HASH_SIZE) else {
+            return Err(anyhow!("Snapshot is truncated").into());
+        };
+        let (bytes, hash) = bytes.split_at(len);
+        if hash_bytes(bytes).data != hash {
+            return Err(anyhow!("Snapshot hash mismatch").into());
+        }
+
+        let mut reader = Reader { bytes };
+        if reader.read(MAGIC.len())? != MAGIC {
+            return Err(anyhow!("Not a snapshot").into());
+        }
+        let commit_offset = reader.read_u64()?;
+        let tx_offset = reader.read_u64()?;
+        let last_commit_hash = match reader.read(1)?[0] {
+            0 => None,
+            _ => Some(Hash::from_slice(reader.read(HASH_SIZE)?)),
+        };
+
+        let n_tables = reader.read_u32()?;
+        let mut tables = Vec::with_capacity(n_tables as usize);
+        for _ in 0..n_tables {
+            let table_id = TableId(reader.read_u32()?);
+            let n_rows = reader.read_u64()?;
+            let rows = (0..n_rows)
+                .map(|_| {
+                    let len = reader.read_u32()?;
+                    reader.read(len as usize).map(<[u8]>::to_vec)
+                })
+                .collect::<Result<_, _>>()?;
+            tables.push(SnapshotTable { table_id, rows });
+        }
+
+        Ok(Self {
+            commit_offset,
+            tx_offset,
+            last_commit_hash,
+            tables,
+        })
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn read(&mut self, n: usize) -> Result<&'a [u8], DBError> {
+        if self.bytes.len() < n {
+            return Err(anyhow!("Snapshot is truncated").into());
+        }
+        let (read, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(read)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, DBError> {
+        Ok(u32::from_le_bytes(self.read(4)?.try_into().unwrap()))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, DBError> {
+        Ok(u64::from_le_bytes(self.read(8)?.try_into().unwrap()))
+    }
+}
+
+/// The directory of the [`Snapshot`]s of a database, one file per snapshot
+/// named after its `commit_offset`.
+#[derive(Debug, Clone)]
+pub struct SnapshotStore {
+    root: PathBuf,
+}
+
+impl SnapshotStore {
+    /// How many of the latest snapshots are kept when writing a new one.
+    const RETAIN: usize = 2;
+
+    pub fn open(root: impl AsRef<Path>) -> Result<Self, DBError> {
+        let root = root.as_ref();
+        fs::create_dir_all(root).with_context(|| format!("could not create snapshot directory: {}", root.display()))?;
+        Ok(Self { root: root.to_owned() })
+    }
+
+    fn path(&self, commit_offset: u64) -> PathBuf {
+        self.root
+            .join(format!("{:0>20}", commit_offset))
+            .with_extension(EXTENSION)
+    }
+
+    /// Returns the commit offsets of the snapshots in the store, in ascending order.
+    pub fn offsets(&self) -> Result<Vec<u64>, DBError> {
+        let mut offsets = Vec::new();
+        for entry in fs::read_dir(&self.root)? {
+            let path = entry?.path();
+            if path.extension().map_or(true, |ext| ext != EXTENSION) {
+                continue;
+            }
+            if let Some(offset) = path.file_stem().and_then(|stem| stem.to_str()?.parse().ok()) {
+                offsets.push(offset);
+            }
+        }
+        offsets.sort_unstable();
+        Ok(offsets)
+    }
+
+    /// Durably writes the `snapshot`, then removes the snapshots older than the latest [`Self::RETAIN`].
+    #[tracing::instrument(skip_all, fields(commit_offset = snapshot.commit_offset))]
+    pub fn write(&self, snapshot: &Snapshot) -> Result<(), DBError> {
+        let mut bytes = Vec::new();
+        snapshot.encode(&mut bytes);
+
+        // Write to a temporary file first, so a crash never leaves a partial snapshot behind.
+        let path = self.path(snapshot.commit_offset);
+        let tmp_path = path.with_extension("tmp");
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &path)?;
+        #[cfg(target_family = "unix")]
+        File::open(&self.root)?.sync_all()?;
+
+        let offsets = self.offsets()?;
+        for offset in &offsets[..offsets.len().saturating_sub(Self::RETAIN)] {
+            fs::remove_file(self.path(*offset))?;
+        }
+        Ok(())
+    }
+
+    /// Reads the snapshot of `commit_offset`.
+    pub fn read(&self, commit_offset: u64) -> Result<Snapshot, DBError> {
+        let path = self.path(commit_offset);
+        let bytes = fs::read(&path).with_context(|| format!("could not read snapshot: {}", path.display()))?;
+        Snapshot::decode(&bytes)
+    }
+
+    /// Reads the latest snapshot, skipping over the ones that fail to decode.
+    pub fn latest(&self) -> Result<Option<Snapshot>, DBError> {
+        for offset in self.offsets()?.into_iter().rev() {
+            match self.read(offset) {
+                Ok(snapshot) => return Ok(Some(snapshot)),
+                Err(e) => log::warn!("Skipping snapshot at commit offset {offset}: {e}"),
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    fn snapshot(commit_offset: u64) -> Snapshot {
+        Snapshot {
+            commit_offset,
+            tx_offset: commit_offset * 2,
+            last_commit_hash: Some(hash_bytes(commit_offset.to_le_bytes())),
+            tables: vec![
+                SnapshotTable {
+                    table_id: TableId(0),
+                    rows: vec![vec![1, 2, 3], vec![]],
+                },
+                SnapshotTable {
+                    table_id: TableId(4096),
+                    rows: vec![vec![4; 300]],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip() -> Result<(), DBError> {
+        let snapshot = snapshot(42);
+        let mut bytes = Vec::new();
+        snapshot.encode(&mut bytes);
+        assert_eq!(Snapshot::decode(&bytes)?, snapshot);
+
+        // A torn write is detected.
+        assert!(Snapshot::decode(&bytes[..bytes.len() - 1]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_store_latest() -> Result<(), DBError> {
+        let tmp = TempDir::new("snapshot_test")?;
+        let store = SnapshotStore::open(tmp.path())?;
+        assert_eq!(store.latest()?, None);
+
+        for offset in [10, 20, 30] {
+            store.write(&snapshot(offset))?;
+        }
+        assert_eq!(store.offsets()?, [20, 30]);
+        assert_eq!(store.latest()?, Some(snapshot(30)));
+
+        // A corrupt snapshot falls back to the previous one.
+        fs::write(store.path(30), b"garbage")?;
+        assert_eq!(store.latest()?, Some(snapshot(20)));
+        Ok(())
+    }
+}
@@ -85,6 +85,24 @@ pub enum SubscriptionError {
     Empty,
     #[error("Queries with side effects not allowed: {0:?}")]
     SideEffect(Crud),
+    #[error("Subscriptions can't be ordered or limited: `{0}`")]
+    Unordered(String),
+    #[error("Subscriptions can't aggregate rows: `{0}`")]
+    Aggregated(String),
+    #[error("Query templates must select from a single table: `{0}`")]
+    UnsupportedTemplate(String),
+    #[error("Parameter `{param}` must be compared for equality with a column of the table of `{template}`")]
+    UnsupportedParameter { template: String, param: String },
+    #[error("Invalid value for the parameter `{param}` of `{template}`: {reason}")]
+    InvalidParameter {
+        template: String,
+        param: String,
+        reason: String,
+    },
+    #[error("Query id {0} already identifies a subscribed query")]
+    DuplicateQueryId(u32),
+    #[error("Query id {0} identifies no query added to the subscription")]
+    UnknownQueryId(u32),
 }
 
 #[derive(Error, Debug)]
@@ -104,6 +122,10 @@ pub enum PlanError {
     },
     #[error("Ambiguous field: `{field}`. Also found in {found:?}")]
     AmbiguousField { field: String, found: Vec<FieldName> },
+    #[error("Field `{field}` must appear in the `GROUP BY` clause or be used in an aggregate function")]
+    Ungrouped { field: FieldName },
+    #[error("No value is bound to the parameter `{name}`")]
+    UnboundParameter { name: String },
     #[error("Plan error: `{0}`")]
     Unstructured(String),
     #[error("Internal DBError: `{0}`")]
@@ -166,6 +188,8 @@ pub enum DBError {
     },
     #[error("SqlError: {error}, executing: `{sql}`")]
     Plan { sql: String, error: PlanError },
+    #[error("Invalid restore point: {0}")]
+    InvalidRestorePoint(String),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
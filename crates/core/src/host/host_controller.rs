@@ -6,6 +6,7 @@ use anyhow::Context;
 // use parking_lot::{Condvar, Mutex};
 use parking_lot::Mutex;
 use serde::Serialize;
+use spacetimedb_lib::name::SchemaDiff;
 use std::collections::HashMap;
 use std::fmt;
 use std::ops::Sub;
@@ -153,6 +154,7 @@ pub enum ReducerOutcome {
     Committed,
     Failed(String),
     BudgetExceeded,
+    Unauthorized(String),
 }
 
 impl ReducerOutcome {
@@ -161,6 +163,7 @@ impl ReducerOutcome {
             Self::Committed => Ok(()),
             Self::Failed(e) => Err(anyhow::anyhow!(e)),
             Self::BudgetExceeded => Err(anyhow::anyhow!("reducer ran out of energy")),
+            Self::Unauthorized(e) => Err(anyhow::anyhow!(e)),
         }
     }
 }
@@ -171,6 +174,7 @@ impl From<&EventStatus> for ReducerOutcome {
             EventStatus::Committed(_) => ReducerOutcome::Committed,
             EventStatus::Failed(e) => ReducerOutcome::Failed(e.clone()),
             EventStatus::OutOfEnergy => ReducerOutcome::BudgetExceeded,
+            EventStatus::Unauthorized(e) => ReducerOutcome::Unauthorized(e.clone()),
         }
     }
 }
@@ -238,6 +242,18 @@ impl HostController {
         })
     }
 
+    /// Computes the changes updating the database to the module of
+    /// `module_host_context` would make to its schema, without making them,
+    /// nor replacing the module host of the database.
+    pub async fn schema_diff(&self, module_host_context: ModuleHostContext) -> Result<SchemaDiff, anyhow::Error> {
+        // The scheduler of this module host is never started.
+        let (module_host, _) = self.make_module_host(module_host_context)?;
+        module_host.start();
+        let diff = module_host.schema_diff().await;
+        module_host.exit().await;
+        diff
+    }
+
     pub async fn add_module_host(&self, module_host_context: ModuleHostContext) -> Result<ModuleHost, anyhow::Error> {
         let module_host = self.spawn_module_host(module_host_context).await?;
         // module_host.init_function(); ??
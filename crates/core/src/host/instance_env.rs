@@ -1,6 +1,6 @@
 use nonempty::NonEmpty;
 use parking_lot::{Mutex, MutexGuard};
-use spacetimedb_lib::{bsatn, ProductValue};
+use spacetimedb_lib::{bsatn, IndexType, ProductValue};
 use std::ops::DerefMut;
 use std::sync::Arc;
 
@@ -18,6 +18,7 @@ use spacetimedb_lib::filter::CmpArgs;
 use spacetimedb_lib::identity::AuthCtx;
 use spacetimedb_lib::operator::OpQuery;
 use spacetimedb_lib::relation::{FieldExpr, FieldName};
+use spacetimedb_lib::schedule::{MisfirePolicy, Repeat};
 use spacetimedb_sats::{ProductType, Typespace};
 use spacetimedb_vm::expr::{Code, ColumnOp};
 
@@ -43,19 +44,29 @@ impl InstanceEnv {
         }
     }
 
+    /// Schedules `reducer` to be called with `args` at `time`,
+    /// and then as often as `repeat` says,
+    /// if the transaction of the current reducer commits.
     #[tracing::instrument(skip_all, fields(reducer=reducer))]
     pub fn schedule(
         &self,
         reducer: String,
         args: Vec<u8>,
         time: Timestamp,
+        repeat: Option<Repeat>,
+        misfire: MisfirePolicy,
     ) -> Result<ScheduledReducerId, ScheduleError> {
-        self.scheduler.schedule(reducer, args, time)
+        let tx = &mut *self.get_tx().map_err(NodesError::from)?;
+        self.scheduler.schedule(tx, reducer, args, time, repeat, misfire)
     }
 
+    /// Cancels the scheduled reducer `id`,
+    /// if the transaction of the current reducer commits.
     #[tracing::instrument(skip_all)]
-    pub fn cancel_reducer(&self, id: ScheduledReducerId) {
-        self.scheduler.cancel(id)
+    pub fn cancel_reducer(&self, id: ScheduledReducerId) -> Result<(), NodesError> {
+        let tx = &mut *self.get_tx()?;
+        self.scheduler.cancel(tx, id)?;
+        Ok(())
     }
 
     fn get_tx(&self) -> Result<impl DerefMut<Target = MutTxId> + '_, GetTxError> {
@@ -268,13 +279,10 @@ impl InstanceEnv {
         let stdb = &*self.dbic.relational_db;
         let tx = &mut *self.get_tx()?;
 
-        // TODO(george) This check should probably move towards src/db/index, but right
-        // now the API is pretty hardwired towards btrees.
-        //
         // TODO(george) Dedup the constant here.
-        match index_type {
-            0 => (),
-            1 => todo!("Hash indexes not yet supported"),
+        let index_type = match index_type {
+            0 => IndexType::BTree,
+            1 => IndexType::Hash,
             _ => return Err(NodesError::BadIndexType(index_type)),
         };
 
@@ -289,6 +297,7 @@ impl InstanceEnv {
             cols,
             name: index_name,
             is_unique,
+            index_type,
         };
 
         stdb.create_index(tx, index)?;
@@ -1,4 +1,5 @@
 use super::host_controller::HostThreadpool;
+use super::scheduler::ScheduledReducerId;
 use super::{ArgsTuple, EnergyDiff, InvalidReducerArguments, ReducerArgs, ReducerCallResult, Timestamp};
 use crate::client::ClientConnectionSender;
 use crate::database_logger::LogLevel;
@@ -9,16 +10,18 @@ use crate::hash::Hash;
 use crate::identity::Identity;
 use crate::json::client_api::{SubscriptionUpdateJson, TableRowOperationJson, TableUpdateJson};
 use crate::protobuf::client_api::{table_row_operation, SubscriptionUpdate, TableRowOperation, TableUpdate};
+use crate::sql::row_filter::RowFilters;
 use crate::subscription::module_subscription_actor::ModuleSubscriptionManager;
 use crate::util::lending_pool::{Closed, LendingPool, LentResource, PoolClosed};
 use crate::util::notify_once::NotifyOnce;
 use base64::{engine::general_purpose::STANDARD as BASE_64_STD, Engine as _};
 use futures::{Future, FutureExt};
 use indexmap::IndexMap;
+use spacetimedb_lib::name::SchemaDiff;
 use spacetimedb_lib::relation::MemTable;
-use spacetimedb_lib::{Address, ReducerDef, TableDef};
-use spacetimedb_sats::{ProductValue, Typespace, WithTypespace};
-use std::collections::HashMap;
+use spacetimedb_lib::{Address, ReducerAuthRule, ReducerDef, RepeatingReducer, TableDef};
+use spacetimedb_sats::{AlgebraicValue, ProductValue, Typespace, WithTypespace};
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::sync::{Arc, Weak};
 use std::time::Duration;
@@ -30,6 +33,26 @@ pub struct DatabaseUpdate {
 }
 
 impl DatabaseUpdate {
+    /// Adds the rows of `other` to `self`, skipping the ones it already has.
+    pub fn merge(&mut self, other: &DatabaseUpdate) {
+        for table in &other.tables {
+            let t = match self.tables.iter().position(|t| t.table_id == table.table_id) {
+                Some(i) => &mut self.tables[i],
+                None => {
+                    self.tables.push(DatabaseTableUpdate {
+                        table_id: table.table_id,
+                        table_name: table.table_name.clone(),
+                        ops: vec![],
+                    });
+                    self.tables.last_mut().unwrap()
+                }
+            };
+            let seen: HashSet<_> = t.ops.iter().map(|op| op.row_pk.clone()).collect();
+            t.ops
+                .extend(table.ops.iter().filter(|op| !seen.contains(&op.row_pk)).cloned());
+        }
+    }
+
     pub fn is_empty(&self) -> bool {
         if self.tables.len() == 0 {
             return true;
@@ -39,8 +62,7 @@ impl DatabaseUpdate {
 
     pub fn from_writes(stdb: &RelationalDB, tx_data: &TxData) -> Self {
         let mut map: HashMap<TableId, Vec<TableOp>> = HashMap::new();
-        //TODO: This should be wrapped with .auto_commit
-        let tx = stdb.begin_tx();
+        let tx = stdb.begin_read_tx();
         for record in tx_data.records.iter() {
             let op = match record.op {
                 TxOp::Delete => 0,
@@ -79,7 +101,7 @@ impl DatabaseUpdate {
                 ops: table_row_operations,
             });
         }
-        stdb.rollback_tx(tx);
+        stdb.release_tx(tx);
 
         DatabaseUpdate { tables: table_updates }
     }
@@ -111,6 +133,9 @@ impl DatabaseUpdate {
                         .collect(),
                 })
                 .collect(),
+            tx_offset: 0,
+            history_id: 0,
+            resumed: false,
         }
     }
 
@@ -142,6 +167,9 @@ impl DatabaseUpdate {
                         .collect(),
                 })
                 .collect(),
+            tx_offset: None,
+            history_id: None,
+            resumed: false,
         }
     }
 }
@@ -165,6 +193,9 @@ pub enum EventStatus {
     Committed(DatabaseUpdate),
     Failed(String),
     OutOfEnergy,
+    /// The caller isn't allowed to call the reducer by its authorization rule,
+    /// so it was never run.
+    Unauthorized(String),
 }
 
 impl EventStatus {
@@ -187,6 +218,9 @@ pub struct ModuleEvent {
     pub timestamp: Timestamp,
     pub caller_identity: Identity,
     pub caller_address: Option<Address>,
+    /// The id the caller gave the call in its `FunctionCall`, if any,
+    /// echoed back to it in the `TransactionUpdate` reporting the outcome.
+    pub request_id: Option<u32>,
     pub function_call: ModuleFunctionCall,
     pub status: EventStatus,
     pub energy_quanta_used: EnergyDiff,
@@ -200,6 +234,15 @@ pub struct ModuleInfo {
     pub module_hash: Hash,
     pub typespace: Typespace,
     pub reducers: IndexMap<String, ReducerDef>,
+    /// Who may call each reducer from a client; reducers without a rule may be called by anyone.
+    pub reducer_auth: HashMap<String, ReducerAuthRule>,
+    /// The rows of its tables that each client may read.
+    pub row_filters: Arc<RowFilters>,
+    /// The reducers that the host schedules to repeat, as declared by the module.
+    pub repeating_reducers: Vec<RepeatingReducer>,
+    /// The values declared by the module for its columns, by table and column name,
+    /// which fill the columns that an update appends to a table in the existing rows.
+    pub column_defaults: HashMap<String, HashMap<String, AlgebraicValue>>,
     pub catalog: HashMap<String, EntityDef>,
     pub log_tx: tokio::sync::broadcast::Sender<bytes::Bytes>,
     pub subscription: ModuleSubscriptionManager,
@@ -238,14 +281,24 @@ pub trait ModuleInstance: Send + 'static {
 
     fn update_database(&mut self, fence: u128) -> anyhow::Result<UpdateDatabaseResult>;
 
+    /// Computes the changes [`Self::update_database`] would make to the
+    /// schema of the database, without making them.
+    fn schema_diff(&self) -> anyhow::Result<SchemaDiff>;
+
     fn call_reducer(
         &mut self,
         caller_identity: Identity,
         caller_address: Option<Address>,
         client: Option<ClientConnectionSender>,
+        request_id: Option<u32>,
         reducer_id: usize,
         args: ArgsTuple,
     ) -> ReducerCallResult;
+
+    /// Calls the reducer scheduled as `id`, deleting it from `st_scheduled`,
+    /// or moving it to its next run if it repeats,
+    /// or returns `None` if it's no longer scheduled.
+    fn call_scheduled_reducer(&mut self, id: ScheduledReducerId) -> anyhow::Result<Option<ReducerCallResult>>;
 }
 
 // TODO: figure out how we want to handle traps. maybe it should just not return to the LendingPool and
@@ -277,17 +330,26 @@ impl<T: Module> ModuleInstance for AutoReplacingModuleInstance<T> {
         self.check_trap();
         ret
     }
+    fn schema_diff(&self) -> anyhow::Result<SchemaDiff> {
+        self.inst.schema_diff()
+    }
     fn call_reducer(
         &mut self,
         caller_identity: Identity,
         caller_address: Option<Address>,
         client: Option<ClientConnectionSender>,
+        request_id: Option<u32>,
         reducer_id: usize,
         args: ArgsTuple,
     ) -> ReducerCallResult {
         let ret = self
             .inst
-            .call_reducer(caller_identity, caller_address, client, reducer_id, args);
+            .call_reducer(caller_identity, caller_address, client, request_id, reducer_id, args);
+        self.check_trap();
+        ret
+    }
+    fn call_scheduled_reducer(&mut self, id: ScheduledReducerId) -> anyhow::Result<Option<ReducerCallResult>> {
+        let ret = self.inst.call_scheduled_reducer(id);
         self.check_trap();
         ret
     }
@@ -416,13 +478,12 @@ pub type UpdateDatabaseResult = Result<UpdateDatabaseSuccess, UpdateDatabaseErro
 
 #[derive(Debug)]
 pub struct UpdateDatabaseSuccess {
-    /// Outcome of calling the module's __update__ reducer, `None` if none is
-    /// defined.
+    /// Outcome of calling the module's __update__ reducer, which committed,
+    /// `None` if none is defined.
     pub update_result: Option<ReducerCallResult>,
-    /// Outcome of calling the module's pending __migrate__ reducers, empty if
-    /// none are defined or pending.
-    ///
-    /// Currently always empty, as __migrate__ is not yet supported.
+    /// Outcome of calling the module's __migrate__ reducer for each table
+    /// whose columns couldn't be migrated automatically, empty if there are
+    /// none.
     pub migrate_results: Vec<ReducerCallResult>,
 }
 
@@ -430,6 +491,10 @@ pub struct UpdateDatabaseSuccess {
 pub enum UpdateDatabaseError {
     #[error("incompatible schema changes for: {tables:?}")]
     IncompatibleSchema { tables: Vec<String> },
+    #[error("migration of table `{table}` failed: {reason}")]
+    MigrationFailed { table: String, reason: String },
+    #[error("the `update` reducer failed: {reason}")]
+    UpdateReducerFailed { reason: String },
     #[error(transparent)]
     Database(#[from] DBError),
 }
@@ -511,6 +576,7 @@ impl ModuleHost {
                 caller_identity,
                 Some(caller_address),
                 None,
+                None,
                 if connected {
                     "__identity_connected__"
                 } else {
@@ -530,6 +596,7 @@ impl ModuleHost {
         caller_identity: Identity,
         caller_address: Option<Address>,
         client: Option<ClientConnectionSender>,
+        request_id: Option<u32>,
         reducer_name: &str,
         args: ReducerArgs,
     ) -> Result<ReducerCallResult, ReducerCallError> {
@@ -541,7 +608,7 @@ impl ModuleHost {
 
         let args = args.into_tuple(self.info.typespace.with_type(schema))?;
 
-        self.call(move |inst| inst.call_reducer(caller_identity, caller_address, client, reducer_id, args))
+        self.call(move |inst| inst.call_reducer(caller_identity, caller_address, client, request_id, reducer_id, args))
             .await
             .map_err(Into::into)
     }
@@ -551,11 +618,12 @@ impl ModuleHost {
         caller_identity: Identity,
         caller_address: Option<Address>,
         client: Option<ClientConnectionSender>,
+        request_id: Option<u32>,
         reducer_name: &str,
         args: ReducerArgs,
     ) -> Result<ReducerCallResult, ReducerCallError> {
         let res = self
-            .call_reducer_inner(caller_identity, caller_address, client, reducer_name, args)
+            .call_reducer_inner(caller_identity, caller_address, client, request_id, reducer_name, args)
             .await;
 
         let log_message = match &res {
@@ -577,6 +645,13 @@ impl ModuleHost {
         res
     }
 
+    pub async fn call_scheduled_reducer(
+        &self,
+        id: ScheduledReducerId,
+    ) -> Result<Option<ReducerCallResult>, anyhow::Error> {
+        self.call(move |inst| inst.call_scheduled_reducer(id)).await?
+    }
+
     pub fn catalog(&self) -> Catalog {
         Catalog(self.info.clone())
     }
@@ -601,6 +676,10 @@ impl ModuleHost {
             .map_err(Into::into)
     }
 
+    pub async fn schema_diff(&self) -> Result<SchemaDiff, anyhow::Error> {
+        self.call(|inst| inst.schema_diff()).await?
+    }
+
     pub async fn exit(&self) {
         self.inner.exit().await
     }
@@ -1,17 +1,23 @@
 use std::path::Path;
+use std::sync::Arc;
 
 use futures::StreamExt;
-use rustc_hash::FxHashMap;
-use sled::transaction::{ConflictableTransactionError::Abort as TxAbort, TransactionError};
-use spacetimedb_lib::bsatn;
-use spacetimedb_lib::bsatn::ser::BsatnError;
+use spacetimedb_lib::schedule::{MisfirePolicy, Repeat};
+use spacetimedb_lib::{bsatn, RepeatingReducer};
+use spacetimedb_sats::AlgebraicValue;
 use tokio::sync::mpsc;
-use tokio_util::time::{delay_queue, DelayQueue};
+use tokio_util::time::DelayQueue;
+
+use crate::db::datastore::locking_tx_datastore::MutTxId;
+use crate::db::datastore::system_tables::{StScheduledFields, StScheduledRow, ST_SCHEDULED_ID};
+use crate::db::datastore::traits::ColId;
+use crate::db::relational_db::RelationalDB;
+use crate::error::{DBError, NodesError};
 
 use super::module_host::WeakModuleHost;
-use super::{ModuleHost, ReducerArgs, ReducerCallError, Timestamp};
+use super::{ModuleHost, Timestamp};
 
-#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub struct ScheduledReducerId(pub u64);
 
 enum MsgOrExit<T> {
@@ -21,56 +27,117 @@ enum MsgOrExit<T> {
 
 enum SchedulerMessage {
     Schedule { id: ScheduledReducerId, at: Timestamp },
-    Cancel { id: ScheduledReducerId },
-}
-
-#[derive(spacetimedb_sats::ser::Serialize, spacetimedb_sats::de::Deserialize)]
-struct ScheduledReducer {
-    at: Timestamp,
-    reducer: String,
-    bsatn_args: Vec<u8>,
 }
 
+/// Schedules the reducers stored in the system table `st_scheduled` of a database.
+///
+/// A reducer is scheduled, or cancelled, by inserting, or deleting, its row
+/// in the transaction of the reducer which schedules it,
+/// so the schedule persists if and only if that transaction commits.
+///
+/// A repeating reducer keeps its row, and its id, from one run to the next,
+/// with `scheduled_at` moved to its next run.
+/// The runs it missed while the host was down are handled on restart
+/// according to its [MisfirePolicy].
 #[derive(Clone)]
 pub struct Scheduler {
     tx: mpsc::UnboundedSender<MsgOrExit<SchedulerMessage>>,
-    db: sled::Db,
+    db: Arc<RelationalDB>,
 }
 
 pub struct SchedulerStarter {
     rx: mpsc::UnboundedReceiver<MsgOrExit<SchedulerMessage>>,
-    db: sled::Db,
+    db: Arc<RelationalDB>,
 }
 
 impl Scheduler {
-    pub fn dummy(dummy_path: &Path) -> Self {
+    pub fn dummy(db: Arc<RelationalDB>) -> Self {
         let (tx, _) = mpsc::unbounded_channel();
-        let db = sled::open(dummy_path).unwrap();
         Self { tx, db }
     }
 
-    pub fn open(scheduler_db_path: impl AsRef<Path>) -> anyhow::Result<(Self, SchedulerStarter)> {
-        let db = sled::Config::default()
-            .path(scheduler_db_path)
-            .flush_every_ms(Some(50))
-            .mode(sled::Mode::HighThroughput)
-            .open()?;
-
-        Ok(Self::from_db(db))
-    }
-
-    fn from_db(db: sled::Db) -> (Self, SchedulerStarter) {
+    pub fn open(db: Arc<RelationalDB>) -> (Self, SchedulerStarter) {
         let (tx, rx) = mpsc::unbounded_channel();
         (Scheduler { tx, db: db.clone() }, SchedulerStarter { rx, db })
     }
 
     pub fn new_with_same_db(&self) -> (Self, SchedulerStarter) {
-        Self::from_db(self.db.clone())
+        Self::open(self.db.clone())
     }
+}
 
-    pub fn clear(&self) {
-        self.db.clear().unwrap()
+/// A reducer scheduled before the schedules were stored in `st_scheduled`,
+/// as it was stored in the sled tree of the scheduler, keyed by its id.
+#[derive(spacetimedb_sats::ser::Serialize, spacetimedb_sats::de::Deserialize)]
+struct LegacyScheduledReducer {
+    at: u64,
+    reducer: String,
+    bsatn_args: Vec<u8>,
+}
+
+/// Moves the reducers scheduled in the sled tree at `path`,
+/// where the scheduler stored them before `st_scheduled`, into the `st_scheduled` of `db`.
+///
+/// The tree is first renamed with the extension `importing`, then once its schedules are committed,
+/// with the extension `imported`, so it's only imported once.
+/// An import interrupted in between is resumed from the `importing` tree on the next call,
+/// skipping the schedules it already committed.
+/// The schedules get new ids from `st_scheduled`, as the ids of the tree could collide with them,
+/// and, like before, those which were due while the host was down run as soon as it restarts.
+pub fn import_legacy_schedules(db: &RelationalDB, path: &Path) -> anyhow::Result<()> {
+    let staging = path.with_extension("importing");
+    let resumed = staging.exists();
+    if !resumed {
+        if !path.exists() {
+            return Ok(());
+        }
+        std::fs::rename(path, &staging)?;
     }
+    let tree = sled::open(&staging)?;
+    let mut rows = tree
+        .iter()
+        .map(|entry| {
+            let (_, value) = entry?;
+            let scheduled: LegacyScheduledReducer = bsatn::from_slice(&value)?;
+            Ok(StScheduledRow {
+                scheduled_id: 0,
+                reducer: scheduled.reducer,
+                args: scheduled.bsatn_args,
+                scheduled_at: scheduled.at,
+                repeat: None,
+                misfire: MisfirePolicy::FireOnce,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    drop(tree);
+
+    db.with_auto_commit(|tx| {
+        if resumed {
+            // The interrupted import may have committed before it could rename the tree,
+            // and the scheduler doesn't start until the import is done, so none of them has run yet.
+            let existing = db
+                .iter(tx, ST_SCHEDULED_ID.0)?
+                .map(|row| StScheduledRow::try_from(row.view()))
+                .collect::<Result<Vec<_>, _>>()?;
+            for existing in existing {
+                let imported = rows.iter().position(|row| {
+                    row.reducer == existing.reducer
+                        && row.args == existing.args
+                        && row.scheduled_at == existing.scheduled_at
+                });
+                if let Some(i) = imported {
+                    rows.swap_remove(i);
+                }
+            }
+        }
+        for row in &rows {
+            db.insert(tx, ST_SCHEDULED_ID.0, row.into())?;
+        }
+        Ok::<_, DBError>(())
+    })?;
+    std::fs::rename(&staging, path.with_extension("imported"))?;
+    log::info!("imported {} scheduled reducers from {}", rows.len(), path.display());
+    Ok(())
 }
 
 impl SchedulerStarter {
@@ -79,20 +146,34 @@ impl SchedulerStarter {
     pub fn start(self, module_host: &ModuleHost) -> anyhow::Result<()> {
         let mut queue = DelayQueue::new();
 
-        for entry in self.db.iter() {
-            let (k, v) = entry?;
-            let get_u64 = |b: &[u8]| u64::from_le_bytes(b.try_into().unwrap());
-            let id = ScheduledReducerId(get_u64(&k));
-            let at = Timestamp(get_u64(&v[..8]));
-            queue.insert(id, at.to_duration_from_now());
+        let now = Timestamp::now();
+        let scheduled = self.db.with_auto_commit(|tx| {
+            let rows = self
+                .db
+                .iter(tx, ST_SCHEDULED_ID.0)?
+                .map(|row| StScheduledRow::try_from(row.view()))
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut scheduled = Vec::with_capacity(rows.len());
+            for row in rows {
+                if let Some(at) = apply_misfire_policy(&self.db, tx, row.clone(), now)? {
+                    scheduled.push((row, at));
+                }
+            }
+            Ok::<_, DBError>(scheduled)
+        })?;
+        for (row, at) in scheduled {
+            let delay = at.to_duration_from_now();
+            if delay >= MAX_SCHEDULE_DELAY {
+                log::warn!("not scheduling reducer `{}`, its delay is too long", row.reducer);
+                continue;
+            }
+            queue.insert(ScheduledReducerId(row.scheduled_id), delay);
         }
 
         tokio::spawn(
             SchedulerActor {
                 rx: self.rx,
                 queue,
-                key_map: FxHashMap::default(),
-                db: self.db,
                 module_host: module_host.downgrade(),
             }
             .run(),
@@ -137,16 +218,21 @@ pub enum ScheduleError {
     #[error("Unable to schedule with long delay at {0:?}")]
     DelayTooLong(Timestamp),
 
-    #[error("Unable to generate a ScheduledReducerId: {0:?}")]
-    IdTransactionError(#[from] TransactionError<BsatnError>),
+    #[error("Unable to store the scheduled reducer: {0}")]
+    Nodes(#[from] NodesError),
 }
 
 impl Scheduler {
+    /// Schedules `reducer` to be called with `bsatn_args` at `at`,
+    /// and then as often as `repeat` says, if the transaction `tx` commits.
     pub fn schedule(
         &self,
+        tx: &mut MutTxId,
         reducer: String,
         bsatn_args: Vec<u8>,
         at: Timestamp,
+        repeat: Option<Repeat>,
+        misfire: MisfirePolicy,
     ) -> Result<ScheduledReducerId, ScheduleError> {
         // Check that `at` is within `tokio_utils::time::DelayQueue`'s accepted time-range.
         //
@@ -175,41 +261,97 @@ impl Scheduler {
             return Err(ScheduleError::DelayTooLong(at));
         }
 
-        let reducer = ScheduledReducer {
-            at,
+        // The `scheduled_id` is generated by the sequence of `st_scheduled`.
+        let row = StScheduledRow {
+            scheduled_id: 0,
             reducer,
-            bsatn_args,
+            args: bsatn_args,
+            scheduled_at: at.0,
+            repeat,
+            misfire,
         };
+        let row = self
+            .db
+            .insert(tx, ST_SCHEDULED_ID.0, (&row).into())
+            .map_err(NodesError::from)?;
+        let id = StScheduledRow::try_from(&row)
+            .map(|row| ScheduledReducerId(row.scheduled_id))
+            .map_err(NodesError::from)?;
 
-        let id = self.db.transaction(|tx| {
-            let id = tx.generate_id()?;
-            let reducer = bsatn::to_vec(&reducer).map_err(TxAbort)?;
-            tx.insert(&id.to_le_bytes(), reducer)?;
-            Ok(ScheduledReducerId(id))
-        })?;
-
-        // if the actor has exited, it's fine to ignore; it means that the host actor calling
-        // schedule will exit soon as well, and it'll be scheduled to run when the module host restarts
-        let _ = self.tx.send(MsgOrExit::Msg(SchedulerMessage::Schedule { id, at }));
+        // The actor is told about the schedule before `tx` commits, or rolls back,
+        // which is fine, as it only calls the reducer if its row was committed.
+        self.queue(id, at);
         Ok(id)
     }
 
-    pub fn cancel(&self, id: ScheduledReducerId) {
-        let res = self.db.transaction(|tx| {
-            tx.remove(&id.0.to_le_bytes())?;
-            Ok(())
-        });
-        match res {
-            Ok(()) => {
-                // if it's exited it's not gonna run it :) see also the comment in schedule()
-                let _ = self.tx.send(MsgOrExit::Msg(SchedulerMessage::Cancel { id }));
+    /// Schedules the reducer declared by the module to repeat, as of `now`, if the transaction `tx` commits,
+    /// unless it is already scheduled that way, e.g. by a previous version of the module.
+    ///
+    /// Its other repeating schedules, e.g. from a previous version of its declaration, are cancelled.
+    pub fn schedule_repeating_reducer(
+        &self,
+        tx: &mut MutTxId,
+        repeating: &RepeatingReducer,
+        now: Timestamp,
+    ) -> Result<(), ScheduleError> {
+        let rows = self
+            .db
+            .iter_by_col_eq(
+                tx,
+                ST_SCHEDULED_ID,
+                ColId(StScheduledFields::Reducer as u32),
+                AlgebraicValue::String(repeating.reducer.clone()),
+            )
+            .map_err(NodesError::from)?
+            .map(|row| row.view().clone())
+            .collect::<Vec<_>>();
+        let mut stale = Vec::new();
+        for row in rows {
+            let scheduled = StScheduledRow::try_from(&row).map_err(NodesError::from)?;
+            let Some(repeat) = &scheduled.repeat else {
+                continue;
+            };
+            if *repeat == repeating.repeat && scheduled.misfire == repeating.misfire {
+                return Ok(());
             }
-            // we could return an error here, but that would give them information that
-            // there exists a scheduled reducer with this id. like returning a HTTP 400
-            // instead of a 404
-            Err(TransactionError::Abort(())) => {}
-            Err(TransactionError::Storage(e)) => panic!("sled error: {e:?}"),
+            stale.push(row);
         }
+        self.db
+            .delete_by_rel(tx, ST_SCHEDULED_ID.0, stale)
+            .map_err(NodesError::from)?;
+
+        let Some(at) = repeating.repeat.next(now.0) else {
+            log::warn!("repeating reducer `{}` has no next run", repeating.reducer);
+            return Ok(());
+        };
+        // The reducer takes no arguments, which are encoded as nothing in BSATN.
+        self.schedule(
+            tx,
+            repeating.reducer.clone(),
+            Vec::new(),
+            Timestamp(at),
+            Some(repeating.repeat.clone()),
+            repeating.misfire,
+        )
+        .map(drop)
+    }
+
+    /// Tells the actor to call the scheduled reducer `id` at `at`,
+    /// which must be stored in `st_scheduled` by then.
+    pub(crate) fn queue(&self, id: ScheduledReducerId, at: Timestamp) {
+        // If the actor has exited, it's fine to ignore; it means that the host actor calling
+        // schedule will exit soon as well, and it'll be scheduled to run when the module host restarts.
+        let _ = self.tx.send(MsgOrExit::Msg(SchedulerMessage::Schedule { id, at }));
+    }
+
+    /// Cancels the scheduled reducer `id`, if the transaction `tx` commits.
+    ///
+    /// The actor isn't told: it finds no row for `id` when its time comes.
+    pub fn cancel(&self, tx: &mut MutTxId, id: ScheduledReducerId) -> Result<(), DBError> {
+        // We could return an error if there's no such schedule, but that would give them information that
+        // there exists a scheduled reducer with this id. Like returning a HTTP 400
+        // instead of a 404.
+        take_scheduled(&self.db, tx, id).map(drop)
     }
 
     pub fn close(&self) {
@@ -217,11 +359,78 @@ impl Scheduler {
     }
 }
 
+/// Deletes the row of the scheduled reducer `id` from `st_scheduled` in `tx`, returning it,
+/// or `None` if it has already run or been cancelled, or the transaction scheduling it rolled back.
+pub(crate) fn take_scheduled(
+    db: &RelationalDB,
+    tx: &mut MutTxId,
+    id: ScheduledReducerId,
+) -> Result<Option<StScheduledRow>, DBError> {
+    let rows = db
+        .iter_by_col_eq(
+            tx,
+            ST_SCHEDULED_ID,
+            ColId(StScheduledFields::ScheduledId as u32),
+            id.0.into(),
+        )?
+        .map(|row| row.view().clone())
+        .collect::<Vec<_>>();
+    let scheduled = rows.first().map(StScheduledRow::try_from).transpose()?;
+    db.delete_by_rel(tx, ST_SCHEDULED_ID.0, rows)?;
+    Ok(scheduled)
+}
+
+/// Stores the next run of the `scheduled` reducer, which has just been taken from `st_scheduled` to run at `now`,
+/// returning when that is, or `None` if it doesn't repeat.
+///
+/// A reducer with the [MisfirePolicy::FireAll] policy runs at each of its times,
+/// even if they are already past, so it catches up on the runs it missed.
+/// Otherwise, it next runs at its first time after `now`.
+pub(crate) fn reschedule(
+    db: &RelationalDB,
+    tx: &mut MutTxId,
+    mut scheduled: StScheduledRow,
+    now: Timestamp,
+) -> Result<Option<Timestamp>, DBError> {
+    let Some(repeat) = &scheduled.repeat else {
+        return Ok(None);
+    };
+    let next = match scheduled.misfire {
+        MisfirePolicy::FireAll => repeat.next(scheduled.scheduled_at),
+        MisfirePolicy::Skip | MisfirePolicy::FireOnce => repeat.next_after(scheduled.scheduled_at, now.0),
+    };
+    let Some(next) = next else {
+        log::warn!("scheduled reducer `{}` has no next run", scheduled.reducer);
+        return Ok(None);
+    };
+    scheduled.scheduled_at = next;
+    db.insert(tx, ST_SCHEDULED_ID.0, (&scheduled).into())?;
+    Ok(Some(Timestamp(next)))
+}
+
+/// Applies the [MisfirePolicy] of the `scheduled` reducer when the host (re)starts at `now`,
+/// returning when it should run next, or `None` if it was dropped.
+///
+/// If it missed its time, its policy is [MisfirePolicy::Skip],
+/// it is moved to its first time after `now`, or dropped if it doesn't repeat.
+/// Otherwise, it runs as soon as possible, and so do the runs it missed under [MisfirePolicy::FireAll].
+fn apply_misfire_policy(
+    db: &RelationalDB,
+    tx: &mut MutTxId,
+    scheduled: StScheduledRow,
+    now: Timestamp,
+) -> Result<Option<Timestamp>, DBError> {
+    if scheduled.scheduled_at >= now.0 || scheduled.misfire != MisfirePolicy::Skip {
+        return Ok(Some(Timestamp(scheduled.scheduled_at)));
+    }
+    let id = ScheduledReducerId(scheduled.scheduled_id);
+    take_scheduled(db, tx, id)?;
+    reschedule(db, tx, scheduled, now)
+}
+
 struct SchedulerActor {
     rx: mpsc::UnboundedReceiver<MsgOrExit<SchedulerMessage>>,
     queue: DelayQueue<ScheduledReducerId>,
-    key_map: FxHashMap<ScheduledReducerId, delay_queue::Key>,
-    db: sled::Db,
     module_host: WeakModuleHost,
 }
 
@@ -236,9 +445,7 @@ impl SchedulerActor {
                     Some(MsgOrExit::Exit) | None => break,
                 },
                 Some(scheduled) = self.queue.next() => {
-                    let id = scheduled.into_inner();
-                    self.key_map.remove(&id);
-                    self.handle_queued(id).await;
+                    self.handle_queued(scheduled.into_inner());
                 }
             }
         }
@@ -247,50 +454,329 @@ impl SchedulerActor {
     fn handle_message(&mut self, msg: SchedulerMessage) {
         match msg {
             SchedulerMessage::Schedule { id, at } => {
-                let key = self.queue.insert(id, at.to_duration_from_now());
-                self.key_map.insert(id, key);
-            }
-            SchedulerMessage::Cancel { id } => {
-                if let Some(key) = self.key_map.remove(&id) {
-                    self.queue.remove(&key);
-                }
+                self.queue.insert(id, at.to_duration_from_now());
             }
         }
     }
 
-    async fn handle_queued(&self, id: ScheduledReducerId) {
+    fn handle_queued(&self, id: ScheduledReducerId) {
         let Some(module_host) = self.module_host.upgrade() else {
             return;
         };
-        let Some(scheduled) = self.db.get(id.0.to_le_bytes()).unwrap() else {
-            return;
-        };
-        let scheduled: ScheduledReducer = bsatn::from_slice(&scheduled).unwrap();
-        let db = self.db.clone();
         tokio::spawn(async move {
-            let info = module_host.info();
-            let identity = info.identity;
             // TODO: pass a logical "now" timestamp to this reducer call, but there's some
             //       intricacies to get right (how much drift to tolerate? what kind of tokio::time::MissedTickBehavior do we want?)
-            let res = module_host
-                .call_reducer(
-                    identity,
-                    // Scheduled reducers take `None` as the caller address.
-                    None,
-                    None,
-                    &scheduled.reducer,
-                    ReducerArgs::Bsatn(scheduled.bsatn_args.into()),
-                )
-                .await;
-            if !matches!(res, Err(ReducerCallError::NoSuchModule(_))) {
-                // if we didn't actually call the reducer because the module exited, leave
-                // the ScheduledReducer in the database for when the module restarts
-                let _ = db.remove(id.0.to_le_bytes());
-            }
-            match res {
-                Ok(_) => {}
-                Err(e) => log::error!("invoking scheduled reducer failed: {e:#}"),
+            // If the module has exited, the row of the scheduled reducer is left in `st_scheduled`,
+            // and it runs when the module restarts.
+            if let Err(e) = module_host.call_scheduled_reducer(id).await {
+                log::error!("invoking scheduled reducer failed: {e:#}");
             }
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::datastore::traits::{ColumnDef, IndexDef, TableDef};
+    use crate::db::relational_db::open_db;
+    use crate::db::relational_db::tests_utils::make_test_db;
+    use spacetimedb_lib::auth::{StAccess, StTableType};
+    use spacetimedb_lib::error::ResultTest;
+    use spacetimedb_sats::{product, AlgebraicType, AlgebraicValue};
+
+    fn scheduled_rows(stdb: &RelationalDB) -> ResultTest<Vec<StScheduledRow>> {
+        Ok(stdb.with_read_only(|tx| {
+            stdb.iter(tx, ST_SCHEDULED_ID.0)?
+                .map(|row| StScheduledRow::try_from(row.view()))
+                .collect::<Result<Vec<_>, DBError>>()
+        })?)
+    }
+
+    #[test]
+    fn test_schedule_and_cancel_in_tx() -> ResultTest<()> {
+        let (stdb, tmp_dir) = make_test_db()?;
+        let stdb = Arc::new(stdb);
+        let (scheduler, _) = Scheduler::open(stdb.clone());
+        let at = Timestamp::now();
+
+        // A rolled back schedule is never stored.
+        let mut tx = stdb.begin_tx();
+        scheduler.schedule(&mut tx, "foo".into(), vec![1], at, None, MisfirePolicy::FireOnce)?;
+        stdb.rollback_tx(tx);
+        assert_eq!(scheduled_rows(&stdb)?, []);
+
+        let mut tx = stdb.begin_tx();
+        let id = scheduler.schedule(&mut tx, "foo".into(), vec![2], at, None, MisfirePolicy::FireOnce)?;
+        stdb.commit_tx(tx)?;
+        let scheduled = StScheduledRow {
+            scheduled_id: id.0,
+            reducer: "foo".into(),
+            args: vec![2],
+            scheduled_at: at.0,
+            repeat: None,
+            misfire: MisfirePolicy::FireOnce,
+        };
+        assert_eq!(scheduled_rows(&stdb)?, [scheduled.clone()]);
+
+        // A rolled back cancellation leaves the reducer scheduled.
+        let mut tx = stdb.begin_tx();
+        scheduler.cancel(&mut tx, id)?;
+        stdb.rollback_tx(tx);
+        assert_eq!(scheduled_rows(&stdb)?, [scheduled.clone()]);
+
+        // The schedule survives a restart, and its id isn't reused.
+        drop((scheduler, stdb));
+        let stdb = Arc::new(open_db(&tmp_dir, false, true)?);
+        let (scheduler, _) = Scheduler::open(stdb.clone());
+        assert_eq!(scheduled_rows(&stdb)?, [scheduled]);
+
+        let mut tx = stdb.begin_tx();
+        scheduler.cancel(&mut tx, id)?;
+        let new_id = scheduler.schedule(&mut tx, "bar".into(), vec![], at, None, MisfirePolicy::FireOnce)?;
+        stdb.commit_tx(tx)?;
+        assert_ne!(new_id, id);
+        assert_eq!(
+            scheduled_rows(&stdb)?,
+            [StScheduledRow {
+                scheduled_id: new_id.0,
+                reducer: "bar".into(),
+                args: vec![],
+                scheduled_at: at.0,
+                repeat: None,
+                misfire: MisfirePolicy::FireOnce,
+            }]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_misfire_policies() -> ResultTest<()> {
+        let (stdb, _tmp_dir) = make_test_db()?;
+        let stdb = Arc::new(stdb);
+        let (scheduler, _) = Scheduler::open(stdb.clone());
+        let minute = 60_000_000;
+        let now = Timestamp::now();
+        let missed = Timestamp(now.0 - 90 * minute);
+        let every_minute = Repeat::every(std::time::Duration::from_secs(60))?;
+
+        let mut tx = stdb.begin_tx();
+        let mut schedule = |reducer: &str, repeat: Option<Repeat>, misfire| {
+            let id = scheduler.schedule(&mut tx, reducer.into(), vec![], now, repeat, misfire)?;
+            // The host was down when the reducer should have run.
+            let mut row = take_scheduled(&stdb, &mut tx, id)?.unwrap();
+            row.scheduled_at = missed.0;
+            stdb.insert(&mut tx, ST_SCHEDULED_ID.0, (&row).into())?;
+            ResultTest::Ok(row)
+        };
+        let once = schedule("once", None, MisfirePolicy::Skip)?;
+        let skip = schedule("skip", Some(every_minute.clone()), MisfirePolicy::Skip)?;
+        let fire_once = schedule("fire_once", Some(every_minute.clone()), MisfirePolicy::FireOnce)?;
+        let fire_all = schedule("fire_all", Some(every_minute.clone()), MisfirePolicy::FireAll)?;
+        stdb.commit_tx(tx)?;
+
+        // On restart, the missed runs of the skipping reducers are dropped.
+        let mut tx = stdb.begin_tx();
+        let mut on_restart = |row: &StScheduledRow| apply_misfire_policy(&stdb, &mut tx, row.clone(), now);
+        assert_eq!(on_restart(&once)?, None);
+        let skipped_to = every_minute.next_after(missed.0, now.0).map(Timestamp);
+        assert_eq!(on_restart(&skip)?, skipped_to);
+        assert_eq!(on_restart(&fire_once)?, Some(missed));
+        assert_eq!(on_restart(&fire_all)?, Some(missed));
+
+        // When they run, the other reducers run once, or at each missed time.
+        let mut run = |row: &StScheduledRow| {
+            let taken = take_scheduled(&stdb, &mut tx, ScheduledReducerId(row.scheduled_id))?.unwrap();
+            reschedule(&stdb, &mut tx, taken, now)
+        };
+        assert_eq!(run(&fire_once)?, skipped_to);
+        assert_eq!(run(&fire_all)?, Some(Timestamp(missed.0 + minute)));
+        stdb.commit_tx(tx)?;
+
+        let mut rows = scheduled_rows(&stdb)?;
+        rows.sort_by_key(|row| row.scheduled_id);
+        let at = |row: &StScheduledRow, at: Option<Timestamp>| StScheduledRow {
+            scheduled_at: at.unwrap().0,
+            ..row.clone()
+        };
+        assert_eq!(
+            rows,
+            [
+                at(&skip, skipped_to),
+                at(&fire_once, skipped_to),
+                at(&fire_all, Some(Timestamp(missed.0 + minute)))
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_schedule_repeating_reducer() -> ResultTest<()> {
+        let (stdb, _tmp_dir) = make_test_db()?;
+        let stdb = Arc::new(stdb);
+        let (scheduler, _) = Scheduler::open(stdb.clone());
+        let now = Timestamp::now();
+        let nightly = RepeatingReducer {
+            reducer: "cleanup".into(),
+            repeat: Repeat::cron("0 4 * * *")?,
+            misfire: MisfirePolicy::Skip,
+        };
+
+        // A one-off schedule of the same reducer is left alone.
+        let mut tx = stdb.begin_tx();
+        let once = scheduler.schedule(&mut tx, "cleanup".into(), vec![], now, None, MisfirePolicy::FireOnce)?;
+        scheduler.schedule_repeating_reducer(&mut tx, &nightly, now)?;
+        stdb.commit_tx(tx)?;
+        let mut rows = scheduled_rows(&stdb)?;
+        rows.sort_by_key(|row| row.scheduled_id);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].scheduled_id, once.0);
+        let scheduled = rows[1].clone();
+        assert_eq!(scheduled.repeat, Some(nightly.repeat.clone()));
+        assert_eq!(Some(scheduled.scheduled_at), nightly.repeat.next(now.0));
+
+        // Publishing the same declaration again keeps the schedule as it is.
+        let mut tx = stdb.begin_tx();
+        scheduler.schedule_repeating_reducer(&mut tx, &nightly, Timestamp(now.0 + 1))?;
+        stdb.commit_tx(tx)?;
+        assert_eq!(scheduled_rows(&stdb)?.len(), 2);
+        assert!(scheduled_rows(&stdb)?.contains(&scheduled));
+
+        // A changed declaration replaces it.
+        let hourly = RepeatingReducer {
+            repeat: Repeat::every(std::time::Duration::from_secs(3600))?,
+            ..nightly
+        };
+        let mut tx = stdb.begin_tx();
+        scheduler.schedule_repeating_reducer(&mut tx, &hourly, now)?;
+        stdb.commit_tx(tx)?;
+        let rows = scheduled_rows(&stdb)?;
+        assert_eq!(rows.len(), 2);
+        assert!(!rows.contains(&scheduled));
+        assert!(rows.iter().any(|row| row.repeat == Some(hourly.repeat.clone())));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reopen_database_created_before_st_scheduled() -> ResultTest<()> {
+        let (stdb, tmp_dir) = make_test_db()?;
+        let mut tx = stdb.begin_tx();
+        let table_id = stdb.create_table(
+            &mut tx,
+            TableDef {
+                table_name: "Foo".into(),
+                columns: vec![ColumnDef {
+                    col_name: "id".into(),
+                    col_type: AlgebraicType::U64,
+                    is_autoinc: true,
+                }],
+                indexes: vec![IndexDef::new("id_idx".into(), 0, 0, true)],
+                table_type: StTableType::User,
+                table_access: StAccess::Public,
+            },
+        )?;
+        // These are the ids which databases created before `st_scheduled` have in their logs,
+        // as they only count the system tables they had, which aren't logged themselves.
+        assert_eq!(table_id, 6);
+        assert_eq!(stdb.sequence_id_from_name(&tx, "Foo_id_seq")?.map(|id| id.0), Some(4));
+        assert_eq!(stdb.index_id_from_name(&tx, "id_idx")?.map(|id| id.0), Some(6));
+        stdb.insert(&mut tx, table_id, product![AlgebraicValue::U64(0)])?;
+        stdb.commit_tx(tx)?;
+        drop(stdb);
+
+        let stdb = Arc::new(open_db(&tmp_dir, false, true)?);
+        let (scheduler, _) = Scheduler::open(stdb.clone());
+        let mut tx = stdb.begin_tx();
+        let at = Timestamp::now();
+        scheduler.schedule(&mut tx, "foo".into(), vec![], at, None, MisfirePolicy::FireOnce)?;
+        stdb.insert(&mut tx, table_id, product![AlgebraicValue::U64(0)])?;
+        stdb.commit_tx(tx)?;
+        drop((scheduler, stdb));
+
+        // The user table and `st_scheduled` are both replayed, without colliding.
+        let stdb = open_db(&tmp_dir, false, true)?;
+        let tx = stdb.begin_tx();
+        assert_eq!(stdb.table_name_from_id(&tx, table_id)?.as_deref(), Some("Foo"));
+        assert_eq!(stdb.iter(&tx, table_id)?.count(), 2);
+        stdb.rollback_tx(tx);
+        assert_eq!(scheduled_rows(&stdb)?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_legacy_schedules() -> ResultTest<()> {
+        let (stdb, tmp_dir) = make_test_db()?;
+        let path = tmp_dir.path().join("scheduler");
+        let at = Timestamp::now();
+        let tree = sled::open(&path)?;
+        for (id, reducer) in [(0u64, "foo"), (1, "bar")] {
+            let scheduled = LegacyScheduledReducer {
+                at: at.0,
+                reducer: reducer.into(),
+                bsatn_args: vec![id as u8],
+            };
+            tree.insert(id.to_le_bytes(), bsatn::to_vec(&scheduled)?)?;
+        }
+        drop(tree);
+
+        import_legacy_schedules(&stdb, &path).unwrap();
+        let mut rows = scheduled_rows(&stdb)?;
+        rows.sort_by_key(|row| row.args.clone());
+        let row = |reducer: &str, args: Vec<u8>| StScheduledRow {
+            scheduled_id: 0,
+            reducer: reducer.into(),
+            args,
+            scheduled_at: at.0,
+            repeat: None,
+            misfire: MisfirePolicy::FireOnce,
+        };
+        let ids_cleared = rows
+            .iter()
+            .map(|row| StScheduledRow {
+                scheduled_id: 0,
+                ..row.clone()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ids_cleared, [row("foo", vec![0]), row("bar", vec![1])]);
+        assert_ne!(rows[0].scheduled_id, rows[1].scheduled_id);
+
+        // The tree is only imported once.
+        assert!(!path.exists());
+        assert!(path.with_extension("imported").exists());
+        import_legacy_schedules(&stdb, &path).unwrap();
+        assert_eq!(scheduled_rows(&stdb)?.len(), 2);
+
+        // An import interrupted after its commit doesn't import the schedules again.
+        std::fs::rename(path.with_extension("imported"), path.with_extension("importing"))?;
+        import_legacy_schedules(&stdb, &path).unwrap();
+        assert_eq!(scheduled_rows(&stdb)?.len(), 2);
+        assert!(!path.with_extension("importing").exists());
+        assert!(path.with_extension("imported").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_resume_legacy_schedules_import() -> ResultTest<()> {
+        let (stdb, tmp_dir) = make_test_db()?;
+        let path = tmp_dir.path().join("scheduler");
+        let scheduled = LegacyScheduledReducer {
+            at: Timestamp::now().0,
+            reducer: "foo".into(),
+            bsatn_args: vec![],
+        };
+        // An import interrupted before its commit left the tree renamed.
+        let tree = sled::open(path.with_extension("importing"))?;
+        tree.insert(0u64.to_le_bytes(), bsatn::to_vec(&scheduled)?)?;
+        drop(tree);
+
+        import_legacy_schedules(&stdb, &path).unwrap();
+        let rows = scheduled_rows(&stdb)?;
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].reducer, "foo");
+        assert!(path.with_extension("imported").exists());
+        Ok(())
+    }
+}
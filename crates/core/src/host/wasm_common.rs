@@ -17,6 +17,10 @@ pub const SETUP_DUNDER: &str = "__setup__";
 pub const INIT_DUNDER: &str = "__init__";
 /// the reducer with this name is invoked when updating the database
 pub const UPDATE_DUNDER: &str = "__update__";
+/// the reducer with this name migrates the rows of a table whose columns changed when updating the database
+pub const MIGRATE_DUNDER: &str = "__migrate__";
+/// functions with this prefix decide whether a client may call the reducer named by the rest of the symbol
+pub const GUARD_DUNDER: &str = "__guard__";
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[allow(unused)]
@@ -119,6 +123,16 @@ const CALL_REDUCER_SIG: StaticFuncSig = FuncSig::new(
         WasmType::I32, // Result buffer
     ],
 );
+const GUARD_SIG: StaticFuncSig = FuncSig::new(
+    &[
+        WasmType::I32, // Sender `Identity` buffer
+        WasmType::I32, // Sender `Address` buffer
+        WasmType::I64, // Timestamp
+    ],
+    &[
+        WasmType::I32, // Nonzero if the call is allowed
+    ],
+);
 
 #[derive(thiserror::Error, Debug)]
 pub enum ValidationError {
@@ -139,6 +153,12 @@ pub enum ValidationError {
     NoMemory,
     #[error("there should be a function called {name:?} but it does not exist")]
     NoFunction { name: &'static str },
+    #[error("reducer {reducer:?} is guarded, but there is no {GUARD_DUNDER}{reducer} function")]
+    NoGuard { reducer: String },
+    #[error("reducer {reducer:?} is declared to repeat, but there is no such reducer taking no arguments")]
+    BadRepeatingReducer { reducer: String },
+    #[error("the default of column {column:?} of table {table:?} doesn't match a column of its type")]
+    BadColumnDefault { table: String, column: String },
 }
 
 #[derive(Default)]
@@ -179,6 +199,8 @@ impl FuncNames {
         } else if let Some(name) = sym.strip_prefix(PREINIT_DUNDER) {
             Self::validate_signature("preinit", ty, name, PREINIT_SIG)?;
             self.preinits.push(sym.to_owned());
+        } else if let Some(name) = sym.strip_prefix(GUARD_DUNDER) {
+            Self::validate_signature("guard", ty, name, GUARD_SIG)?;
         }
         Ok(())
     }
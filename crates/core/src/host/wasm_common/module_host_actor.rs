@@ -3,15 +3,20 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use crate::db::datastore::locking_tx_datastore::MutTxId;
-use crate::db::datastore::traits::{ColumnDef, IndexDef, IndexId, TableDef};
-use crate::host::scheduler::Scheduler;
+use crate::db::datastore::traits::{ColumnDef, IndexDef, IndexId, IndexSchema, TableDef};
+use crate::db::migration::TableMigration;
+use crate::host::scheduler::{reschedule, take_scheduled, ScheduledReducerId, Scheduler};
 use crate::sql;
+use crate::sql::row_filter::RowFilters;
 use anyhow::Context;
 use bytes::Bytes;
+use itertools::Itertools;
 use nonempty::NonEmpty;
 use spacetimedb_lib::buffer::DecodeError;
 use spacetimedb_lib::identity::AuthCtx;
-use spacetimedb_lib::{bsatn, Address, IndexType, ModuleDef};
+use spacetimedb_lib::name::{SchemaDiff, TaintedTable};
+use spacetimedb_lib::{bsatn, Address, IndexType, MiscModuleExport, ModuleDef, ReducerAuthRule};
+use spacetimedb_sats::{product, AlgebraicType, AlgebraicValue, ArrayValue, ProductValue};
 use spacetimedb_vm::expr::CrudExpr;
 
 use crate::client::ClientConnectionSender;
@@ -24,8 +29,8 @@ use crate::host::module_host::{
     UpdateDatabaseError, UpdateDatabaseResult, UpdateDatabaseSuccess,
 };
 use crate::host::{
-    ArgsTuple, EnergyDiff, EnergyMonitor, EnergyMonitorFingerprint, EnergyQuanta, EntityDef, ReducerCallResult,
-    ReducerOutcome, Timestamp,
+    ArgsTuple, EnergyDiff, EnergyMonitor, EnergyMonitorFingerprint, EnergyQuanta, EntityDef, ReducerArgs,
+    ReducerCallError, ReducerCallResult, ReducerOutcome, Timestamp,
 };
 use crate::identity::Identity;
 use crate::subscription::module_subscription_actor::{ModuleSubscriptionManager, SubscriptionEventSender};
@@ -66,6 +71,16 @@ pub trait WasmInstance: Send + Sync + 'static {
         arg_bytes: Bytes,
     ) -> ExecuteResult<Self::Trap>;
 
+    /// Calls the guard of the reducer named `reducer`, returning whether
+    /// the sender may call it. Guards aren't charged energy.
+    fn call_guard(
+        &mut self,
+        reducer: &str,
+        sender_identity: &Identity,
+        sender_address: &Address,
+        timestamp: Timestamp,
+    ) -> Result<bool, Self::Trap>;
+
     fn log_traceback(func_type: &str, func: &str, trap: &Self::Trap);
 }
 
@@ -142,7 +157,6 @@ impl<T: WasmModule> WasmModuleHostActor<T> {
 
         let owner_identity = database_instance_context.identity;
         let relational_db = database_instance_context.relational_db.clone();
-        let (subscription, event_tx) = ModuleSubscriptionManager::spawn(relational_db, owner_identity);
 
         let uninit_instance = module.instantiate_pre()?;
         let mut instance = uninit_instance.instantiate(
@@ -156,8 +170,77 @@ impl<T: WasmModule> WasmModuleHostActor<T> {
             typespace,
             tables,
             reducers,
-            misc_exports: _,
+            misc_exports,
         } = desc;
+        let mut reducer_auth = HashMap::new();
+        let mut row_filters = Vec::new();
+        let mut repeating_reducers = Vec::new();
+        let mut column_defaults = HashMap::<_, HashMap<_, _>>::new();
+        for export in misc_exports {
+            match export {
+                MiscModuleExport::ReducerAuth(auth) => {
+                    reducer_auth.insert(auth.reducer, auth.rule);
+                }
+                MiscModuleExport::RowFilter(filter) => row_filters.push(filter),
+                MiscModuleExport::RepeatingReducer(repeating) => repeating_reducers.push(repeating),
+                MiscModuleExport::ColumnDefault(default) => {
+                    // The value must be exactly one of the type of its column, which must exist.
+                    let value = tables
+                        .iter()
+                        .find(|table| table.name == default.table)
+                        .and_then(|table| typespace.with_type(&table.data).resolve_refs())
+                        .and_then(|row_type| row_type.into_product().ok())
+                        .and_then(|row_type| {
+                            row_type
+                                .elements
+                                .into_iter()
+                                .find(|col| col.name.as_deref() == Some(&*default.column))
+                        })
+                        .and_then(|col| {
+                            let mut bytes = &default.value[..];
+                            let value = AlgebraicValue::decode(&col.algebraic_type, &mut bytes).ok()?;
+                            bytes.is_empty().then_some(value)
+                        });
+                    let Some(value) = value else {
+                        return Err(ValidationError::BadColumnDefault {
+                            table: default.table,
+                            column: default.column,
+                        }
+                        .into());
+                    };
+                    column_defaults
+                        .entry(default.table)
+                        .or_default()
+                        .insert(default.column, value);
+                }
+                MiscModuleExport::TypeAlias(_) => {}
+            }
+        }
+        let row_filters = Arc::new(RowFilters::new(row_filters));
+        let (subscription, event_tx) =
+            ModuleSubscriptionManager::spawn(relational_db, owner_identity, row_filters.clone());
+        for (reducer, rule) in &reducer_auth {
+            if let ReducerAuthRule::Guard(_) = rule {
+                if module.get_export(&format!("{GUARD_DUNDER}{reducer}")).is_none() {
+                    return Err(ValidationError::NoGuard {
+                        reducer: reducer.clone(),
+                    }
+                    .into());
+                }
+            }
+        }
+        for repeating in &repeating_reducers {
+            // The host schedules the reducer without any arguments.
+            if !reducers
+                .iter()
+                .any(|def| def.name == repeating.reducer && def.args.is_empty())
+            {
+                return Err(ValidationError::BadRepeatingReducer {
+                    reducer: repeating.reducer.clone(),
+                }
+                .into());
+            }
+        }
         let catalog = itertools::chain(
             tables.into_iter().map(|x| (x.name.clone(), EntityDef::Table(x))),
             reducers.iter().map(|x| (x.name.clone(), EntityDef::Reducer(x.clone()))),
@@ -171,6 +254,10 @@ impl<T: WasmModule> WasmModuleHostActor<T> {
             module_hash,
             typespace,
             reducers,
+            reducer_auth,
+            row_filters,
+            repeating_reducers,
+            column_defaults,
             catalog,
             log_tx,
             subscription,
@@ -254,13 +341,12 @@ impl<T: WasmModule> Module for WasmModuleHostActor<T> {
     ) -> Result<Vec<spacetimedb_lib::relation::MemTable>, DBError> {
         let db = &self.worker_database_instance.relational_db;
         let auth = AuthCtx::new(self.worker_database_instance.identity, caller_identity);
-        // TODO(jgilles): make this a read-only TX when those get added
-
         db.with_read_only(|tx| {
             log::debug!("One-off query: {query}");
             // NOTE(jgilles): this returns errors about mutating queries as SubscriptionErrors, which is perhaps
             // mildly confusing, since the user did not subscribe to anything. Should we rename SubscriptionError to ReadOnlyQueryError?
-            let compiled = crate::subscription::query::compile_read_only_query(db, tx, &auth, &query)?;
+            let compiled =
+                crate::subscription::query::compile_read_only_query(db, tx, &auth, &self.info.row_filters, &query)?;
 
             sql::execute::execute_sql(
                 db,
@@ -372,6 +458,10 @@ impl<T: WasmInstance> ModuleInstance for WasmModuleInstance<T> {
             .with_auto_rollback(tx, |tx| stdb.set_program_hash(tx, fence, self.info.module_hash))
             .map(|(tx, ())| tx)?;
 
+        tx = stdb
+            .with_auto_rollback(tx, |tx| self.schedule_repeating_reducers(tx))
+            .map(|(tx, ())| tx)?;
+
         let rcr = match self.info.reducers.get_index_of(INIT_DUNDER) {
             None => {
                 stdb.commit_tx(tx)?;
@@ -390,7 +480,15 @@ impl<T: WasmInstance> ModuleInstance for WasmModuleInstance<T> {
                 // This is useful for bootstrapping the control DB in SpacetimeDB-cloud.
                 let caller_address = self.database_instance_context().publisher_address;
                 let client = None;
-                self.call_reducer_internal(Some(tx), caller_identity, caller_address, client, reducer_id, args)
+                self.call_reducer_internal(
+                    Some(tx),
+                    caller_identity,
+                    caller_address,
+                    client,
+                    None,
+                    reducer_id,
+                    args,
+                )
             }
         };
 
@@ -401,44 +499,109 @@ impl<T: WasmInstance> ModuleInstance for WasmModuleInstance<T> {
 
     #[tracing::instrument(skip_all)]
     fn update_database(&mut self, fence: u128) -> Result<UpdateDatabaseResult, anyhow::Error> {
-        let stdb = &*self.database_instance_context().relational_db;
+        // Cloned, as `self` is borrowed mutably to call the `migrate` reducer.
+        let stdb = self.database_instance_context().relational_db.clone();
         let mut tx = stdb.begin_tx();
 
-        let (tx0, updates) = stdb.with_auto_rollback::<_, _, anyhow::Error>(tx, |tx| self.schema_updates(tx))?;
+        let migrate_reducer = self.migrate_reducer();
+        let (tx0, updates) = stdb
+            .with_auto_rollback::<_, _, anyhow::Error>(tx, |tx| self.schema_updates(tx, migrate_reducer.is_some()))?;
         tx = tx0;
-        if updates.tainted_tables.is_empty() {
-            tx = stdb
-                .with_auto_rollback::<_, _, DBError>(tx, |tx| {
-                    for (name, schema) in updates.new_tables {
-                        self.system_logger().info(&format!("Creating table `{}`", name));
-                        stdb.create_table(tx, schema)
-                            .with_context(|| format!("failed to create table {}", name))?;
-                    }
-
-                    for index_id in updates.indexes_to_drop {
-                        self.system_logger()
-                            .info(&format!("Dropping index with id {}", index_id.0));
-                        stdb.drop_index(tx, index_id)?;
-                    }
-
-                    for index_def in updates.indexes_to_create {
-                        self.system_logger()
-                            .info(&format!("Creating index `{}`", index_def.name));
-                        stdb.create_index(tx, index_def)?;
-                    }
-
-                    Ok(())
-                })
-                .map(|(tx, ())| tx)?;
-        } else {
+        if !updates.tainted_tables.is_empty() {
             stdb.rollback_tx(tx);
+            for tainted in &updates.tainted_tables {
+                self.system_logger().warn(&format!(
+                    "stored and proposed schema of `{}` differ: {}",
+                    tainted.table,
+                    tainted.reasons.join("; ")
+                ));
+            }
             self.system_logger()
                 .error("Module update rejected due to schema mismatch");
             return Ok(Err(UpdateDatabaseError::IncompatibleSchema {
-                tables: updates.tainted_tables,
+                tables: updates
+                    .tainted_tables
+                    .into_iter()
+                    .map(|tainted| tainted.table)
+                    .collect(),
             }));
         }
 
+        let (tx0, rows_to_migrate) = stdb.with_auto_rollback::<_, _, DBError>(tx, |tx| {
+            for (name, schema) in updates.new_tables {
+                self.system_logger().info(&format!("Creating table `{}`", name));
+                stdb.create_table(tx, schema)
+                    .with_context(|| format!("failed to create table {}", name))?;
+            }
+
+            // The indexes are dropped first, as they may be on columns
+            // whose values are about to change.
+            for index in updates.indexes_to_drop {
+                self.system_logger()
+                    .info(&format!("Dropping index `{}`", index.index_name));
+                stdb.drop_index(tx, IndexId(index.index_id))?;
+            }
+
+            let mut rows_to_migrate = Vec::new();
+            for alteration in updates.tables_to_alter {
+                self.system_logger()
+                    .info(&format!("Migrating table `{}`", alteration.table_name));
+                let rows = stdb.alter_table(tx, alteration.table_id, alteration.columns)?;
+                match alteration.migration {
+                    Some(migration) => {
+                        for row in rows {
+                            stdb.insert(tx, alteration.table_id, migration.migrate_row(row))?;
+                        }
+                    }
+                    None => rows_to_migrate.push((alteration.table_name, rows)),
+                }
+            }
+
+            Ok(rows_to_migrate)
+        })?;
+        tx = tx0;
+
+        // The rows of the tables which can't be migrated automatically are
+        // handed to the `migrate` reducer, within the same transaction, so a
+        // failed migration leaves the database as it was.
+        let mut migrate_results = Vec::with_capacity(rows_to_migrate.len());
+        if let Some(reducer_id) = migrate_reducer {
+            for (table_name, rows) in rows_to_migrate {
+                self.system_logger()
+                    .info(&format!("Invoking `migrate` reducer for table `{}`", table_name));
+                let (tx0, result) = self.call_migrate_reducer(tx, reducer_id, &table_name, rows);
+                tx = tx0;
+                let reason = failure_reason(&result.outcome);
+                migrate_results.push(result);
+                if let Some(reason) = reason {
+                    stdb.rollback_tx(tx);
+                    self.system_logger().error(&format!(
+                        "Module update rejected, as the migration of table `{}` failed",
+                        table_name
+                    ));
+                    return Ok(Err(UpdateDatabaseError::MigrationFailed {
+                        table: table_name,
+                        reason,
+                    }));
+                }
+            }
+        }
+
+        tx = stdb
+            .with_auto_rollback::<_, _, DBError>(tx, |tx| {
+                for index_def in updates.indexes_to_create {
+                    self.system_logger()
+                        .info(&format!("Creating index `{}`", index_def.name));
+                    stdb.create_index(tx, index_def)?;
+                }
+                Ok(())
+            })
+            .map(|(tx, ())| tx)?;
+
+        tx = stdb
+            .with_auto_rollback(tx, |tx| self.schedule_repeating_reducers(tx))
+            .map(|(tx, ())| tx)?;
+
         // Update the module hash. Morally, this should be done _after_ calling
         // the `update` reducer, but that consumes our transaction context.
         tx = stdb
@@ -464,9 +627,17 @@ impl<T: WasmInstance> ModuleInstance for WasmModuleInstance<T> {
                     caller_identity,
                     caller_address,
                     client,
+                    None,
                     reducer_id,
                     ArgsTuple::default(),
                 );
+                // The reducer ran in the transaction of the update, so its
+                // failure rolled back the schema changes and the migrated rows too.
+                if let Some(reason) = failure_reason(&res.outcome) {
+                    self.system_logger()
+                        .error("Module update rejected, as the `update` reducer failed");
+                    return Ok(Err(UpdateDatabaseError::UpdateReducerFailed { reason }));
+                }
                 Some(res)
             }
         };
@@ -475,20 +646,118 @@ impl<T: WasmInstance> ModuleInstance for WasmModuleInstance<T> {
 
         Ok(Ok(UpdateDatabaseSuccess {
             update_result,
-            migrate_results: vec![],
+            migrate_results,
         }))
     }
 
+    fn schema_diff(&self) -> Result<SchemaDiff, anyhow::Error> {
+        let stdb = &*self.database_instance_context().relational_db;
+        stdb.with_read_only(|tx| Ok(self.schema_updates(tx, self.migrate_reducer().is_some())?.diff()))
+    }
+
     #[tracing::instrument(skip_all)]
     fn call_reducer(
         &mut self,
         caller_identity: Identity,
         caller_address: Option<Address>,
         client: Option<ClientConnectionSender>,
+        request_id: Option<u32>,
         reducer_id: usize,
         args: ArgsTuple,
     ) -> ReducerCallResult {
-        self.call_reducer_internal(None, caller_identity, caller_address, client, reducer_id, args)
+        let start_instant = Instant::now();
+        let timestamp = Timestamp::now();
+        let sender_address = caller_address.unwrap_or(Address::__dummy());
+        if let Err(errmsg) = self.authorize(reducer_id, &caller_identity, &sender_address, timestamp) {
+            log::info!("reducer call denied: {errmsg}");
+            let event = ModuleEvent {
+                timestamp,
+                caller_identity,
+                caller_address,
+                request_id,
+                function_call: ModuleFunctionCall {
+                    reducer: self.info.reducers[reducer_id].name.clone(),
+                    args,
+                },
+                status: EventStatus::Unauthorized(errmsg),
+                energy_quanta_used: EnergyDiff::ZERO,
+                host_execution_duration: start_instant.elapsed(),
+            };
+            return self.broadcast_event(client, event);
+        }
+        self.call_reducer_internal(
+            None,
+            caller_identity,
+            caller_address,
+            client,
+            request_id,
+            reducer_id,
+            args,
+        )
+    }
+
+    fn call_scheduled_reducer(&mut self, id: ScheduledReducerId) -> anyhow::Result<Option<ReducerCallResult>> {
+        let stdb = self.database_instance_context().relational_db.clone();
+        let now = Timestamp::now();
+        let mut tx = stdb.begin_tx();
+        // The row is deleted, or moved to the next run of a repeating reducer,
+        // in the transaction of the reducer, so the reducer runs at most once per time, unless it fails.
+        let taken = take_scheduled(&stdb, &mut tx, id).and_then(|scheduled| {
+            let Some(scheduled) = scheduled else {
+                return Ok(None);
+            };
+            let next = reschedule(&stdb, &mut tx, scheduled.clone(), now)?;
+            Ok(Some((scheduled, next)))
+        });
+        let (scheduled, next) = match taken {
+            Ok(Some(taken)) => taken,
+            Ok(None) => {
+                stdb.rollback_tx(tx);
+                return Ok(None);
+            }
+            Err(e) => {
+                stdb.rollback_tx(tx);
+                return Err(e.into());
+            }
+        };
+
+        let reducer = self
+            .info
+            .reducers
+            .get_full(&scheduled.reducer)
+            .ok_or(ReducerCallError::NoSuchReducer)
+            .and_then(|(reducer_id, _, schema)| {
+                let args =
+                    ReducerArgs::Bsatn(scheduled.args.into()).into_tuple(self.info.typespace.with_type(schema))?;
+                Ok((reducer_id, args))
+            });
+        let (reducer_id, args) = match reducer {
+            Ok(reducer) => reducer,
+            Err(e) => {
+                // The reducer can never be called, so it isn't kept scheduled, even if it repeats.
+                if next.is_some() {
+                    take_scheduled(&stdb, &mut tx, id)?;
+                }
+                stdb.commit_tx(tx)?;
+                return Err(e).with_context(|| format!("scheduled reducer `{}`", scheduled.reducer));
+            }
+        };
+
+        let result = self.call_reducer_internal(Some(tx), self.info.identity, None, None, None, reducer_id, args);
+        let next = if matches!(result.outcome, ReducerOutcome::Committed) {
+            next
+        } else {
+            // The deletion of the row was rolled back along with the failed reducer,
+            // but a repeating reducer still runs next time.
+            stdb.with_auto_commit(|tx| match take_scheduled(&stdb, tx, id)? {
+                Some(scheduled) => reschedule(&stdb, tx, scheduled, now),
+                None => Ok(None),
+            })?
+        };
+        if let Some(at) = next {
+            self.instance.instance_env().scheduler.queue(id, at);
+        }
+        Ok(Some(result))
     }
 }
 
@@ -508,12 +777,14 @@ impl<T: WasmInstance> WasmModuleInstance<T> {
     /// the call.
     ///
     /// See also: [`Self::execute`]
+    #[allow(clippy::too_many_arguments)]
     fn call_reducer_internal(
         &mut self,
         tx: Option<MutTxId>,
         caller_identity: Identity,
         caller_address: Option<Address>,
         client: Option<ClientConnectionSender>,
+        request_id: Option<u32>,
         reducer_id: usize,
         mut args: ArgsTuple,
     ) -> ReducerCallResult {
@@ -538,13 +809,12 @@ impl<T: WasmInstance> WasmModuleInstance<T> {
 
         let execution_duration = start_instant.elapsed();
 
-        let outcome = ReducerOutcome::from(&status);
-
         let reducerdef = &self.info.reducers[reducer_id];
         let event = ModuleEvent {
             timestamp,
             caller_identity,
             caller_address,
+            request_id,
             function_call: ModuleFunctionCall {
                 reducer: reducerdef.name.clone(),
                 args,
@@ -553,12 +823,62 @@ impl<T: WasmInstance> WasmModuleInstance<T> {
             energy_quanta_used: energy.used,
             host_execution_duration: execution_duration,
         };
+        self.broadcast_event(client, event)
+    }
+
+    /// Broadcast the `event` of a reducer call, returning its outcome.
+    fn broadcast_event(&self, client: Option<ClientConnectionSender>, event: ModuleEvent) -> ReducerCallResult {
+        let result = ReducerCallResult {
+            outcome: ReducerOutcome::from(&event.status),
+            energy_used: event.energy_quanta_used,
+            execution_duration: event.host_execution_duration,
+        };
         self.event_tx.broadcast_event_blocking(client.as_ref(), event);
+        result
+    }
 
-        ReducerCallResult {
-            outcome,
-            energy_used: energy.used,
-            execution_duration,
+    /// Check that `sender_identity` may call the reducer `reducer_id`
+    /// according to its authorization rule, if it has one.
+    ///
+    /// A guard runs in a transaction of its own, which is always rolled back.
+    ///
+    /// Returns why the call is denied, if it is.
+    fn authorize(
+        &mut self,
+        reducer_id: usize,
+        sender_identity: &Identity,
+        sender_address: &Address,
+        timestamp: Timestamp,
+    ) -> Result<(), String> {
+        let info = self.info.clone();
+        let reducer = &*info.reducers[reducer_id].name;
+        match info.reducer_auth.get(reducer) {
+            None => Ok(()),
+            Some(ReducerAuthRule::Owner) if *sender_identity == info.identity => Ok(()),
+            Some(ReducerAuthRule::Owner) => Err(format!(
+                "only the owner of the database may call the reducer `{reducer}`"
+            )),
+            Some(ReducerAuthRule::Guard(guard)) => {
+                let stdb = self.database_instance_context().relational_db.clone();
+                let tx_slot = self.instance.instance_env().tx.clone();
+                let (tx, allowed) = tx_slot.set(stdb.begin_tx(), || {
+                    self.instance
+                        .call_guard(reducer, sender_identity, sender_address, timestamp)
+                });
+                stdb.rollback_tx(tx);
+                match allowed {
+                    Ok(true) => Ok(()),
+                    Ok(false) => Err(format!(
+                        "the guard `{guard}` denied the call to the reducer `{reducer}`"
+                    )),
+                    Err(err) => {
+                        T::log_traceback("guard", guard, &err);
+                        // discard this instance
+                        self.trapped = true;
+                        Err(format!("the guard `{guard}` of the reducer `{reducer}` failed"))
+                    }
+                }
+            }
         }
     }
 
@@ -575,9 +895,48 @@ impl<T: WasmInstance> WasmModuleInstance<T> {
     // from transaction data in the [`EventStatus::Committed`] (i.e. success)
     // case.
     //
-    /// The method also performs various measurements and records energy usage.
+    /// See also: [`Self::execute_in_tx`]
     #[tracing::instrument(skip_all)]
     fn execute(&mut self, tx: Option<MutTxId>, op: ReducerOp<'_>) -> (EventStatus, EnergyStats) {
+        let address = &self.database_instance_context().address.to_abbreviated_hex();
+        let func_ident = self.info.reducers[op.id].name.clone();
+
+        let tx = tx.unwrap_or_else(|| self.database_instance_context().relational_db.begin_tx());
+        let (tx, result, energy) = self.execute_in_tx(tx, op);
+
+        let stdb = &*self.database_instance_context().relational_db;
+        let status = match result {
+            Err(status) => {
+                stdb.rollback_tx(tx);
+                status
+            }
+            Ok(()) => {
+                if let Some((tx_data, bytes_written)) = stdb.commit_tx(tx).unwrap() {
+                    // TODO(cloutiertyler): This tracking doesn't really belong here if we want to write transactions to disk
+                    // in batches. This is because it's possible for a tiny reducer call to trigger a whole commit to be written to disk.
+                    // We should track the commit sizes instead internally to the CommitLog probably.
+                    if let Some(bytes_written) = bytes_written {
+                        REDUCER_WRITE_SIZE
+                            .with_label_values(&[address, &func_ident])
+                            .observe(bytes_written as f64);
+                    }
+                    EventStatus::Committed(DatabaseUpdate::from_writes(stdb, &tx_data))
+                } else {
+                    todo!("Write skew, you need to implement retries my man, T-dawg.");
+                }
+            }
+        };
+        (status, energy)
+    }
+
+    /// Execute a reducer within the transaction `tx`, which is handed back
+    /// neither committed nor rolled back.
+    ///
+    /// Returns the [`EventStatus`] of the call if it failed, in which case the
+    /// transaction should be rolled back.
+    ///
+    /// The method also performs various measurements and records energy usage.
+    fn execute_in_tx(&mut self, tx: MutTxId, op: ReducerOp<'_>) -> (MutTxId, Result<(), EventStatus>, EnergyStats) {
         let address = &self.database_instance_context().address.to_abbreviated_hex();
         let func_ident = &*self.info.reducers[op.id].name;
         REDUCER_COUNT.with_label_values(&[address, func_ident]).inc();
@@ -591,8 +950,6 @@ impl<T: WasmInstance> WasmModuleInstance<T> {
 
         let budget = self.energy_monitor.reducer_budget(&energy_fingerprint);
 
-        let tx = tx.unwrap_or_else(|| self.database_instance_context().relational_db.begin_tx());
-
         let tx_slot = self.instance.instance_env().tx.clone();
         let (tx, result) = tx_slot.set(tx, || {
             self.instance.call_reducer(
@@ -640,46 +997,83 @@ impl<T: WasmInstance> WasmModuleInstance<T> {
         //     };
         // }
 
-        let stdb = &*self.database_instance_context().relational_db;
-        let status = match call_result {
+        let result = match call_result {
             Err(err) => {
-                stdb.rollback_tx(tx);
-
                 T::log_traceback("reducer", func_ident, &err);
 
                 // discard this instance
                 self.trapped = true;
 
                 if energy.remaining == EnergyQuanta::ZERO {
-                    EventStatus::OutOfEnergy
+                    Err(EventStatus::OutOfEnergy)
                 } else {
-                    EventStatus::Failed("The Wasm instance encountered a fatal error.".into())
+                    Err(EventStatus::Failed(
+                        "The Wasm instance encountered a fatal error.".into(),
+                    ))
                 }
             }
             Ok(Err(errmsg)) => {
-                stdb.rollback_tx(tx);
-
                 log::info!("reducer returned error: {errmsg}");
 
-                EventStatus::Failed(errmsg.into())
-            }
-            Ok(Ok(())) => {
-                if let Some((tx_data, bytes_written)) = stdb.commit_tx(tx).unwrap() {
-                    // TODO(cloutiertyler): This tracking doesn't really belong here if we want to write transactions to disk
-                    // in batches. This is because it's possible for a tiny reducer call to trigger a whole commit to be written to disk.
-                    // We should track the commit sizes instead internally to the CommitLog probably.
-                    if let Some(bytes_written) = bytes_written {
-                        REDUCER_WRITE_SIZE
-                            .with_label_values(&[address, func_ident])
-                            .observe(bytes_written as f64);
-                    }
-                    EventStatus::Committed(DatabaseUpdate::from_writes(stdb, &tx_data))
-                } else {
-                    todo!("Write skew, you need to implement retries my man, T-dawg.");
-                }
+                Err(EventStatus::Failed(errmsg.into()))
             }
+            Ok(Ok(())) => Ok(()),
         };
-        (status, energy)
+        (tx, result, energy)
+    }
+
+    /// Call the `migrate` reducer within the transaction `tx`, with the name
+    /// of a table and its `rows` in the shape of its old columns, which the
+    /// reducer is expected to insert back in the shape of the new ones.
+    ///
+    /// The rows are passed encoded in BSATN, as the module no longer defines
+    /// their type. The transaction is handed back neither committed nor
+    /// rolled back.
+    fn call_migrate_reducer(
+        &mut self,
+        tx: MutTxId,
+        reducer_id: usize,
+        table_name: &str,
+        rows: Vec<ProductValue>,
+    ) -> (MutTxId, ReducerCallResult) {
+        let start_instant = Instant::now();
+
+        let rows = rows
+            .iter()
+            .map(|row| ArrayValue::U8(bsatn::to_vec(row).unwrap()))
+            .collect::<Vec<_>>();
+        let mut args = ArgsTuple {
+            tuple: product![table_name.to_owned(), AlgebraicValue::Array(rows.into())],
+            bsatn: None,
+            json: None,
+        };
+
+        let caller_identity = self.database_instance_context().identity;
+        let caller_address = self
+            .database_instance_context()
+            .publisher_address
+            .unwrap_or(Address::__dummy());
+        let (tx, result, energy) = self.execute_in_tx(
+            tx,
+            ReducerOp {
+                id: reducer_id,
+                sender_identity: &caller_identity,
+                sender_address: &caller_address,
+                timestamp: Timestamp::now(),
+                arg_bytes: args.get_bsatn().clone(),
+            },
+        );
+
+        let outcome = match result {
+            Ok(()) => ReducerOutcome::Committed,
+            Err(status) => ReducerOutcome::from(&status),
+        };
+        let result = ReducerCallResult {
+            outcome,
+            energy_used: energy.used,
+            execution_duration: start_instant.elapsed(),
+        };
+        (tx, result)
     }
 
     // Helpers - NOT API
@@ -725,16 +1119,12 @@ impl<T: WasmInstance> WasmModuleInstance<T> {
             // If there's an index defined for this column already, use it
             // making sure that it is unique if the column has a unique constraint
             if let Some(index) = index_for_column {
-                match index.ty {
-                    IndexType::BTree => {}
-                    // TODO
-                    IndexType::Hash => anyhow::bail!("hash indexes not yet supported"),
-                }
                 let index = IndexDef {
                     table_id: 0, // Will be ignored
                     cols: NonEmpty::new(col_id as u32),
                     name: index.name.clone(),
                     is_unique: col_attr.is_unique(),
+                    index_type: index.ty,
                 };
                 indexes.push(index);
             } else if col_attr.is_unique() {
@@ -745,6 +1135,7 @@ impl<T: WasmInstance> WasmModuleInstance<T> {
                     cols: NonEmpty::new(col_id as u32),
                     name: format!("{}_{}_unique", table.name, col.col_name),
                     is_unique: true,
+                    index_type: IndexType::BTree,
                 };
                 indexes.push(index);
             }
@@ -764,31 +1155,46 @@ impl<T: WasmInstance> WasmModuleInstance<T> {
         SystemLogger { inner }
     }
 
-    /// Compute the diff between the current and proposed schema.
-    fn schema_updates(&self, tx: &MutTxId) -> anyhow::Result<SchemaUpdates> {
-        let stdb = &*self.database_instance_context().relational_db;
+    /// Returns the id of the module's `migrate` reducer, if it defines one
+    /// taking the name of a table and its rows, i.e. `(String, Vec<Vec<u8>>)`.
+    fn migrate_reducer(&self) -> Option<usize> {
+        let (reducer_id, _, reducer) = self.info.reducers.get_full(MIGRATE_DUNDER)?;
+        let arg_types = reducer.args.iter().map(|arg| &arg.algebraic_type);
+        if arg_types.eq([&AlgebraicType::String, &AlgebraicType::array(AlgebraicType::bytes())]) {
+            Some(reducer_id)
+        } else {
+            self.system_logger().warn(
+                "The `migrate` reducer is ignored, as it should take the name of a table and its rows: `(String, OldRows)`",
+            );
+            None
+        }
+    }
 
-        // Until we know how to migrate schemas, we only accept `TableDef`s for
-        // existing tables which are equal sans their indexes.
-        struct Equiv<'a>(&'a TableDef);
-        impl PartialEq for Equiv<'_> {
-            fn eq(&self, other: &Self) -> bool {
-                let TableDef {
-                    table_name,
-                    columns,
-                    indexes: _,
-                    table_type,
-                    table_access,
-                } = &self.0;
-                table_name == &other.0.table_name
-                    && table_type == &other.0.table_type
-                    && table_access == &other.0.table_access
-                    && columns == &other.0.columns
-            }
+    /// Schedules the reducers that the module declares to repeat, in `tx`,
+    /// keeping those already scheduled the same way by a previous version of the module.
+    fn schedule_repeating_reducers(&self, tx: &mut MutTxId) -> anyhow::Result<()> {
+        let now = Timestamp::now();
+        for repeating in &self.info.repeating_reducers {
+            self.instance
+                .instance_env()
+                .scheduler
+                .schedule_repeating_reducer(tx, repeating, now)
+                .with_context(|| format!("failed to schedule repeating reducer {}", repeating.reducer))?;
         }
+        Ok(())
+    }
+
+    /// Compute the diff between the current and proposed schema.
+    ///
+    /// The tables whose columns can't be migrated automatically are tainted,
+    /// unless `has_migrate_reducer`, in which case the `migrate` reducer
+    /// migrates their rows.
+    fn schema_updates(&self, tx: &MutTxId, has_migrate_reducer: bool) -> anyhow::Result<SchemaUpdates> {
+        let stdb = &*self.database_instance_context().relational_db;
 
         let mut new_tables = HashMap::new();
         let mut tainted_tables = Vec::new();
+        let mut tables_to_alter = Vec::new();
         let mut indexes_to_create = Vec::new();
         let mut indexes_to_drop = Vec::new();
 
@@ -798,47 +1204,82 @@ impl<T: WasmInstance> WasmModuleInstance<T> {
             .map(|schema| (schema.table_name.clone(), schema))
             .collect();
 
+        let no_defaults = HashMap::new();
         for table in self.info.catalog.values().filter_map(EntityDef::as_table) {
             let proposed_schema_def = self.schema_for(table)?;
             if let Some(known_schema) = known_tables.remove(&table.name) {
                 let table_id = known_schema.table_id;
                 let known_schema_def = TableDef::from(&*known_schema);
-                // If the schemas differ acc. to [Equiv], the update should be
-                // rejected.
-                if Equiv(&known_schema_def) != Equiv(&proposed_schema_def) {
-                    self.system_logger()
-                        .warn(&format!("stored and proposed schema of `{}` differ", table.name));
-                    tainted_tables.push(table.name.to_owned());
-                } else {
-                    // The schema is unchanged, but maybe the indexes are.
-                    let mut known_indexes = known_schema
-                        .indexes
-                        .iter()
-                        .map(|idx| (&idx.index_name, idx))
-                        .collect::<BTreeMap<_, _>>();
-
-                    for mut index_def in proposed_schema_def.indexes {
-                        // This is zero in the proposed schema, as the table id
-                        // is not known at proposal time.
-                        index_def.table_id = table_id;
-
-                        match known_indexes.remove(&index_def.name) {
-                            None => indexes_to_create.push(index_def),
-                            Some(known_index) => {
-                                let known_id = IndexId(known_index.index_id);
-                                let known_index_def = IndexDef::from(known_index.clone());
-                                if known_index_def != index_def {
-                                    indexes_to_drop.push(known_id);
-                                    indexes_to_create.push(index_def);
-                                }
+                // The columns may be migrated automatically, see [TableMigration],
+                // but the type and access of the table can't change.
+                let mut reasons = Vec::new();
+                if known_schema_def.table_type != proposed_schema_def.table_type {
+                    reasons.push(format!(
+                        "table type changed from {:?} to {:?}",
+                        known_schema_def.table_type, proposed_schema_def.table_type
+                    ));
+                }
+                if known_schema_def.table_access != proposed_schema_def.table_access {
+                    reasons.push(format!(
+                        "table access changed from {:?} to {:?}",
+                        known_schema_def.table_access, proposed_schema_def.table_access
+                    ));
+                }
+                let defaults = self.info.column_defaults.get(&table.name).unwrap_or(&no_defaults);
+                let migration =
+                    match TableMigration::new(&known_schema_def.columns, &proposed_schema_def.columns, defaults) {
+                        Ok(migration) => Some(migration),
+                        // The module migrates the rows itself.
+                        Err(_) if has_migrate_reducer => None,
+                        Err(errors) => {
+                            reasons.extend(errors.iter().map(ToString::to_string));
+                            None
+                        }
+                    };
+                if !reasons.is_empty() {
+                    tainted_tables.push(TaintedTable {
+                        table: table.name.to_owned(),
+                        reasons,
+                    });
+                    continue;
+                }
+
+                if !migration.as_ref().is_some_and(TableMigration::is_identity) {
+                    tables_to_alter.push(TableAlteration {
+                        table_id,
+                        table_name: table.name.to_owned(),
+                        columns: proposed_schema_def.columns,
+                        migration,
+                    });
+                }
+
+                // Maybe the indexes changed as well.
+                let mut known_indexes = known_schema
+                    .indexes
+                    .iter()
+                    .map(|idx| (&idx.index_name, idx))
+                    .collect::<BTreeMap<_, _>>();
+
+                for mut index_def in proposed_schema_def.indexes {
+                    // This is zero in the proposed schema, as the table id
+                    // is not known at proposal time.
+                    index_def.table_id = table_id;
+
+                    match known_indexes.remove(&index_def.name) {
+                        None => indexes_to_create.push(index_def),
+                        Some(known_index) => {
+                            let known_index_def = IndexDef::from(known_index.clone());
+                            if known_index_def != index_def {
+                                indexes_to_drop.push(known_index.clone());
+                                indexes_to_create.push(index_def);
                             }
                         }
                     }
+                }
 
-                    // Indexes not in the proposed schema shall be dropped.
-                    for index in known_indexes.into_values() {
-                        indexes_to_drop.push(IndexId(index.index_id));
-                    }
+                // Indexes not in the proposed schema shall be dropped.
+                for index in known_indexes.into_values() {
+                    indexes_to_drop.push(index.clone());
                 }
             } else {
                 new_tables.insert(table.name.to_owned(), proposed_schema_def);
@@ -848,15 +1289,17 @@ impl<T: WasmInstance> WasmModuleInstance<T> {
         // but for now it's an incompatible schema change
         for orphan in known_tables.into_keys() {
             if !orphan.starts_with("st_") {
-                self.system_logger()
-                    .warn(format!("Orphaned table: {}", orphan).as_str());
-                tainted_tables.push(orphan);
+                tainted_tables.push(TaintedTable {
+                    table: orphan,
+                    reasons: vec!["orphaned table, which the module no longer defines".to_owned()],
+                });
             }
         }
 
         Ok(SchemaUpdates {
             new_tables,
             tainted_tables,
+            tables_to_alter,
             indexes_to_drop,
             indexes_to_create,
         })
@@ -866,19 +1309,62 @@ impl<T: WasmInstance> WasmModuleInstance<T> {
 struct SchemaUpdates {
     /// Tables to create.
     new_tables: HashMap<String, TableDef>,
-    /// Names of tables with incompatible schema updates.
-    tainted_tables: Vec<String>,
+    /// Tables with incompatible schema updates.
+    tainted_tables: Vec<TaintedTable>,
+    /// Tables whose columns changed, migrated either automatically or by the
+    /// `migrate` reducer.
+    ///
+    /// Should be processed _after_ `indexes_to_drop`, which may be on the old
+    /// columns, and _before_ `indexes_to_create`, which may be on the new ones.
+    tables_to_alter: Vec<TableAlteration>,
     /// Indexes to drop.
     ///
     /// Should be processed _before_ `indexes_to_create`, as we might be
     /// updating (i.e. drop then create with different parameters).
-    indexes_to_drop: Vec<IndexId>,
+    indexes_to_drop: Vec<IndexSchema>,
     /// Indexes to create.
     ///
     /// Should be processed _after_ `indexes_to_drop`.
     indexes_to_create: Vec<IndexDef>,
 }
 
+impl SchemaUpdates {
+    /// Returns what the updates change, as reported by a dry-run publish.
+    fn diff(&self) -> SchemaDiff {
+        SchemaDiff {
+            tables_to_create: self.new_tables.keys().cloned().sorted().collect(),
+            tables_to_migrate: self.tables_to_alter.iter().map(|t| t.table_name.clone()).collect(),
+            indexes_to_create: self.indexes_to_create.iter().map(|index| index.name.clone()).collect(),
+            indexes_to_drop: self
+                .indexes_to_drop
+                .iter()
+                .map(|index| index.index_name.clone())
+                .collect(),
+            tainted_tables: self.tainted_tables.clone(),
+        }
+    }
+}
+
+/// The migration of an existing table to new columns.
+struct TableAlteration {
+    table_id: u32,
+    table_name: String,
+    /// The new columns of the table.
+    columns: Vec<ColumnDef>,
+    /// The conversion of the existing rows to the new columns,
+    /// or `None` if they are converted by the `migrate` reducer.
+    migration: Option<TableMigration>,
+}
+
+/// Why a reducer called by an update failed, `None` if it committed.
+fn failure_reason(outcome: &ReducerOutcome) -> Option<String> {
+    match outcome {
+        ReducerOutcome::Committed => None,
+        ReducerOutcome::Failed(e) | ReducerOutcome::Unauthorized(e) => Some(e.clone()),
+        ReducerOutcome::BudgetExceeded => Some("reducer ran out of energy".to_owned()),
+    }
+}
+
 #[derive(Debug)]
 struct ReducerOp<'a> {
     id: usize,
@@ -887,3 +1373,690 @@ struct ReducerOp<'a> {
     timestamp: Timestamp,
     arg_bytes: Bytes,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migration::MigrationError;
+    use crate::db::{Config, FsyncPolicy, Storage};
+    use crate::hash::hash_bytes;
+    use crate::host::NullEnergyMonitor;
+    use once_cell::sync::Lazy;
+    use spacetimedb_lib::auth::{StAccess, StTableType};
+    use spacetimedb_lib::{ColumnDefault, ColumnIndexAttribute, ReducerAuth, ReducerDef};
+    use spacetimedb_sats::{ProductType, ProductTypeElement};
+    use tempdir::TempDir;
+    use wasmer::{ExternType, FunctionType, MemoryType, Type};
+
+    type FakeReducer =
+        Arc<dyn Fn(&InstanceEnv, &Identity, &[u8]) -> Result<Result<(), Box<str>>, anyhow::Error> + Send + Sync>;
+    type FakeGuard = Arc<dyn Fn(&InstanceEnv, &Identity) -> Result<bool, anyhow::Error> + Send + Sync>;
+
+    /// A module whose reducers and guards are closures rather than wasm functions.
+    #[derive(Clone, Default)]
+    struct FakeModule {
+        def: ModuleDef,
+        reducers: Vec<FakeReducer>,
+        guards: HashMap<String, FakeGuard>,
+        energy_per_call: EnergyDiff,
+    }
+
+    impl FakeModule {
+        fn table(mut self, name: &str, columns: &[(&str, AlgebraicType)]) -> Self {
+            let columns = columns
+                .iter()
+                .map(|(name, ty)| ProductTypeElement::new_named(ty.clone(), *name))
+                .collect();
+            let data = self
+                .def
+                .typespace
+                .add(AlgebraicType::Product(ProductType::new(columns)));
+            self.def.tables.push(spacetimedb_lib::TableDef {
+                name: name.to_owned(),
+                data,
+                column_attrs: vec![
+                    ColumnIndexAttribute::UNSET;
+                    self.def.typespace[data].as_product().unwrap().elements.len()
+                ],
+                indexes: vec![],
+                table_type: StTableType::User,
+                table_access: StAccess::Public,
+            });
+            self
+        }
+
+        fn reducer(
+            mut self,
+            name: &str,
+            args: &[AlgebraicType],
+            f: impl Fn(&InstanceEnv, &Identity, &[u8]) -> Result<Result<(), Box<str>>, anyhow::Error>
+                + Send
+                + Sync
+                + 'static,
+        ) -> Self {
+            self.def.reducers.push(ReducerDef {
+                name: name.to_owned(),
+                args: args
+                    .iter()
+                    .map(|ty| ProductTypeElement::new(ty.clone(), None))
+                    .collect(),
+            });
+            self.reducers.push(Arc::new(f));
+            self
+        }
+
+        fn auth(mut self, reducer: &str, rule: ReducerAuthRule) -> Self {
+            self.def.misc_exports.push(MiscModuleExport::ReducerAuth(ReducerAuth {
+                reducer: reducer.to_owned(),
+                rule,
+            }));
+            self
+        }
+
+        fn column_default(mut self, table: &str, column: &str, value: AlgebraicValue) -> Self {
+            self.def
+                .misc_exports
+                .push(MiscModuleExport::ColumnDefault(ColumnDefault {
+                    table: table.to_owned(),
+                    column: column.to_owned(),
+                    value: bsatn::to_vec(&value).unwrap(),
+                }));
+            self
+        }
+
+        fn guard(
+            mut self,
+            reducer: &str,
+            f: impl Fn(&InstanceEnv, &Identity) -> Result<bool, anyhow::Error> + Send + Sync + 'static,
+        ) -> Self {
+            self.guards.insert(reducer.to_owned(), Arc::new(f));
+            self.auth(reducer, ReducerAuthRule::Guard(format!("{reducer}_guard")))
+        }
+
+        /// Makes each reducer call cost `energy`, to tell a call apart from one that never reached the reducer.
+        fn energy_per_call(mut self, energy: EnergyDiff) -> Self {
+            self.energy_per_call = energy;
+            self
+        }
+    }
+
+    impl WasmModule for FakeModule {
+        type Instance = FakeInstance;
+        type InstancePre = FakeModule;
+        type ExternType = ExternType;
+
+        fn get_export(&self, s: &str) -> Option<ExternType> {
+            let func = |params: &[Type], results: &[Type]| ExternType::Function(FunctionType::new(params, results));
+            match s {
+                "memory" => Some(ExternType::Memory(MemoryType::new(1, None, false))),
+                CALL_REDUCER_DUNDER => Some(func(
+                    &[Type::I32, Type::I32, Type::I32, Type::I64, Type::I32],
+                    &[Type::I32],
+                )),
+                DESCRIBE_MODULE_DUNDER => Some(func(&[], &[Type::I32])),
+                _ => {
+                    let reducer = s.strip_prefix(GUARD_DUNDER)?;
+                    self.guards
+                        .contains_key(reducer)
+                        .then(|| func(&[Type::I32, Type::I32, Type::I64], &[Type::I32]))
+                }
+            }
+        }
+
+        fn for_each_export<E>(&self, mut f: impl FnMut(&str, &ExternType) -> Result<(), E>) -> Result<(), E> {
+            let guards = self.guards.keys().map(|reducer| format!("{GUARD_DUNDER}{reducer}"));
+            for name in ["memory", CALL_REDUCER_DUNDER, DESCRIBE_MODULE_DUNDER]
+                .map(String::from)
+                .into_iter()
+                .chain(guards)
+            {
+                f(&name, &self.get_export(&name).unwrap())?;
+            }
+            Ok(())
+        }
+
+        fn instantiate_pre(&self) -> Result<Self, InitializationError> {
+            Ok(self.clone())
+        }
+    }
+
+    impl WasmInstancePre for FakeModule {
+        type Instance = FakeInstance;
+
+        fn instantiate(&self, env: InstanceEnv, _func_names: &FuncNames) -> Result<FakeInstance, InitializationError> {
+            Ok(FakeInstance {
+                module: self.clone(),
+                env,
+            })
+        }
+    }
+
+    struct FakeInstance {
+        module: FakeModule,
+        env: InstanceEnv,
+    }
+
+    impl WasmInstance for FakeInstance {
+        fn extract_descriptions(&mut self) -> Result<Bytes, DescribeError> {
+            Ok(bsatn::to_vec(&self.module.def).unwrap().into())
+        }
+
+        fn instance_env(&self) -> &InstanceEnv {
+            &self.env
+        }
+
+        type Trap = anyhow::Error;
+
+        fn call_reducer(
+            &mut self,
+            reducer_id: usize,
+            budget: EnergyQuanta,
+            sender_identity: &Identity,
+            _sender_address: &Address,
+            _timestamp: Timestamp,
+            arg_bytes: Bytes,
+        ) -> ExecuteResult<Self::Trap> {
+            let call_result = (self.module.reducers[reducer_id])(&self.env, sender_identity, &arg_bytes);
+            ExecuteResult {
+                energy: EnergyStats {
+                    used: self.module.energy_per_call,
+                    remaining: budget,
+                },
+                execution_duration: Duration::ZERO,
+                call_result,
+            }
+        }
+
+        fn call_guard(
+            &mut self,
+            reducer: &str,
+            sender_identity: &Identity,
+            _sender_address: &Address,
+            _timestamp: Timestamp,
+        ) -> Result<bool, Self::Trap> {
+            (self.module.guards[reducer])(&self.env, sender_identity)
+        }
+
+        fn log_traceback(func_type: &str, func: &str, trap: &Self::Trap) {
+            log::info!("{func_type} `{func}` trapped: {trap:#}");
+        }
+    }
+
+    static OWNER: Lazy<Identity> = Lazy::new(|| Identity::from_hashing_bytes("owner"));
+
+    /// Runs `test` against a fresh database owned by [OWNER], within the tokio runtime that [instantiate] needs.
+    fn with_database(name: &str, test: impl FnOnce(&Arc<DatabaseInstanceContext>)) {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+        let tmp_dir = TempDir::new(name).unwrap();
+        let config = Config {
+            fsync: FsyncPolicy::Never,
+            storage: Storage::Memory,
+            snapshot_interval: None,
+            compact_log: false,
+        };
+        let dbic = DatabaseInstanceContext::new(
+            config,
+            0,
+            0,
+            *OWNER,
+            Address::zero(),
+            tmp_dir.path().join("db"),
+            &tmp_dir.path().join("log"),
+            None,
+        );
+        test(&dbic);
+    }
+
+    /// Instantiates `module` on the database of `dbic`, which must be done within a tokio runtime.
+    fn instantiate(
+        dbic: &Arc<DatabaseInstanceContext>,
+        name: &str,
+        module: FakeModule,
+    ) -> WasmModuleInstance<FakeInstance> {
+        let (scheduler, _) = Scheduler::open(dbic.relational_db.clone());
+        let mut actor = WasmModuleHostActor::new(
+            dbic.clone(),
+            hash_bytes(name),
+            module,
+            scheduler,
+            Arc::new(NullEnergyMonitor),
+        )
+        .unwrap();
+        actor.initial_instances().unwrap()
+    }
+
+    fn person_v1() -> FakeModule {
+        FakeModule::default()
+            .table(
+                "person",
+                &[("name", AlgebraicType::String), ("age", AlgebraicType::U32)],
+            )
+            .reducer("add", &[AlgebraicType::String, AlgebraicType::U32], |env, _, args| {
+                let table_id = env.get_table_id("person".into())?;
+                env.insert(table_id, args)?;
+                Ok(Ok(()))
+            })
+    }
+
+    /// The next version of [person_v1], in which the age is a string,
+    /// and which defines a new table.
+    fn person_v2(
+        migrate: impl Fn(&InstanceEnv, &str, Vec<ProductValue>) -> Result<(), Box<str>> + Send + Sync + 'static,
+    ) -> FakeModule {
+        FakeModule::default()
+            .table(
+                "person",
+                &[("name", AlgebraicType::String), ("age", AlgebraicType::String)],
+            )
+            .table("pet", &[("name", AlgebraicType::String)])
+            .reducer(
+                MIGRATE_DUNDER,
+                &[AlgebraicType::String, AlgebraicType::array(AlgebraicType::bytes())],
+                move |env, _, args| {
+                    let args_ty =
+                        ProductType::from([AlgebraicType::String, AlgebraicType::array(AlgebraicType::bytes())]);
+                    let args = ProductValue::decode(&args_ty, &mut &args[..])?;
+                    let table = args.elements[0].as_string().unwrap();
+                    let row_ty = ProductType::from([AlgebraicType::String, AlgebraicType::U32]);
+                    let ArrayValue::Array(rows) = args.elements[1].as_array().unwrap() else {
+                        panic!("rows should be an array of byte arrays")
+                    };
+                    let rows = rows
+                        .iter()
+                        .map(|row| {
+                            let ArrayValue::U8(row) = row else {
+                                panic!("a row should be a byte array")
+                            };
+                            ProductValue::decode(&row_ty, &mut &row[..])
+                        })
+                        .collect::<Result<_, _>>()?;
+                    Ok(migrate(env, table, rows))
+                },
+            )
+    }
+
+    /// Inserts the `row` of the `person` table of [person_v2], in the transaction of the `migrate` reducer.
+    fn insert_person_v2(env: &InstanceEnv, row: &ProductValue) -> Result<(), Box<str>> {
+        let table_id = env.get_table_id("person".into()).map_err(|e| e.to_string())?;
+        env.insert(table_id, &bsatn::to_vec(row).unwrap())
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn rows(dbic: &DatabaseInstanceContext, table: &str) -> Vec<ProductValue> {
+        let stdb = &*dbic.relational_db;
+        stdb.with_read_only(|tx| {
+            let table_id = stdb.table_id_from_name(tx, table)?.unwrap();
+            Ok::<_, DBError>(
+                stdb.iter(tx, table_id)?
+                    .map(|row| row.view().clone())
+                    .sorted()
+                    .collect(),
+            )
+        })
+        .unwrap()
+    }
+
+    fn add_person(instance: &mut WasmModuleInstance<FakeInstance>, name: &str, age: u32) -> ReducerCallResult {
+        add_person_as(instance, *OWNER, name, age)
+    }
+
+    fn add_person_as(
+        instance: &mut WasmModuleInstance<FakeInstance>,
+        caller: Identity,
+        name: &str,
+        age: u32,
+    ) -> ReducerCallResult {
+        let (reducer_id, _, _) = instance.info.reducers.get_full("add").unwrap();
+        let args = ArgsTuple {
+            tuple: product![name.to_owned(), age],
+            bsatn: None,
+            json: None,
+        };
+        instance.call_reducer(caller, None, None, None, reducer_id, args)
+    }
+
+    /// Asserts that the call was denied without running the reducer.
+    fn assert_unauthorized(result: ReducerCallResult, reason: &str) {
+        match result.outcome {
+            ReducerOutcome::Unauthorized(errmsg) => assert!(errmsg.contains(reason), "unexpected reason: {errmsg}"),
+            outcome => panic!("unexpected outcome: {outcome:?}"),
+        }
+        assert_eq!(result.energy_used, EnergyDiff::ZERO);
+    }
+
+    #[test]
+    fn test_update_database_migrates_rows() {
+        with_database("update_database", |dbic| {
+            let mut v1 = instantiate(dbic, "v1", person_v1());
+            v1.init_database(1, ArgsTuple::default()).unwrap();
+            for (name, age) in [("alice", 30), ("bob", 40)] {
+                assert!(matches!(
+                    add_person(&mut v1, name, age).outcome,
+                    ReducerOutcome::Committed
+                ));
+            }
+
+            let mut v2 = instantiate(
+                dbic,
+                "v2",
+                person_v2(|env, table, rows| {
+                    assert_eq!(table, "person");
+                    for row in rows {
+                        let [name, age] = &row.elements[..] else { panic!() };
+                        let age = age.as_u32().unwrap().to_string();
+                        insert_person_v2(env, &product![name.clone(), age])?;
+                    }
+                    Ok(())
+                }),
+            );
+            let success = v2.update_database(2).unwrap().unwrap();
+            assert!(success.update_result.is_none());
+            assert_eq!(success.migrate_results.len(), 1);
+            assert!(matches!(success.migrate_results[0].outcome, ReducerOutcome::Committed));
+
+            assert_eq!(rows(dbic, "person"), [product!("alice", "30"), product!("bob", "40")]);
+            assert_eq!(rows(dbic, "pet"), []);
+            let stdb = &*dbic.relational_db;
+            let program_hash = stdb.with_read_only(|tx| stdb.program_hash(tx)).unwrap();
+            assert_eq!(program_hash, Some(hash_bytes("v2")));
+        });
+    }
+
+    #[test]
+    fn test_update_database_fills_new_columns_with_defaults() {
+        with_database("update_database", |dbic| {
+            let mut v1 = instantiate(dbic, "v1", person_v1());
+            v1.init_database(1, ArgsTuple::default()).unwrap();
+            assert!(matches!(
+                add_person(&mut v1, "alice", 30).outcome,
+                ReducerOutcome::Committed
+            ));
+
+            // The declared default of `nick` replaces its zero value,
+            // and `tags`, an array, has none at all.
+            let v2 = FakeModule::default()
+                .table(
+                    "person",
+                    &[
+                        ("name", AlgebraicType::String),
+                        ("age", AlgebraicType::U32),
+                        ("nick", AlgebraicType::String),
+                        ("tags", AlgebraicType::array(AlgebraicType::String)),
+                    ],
+                )
+                .column_default("person", "nick", AlgebraicValue::String("anon".into()))
+                .column_default("person", "tags", AlgebraicValue::Array(vec!["new".to_owned()].into()));
+            let mut v2 = instantiate(dbic, "v2", v2);
+            v2.update_database(2).unwrap().unwrap();
+
+            assert_eq!(
+                rows(dbic, "person"),
+                [product![
+                    "alice",
+                    30u32,
+                    "anon",
+                    AlgebraicValue::Array(vec!["new".to_owned()].into())
+                ]]
+            );
+        });
+    }
+
+    #[test]
+    fn test_bad_column_default_is_rejected() {
+        with_database("bad_column_default", |dbic| {
+            let module = person_v1().column_default("person", "age", AlgebraicValue::String("thirty".into()));
+            let (scheduler, _) = Scheduler::open(dbic.relational_db.clone());
+            let res = WasmModuleHostActor::new(
+                dbic.clone(),
+                hash_bytes("v1"),
+                module,
+                scheduler,
+                Arc::new(NullEnergyMonitor),
+            );
+            assert!(matches!(
+                res,
+                Err(InitializationError::Validation(
+                    ValidationError::BadColumnDefault { .. }
+                ))
+            ));
+        });
+    }
+
+    #[test]
+    fn test_update_database_failed_migration_rolls_back() {
+        with_database("update_database", |dbic| {
+            let mut v1 = instantiate(dbic, "v1", person_v1());
+            v1.init_database(1, ArgsTuple::default()).unwrap();
+            assert!(matches!(
+                add_person(&mut v1, "alice", 30).outcome,
+                ReducerOutcome::Committed
+            ));
+
+            // The migration fails after it has rewritten a row.
+            let mut v2 = instantiate(
+                dbic,
+                "v2",
+                person_v2(|env, _, _| {
+                    insert_person_v2(env, &product!("alice", "30"))?;
+                    Err("can't migrate".into())
+                }),
+            );
+            match v2.update_database(2).unwrap() {
+                Err(UpdateDatabaseError::MigrationFailed { table, reason }) => {
+                    assert_eq!(table, "person");
+                    assert_eq!(reason, "can't migrate");
+                }
+                res => panic!("unexpected result: {res:?}"),
+            }
+
+            // The schema and the rows are as they were, and the new table wasn't created.
+            let stdb = &*dbic.relational_db;
+            stdb.with_read_only(|tx| {
+                let table_id = stdb.table_id_from_name(tx, "person")?.unwrap();
+                let schema = stdb.schema_for_table(tx, table_id)?;
+                let types = schema
+                    .columns
+                    .iter()
+                    .map(|col| col.col_type.clone())
+                    .collect::<Vec<_>>();
+                assert_eq!(types, [AlgebraicType::String, AlgebraicType::U32]);
+                assert_eq!(stdb.table_id_from_name(tx, "pet")?, None);
+                assert_eq!(stdb.program_hash(tx)?, Some(hash_bytes("v1")));
+                Ok::<_, DBError>(())
+            })
+            .unwrap();
+            assert_eq!(rows(dbic, "person"), [product!("alice", 30u32)]);
+
+            // The old module still runs against the database.
+            assert!(matches!(
+                add_person(&mut v1, "bob", 40).outcome,
+                ReducerOutcome::Committed
+            ));
+            assert_eq!(rows(dbic, "person"), [product!("alice", 30u32), product!("bob", 40u32)]);
+        });
+    }
+
+    #[test]
+    fn test_update_database_failed_update_reducer_rolls_back() {
+        with_database("update_database", |dbic| {
+            let mut v1 = instantiate(dbic, "v1", person_v1());
+            v1.init_database(1, ArgsTuple::default()).unwrap();
+            assert!(matches!(
+                add_person(&mut v1, "alice", 30).outcome,
+                ReducerOutcome::Committed
+            ));
+
+            // The migration succeeds, but the `update` reducer run after it fails.
+            let v2 = person_v2(|env, _, rows| {
+                for row in rows {
+                    let [name, age] = &row.elements[..] else { panic!() };
+                    let age = age.as_u32().unwrap().to_string();
+                    insert_person_v2(env, &product![name.clone(), age])?;
+                }
+                Ok(())
+            })
+            .reducer(UPDATE_DUNDER, &[], |_, _, _| Ok(Err("can't update".into())));
+            let mut v2 = instantiate(dbic, "v2", v2);
+            match v2.update_database(2).unwrap() {
+                Err(UpdateDatabaseError::UpdateReducerFailed { reason }) => assert_eq!(reason, "can't update"),
+                res => panic!("unexpected result: {res:?}"),
+            }
+
+            // The schema change, the migrated rows and the new program hash were rolled back.
+            let stdb = &*dbic.relational_db;
+            stdb.with_read_only(|tx| {
+                assert_eq!(stdb.table_id_from_name(tx, "pet")?, None);
+                assert_eq!(stdb.program_hash(tx)?, Some(hash_bytes("v1")));
+                Ok::<_, DBError>(())
+            })
+            .unwrap();
+            assert_eq!(rows(dbic, "person"), [product!("alice", 30u32)]);
+
+            // The old module still runs against the database.
+            assert!(matches!(
+                add_person(&mut v1, "bob", 40).outcome,
+                ReducerOutcome::Committed
+            ));
+        });
+    }
+    #[test]
+    fn test_schema_diff() {
+        with_database("schema_diff", |dbic| {
+            let mut v1 = instantiate(
+                dbic,
+                "v1",
+                person_v1()
+                    .table("item", &[("id", AlgebraicType::U32)])
+                    .table("ghost", &[("id", AlgebraicType::U32)]),
+            );
+            v1.init_database(1, ArgsTuple::default()).unwrap();
+            assert!(matches!(
+                add_person(&mut v1, "alice", 30).outcome,
+                ReducerOutcome::Committed
+            ));
+
+            // `person` can't be migrated without a `migrate` reducer, but `item` can be,
+            // `pet` is new and `ghost` is gone.
+            let v2 = instantiate(
+                dbic,
+                "v2",
+                FakeModule::default()
+                    .table(
+                        "person",
+                        &[("name", AlgebraicType::String), ("age", AlgebraicType::String)],
+                    )
+                    .table("item", &[("id", AlgebraicType::U64), ("count", AlgebraicType::U32)])
+                    .table("pet", &[("name", AlgebraicType::String)]),
+            );
+            let mut diff = v2.schema_diff().unwrap();
+            diff.tainted_tables.sort_by(|a, b| a.table.cmp(&b.table));
+            let age_changed = MigrationError::ColumnTypeChanged {
+                name: "age".into(),
+                from: AlgebraicType::U32,
+                to: AlgebraicType::String,
+            };
+            assert_eq!(
+                diff,
+                SchemaDiff {
+                    tables_to_create: vec!["pet".into()],
+                    tables_to_migrate: vec!["item".into()],
+                    indexes_to_create: vec![],
+                    indexes_to_drop: vec![],
+                    tainted_tables: vec![
+                        TaintedTable {
+                            table: "ghost".into(),
+                            reasons: vec!["orphaned table, which the module no longer defines".into()],
+                        },
+                        TaintedTable {
+                            table: "person".into(),
+                            reasons: vec![age_changed.to_string()],
+                        },
+                    ],
+                }
+            );
+
+            // Nothing was committed.
+            let stdb = &*dbic.relational_db;
+            stdb.with_read_only(|tx| {
+                let table_id = stdb.table_id_from_name(tx, "item")?.unwrap();
+                let schema = stdb.schema_for_table(tx, table_id)?;
+                let types = schema
+                    .columns
+                    .iter()
+                    .map(|col| col.col_type.clone())
+                    .collect::<Vec<_>>();
+                assert_eq!(types, [AlgebraicType::U32]);
+                assert_eq!(stdb.table_id_from_name(tx, "pet")?, None);
+                assert!(stdb.table_id_from_name(tx, "ghost")?.is_some());
+                assert_eq!(stdb.program_hash(tx)?, Some(hash_bytes("v1")));
+                Ok::<_, DBError>(())
+            })
+            .unwrap();
+            assert_eq!(rows(dbic, "person"), [product!("alice", 30u32)]);
+        });
+    }
+
+    #[test]
+    fn test_owner_rule_denies_other_callers() {
+        with_database("authorize", |dbic| {
+            let module = person_v1()
+                .auth("add", ReducerAuthRule::Owner)
+                .energy_per_call(EnergyDiff(1));
+            let mut instance = instantiate(dbic, "v1", module);
+            instance.init_database(1, ArgsTuple::default()).unwrap();
+
+            let stranger = Identity::from_hashing_bytes("stranger");
+            assert_unauthorized(add_person_as(&mut instance, stranger, "alice", 30), "owner");
+            assert!(!instance.trapped());
+            assert_eq!(rows(dbic, "person"), []);
+
+            let result = add_person(&mut instance, "bob", 40);
+            assert!(matches!(result.outcome, ReducerOutcome::Committed));
+            assert_eq!(result.energy_used, EnergyDiff(1));
+            assert_eq!(rows(dbic, "person"), [product!("bob", 40u32)]);
+        });
+    }
+
+    #[test]
+    fn test_guard_denies_call() {
+        with_database("authorize", |dbic| {
+            // The guard writes to the database, which must not outlive the guard.
+            let module = person_v1()
+                .guard("add", |env, caller| {
+                    let table_id = env.get_table_id("person".into())?;
+                    env.insert(table_id, &bsatn::to_vec(&product!("guard", 0u32)).unwrap())?;
+                    Ok(*caller == *OWNER)
+                })
+                .energy_per_call(EnergyDiff(1));
+            let mut instance = instantiate(dbic, "v1", module);
+            instance.init_database(1, ArgsTuple::default()).unwrap();
+
+            let stranger = Identity::from_hashing_bytes("stranger");
+            assert_unauthorized(add_person_as(&mut instance, stranger, "alice", 30), "add_guard");
+            assert!(!instance.trapped());
+            assert_eq!(rows(dbic, "person"), []);
+
+            let result = add_person(&mut instance, "bob", 40);
+            assert!(matches!(result.outcome, ReducerOutcome::Committed));
+            assert_eq!(rows(dbic, "person"), [product!("bob", 40u32)]);
+        });
+    }
+
+    #[test]
+    fn test_trapping_guard_denies_call() {
+        with_database("authorize", |dbic| {
+            let module = person_v1()
+                .guard("add", |_, _| Err(anyhow::anyhow!("unreachable")))
+                .energy_per_call(EnergyDiff(1));
+            let mut instance = instantiate(dbic, "v1", module);
+            instance.init_database(1, ArgsTuple::default()).unwrap();
+
+            assert_unauthorized(add_person(&mut instance, "alice", 30), "failed");
+            // The instance is discarded, as after a trapping reducer.
+            assert!(instance.trapped());
+            assert_eq!(rows(dbic, "person"), []);
+        });
+    }
+}
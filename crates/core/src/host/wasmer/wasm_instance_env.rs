@@ -9,6 +9,7 @@ use crate::host::wasm_common::{
 };
 use bytes::Bytes;
 use itertools::Itertools;
+use spacetimedb_lib::schedule::{MisfirePolicy, Repeat};
 use wasmer::{FunctionEnvMut, MemoryAccessError, RuntimeError, ValueType, WasmPtr};
 
 use crate::host::instance_env::InstanceEnv;
@@ -178,11 +179,15 @@ impl WasmInstanceEnv {
     /// A generated schedule id is assigned to the reducer.
     /// This id is written to the pointer `out`.
     ///
+    /// The reducer is stored in the system table `st_scheduled`,
+    /// and is only scheduled if the transaction of the calling reducer commits.
+    ///
     /// Returns an error if
     /// - the `time` delay exceeds `64^6 - 1` milliseconds from now
     /// - `name` does not point to valid UTF-8
     /// - `name + name_len` or `args + args_len` overflow a 64-bit integer
     /// - writing to `out` overflows a 64-bit integer
+    /// - the reducer can't be inserted into `st_scheduled`
     #[tracing::instrument(skip_all)]
     pub fn schedule_reducer(
         caller: FunctionEnvMut<'_, Self>,
@@ -208,24 +213,84 @@ impl WasmInstanceEnv {
             let ScheduledReducerId(id) = caller
                 .data()
                 .instance_env
-                .schedule(name, args, Timestamp(time))
-                .map_err(|e| match e {
-                    ScheduleError::DelayTooLong(_) => RuntimeError::new("requested delay is too long"),
-                    ScheduleError::IdTransactionError(_) => {
-                        RuntimeError::new("transaction to acquire ScheduleReducerId failed")
-                    }
-                })?;
+                .schedule(name, args, Timestamp(time), None, MisfirePolicy::FireOnce)
+                .map_err(Self::schedule_error)?;
+            Ok(id)
+        })
+        .map(|_| ())
+    }
+
+    /// Schedules a reducer to be called asynchronously at `time`,
+    /// and then repeatedly, as described by the [`Repeat`] in the UTF-8 slice `(repeat, repeat_len)`,
+    /// e.g. `every 1mo` or `cron 0 9 * * mon-fri`.
+    ///
+    /// The runs missed while the host is down are handled on restart according to `misfire`,
+    /// which is `0` to skip them, `1` to run once in their place, or `2` to run them all.
+    ///
+    /// Otherwise, this is the same as [`Self::schedule_reducer`],
+    /// and the reducer keeps its id from one run to the next until it's cancelled.
+    ///
+    /// Returns an error if
+    /// - `repeat` is not a valid [`Repeat`]
+    /// - `misfire` is not a valid policy
+    /// - any of the conditions of [`Self::schedule_reducer`] happen
+    #[tracing::instrument(skip_all)]
+    pub fn schedule_reducer_repeating(
+        caller: FunctionEnvMut<'_, Self>,
+        name: WasmPtr<u8>,
+        name_len: u32,
+        args: WasmPtr<u8>,
+        args_len: u32,
+        time: u64,
+        repeat: WasmPtr<u8>,
+        repeat_len: u32,
+        misfire: u8,
+        out: WasmPtr<u64>,
+    ) -> RtResult<()> {
+        Self::cvt_ret(caller, "schedule_reducer_repeating", out, |caller, mem| {
+            let name = Self::read_string(&caller, mem, name, name_len)?;
+            let args = mem.read_bytes(&caller, args, args_len)?;
+            let repeat = mem.read_bytes(&caller, repeat, repeat_len)?;
+            let repeat = std::str::from_utf8(&repeat)
+                .map_err(|_| RuntimeError::new("repeat must be utf8"))?
+                .parse::<Repeat>()
+                .map_err(|e| RuntimeError::new(format!("invalid repeat: {e}")))?;
+            let misfire = MisfirePolicy::from_u8(misfire).ok_or_else(|| RuntimeError::new("invalid misfire policy"))?;
+
+            let ScheduledReducerId(id) = caller
+                .data()
+                .instance_env
+                .schedule(name, args, Timestamp(time), Some(repeat), misfire)
+                .map_err(Self::schedule_error)?;
             Ok(id)
         })
         .map(|_| ())
     }
 
+    fn schedule_error(e: ScheduleError) -> WasmError {
+        match e {
+            ScheduleError::DelayTooLong(_) => RuntimeError::new("requested delay is too long").into(),
+            ScheduleError::Nodes(e) => WasmError::Db(e),
+        }
+    }
+
     /// Unschedule a reducer using the same `id` generated as when it was scheduled.
     ///
     /// This assumes that the reducer hasn't already been executed.
+    ///
+    /// Traps if the row of the scheduled reducer can't be deleted.
     #[tracing::instrument(skip_all)]
-    pub fn cancel_reducer(caller: FunctionEnvMut<'_, Self>, id: u64) {
-        caller.data().instance_env.cancel_reducer(ScheduledReducerId(id))
+    pub fn cancel_reducer(caller: FunctionEnvMut<'_, Self>, id: u64) -> RtResult<()> {
+        caller
+            .data()
+            .instance_env
+            .cancel_reducer(ScheduledReducerId(id))
+            .map_err(|err| {
+                RuntimeError::user(Box::new(AbiRuntimeError {
+                    func: "cancel_reducer",
+                    err,
+                }))
+            })
     }
 
     /// Log at `level` a `message` message occuring in `filename:line_number`
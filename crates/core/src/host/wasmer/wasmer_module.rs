@@ -54,6 +54,11 @@ impl WasmerModule {
         imports! {
             "spacetime_6.0" => {
                 "_schedule_reducer" => Function::new_typed_with_env(store, env, WasmInstanceEnv::schedule_reducer),
+                "_schedule_reducer_repeating" => Function::new_typed_with_env(
+                    store,
+                    env,
+                    WasmInstanceEnv::schedule_reducer_repeating,
+                ),
                 "_cancel_reducer" => Function::new_typed_with_env(store, env, WasmInstanceEnv::cancel_reducer),
                 "_delete_by_col_eq" => Function::new_typed_with_env(
                     store,
@@ -292,6 +297,37 @@ impl module_host_actor::WasmInstance for WasmerInstance {
         )
     }
 
+    fn call_guard(
+        &mut self,
+        reducer: &str,
+        sender_identity: &Identity,
+        sender_address: &Address,
+        timestamp: Timestamp,
+    ) -> Result<bool, Self::Trap> {
+        let store = &mut self.store;
+        let instance = &self.instance;
+
+        // Like the initializers, guards run on a budget that isn't charged.
+        let budget = EnergyQuanta::DEFAULT_BUDGET.as_points();
+        wasmer_metering::set_remaining_points(store, instance, budget);
+
+        let guard = instance
+            .exports
+            .get_typed_function::<(u32, u32, u64), u32>(store, &format!("{GUARD_DUNDER}{reducer}"))
+            .map_err(|err| RuntimeError::new(err.to_string()))?;
+
+        let env = self.env.as_mut(store);
+        let sender_identity = env.insert_buffer(Bytes::copy_from_slice(sender_identity.as_bytes()));
+        let sender_address = env.insert_buffer(Bytes::copy_from_slice(sender_address.as_slice()));
+
+        let result = guard.call(store, sender_identity.0, sender_address.0, timestamp.0);
+
+        // Clear all of the instance state associated to this guard call.
+        self.env.as_mut(store).clear_reducer_state();
+
+        Ok(result? != 0)
+    }
+
     fn log_traceback(func_type: &str, func: &str, trap: &Self::Trap) {
         log_traceback(func_type, func, trap)
     }
@@ -35,6 +35,7 @@ pub enum MessageJson {
     TransactionUpdate(TransactionUpdateJson),
     IdentityToken(IdentityTokenJson),
     OneOffQueryResponse(OneOffQueryResponseJson),
+    QueryUpdate(QueryUpdateJson),
 }
 
 impl MessageJson {
@@ -75,6 +76,21 @@ pub struct TableRowOperationJson {
 #[derive(Debug, Clone, Serialize)]
 pub struct SubscriptionUpdateJson {
     pub table_updates: Vec<TableUpdateJson>,
+    /// The offset of the last transaction whose changes it contains, in a `SubscriptionUpdate` sent alone.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tx_offset: Option<u64>,
+    /// Identifies the numbering of the `tx_offset`s, in a `SubscriptionUpdate` sent alone.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history_id: Option<u64>,
+    /// Whether it contains only the changes since the `resume_from` of the `subscribe` message.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub resumed: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryUpdateJson {
+    pub query_id: u32,
+    pub subscription_update: SubscriptionUpdateJson,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -92,6 +108,12 @@ pub struct EventJson {
 pub struct TransactionUpdateJson {
     pub event: EventJson,
     pub subscription_update: SubscriptionUpdateJson,
+    /// The `request_id` of the caller's `call` message, in the message sent to the caller.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<u32>,
+    /// The offset of the transaction of a committed reducer run.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tx_offset: Option<u64>,
 }
 
 #[serde_as]
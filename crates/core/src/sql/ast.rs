@@ -5,8 +5,9 @@ use spacetimedb_lib::ColumnIndexAttribute;
 use spacetimedb_sats::{AlgebraicType, AlgebraicValue, ProductTypeElement};
 use sqlparser::ast::{
     Assignment, BinaryOperator, ColumnDef as SqlColumnDef, ColumnOption, DataType, ExactNumberInfo, Expr as SqlExpr,
-    GeneratedAs, HiveDistributionStyle, Ident, JoinConstraint, JoinOperator, ObjectName, ObjectType, Query, Select,
-    SelectItem, SetExpr, Statement, TableFactor, TableWithJoins, Value, Values,
+    Function, FunctionArg, FunctionArgExpr, GeneratedAs, HiveDistributionStyle, Ident, JoinConstraint, JoinOperator,
+    ObjectName, ObjectType, Offset, OrderByExpr, Query, Select, SelectItem, SetExpr, Statement, TableFactor,
+    TableWithJoins, Value, Values,
 };
 use sqlparser::dialect::PostgreSqlDialect;
 use sqlparser::parser::Parser;
@@ -19,7 +20,7 @@ use crate::db::relational_db::RelationalDB;
 use crate::error::{DBError, PlanError};
 use spacetimedb_lib::relation::{extract_table_field, FieldExpr, FieldName};
 use spacetimedb_vm::errors::ErrorVm;
-use spacetimedb_vm::expr::{ColumnOp, DbType, Expr};
+use spacetimedb_vm::expr::{AggExpr, AggFn, ColumnOp, DbType, Expr, LimitExpr, SortKey};
 use spacetimedb_vm::operator::{OpCmp, OpLogic, OpQuery};
 use spacetimedb_vm::ops::parse::parse;
 
@@ -78,6 +79,9 @@ macro_rules! unsupported{
     }
 }
 
+/// The values bound to the placeholders of a `SQL` statement, keyed by the placeholder as written, like `:sender`.
+pub type SqlParams = HashMap<String, AlgebraicValue>;
+
 /// A convenient wrapper for a table name (that comes from an `ObjectName`).
 pub struct Table {
     pub(crate) name: String,
@@ -97,6 +101,8 @@ pub enum Column {
     QualifiedWildcard { table: String },
     /// An unqualified `SELECT *`
     Wildcard,
+    /// An aggregate function, like `COUNT(*)`
+    Aggregate(AggExpr),
 }
 
 /// The list of expressions for `SELECT expr1, expr2...` determining what data to extract.
@@ -129,6 +135,15 @@ pub struct FromField {
     pub column: ColumnDef,
 }
 
+/// The `GROUP BY keys [HAVING having]` clauses, with all the aggregates used by the query.
+///
+/// A query that uses aggregates without `GROUP BY` aggregates all the rows in a single group.
+pub struct GroupBy {
+    pub keys: Vec<FieldName>,
+    pub aggs: Vec<AggExpr>,
+    pub having: Option<Selection>,
+}
+
 /// The list of tables in `... FROM table1 [JOIN table2] ...`
 pub struct From {
     pub root: TableSchema,
@@ -221,6 +236,9 @@ pub enum SqlAst {
         from: From,
         project: Vec<Column>,
         selection: Option<Selection>,
+        group_by: Option<GroupBy>,
+        order_by: Vec<SortKey>,
+        limit: LimitExpr,
     },
     Insert {
         table: TableSchema,
@@ -249,6 +267,17 @@ pub enum SqlAst {
     },
 }
 
+impl SqlAst {
+    /// Returns the tables read by the statement, which are the ones its plan depends on.
+    pub fn tables(&self) -> Vec<&TableSchema> {
+        match self {
+            SqlAst::Select { from, .. } => from.iter_tables().collect(),
+            SqlAst::Update { table, .. } | SqlAst::Delete { table, .. } => vec![table],
+            SqlAst::Insert { .. } | SqlAst::CreateTable { .. } | SqlAst::Drop { .. } => vec![],
+        }
+    }
+}
+
 fn extract_field(table: &From, of: &SqlExpr) -> Result<Option<ProductTypeElement>, PlanError> {
     match of {
         SqlExpr::Identifier(x) => {
@@ -289,7 +318,12 @@ fn infer_number(field: Option<&ProductTypeElement>, value: &str, is_long: bool)
 }
 
 /// Compiles a [SqlExpr] expression into a [ColumnOp]
-fn compile_expr_value(table: &From, field: Option<&ProductTypeElement>, of: SqlExpr) -> Result<ColumnOp, PlanError> {
+fn compile_expr_value(
+    table: &From,
+    params: &SqlParams,
+    field: Option<&ProductTypeElement>,
+    of: SqlExpr,
+) -> Result<ColumnOp, PlanError> {
     Ok(ColumnOp::Field(match of {
         SqlExpr::Identifier(name) => FieldExpr::Name(table.resolve_field(&name.value)?.field),
         SqlExpr::CompoundIdentifier(ident) => {
@@ -302,6 +336,7 @@ fn compile_expr_value(table: &From, field: Option<&ProductTypeElement>, of: SqlE
             Value::DoubleQuotedString(s) => AlgebraicValue::String(s),
             Value::Boolean(x) => AlgebraicValue::Bool(x),
             Value::Null => AlgebraicValue::OptionNone(),
+            Value::Placeholder(name) => params.get(&name).cloned().ok_or(PlanError::UnboundParameter { name })?,
             x => {
                 return Err(PlanError::Unsupported {
                     feature: format!("Unsupported value: {x}."),
@@ -309,12 +344,12 @@ fn compile_expr_value(table: &From, field: Option<&ProductTypeElement>, of: SqlE
             }
         }),
         SqlExpr::BinaryOp { left, op, right } => {
-            let (op, lhs, rhs) = compile_bin_op(table, op, left, right)?;
+            let (op, lhs, rhs) = compile_bin_op(table, params, op, left, right)?;
 
             return Ok(ColumnOp::new(op, lhs, rhs));
         }
         SqlExpr::Nested(x) => {
-            return compile_expr_value(table, field, *x);
+            return compile_expr_value(table, params, field, *x);
         }
         x => {
             return Err(PlanError::Unsupported {
@@ -324,8 +359,13 @@ fn compile_expr_value(table: &From, field: Option<&ProductTypeElement>, of: SqlE
     }))
 }
 
-fn compile_expr_field(table: &From, field: Option<&ProductTypeElement>, of: SqlExpr) -> Result<FieldExpr, PlanError> {
-    match compile_expr_value(table, field, of)? {
+fn compile_expr_field(
+    table: &From,
+    params: &SqlParams,
+    field: Option<&ProductTypeElement>,
+    of: SqlExpr,
+) -> Result<FieldExpr, PlanError> {
+    match compile_expr_value(table, params, field, of)? {
         ColumnOp::Field(field) => Ok(field),
         x => Err(PlanError::Unsupported {
             feature: format!("Complex expression {x} on insert..."),
@@ -352,14 +392,9 @@ fn compile_table_factor(table: TableFactor) -> Result<Table, PlanError> {
     }
 }
 
-/// Compiles a binary operation like `field > 1`
-fn compile_bin_op(
-    table: &From,
-    op: BinaryOperator,
-    lhs: Box<sqlparser::ast::Expr>,
-    rhs: Box<sqlparser::ast::Expr>,
-) -> Result<(OpQuery, ColumnOp, ColumnOp), PlanError> {
-    let op: OpQuery = match op {
+/// Compiles a binary operator like `>`
+fn compile_bin_operator(op: BinaryOperator) -> Result<OpQuery, PlanError> {
+    Ok(match op {
         BinaryOperator::Gt => OpCmp::Gt.into(),
         BinaryOperator::Lt => OpCmp::Lt.into(),
         BinaryOperator::GtEq => OpCmp::GtEq.into(),
@@ -373,26 +408,37 @@ fn compile_bin_op(
                 feature: format!("BinaryOperator not supported in WHERE: {x}."),
             })
         }
-    };
+    })
+}
+
+/// Compiles a binary operation like `field > 1`
+fn compile_bin_op(
+    table: &From,
+    params: &SqlParams,
+    op: BinaryOperator,
+    lhs: Box<sqlparser::ast::Expr>,
+    rhs: Box<sqlparser::ast::Expr>,
+) -> Result<(OpQuery, ColumnOp, ColumnOp), PlanError> {
+    let op = compile_bin_operator(op)?;
 
     let field_lhs = extract_field(table, &lhs)?;
     let field_rhs = extract_field(table, &rhs)?;
     // This inversion is for inferring the type of the right side, like in `inventory.id = 1`,
     // so `1` get the type of `inventory.id`
-    let lhs = compile_expr_value(table, field_rhs.as_ref(), *lhs)?;
-    let rhs = compile_expr_value(table, field_lhs.as_ref(), *rhs)?;
+    let lhs = compile_expr_value(table, params, field_rhs.as_ref(), *lhs)?;
+    let rhs = compile_expr_value(table, params, field_lhs.as_ref(), *rhs)?;
 
     Ok((op, lhs, rhs))
 }
 
-fn _compile_where(table: &From, filter: SqlExpr) -> Result<Option<Selection>, PlanError> {
+fn _compile_where(table: &From, params: &SqlParams, filter: SqlExpr) -> Result<Option<Selection>, PlanError> {
     match filter {
         SqlExpr::BinaryOp { left, op, right } => {
-            let (op, lhs, rhs) = compile_bin_op(table, op, left, right)?;
+            let (op, lhs, rhs) = compile_bin_op(table, params, op, left, right)?;
 
             Ok(Some(Selection::with_cmp(op, lhs, rhs)))
         }
-        SqlExpr::Nested(x) => _compile_where(table, *x),
+        SqlExpr::Nested(x) => _compile_where(table, params, *x),
         x => Err(PlanError::Unsupported {
             feature: format!("Unsupported in WHERE: {x}."),
         }),
@@ -400,9 +446,9 @@ fn _compile_where(table: &From, filter: SqlExpr) -> Result<Option<Selection>, Pl
 }
 
 /// Compiles the `WHERE` clause
-fn compile_where(table: &From, filter: Option<SqlExpr>) -> Result<Option<Selection>, PlanError> {
+fn compile_where(table: &From, params: &SqlParams, filter: Option<SqlExpr>) -> Result<Option<Selection>, PlanError> {
     if let Some(filter) = filter {
-        _compile_where(table, filter)
+        _compile_where(table, params, filter)
     } else {
         Ok(None)
     }
@@ -423,7 +469,12 @@ fn find_table<'tx>(db: &RelationalDB, tx: &'tx MutTxId, t: Table) -> Result<Cow<
 }
 
 /// Compiles the `FROM` clause
-fn compile_from(db: &RelationalDB, tx: &MutTxId, from: &[TableWithJoins]) -> Result<From, PlanError> {
+fn compile_from(
+    db: &RelationalDB,
+    tx: &MutTxId,
+    params: &SqlParams,
+    from: &[TableWithJoins],
+) -> Result<From, PlanError> {
     if from.len() > 1 {
         return Err(PlanError::Unsupported {
             feature: "Multiple tables in `FROM`.".into(),
@@ -449,7 +500,7 @@ fn compile_from(db: &RelationalDB, tx: &MutTxId, from: &[TableWithJoins]) -> Res
 
                 match constraint {
                     JoinConstraint::On(x) => {
-                        let expr = compile_expr_value(&base, None, x.clone())?;
+                        let expr = compile_expr_value(&base, params, None, x.clone())?;
                         match expr {
                             ColumnOp::Field(_) => {}
                             ColumnOp::Cmp { op, lhs, rhs } => {
@@ -500,7 +551,7 @@ fn compound_ident(ident: &[Ident]) -> String {
     ident.iter().map(ToString::to_string).collect::<Vec<_>>().join(".")
 }
 
-fn compile_select_item(from: &From, select_item: SelectItem) -> Result<Column, PlanError> {
+fn compile_select_item(from: &From, params: &SqlParams, select_item: SelectItem) -> Result<Column, PlanError> {
     match select_item {
         SelectItem::UnnamedExpr(expr) => match expr {
             sqlparser::ast::Expr::Identifier(ident) => {
@@ -514,7 +565,7 @@ fn compile_select_item(from: &From, select_item: SelectItem) -> Result<Column, P
                 Ok(Column::UnnamedExpr(Expr::Ident(col_name)))
             }
             sqlparser::ast::Expr::Value(_) => {
-                let value = compile_expr_value(from, None, expr)?;
+                let value = compile_expr_value(from, params, None, expr)?;
                 match value {
                     ColumnOp::Field(value) => match value {
                         FieldExpr::Name(_) => Err(PlanError::Unsupported {
@@ -527,9 +578,10 @@ fn compile_select_item(from: &From, select_item: SelectItem) -> Result<Column, P
                     }),
                 }
             }
-            sqlparser::ast::Expr::Nested(x) => compile_select_item(from, SelectItem::UnnamedExpr(*x)),
+            sqlparser::ast::Expr::Nested(x) => compile_select_item(from, params, SelectItem::UnnamedExpr(*x)),
+            sqlparser::ast::Expr::Function(f) => Ok(Column::Aggregate(compile_aggregate(from, f)?)),
             _ => Err(PlanError::Unsupported {
-                feature: "Only columns names & scalars are supported.".into(),
+                feature: "Only columns names, scalars & aggregates are supported.".into(),
             }),
         },
         SelectItem::ExprWithAlias { expr: _, alias: _ } => Err(PlanError::Unsupported {
@@ -542,36 +594,229 @@ fn compile_select_item(from: &From, select_item: SelectItem) -> Result<Column, P
     }
 }
 
+/// Compiles a column name in the `clause`
+fn compile_column_name(from: &From, clause: &str, expr: SqlExpr) -> Result<FieldName, PlanError> {
+    match expr {
+        SqlExpr::Identifier(ident) => Ok(from.resolve_field(&ident.value)?.field),
+        SqlExpr::CompoundIdentifier(ident) => Ok(from.resolve_field(&compound_ident(&ident))?.field),
+        SqlExpr::Nested(x) => compile_column_name(from, clause, *x),
+        x => Err(PlanError::Unsupported {
+            feature: format!("{clause} expression `{x}`, only columns names are supported."),
+        }),
+    }
+}
+
+/// Compiles an aggregate function call like `COUNT(*)` or `SUM(field)`
+fn compile_aggregate(from: &From, f: Function) -> Result<AggExpr, PlanError> {
+    unsupported!("Aggregate", f.over, f.distinct, f.order_by);
+
+    let func = AggFn::parse(&f.name.to_string()).ok_or_else(|| PlanError::Unsupported {
+        feature: format!(
+            "Function `{}`, only the aggregates COUNT, SUM, MIN, MAX & AVG are supported.",
+            f.name
+        ),
+    })?;
+    let field = match <[FunctionArg; 1]>::try_from(f.args) {
+        Ok([FunctionArg::Unnamed(FunctionArgExpr::Wildcard)]) if func == AggFn::Count => None,
+        Ok([FunctionArg::Unnamed(FunctionArgExpr::Expr(expr))]) => Some(compile_column_name(from, "Aggregate", expr)?),
+        _ => {
+            return Err(PlanError::Unsupported {
+                feature: format!("Arguments of `{}`, expected a single column name.", f.name),
+            })
+        }
+    };
+    let agg = AggExpr::new(func, field);
+    // Check the aggregate supports the type of the field
+    aggregate_type(from, &agg, false)?;
+
+    Ok(agg)
+}
+
+/// Infers the type of the result of the `agg`, see [AggExpr::result_type]
+fn aggregate_type(from: &From, agg: &AggExpr, nullable: bool) -> Result<AlgebraicType, PlanError> {
+    let field = match &agg.field {
+        Some(field) => Some(from.resolve_field(&field.to_string())?),
+        None => None,
+    };
+    Ok(agg
+        .result_type(field.as_ref().map(|x| &x.column.column.algebraic_type), nullable)
+        .map_err(ErrorVm::from)?)
+}
+
+/// Returns the [FieldName] of the result of the `agg`, adding it to the `aggs` of the query
+fn aggregate_field(from: &From, aggs: &mut Vec<AggExpr>, agg: AggExpr) -> FieldName {
+    let field = agg.field_name(&from.root.table_name);
+    if !aggs.contains(&agg) {
+        aggs.push(agg);
+    }
+    field
+}
+
+/// Compiles the `ORDER BY` clause
+fn compile_order_by(
+    from: &From,
+    aggs: &mut Vec<AggExpr>,
+    order_by: Vec<OrderByExpr>,
+) -> Result<Vec<SortKey>, PlanError> {
+    let mut keys = Vec::with_capacity(order_by.len());
+    for x in order_by {
+        unsupported!("ORDER BY", x.nulls_first);
+
+        let field = match x.expr {
+            SqlExpr::Function(f) => {
+                let agg = compile_aggregate(from, f)?;
+                aggregate_field(from, aggs, agg)
+            }
+            expr => compile_column_name(from, "ORDER BY", expr)?,
+        };
+        keys.push(SortKey::new(field, x.asc.unwrap_or(true)));
+    }
+    Ok(keys)
+}
+
+/// Infers the type of an expression of the `HAVING` clause, when it is a field or an aggregate
+fn having_type(from: &From, nullable: bool, of: &SqlExpr) -> Result<Option<AlgebraicType>, PlanError> {
+    Ok(match of {
+        SqlExpr::Function(f) => Some(aggregate_type(from, &compile_aggregate(from, f.clone())?, nullable)?),
+        SqlExpr::Nested(x) => having_type(from, nullable, x)?,
+        x => extract_field(from, x)?.map(|x| x.algebraic_type),
+    })
+}
+
+/// Compiles an expression of the `HAVING` clause, that can use the aggregates over the rows of the groups
+///
+/// The aggregates are `NULL` over an empty input if `nullable`, so the values they are compared to are optional.
+fn compile_having_value(
+    from: &From,
+    params: &SqlParams,
+    aggs: &mut Vec<AggExpr>,
+    nullable: bool,
+    ty: Option<&AlgebraicType>,
+    of: SqlExpr,
+) -> Result<ColumnOp, PlanError> {
+    match of {
+        SqlExpr::BinaryOp { left, op, right } => {
+            let op = compile_bin_operator(op)?;
+            // Like in `compile_bin_op`, each side gets the type of the other
+            let ty_lhs = having_type(from, nullable, &left)?;
+            let ty_rhs = having_type(from, nullable, &right)?;
+            let lhs = compile_having_value(from, params, aggs, nullable, ty_rhs.as_ref(), *left)?;
+            let rhs = compile_having_value(from, params, aggs, nullable, ty_lhs.as_ref(), *right)?;
+
+            Ok(ColumnOp::new(op, lhs, rhs))
+        }
+        SqlExpr::Nested(x) => compile_having_value(from, params, aggs, nullable, ty, *x),
+        SqlExpr::Function(f) => {
+            let agg = compile_aggregate(from, f)?;
+            Ok(ColumnOp::Field(FieldExpr::Name(aggregate_field(from, aggs, agg))))
+        }
+        SqlExpr::Value(Value::Number(value, is_long)) => {
+            let value = match ty.and_then(|ty| ty.as_sum()?.as_option()) {
+                Some(ty) => AlgebraicValue::OptionSome(parse(&value, ty)?),
+                None => infer_number(ty.cloned().map(ProductTypeElement::from).as_ref(), &value, is_long)?,
+            };
+            Ok(ColumnOp::Field(FieldExpr::Value(value)))
+        }
+        x => compile_expr_value(from, params, None, x),
+    }
+}
+
+/// Compiles the `GROUP BY` & `HAVING` clauses
+fn compile_group_by(
+    from: &From,
+    params: &SqlParams,
+    aggs: &mut Vec<AggExpr>,
+    group_by: Vec<SqlExpr>,
+    having: Option<SqlExpr>,
+) -> Result<(Vec<FieldName>, Option<Selection>), PlanError> {
+    let keys = group_by
+        .into_iter()
+        .map(|x| compile_column_name(from, "GROUP BY", x))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let having = match having {
+        Some(having) => {
+            let clause = compile_having_value(from, params, aggs, keys.is_empty(), None, having)?;
+            Some(Selection { clause })
+        }
+        None => None,
+    };
+
+    Ok((keys, having))
+}
+
+/// Compiles the number of rows of a `LIMIT` or `OFFSET` clause
+fn compile_row_count(clause: &str, expr: SqlExpr) -> Result<u64, PlanError> {
+    match expr {
+        SqlExpr::Value(Value::Number(value, _)) => value.parse().map_err(|_| PlanError::Unsupported {
+            feature: format!("{clause} with `{value}`, expected a non-negative integer."),
+        }),
+        SqlExpr::Nested(x) => compile_row_count(clause, *x),
+        x => Err(PlanError::Unsupported {
+            feature: format!("{clause} with `{x}`, expected a non-negative integer."),
+        }),
+    }
+}
+
+/// Compiles the `LIMIT` & `OFFSET` clauses
+fn compile_limit(limit: Option<SqlExpr>, offset: Option<Offset>) -> Result<LimitExpr, PlanError> {
+    Ok(LimitExpr {
+        offset: offset
+            .map(|x| compile_row_count("OFFSET", x.value))
+            .transpose()?
+            .unwrap_or_default(),
+        limit: limit.map(|x| compile_row_count("LIMIT", x)).transpose()?,
+    })
+}
+
 /// Compiles the `SELECT ...` clause
-fn compile_select(db: &RelationalDB, tx: &MutTxId, select: Select) -> Result<SqlAst, PlanError> {
-    let from = compile_from(db, tx, &select.from)?;
+fn compile_select(
+    db: &RelationalDB,
+    tx: &MutTxId,
+    params: &SqlParams,
+    select: Select,
+    order_by: Vec<OrderByExpr>,
+    limit: LimitExpr,
+) -> Result<SqlAst, PlanError> {
+    let from = compile_from(db, tx, params, &select.from)?;
     // SELECT ...
     let mut project = Vec::new();
     for select_item in select.projection {
-        let col = compile_select_item(&from, select_item)?;
+        let col = compile_select_item(&from, params, select_item)?;
         project.push(col);
     }
 
-    let selection = compile_where(&from, select.selection)?;
+    let selection = compile_where(&from, params, select.selection)?;
+
+    let mut aggs = project
+        .iter()
+        .filter_map(|x| match x {
+            Column::Aggregate(agg) => Some(agg.clone()),
+            _ => None,
+        })
+        .collect();
+    let (keys, having) = compile_group_by(&from, params, &mut aggs, select.group_by, select.having)?;
+    let order_by = compile_order_by(&from, &mut aggs, order_by)?;
+
+    let group_by = if !keys.is_empty() || !aggs.is_empty() || having.is_some() {
+        Some(GroupBy { keys, aggs, having })
+    } else {
+        None
+    };
 
     Ok(SqlAst::Select {
         from,
         project,
         selection,
+        group_by,
+        order_by,
+        limit,
     })
 }
 
 /// Compiles any `query` clause (currently only `SELECT...`)
-fn compile_query(db: &RelationalDB, tx: &MutTxId, query: Query) -> Result<SqlAst, PlanError> {
-    unsupported!(
-        "SELECT",
-        query.order_by,
-        query.fetch,
-        query.limit,
-        query.offset,
-        query.locks,
-        query.with
-    );
+fn compile_query(db: &RelationalDB, tx: &MutTxId, params: &SqlParams, query: Query) -> Result<SqlAst, PlanError> {
+    unsupported!("SELECT", query.fetch, query.locks, query.with);
 
     match *query.body {
         SetExpr::Select(select) => {
@@ -581,12 +826,11 @@ fn compile_query(db: &RelationalDB, tx: &MutTxId, query: Query) -> Result<SqlAst
                 select.top,
                 select.into,
                 select.lateral_views,
-                select.group_by,
-                select.having,
                 select.sort_by
             );
 
-            compile_select(db, tx, *select)
+            let limit = compile_limit(query.limit, query.offset)?;
+            compile_select(db, tx, params, *select, query.order_by, limit)
         }
         SetExpr::Query(_) => Err(PlanError::Unsupported {
             feature: "Query".into(),
@@ -618,6 +862,7 @@ fn compile_query(db: &RelationalDB, tx: &MutTxId, query: Query) -> Result<SqlAst
 fn compile_insert(
     db: &RelationalDB,
     tx: &MutTxId,
+    params: &SqlParams,
     table_name: ObjectName,
     columns: Vec<Ident>,
     data: &Values,
@@ -637,7 +882,7 @@ fn compile_insert(
         let mut row = Vec::with_capacity(x.len());
         for (pos, v) in x.iter().enumerate() {
             let field = table.root.get_column(pos).map(ProductTypeElement::from);
-            row.push(compile_expr_field(&table, field.as_ref(), v.clone())?);
+            row.push(compile_expr_field(&table, params, field.as_ref(), v.clone())?);
         }
 
         values.push(row);
@@ -653,12 +898,13 @@ fn compile_insert(
 fn compile_update(
     db: &RelationalDB,
     tx: &MutTxId,
+    params: &SqlParams,
     table: Table,
     assignments: Vec<Assignment>,
     selection: Option<SqlExpr>,
 ) -> Result<SqlAst, PlanError> {
     let table = From::new(find_table(db, tx, table)?.into_owned());
-    let selection = compile_where(&table, selection)?;
+    let selection = compile_where(&table, params, selection)?;
 
     let mut x = HashMap::with_capacity(assignments.len());
 
@@ -666,7 +912,7 @@ fn compile_update(
         let name: String = col.id.iter().map(|x| x.to_string()).collect();
 
         let field = table.root.get_column_by_name(&name).map(ProductTypeElement::from);
-        let value = compile_expr_field(&table, field.as_ref(), col.value)?;
+        let value = compile_expr_field(&table, params, field.as_ref(), col.value)?;
         x.insert(FieldName::named(&table.root.table_name, &name), value);
     }
 
@@ -681,11 +927,12 @@ fn compile_update(
 fn compile_delete(
     db: &RelationalDB,
     tx: &MutTxId,
+    params: &SqlParams,
     table: Table,
     selection: Option<SqlExpr>,
 ) -> Result<SqlAst, PlanError> {
     let table = From::new(find_table(db, tx, table)?.into_owned());
-    let selection = compile_where(&table, selection)?;
+    let selection = compile_where(&table, params, selection)?;
 
     Ok(SqlAst::Delete {
         table: table.root,
@@ -849,9 +1096,14 @@ fn compile_drop(name: &ObjectName, kind: ObjectType) -> Result<SqlAst, PlanError
 }
 
 /// Compiles a `SQL` clause
-fn compile_statement(db: &RelationalDB, tx: &MutTxId, statement: Statement) -> Result<SqlAst, PlanError> {
+fn compile_statement(
+    db: &RelationalDB,
+    tx: &MutTxId,
+    params: &SqlParams,
+    statement: Statement,
+) -> Result<SqlAst, PlanError> {
     match statement {
-        Statement::Query(query) => Ok(compile_query(db, tx, *query)?),
+        Statement::Query(query) => Ok(compile_query(db, tx, params, *query)?),
         Statement::Insert {
             or,
             into,
@@ -885,7 +1137,7 @@ fn compile_statement(db: &RelationalDB, tx: &MutTxId, statement: Statement) -> R
                     }
                 };
 
-                return compile_insert(db, tx, table_name, columns, values);
+                return compile_insert(db, tx, params, table_name, columns, values);
             };
 
             Err(PlanError::Unsupported {
@@ -902,7 +1154,7 @@ fn compile_statement(db: &RelationalDB, tx: &MutTxId, statement: Statement) -> R
             unsupported!("UPDATE", from, returning);
 
             let table_name = compile_table_factor(table.relation)?;
-            compile_update(db, tx, table_name, assignments, selection)
+            compile_update(db, tx, params, table_name, assignments, selection)
         }
         Statement::Delete {
             tables,
@@ -918,7 +1170,7 @@ fn compile_statement(db: &RelationalDB, tx: &MutTxId, statement: Statement) -> R
 
             let table = from.first().unwrap().clone();
             let table_name = compile_table_factor(table.relation)?;
-            compile_delete(db, tx, table_name, selection)
+            compile_delete(db, tx, params, table_name, selection)
         }
         Statement::CreateTable {
             transient,
@@ -1016,8 +1268,14 @@ fn compile_statement(db: &RelationalDB, tx: &MutTxId, statement: Statement) -> R
     }
 }
 
-/// Compiles a `sql` string into a `Vec<SqlAst>` using a SQL parser with [PostgreSqlDialect]
-pub(crate) fn compile_to_ast(db: &RelationalDB, tx: &MutTxId, sql_text: &str) -> Result<Vec<SqlAst>, DBError> {
+/// Compiles a `sql` string into a `Vec<SqlAst>` using a SQL parser with [PostgreSqlDialect],
+/// binding its placeholders, like `:sender`, to the values in `params`
+pub(crate) fn compile_to_ast(
+    db: &RelationalDB,
+    tx: &MutTxId,
+    sql_text: &str,
+    params: &SqlParams,
+) -> Result<Vec<SqlAst>, DBError> {
     let dialect = PostgreSqlDialect {};
     let ast = Parser::parse_sql(&dialect, sql_text).map_err(|error| DBError::SqlParser {
         sql: sql_text.to_string(),
@@ -1026,7 +1284,7 @@ pub(crate) fn compile_to_ast(db: &RelationalDB, tx: &MutTxId, sql_text: &str) ->
 
     let mut results = Vec::new();
     for statement in ast {
-        let plan_result = compile_statement(db, tx, statement);
+        let plan_result = compile_statement(db, tx, params, statement);
         let query = match plan_result {
             Ok(plan) => plan,
             Err(error) => {
@@ -6,26 +6,43 @@ use crate::db::datastore::locking_tx_datastore::MutTxId;
 use crate::db::datastore::traits::{IndexSchema, TableSchema};
 use crate::db::relational_db::RelationalDB;
 use crate::error::{DBError, PlanError};
-use crate::sql::ast::{compile_to_ast, Column, From, Join, Selection, SqlAst};
+use crate::sql::ast::{compile_to_ast, Column, From, GroupBy, Join, Selection, SqlAst, SqlParams};
+use crate::sql::optimizer::Statistics;
 use spacetimedb_lib::auth::{StAccess, StTableType};
 use spacetimedb_lib::operator::OpQuery;
 use spacetimedb_lib::relation::{self, DbTable, FieldExpr, FieldName, Header};
 use spacetimedb_lib::table::ProductTypeMeta;
+use spacetimedb_lib::IndexType;
 use spacetimedb_sats::{AlgebraicValue, ProductType};
 use spacetimedb_vm::dsl::{db_table, db_table_raw, query};
-use spacetimedb_vm::expr::{ColumnOp, CrudExpr, DbType, Expr, IndexJoin, JoinExpr, Query, QueryExpr, SourceExpr};
+use spacetimedb_vm::expr::{
+    ColumnOp, CrudExpr, DbType, Expr, IndexJoin, IndexScan, JoinExpr, LimitExpr, Query, QueryExpr, SortKey, SourceExpr,
+};
 use spacetimedb_vm::operator::OpCmp;
+use std::ops::Bound;
 
 /// Compile the `SQL` expression into a `ast`
 #[tracing::instrument(skip_all)]
 pub fn compile_sql(db: &RelationalDB, tx: &MutTxId, sql_text: &str) -> Result<Vec<CrudExpr>, DBError> {
+    compile_sql_with_params(db, tx, sql_text, &SqlParams::new())
+}
+
+/// Compile the `SQL` expression into a `ast`, binding its placeholders to the values in `params`
+#[tracing::instrument(skip_all)]
+pub fn compile_sql_with_params(
+    db: &RelationalDB,
+    tx: &MutTxId,
+    sql_text: &str,
+    params: &SqlParams,
+) -> Result<Vec<CrudExpr>, DBError> {
     info!(sql = sql_text);
-    let ast = compile_to_ast(db, tx, sql_text)?;
+    let ast = compile_to_ast(db, tx, sql_text, params)?;
 
     let mut results = Vec::with_capacity(ast.len());
 
     for sql in ast {
-        results.push(compile_statement(sql).map_err(|error| DBError::Plan {
+        let stats = Statistics::collect(db, tx, sql.tables())?;
+        results.push(compile_statement(sql, &stats).map_err(|error| DBError::Plan {
             sql: sql_text.to_string(),
             error,
         })?);
@@ -74,46 +91,78 @@ fn check_cmp_expr(table: &From, expr: &ColumnOp) -> Result<(), PlanError> {
 }
 
 /// Compiles a `WHERE ...` clause
-fn compile_where(mut q: QueryExpr, table: &From, filter: Selection) -> Result<QueryExpr, PlanError> {
+///
+/// For each table, the sargable conditions on the index that is cheapest to seek are planned as an [IndexScan],
+/// unless a table scan is estimated to be cheaper.
+/// The rest of the conditions are planned as filters, from the most to the least selective,
+/// so the ones that discard the most rows are evaluated first.
+fn compile_where(
+    mut q: QueryExpr,
+    table: &From,
+    filter: Selection,
+    stats: &Statistics,
+) -> Result<QueryExpr, PlanError> {
     check_cmp_expr(table, &filter.clause)?;
-    'outer: for ref op in filter.clause.to_vec() {
-        // Go through each table schema referenced in the query.
-        // Find the first sargable condition and short-circuit.
-        for schema in core::iter::once(&table.root).chain(
-            table
-                .join
-                .iter()
-                .flat_map(|v| v.iter().map(|Join::Inner { rhs, .. }| rhs)),
-        ) {
-            match is_sargable(schema, op) {
-                // found sargable equality condition for one of the table schemas
-                Some(IndexArgument::Eq { col_id, value }) => {
-                    q = q.with_index_eq(schema.into(), col_id, value);
-                    continue 'outer;
-                }
-                // found sargable range condition for one of the table schemas
-                Some(IndexArgument::LowerBound {
+
+    let mut seeks = Vec::new();
+    let mut filters = Vec::new();
+    for op in filter.clause.to_vec() {
+        let selectivity = stats.selectivity(table, &op);
+        match table
+            .iter_tables()
+            .find_map(|schema| is_sargable(schema, &op).map(|arg| (schema, arg)))
+        {
+            Some((schema, arg)) => seeks.push((schema, arg, selectivity, op)),
+            None => filters.push((selectivity, op)),
+        }
+    }
+
+    for schema in table.iter_tables() {
+        // The conditions on the same column are answered by a single seek of its index.
+        let mut by_column: Vec<(u32, f64)> = Vec::new();
+        for (_, arg, selectivity, _) in seeks.iter().filter(|(s, ..)| s.table_id == schema.table_id) {
+            match by_column.iter_mut().find(|(col_id, _)| *col_id == arg.col_id()) {
+                Some((_, combined)) => *combined *= selectivity,
+                None => by_column.push((arg.col_id(), *selectivity)),
+            }
+        }
+        let best_col = by_column
+            .into_iter()
+            .map(|(col_id, selectivity)| (col_id, stats.index_scan_cost(schema.table_id, selectivity)))
+            .filter(|(_, cost)| *cost <= stats.scan_cost(schema.table_id))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(col_id, _)| col_id);
+
+        let (index_seeks, others): (Vec<_>, Vec<_>) = std::mem::take(&mut seeks)
+            .into_iter()
+            .partition(|(s, arg, ..)| s.table_id == schema.table_id && Some(arg.col_id()) == best_col);
+        seeks = others;
+        // An equality is seeked first, as it can't be merged with a range.
+        let (eqs, ranges): (Vec<_>, Vec<_>) = index_seeks
+            .into_iter()
+            .partition(|(_, arg, ..)| matches!(arg, IndexArgument::Eq { .. }));
+        for (schema, arg, ..) in eqs.into_iter().chain(ranges) {
+            q = match arg {
+                IndexArgument::Eq { col_id, value } => q.with_index_eq(schema.into(), col_id, value),
+                IndexArgument::LowerBound {
                     col_id,
                     value,
                     inclusive,
-                }) => {
-                    q = q.with_index_lower_bound(schema.into(), col_id, value, inclusive);
-                    continue 'outer;
-                }
-                // found sargable range condition for one of the table schemas
-                Some(IndexArgument::UpperBound {
+                } => q.with_index_lower_bound(schema.into(), col_id, value, inclusive),
+                IndexArgument::UpperBound {
                     col_id,
                     value,
                     inclusive,
-                }) => {
-                    q = q.with_index_upper_bound(schema.into(), col_id, value, inclusive);
-                    continue 'outer;
-                }
-                None => {}
-            }
+                } => q.with_index_upper_bound(schema.into(), col_id, value, inclusive),
+            };
         }
-        // filter condition cannot be answered using an index
-        q = q.with_select(op.clone());
+    }
+
+    // The sargable conditions that don't use an index are filters too.
+    filters.extend(seeks.into_iter().map(|(_, _, selectivity, op)| (selectivity, op)));
+    filters.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+    for (_, op) in filters {
+        q = q.with_select(op);
     }
     Ok(q)
 }
@@ -137,6 +186,14 @@ pub enum IndexArgument {
     },
 }
 
+impl IndexArgument {
+    fn col_id(&self) -> u32 {
+        match self {
+            Self::Eq { col_id, .. } | Self::LowerBound { col_id, .. } | Self::UpperBound { col_id, .. } => *col_id,
+        }
+    }
+}
+
 // Sargable stands for Search ARGument ABLE.
 // A sargable predicate is one that can be answered using an index.
 fn is_sargable(table: &TableSchema, op: &ColumnOp) -> Option<IndexArgument> {
@@ -154,7 +211,10 @@ fn is_sargable(table: &TableSchema, op: &ColumnOp) -> Option<IndexArgument> {
         let ColumnOp::Field(FieldExpr::Value(ref value)) = **rhs else {
             return None;
         };
-        // lhs field must exist
+        // lhs field must exist in this table
+        if name.table() != table.table_name {
+            return None;
+        }
         let column = table.get_column_by_field(name)?;
         // lhs field must have an index
         let index = table
@@ -164,6 +224,11 @@ fn is_sargable(table: &TableSchema, op: &ColumnOp) -> Option<IndexArgument> {
 
         assert_eq!(index.cols.len(), 1, "No yet supported multi-column indexes");
 
+        // a hash index only answers equality
+        if index.index_type == IndexType::Hash && *op != OpCmp::Eq {
+            return None;
+        }
+
         match op {
             OpCmp::Eq => Some(IndexArgument::Eq {
                 col_id: index.cols.head,
@@ -200,11 +265,130 @@ fn is_sargable(table: &TableSchema, op: &ColumnOp) -> Option<IndexArgument> {
     }
 }
 
+/// Compiles an `ORDER BY ...` clause
+///
+/// If the rows can be streamed in index order, a full [IndexScan] is planned instead of a sort.
+fn compile_order_by(mut q: QueryExpr, table: &From, keys: &[SortKey]) -> QueryExpr {
+    match index_order(&q, table, keys) {
+        Some(col_id) => {
+            if q.query.is_empty() || matches!(q.query.first(), Some(Query::Select(_))) {
+                q.query.insert(
+                    0,
+                    Query::IndexScan(IndexScan {
+                        table: (&table.root).into(),
+                        col_id,
+                        lower_bound: Bound::Unbounded,
+                        upper_bound: Bound::Unbounded,
+                    }),
+                );
+            }
+            q
+        }
+        None => q.with_sort(keys),
+    }
+}
+
+// Returns the column of the index that yields the rows of `q` already sorted by `keys`, if any.
+//
+// This is the case for an ascending sort on a single indexed column of a query without joins,
+// when the query either does not use an index yet or already scans that same index.
+// Filters do not change the relative order of the rows.
+fn index_order(q: &QueryExpr, table: &From, keys: &[SortKey]) -> Option<u32> {
+    let [SortKey { field, asc: true }] = keys else {
+        return None;
+    };
+    if table.join.is_some() {
+        return None;
+    }
+    let column = table.root.get_column_by_field(field)?;
+    // Only a btree index keeps its values in order.
+    table
+        .root
+        .indexes
+        .iter()
+        .find(|index| index.cols == NonEmpty::new(column.col_id) && index.index_type == IndexType::BTree)?;
+
+    let (first, rest) = match q.query.split_first() {
+        Some((first, rest)) => (Some(first), rest),
+        None => (None, &[][..]),
+    };
+    match first {
+        Some(Query::IndexScan(scan)) if scan.col_id != column.col_id => return None,
+        Some(Query::IndexScan(_) | Query::Select(_)) | None => {}
+        Some(_) => return None,
+    }
+    rest.iter()
+        .all(|op| matches!(op, Query::Select(_)))
+        .then_some(column.col_id)
+}
+
+/// Verify the `field` can be used after grouping the rows, so it is a key or an aggregate of the `group_by`
+fn check_grouped(table: &From, group_by: &GroupBy, field: &FieldName) -> Result<(), PlanError> {
+    let is_grouped = group_by.keys.contains(field)
+        || group_by
+            .aggs
+            .iter()
+            .any(|agg| agg.field_name(&table.root.table_name) == *field);
+    if is_grouped {
+        Ok(())
+    } else {
+        Err(PlanError::Ungrouped { field: field.clone() })
+    }
+}
+
+/// Verify the `fields` inside the `expr` can be used after grouping the rows
+fn check_grouped_expr(table: &From, group_by: &GroupBy, expr: &ColumnOp) -> Result<(), PlanError> {
+    match expr {
+        ColumnOp::Field(FieldExpr::Name(field)) => check_grouped(table, group_by, field),
+        ColumnOp::Field(FieldExpr::Value(_)) => Ok(()),
+        ColumnOp::Cmp { op: _, lhs, rhs } => {
+            check_grouped_expr(table, group_by, lhs)?;
+            check_grouped_expr(table, group_by, rhs)
+        }
+    }
+}
+
+/// Compiles a `GROUP BY ... HAVING ...` clause
+///
+/// After it, only the keys and aggregates of the `group_by` can be used by the rest of the query.
+fn compile_group_by(
+    mut q: QueryExpr,
+    table: &From,
+    group_by: &GroupBy,
+    col_ids: &[FieldExpr],
+    order_by: &[SortKey],
+) -> Result<QueryExpr, PlanError> {
+    for field in col_ids {
+        if let FieldExpr::Name(field) = field {
+            check_grouped(table, group_by, field)?;
+        }
+    }
+    for key in order_by {
+        check_grouped(table, group_by, &key.field)?;
+    }
+
+    q = q.with_aggregate(&group_by.keys, &group_by.aggs);
+    if let Some(having) = &group_by.having {
+        check_grouped_expr(table, group_by, &having.clause)?;
+        q = q.with_select(having.clause.clone());
+    }
+    Ok(q)
+}
+
 /// Compiles a `SELECT ...` clause
-fn compile_select(table: From, project: Vec<Column>, selection: Option<Selection>) -> Result<QueryExpr, PlanError> {
+fn compile_select(
+    table: From,
+    project: Vec<Column>,
+    selection: Option<Selection>,
+    group_by: Option<GroupBy>,
+    order_by: Vec<SortKey>,
+    limit: LimitExpr,
+    stats: &Statistics,
+) -> Result<QueryExpr, PlanError> {
     let mut not_found = Vec::with_capacity(project.len());
     let mut col_ids = Vec::new();
     let mut qualified_wildcards = Vec::new();
+    let mut wildcard = false;
     //Match columns to their tables...
     for select_item in project {
         match select_item {
@@ -223,7 +407,8 @@ fn compile_select(table: From, project: Vec<Column>, selection: Option<Selection
                     return Err(PlanError::TableNotFoundQualified { expect: name });
                 }
             }
-            Column::Wildcard => {}
+            Column::Wildcard => wildcard = true,
+            Column::Aggregate(agg) => col_ids.push(agg.field_name(&table.root.table_name).into()),
         }
     }
 
@@ -258,7 +443,19 @@ fn compile_select(table: From, project: Vec<Column>, selection: Option<Selection
     };
 
     if let Some(filter) = selection {
-        q = compile_where(q, &table, filter)?;
+        q = compile_where(q, &table, filter, stats)?;
+    }
+    if let Some(group_by) = &group_by {
+        if wildcard || !qualified_wildcards.is_empty() {
+            return Err(PlanError::Unsupported {
+                feature: "Wildcard projection with `GROUP BY` or aggregates".into(),
+            });
+        }
+        q = compile_group_by(q, &table, group_by, &col_ids, &order_by)?;
+    }
+    // Sorting happens before the projection, so it can use fields that are not projected.
+    if !order_by.is_empty() {
+        q = compile_order_by(q, &table, &order_by);
     }
     // It is important to project at the end.
     // This is so joins and filters see fields that are not projected.
@@ -270,7 +467,8 @@ fn compile_select(table: From, project: Vec<Column>, selection: Option<Selection
         None
     };
     q = q.with_project(&col_ids, qualified_wildcard);
-    q = try_index_join(q, &table);
+    q = try_index_join(q, &table, stats);
+    q = q.with_limit(limit.offset, limit.limit);
 
     Ok(q)
 }
@@ -278,10 +476,12 @@ fn compile_select(table: From, project: Vec<Column>, selection: Option<Selection
 // Try to turn an applicable join into an index join.
 // An applicable join is one that can use an index to probe the lhs.
 // It must also project only the columns from the lhs.
+// It is only turned into an index join if probing the index for each row of the rhs
+// is estimated to be cheaper than a hash join with all the rows of the lhs.
 //
 // Ex. SELECT Left.* FROM Left JOIN Right ON Left.id = Right.id ...
 // where `Left` has an index defined on `id`.
-fn try_index_join(mut query: QueryExpr, table: &From) -> QueryExpr {
+fn try_index_join(mut query: QueryExpr, table: &From, stats: &Statistics) -> QueryExpr {
     // We expect 2 and only 2 operations - a join followed by a wildcard projection.
     if query.query.len() != 2 {
         return query;
@@ -305,14 +505,17 @@ fn try_index_join(mut query: QueryExpr, table: &From) -> QueryExpr {
             col_lhs: index_field,
             col_rhs: probe_field,
         }) => {
-            if !probe_side.query.is_empty() && wildcard_table_id == table.root.table_id {
+            if wildcard_table_id == table.root.table_id {
                 // An applicable join must have an index defined on the correct field.
                 if let Some(IndexSchema {
                     cols: NonEmpty { head, tail },
                     ..
                 }) = table.root.get_index_by_field(&index_field)
                 {
-                    if tail.is_empty() {
+                    let probe_rows = stats.rows(table, &probe_side);
+                    let index_join_cost = stats.index_join_cost(probe_rows, table.root.table_id, *head);
+                    let hash_join_cost = stats.hash_join_cost(probe_rows, table.root.table_id);
+                    if tail.is_empty() && index_join_cost <= hash_join_cost {
                         let schema = &table.root;
                         let index_join = IndexJoin {
                             probe_side,
@@ -379,10 +582,10 @@ fn compile_insert(
 }
 
 /// Compiles a `DELETE ...` clause
-fn compile_delete(table: TableSchema, selection: Option<Selection>) -> Result<CrudExpr, PlanError> {
+fn compile_delete(table: TableSchema, selection: Option<Selection>, stats: &Statistics) -> Result<CrudExpr, PlanError> {
     let query = if let Some(filter) = selection {
         let query = QueryExpr::new(&table);
-        compile_where(query, &From::new(table), filter)?
+        compile_where(query, &From::new(table), filter, stats)?
     } else {
         QueryExpr::new(&table)
     };
@@ -394,11 +597,12 @@ fn compile_update(
     table: TableSchema,
     assignments: HashMap<FieldName, FieldExpr>,
     selection: Option<Selection>,
+    stats: &Statistics,
 ) -> Result<CrudExpr, PlanError> {
     let table = From::new(table);
     let delete = if let Some(filter) = selection.clone() {
         let query = QueryExpr::new(&table.root);
-        compile_where(query, &table, filter)?
+        compile_where(query, &table, filter, stats)?
     } else {
         QueryExpr::new(&table.root)
     };
@@ -415,7 +619,7 @@ fn compile_update(
 
     let insert = QueryExpr::new(&table.root).with_project(&cols, None);
     let insert = if let Some(filter) = selection {
-        compile_where(insert, &table, filter)?
+        compile_where(insert, &table, filter, stats)?
     } else {
         insert
     };
@@ -447,20 +651,25 @@ fn compile_drop(name: String, kind: DbType, table_access: StAccess) -> Result<Cr
 }
 
 /// Compiles a `SQL` clause
-fn compile_statement(statement: SqlAst) -> Result<CrudExpr, PlanError> {
+fn compile_statement(statement: SqlAst, stats: &Statistics) -> Result<CrudExpr, PlanError> {
     let q = match statement {
         SqlAst::Select {
             from,
             project,
             selection,
-        } => CrudExpr::Query(compile_select(from, project, selection)?),
+            group_by,
+            order_by,
+            limit,
+        } => CrudExpr::Query(compile_select(
+            from, project, selection, group_by, order_by, limit, stats,
+        )?),
         SqlAst::Insert { table, columns, values } => compile_insert(table, columns, values)?,
         SqlAst::Update {
             table,
             assignments,
             selection,
-        } => compile_update(table, assignments, selection)?,
-        SqlAst::Delete { table, selection } => compile_delete(table, selection)?,
+        } => compile_update(table, assignments, selection, stats)?,
+        SqlAst::Delete { table, selection } => compile_delete(table, selection, stats)?,
         SqlAst::CreateTable {
             table,
             columns,
@@ -483,12 +692,13 @@ mod tests {
 
     use super::*;
     use itertools::Itertools;
+    use spacetimedb_lib::operator::OpLogic;
     use spacetimedb_lib::{
         auth::{StAccess, StTableType},
         error::ResultTest,
     };
-    use spacetimedb_sats::AlgebraicType;
-    use spacetimedb_vm::expr::{IndexScan, JoinExpr, Query};
+    use spacetimedb_sats::{product, AlgebraicType};
+    use spacetimedb_vm::expr::{AggExpr, AggFn, IndexScan, JoinExpr, Query};
 
     use crate::db::{
         datastore::traits::{ColumnDef, IndexDef, TableDef},
@@ -522,6 +732,7 @@ mod tests {
                 cols: NonEmpty::new(*col_id),
                 name: index_name.to_string(),
                 is_unique: false,
+                index_type: IndexType::BTree,
             })
             .collect_vec();
 
@@ -613,8 +824,8 @@ mod tests {
         let indexes = &[(1, "b")];
         create_table(&db, &mut tx, "test", schema, indexes)?;
 
-        // Note, order matters - the sargable predicate occurs last which means
-        // no index scan will be generated.
+        // Note, order doesn't matter - the sargable predicate occurs last
+        // but an index scan is still generated for it.
         let sql = "select * from test where a = 1 and b = 2";
         let CrudExpr::Query(QueryExpr {
             source: _,
@@ -624,9 +835,14 @@ mod tests {
             panic!("Expected QueryExpr");
         };
 
-        assert_eq!(1, ops.len());
+        assert_eq!(2, ops.len());
 
-        // Assert no index scan
+        // Assert index scan
+        let Query::IndexScan(IndexScan { col_id: 1, .. }) = ops.remove(0) else {
+            panic!("Expected IndexScan");
+        };
+
+        // Followed by a selection
         let Query::Select(_) = ops.remove(0) else {
             panic!("Expected Select");
         };
@@ -643,8 +859,7 @@ mod tests {
         let indexes = &[(1, "b")];
         create_table(&db, &mut tx, "test", schema, indexes)?;
 
-        // Note, order matters - the sargable predicate occurs first which
-        // means an index scan will be generated.
+        // The sargable predicate occurs first, and an index scan is generated for it.
         let sql = "select * from test where b = 2 and a = 1";
         let CrudExpr::Query(QueryExpr {
             source: _,
@@ -737,6 +952,46 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn compile_hash_index() -> ResultTest<()> {
+        let (db, _) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        // Create table [test] with a hash index on [b]
+        let schema = &[("a", AlgebraicType::U64), ("b", AlgebraicType::U64)];
+        let table_id = create_table(&db, &mut tx, "test", schema, &[])?;
+        let index = IndexDef::new("b".into(), table_id, 1, false).with_index_type(IndexType::Hash);
+        db.create_index(&mut tx, index)?;
+
+        let compile = |sql| match compile_sql(&db, &tx, sql).map(|mut exprs| exprs.remove(0)) {
+            Ok(CrudExpr::Query(QueryExpr { source: _, query })) => query,
+            _ => panic!("Expected QueryExpr"),
+        };
+
+        // An equality is answered by the hash index
+        let ops = compile("select * from test where b = 2");
+        let [Query::IndexScan(IndexScan {
+            table: _,
+            col_id: 1,
+            lower_bound: Bound::Included(u),
+            upper_bound: Bound::Included(v),
+        })] = &ops[..]
+        else {
+            panic!("Expected IndexScan");
+        };
+        assert_eq!(u, v);
+        assert_eq!(*v, AlgebraicValue::U64(2));
+
+        // A range is not
+        let ops = compile("select * from test where b > 2");
+        assert!(matches!(&ops[..], [Query::Select(_)]), "Expected Select: {ops:?}");
+
+        // Nor is the order of the rows
+        let ops = compile("select * from test order by b");
+        assert!(matches!(&ops[..], [Query::Sort(_)]), "Expected Sort: {ops:?}");
+        Ok(())
+    }
+
     #[test]
     fn compile_index_range_closed() -> ResultTest<()> {
         let (db, _) = make_test_db()?;
@@ -784,9 +1039,9 @@ mod tests {
         let indexes = &[(0, "a"), (1, "b")];
         create_table(&db, &mut tx, "test", schema, indexes)?;
 
-        // Note, order matters - the equality condition occurs first which
-        // means an index scan will be generated it rather than the range
-        // condition.
+        // Note, the table is empty so both indexes are as cheap to seek,
+        // and the equality condition that occurs first gets the index scan
+        // rather than the range condition.
         let sql = "select * from test where a = 3 and b > 2 and b < 5";
         let CrudExpr::Query(QueryExpr {
             source: _,
@@ -817,6 +1072,96 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn compile_index_selectivity() -> ResultTest<()> {
+        let (db, _) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        // Create table [test] with indexes on [a], [b] and [c]
+        let schema = &[
+            ("a", AlgebraicType::U64),
+            ("b", AlgebraicType::U64),
+            ("c", AlgebraicType::U64),
+        ];
+        let indexes = &[(0, "a"), (1, "b"), (2, "c")];
+        let table_id = create_table(&db, &mut tx, "test", schema, indexes)?;
+
+        // [a] is unique, [b] has two values and [c] only one
+        for i in 0..100u64 {
+            db.insert(&mut tx, table_id, product![i, i % 2, 0u64])?;
+        }
+
+        let compile = |sql| match compile_sql(&db, &tx, sql).map(|mut exprs| exprs.remove(0)) {
+            Ok(CrudExpr::Query(QueryExpr { source: _, query })) => query,
+            _ => panic!("Expected QueryExpr"),
+        };
+
+        // The most selective index is seeked, wherever its condition occurs
+        let ops = compile("select * from test where b = 1 and a = 5");
+        let [Query::IndexScan(IndexScan { col_id: 0, .. }), Query::Select(_)] = &ops[..] else {
+            panic!("Expected IndexScan on [a]: {ops:?}");
+        };
+
+        // An index that only skips half of the rows is still cheaper than a scan
+        let ops = compile("select * from test where b = 1");
+        let [Query::IndexScan(IndexScan { col_id: 1, .. })] = &ops[..] else {
+            panic!("Expected IndexScan on [b]: {ops:?}");
+        };
+
+        // But not one that matches all of them
+        let ops = compile("select * from test where c = 0");
+        assert!(matches!(&ops[..], [Query::Select(_)]), "Expected Select: {ops:?}");
+        Ok(())
+    }
+
+    #[test]
+    fn compile_filter_order() -> ResultTest<()> {
+        let (db, _) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        // Create table [test] without any indexes
+        let schema = &[("a", AlgebraicType::U64), ("b", AlgebraicType::U64)];
+        create_table(&db, &mut tx, "test", schema, &[])?;
+
+        // The equality is more selective than the range, so it is evaluated first
+        let sql = "select * from test where a > 1 and b = 2";
+        let CrudExpr::Query(QueryExpr {
+            source: _,
+            query: mut ops,
+        }) = compile_sql(&db, &tx, sql)?.remove(0)
+        else {
+            panic!("Expected QueryExpr");
+        };
+
+        assert_eq!(1, ops.len());
+
+        let Query::Select(ColumnOp::Cmp {
+            op: OpQuery::Logic(OpLogic::And),
+            lhs,
+            rhs,
+        }) = ops.remove(0)
+        else {
+            panic!("Expected Select");
+        };
+
+        let ColumnOp::Cmp {
+            op: OpQuery::Cmp(OpCmp::Eq),
+            ..
+        } = *lhs
+        else {
+            panic!("unexpected left hand side {:#?}", lhs);
+        };
+
+        let ColumnOp::Cmp {
+            op: OpQuery::Cmp(OpCmp::Gt),
+            ..
+        } = *rhs
+        else {
+            panic!("unexpected right hand side {:#?}", rhs);
+        };
+        Ok(())
+    }
+
     #[test]
     fn compile_join_lhs_push_down() -> ResultTest<()> {
         let (db, _) = make_test_db()?;
@@ -1240,4 +1585,202 @@ mod tests {
         };
         Ok(())
     }
+
+    #[test]
+    fn compile_index_join_cost() -> ResultTest<()> {
+        let (db, _) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        // Create table [lhs] with index on [b]
+        let schema = &[("a", AlgebraicType::U64), ("b", AlgebraicType::U64)];
+        let lhs_id = create_table(&db, &mut tx, "lhs", schema, &[(1, "b")])?;
+
+        // Create table [rhs] with no indexes
+        let schema = &[("b", AlgebraicType::U64), ("c", AlgebraicType::U64)];
+        let rhs_id = create_table(&db, &mut tx, "rhs", schema, &[])?;
+
+        for i in 0..10u64 {
+            db.insert(&mut tx, lhs_id, product![i, i])?;
+        }
+        for i in 0..100u64 {
+            db.insert(&mut tx, rhs_id, product![i % 10, i])?;
+        }
+
+        let compile = |sql| match compile_sql(&db, &tx, sql).map(|mut exprs| exprs.remove(0)) {
+            Ok(CrudExpr::Query(QueryExpr { source: _, query })) => query,
+            _ => panic!("Expected QueryExpr"),
+        };
+
+        // Probing the small lhs for every row of the rhs costs more than hashing it
+        let ops = compile("select lhs.* from lhs join rhs on lhs.b = rhs.b");
+        assert!(
+            matches!(&ops[..], [Query::JoinInner(_), Query::Project(..)]),
+            "Expected JoinInner: {ops:?}"
+        );
+
+        // Unless the rhs is filtered down to a few rows
+        let ops = compile("select lhs.* from lhs join rhs on lhs.b = rhs.b where rhs.c = 3");
+        assert!(matches!(&ops[..], [Query::IndexJoin(_)]), "Expected IndexJoin: {ops:?}");
+        Ok(())
+    }
+
+    #[test]
+    fn compile_order_by() -> ResultTest<()> {
+        let (db, _) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        // Create table [test] with index on [b]
+        let schema = &[("a", AlgebraicType::U64), ("b", AlgebraicType::U64)];
+        let indexes = &[(1, "b")];
+        create_table(&db, &mut tx, "test", schema, indexes)?;
+
+        // [a] is not indexed, so the rows must be sorted
+        let sql = "select * from test order by a desc limit 10 offset 5";
+        let CrudExpr::Query(QueryExpr {
+            source: _,
+            query: mut ops,
+        }) = compile_sql(&db, &tx, sql)?.remove(0)
+        else {
+            panic!("Expected QueryExpr");
+        };
+
+        assert_eq!(2, ops.len());
+
+        let Query::Sort(keys) = ops.remove(0) else {
+            panic!("Expected Sort");
+        };
+        assert_eq!(keys, vec![SortKey::new(FieldName::named("test", "a"), false)]);
+
+        let Query::Limit(limit) = ops.remove(0) else {
+            panic!("Expected Limit");
+        };
+        assert_eq!(
+            limit,
+            LimitExpr {
+                offset: 5,
+                limit: Some(10)
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn compile_index_order_by() -> ResultTest<()> {
+        let (db, _) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        // Create table [test] with index on [b]
+        let schema = &[("a", AlgebraicType::U64), ("b", AlgebraicType::U64)];
+        let indexes = &[(1, "b")];
+        create_table(&db, &mut tx, "test", schema, indexes)?;
+
+        // [b] is indexed, so the rows are streamed in index order
+        let sql = "select * from test where a = 1 order by b";
+        let CrudExpr::Query(QueryExpr {
+            source: _,
+            query: mut ops,
+        }) = compile_sql(&db, &tx, sql)?.remove(0)
+        else {
+            panic!("Expected QueryExpr");
+        };
+
+        assert_eq!(2, ops.len());
+
+        let Query::IndexScan(IndexScan {
+            table: _,
+            col_id: 1,
+            lower_bound: Bound::Unbounded,
+            upper_bound: Bound::Unbounded,
+        }) = ops.remove(0)
+        else {
+            panic!("Expected IndexScan");
+        };
+        let Query::Select(_) = ops.remove(0) else {
+            panic!("Expected Select");
+        };
+
+        // The range scan on [b] already returns the rows in order
+        let sql = "select * from test where b > 1 order by b";
+        let CrudExpr::Query(QueryExpr {
+            source: _,
+            query: mut ops,
+        }) = compile_sql(&db, &tx, sql)?.remove(0)
+        else {
+            panic!("Expected QueryExpr");
+        };
+
+        assert_eq!(1, ops.len());
+
+        let Query::IndexScan(IndexScan {
+            table: _,
+            col_id: 1,
+            lower_bound: Bound::Excluded(_),
+            upper_bound: Bound::Unbounded,
+        }) = ops.remove(0)
+        else {
+            panic!("Expected IndexScan");
+        };
+        Ok(())
+    }
+
+    #[test]
+    fn compile_group_by() -> ResultTest<()> {
+        let (db, _) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        let schema = &[("team", AlgebraicType::U64), ("score", AlgebraicType::U64)];
+        create_table(&db, &mut tx, "player", schema, &[])?;
+
+        let sql = "select team, count(*) from player group by team having count(*) > 10 order by sum(score)";
+        let CrudExpr::Query(QueryExpr {
+            source: _,
+            query: mut ops,
+        }) = compile_sql(&db, &tx, sql)?.remove(0)
+        else {
+            panic!("Expected QueryExpr");
+        };
+
+        assert_eq!(4, ops.len());
+
+        let count = AggExpr::new(AggFn::Count, None);
+        let sum = AggExpr::new(AggFn::Sum, Some(FieldName::named("player", "score")));
+        let Query::Aggregate(group) = ops.remove(0) else {
+            panic!("Expected Aggregate");
+        };
+        assert_eq!(group.keys, vec![FieldName::named("player", "team")]);
+        assert_eq!(group.aggs, vec![count.clone(), sum.clone()]);
+
+        // The literal gets the type of the aggregate
+        let Query::Select(ColumnOp::Cmp { op: _, lhs, rhs }) = ops.remove(0) else {
+            panic!("Expected Select");
+        };
+        assert_eq!(*lhs, ColumnOp::Field(count.field_name("player").into()));
+        assert_eq!(*rhs, ColumnOp::Field(AlgebraicValue::U64(10).into()));
+
+        let Query::Sort(keys) = ops.remove(0) else {
+            panic!("Expected Sort");
+        };
+        assert_eq!(keys, vec![SortKey::new(sum.field_name("player"), true)]);
+
+        let Query::Project(fields, None) = ops.remove(0) else {
+            panic!("Expected Project");
+        };
+        assert_eq!(
+            fields,
+            vec![
+                FieldName::named("player", "team").into(),
+                count.field_name("player").into()
+            ]
+        );
+
+        // Only the keys & aggregates are available after grouping
+        assert!(matches!(
+            compile_sql(&db, &tx, "select score from player group by team"),
+            Err(DBError::Plan {
+                error: PlanError::Ungrouped { .. },
+                ..
+            })
+        ));
+        Ok(())
+    }
 }
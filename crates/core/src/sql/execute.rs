@@ -10,6 +10,7 @@ use crate::db::datastore::locking_tx_datastore::MutTxId;
 use crate::db::relational_db::RelationalDB;
 use crate::error::{DBError, DatabaseError};
 use crate::sql::compiler::compile_sql;
+use crate::sql::row_filter::RowFilters;
 use crate::vm::DbProgram;
 
 pub struct StmtResult {
@@ -27,12 +28,12 @@ pub fn execute(
     database_instance_id: u64,
     sql_text: String,
     auth: AuthCtx,
+    row_filters: &RowFilters,
 ) -> Result<Vec<MemTable>, DBError> {
     info!(sql = sql_text);
     if let Some((database_instance_context, _)) = db_inst_ctx_controller.get(database_instance_id) {
-        database_instance_context
-            .relational_db
-            .with_auto_commit(|tx| run(&database_instance_context.relational_db, tx, &sql_text, auth))
+        let db = &database_instance_context.relational_db;
+        db.with_auto_commit(|tx| run_with_filters(db, tx, &sql_text, auth, row_filters))
     } else {
         Err(DatabaseError::NotFound(database_instance_id).into())
     }
@@ -90,21 +91,39 @@ pub fn execute_sql(
 /// Run the `SQL` string using the `auth` credentials
 #[tracing::instrument(skip_all)]
 pub fn run(db: &RelationalDB, tx: &mut MutTxId, sql_text: &str, auth: AuthCtx) -> Result<Vec<MemTable>, DBError> {
-    let ast = compile_sql(db, tx, sql_text)?;
+    run_with_filters(db, tx, sql_text, auth, &RowFilters::default())
+}
+
+/// Run the `SQL` string using the `auth` credentials,
+/// reading and modifying only the rows that `row_filters` let `auth.caller` read.
+#[tracing::instrument(skip_all)]
+pub fn run_with_filters(
+    db: &RelationalDB,
+    tx: &mut MutTxId,
+    sql_text: &str,
+    auth: AuthCtx,
+    row_filters: &RowFilters,
+) -> Result<Vec<MemTable>, DBError> {
+    let ast = compile_sql(db, tx, sql_text)?
+        .into_iter()
+        .map(|expr| row_filters.restrict_crud(db, tx, &auth, expr))
+        .collect::<Result<_, _>>()?;
     execute_sql(db, tx, ast, auth)
 }
 
 #[cfg(test)]
 pub(crate) mod tests {
     use super::*;
+    use crate::db::datastore::traits::IndexDef;
     use crate::db::relational_db::tests_utils::make_test_db;
     use crate::db::relational_db::{ST_TABLES_ID, ST_TABLES_NAME};
+    use crate::error::PlanError;
     use crate::vm::tests::create_table_with_rows;
     use itertools::Itertools;
     use spacetimedb_lib::auth::{StAccess, StTableType};
     use spacetimedb_lib::error::ResultTest;
     use spacetimedb_lib::relation::{Header, RelValue};
-    use spacetimedb_sats::{product, AlgebraicType, ProductType};
+    use spacetimedb_sats::{product, AlgebraicType, AlgebraicValue, ProductType};
     use spacetimedb_vm::dsl::{mem_table, scalar};
     use spacetimedb_vm::eval::create_game_data;
     use tempdir::TempDir;
@@ -348,6 +367,269 @@ pub(crate) mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_order_by_limit_offset() -> ResultTest<()> {
+        let (db, table, _tmp_dir) = create_data(5)?;
+        let mut tx = db.begin_tx();
+
+        let result = run_for_testing(
+            &db,
+            &mut tx,
+            "SELECT inventory_id FROM inventory ORDER BY name DESC LIMIT 2 OFFSET 1",
+        )?;
+
+        assert_eq!(result.len(), 1, "Not return results");
+        let result = result.first().unwrap().clone();
+
+        //The expected result
+        let col = table.head.find_by_name("inventory_id").unwrap();
+        let inv = table.head.project(&[col.field.clone()]).unwrap();
+        let input = mem_table(inv, vec![product!(scalar(4u64)), product!(scalar(3u64))]);
+
+        assert_eq!(
+            result.as_without_table_name(),
+            input.as_without_table_name(),
+            "Inventory"
+        );
+
+        let result = run_for_testing(&db, &mut tx, "SELECT * FROM inventory LIMIT 0")?;
+        assert_eq!(result.first().unwrap().data.len(), 0, "Limit 0");
+
+        let result = run_for_testing(&db, &mut tx, "SELECT * FROM inventory OFFSET 10")?;
+        assert_eq!(result.first().unwrap().data.len(), 0, "Offset past the end");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_order_by_index() -> ResultTest<()> {
+        let (db, _, _tmp_dir) = create_data(0)?;
+        let mut tx = db.begin_tx();
+        let table_id = db.table_id_from_name(&tx, "inventory")?.unwrap();
+        db.create_index(&mut tx, IndexDef::new("idx_inventory_id".into(), table_id, 0, false))?;
+        for i in [3u64, 1, 5] {
+            db.insert(&mut tx, table_id, product!(i, format!("health{i}")))?;
+        }
+        db.commit_tx(tx)?;
+
+        // Rows inserted by the current transaction must be merged in index order.
+        let mut tx = db.begin_tx();
+        for i in [4u64, 2] {
+            db.insert(&mut tx, table_id, product!(i, format!("health{i}")))?;
+        }
+
+        let result = run_for_testing(
+            &db,
+            &mut tx,
+            "SELECT inventory_id FROM inventory WHERE inventory_id > 1 ORDER BY inventory_id LIMIT 3",
+        )?;
+        let ids = result
+            .first()
+            .unwrap()
+            .data
+            .iter()
+            .map(|row| row.data.elements[0].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, [2u64, 3, 4].map(AlgebraicValue::U64), "Index order");
+
+        Ok(())
+    }
+
+    fn create_players(db: &RelationalDB) -> ResultTest<()> {
+        let mut tx = db.begin_tx();
+        let head = ProductType::from([("team", AlgebraicType::String), ("score", AlgebraicType::I32)]);
+        let rows = [
+            ("red", 3),
+            ("blue", 5),
+            ("red", -1),
+            ("green", 7),
+            ("blue", 6),
+            ("red", 4),
+        ]
+        .map(|(team, score)| product!(team, score));
+        create_table_with_rows(db, &mut tx, "player", head, &rows)?;
+        db.commit_tx(tx)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_by() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        create_players(&db)?;
+        let mut tx = db.begin_tx();
+
+        let result = run_for_testing(
+            &db,
+            &mut tx,
+            "SELECT team, COUNT(*), SUM(score), MIN(score), MAX(score), AVG(score) FROM player GROUP BY team",
+        )?;
+        let result = result.first().unwrap();
+
+        let types = result
+            .head
+            .fields
+            .iter()
+            .map(|x| x.algebraic_type.clone())
+            .collect_vec();
+        assert_eq!(
+            types,
+            [
+                AlgebraicType::String,
+                AlgebraicType::U64,
+                AlgebraicType::I64,
+                AlgebraicType::I32,
+                AlgebraicType::I32,
+                AlgebraicType::F64
+            ],
+            "Aggregate types"
+        );
+        let rows = result.data.iter().map(|row| row.data.clone()).collect_vec();
+        assert_eq!(
+            rows,
+            [
+                product!("blue", 2u64, 11i64, 5i32, 6i32, AlgebraicValue::F64(5.5.into())),
+                product!("green", 1u64, 7i64, 7i32, 7i32, AlgebraicValue::F64(7.0.into())),
+                product!("red", 3u64, 6i64, -1i32, 4i32, AlgebraicValue::F64(2.0.into())),
+            ],
+            "Groups"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_by_having() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        create_players(&db)?;
+        let mut tx = db.begin_tx();
+
+        let result = run_for_testing(
+            &db,
+            &mut tx,
+            "SELECT team, COUNT(*) FROM player WHERE score > 0 GROUP BY team HAVING COUNT(*) > 1 ORDER BY SUM(score) DESC",
+        )?;
+        let rows = result
+            .first()
+            .unwrap()
+            .data
+            .iter()
+            .map(|row| row.data.clone())
+            .collect_vec();
+        assert_eq!(rows, [product!("blue", 2u64), product!("red", 2u64)], "Having");
+
+        // Fields must be grouped
+        assert!(matches!(
+            run_for_testing(&db, &mut tx, "SELECT team, score FROM player GROUP BY team"),
+            Err(DBError::Plan {
+                error: PlanError::Ungrouped { .. },
+                ..
+            })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_aggregate_without_group_by() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        create_players(&db)?;
+        let mut tx = db.begin_tx();
+
+        let result = run_for_testing(&db, &mut tx, "SELECT COUNT(*), MAX(score) FROM player")?;
+        let rows = result
+            .first()
+            .unwrap()
+            .data
+            .iter()
+            .map(|row| row.data.clone())
+            .collect_vec();
+        assert_eq!(rows, [product!(6u64, AlgebraicValue::OptionSome(7i32.into()))]);
+
+        // There is still a single group, with `NULL` aggregates
+        let result = run_for_testing(
+            &db,
+            &mut tx,
+            "SELECT COUNT(*), SUM(score) FROM player WHERE score > 100 HAVING COUNT(*) = 0",
+        )?;
+        let rows = result
+            .first()
+            .unwrap()
+            .data
+            .iter()
+            .map(|row| row.data.clone())
+            .collect_vec();
+        assert_eq!(rows, [product!(0u64, AlgebraicValue::OptionNone())]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_by_all_null() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        let mut tx = db.begin_tx();
+        let head = ProductType::from([
+            ("team", AlgebraicType::String),
+            ("score", AlgebraicType::option(AlgebraicType::I32)),
+        ]);
+        let rows = [("red", Some(3)), ("blue", None), ("red", None), ("red", Some(-1))].map(|(team, score)| {
+            let score = match score {
+                Some(score) => AlgebraicValue::OptionSome(AlgebraicValue::I32(score)),
+                None => AlgebraicValue::OptionNone(),
+            };
+            product!(team, score)
+        });
+        create_table_with_rows(&db, &mut tx, "player", head, &rows)?;
+
+        // The aggregates skip the `NULL`s, and are `NULL` over a group of only `NULL`s
+        let result = run_for_testing(
+            &db,
+            &mut tx,
+            "SELECT team, COUNT(*), COUNT(score), MIN(score), MAX(score), SUM(score), AVG(score) FROM player GROUP BY team",
+        )?;
+        let rows = result
+            .first()
+            .unwrap()
+            .data
+            .iter()
+            .map(|row| row.data.clone())
+            .collect_vec();
+        let none = AlgebraicValue::OptionNone;
+        let some = AlgebraicValue::OptionSome;
+        assert_eq!(
+            rows,
+            [
+                product!("blue", 1u64, 0u64, none(), none(), none(), none()),
+                product!(
+                    "red",
+                    3u64,
+                    2u64,
+                    some(AlgebraicValue::I32(-1)),
+                    some(AlgebraicValue::I32(3)),
+                    some(AlgebraicValue::I64(2)),
+                    some(1.0f64.into())
+                ),
+            ],
+            "Groups"
+        );
+
+        // Without `GROUP BY`, the aggregates of a nullable field are nullable once
+        let result = run_for_testing(&db, &mut tx, "SELECT MIN(score), SUM(score) FROM player")?;
+        let rows = result
+            .first()
+            .unwrap()
+            .data
+            .iter()
+            .map(|row| row.data.clone())
+            .collect_vec();
+        assert_eq!(
+            rows,
+            [product!(some(AlgebraicValue::I32(-1)), some(AlgebraicValue::I64(2)))],
+            "Aggregates"
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_inner_join() -> ResultTest<()> {
         let data = create_game_data();
@@ -1,3 +1,5 @@
 pub mod ast;
 pub mod compiler;
 pub mod execute;
+pub mod optimizer;
+pub mod row_filter;
@@ -0,0 +1,177 @@
+//! The statistics and the cost model used by the [compiler](super::compiler)
+//! to choose between the plans of a query.
+//!
+//! The costs are estimated from the number of rows of each table
+//! and the number of distinct values of each index, as reported by the datastore.
+//! They are measured in rows read by a table scan.
+use std::collections::HashMap;
+
+use nonempty::NonEmpty;
+use spacetimedb_lib::operator::{OpLogic, OpQuery};
+use spacetimedb_lib::relation::{FieldExpr, FieldName};
+use spacetimedb_vm::expr::{ColumnOp, IndexScan, Query, QueryExpr};
+use spacetimedb_vm::operator::OpCmp;
+use std::ops::Bound;
+
+use crate::db::datastore::locking_tx_datastore::MutTxId;
+use crate::db::datastore::traits::{ColumnSchema, TableSchema, TableStats};
+use crate::db::relational_db::RelationalDB;
+use crate::error::DBError;
+use crate::sql::ast::From;
+
+/// The cost of reading a row through an index, relative to reading it in a table scan,
+/// as the row is fetched from the table by its id.
+const INDEX_ROW_COST: f64 = 1.5;
+/// The cost of probing an index for a value, even if no row matches it.
+const INDEX_PROBE_COST: f64 = 1.0;
+/// The cost of inserting or probing a row in the hash table of a hash join.
+const HASH_ROW_COST: f64 = 1.0;
+
+/// The fraction of the rows matching an equality on a column without statistics.
+const EQ_SELECTIVITY: f64 = 0.1;
+/// The fraction of the rows matching a range on a column, e.g. `a > 5`.
+const RANGE_SELECTIVITY: f64 = 0.25;
+/// The fraction of the rows matching any other condition.
+const DEFAULT_SELECTIVITY: f64 = 0.5;
+
+/// The [TableStats] of the tables of a statement.
+#[derive(Debug, Default)]
+pub struct Statistics {
+    tables: HashMap<u32, TableStats>,
+}
+
+impl Statistics {
+    /// Collects the statistics of the `tables`, as seen by `tx`.
+    pub fn collect<'a>(
+        db: &RelationalDB,
+        tx: &MutTxId,
+        tables: impl IntoIterator<Item = &'a TableSchema>,
+    ) -> Result<Self, DBError> {
+        let mut stats = Self::default();
+        for table in tables {
+            stats.tables.insert(table.table_id, db.table_stats(tx, table.table_id)?);
+        }
+        Ok(stats)
+    }
+
+    /// The number of rows of the table.
+    pub fn row_count(&self, table_id: u32) -> f64 {
+        self.tables.get(&table_id).map_or(0.0, |stats| stats.row_count as f64)
+    }
+
+    /// The number of distinct values of the index on `col_id`, if there is one.
+    fn distinct_values(&self, table_id: u32, col_id: u32) -> Option<f64> {
+        let stats = self.tables.get(&table_id)?;
+        let distinct = stats.distinct_values.get(&NonEmpty::new(col_id))?;
+        Some((*distinct).max(1) as f64)
+    }
+
+    /// The fraction of the rows of the table with a given value in `col_id`.
+    ///
+    /// Assumes the values are uniformly distributed among the distinct values of its index.
+    pub fn eq_selectivity(&self, table_id: u32, col_id: u32) -> f64 {
+        self.distinct_values(table_id, col_id)
+            .map_or(EQ_SELECTIVITY, |distinct| 1.0 / distinct)
+    }
+
+    /// The fraction of the rows of the tables of `from` that satisfy `op`.
+    ///
+    /// The conditions of a conjunction or disjunction are assumed to be independent.
+    pub fn selectivity(&self, from: &From, op: &ColumnOp) -> f64 {
+        match op {
+            ColumnOp::Cmp {
+                op: OpQuery::Logic(logic),
+                lhs,
+                rhs,
+            } => {
+                let (lhs, rhs) = (self.selectivity(from, lhs), self.selectivity(from, rhs));
+                match logic {
+                    OpLogic::And => lhs * rhs,
+                    OpLogic::Or => lhs + rhs - lhs * rhs,
+                }
+            }
+            ColumnOp::Cmp {
+                op: OpQuery::Cmp(cmp),
+                lhs,
+                rhs,
+            } => {
+                let eq = match (&**lhs, &**rhs) {
+                    (ColumnOp::Field(FieldExpr::Name(field)), ColumnOp::Field(FieldExpr::Value(_)))
+                    | (ColumnOp::Field(FieldExpr::Value(_)), ColumnOp::Field(FieldExpr::Name(field))) => {
+                        find_column(from, field).map_or(EQ_SELECTIVITY, |(table, column)| {
+                            self.eq_selectivity(table.table_id, column.col_id)
+                        })
+                    }
+                    _ => EQ_SELECTIVITY,
+                };
+                match cmp {
+                    OpCmp::Eq => eq,
+                    OpCmp::NotEq => 1.0 - eq,
+                    OpCmp::Lt | OpCmp::LtEq | OpCmp::Gt | OpCmp::GtEq => RANGE_SELECTIVITY,
+                }
+            }
+            ColumnOp::Field(_) => DEFAULT_SELECTIVITY,
+        }
+    }
+
+    /// The fraction of the rows of its table that fall within the range of the `scan`.
+    pub fn index_scan_selectivity(&self, scan: &IndexScan) -> f64 {
+        let bound = |bound: &Bound<_>| match bound {
+            Bound::Unbounded => 1.0,
+            _ => RANGE_SELECTIVITY,
+        };
+        match (&scan.lower_bound, &scan.upper_bound) {
+            (Bound::Included(lower), Bound::Included(upper)) if lower == upper => {
+                self.eq_selectivity(scan.table.table_id, scan.col_id)
+            }
+            (lower, upper) => bound(lower) * bound(upper),
+        }
+    }
+
+    /// The estimated number of rows yielded by `query`.
+    ///
+    /// Only the index scans and the filters are taken into account,
+    /// the other operations are assumed to yield as many rows as they are given.
+    pub fn rows(&self, from: &From, query: &QueryExpr) -> f64 {
+        let Some(table) = query.source.get_db_table() else {
+            return 0.0;
+        };
+        query
+            .query
+            .iter()
+            .fold(self.row_count(table.table_id), |rows, op| match op {
+                Query::IndexScan(scan) => rows * self.index_scan_selectivity(scan),
+                Query::Select(op) => rows * self.selectivity(from, op),
+                _ => rows,
+            })
+    }
+
+    /// The cost of reading all the rows of the table.
+    pub fn scan_cost(&self, table_id: u32) -> f64 {
+        self.row_count(table_id)
+    }
+
+    /// The cost of reading the `selectivity` fraction of the rows of the table through an index.
+    pub fn index_scan_cost(&self, table_id: u32, selectivity: f64) -> f64 {
+        self.row_count(table_id) * selectivity * INDEX_ROW_COST
+    }
+
+    /// The cost of joining the `probe_rows` with the rows of the table
+    /// by probing the index on `index_col` for each of them.
+    pub fn index_join_cost(&self, probe_rows: f64, index_table: u32, index_col: u32) -> f64 {
+        let matches = self.row_count(index_table) * self.eq_selectivity(index_table, index_col);
+        probe_rows * (INDEX_PROBE_COST + matches * INDEX_ROW_COST)
+    }
+
+    /// The cost of joining the `probe_rows` with all the rows of the table in a hash join.
+    pub fn hash_join_cost(&self, probe_rows: f64, table_id: u32) -> f64 {
+        self.scan_cost(table_id) + (self.row_count(table_id) + probe_rows) * HASH_ROW_COST
+    }
+}
+
+/// Finds the table and column of `field` among the tables of `from`.
+pub(crate) fn find_column<'a>(from: &'a From, field: &FieldName) -> Option<(&'a TableSchema, &'a ColumnSchema)> {
+    from.iter_tables()
+        .filter(|table| table.table_name == field.table())
+        .find_map(|table| table.get_column_by_field(field).map(|column| (table, column)))
+}
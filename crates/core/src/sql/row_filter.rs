@@ -0,0 +1,166 @@
+//! Row-level security: the conditions, declared by the module for its tables,
+//! on the rows that a client other than the owner of the database may read.
+
+use std::collections::HashMap;
+
+use spacetimedb_lib::identity::AuthCtx;
+use spacetimedb_lib::{Identity, RowFilter};
+use spacetimedb_sats::AlgebraicValue;
+use spacetimedb_vm::expr::{ColumnOp, CrudExpr, Query, QueryExpr};
+
+use crate::db::datastore::locking_tx_datastore::MutTxId;
+use crate::db::relational_db::RelationalDB;
+use crate::error::{DBError, PlanError};
+use crate::sql::ast::SqlParams;
+use crate::sql::compiler::compile_sql_with_params;
+
+/// The placeholder bound to the `Identity` of the client in a row filter.
+pub const SENDER_PARAM: &str = ":sender";
+
+/// The row filters of the tables of a module, by table name.
+#[derive(Debug, Clone, Default)]
+pub struct RowFilters {
+    filters: HashMap<String, String>,
+}
+
+impl RowFilters {
+    pub fn new(filters: impl IntoIterator<Item = RowFilter>) -> Self {
+        let filters = filters.into_iter().map(|x| (x.table, x.filter)).collect();
+        Self { filters }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    /// Returns whether the filters restrict the rows read by `auth.caller`,
+    /// which they don't for the owner of the database.
+    pub fn applies_to(&self, auth: &AuthCtx) -> bool {
+        !self.filters.is_empty() && auth.owner != auth.caller
+    }
+
+    /// Restricts the rows of every table read by `query`, including the ones it joins,
+    /// to the rows that `auth.caller` may read.
+    ///
+    /// The owner of the database reads every row.
+    pub fn restrict_query(
+        &self,
+        db: &RelationalDB,
+        tx: &MutTxId,
+        auth: &AuthCtx,
+        query: QueryExpr,
+    ) -> Result<QueryExpr, DBError> {
+        if !self.applies_to(auth) {
+            return Ok(query);
+        }
+        Restrict::new(self, db, tx, auth.caller).query(query)
+    }
+
+    /// Restricts the rows read by the statement `expr`, like [`Self::restrict_query`],
+    /// so a client can't update or delete the rows it can't read either.
+    pub fn restrict_crud(
+        &self,
+        db: &RelationalDB,
+        tx: &MutTxId,
+        auth: &AuthCtx,
+        expr: CrudExpr,
+    ) -> Result<CrudExpr, DBError> {
+        Ok(match expr {
+            CrudExpr::Query(query) => CrudExpr::Query(self.restrict_query(db, tx, auth, query)?),
+            CrudExpr::Update { insert, delete } => CrudExpr::Update {
+                insert: self.restrict_query(db, tx, auth, insert)?,
+                delete: self.restrict_query(db, tx, auth, delete)?,
+            },
+            CrudExpr::Delete { query } => CrudExpr::Delete {
+                query: self.restrict_query(db, tx, auth, query)?,
+            },
+            expr => expr,
+        })
+    }
+}
+
+/// The rewriting of the queries of a single caller, compiling each filter at most once.
+struct Restrict<'a> {
+    filters: &'a RowFilters,
+    db: &'a RelationalDB,
+    tx: &'a MutTxId,
+    params: SqlParams,
+    compiled: HashMap<String, Vec<Query>>,
+}
+
+impl<'a> Restrict<'a> {
+    fn new(filters: &'a RowFilters, db: &'a RelationalDB, tx: &'a MutTxId, sender: Identity) -> Self {
+        let sender = AlgebraicValue::product(vec![AlgebraicValue::Bytes(sender.as_bytes().to_vec())]);
+        Self {
+            filters,
+            db,
+            tx,
+            params: [(SENDER_PARAM.to_string(), sender)].into(),
+            compiled: HashMap::new(),
+        }
+    }
+
+    fn query(&mut self, query: QueryExpr) -> Result<QueryExpr, DBError> {
+        let QueryExpr { source, query } = query;
+
+        let mut ops = Vec::with_capacity(query.len());
+        for op in query {
+            ops.push(match op {
+                Query::JoinInner(mut join) => {
+                    join.rhs = self.query(join.rhs)?;
+                    Query::JoinInner(join)
+                }
+                Query::IndexJoin(mut join) => {
+                    join.probe_side = self.query(join.probe_side)?;
+                    Query::IndexJoin(join)
+                }
+                op => op,
+            });
+        }
+
+        // The filter goes right after the ops that read the rows of the source from the database,
+        // and before any projection can drop the columns it uses.
+        if let Some(filter) = self.filter(source.table_name())? {
+            let pos = ops
+                .iter()
+                .position(|op| !matches!(op, Query::IndexScan(_) | Query::IndexJoin(_)))
+                .unwrap_or(ops.len());
+            ops.splice(pos..pos, filter.iter().cloned());
+        }
+
+        Ok(QueryExpr { source, query: ops })
+    }
+
+    /// Compiles the filter of the table `table`, if it has one, into selections over its rows.
+    fn filter(&mut self, table: &str) -> Result<Option<&Vec<Query>>, DBError> {
+        let Some(filter) = self.filters.filters.get(table) else {
+            return Ok(None);
+        };
+        if !self.compiled.contains_key(table) {
+            let sql = format!("SELECT * FROM {table} WHERE {filter}");
+            let compiled = compile_sql_with_params(self.db, self.tx, &sql, &self.params)?;
+            let unsupported = || DBError::Plan {
+                sql: sql.clone(),
+                error: PlanError::Unsupported {
+                    feature: format!("Row filter `{filter}` on table `{table}`, expected a condition on its columns."),
+                },
+            };
+            let [CrudExpr::Query(query)] = <[CrudExpr; 1]>::try_from(compiled).map_err(|_| unsupported())? else {
+                return Err(unsupported());
+            };
+            let mut selections = Vec::with_capacity(query.query.len());
+            for op in query.query {
+                match op {
+                    // The rows are already read by the query being restricted,
+                    // so an index scan of the filter becomes a selection.
+                    Query::IndexScan(scan) => selections.push(Query::Select(ColumnOp::from(scan))),
+                    Query::Select(cmp) => selections.push(Query::Select(cmp)),
+                    Query::Project(cols, _) if cols.is_empty() => {}
+                    _ => return Err(unsupported()),
+                }
+            }
+            self.compiled.insert(table.to_string(), selections);
+        }
+        Ok(self.compiled.get(table))
+    }
+}
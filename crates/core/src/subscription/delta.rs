@@ -0,0 +1,474 @@
+//! Incremental evaluation of the subscription queries joining tables, by delta queries.
+//!
+//! Instead of evaluating a join again over the rows changed by a transaction,
+//! its plan is turned into one delta query per input table:
+//! the rows of the table changed by the transaction are joined with the other tables,
+//! read as they are after the transaction for the tables before it in the join,
+//! and as they were before the transaction for the tables after it.
+//! Together, they yield every combination of rows the transaction added to the join, or removed from it,
+//! at a cost which scales with the size of the changes rather than with the size of the tables.
+//!
+//! A subscription reports the rows of the source table of a join, rather than the combinations,
+//! so the delta queries count the combinations of each of these rows,
+//! which is inserted when it goes from none to some, and deleted when it goes from some to none.
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+
+use nonempty::NonEmpty;
+use spacetimedb_lib::relation::{FieldExpr, FieldName, Header, RelValue};
+use spacetimedb_lib::{DataKey, PrimaryKey};
+use spacetimedb_sats::{AlgebraicValue, ProductValue};
+use spacetimedb_vm::expr::{ColumnOp, Query, QueryExpr, SourceExpr};
+
+use crate::db::datastore::locking_tx_datastore::MutTxId;
+use crate::db::datastore::traits::ColId;
+use crate::db::relational_db::RelationalDB;
+use crate::error::DBError;
+use crate::host::module_host::{DatabaseTableUpdate, DatabaseUpdate, TableOp};
+
+/// A table read by a [`JoinPlan`].
+struct JoinTable {
+    table_id: u32,
+    head: Header,
+    /// The selections on the rows of the table alone.
+    filter: Vec<ColumnOp>,
+}
+
+/// An equi-join condition between the column `.1` of the table `.0` on each side.
+struct JoinCond {
+    lhs: (usize, usize),
+    rhs: (usize, usize),
+}
+
+/// A step of a delta query, joining the rows of `table`
+/// whose column `col` is equal to the column `from.1` of the already joined table `from.0`.
+struct JoinStep {
+    table: usize,
+    col: usize,
+    from: (usize, usize),
+}
+
+/// Whether a table is read as it is after the transaction, or as it was before it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TableState {
+    After,
+    Before,
+}
+
+/// The plan of a subscription query joining tables, in a form the delta queries are derived from:
+/// the rows of the first of its `tables`, the source of the query,
+/// for which there are rows in each of the others meeting the `conds`,
+/// with the combined row matching the `filter`.
+pub struct JoinPlan {
+    tables: Vec<JoinTable>,
+    conds: Vec<JoinCond>,
+    /// The selections on the combined rows.
+    filter: Vec<ColumnOp>,
+    /// The header of the combined rows.
+    head: Header,
+}
+
+impl JoinPlan {
+    /// Derives the join plan of `query`,
+    /// or returns `None` if it doesn't join tables, or in a way the delta queries don't support,
+    /// in which case it must be evaluated again over the changed rows.
+    pub fn compile(query: &QueryExpr) -> Option<Self> {
+        let SourceExpr::DbTable(source) = &query.source else {
+            return None;
+        };
+        let mut plan = JoinPlan {
+            tables: vec![JoinTable {
+                table_id: source.table_id,
+                head: source.head.clone(),
+                filter: Vec::new(),
+            }],
+            conds: Vec::new(),
+            filter: Vec::new(),
+            head: source.head.clone(),
+        };
+
+        // Whether the rows of the query are the rows of its source,
+        // rather than the combined rows of the join.
+        let mut source_rows = true;
+        let mut ops = query.query.iter().peekable();
+        while let Some(op) = ops.next() {
+            match op {
+                Query::IndexScan(scan) if plan.tables.len() == 1 => {
+                    plan.tables[0].filter.push(ColumnOp::from(scan.clone()))
+                }
+                Query::Select(cmp) if plan.tables.len() == 1 => plan.tables[0].filter.push(cmp.clone()),
+                Query::IndexScan(scan) => plan.filter.push(ColumnOp::from(scan.clone())),
+                Query::Select(cmp) => plan.filter.push(cmp.clone()),
+                // The index side of a semijoin is the source of the query.
+                Query::IndexJoin(join) if join.index_table == plan.tables[0].table_id => {
+                    let index_field = plan.tables[0].head.fields.get(join.index_col as usize)?.field.clone();
+                    plan.join(&join.probe_side, &index_field, &join.probe_field)?;
+                }
+                Query::JoinInner(join) => {
+                    plan.join(&join.rhs, &join.col_lhs, &join.col_rhs)?;
+                    source_rows = false;
+                }
+                // Only a projection on the columns of the source, last, yields its rows.
+                Query::Project(cols, _) if ops.peek().is_none() => {
+                    let source = &plan.tables[0].head.fields;
+                    source_rows = cols.len() == source.len()
+                        && cols
+                            .iter()
+                            .zip(source)
+                            .all(|(col, field)| matches!(col, FieldExpr::Name(name) if *name == field.field));
+                }
+                _ => return None,
+            }
+        }
+
+        (plan.tables.len() > 1 && source_rows).then_some(plan)
+    }
+
+    /// Adds the table read by `query` to the join,
+    /// on the condition that its column `col_rhs` is equal to the column `col_lhs` of a table already joined.
+    fn join(&mut self, query: &QueryExpr, col_lhs: &FieldName, col_rhs: &FieldName) -> Option<()> {
+        let SourceExpr::DbTable(table) = &query.source else {
+            return None;
+        };
+        // The columns of the combined rows are named after their tables,
+        // so a table can't be joined with itself.
+        if self.tables.iter().any(|t| t.head.table_name == table.head.table_name) {
+            return None;
+        }
+
+        let mut filter = Vec::new();
+        for op in &query.query {
+            match op {
+                Query::IndexScan(scan) => filter.push(ColumnOp::from(scan.clone())),
+                Query::Select(cmp) => filter.push(cmp.clone()),
+                Query::Project(cols, _) if cols.is_empty() => {}
+                _ => return None,
+            }
+        }
+
+        let rhs = self.tables.len();
+        let (lhs, rhs) = match (self.column(col_lhs), table.head.column_pos(col_rhs)) {
+            (Some(lhs), Some(col)) => (lhs, (rhs, col)),
+            _ => (self.column(col_rhs)?, (rhs, table.head.column_pos(col_lhs)?)),
+        };
+        self.conds.push(JoinCond { lhs, rhs });
+        self.head = self.head.extend(&table.head);
+        self.tables.push(JoinTable {
+            table_id: table.table_id,
+            head: table.head.clone(),
+            filter,
+        });
+        Some(())
+    }
+
+    /// Finds the table, and the position in it, of the column `field` of a table already joined.
+    fn column(&self, field: &FieldName) -> Option<(usize, usize)> {
+        self.tables
+            .iter()
+            .enumerate()
+            .find_map(|(i, t)| Some((i, t.head.column_pos(field)?)))
+    }
+
+    /// Orders the tables to join, starting from the table `start`,
+    /// so each is joined on a condition with a table joined before it.
+    fn steps_from(&self, start: usize) -> Option<Vec<JoinStep>> {
+        let mut joined = vec![false; self.tables.len()];
+        joined[start] = true;
+        let mut steps = Vec::with_capacity(self.tables.len() - 1);
+        while steps.len() + 1 < self.tables.len() {
+            let step = self.conds.iter().find_map(|cond| {
+                let (from, to) = match (joined[cond.lhs.0], joined[cond.rhs.0]) {
+                    (true, false) => (cond.lhs, cond.rhs),
+                    (false, true) => (cond.rhs, cond.lhs),
+                    _ => return None,
+                };
+                Some(JoinStep {
+                    table: to.0,
+                    col: to.1,
+                    from,
+                })
+            })?;
+            joined[step.table] = true;
+            steps.push(step);
+        }
+        Some(steps)
+    }
+
+    /// Incremental evaluation of the join over the rows changed by `database_update`,
+    /// returning the rows of its source it started or stopped matching.
+    #[tracing::instrument(skip_all)]
+    pub fn eval_incr(
+        &self,
+        relational_db: &RelationalDB,
+        tx: &MutTxId,
+        database_update: &DatabaseUpdate,
+    ) -> Result<Option<DatabaseTableUpdate>, DBError> {
+        let deltas = self
+            .tables
+            .iter()
+            .map(|t| Delta::new(database_update.tables.iter().find(|u| u.table_id == t.table_id)))
+            .collect::<Vec<_>>();
+        if deltas.iter().all(Delta::is_empty) {
+            return Ok(None);
+        }
+        let mut eval = Eval {
+            plan: self,
+            db: relational_db,
+            tx,
+            deltas: &deltas,
+            indexed: HashMap::new(),
+            scans: HashMap::new(),
+        };
+
+        // The difference in the number of combinations of each row of the source,
+        // summed over the delta queries of each table.
+        let mut diffs: HashMap<DataKey, (ProductValue, i64)> = HashMap::new();
+        for (k, delta) in deltas.iter().enumerate() {
+            if delta.is_empty() {
+                continue;
+            }
+            let Some(steps) = self.steps_from(k) else {
+                return Ok(None);
+            };
+            let states = (0..self.tables.len())
+                .map(|j| if j < k { TableState::After } else { TableState::Before })
+                .collect::<Vec<_>>();
+            let seeds = delta
+                .inserts
+                .iter()
+                .map(|row| (row, 1))
+                .chain(delta.deletes.iter().map(|row| (row, -1)));
+            for (seed, diff) in seeds {
+                eval.count(k, seed, &steps, &states, &mut |source| {
+                    let (_, count) = diffs
+                        .entry(source.id.unwrap())
+                        .or_insert_with(|| (source.data.clone(), 0));
+                    *count += diff;
+                })?;
+            }
+        }
+
+        // The number of combinations of each row of the source whose number changed, after the transaction.
+        let steps = self.steps_from(0).unwrap_or_default();
+        let states = vec![TableState::After; self.tables.len()];
+        let mut ops = Vec::new();
+        for (id, (row, diff)) in diffs {
+            if diff == 0 {
+                continue;
+            }
+            let mut after = 0;
+            if deltas[0].exists_after(&id) {
+                let seed = RelValue::new(row.clone(), Some(id));
+                eval.count(0, &seed, &steps, &states, &mut |_| after += 1)?;
+            }
+            let before = after - diff;
+            let op_type = match (before, after) {
+                (0, 1..) => 1,
+                (1.., 0) => 0,
+                _ => continue,
+            };
+            let row_pk = PrimaryKey { data_key: id }.to_bytes();
+            ops.push(TableOp { op_type, row_pk, row });
+        }
+
+        Ok((!ops.is_empty()).then(|| DatabaseTableUpdate {
+            table_id: self.tables[0].table_id,
+            table_name: self.tables[0].head.table_name.clone(),
+            ops,
+        }))
+    }
+}
+
+/// The rows of a table changed by a transaction.
+#[derive(Default)]
+struct Delta {
+    inserts: Vec<RelValue>,
+    deletes: Vec<RelValue>,
+    inserted: HashSet<DataKey>,
+    deleted: HashSet<DataKey>,
+}
+
+impl Delta {
+    fn new(update: Option<&DatabaseTableUpdate>) -> Self {
+        let mut delta = Self::default();
+        for op in update.iter().flat_map(|u| &u.ops) {
+            let id = DataKey::decode(&mut &op.row_pk[..]).unwrap();
+            let row = RelValue::new(op.row.clone(), Some(id));
+            if op.op_type == 1 {
+                delta.inserted.insert(id);
+                delta.inserts.push(row);
+            } else {
+                delta.deleted.insert(id);
+                delta.deletes.push(row);
+            }
+        }
+        delta
+    }
+
+    fn is_empty(&self) -> bool {
+        self.inserts.is_empty() && self.deletes.is_empty()
+    }
+
+    /// Returns whether the row `id` of the table exists after the transaction,
+    /// knowing it exists either before or after it.
+    fn exists_after(&self, id: &DataKey) -> bool {
+        !self.deleted.contains(id) || self.inserted.contains(id)
+    }
+}
+
+/// The evaluation of the delta queries of a [`JoinPlan`].
+struct Eval<'a> {
+    plan: &'a JoinPlan,
+    db: &'a RelationalDB,
+    tx: &'a MutTxId,
+    deltas: &'a [Delta],
+    /// Whether the column `.1` of the table `.0` is indexed.
+    indexed: HashMap<(usize, usize), bool>,
+    /// The rows of the tables joined on columns without an index, by the values of these columns.
+    scans: HashMap<(usize, usize), HashMap<AlgebraicValue, Vec<RelValue>>>,
+}
+
+impl Eval<'_> {
+    /// Counts the combinations of the row `seed` of the table `start` with the rows of the other tables,
+    /// in the `states`, calling `f` with the row of the source of each.
+    fn count(
+        &mut self,
+        start: usize,
+        seed: &RelValue,
+        steps: &[JoinStep],
+        states: &[TableState],
+        f: &mut impl FnMut(&RelValue),
+    ) -> Result<(), DBError> {
+        if !self.matches(start, seed)? {
+            return Ok(());
+        }
+        let mut rows = vec![None; self.plan.tables.len()];
+        rows[start] = Some(seed.clone());
+        self.extend(&mut rows, steps, states, f)
+    }
+
+    fn extend(
+        &mut self,
+        rows: &mut Vec<Option<RelValue>>,
+        steps: &[JoinStep],
+        states: &[TableState],
+        f: &mut impl FnMut(&RelValue),
+    ) -> Result<(), DBError> {
+        let Some((step, steps)) = steps.split_first() else {
+            if self.matches_combined(rows)? {
+                f(rows[0].as_ref().unwrap());
+            }
+            return Ok(());
+        };
+
+        let (from, col) = step.from;
+        let value = rows[from].as_ref().unwrap().data.elements[col].clone();
+        for row in self.rows(step.table, step.col, value, states[step.table])? {
+            rows[step.table] = Some(row);
+            self.extend(rows, steps, states, f)?;
+        }
+        rows[step.table] = None;
+        Ok(())
+    }
+
+    /// Returns whether `row` matches the selections of the table `table`.
+    fn matches(&self, table: usize, row: &RelValue) -> Result<bool, DBError> {
+        let table = &self.plan.tables[table];
+        for cmp in &table.filter {
+            if !cmp.compare(row.as_val_ref(), &table.head)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Returns whether the combined `rows` meet the conditions of the join,
+    /// the ones not already met by joining them, and its selections.
+    fn matches_combined(&self, rows: &[Option<RelValue>]) -> Result<bool, DBError> {
+        let value = |(table, col): (usize, usize)| &rows[table].as_ref().unwrap().data.elements[col];
+        if !self.plan.conds.iter().all(|cond| value(cond.lhs) == value(cond.rhs)) {
+            return Ok(false);
+        }
+        if self.plan.filter.is_empty() {
+            return Ok(true);
+        }
+        let elements = rows
+            .iter()
+            .flat_map(|row| row.as_ref().unwrap().data.elements.iter().cloned());
+        let row = RelValue::new(ProductValue::from_iter(elements), None);
+        for cmp in &self.plan.filter {
+            if !cmp.compare(row.as_val_ref(), &self.plan.head)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Returns the rows of the table `table` in the `state`, whose column `col` is equal to `value`,
+    /// matching the selections of the table.
+    fn rows(
+        &mut self,
+        table: usize,
+        col: usize,
+        value: AlgebraicValue,
+        state: TableState,
+    ) -> Result<Vec<RelValue>, DBError> {
+        let mut rows = self.rows_after(table, col, &value)?;
+        if state == TableState::Before {
+            let delta = &self.deltas[table];
+            rows.retain(|row| !delta.inserted.contains(row.id.as_ref().unwrap()));
+            rows.extend(
+                delta
+                    .deletes
+                    .iter()
+                    .filter(|row| row.data.elements[col] == value)
+                    .cloned(),
+            );
+        }
+        let mut matching = Vec::with_capacity(rows.len());
+        for row in rows {
+            if self.matches(table, &row)? {
+                matching.push(row);
+            }
+        }
+        Ok(matching)
+    }
+
+    /// Returns the rows of the table `table` after the transaction, whose column `col` is equal to `value`,
+    /// seeking its index on the column or, without one, scanning the table once for all the values.
+    fn rows_after(&mut self, table: usize, col: usize, value: &AlgebraicValue) -> Result<Vec<RelValue>, DBError> {
+        let table_id = self.plan.tables[table].table_id;
+        let indexed = match self.indexed.get(&(table, col)) {
+            Some(indexed) => *indexed,
+            None => {
+                let schema = self.db.schema_for_table(self.tx, table_id)?;
+                let indexed = schema.indexes.iter().any(|i| i.cols == NonEmpty::new(col as u32));
+                self.indexed.insert((table, col), indexed);
+                indexed
+            }
+        };
+
+        if indexed {
+            let rows = self
+                .db
+                .iter_by_col_eq(self.tx, table_id, ColId(col as u32), value.clone())?
+                .map(RelValue::from)
+                .collect();
+            return Ok(rows);
+        }
+
+        let scan = match self.scans.entry((table, col)) {
+            Entry::Occupied(scan) => scan.into_mut(),
+            Entry::Vacant(entry) => {
+                let mut scan: HashMap<_, Vec<_>> = HashMap::new();
+                for row in self.db.iter(self.tx, table_id)? {
+                    let row = RelValue::from(row);
+                    scan.entry(row.data.elements[col].clone()).or_default().push(row);
+                }
+                entry.insert(scan)
+            }
+        };
+        Ok(scan.get(value).cloned().unwrap_or_default())
+    }
+}
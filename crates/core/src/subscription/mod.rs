@@ -1,4 +1,6 @@
+pub mod delta;
 pub mod module_subscription_actor;
 pub mod query;
 #[allow(clippy::module_inception)] // it's right this isn't ideal :/
 pub mod subscription;
+pub mod template;
@@ -1,15 +1,20 @@
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
 
 use super::{
-    query::compile_read_only_query,
+    query::{compile_subscription_query, Query},
     subscription::{QuerySet, Subscription},
+    template::{BoundTemplate, QueryTemplate},
 };
 use crate::db::datastore::locking_tx_datastore::MutTxId;
-use crate::host::module_host::{EventStatus, ModuleEvent};
-use crate::protobuf::client_api::Subscribe;
+use crate::error::SubscriptionError;
+use crate::host::module_host::{DatabaseTableUpdate, DatabaseUpdate, EventStatus, ModuleEvent, TableOp};
+use crate::host::Timestamp;
+use crate::protobuf::client_api::{ResumeFrom, Subscribe, SubscribeAdd, Unsubscribe};
+use crate::sql::row_filter::RowFilters;
 use crate::{
     client::{
-        messages::{CachedMessage, SubscriptionUpdateMessage, TransactionUpdateMessage},
+        messages::{CachedMessage, QueryUpdateMessage, SubscriptionUpdateMessage, TransactionUpdateMessage},
         ClientActorId, ClientConnectionSender,
     },
     host::NoSuchModule,
@@ -29,12 +34,23 @@ enum ModuleSubscriptionCommand {
     RemoveSubscriber {
         client_id: ClientActorId,
     },
+    AddQuery {
+        sender: ClientConnectionSender,
+        subscribe_add: SubscribeAdd,
+    },
+    RemoveQuery {
+        sender: ClientConnectionSender,
+        unsubscribe: Unsubscribe,
+    },
 }
 
 #[derive(Debug)]
 enum Command {
     Subscription(ModuleSubscriptionCommand),
-    BroadcastCommitEvent { event: ModuleEvent },
+    BroadcastCommitEvent {
+        event: ModuleEvent,
+        caller: Option<ClientConnectionSender>,
+    },
 }
 
 #[derive(Clone, Debug)]
@@ -44,19 +60,23 @@ pub struct ModuleSubscriptionManager {
 
 #[derive(Clone)]
 pub struct SubscriptionEventSender {
-    commit_event_tx: mpsc::UnboundedSender<ModuleEvent>,
+    commit_event_tx: mpsc::UnboundedSender<(ModuleEvent, Option<ClientConnectionSender>)>,
 }
 
 impl ModuleSubscriptionManager {
-    pub fn spawn(relational_db: Arc<RelationalDB>, owner_identity: Identity) -> (Self, SubscriptionEventSender) {
+    pub fn spawn(
+        relational_db: Arc<RelationalDB>,
+        owner_identity: Identity,
+        row_filters: Arc<RowFilters>,
+    ) -> (Self, SubscriptionEventSender) {
         let (tx, mut rx) = mpsc::unbounded_channel();
         let (commit_event_tx, mut commit_event_rx) = mpsc::unbounded_channel();
         tokio::spawn(async move {
-            let mut actor = ModuleSubscriptionActor::new(relational_db, owner_identity);
+            let mut actor = ModuleSubscriptionActor::new(relational_db, owner_identity, row_filters);
             loop {
                 let command = tokio::select! {
                     event = commit_event_rx.recv() => match event {
-                        Some(event) => Command::BroadcastCommitEvent { event },
+                        Some((event, caller)) => Command::BroadcastCommitEvent { event, caller },
                         // the module has exited
                         None => break,
                     },
@@ -81,26 +101,42 @@ impl ModuleSubscriptionManager {
             .send(ModuleSubscriptionCommand::RemoveSubscriber { client_id })
             .map_err(|_| NoSuchModule)
     }
+
+    pub fn add_query(&self, sender: ClientConnectionSender, subscribe_add: SubscribeAdd) -> Result<(), NoSuchModule> {
+        self.tx
+            .send(ModuleSubscriptionCommand::AddQuery { sender, subscribe_add })
+            .map_err(|_| NoSuchModule)
+    }
+
+    pub fn remove_query(&self, sender: ClientConnectionSender, unsubscribe: Unsubscribe) -> Result<(), NoSuchModule> {
+        self.tx
+            .send(ModuleSubscriptionCommand::RemoveQuery { sender, unsubscribe })
+            .map_err(|_| NoSuchModule)
+    }
 }
 
 impl SubscriptionEventSender {
     pub async fn broadcast_event(&self, client: Option<&ClientConnectionSender>, mut event: ModuleEvent) {
         match event.status {
             EventStatus::Committed(_) => {
-                self.commit_event_tx.send(event).expect("subscription actor panicked");
+                // The caller is only told about the commit if it's waiting for it.
+                let caller = client.filter(|_| event.request_id.is_some()).cloned();
+                self.commit_event_tx
+                    .send((event, caller))
+                    .expect("subscription actor panicked");
             }
-            EventStatus::Failed(_) => {
+            EventStatus::Failed(_) | EventStatus::Unauthorized(_) | EventStatus::OutOfEnergy => {
                 if let Some(client) = client {
                     let message = TransactionUpdateMessage {
                         event: &mut event,
                         database_update: Default::default(),
+                        tx_offset: 0,
                     };
                     let _ = client.send_message(message).await;
                 } else {
                     log::trace!("Reducer failed but there is no client to send the failure to!")
                 }
             }
-            EventStatus::OutOfEnergy => {} // ?
         }
     }
 
@@ -109,18 +145,46 @@ impl SubscriptionEventSender {
     }
 }
 
+/// The number of changed rows of the latest committed transactions a `ModuleSubscriptionActor` keeps,
+/// to send a client resuming its subscription only the changes since the transaction it holds.
+///
+/// Each transaction also counts as one row, so that a run of empty transactions is bounded too.
+const HISTORY_ROWS: usize = 64 * 1024;
+
 struct ModuleSubscriptionActor {
     relational_db: Arc<RelationalDB>,
     subscriptions: Vec<Subscription>,
+    /// The query templates bound by the subscriptions.
+    templates: Vec<Arc<QueryTemplate>>,
+    /// The queries added to the subscription of each client by `SubscribeAdd`, with their ids.
+    added_queries: HashMap<ClientActorId, Vec<(u32, Query)>>,
     owner_identity: Identity,
+    row_filters: Arc<RowFilters>,
+    /// The changes of the last transactions broadcast, with their offsets,
+    /// up to `HISTORY_ROWS` rows in total.
+    history: VecDeque<(u64, DatabaseUpdate)>,
+    /// The number of rows in `history`, as counted by `history_rows`.
+    history_len: usize,
+    /// The offset of the last transaction broadcast, counting from 1.
+    tx_offset: u64,
+    /// Identifies the numbering of `tx_offset`, which restarts along with the actor,
+    /// by the time the actor started, in microseconds.
+    history_id: u64,
 }
 
 impl ModuleSubscriptionActor {
-    fn new(relational_db: Arc<RelationalDB>, owner_identity: Identity) -> Self {
+    fn new(relational_db: Arc<RelationalDB>, owner_identity: Identity, row_filters: Arc<RowFilters>) -> Self {
         Self {
             relational_db,
             subscriptions: Vec::new(),
+            templates: Vec::new(),
+            added_queries: HashMap::new(),
             owner_identity,
+            row_filters,
+            history: VecDeque::new(),
+            history_len: 0,
+            tx_offset: 0,
+            history_id: Timestamp::now().0,
         }
     }
 
@@ -132,7 +196,19 @@ impl ModuleSubscriptionActor {
             Command::Subscription(ModuleSubscriptionCommand::RemoveSubscriber { client_id }) => {
                 self.remove_subscriber(client_id)
             }
-            Command::BroadcastCommitEvent { event } => self.broadcast_commit_event(event).await?,
+            Command::Subscription(ModuleSubscriptionCommand::AddQuery { sender, subscribe_add }) => {
+                let mut tx = self.relational_db.begin_read_tx();
+                let result = self.add_query(sender, subscribe_add, &mut tx).await;
+                self.relational_db.release_tx(tx);
+                result?
+            }
+            Command::Subscription(ModuleSubscriptionCommand::RemoveQuery { sender, unsubscribe }) => {
+                let mut tx = self.relational_db.begin_read_tx();
+                let result = self.remove_query(sender, unsubscribe, &mut tx).await;
+                self.relational_db.release_tx(tx);
+                result?
+            }
+            Command::BroadcastCommitEvent { event, caller } => self.broadcast_commit_event(event, caller).await?,
         }
         Ok(())
     }
@@ -146,94 +222,599 @@ impl ModuleSubscriptionActor {
         self.remove_subscriber(sender.id);
         let auth = AuthCtx::new(self.owner_identity, sender.id.identity);
 
-        let queries: QuerySet = subscription
+        let mut queries: QuerySet = subscription
             .query_strings
             .into_iter()
-            .map(|query| compile_read_only_query(&self.relational_db, tx, &auth, &query))
+            .map(|query| compile_subscription_query(&self.relational_db, tx, &auth, &self.row_filters, &query))
             .collect::<Result<_, _>>()?;
 
-        let sub = match self.subscriptions.iter_mut().find(|s| s.queries == queries) {
-            Some(sub) => {
+        // The added queries are subscribed to like the others,
+        // but are remembered with their ids so they can be removed.
+        let mut added = Vec::with_capacity(subscription.added_queries.len());
+        for SubscribeAdd { query_id, query_string } in subscription.added_queries {
+            if added.iter().any(|(id, _)| *id == query_id) {
+                return Err(SubscriptionError::DuplicateQueryId(query_id).into());
+            }
+            let query = compile_subscription_query(&self.relational_db, tx, &auth, &self.row_filters, &query_string)?;
+            queries.0.push(query.clone());
+            added.push((query_id, query));
+        }
+
+        let mut templates = Vec::with_capacity(subscription.parameterized_queries.len());
+        let mut bound_queries = Vec::with_capacity(subscription.parameterized_queries.len());
+        for query in subscription.parameterized_queries {
+            let template = self.template(tx, &auth, &query.template)?;
+            let values = template.bind(&query.params)?;
+            bound_queries.push(template.compile_bound(&self.relational_db, tx, &auth, &self.row_filters, &values)?);
+            templates.push(BoundTemplate { template, values });
+        }
+        templates.sort();
+        templates.dedup();
+
+        // The initial rows of the parameterized queries are read with their parameters bound,
+        // which lets them use the indexes of the columns compared with the parameters.
+        let initial = QuerySet(queries.0.iter().cloned().chain(bound_queries).collect());
+        // A client resuming from a transaction still in the history is only sent the changes since,
+        // which the queries select like those of a single transaction.
+        let changes = subscription
+            .resume_from
+            .and_then(|resume_from| self.changes_since(&resume_from));
+        let resumed = changes.is_some();
+        let database_update = match changes {
+            Some(changes) => initial.eval_incr(&self.relational_db, tx, &changes, auth)?,
+            None => initial.eval(&self.relational_db, tx, auth)?,
+        };
+        let (tx_offset, history_id) = (self.tx_offset, self.history_id);
+
+        if !added.is_empty() {
+            self.added_queries.insert(sender.id, added);
+        }
+        let sender = self.join_subscription(sender, queries, templates);
+
+        // NOTE: It is important to send the state in this thread because if you spawn a new
+        // thread it's possible for messages to get sent to the client out of order. If you do
+        // spawn in another thread messages will need to be buffered until the state is sent out
+        // on the wire
+        let _ = sender
+            .send_message(SubscriptionUpdateMessage {
+                database_update,
+                tx_offset,
+                history_id,
+                resumed,
+            })
+            .await;
+
+        Ok(())
+    }
+
+    async fn add_subscription(
+        &mut self,
+        sender: ClientConnectionSender,
+        subscription: Subscribe,
+    ) -> Result<(), DBError> {
+        //Split logic to properly handle `Error` + `Tx`
+        let mut tx = self.relational_db.begin_read_tx();
+        let result = self._add_subscription(sender, subscription, &mut tx).await;
+        self.relational_db.release_tx(tx);
+        result
+    }
+
+    /// Adds `sender` to the subscription to `queries` and `templates`,
+    /// which is created if no other client is subscribed to them.
+    fn join_subscription(
+        &mut self,
+        sender: ClientConnectionSender,
+        queries: QuerySet,
+        templates: Vec<BoundTemplate>,
+    ) -> &ClientConnectionSender {
+        let sub = match self
+            .subscriptions
+            .iter()
+            .position(|s| s.queries == queries && s.templates == templates)
+        {
+            Some(i) => {
+                let sub = &mut self.subscriptions[i];
                 sub.subscribers.push(sender);
                 sub
             }
             None => {
                 self.subscriptions.push(Subscription {
                     queries,
+                    templates,
                     subscribers: vec![sender],
                 });
                 self.subscriptions.last_mut().unwrap()
             }
         };
+        sub.subscribers.last().unwrap()
+    }
 
-        let database_update = sub.queries.eval(&self.relational_db, tx, auth)?;
+    /// Returns the queries and the bound templates the client `client_id` is subscribed to.
+    fn queries_of(&self, client_id: ClientActorId) -> (QuerySet, Vec<BoundTemplate>) {
+        match self
+            .subscriptions
+            .iter()
+            .find(|sub| sub.subscribers.iter().any(|s| s.id == client_id))
+        {
+            Some(sub) => (QuerySet(sub.queries.0.clone()), sub.templates.clone()),
+            None => (QuerySet(Vec::new()), Vec::new()),
+        }
+    }
+
+    /// Moves the client `sender` to the subscription to `queries` and `templates`,
+    /// keeping the queries it added.
+    fn move_subscriber(&mut self, sender: ClientConnectionSender, queries: QuerySet, templates: Vec<BoundTemplate>) {
+        self.subscriptions.retain_mut(|sub| {
+            sub.remove_subscriber(sender.id);
+            !sub.subscribers.is_empty()
+        });
+        self.join_subscription(sender, queries, templates);
+    }
 
-        let sender = sub.subscribers.last().unwrap();
+    /// Returns the rows of `database_update` matched by any of `queries` and `templates`.
+    fn matching_rows(
+        &self,
+        tx: &mut MutTxId,
+        auth: AuthCtx,
+        queries: &QuerySet,
+        templates: &[BoundTemplate],
+        database_update: &DatabaseUpdate,
+    ) -> Result<HashSet<(u32, Vec<u8>)>, DBError> {
+        let mut all = QuerySet(queries.0.clone());
+        for bound in templates {
+            let query =
+                bound
+                    .template
+                    .compile_bound(&self.relational_db, tx, &auth, &self.row_filters, &bound.values)?;
+            all.0.push(query);
+        }
+        all.matching_rows(&self.relational_db, tx, database_update, auth)
+    }
 
-        // NOTE: It is important to send the state in this thread because if you spawn a new
-        // thread it's possible for messages to get sent to the client out of order. If you do
-        // spawn in another thread messages will need to be buffered until the state is sent out
-        // on the wire
-        let _ = sender.send_message(SubscriptionUpdateMessage { database_update }).await;
+    /// Adds a query to the subscription of the client `sender`,
+    /// sending it the rows of the query which none of its other queries match.
+    async fn add_query(
+        &mut self,
+        sender: ClientConnectionSender,
+        subscribe_add: SubscribeAdd,
+        tx: &mut MutTxId,
+    ) -> Result<(), DBError> {
+        let SubscribeAdd { query_id, query_string } = subscribe_add;
+        let added = self.added_queries.get(&sender.id);
+        if added.is_some_and(|added| added.iter().any(|(id, _)| *id == query_id)) {
+            return Err(SubscriptionError::DuplicateQueryId(query_id).into());
+        }
+        let auth = AuthCtx::new(self.owner_identity, sender.id.identity);
+        let query = compile_subscription_query(&self.relational_db, tx, &auth, &self.row_filters, &query_string)?;
+
+        // The other queries are only evaluated over the rows of the new one.
+        let (mut queries, templates) = self.queries_of(sender.id);
+        let mut database_update = QuerySet(vec![query.clone()]).eval(&self.relational_db, tx, auth)?;
+        let matching = self.matching_rows(tx, auth, &queries, &templates, &database_update)?;
+        retain_rows(&mut database_update, |table_id, row_pk| {
+            !matching.contains(&(table_id, row_pk.to_vec()))
+        });
+
+        queries.0.push(query.clone());
+        self.added_queries.entry(sender.id).or_default().push((query_id, query));
+        self.move_subscriber(sender.clone(), queries, templates);
+
+        let _ = sender
+            .send_message(QueryUpdateMessage {
+                query_id,
+                database_update,
+            })
+            .await;
 
         Ok(())
     }
 
-    async fn add_subscription(
+    /// Removes a query added by `SubscribeAdd` from the subscription of the client `sender`,
+    /// sending it a delete for each row of the query which none of its remaining queries match.
+    async fn remove_query(
         &mut self,
         sender: ClientConnectionSender,
-        subscription: Subscribe,
+        unsubscribe: Unsubscribe,
+        tx: &mut MutTxId,
     ) -> Result<(), DBError> {
-        //Split logic to properly handle `Error` + `Tx`
-        let mut tx = self.relational_db.begin_tx();
-        let result = self._add_subscription(sender, subscription, &mut tx).await;
-        self.relational_db.finish_tx(tx, result)
+        let Unsubscribe { query_id } = unsubscribe;
+        let Some(pos) = self
+            .added_queries
+            .get(&sender.id)
+            .and_then(|added| added.iter().position(|(id, _)| *id == query_id))
+        else {
+            return Err(SubscriptionError::UnknownQueryId(query_id).into());
+        };
+        let auth = AuthCtx::new(self.owner_identity, sender.id.identity);
+        let query = self.added_queries[&sender.id][pos].1.clone();
+
+        let (mut queries, templates) = self.queries_of(sender.id);
+        if let Some(i) = queries.0.iter().position(|q| *q == query) {
+            queries.0.remove(i);
+        }
+        let mut database_update = QuerySet(vec![query]).eval(&self.relational_db, tx, auth)?;
+        let matching = self.matching_rows(tx, auth, &queries, &templates, &database_update)?;
+        retain_rows(&mut database_update, |table_id, row_pk| {
+            !matching.contains(&(table_id, row_pk.to_vec()))
+        });
+        for op in database_update.tables.iter_mut().flat_map(|t| &mut t.ops) {
+            op.op_type = 0; // Delete
+        }
+
+        if let Some(added) = self.added_queries.get_mut(&sender.id) {
+            added.remove(pos);
+        }
+        self.move_subscriber(sender.clone(), queries, templates);
+
+        let _ = sender
+            .send_message(QueryUpdateMessage {
+                query_id,
+                database_update,
+            })
+            .await;
+
+        Ok(())
+    }
+
+    /// Returns the template `text` compiled for the client `auth.caller`,
+    /// sharing it with the other subscriptions to it.
+    fn template(&mut self, tx: &MutTxId, auth: &AuthCtx, text: &str) -> Result<Arc<QueryTemplate>, DBError> {
+        if let Some(template) = self
+            .templates
+            .iter()
+            .find(|t| t.is_shared_by(text, auth, &self.row_filters))
+        {
+            return Ok(template.clone());
+        }
+        let template = QueryTemplate::compile(&self.relational_db, tx, auth, &self.row_filters, text)?;
+        let template = Arc::new(template);
+        self.templates.push(template.clone());
+        Ok(template)
+    }
+
+    /// Returns the net changes of the transactions broadcast after `resume_from`,
+    /// or `None` if it's from another history or they aren't all in the history anymore.
+    fn changes_since(&self, resume_from: &ResumeFrom) -> Option<DatabaseUpdate> {
+        let oldest = self.history.front().map_or(self.tx_offset + 1, |(offset, _)| *offset);
+        if resume_from.history_id != self.history_id
+            || resume_from.tx_offset > self.tx_offset
+            || resume_from.tx_offset + 1 < oldest
+        {
+            return None;
+        }
+        let updates = self
+            .history
+            .iter()
+            .filter(|(offset, _)| *offset > resume_from.tx_offset)
+            .map(|(_, update)| update);
+        Some(net_changes(updates))
+    }
+
+    /// Appends the changes of the transaction `tx_offset` to the history,
+    /// dropping the oldest ones to keep it within `HISTORY_ROWS`.
+    fn record_history(&mut self, tx_offset: u64, database_update: &DatabaseUpdate) {
+        let rows = history_rows(database_update);
+        if rows > HISTORY_ROWS {
+            // The transaction can't be kept, so no client can resume from before it.
+            self.history.clear();
+            self.history_len = 0;
+            return;
+        }
+        while self.history_len + rows > HISTORY_ROWS {
+            let Some((_, oldest)) = self.history.pop_front() else {
+                break;
+            };
+            self.history_len -= history_rows(&oldest);
+        }
+        self.history.push_back((tx_offset, database_update.clone()));
+        self.history_len += rows;
     }
 
     fn remove_subscriber(&mut self, client_id: ClientActorId) {
         self.subscriptions.retain_mut(|sub| {
             sub.remove_subscriber(client_id);
             !sub.subscribers.is_empty()
-        })
+        });
+        self.added_queries.remove(&client_id);
+        // Drop the templates no subscription binds anymore.
+        self.templates.retain(|template| Arc::strong_count(template) > 1);
     }
 
-    async fn _broadcast_commit_event(&mut self, mut event: ModuleEvent, tx: &mut MutTxId) -> Result<(), DBError> {
+    async fn _broadcast_commit_event(
+        &mut self,
+        mut event: ModuleEvent,
+        caller: Option<ClientConnectionSender>,
+        tx: &mut MutTxId,
+    ) -> Result<(), DBError> {
         let futures = FuturesUnordered::new();
         let auth = AuthCtx::new(self.owner_identity, event.caller_identity);
+        // Only the caller is sent the id of its call,
+        // along with its subscribed rows which the call updated, if any.
+        let request_id = event.request_id.take();
+        let mut caller_update = None;
 
-        for subscription in &mut self.subscriptions {
-            let database_update = event.status.database_update().unwrap();
-            let incr = subscription
-                .queries
-                .eval_incr(&self.relational_db, tx, database_update, auth)?;
+        self.tx_offset += 1;
+        let tx_offset = self.tx_offset;
+        self.record_history(tx_offset, event.status.database_update().unwrap());
 
-            if incr.tables.is_empty() {
-                continue;
+        // The raw pointers keying the rows of the templates aren't `Send`,
+        // so they must be out of scope before awaiting.
+        {
+            // Each template is evaluated once, for all the subscriptions binding its parameters,
+            // which then look up the rows matching the values they bound.
+            let mut template_incrs = HashMap::with_capacity(self.templates.len());
+            for template in &self.templates {
+                let database_update = event.status.database_update().unwrap();
+                let incr = template.eval_incr(&self.relational_db, tx, database_update, auth)?;
+                template_incrs.insert(Arc::as_ptr(template), incr);
             }
 
-            let message = TransactionUpdateMessage {
-                event: &mut event,
-                database_update: incr,
-            };
-            let mut message = CachedMessage::new(message);
+            for subscription in &mut self.subscriptions {
+                let database_update = event.status.database_update().unwrap();
+                let mut incr = subscription
+                    .queries
+                    .eval_incr(&self.relational_db, tx, database_update, auth)?;
+                for bound in &subscription.templates {
+                    if let Some(update) = template_incrs
+                        .get(&Arc::as_ptr(&bound.template))
+                        .and_then(|incr| incr.get(&bound.values))
+                    {
+                        incr.merge(update);
+                    }
+                }
+
+                let is_caller = |subscriber: &ClientConnectionSender| {
+                    caller.as_ref().is_some_and(|caller| caller.id == subscriber.id)
+                };
+                if subscription.subscribers.iter().any(is_caller) {
+                    caller_update = Some(incr.clone());
+                }
+
+                if incr.tables.is_empty() {
+                    continue;
+                }
 
-            for subscriber in &subscription.subscribers {
-                // rustc realllly doesn't like subscriber.send_message(message) here for weird
-                // lifetime reasons, even though it would be sound
-                let message = message.serialize(subscriber.protocol);
-                futures.push(subscriber.send(message).map(drop))
+                let message = TransactionUpdateMessage {
+                    event: &mut event,
+                    database_update: incr,
+                    tx_offset,
+                };
+                let mut message = CachedMessage::new(message);
+
+                for subscriber in subscription
+                    .subscribers
+                    .iter()
+                    .filter(|subscriber| !is_caller(subscriber))
+                {
+                    // rustc realllly doesn't like subscriber.send_message(message) here for weird
+                    // lifetime reasons, even though it would be sound
+                    let message = message.serialize(subscriber.protocol);
+                    futures.push(subscriber.send(message).map(drop))
+                }
             }
         }
 
         futures.collect::<()>().await;
 
+        if let Some(caller) = caller {
+            event.request_id = request_id;
+            let message = TransactionUpdateMessage {
+                event: &mut event,
+                database_update: caller_update.unwrap_or_default(),
+                tx_offset,
+            };
+            let _ = caller.send_message(message).await;
+        }
+
         Ok(())
     }
 
-    async fn broadcast_commit_event(&mut self, event: ModuleEvent) -> Result<(), DBError> {
+    async fn broadcast_commit_event(
+        &mut self,
+        event: ModuleEvent,
+        caller: Option<ClientConnectionSender>,
+    ) -> Result<(), DBError> {
         //Split logic to properly handle `Error` + `Tx`
-        let mut tx = self.relational_db.begin_tx();
-        let result = self._broadcast_commit_event(event, &mut tx).await;
-        self.relational_db.finish_tx(tx, result)
+        let mut tx = self.relational_db.begin_read_tx();
+        let result = self._broadcast_commit_event(event, caller, &mut tx).await;
+        self.relational_db.release_tx(tx);
+        result
+    }
+}
+
+/// Keeps the rows of `database_update` for which `f` returns `true`, given their table id and primary key.
+fn retain_rows(database_update: &mut DatabaseUpdate, mut f: impl FnMut(u32, &[u8]) -> bool) {
+    for table in &mut database_update.tables {
+        let table_id = table.table_id;
+        table.ops.retain(|op| f(table_id, &op.row_pk));
+    }
+    database_update.tables.retain(|table| !table.ops.is_empty());
+}
+
+/// Returns the net changes of the `updates` applied in order,
+/// in which a row inserted then deleted, or deleted then inserted, is left out.
+fn net_changes<'a>(updates: impl Iterator<Item = &'a DatabaseUpdate>) -> DatabaseUpdate {
+    // For each table, the net number of inserts of each row, keyed on its primary key.
+    type NetRows = HashMap<Vec<u8>, (i32, TableOp)>;
+    let mut tables: Vec<(DatabaseTableUpdate, NetRows)> = Vec::new();
+    for update in updates {
+        for table in &update.tables {
+            let i = match tables.iter().position(|(t, _)| t.table_id == table.table_id) {
+                Some(i) => i,
+                None => {
+                    let empty = DatabaseTableUpdate {
+                        table_id: table.table_id,
+                        table_name: table.table_name.clone(),
+                        ops: Vec::new(),
+                    };
+                    tables.push((empty, HashMap::new()));
+                    tables.len() - 1
+                }
+            };
+            for op in &table.ops {
+                let count = if op.op_type == 1 { 1 } else { -1 };
+                let entry = tables[i].1.entry(op.row_pk.clone()).or_insert((0, op.clone()));
+                entry.0 += count;
+            }
+        }
+    }
+    let tables = tables
+        .into_iter()
+        .map(|(mut table, rows)| {
+            table.ops = rows
+                .into_values()
+                .filter(|(count, _)| *count != 0)
+                .map(|(count, op)| TableOp {
+                    op_type: u8::from(count > 0),
+                    ..op
+                })
+                .collect();
+            table
+        })
+        .filter(|table| !table.ops.is_empty())
+        .collect();
+    DatabaseUpdate { tables }
+}
+
+/// The number of rows `database_update` takes in the history of a `ModuleSubscriptionActor`.
+fn history_rows(database_update: &DatabaseUpdate) -> usize {
+    1 + database_update.tables.iter().map(|table| table.ops.len()).sum::<usize>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{ClientName, DataMessage, Protocol};
+    use crate::host::module_host::ModuleFunctionCall;
+    use crate::host::{ArgsTuple, EnergyDiff};
+    use crate::protobuf::client_api::{event, message, Message};
+    use prost::Message as _;
+    use spacetimedb_lib::Address;
+    use std::time::Duration;
+    use crate::db::relational_db::tests_utils::make_test_db;
+    use spacetimedb_lib::data_key::ToDataKey;
+    use spacetimedb_lib::error::ResultTest;
+    use spacetimedb_sats::product;
+
+    fn update(ops: &[(u8, u64)]) -> DatabaseUpdate {
+        let ops = ops
+            .iter()
+            .map(|&(op_type, value)| {
+                let row = product!(value);
+                TableOp {
+                    op_type,
+                    row_pk: row.to_data_key().to_bytes(),
+                    row,
+                }
+            })
+            .collect();
+        DatabaseUpdate {
+            tables: vec![DatabaseTableUpdate {
+                table_id: 4096,
+                table_name: "t".into(),
+                ops,
+            }],
+        }
+    }
+
+    fn ops(update: &DatabaseUpdate) -> Vec<(u8, String)> {
+        let mut ops = update
+            .tables
+            .iter()
+            .flat_map(|t| &t.ops)
+            .map(|op| (op.op_type, format!("{:?}", op.row)))
+            .collect::<Vec<_>>();
+        ops.sort();
+        ops
+    }
+
+    #[tokio::test]
+    async fn out_of_energy_is_sent_to_the_caller() {
+        let id = ClientActorId {
+            identity: Identity::__dummy(),
+            address: Address::ZERO,
+            name: ClientName(0),
+        };
+        let (client, mut rx) = ClientConnectionSender::dummy_with_channel(id, Protocol::Binary);
+        let (commit_event_tx, _) = mpsc::unbounded_channel();
+        let sender = SubscriptionEventSender { commit_event_tx };
+        let event = ModuleEvent {
+            timestamp: Timestamp::now(),
+            caller_identity: id.identity,
+            caller_address: None,
+            request_id: Some(7),
+            function_call: ModuleFunctionCall {
+                reducer: "spend".into(),
+                args: ArgsTuple::default(),
+            },
+            status: EventStatus::OutOfEnergy,
+            energy_quanta_used: EnergyDiff(0),
+            host_execution_duration: Duration::ZERO,
+        };
+        sender.broadcast_event(Some(&client), event).await;
+
+        let Some(DataMessage::Binary(bytes)) = rx.recv().await else {
+            panic!("no binary message sent to the caller");
+        };
+        let Some(message::Type::TransactionUpdate(update)) = Message::decode(&bytes[..]).unwrap().r#type else {
+            panic!("not a transaction update");
+        };
+        assert_eq!(update.request_id, 7);
+        assert_eq!(update.event.unwrap().status, event::Status::OutOfEnergy as i32);
+    }
+
+    #[test]
+    fn net_changes_leave_out_rows_changed_back() {
+        let updates = [
+            update(&[(1, 1), (1, 2), (0, 3)]),
+            update(&[(0, 1), (1, 3)]),
+            update(&[(1, 4), (0, 5)]),
+        ];
+        let net = net_changes(updates.iter());
+        let expected = update(&[(1, 2), (1, 4), (0, 5)]);
+        assert_eq!(ops(&net), ops(&expected));
+
+        let net = net_changes([update(&[(1, 1)]), update(&[(0, 1)])].iter());
+        assert!(net.tables.is_empty());
+    }
+
+    #[test]
+    fn changes_since_requires_the_history_to_cover_the_offset() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        let mut actor = ModuleSubscriptionActor::new(Arc::new(db), Identity::__dummy(), Default::default());
+        for (offset, value) in (1..=3).zip(10..) {
+            actor.tx_offset = offset;
+            actor.record_history(offset, &update(&[(1, value)]));
+        }
+        let history_id = actor.history_id;
+        let resume = |tx_offset| ResumeFrom { history_id, tx_offset };
+
+        let changes = actor.changes_since(&resume(1)).unwrap();
+        assert_eq!(ops(&changes), ops(&update(&[(1, 11), (1, 12)])));
+        assert!(actor.changes_since(&resume(3)).unwrap().tables.is_empty());
+        assert_eq!(ops(&actor.changes_since(&resume(0)).unwrap()).len(), 3);
+        // An offset from the future, or from another history, can't be resumed from.
+        assert!(actor.changes_since(&resume(4)).is_none());
+        let other_history = ResumeFrom {
+            history_id: history_id.wrapping_add(1),
+            tx_offset: 3,
+        };
+        assert!(actor.changes_since(&other_history).is_none());
+
+        // Once the oldest transactions are dropped from the history, neither can an offset before them.
+        // The three transactions so far take two rows each, so this drops only the first.
+        let rows = (0..(HISTORY_ROWS - 6) as u64).map(|value| (1, value)).collect::<Vec<_>>();
+        actor.tx_offset = 4;
+        actor.record_history(4, &update(&rows));
+        assert!(actor.changes_since(&resume(0)).is_none());
+        assert!(actor.changes_since(&resume(1)).is_some());
+
+        // A transaction too large to keep leaves only the offset after it to resume from.
+        let rows = (0..HISTORY_ROWS as u64).map(|value| (1, value)).collect::<Vec<_>>();
+        actor.tx_offset = 5;
+        actor.record_history(5, &update(&rows));
+        assert!(actor.changes_since(&resume(4)).is_none());
+        assert!(actor.changes_since(&resume(5)).unwrap().tables.is_empty());
+        Ok(())
     }
 }
@@ -1,15 +1,18 @@
 use crate::db::datastore::locking_tx_datastore::MutTxId;
 use crate::db::relational_db::RelationalDB;
 use crate::error::{DBError, SubscriptionError};
-use crate::host::module_host::DatabaseTableUpdate;
-use crate::sql::compiler::compile_sql;
+use crate::host::module_host::{DatabaseTableUpdate, DatabaseUpdate, TableOp};
+use crate::sql::ast::SqlParams;
+use crate::sql::compiler::compile_sql_with_params;
 use crate::sql::execute::execute_single_sql;
-use crate::subscription::subscription::QuerySet;
+use crate::sql::row_filter::RowFilters;
+use crate::subscription::delta::JoinPlan;
+use crate::subscription::subscription::{pk_for_row, QuerySet};
 use spacetimedb_lib::identity::AuthCtx;
 use spacetimedb_lib::relation::{Column, FieldName, MemTable, RelValue};
 use spacetimedb_lib::DataKey;
-use spacetimedb_sats::AlgebraicType;
-use spacetimedb_vm::expr::{Crud, CrudExpr, DbType, QueryExpr, SourceExpr};
+use spacetimedb_sats::{AlgebraicType, AlgebraicValue};
+use spacetimedb_vm::expr::{Crud, CrudExpr, DbType, Query as VmQuery, QueryExpr, SourceExpr};
 
 pub const SUBSCRIBE_TO_ALL_QUERY: &str = "SELECT * FROM *";
 
@@ -88,6 +91,78 @@ pub(crate) fn run_query(
     execute_single_sql(db, tx, CrudExpr::Query(query.clone()), auth)
 }
 
+/// Incremental evaluation of `query` over the rows changed by `database_update`,
+/// returning the rows it started or stopped matching, by the table they are of.
+///
+/// A query joining tables is evaluated by the delta queries of its [`JoinPlan`],
+/// and any other query is evaluated again over the changed rows of its source table, see [`to_mem_table`].
+#[tracing::instrument(skip_all)]
+pub(crate) fn eval_incr_query(
+    db: &RelationalDB,
+    tx: &mut MutTxId,
+    query: &QueryExpr,
+    database_update: &DatabaseUpdate,
+    auth: AuthCtx,
+) -> Result<Vec<DatabaseTableUpdate>, DBError> {
+    if let Some(plan) = JoinPlan::compile(query) {
+        return Ok(plan.eval_incr(db, tx, database_update)?.into_iter().collect());
+    }
+
+    let mut output = Vec::new();
+    let Some(source) = query.source.get_db_table() else {
+        return Ok(output);
+    };
+    for table in database_update.tables.iter().filter(|t| t.table_id == source.table_id) {
+        output.extend(run_query_over(db, tx, query, table, auth)?);
+    }
+    Ok(output)
+}
+
+/// Runs `query` over the rows of `table` in place of its source table,
+/// returning the ones it matches, with the operations they have in `table`.
+#[tracing::instrument(skip_all)]
+pub(crate) fn run_query_over(
+    db: &RelationalDB,
+    tx: &mut MutTxId,
+    query: &QueryExpr,
+    table: &DatabaseTableUpdate,
+    auth: AuthCtx,
+) -> Result<Option<DatabaseTableUpdate>, DBError> {
+    let q = to_mem_table(query.clone(), table);
+    let Some(result) = run_query(db, tx, &q, auth)?.into_iter().find(|x| !x.data.is_empty()) else {
+        return Ok(None);
+    };
+    let pos_op_type = result.head.find_pos_by_name(OP_TYPE_FIELD_NAME);
+
+    let mut ops = Vec::with_capacity(result.data.len());
+    for mut row in result.data {
+        //Hack: remove the hidden field OP_TYPE_FIELD_NAME. see `to_mem_table`
+        // Needs to be done before calculating the PK.
+        let op_type = match pos_op_type.map(|pos| row.data.elements.remove(pos)) {
+            Some(AlgebraicValue::U8(op)) => Some(op),
+            Some(_) => panic!("Fail to extract `{OP_TYPE_FIELD_NAME}` on `{}`", result.head.table_name),
+            None => None,
+        };
+        let row_pk = pk_for_row(&row).to_bytes();
+        // A projection on the columns of the source, after a join, drops the hidden field,
+        // but keeps the rows, which are then found by their PK.
+        let Some(op_type) = op_type.or_else(|| table.ops.iter().find(|op| op.row_pk == row_pk).map(|op| op.op_type))
+        else {
+            continue;
+        };
+        ops.push(TableOp {
+            op_type,
+            row_pk,
+            row: row.data,
+        });
+    }
+    Ok(Some(DatabaseTableUpdate {
+        table_id: table.table_id,
+        table_name: table.table_name.clone(),
+        ops,
+    }))
+}
+
 // TODO: It's semantically wrong to `SUBSCRIBE_TO_ALL_QUERY`
 // as it can only return back the changes valid for the tables in scope *right now*
 // instead of **continuously updating** the db changes
@@ -103,13 +178,30 @@ pub(crate) fn run_query(
 /// }
 /// ```
 ///
+/// The rows of every table read by the queries are restricted by `row_filters` to the rows `auth.caller` may read.
+///
 /// WARNING: [`SUBSCRIBE_TO_ALL_QUERY`] is only valid for repeated calls as long there is not change on database schema, and the clients must `unsubscribe` before modifying it.
 #[tracing::instrument(skip(relational_db, auth, tx))]
 pub fn compile_read_only_query(
     relational_db: &RelationalDB,
     tx: &MutTxId,
     auth: &AuthCtx,
+    row_filters: &RowFilters,
     input: &str,
+) -> Result<Query, DBError> {
+    compile_read_only_query_with_params(relational_db, tx, auth, row_filters, input, &SqlParams::new())
+}
+
+/// Compile from `SQL` into a [`Query`] like [`compile_read_only_query`],
+/// binding the `params` to the placeholders of the `input`.
+#[tracing::instrument(skip(relational_db, auth, tx, params))]
+pub fn compile_read_only_query_with_params(
+    relational_db: &RelationalDB,
+    tx: &MutTxId,
+    auth: &AuthCtx,
+    row_filters: &RowFilters,
+    input: &str,
+    params: &SqlParams,
 ) -> Result<Query, DBError> {
     let input = input.trim();
     if input.is_empty() {
@@ -117,14 +209,19 @@ pub fn compile_read_only_query(
     }
 
     if input == SUBSCRIBE_TO_ALL_QUERY {
-        return QuerySet::get_all(relational_db, tx, auth);
+        let Query { queries } = QuerySet::get_all(relational_db, tx, auth)?;
+        let queries = queries
+            .into_iter()
+            .map(|q| row_filters.restrict_query(relational_db, tx, auth, q))
+            .collect::<Result<_, _>>()?;
+        return Ok(Query { queries });
     }
 
-    let compiled = compile_sql(relational_db, tx, input)?;
+    let compiled = compile_sql_with_params(relational_db, tx, input, params)?;
     let mut queries = Vec::with_capacity(compiled.len());
     for q in compiled {
         match q {
-            CrudExpr::Query(x) => queries.push(x),
+            CrudExpr::Query(x) => queries.push(row_filters.restrict_query(relational_db, tx, auth, x)?),
             CrudExpr::Insert { .. } => {
                 return Err(SubscriptionError::SideEffect(Crud::Insert).into());
             }
@@ -144,13 +241,55 @@ pub fn compile_read_only_query(
     }
 }
 
+/// Compile from `SQL` into a [`Query`] for a subscription, see [`compile_read_only_query`].
+///
+/// Also rejects the queries that can't be maintained incrementally.
+#[tracing::instrument(skip(relational_db, auth, tx))]
+pub fn compile_subscription_query(
+    relational_db: &RelationalDB,
+    tx: &MutTxId,
+    auth: &AuthCtx,
+    row_filters: &RowFilters,
+    input: &str,
+) -> Result<Query, DBError> {
+    compile_subscription_query_with_params(relational_db, tx, auth, row_filters, input, &SqlParams::new())
+}
+
+/// Compile from `SQL` into a [`Query`] for a subscription like [`compile_subscription_query`],
+/// binding the `params` to the placeholders of the `input`.
+#[tracing::instrument(skip(relational_db, auth, tx, params))]
+pub fn compile_subscription_query_with_params(
+    relational_db: &RelationalDB,
+    tx: &MutTxId,
+    auth: &AuthCtx,
+    row_filters: &RowFilters,
+    input: &str,
+    params: &SqlParams,
+) -> Result<Query, DBError> {
+    let query = compile_read_only_query_with_params(relational_db, tx, auth, row_filters, input, params)?;
+    for op in query.queries.iter().flat_map(|x| &x.query) {
+        match op {
+            // Incremental updates can't maintain the order or the window of the rows.
+            VmQuery::Sort(_) | VmQuery::Limit(_) => {
+                return Err(SubscriptionError::Unordered(input.trim().to_string()).into());
+            }
+            // Nor the aggregates, as they depend on all the rows of their group.
+            VmQuery::Aggregate(_) => {
+                return Err(SubscriptionError::Aggregated(input.trim().to_string()).into());
+            }
+            _ => {}
+        }
+    }
+    Ok(query)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::db::datastore::traits::{ColumnDef, IndexDef, TableDef, TableSchema};
     use crate::db::relational_db::tests_utils::make_test_db;
-    use crate::host::module_host::{DatabaseTableUpdate, DatabaseUpdate, TableOp};
-    use crate::sql::execute::run;
+    use crate::sql::compiler::compile_sql;
+    use crate::sql::execute::{run, run_with_filters};
     use crate::subscription::subscription::QuerySet;
     use crate::vm::tests::create_table_with_rows;
     use itertools::Itertools;
@@ -159,10 +298,11 @@ mod tests {
     use spacetimedb_lib::data_key::ToDataKey;
     use spacetimedb_lib::error::ResultTest;
     use spacetimedb_lib::relation::FieldName;
-    use spacetimedb_lib::Identity;
-    use spacetimedb_sats::{product, ProductType, ProductValue};
+    use spacetimedb_lib::{Identity, IndexType, RowFilter};
+    use spacetimedb_sats::{product, AlgebraicValue, ProductType, ProductValue};
     use spacetimedb_vm::dsl::{db_table, mem_table, scalar};
     use spacetimedb_vm::operator::OpCmp;
+    use std::collections::HashSet;
 
     fn create_table(
         db: &RelationalDB,
@@ -191,6 +331,7 @@ mod tests {
                 cols: NonEmpty::new(*col_id),
                 name: index_name.to_string(),
                 is_unique: false,
+                index_type: IndexType::BTree,
             })
             .collect_vec();
 
@@ -531,6 +672,255 @@ mod tests {
         Ok(())
     }
 
+    fn table_update(table_id: u32, table_name: &str, ops: &[(u8, ProductValue)]) -> DatabaseTableUpdate {
+        let ops = ops
+            .iter()
+            .map(|(op_type, row)| TableOp {
+                op_type: *op_type,
+                row_pk: row.to_data_key().to_bytes(),
+                row: row.clone(),
+            })
+            .collect();
+        DatabaseTableUpdate {
+            table_id,
+            table_name: table_name.into(),
+            ops,
+        }
+    }
+
+    fn get_ops(result: DatabaseUpdate) -> Vec<(u8, ProductValue)> {
+        result
+            .tables
+            .into_iter()
+            .flat_map(|x| x.ops.into_iter().map(|x| (x.op_type, x.row)))
+            .sorted()
+            .collect::<Vec<_>>()
+    }
+
+    #[test]
+    fn test_eval_incr_for_join_rhs_change() -> ResultTest<()> {
+        let (db, _) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        // Create table [lhs] with index on [b]
+        let schema = &[("a", AlgebraicType::U64), ("b", AlgebraicType::U64)];
+        let lhs_id = create_table(&db, &mut tx, "lhs", schema, &[(1, "b")])?;
+
+        // Create table [rhs] with no indexes
+        let schema = &[("b", AlgebraicType::U64), ("c", AlgebraicType::U64)];
+        let rhs_id = create_table(&db, &mut tx, "rhs", schema, &[])?;
+
+        let sql = "select lhs.* from lhs join rhs on lhs.b = rhs.b where rhs.c = 1";
+        let mut exp = compile_sql(&db, &tx, sql)?;
+        let Some(CrudExpr::Query(query)) = exp.pop() else {
+            panic!("unexpected query {:#?}", exp[0]);
+        };
+        let query = QuerySet(vec![Query { queries: vec![query] }]);
+
+        for i in 0..5u64 {
+            db.insert(&mut tx, lhs_id, product!(i, i))?;
+            db.insert(&mut tx, rhs_id, product!(i, i))?;
+        }
+        db.insert(&mut tx, lhs_id, product!(7u64, 7u64))?;
+        db.insert(&mut tx, lhs_id, product!(8u64, 7u64))?;
+
+        // A row of the right table matching the rows of the left table no other row matched
+        db.insert(&mut tx, rhs_id, product!(7u64, 1u64))?;
+        let update = DatabaseUpdate {
+            tables: vec![table_update(rhs_id, "rhs", &[(1, product!(7u64, 1u64))])],
+        };
+        let result = query.eval_incr(&db, &mut tx, &update, AuthCtx::for_testing())?;
+        assert_eq!(result.tables.len(), 1);
+        assert_eq!(result.tables[0].table_id, lhs_id);
+        assert_eq!(
+            get_ops(result),
+            vec![(1, product!(7u64, 7u64)), (1, product!(8u64, 7u64))]
+        );
+
+        // A row of the right table not matching the selection
+        db.insert(&mut tx, rhs_id, product!(3u64, 2u64))?;
+        let update = DatabaseUpdate {
+            tables: vec![table_update(rhs_id, "rhs", &[(1, product!(3u64, 2u64))])],
+        };
+        let result = query.eval_incr(&db, &mut tx, &update, AuthCtx::for_testing())?;
+        assert!(result.tables.is_empty());
+
+        // The only row of the right table the rows of the left table matched
+        db.delete_by_rel(&mut tx, rhs_id, vec![product!(7u64, 1u64)])?;
+        let update = DatabaseUpdate {
+            tables: vec![table_update(rhs_id, "rhs", &[(0, product!(7u64, 1u64))])],
+        };
+        let result = query.eval_incr(&db, &mut tx, &update, AuthCtx::for_testing())?;
+        assert_eq!(
+            get_ops(result),
+            vec![(0, product!(7u64, 7u64)), (0, product!(8u64, 7u64))]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_incr_for_unindexed_join() -> ResultTest<()> {
+        let (db, _) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        // Create tables [lhs] and [rhs] with no indexes
+        let schema = &[("a", AlgebraicType::U64), ("b", AlgebraicType::U64)];
+        let lhs_id = create_table(&db, &mut tx, "lhs", schema, &[])?;
+        let schema = &[("b", AlgebraicType::U64), ("c", AlgebraicType::U64)];
+        let rhs_id = create_table(&db, &mut tx, "rhs", schema, &[])?;
+
+        let sql = "select lhs.* from lhs join rhs on lhs.b = rhs.b";
+        let mut exp = compile_sql(&db, &tx, sql)?;
+        let Some(CrudExpr::Query(query)) = exp.pop() else {
+            panic!("unexpected query {:#?}", exp[0]);
+        };
+        let query = QuerySet(vec![Query { queries: vec![query] }]);
+
+        db.insert(&mut tx, lhs_id, product!(1u64, 1u64))?;
+        db.insert(&mut tx, lhs_id, product!(2u64, 2u64))?;
+        db.insert(&mut tx, rhs_id, product!(1u64, 10u64))?;
+        db.insert(&mut tx, rhs_id, product!(1u64, 11u64))?;
+        db.insert(&mut tx, rhs_id, product!(2u64, 20u64))?;
+
+        // The rows of the left table are reported once they match no row at all,
+        // or start matching some, whichever side changed
+        db.delete_by_rel(&mut tx, rhs_id, vec![product!(1u64, 10u64), product!(1u64, 11u64)])?;
+        db.insert(&mut tx, lhs_id, product!(3u64, 2u64))?;
+        let update = DatabaseUpdate {
+            tables: vec![
+                table_update(lhs_id, "lhs", &[(1, product!(3u64, 2u64))]),
+                table_update(rhs_id, "rhs", &[(0, product!(1u64, 10u64)), (0, product!(1u64, 11u64))]),
+            ],
+        };
+        let result = query.eval_incr(&db, &mut tx, &update, AuthCtx::for_testing())?;
+        assert_eq!(result.tables.len(), 1);
+        assert_eq!(
+            get_ops(result),
+            vec![(0, product!(1u64, 1u64)), (1, product!(3u64, 2u64))]
+        );
+
+        // Both sides of a match deleted at once
+        db.delete_by_rel(&mut tx, lhs_id, vec![product!(2u64, 2u64)])?;
+        db.delete_by_rel(&mut tx, rhs_id, vec![product!(2u64, 20u64)])?;
+        let update = DatabaseUpdate {
+            tables: vec![
+                table_update(lhs_id, "lhs", &[(0, product!(2u64, 2u64))]),
+                table_update(rhs_id, "rhs", &[(0, product!(2u64, 20u64))]),
+            ],
+        };
+        let result = query.eval_incr(&db, &mut tx, &update, AuthCtx::for_testing())?;
+        assert_eq!(
+            get_ops(result),
+            vec![(0, product!(2u64, 2u64)), (0, product!(3u64, 2u64))]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_incr_for_multi_way_join() -> ResultTest<()> {
+        let (db, _) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        let schema = &[("id", AlgebraicType::U64), ("x", AlgebraicType::U64)];
+        let a_id = create_table(&db, &mut tx, "a", schema, &[(1, "x")])?;
+        let schema = &[("x", AlgebraicType::U64), ("y", AlgebraicType::U64)];
+        let b_id = create_table(&db, &mut tx, "b", schema, &[])?;
+        let schema = &[("y", AlgebraicType::U64), ("z", AlgebraicType::U64)];
+        let c_id = create_table(&db, &mut tx, "c", schema, &[(0, "y")])?;
+
+        let sql = "select a.* from a join b on a.x = b.x join c on b.y = c.y where c.z > 0";
+        let mut exp = compile_sql(&db, &tx, sql)?;
+        let Some(CrudExpr::Query(query)) = exp.pop() else {
+            panic!("unexpected query {:#?}", exp[0]);
+        };
+        let query = QuerySet(vec![Query { queries: vec![query] }]);
+
+        db.insert(&mut tx, a_id, product!(1u64, 1u64))?;
+        db.insert(&mut tx, a_id, product!(2u64, 2u64))?;
+        db.insert(&mut tx, b_id, product!(1u64, 10u64))?;
+        db.insert(&mut tx, b_id, product!(2u64, 20u64))?;
+        db.insert(&mut tx, c_id, product!(10u64, 100u64))?;
+
+        // A row of the last table of the join
+        db.insert(&mut tx, c_id, product!(20u64, 200u64))?;
+        let update = DatabaseUpdate {
+            tables: vec![table_update(c_id, "c", &[(1, product!(20u64, 200u64))])],
+        };
+        let result = query.eval_incr(&db, &mut tx, &update, AuthCtx::for_testing())?;
+        assert_eq!(result.tables.len(), 1);
+        assert_eq!(result.tables[0].table_id, a_id);
+        assert_eq!(get_ops(result), vec![(1, product!(2u64, 2u64))]);
+
+        // A row of the table in the middle of the join
+        db.delete_by_rel(&mut tx, b_id, vec![product!(1u64, 10u64)])?;
+        let update = DatabaseUpdate {
+            tables: vec![table_update(b_id, "b", &[(0, product!(1u64, 10u64))])],
+        };
+        let result = query.eval_incr(&db, &mut tx, &update, AuthCtx::for_testing())?;
+        assert_eq!(get_ops(result), vec![(0, product!(1u64, 1u64))]);
+
+        // A row of the last table not matching the selection on it
+        db.insert(&mut tx, c_id, product!(10u64, 0u64))?;
+        db.insert(&mut tx, b_id, product!(1u64, 10u64))?;
+        let update = DatabaseUpdate {
+            tables: vec![
+                table_update(b_id, "b", &[(1, product!(1u64, 10u64))]),
+                table_update(c_id, "c", &[(1, product!(10u64, 0u64))]),
+            ],
+        };
+        let result = query.eval_incr(&db, &mut tx, &update, AuthCtx::for_testing())?;
+        assert_eq!(get_ops(result), vec![(1, product!(1u64, 1u64))]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_matching_rows() -> ResultTest<()> {
+        let (db, _) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        let schema = &[("a", AlgebraicType::U64), ("b", AlgebraicType::U64)];
+        let lhs_id = create_table(&db, &mut tx, "lhs", schema, &[])?;
+        let schema = &[("b", AlgebraicType::U64), ("c", AlgebraicType::U64)];
+        let rhs_id = create_table(&db, &mut tx, "rhs", schema, &[])?;
+
+        for i in 0..10u64 {
+            db.insert(&mut tx, lhs_id, product!(i, i))?;
+        }
+        db.insert(&mut tx, rhs_id, product!(2u64, 0u64))?;
+
+        let queries = [
+            "select * from lhs where a > 7",
+            "select lhs.* from lhs join rhs on lhs.b = rhs.b",
+        ]
+        .iter()
+        .map(|sql| compile_subscription_query(&db, &tx, &AuthCtx::for_testing(), &RowFilters::default(), sql))
+        .collect::<Result<QuerySet, _>>()?;
+
+        // The rows of a query not matching any other
+        let rows = compile_subscription_query(
+            &db,
+            &tx,
+            &AuthCtx::for_testing(),
+            &RowFilters::default(),
+            "select * from lhs where a = 5",
+        )?;
+        let rows = QuerySet(vec![rows]).eval(&db, &mut tx, AuthCtx::for_testing())?;
+        assert!(queries
+            .matching_rows(&db, &mut tx, &rows, AuthCtx::for_testing())?
+            .is_empty());
+
+        // ... and matching some of them, through the join
+        let all = QuerySet::get_all(&db, &tx, &AuthCtx::for_testing())?;
+        let rows = QuerySet(vec![all]).eval(&db, &mut tx, AuthCtx::for_testing())?;
+        let matching = queries.matching_rows(&db, &mut tx, &rows, AuthCtx::for_testing())?;
+        let expected = [product!(2u64, 2u64), product!(8u64, 8u64), product!(9u64, 9u64)]
+            .iter()
+            .map(|row| (lhs_id, row.to_data_key().to_bytes()))
+            .collect::<HashSet<_>>();
+        assert_eq!(matching, expected);
+        Ok(())
+    }
+
     #[test]
     fn test_subscribe() -> ResultTest<()> {
         let (db, _tmp_dir) = make_test_db()?;
@@ -740,7 +1130,7 @@ mod tests {
         run(&db, &mut tx, sql_create, AuthCtx::for_testing())?;
 
         let sql_query = "SELECT * FROM MobileEntityState JOIN EnemyState ON MobileEntityState.entity_id = EnemyState.entity_id WHERE location_x > 96000 AND MobileEntityState.location_x < 192000 AND MobileEntityState.location_z > 96000 AND MobileEntityState.location_z < 192000";
-        let q = compile_read_only_query(&db, &tx, &AuthCtx::for_testing(), sql_query)?;
+        let q = compile_read_only_query(&db, &tx, &AuthCtx::for_testing(), &RowFilters::default(), sql_query)?;
 
         for q in q.queries {
             assert_eq!(
@@ -753,6 +1143,51 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_subscribe_ordered() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        make_inv(&db, &mut tx, StAccess::Public)?;
+
+        for sql in [
+            "SELECT * FROM inventory ORDER BY name",
+            "SELECT * FROM inventory LIMIT 1",
+        ] {
+            // One-off queries can be ordered, but subscriptions can't.
+            compile_read_only_query(&db, &tx, &AuthCtx::for_testing(), &RowFilters::default(), sql)?;
+            let err = compile_subscription_query(&db, &tx, &AuthCtx::for_testing(), &RowFilters::default(), sql);
+            assert!(
+                matches!(err, Err(DBError::Subscription(SubscriptionError::Unordered(_)))),
+                "Subscription to `{sql}` should fail"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_one_off_aggregate() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        make_inv(&db, &mut tx, StAccess::Public)?;
+
+        let sql = "SELECT name, COUNT(*) FROM inventory GROUP BY name";
+        let q = compile_read_only_query(&db, &tx, &AuthCtx::for_testing(), &RowFilters::default(), sql)?;
+        let result = run_query(&db, &mut tx, &q.queries[0], AuthCtx::for_testing())?;
+        let rows = result[0].data.iter().map(|row| row.data.clone()).collect::<Vec<_>>();
+        assert_eq!(rows, [product!("health", 1u64)]);
+
+        let err = compile_subscription_query(&db, &tx, &AuthCtx::for_testing(), &RowFilters::default(), sql);
+        assert!(
+            matches!(err, Err(DBError::Subscription(SubscriptionError::Aggregated(_)))),
+            "Subscription to `{sql}` should fail"
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_subscribe_all() -> ResultTest<()> {
         let (db, _tmp_dir) = make_test_db()?;
@@ -767,6 +1202,7 @@ mod tests {
             &db,
             &tx,
             &AuthCtx::for_testing(),
+            &RowFilters::default(),
             SUBSCRIBE_TO_ALL_QUERY,
         )?]);
 
@@ -806,4 +1242,78 @@ mod tests {
 
         Ok(())
     }
+
+    // Check that a client only reads the rows that the row filter of a table lets it read,
+    // both in the initial and the incremental evaluation, and in one-off SQL
+    #[test]
+    fn test_subscribe_row_filter() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        let identity = |id: u8| Identity::from_byte_array([id; 32]);
+        let identity_value = |id: u8| AlgebraicValue::product(vec![AlgebraicValue::Bytes(vec![id; 32])]);
+        let (owner, alice, bob) = (identity(0), identity(1), identity(2));
+
+        let head = ProductType::from([
+            (
+                "author",
+                AlgebraicType::product([("__identity_bytes", AlgebraicType::bytes())]),
+            ),
+            ("text", AlgebraicType::String),
+        ]);
+        let alice_row = product!(identity_value(1), "from alice");
+        let bob_row = product!(identity_value(2), "from bob");
+        let table_id = create_table_with_rows(&db, &mut tx, "note", head, &[alice_row.clone(), bob_row.clone()])?;
+
+        let row_filters = RowFilters::new([RowFilter {
+            table: "note".into(),
+            filter: "author = :sender".into(),
+        }]);
+
+        let auth = AuthCtx::new(owner, alice);
+        let s = QuerySet(vec![compile_subscription_query(
+            &db,
+            &tx,
+            &auth,
+            &row_filters,
+            "SELECT * FROM note",
+        )?]);
+        check_query_eval(&db, &mut tx, &s, 1, &[alice_row.clone()])?;
+
+        let ops = [&alice_row, &bob_row].map(|row| TableOp {
+            op_type: 1,
+            row_pk: row.to_data_key().to_bytes(),
+            row: row.clone(),
+        });
+        let update = DatabaseUpdate {
+            tables: vec![DatabaseTableUpdate {
+                table_id,
+                table_name: "note".to_string(),
+                ops: ops.into(),
+            }],
+        };
+        check_query_incr(&db, &mut tx, &s, &update, 1, &[alice_row.clone()])?;
+
+        // The owner reads every row.
+        let s = QuerySet(vec![compile_subscription_query(
+            &db,
+            &tx,
+            &AuthCtx::new(owner, owner),
+            &row_filters,
+            SUBSCRIBE_TO_ALL_QUERY,
+        )?]);
+        check_query_eval(&db, &mut tx, &s, 1, &[alice_row.clone(), bob_row.clone()])?;
+
+        // A client can't delete the rows it can't read either.
+        let auth = AuthCtx::new(owner, bob);
+        let result = run_with_filters(&db, &mut tx, "SELECT * FROM note", auth, &row_filters)?;
+        assert_eq!(result[0].data.len(), 1);
+        assert_eq!(result[0].data[0].data, bob_row);
+        run_with_filters(&db, &mut tx, "DELETE FROM note", auth, &row_filters)?;
+        let result = run(&db, &mut tx, "SELECT * FROM note", AuthCtx::new(owner, owner))?;
+        assert_eq!(result[0].data.len(), 1);
+        assert_eq!(result[0].data[0].data, alice_row);
+
+        Ok(())
+    }
 }
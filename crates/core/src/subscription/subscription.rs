@@ -2,15 +2,15 @@ use spacetimedb_lib::auth::{StAccess, StTableType};
 use spacetimedb_lib::identity::AuthCtx;
 use spacetimedb_lib::relation::RelValue;
 use spacetimedb_lib::PrimaryKey;
-use spacetimedb_sats::AlgebraicValue;
 use spacetimedb_vm::expr::QueryExpr;
 use std::collections::{HashMap, HashSet};
 use std::ops::Deref;
 
 use super::query::Query;
+use super::template::BoundTemplate;
 use crate::db::datastore::locking_tx_datastore::MutTxId;
 use crate::error::DBError;
-use crate::subscription::query::{run_query, OP_TYPE_FIELD_NAME};
+use crate::subscription::query::{eval_incr_query, run_query, run_query_over};
 use crate::{
     client::{ClientActorId, ClientConnectionSender},
     db::relational_db::RelationalDB,
@@ -19,6 +19,8 @@ use crate::{
 
 pub struct Subscription {
     pub queries: QuerySet,
+    /// The parameterized queries, sorted.
+    pub templates: Vec<BoundTemplate>,
     pub subscribers: Vec<ClientConnectionSender>,
 }
 
@@ -55,7 +57,7 @@ impl Subscription {
 
 // If a RelValue has an id (DataKey) return it directly, otherwise we must construct it from the
 // row itself which can be an expensive operation.
-fn pk_for_row(row: &RelValue) -> PrimaryKey {
+pub(super) fn pk_for_row(row: &RelValue) -> PrimaryKey {
     match row.id {
         Some(data_key) => PrimaryKey { data_key },
         None => RelationalDB::pk_for_row(&row.data),
@@ -82,6 +84,7 @@ impl QuerySet {
     /// Incremental evaluation of `rows` that matched the [Query] (aka subscriptions)
     ///
     /// This is equivalent to run a `trigger` on `INSERT/UPDATE/DELETE`, run the [Query] and see if the `row` is matched.
+    /// The queries joining tables are evaluated by delta queries instead, see [`eval_incr_query`].
     ///
     /// NOTE: The returned `rows` in [DatabaseUpdate] are **deduplicated** so if 2 queries match the same `row`, only one copy is returned.
     #[tracing::instrument(skip_all)]
@@ -97,45 +100,17 @@ impl QuerySet {
         let mut seen = HashSet::new();
 
         for query in &self.0 {
-            for table in database_update.tables.iter().cloned() {
-                let (_, table_row_operations) = table_ops
-                    .entry(table.table_id)
-                    .or_insert((table.table_name.clone(), vec![]));
-                for q in query.queries_of_table_id(&table) {
-                    if let Some(result) = run_query(relational_db, tx, &q, auth)?
-                        .into_iter()
-                        .find(|x| !x.data.is_empty())
-                    {
-                        let pos_op_type = result.head.find_pos_by_name(OP_TYPE_FIELD_NAME).unwrap_or_else(|| {
-                            panic!(
-                                "failed to locate `{OP_TYPE_FIELD_NAME}` on `{}`. fields: {:?}",
-                                result.head.table_name,
-                                result.head.fields.iter().map(|x| &x.field).collect::<Vec<_>>()
-                            )
-                        });
-
-                        for mut row in result.data {
-                            //Hack: remove the hidden field OP_TYPE_FIELD_NAME. see `to_mem_table`
-                            // Needs to be done before calculating the PK.
-                            let op_type = if let AlgebraicValue::U8(op) = row.data.elements.remove(pos_op_type) {
-                                op
-                            } else {
-                                panic!("Fail to extract `{OP_TYPE_FIELD_NAME}` on `{}`", result.head.table_name)
-                            };
-
-                            let row_pk = pk_for_row(&row);
-
-                            //Skip rows that are already resolved in a previous subscription...
-                            if seen.contains(&(table.table_id, row_pk)) {
-                                continue;
-                            }
-
-                            seen.insert((table.table_id, row_pk));
-
-                            let row_pk = row_pk.to_bytes();
-                            let row = row.data;
-                            table_row_operations.push(TableOp { op_type, row_pk, row });
+            for q in &query.queries {
+                for table in eval_incr_query(relational_db, tx, q, database_update, auth)? {
+                    let (_, table_row_operations) = table_ops
+                        .entry(table.table_id)
+                        .or_insert((table.table_name.clone(), vec![]));
+                    for op in table.ops {
+                        //Skip rows that are already resolved in a previous subscription...
+                        if !seen.insert((table.table_id, op.row_pk.clone())) {
+                            continue;
                         }
+                        table_row_operations.push(op);
                     }
                 }
             }
@@ -150,6 +125,33 @@ impl QuerySet {
         Ok(output)
     }
 
+    /// Returns the rows of `database_update` matched by any of the queries, by table id and primary key.
+    ///
+    /// The queries are evaluated over these rows alone, in place of their source tables.
+    #[tracing::instrument(skip_all)]
+    pub fn matching_rows(
+        &self,
+        relational_db: &RelationalDB,
+        tx: &mut MutTxId,
+        database_update: &DatabaseUpdate,
+        auth: AuthCtx,
+    ) -> Result<HashSet<(u32, Vec<u8>)>, DBError> {
+        let mut matching = HashSet::new();
+        for query in &self.0 {
+            for q in &query.queries {
+                let Some(source) = q.source.get_db_table() else {
+                    continue;
+                };
+                for table in database_update.tables.iter().filter(|t| t.table_id == source.table_id) {
+                    if let Some(update) = run_query_over(relational_db, tx, q, table, auth)? {
+                        matching.extend(update.ops.into_iter().map(|op| (table.table_id, op.row_pk)));
+                    }
+                }
+            }
+        }
+        Ok(matching)
+    }
+
     /// Direct execution of [Query] (aka subscriptions)
     ///
     /// This is equivalent to run a direct query like `SELECT * FROM table` and get back all the `rows` that match it.
@@ -0,0 +1,455 @@
+//! Parameterized subscription queries.
+//!
+//! A query template is compiled once, without the conditions on its parameters,
+//! into a plan shared by every subscription binding values to them.
+//! The plan is evaluated once per transaction for all of these subscriptions,
+//! and the rows it matches are handed out by the values of the parameters they match.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use spacetimedb_lib::identity::AuthCtx;
+use spacetimedb_lib::relation::{FieldExpr, FieldName};
+use spacetimedb_lib::Identity;
+use spacetimedb_sats::{AlgebraicType, AlgebraicValue};
+use spacetimedb_vm::expr::Query as VmQuery;
+use sqlparser::ast::{BinaryOperator, Expr as SqlExpr, Ident, SetExpr, Statement, TableFactor, Value};
+use sqlparser::dialect::PostgreSqlDialect;
+use sqlparser::parser::Parser;
+
+use super::query::{compile_subscription_query, compile_subscription_query_with_params, eval_incr_query, Query};
+use crate::db::datastore::locking_tx_datastore::MutTxId;
+use crate::db::relational_db::RelationalDB;
+use crate::error::{DBError, PlanError, SubscriptionError};
+use crate::host::module_host::{DatabaseTableUpdate, DatabaseUpdate};
+use crate::protobuf::client_api::QueryParam;
+use crate::sql::row_filter::RowFilters;
+
+/// The values bound to the parameters of a [`QueryTemplate`], in the order of its parameters.
+pub type ParamValues = Vec<AlgebraicValue>;
+
+/// A parameter of a [`QueryTemplate`], compared for equality with a column of its table.
+struct TemplateParam {
+    /// The name of the parameter, without the leading `:`.
+    name: String,
+    /// The parameter as written in the template.
+    placeholder: String,
+    /// The column the parameter is compared with.
+    column: String,
+    /// The type of the column, in which the values bound to the parameter are encoded.
+    ty: AlgebraicType,
+}
+
+/// A SQL query template, in which each parameter is compared for equality with a column of its table.
+pub struct QueryTemplate {
+    text: String,
+    /// The client the plan is restricted to by the row filters of the module, if they apply.
+    caller: Option<Identity>,
+    /// The template without the conditions on its parameters.
+    plan: Query,
+    params: Vec<TemplateParam>,
+}
+
+impl QueryTemplate {
+    /// Compiles the template `text` for the client `auth.caller`.
+    pub fn compile(
+        relational_db: &RelationalDB,
+        tx: &MutTxId,
+        auth: &AuthCtx,
+        row_filters: &RowFilters,
+        text: &str,
+    ) -> Result<Self, DBError> {
+        let text = text.trim();
+        let unsupported = || DBError::from(SubscriptionError::UnsupportedTemplate(text.to_string()));
+        let unsupported_param = |param: &str| {
+            DBError::from(SubscriptionError::UnsupportedParameter {
+                template: text.to_string(),
+                param: param.to_string(),
+            })
+        };
+
+        let mut statements = Parser::parse_sql(&PostgreSqlDialect {}, text).map_err(|error| DBError::SqlParser {
+            sql: text.to_string(),
+            error,
+        })?;
+        let [Statement::Query(query)] = statements.as_mut_slice() else {
+            return Err(unsupported());
+        };
+        let SetExpr::Select(select) = query.body.as_mut() else {
+            return Err(unsupported());
+        };
+        let [from] = select.from.as_slice() else {
+            return Err(unsupported());
+        };
+        let TableFactor::Table { name: table, .. } = &from.relation else {
+            return Err(unsupported());
+        };
+        let table = table.to_string();
+
+        let mut conditions = Vec::new();
+        select.selection = select
+            .selection
+            .take()
+            .and_then(|expr| take_param_conditions(expr, &mut conditions));
+
+        // Any parameter left is not compared for equality with a column.
+        let plan = compile_subscription_query(relational_db, tx, auth, row_filters, &statements[0].to_string())
+            .map_err(|err| match err {
+                DBError::Plan {
+                    error: PlanError::UnboundParameter { name },
+                    ..
+                } => unsupported_param(&name),
+                err => err,
+            })?;
+
+        let table_id = relational_db.table_id_from_name(tx, &table)?.ok_or_else(unsupported)?;
+        let schema = relational_db.schema_for_table(tx, table_id)?;
+        let params = conditions
+            .into_iter()
+            .map(|(column, placeholder)| {
+                let column = match column.as_slice() {
+                    [column] => column,
+                    [table_name, column] if table_name.value == table => column,
+                    _ => return Err(unsupported_param(&placeholder)),
+                };
+                let column = schema
+                    .columns
+                    .iter()
+                    .find(|col| col.col_name == column.value)
+                    .ok_or_else(|| unsupported_param(&placeholder))?;
+
+                // The rows matched by the plan are handed out by the value of the column,
+                // so it can't be projected away.
+                let field = FieldName::named(&schema.table_name, &column.col_name);
+                let projected_away = plan.queries.iter().flat_map(|q| &q.query).any(|op| {
+                    matches!(op, VmQuery::Project(cols, _)
+                        if !cols.is_empty() && !cols.iter().any(|col| matches!(col, FieldExpr::Name(x) if *x == field)))
+                });
+                if projected_away {
+                    return Err(unsupported_param(&placeholder));
+                }
+
+                Ok(TemplateParam {
+                    name: placeholder.strip_prefix(':').unwrap_or(&placeholder).to_string(),
+                    placeholder,
+                    column: column.col_name.clone(),
+                    ty: column.col_type.clone(),
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            text: text.to_string(),
+            caller: row_filters.applies_to(auth).then_some(auth.caller),
+            plan,
+            params,
+        })
+    }
+
+    /// Returns whether this is the template `text` compiled for the client `auth.caller`,
+    /// which can then share it.
+    pub fn is_shared_by(&self, text: &str, auth: &AuthCtx, row_filters: &RowFilters) -> bool {
+        self.text == text.trim() && self.caller == row_filters.applies_to(auth).then_some(auth.caller)
+    }
+
+    /// Decodes the values bound to the parameters of the template by `params`.
+    pub fn bind(&self, params: &[QueryParam]) -> Result<ParamValues, DBError> {
+        let invalid = |param: &str, reason: &str| {
+            DBError::from(SubscriptionError::InvalidParameter {
+                template: self.text.clone(),
+                param: param.to_string(),
+                reason: reason.to_string(),
+            })
+        };
+
+        if let Some(param) = params.iter().find(|p| !self.params.iter().any(|x| x.name == p.name)) {
+            return Err(invalid(&param.name, "the template has no such parameter"));
+        }
+        self.params
+            .iter()
+            .map(|param| {
+                let value = params
+                    .iter()
+                    .find(|p| p.name == param.name)
+                    .ok_or_else(|| invalid(&param.name, "no value is bound to it"))?;
+                AlgebraicValue::decode(&param.ty, &mut &value.value[..])
+                    .map_err(|err| invalid(&param.name, &err.to_string()))
+            })
+            .collect()
+    }
+
+    /// Compiles the template with the `values` bound to its parameters,
+    /// for the initial evaluation of a subscription.
+    pub fn compile_bound(
+        &self,
+        relational_db: &RelationalDB,
+        tx: &MutTxId,
+        auth: &AuthCtx,
+        row_filters: &RowFilters,
+        values: &ParamValues,
+    ) -> Result<Query, DBError> {
+        let params = self
+            .params
+            .iter()
+            .zip(values)
+            .map(|(param, value)| (param.placeholder.clone(), value.clone()))
+            .collect();
+        compile_subscription_query_with_params(relational_db, tx, auth, row_filters, &self.text, &params)
+    }
+
+    /// Incremental evaluation of the plan of the template over the rows changed by `database_update`,
+    /// returning the rows it matches by the values of the parameters they match.
+    #[tracing::instrument(skip_all)]
+    pub fn eval_incr(
+        &self,
+        relational_db: &RelationalDB,
+        tx: &mut MutTxId,
+        database_update: &DatabaseUpdate,
+        auth: AuthCtx,
+    ) -> Result<HashMap<ParamValues, DatabaseUpdate>, DBError> {
+        let mut output: HashMap<ParamValues, DatabaseUpdate> = HashMap::new();
+
+        for q in &self.plan.queries {
+            let Some(source) = q.source.get_db_table() else {
+                continue;
+            };
+            // The rows of a query are rows of its source table, in which the parameters are columns.
+            let Some(pos_params) = self
+                .params
+                .iter()
+                .map(|param| source.head.find_pos_by_name(&param.column))
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+
+            for table in eval_incr_query(relational_db, tx, q, database_update, auth)? {
+                for op in table.ops {
+                    let values = pos_params.iter().map(|&pos| op.row.elements[pos].clone()).collect();
+                    let update = output.entry(values).or_default();
+                    match update.tables.iter_mut().find(|t| t.table_id == table.table_id) {
+                        Some(t) => t.ops.push(op),
+                        None => update.tables.push(DatabaseTableUpdate {
+                            table_id: table.table_id,
+                            table_name: table.table_name.clone(),
+                            ops: vec![op],
+                        }),
+                    }
+                }
+            }
+        }
+
+        Ok(output)
+    }
+}
+
+/// Takes the conditions `column = :param` out of the conjunction `expr`,
+/// returning what is left of it.
+fn take_param_conditions(expr: SqlExpr, conditions: &mut Vec<(Vec<Ident>, String)>) -> Option<SqlExpr> {
+    let column = |expr: &SqlExpr| match expr {
+        SqlExpr::Identifier(ident) => Some(vec![ident.clone()]),
+        SqlExpr::CompoundIdentifier(idents) => Some(idents.clone()),
+        _ => None,
+    };
+    let placeholder = |expr: &SqlExpr| match expr {
+        SqlExpr::Value(Value::Placeholder(name)) => Some(name.clone()),
+        _ => None,
+    };
+
+    match expr {
+        SqlExpr::BinaryOp {
+            left,
+            op: BinaryOperator::And,
+            right,
+        } => {
+            let left = take_param_conditions(*left, conditions);
+            let right = take_param_conditions(*right, conditions);
+            match (left, right) {
+                (Some(left), Some(right)) => Some(SqlExpr::BinaryOp {
+                    left: Box::new(left),
+                    op: BinaryOperator::And,
+                    right: Box::new(right),
+                }),
+                (left, right) => left.or(right),
+            }
+        }
+        SqlExpr::Nested(expr) => take_param_conditions(*expr, conditions).map(|expr| SqlExpr::Nested(Box::new(expr))),
+        SqlExpr::BinaryOp {
+            left,
+            op: BinaryOperator::Eq,
+            right,
+        } => match column(&left)
+            .zip(placeholder(&right))
+            .or_else(|| column(&right).zip(placeholder(&left)))
+        {
+            Some(condition) => {
+                conditions.push(condition);
+                None
+            }
+            None => Some(SqlExpr::BinaryOp {
+                left,
+                op: BinaryOperator::Eq,
+                right,
+            }),
+        },
+        expr => Some(expr),
+    }
+}
+
+/// A [`QueryTemplate`] with values bound to its parameters, part of a [`Subscription`](super::subscription::Subscription).
+#[derive(Clone)]
+pub struct BoundTemplate {
+    pub template: Arc<QueryTemplate>,
+    pub values: ParamValues,
+}
+
+impl PartialEq for BoundTemplate {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.template, &other.template) && self.values == other.values
+    }
+}
+
+impl Eq for BoundTemplate {}
+
+impl PartialOrd for BoundTemplate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BoundTemplate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (Arc::as_ptr(&self.template), &self.values).cmp(&(Arc::as_ptr(&other.template), &other.values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::relational_db::tests_utils::make_test_db;
+    use crate::host::module_host::TableOp;
+    use crate::subscription::subscription::QuerySet;
+    use crate::vm::tests::create_table_with_rows;
+    use spacetimedb_lib::data_key::ToDataKey;
+    use spacetimedb_lib::error::ResultTest;
+    use spacetimedb_sats::{bsatn, product, ProductType, ProductValue};
+
+    fn param(name: &str, value: &str) -> QueryParam {
+        QueryParam {
+            name: name.to_string(),
+            value: bsatn::to_vec(&value.to_string()).unwrap(),
+        }
+    }
+
+    fn rows(update: &DatabaseUpdate) -> Vec<ProductValue> {
+        let mut rows = update
+            .tables
+            .iter()
+            .flat_map(|t| t.ops.iter().map(|op| op.row.clone()))
+            .collect::<Vec<_>>();
+        rows.sort();
+        rows
+    }
+
+    #[test]
+    fn test_template_eval_by_param() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        let head = ProductType::from([("id", AlgebraicType::U64), ("channel", AlgebraicType::String)]);
+        let general_1 = product!(1u64, "general");
+        let random = product!(2u64, "random");
+        let general_2 = product!(3u64, "general");
+        let rows_before = [general_1.clone(), random.clone()];
+        let table_id = create_table_with_rows(&db, &mut tx, "message", head, &rows_before)?;
+
+        let auth = AuthCtx::for_testing();
+        let row_filters = RowFilters::default();
+        let template = QueryTemplate::compile(
+            &db,
+            &tx,
+            &auth,
+            &row_filters,
+            "SELECT * FROM message WHERE channel = :channel AND id > 0",
+        )?;
+        assert!(template.is_shared_by(
+            " SELECT * FROM message WHERE channel = :channel AND id > 0",
+            &auth,
+            &row_filters
+        ));
+
+        let general = template.bind(&[param("channel", "general")])?;
+        let query = template.compile_bound(&db, &tx, &auth, &row_filters, &general)?;
+        let result = QuerySet(vec![query]).eval(&db, &mut tx, auth)?;
+        assert_eq!(rows(&result), [general_1.clone()]);
+
+        // The plan is evaluated once, and the rows are handed out by the channel they match.
+        let ops = [&general_2, &random]
+            .map(|row| TableOp {
+                op_type: 1,
+                row_pk: row.to_data_key().to_bytes(),
+                row: row.clone(),
+            })
+            .into();
+        let update = DatabaseUpdate {
+            tables: vec![DatabaseTableUpdate {
+                table_id,
+                table_name: "message".to_string(),
+                ops,
+            }],
+        };
+        let incr = template.eval_incr(&db, &mut tx, &update, auth)?;
+        assert_eq!(incr.len(), 2);
+        assert_eq!(rows(&incr[&general]), [general_2]);
+        let random_values = template.bind(&[param("channel", "random")])?;
+        assert_eq!(rows(&incr[&random_values]), [random]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_template_invalid_params() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        let head = ProductType::from([("id", AlgebraicType::U64), ("channel", AlgebraicType::String)]);
+        create_table_with_rows(&db, &mut tx, "message", head, &[])?;
+
+        let auth = AuthCtx::for_testing();
+        let row_filters = RowFilters::default();
+        let compile = |sql| QueryTemplate::compile(&db, &tx, &auth, &row_filters, sql);
+
+        // Parameters must be compared for equality with a column.
+        for sql in [
+            "SELECT * FROM message WHERE id > :id",
+            "SELECT * FROM message WHERE channel = :channel OR id = 1",
+            "SELECT id FROM message WHERE channel = :channel",
+        ] {
+            assert!(
+                matches!(
+                    compile(sql),
+                    Err(DBError::Subscription(SubscriptionError::UnsupportedParameter { .. }))
+                ),
+                "{sql}"
+            );
+        }
+
+        let template = compile("SELECT * FROM message WHERE channel = :channel")?;
+        for params in [
+            vec![],
+            vec![param("channel", "general"), param("id", "1")],
+            vec![QueryParam {
+                name: "channel".to_string(),
+                value: vec![0xff],
+            }],
+        ] {
+            assert!(matches!(
+                template.bind(&params),
+                Err(DBError::Subscription(SubscriptionError::InvalidParameter { .. }))
+            ));
+        }
+
+        Ok(())
+    }
+}
@@ -10,11 +10,12 @@ use spacetimedb_lib::identity::AuthCtx;
 use spacetimedb_lib::relation::{DbTable, FieldExpr, FieldName, Relation};
 use spacetimedb_lib::relation::{Header, MemTable, RelIter, RelValue, RowCount, Table};
 use spacetimedb_lib::table::ProductTypeMeta;
+use spacetimedb_lib::IndexType;
 use spacetimedb_sats::{AlgebraicValue, ProductValue};
 use spacetimedb_vm::dsl::mem_table;
 use spacetimedb_vm::env::EnvDb;
 use spacetimedb_vm::errors::ErrorVm;
-use spacetimedb_vm::eval::IterRows;
+use spacetimedb_vm::eval::{build_aggregate, build_limit, sort_cmp, IterRows};
 use spacetimedb_vm::expr::*;
 use spacetimedb_vm::program::{ProgramRef, ProgramVm};
 use spacetimedb_vm::rel_ops::RelOps;
@@ -79,6 +80,12 @@ pub fn build_query<'a>(
                 let iter = join_inner(stdb, tx, result, join, false)?;
                 Box::new(iter)
             }
+            Query::Sort(keys) => {
+                let cmp = sort_cmp(result.head(), &keys)?;
+                Box::new(result.sort_by(cmp))
+            }
+            Query::Limit(limit) => build_limit(result, limit),
+            Query::Aggregate(group) => build_aggregate(result, group)?,
         }
     }
     Ok(result)
@@ -335,6 +342,7 @@ impl<'db, 'tx> DbProgram<'db, 'tx> {
                     cols: NonEmpty::new(i as u32),
                     name: format!("{}_{}_idx", table_name, i),
                     is_unique: true,
+                    index_type: IndexType::BTree,
                 });
             }
             cols.push(ColumnDef {
@@ -719,6 +727,7 @@ pub(crate) mod tests {
                 table_id,
                 cols: NonEmpty::new(0),
                 is_unique: true,
+                index_type: IndexType::BTree,
             })
                 .into(),
             q,
@@ -23,6 +23,7 @@ pub mod auth;
 #[cfg(feature = "serde")]
 pub mod recovery;
 pub mod relation;
+pub mod schedule;
 pub mod table;
 #[cfg(feature = "cli")]
 pub mod util;
@@ -183,6 +184,10 @@ pub struct ModuleDef {
 #[derive(Debug, Clone, de::Deserialize, ser::Serialize)]
 pub enum MiscModuleExport {
     TypeAlias(TypeAlias),
+    ReducerAuth(ReducerAuth),
+    RowFilter(RowFilter),
+    RepeatingReducer(RepeatingReducer),
+    ColumnDefault(ColumnDefault),
 }
 
 #[derive(Debug, Clone, de::Deserialize, ser::Serialize)]
@@ -191,6 +196,54 @@ pub struct TypeAlias {
     pub ty: sats::AlgebraicTypeRef,
 }
 
+/// Restricts who may call the reducer `reducer` from a client.
+///
+/// A reducer without a `ReducerAuth` may be called by any identity.
+#[derive(Debug, Clone, PartialEq, Eq, de::Deserialize, ser::Serialize)]
+pub struct ReducerAuth {
+    pub reducer: String,
+    pub rule: ReducerAuthRule,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, de::Deserialize, ser::Serialize)]
+pub enum ReducerAuthRule {
+    /// Only the owner of the database may call the reducer.
+    Owner,
+    /// The call is allowed if the guard function of this name,
+    /// exported by the module as `__guard__{reducer}`, returns true.
+    Guard(String),
+}
+
+/// Restricts the rows of the table `table` that a client other than the owner may read.
+///
+/// `filter` is a `SQL` condition, as in a `WHERE` clause over `table`,
+/// in which `:sender` is the `Identity` of the client, e.g., `owner = :sender`.
+#[derive(Debug, Clone, PartialEq, Eq, de::Deserialize, ser::Serialize)]
+pub struct RowFilter {
+    pub table: String,
+    pub filter: String,
+}
+
+/// Schedules the reducer `reducer`, which takes no arguments, to run as `repeat` says
+/// from when the module is published, with the runs it misses while the host is down handled per `misfire`.
+#[derive(Debug, Clone, PartialEq, Eq, de::Deserialize, ser::Serialize)]
+pub struct RepeatingReducer {
+    pub reducer: String,
+    pub repeat: schedule::Repeat,
+    pub misfire: schedule::MisfirePolicy,
+}
+
+/// The value of the column `column` of the table `table` in the existing rows,
+/// when an update of the module appends the column to the table.
+///
+/// `value` is encoded in BSATN, with the type of the column.
+#[derive(Debug, Clone, PartialEq, Eq, de::Deserialize, ser::Serialize)]
+pub struct ColumnDefault {
+    pub table: String,
+    pub column: String,
+    pub value: Vec<u8>,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord, de::Deserialize, ser::Serialize)]
 pub struct IndexDef {
     pub name: String,
@@ -204,6 +257,27 @@ pub enum IndexType {
     Hash,
 }
 
+impl IndexType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::BTree => "btree",
+            Self::Hash => "hash",
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a str> for IndexType {
+    type Error = &'a str;
+
+    fn try_from(value: &'a str) -> Result<Self, Self::Error> {
+        Ok(match value {
+            "btree" => Self::BTree,
+            "hash" => Self::Hash,
+            x => return Err(x),
+        })
+    }
+}
+
 // NOTE: Duplicated in `crates/bindings-macro/src/lib.rs`
 bitflags::bitflags! {
     #[derive(Debug, Default, Clone, Copy, Eq, PartialEq, PartialOrd, Ord)]
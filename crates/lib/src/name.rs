@@ -67,6 +67,15 @@ pub enum PublishResult {
         op: PublishOp,
     },
 
+    /// What publishing would do, in dry-run mode, which doesn't change anything.
+    DryRun {
+        /// Whether the database would be created or updated.
+        op: PublishOp,
+        /// The changes to the schema of the existing database, `None` if it
+        /// would be created, or cleared and created anew.
+        diff: Option<SchemaDiff>,
+    },
+
     // TODO: below variants are obsolete with control db module
     /// The top level domain for the database name is not registered. For example:
     ///
@@ -86,6 +95,34 @@ pub enum PublishResult {
     PermissionDenied { domain: DomainName },
 }
 
+/// The changes an update to its module would make to the schema of a database.
+///
+/// The update is rejected if there are any `tainted_tables`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SchemaDiff {
+    /// The tables to create.
+    pub tables_to_create: Vec<String>,
+    /// The tables whose columns change, with their rows migrated either
+    /// automatically or by the `migrate` reducer of the module.
+    pub tables_to_migrate: Vec<String>,
+    /// The indexes to create.
+    pub indexes_to_create: Vec<String>,
+    /// The indexes to drop.
+    pub indexes_to_drop: Vec<String>,
+    /// The tables whose schema can't be updated.
+    pub tainted_tables: Vec<TaintedTable>,
+}
+
+/// A table whose schema can't be updated, and why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TaintedTable {
+    pub table: String,
+    /// An explanation for each offending change, e.g. to a column.
+    pub reasons: Vec<String>,
+}
+
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DnsLookupResponse {
@@ -0,0 +1,572 @@
+//! The recurring schedules of reducers, i.e. calendar intervals and cron expressions,
+//! and what happens to the firings of a schedule that were missed while the host was down.
+//!
+//! Times are in microseconds since the Unix epoch, in UTC.
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use spacetimedb_sats::{impl_deserialize, impl_serialize};
+
+use crate::de::Error;
+
+const MICROS_PER_MINUTE: u64 = 60_000_000;
+const MINUTES_PER_DAY: u64 = 24 * 60;
+
+/// The number of days a cron expression is searched for its next firing,
+/// which covers the eight years between two February 29ths.
+const CRON_SEARCH_DAYS: u64 = 9 * 366;
+
+/// What happens to the firings of a repeating schedule
+/// that were missed while the host was down, when it restarts.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum MisfirePolicy {
+    /// The missed firings are dropped,
+    /// and the schedule resumes at its next firing after the restart.
+    Skip,
+    /// The missed firings are coalesced into a single firing on restart.
+    #[default]
+    FireOnce,
+    /// Every missed firing is run, one after the other, on restart.
+    FireAll,
+}
+
+impl MisfirePolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Skip => "skip",
+            Self::FireOnce => "fire_once",
+            Self::FireAll => "fire_all",
+        }
+    }
+
+    pub fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            0 => Self::Skip,
+            1 => Self::FireOnce,
+            2 => Self::FireAll,
+            _ => return None,
+        })
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Self::Skip => 0,
+            Self::FireOnce => 1,
+            Self::FireAll => 2,
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a str> for MisfirePolicy {
+    type Error = &'a str;
+
+    fn try_from(value: &'a str) -> Result<Self, Self::Error> {
+        Ok(match value {
+            "skip" => Self::Skip,
+            "fire_once" => Self::FireOnce,
+            "fire_all" => Self::FireAll,
+            x => return Err(x),
+        })
+    }
+}
+
+impl_serialize!([] MisfirePolicy, (self, ser) => ser.serialize_str(self.as_str()));
+impl_deserialize!([] MisfirePolicy, de => {
+    let value = de.deserialize_str_slice()?;
+    MisfirePolicy::try_from(value).map_err(|x| {
+        Error::custom(format!(
+            "DecodeError for MisfirePolicy: `{x}`. Expected 'skip' | 'fire_once' | 'fire_all'"
+        ))
+    })
+});
+
+/// Why a [Repeat] couldn't be parsed.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum ParseRepeatError {
+    #[error("expected `every <interval>` or `cron <expression>`, found `{0}`")]
+    UnknownKind(String),
+    #[error("invalid interval `{0}`, expected e.g. `1mo`, `2w` or `1h 30m`")]
+    Interval(String),
+    #[error("the interval is zero")]
+    ZeroInterval,
+    #[error("invalid cron expression `{expr}`: {reason}")]
+    Cron { expr: String, reason: String },
+}
+
+/// How a schedule repeats after its first firing.
+///
+/// Its textual form, as stored in `st_scheduled`, is either
+/// `every <interval>`, e.g. `every 1mo` or `every 1h 30m`, or `cron <expression>`, e.g. `cron 0 9 * * mon-fri`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Repeat {
+    /// Every `months` calendar months plus `micros` microseconds.
+    ///
+    /// Adding a month keeps the day of the month,
+    /// or clamps it to the last day of a shorter month.
+    Every { months: u32, micros: u64 },
+    /// At the minutes matching a cron expression.
+    Cron(Cron),
+}
+
+impl Repeat {
+    /// Repeats every `interval`.
+    pub fn every(interval: Duration) -> Result<Self, ParseRepeatError> {
+        Self::every_months(0, interval)
+    }
+
+    /// Repeats every `months` calendar months plus `interval`.
+    pub fn every_months(months: u32, interval: Duration) -> Result<Self, ParseRepeatError> {
+        let micros = interval.as_micros().try_into().unwrap_or(u64::MAX);
+        if months == 0 && micros == 0 {
+            return Err(ParseRepeatError::ZeroInterval);
+        }
+        Ok(Self::Every { months, micros })
+    }
+
+    /// Repeats at the minutes matching the cron expression `expr`.
+    pub fn cron(expr: &str) -> Result<Self, ParseRepeatError> {
+        expr.parse().map(Self::Cron)
+    }
+
+    /// Returns the firing that follows the one at `prev`,
+    /// or `None` if there is no such firing that fits in a `u64`.
+    pub fn next(&self, prev: u64) -> Option<u64> {
+        match self {
+            Self::Every { months, micros } => add_months(prev, *months)?.checked_add(*micros),
+            Self::Cron(cron) => cron.next(prev),
+        }
+    }
+
+    /// Returns the first firing that follows the one at `prev` and is strictly after `now`.
+    pub fn next_after(&self, prev: u64, now: u64) -> Option<u64> {
+        match self {
+            Self::Every { months: 0, micros } => {
+                let skipped = now.saturating_sub(prev) / micros;
+                prev.checked_add(skipped.checked_add(1)?.checked_mul(*micros)?)
+            }
+            Self::Every { .. } => {
+                let mut next = self.next(prev)?;
+                while next <= now {
+                    next = self.next(next)?;
+                }
+                Some(next)
+            }
+            Self::Cron(cron) => cron.next(prev.max(now)),
+        }
+    }
+}
+
+impl fmt::Display for Repeat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Every { months, micros } => {
+                f.write_str("every")?;
+                match (months / 12, months % 12) {
+                    (0, 0) => {}
+                    (years, 0) => write!(f, " {years}y")?,
+                    _ => write!(f, " {months}mo")?,
+                }
+                let mut rest = *micros;
+                for (unit, unit_micros) in INTERVAL_UNITS {
+                    if rest >= unit_micros {
+                        write!(f, " {}{unit}", rest / unit_micros)?;
+                        rest %= unit_micros;
+                    }
+                }
+                Ok(())
+            }
+            Self::Cron(cron) => write!(f, "cron {cron}"),
+        }
+    }
+}
+
+/// The units of the microseconds of an interval, from the largest, as displayed.
+const INTERVAL_UNITS: [(&str, u64); 7] = [
+    ("w", 7 * MINUTES_PER_DAY * MICROS_PER_MINUTE),
+    ("d", MINUTES_PER_DAY * MICROS_PER_MINUTE),
+    ("h", 60 * MICROS_PER_MINUTE),
+    ("m", MICROS_PER_MINUTE),
+    ("s", 1_000_000),
+    ("ms", 1_000),
+    ("us", 1),
+];
+
+impl FromStr for Repeat {
+    type Err = ParseRepeatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(interval) = s.strip_prefix("every ") {
+            let (months, micros) =
+                parse_interval(interval).ok_or_else(|| ParseRepeatError::Interval(interval.into()))?;
+            Self::every_months(months, Duration::from_micros(micros))
+        } else if let Some(expr) = s.strip_prefix("cron ") {
+            Self::cron(expr)
+        } else {
+            Err(ParseRepeatError::UnknownKind(s.into()))
+        }
+    }
+}
+
+impl_serialize!([] Repeat, (self, ser) => ser.serialize_str(&self.to_string()));
+impl_deserialize!([] Repeat, de => {
+    let value = de.deserialize_str_slice()?;
+    value.parse().map_err(|e| Error::custom(format!("DecodeError for Repeat: {e}")))
+});
+
+/// Parses an interval such as `1mo 2d` or `1h30m` into months and microseconds.
+fn parse_interval(s: &str) -> Option<(u32, u64)> {
+    let (mut months, mut micros) = (0u32, 0u64);
+    let mut rest = s.trim_start();
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let n: u64 = rest[..digits].parse().ok()?;
+        rest = rest[digits..].trim_start();
+        let letters = rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+        let unit = &rest[..letters];
+        rest = rest[letters..].trim_start_matches([' ', ',']);
+        let unit_months = match unit {
+            "mo" | "month" | "months" => Some(1),
+            "y" | "year" | "years" => Some(12),
+            _ => None,
+        };
+        if let Some(unit_months) = unit_months {
+            months = months.checked_add(u32::try_from(n).ok()?.checked_mul(unit_months)?)?;
+            continue;
+        }
+        let unit_micros = match unit {
+            "us" => 1,
+            "ms" => 1_000,
+            "s" | "sec" | "secs" | "second" | "seconds" => 1_000_000,
+            "m" | "min" | "mins" | "minute" | "minutes" => MICROS_PER_MINUTE,
+            "h" | "hr" | "hrs" | "hour" | "hours" => 60 * MICROS_PER_MINUTE,
+            "d" | "day" | "days" => MINUTES_PER_DAY * MICROS_PER_MINUTE,
+            "w" | "week" | "weeks" => 7 * MINUTES_PER_DAY * MICROS_PER_MINUTE,
+            _ => return None,
+        };
+        micros = micros.checked_add(n.checked_mul(unit_micros)?)?;
+    }
+    Some((months, micros))
+}
+
+/// A cron expression, in UTC, with the five fields
+/// `minute hour day-of-month month day-of-week`.
+///
+/// Each field is `*` or a comma separated list of values or ranges `a-b`,
+/// optionally followed by a step `/n`.
+/// Months and days of the week may be named, e.g. `jan` or `mon`,
+/// and Sunday is both `0` and `7`.
+/// As in Vixie cron, when both the day of the month and the day of the week are restricted,
+/// a day matches if either of them does.
+/// An expression whose days of the month never occur in its months, e.g. `0 0 30 2 *`, is rejected.
+/// The nicknames `@yearly`, `@monthly`, `@weekly`, `@daily` and `@hourly` are also accepted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    expr: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+const MONTH_NAMES: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const DAY_NAMES: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+impl Cron {
+    /// Returns the first minute matching the expression strictly after `prev`.
+    pub fn next(&self, prev: u64) -> Option<u64> {
+        let start = prev / MICROS_PER_MINUTE + 1;
+        let start_day = start / MINUTES_PER_DAY;
+        for day in start_day..start_day + CRON_SEARCH_DAYS {
+            let (_, month, day_of_month) = civil_from_days(day);
+            // The 1st of January 1970 was a Thursday.
+            let day_of_week = (day + 4) % 7;
+            if !bit(self.months, month) || !self.matches_day(day_of_month, day_of_week) {
+                continue;
+            }
+            let first = if day == start_day { start % MINUTES_PER_DAY } else { 0 };
+            let found = (first..MINUTES_PER_DAY).find(|m| bit(self.hours, m / 60) && bit(self.minutes, m % 60));
+            if let Some(minute) = found {
+                return (day * MINUTES_PER_DAY + minute).checked_mul(MICROS_PER_MINUTE);
+            }
+        }
+        None
+    }
+
+    fn matches_day(&self, day_of_month: u64, day_of_week: u64) -> bool {
+        let dom = bit(self.days_of_month, day_of_month);
+        let dow = bit(self.days_of_week, day_of_week);
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => dom || dow,
+            _ => dom && dow,
+        }
+    }
+}
+
+fn bit(set: u64, n: u64) -> bool {
+    set & (1 << n) != 0
+}
+
+impl fmt::Display for Cron {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expr)
+    }
+}
+
+impl FromStr for Cron {
+    type Err = ParseRepeatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = |reason: String| ParseRepeatError::Cron { expr: s.into(), reason };
+        let expanded = match s.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            other => other,
+        };
+        let fields = expanded.split_whitespace().collect::<Vec<_>>();
+        let [minutes, hours, days_of_month, months, days_of_week] = fields[..] else {
+            return Err(err(format!("expected 5 fields, found {}", fields.len())));
+        };
+        let days_of_week = parse_cron_field(days_of_week, 0, 7, DAY_NAMES).map_err(err)?;
+        let cron = Self {
+            expr: fields.join(" "),
+            minutes: parse_cron_field(minutes, 0, 59, &[]).map_err(err)?,
+            hours: parse_cron_field(hours, 0, 23, &[]).map_err(err)?,
+            days_of_month: parse_cron_field(days_of_month, 1, 31, &[]).map_err(err)?,
+            months: parse_cron_field(months, 1, 12, MONTH_NAMES).map_err(err)?,
+            // Sunday is both `0` and `7`.
+            days_of_week: (days_of_week | days_of_week >> 7) & 0x7f,
+            any_day_of_month: days_of_month.starts_with('*'),
+            any_day_of_week: fields[4].starts_with('*'),
+        };
+        // Unless the day of the week may match instead, a day of the month must occur in one of the months,
+        // or `next` would search years of days on every call to find nothing.
+        let day_of_month_decides = cron.any_day_of_month || cron.any_day_of_week;
+        if day_of_month_decides
+            && !(1..=12).any(|month| cron.days_of_month & days_of_month_mask(month, cron.months) != 0)
+        {
+            return Err(err("no day of the month exists in the months".into()));
+        }
+        Ok(cron)
+    }
+}
+
+/// Returns the days of `month`, as bits, if `month` is in the set `months`, else no day.
+fn days_of_month_mask(month: u64, months: u64) -> u64 {
+    if !bit(months, month) {
+        return 0;
+    }
+    // 2000 was a leap year, so February has its 29th.
+    let last = days_in_month(2000, month);
+    ((1 << (last + 1)) - 1) & !1
+}
+
+/// Parses a cron `field` whose values are within `min..=max`
+/// into the set of its values, as bits.
+///
+/// The `names` are the names of the values from `min`.
+fn parse_cron_field(field: &str, min: u64, max: u64, names: &[&str]) -> Result<u64, String> {
+    let value = |s: &str| -> Result<u64, String> {
+        let lower = s.to_ascii_lowercase();
+        let n = match names.iter().position(|name| *name == lower) {
+            Some(i) => i as u64 + min,
+            None => s.parse().map_err(|_| format!("invalid value `{s}`"))?,
+        };
+        if (min..=max).contains(&n) {
+            Ok(n)
+        } else {
+            Err(format!("`{s}` is not within {min}-{max}"))
+        }
+    };
+    let mut set = 0;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => match step.parse::<u64>() {
+                Ok(step) if step > 0 => (range, Some(step)),
+                _ => return Err(format!("invalid step `{step}`")),
+            },
+            None => (item, None),
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (value(start)?, value(end)?),
+            // A single value with a step, e.g. `5/15`, runs to the end of the range.
+            None if step.is_some() => (value(range)?, max),
+            None => {
+                let n = value(range)?;
+                (n, n)
+            }
+        };
+        if start > end {
+            return Err(format!("invalid range `{range}`"));
+        }
+        for n in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            set |= 1 << n;
+        }
+    }
+    Ok(set)
+}
+
+/// Returns the time `months` calendar months after `time`,
+/// clamping its day to the last day of the month.
+fn add_months(time: u64, months: u32) -> Option<u64> {
+    if months == 0 {
+        return Some(time);
+    }
+    let micros_per_day = MINUTES_PER_DAY * MICROS_PER_MINUTE;
+    let (day, time_of_day) = (time / micros_per_day, time % micros_per_day);
+    let (year, month, day_of_month) = civil_from_days(day);
+    let month0 = year * 12 + (month - 1) + months as u64;
+    let (year, month) = (month0 / 12, month0 % 12 + 1);
+    let day = days_from_civil(year, month, day_of_month.min(days_in_month(year, month)));
+    day.checked_mul(micros_per_day)?.checked_add(time_of_day)
+}
+
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Returns the `(year, month, day)` of the `days` since the 1st of January 1970,
+/// after Howard Hinnant's `civil_from_days`.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as u64;
+    (year, month, day)
+}
+
+/// Returns the days since the 1st of January 1970 of a date from 1970 on,
+/// after Howard Hinnant's `days_from_civil`.
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = year - (month <= 2) as u64;
+    let era = year / 400;
+    let yoe = year % 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The microseconds since the epoch of a UTC date and time.
+    fn at(year: u64, month: u64, day: u64, hour: u64, minute: u64) -> u64 {
+        (days_from_civil(year, month, day) * MINUTES_PER_DAY + hour * 60 + minute) * MICROS_PER_MINUTE
+    }
+
+    #[test]
+    fn test_civil_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+        for days in [0, 59, 365, 11_016, 11_017, 19_782, 47_541] {
+            let (y, m, d) = civil_from_days(days);
+            assert_eq!(days_from_civil(y, m, d), days);
+        }
+    }
+
+    #[test]
+    fn test_every() {
+        let monthly: Repeat = "every 1mo".parse().unwrap();
+        assert_eq!(monthly.next(at(2024, 1, 31, 9, 0)), Some(at(2024, 2, 29, 9, 0)));
+        assert_eq!(monthly.next(at(2024, 12, 15, 0, 0)), Some(at(2025, 1, 15, 0, 0)));
+
+        let every: Repeat = "every 1h 30m".parse().unwrap();
+        assert_eq!(every, Repeat::every(Duration::from_secs(90 * 60)).unwrap());
+        assert_eq!(every.to_string(), "every 1h 30m");
+        assert_eq!(every.next(at(2024, 1, 1, 23, 0)), Some(at(2024, 1, 2, 0, 30)));
+        assert_eq!(
+            every.next_after(at(2024, 1, 1, 0, 0), at(2024, 1, 1, 3, 0)),
+            Some(at(2024, 1, 1, 4, 30))
+        );
+
+        let yearly = Repeat::every_months(12, Duration::ZERO).unwrap();
+        assert_eq!(yearly.to_string(), "every 1y");
+        assert_eq!("every 1y".parse(), Ok(yearly));
+        assert_eq!("every 13mo 2d".parse::<Repeat>().unwrap().to_string(), "every 13mo 2d");
+
+        assert_eq!("every 0s".parse::<Repeat>(), Err(ParseRepeatError::ZeroInterval));
+        assert!(matches!(
+            "every 3 fortnights".parse::<Repeat>(),
+            Err(ParseRepeatError::Interval(_))
+        ));
+        assert!(matches!(
+            "hourly".parse::<Repeat>(),
+            Err(ParseRepeatError::UnknownKind(_))
+        ));
+    }
+
+    #[test]
+    fn test_cron() {
+        let weekdays = Repeat::cron("30 9 * * mon-fri").unwrap();
+        assert_eq!(weekdays.to_string(), "cron 30 9 * * mon-fri");
+        // The 5th of January 2024 was a Friday.
+        assert_eq!(weekdays.next(at(2024, 1, 5, 9, 30)), Some(at(2024, 1, 8, 9, 30)));
+        assert_eq!(weekdays.next(at(2024, 1, 5, 9, 29)), Some(at(2024, 1, 5, 9, 30)));
+
+        let quarter = Repeat::cron("*/15 * * * *").unwrap();
+        assert_eq!(quarter.next(at(2024, 1, 1, 23, 50)), Some(at(2024, 1, 2, 0, 0)));
+        assert_eq!(
+            quarter.next_after(at(2024, 1, 1, 0, 0), at(2024, 1, 1, 10, 7)),
+            Some(at(2024, 1, 1, 10, 15))
+        );
+
+        // Either the 1st of the month or a Sunday.
+        let either = Repeat::cron("0 0 1 * 7").unwrap();
+        assert_eq!(either.next(at(2024, 1, 1, 0, 0)), Some(at(2024, 1, 7, 0, 0)));
+
+        let leap = Repeat::cron("0 12 29 feb *").unwrap();
+        assert_eq!(leap.next(at(2024, 3, 1, 0, 0)), Some(at(2028, 2, 29, 12, 0)));
+        // The 30th of February never comes, but a Monday in February does.
+        assert!(matches!(Repeat::cron("0 0 30 2 *"), Err(ParseRepeatError::Cron { .. })));
+        assert!(Repeat::cron("0 0 30 2 mon").unwrap().next(0).is_some());
+
+        assert_eq!(Cron::from_str("@daily"), Cron::from_str("0 0 * * *"));
+        for invalid in [
+            "* * * *",
+            "60 * * * *",
+            "* * 0 * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "0 0 31 apr,jun,sep,nov *",
+            "0 0 30-31 feb */2",
+        ] {
+            assert!(
+                matches!(Repeat::cron(invalid), Err(ParseRepeatError::Cron { .. })),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_misfire_policy() {
+        for policy in [MisfirePolicy::Skip, MisfirePolicy::FireOnce, MisfirePolicy::FireAll] {
+            assert_eq!(MisfirePolicy::try_from(policy.as_str()), Ok(policy));
+            assert_eq!(MisfirePolicy::from_u8(policy.to_u8()), Some(policy));
+        }
+    }
+}
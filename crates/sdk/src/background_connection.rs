@@ -1,17 +1,22 @@
 use crate::callbacks::{
-    CredentialStore, DbCallbacks, DisconnectCallbacks, ReducerCallbacks, SubscriptionAppliedCallbacks,
+    parse_status, CredentialStore, DbCallbacks, DisconnectCallbacks, ReducerCallbacks, SubscriptionAppliedCallbacks,
 };
 use crate::client_api_messages;
 use crate::client_cache::{ClientCache, ClientCacheView, RowCallbackReminders};
 use crate::global_connection::CLIENT_CACHE;
 use crate::identity::Credentials;
-use crate::reducer::{AnyReducerEvent, Reducer};
+use crate::reducer::{AnyReducerEvent, InvokeFuture, Reducer, Status};
 use crate::spacetime_module::SpacetimeModule;
-use crate::websocket::DbConnection;
+use crate::websocket::WsConnection;
+use crate::{ParameterizedQuery, QueryId, ReconnectPolicy};
 use anyhow::{Context, Result};
 use futures::stream::StreamExt;
-use futures_channel::mpsc;
+use futures_channel::{mpsc, oneshot};
+use prost::Message as _;
 use spacetimedb_sats::bsatn;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::{Arc, Mutex};
 use tokio::runtime::{self, Builder, Runtime};
 use tokio::task::JoinHandle;
@@ -19,6 +24,10 @@ use tokio::task::JoinHandle;
 /// A thread-safe mutable place that can be shared by multiple referents.
 type SharedCell<T> = Arc<Mutex<T>>;
 
+/// The reducer calls made by `invoke_reducer_async` whose outcome hasn't been received yet,
+/// keyed on their request id.
+type PendingCalls = HashMap<u32, oneshot::Sender<Status>>;
+
 pub struct BackgroundDbConnection {
     /// `Some` if not within the context of an outer runtime. The `Runtime` must
     /// then live as long as `Self`.
@@ -26,8 +35,10 @@ pub struct BackgroundDbConnection {
     runtime: Option<Runtime>,
 
     handle: runtime::Handle,
-    /// None if not yet connected.
-    send_chan: Option<mpsc::UnboundedSender<client_api_messages::Message>>,
+    /// None if not yet connected, or after `disconnect`.
+    ///
+    /// Shared with the `receiver_loop`, which replaces the sender when it reconnects.
+    send_chan: SharedCell<Option<mpsc::UnboundedSender<client_api_messages::Message>>>,
     #[allow(unused)]
     /// None if not yet connected.
     websocket_loop_handle: Option<JoinHandle<()>>,
@@ -60,6 +71,131 @@ pub struct BackgroundDbConnection {
     pub(crate) reducer_callbacks: SharedCell<ReducerCallbacks>,
     pub(crate) subscription_callbacks: SharedCell<SubscriptionAppliedCallbacks>,
     pub(crate) disconnect_callbacks: SharedCell<DisconnectCallbacks>,
+
+    /// The id of the next query added by `subscribe_add`.
+    next_query_id: AtomicU32,
+
+    pending_calls: SharedCell<PendingCalls>,
+    /// The request id of the next reducer call made by `invoke_reducer_async`.
+    /// Starts at 1, as a request id of 0 means the caller isn't waiting for the outcome.
+    next_request_id: AtomicU32,
+
+    subscription: SharedCell<SubscriptionState>,
+    /// How to reconnect when the connection drops, or None not to.
+    reconnect_policy: Option<ReconnectPolicy>,
+    /// The file to which the client cache is saved when the connection ends,
+    /// and from which it's loaded when connecting, or None not to persist it.
+    cache_file: Option<PathBuf>,
+}
+
+/// The subscription of a connection, kept to resume it after reconnecting,
+/// or from the client cache saved to a file.
+#[derive(Default)]
+struct SubscriptionState {
+    /// The most recent `Subscribe` message, with the queries added by `subscribe_add` since,
+    /// and without a `resume_from`.
+    ///
+    /// None if not yet subscribed.
+    subscribe: Option<client_api_messages::Subscribe>,
+    /// The transaction as of which the client cache holds the rows matching `subscribe`,
+    /// or None if it isn't known, as while a new `Subscribe` awaits its `SubscriptionUpdate`,
+    /// or after `subscribe_add` or `unsubscribe`, whose rows are as of no known transaction.
+    resume_point: Option<client_api_messages::ResumeFrom>,
+    /// Whether the next `Subscribe` may resume from `resume_point`,
+    /// as it was loaded from a file, and the database isn't sending updates to it yet.
+    resume_next_subscribe: bool,
+}
+
+impl SubscriptionState {
+    /// Returns `subscribe` resuming from `resume_point`, if both are known.
+    fn resuming_subscribe(&self) -> Option<client_api_messages::Subscribe> {
+        let resume_from = self.resume_point.clone();
+        self.subscribe.clone().map(|subscribe| client_api_messages::Subscribe {
+            resume_from,
+            ..subscribe
+        })
+    }
+}
+
+/// What the `receiver_loop` needs to re-establish a connection which dropped.
+struct Reconnect {
+    policy: ReconnectPolicy,
+    handle: runtime::Handle,
+    spacetimedb_uri: http::Uri,
+    db_name: String,
+    credentials: SharedCell<CredentialStore>,
+    send_chan: SharedCell<Option<mpsc::UnboundedSender<client_api_messages::Message>>>,
+    subscription: SharedCell<SubscriptionState>,
+}
+
+impl Reconnect {
+    /// Connect again, with exponential backoff between failed attempts,
+    /// and resubscribe to the queries of the dropped connection.
+    ///
+    /// Returns the receiver of the messages from the new connection,
+    /// and whether the subscription was sent again,
+    /// or `None` after a call to `disconnect` or once the attempts run out.
+    async fn reconnect(&self) -> Option<(mpsc::UnboundedReceiver<client_api_messages::Message>, bool)> {
+        let mut delay = self.policy.initial_delay;
+        let mut attempts = 0;
+        loop {
+            if self.send_chan.lock().expect("send_chan Mutex is poisoned").is_none() {
+                return None;
+            }
+            if self.policy.max_attempts.is_some_and(|max| attempts >= max) {
+                log::error!(
+                    "Giving up on reconnecting to {} after {} attempts",
+                    self.db_name,
+                    attempts
+                );
+                return None;
+            }
+            attempts += 1;
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(self.policy.max_delay);
+
+            let (credentials, client_address) = {
+                let mut lock = self.credentials.lock().expect("CredentialStore Mutex is poisoned");
+                (lock.credentials(), lock.get_or_init_address())
+            };
+            let connection = match WsConnection::connect(
+                self.spacetimedb_uri.clone(),
+                &self.db_name,
+                credentials.as_ref(),
+                client_address,
+            )
+            .await
+            {
+                Ok(connection) => connection,
+                Err(e) => {
+                    log::warn!("Failed to reconnect to {}: {:?}", self.db_name, e);
+                    continue;
+                }
+            };
+
+            // Hold the locks until the new sender is published, taking them in the same order
+            // as `subscribe_parameterized`, so that a concurrent `disconnect` either prevents it or closes it,
+            // and a concurrent subscription change is either resent here or fails on the dropped connection.
+            let subscription = self.subscription.lock().expect("SubscriptionState Mutex is poisoned");
+            let mut send_chan_lock = self.send_chan.lock().expect("send_chan Mutex is poisoned");
+            if send_chan_lock.is_none() {
+                return None;
+            }
+            let (_websocket_loop_handle, recv_chan, send_chan) = connection.spawn_message_loop(&self.handle);
+            // The client cache kept across the reconnection holds the rows as of `resume_point`,
+            // so the database only sends the changes since, if it still can.
+            let subscribe = subscription.resuming_subscribe();
+            let resubscribed = subscribe.is_some();
+            if let Some(subscribe) = subscribe {
+                let _ = send_chan.unbounded_send(client_api_messages::Message {
+                    r#type: Some(client_api_messages::message::Type::Subscribe(subscribe)),
+                });
+            }
+            *send_chan_lock = Some(send_chan);
+            log::info!("Reconnected to {}", self.db_name);
+            return Some((recv_chan, resubscribed));
+        }
+    }
 }
 
 // When called from within an async context, return a handle to it (and no
@@ -159,11 +295,30 @@ fn process_transaction_update(
     client_api_messages::TransactionUpdate {
         subscription_update,
         event,
+        request_id,
+        ..
     }: client_api_messages::TransactionUpdate,
     client_cache: &Mutex<Option<ClientCacheView>>,
     db_callbacks: &Mutex<DbCallbacks>,
     reducer_callbacks: &Mutex<ReducerCallbacks>,
+    pending_calls: &Mutex<PendingCalls>,
 ) {
+    // Take the status before the `event` is consumed,
+    // and resolve the pending call once the update has been applied to the client cache.
+    let pending_call = (request_id != 0)
+        .then(|| {
+            pending_calls
+                .lock()
+                .expect("PendingCalls Mutex is poisoned")
+                .remove(&request_id)
+        })
+        .flatten()
+        .zip(
+            event
+                .as_ref()
+                .and_then(|event| parse_status(event.status, event.message.clone())),
+        );
+
     // Process the updated tables in the `subscription_update`.
     if let Some(update) = subscription_update {
         let mut callback_reminders = RowCallbackReminders::new_for_subscription_update(&update);
@@ -184,11 +339,63 @@ fn process_transaction_update(
     } else {
         log::error!("Received TransactionUpdate with no SubscriptionUpdate");
     }
+
+    if let Some((send, status)) = pending_call {
+        // The caller may have dropped its `InvokeFuture`.
+        let _ = send.send(status);
+    }
+}
+
+/// Saves the rows of `client_cache` to the file at `path`, along with the `subscribe` they match,
+/// if they're known to be as of a transaction, from which a later connection can resume.
+///
+/// The file holds the `Subscribe` with its `resume_from`,
+/// followed by a `SubscriptionUpdate` inserting every row, both length-delimited.
+fn save_client_cache(
+    path: &Path,
+    client_cache: &ClientCache,
+    subscribe: Option<client_api_messages::Subscribe>,
+) -> Result<()> {
+    let Some(subscribe) = subscribe.filter(|subscribe| subscribe.resume_from.is_some()) else {
+        return Ok(());
+    };
+    let mut bytes = Vec::new();
+    subscribe.encode_length_delimited(&mut bytes)?;
+    client_cache.all_rows().encode_length_delimited(&mut bytes)?;
+    // Write to a temporary file first, so an interrupted write doesn't corrupt the previous one.
+    let tmp_path = path.with_extension("tmp");
+    std::fs::write(&tmp_path, bytes)?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Loads the client cache saved by `save_client_cache` to the file at `path`,
+/// returning it along with the `Subscribe` its rows match,
+/// or `None` if there's no such file.
+fn load_client_cache(
+    path: &Path,
+    module: Arc<dyn SpacetimeModule>,
+) -> Result<Option<(ClientCache, client_api_messages::Subscribe)>> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut bytes = &bytes[..];
+    let subscribe = client_api_messages::Subscribe::decode_length_delimited(&mut bytes)?;
+    let rows = client_api_messages::SubscriptionUpdate::decode_length_delimited(&mut bytes)?;
+    let mut client_cache = ClientCache::new(module);
+    // The rows are loaded without invoking row callbacks,
+    // which are only invoked for the rows changed since, once subscribed.
+    let mut callback_reminders = RowCallbackReminders::new_for_subscription_update(&rows);
+    process_subscription_update_for_new_subscribed_set(rows, &mut client_cache, &mut callback_reminders);
+    Ok(Some((client_cache, subscribe)))
 }
 
 // This function's future will be run in the background with `Runtime::spawn`, so the
 // future must be `'static`. As a result, it must own (shared pointers to) the
 // `ClientCache`, `ReducerCallbacks` and `Credentials`, rather than references.
+#[allow(clippy::too_many_arguments)]
 async fn receiver_loop(
     mut recv: mpsc::UnboundedReceiver<client_api_messages::Message>,
     client_cache: SharedCell<Option<ClientCacheView>>,
@@ -197,47 +404,132 @@ async fn receiver_loop(
     credentials: SharedCell<CredentialStore>,
     subscription_callbacks: SharedCell<SubscriptionAppliedCallbacks>,
     disconnect_callbacks: SharedCell<DisconnectCallbacks>,
+    pending_calls: SharedCell<PendingCalls>,
+    subscription: SharedCell<SubscriptionState>,
+    cache_file: Option<PathBuf>,
+    reconnect: Option<Reconnect>,
 ) {
-    while let Some(msg) = recv.next().await {
-        match msg {
-            client_api_messages::Message { r#type: None } => (),
-            client_api_messages::Message {
-                r#type: Some(client_api_messages::message::Type::SubscriptionUpdate(update)),
-            } => {
-                log::info!("Message SubscriptionUpdate");
-                let mut callback_reminders = RowCallbackReminders::new_for_subscription_update(&update);
-                let new_state = update_client_cache(&client_cache, |client_cache| {
-                    process_subscription_update_for_new_subscribed_set(update, client_cache, &mut callback_reminders);
-                });
-
-                subscription_callbacks
-                    .lock()
-                    .expect("SubscriptionAppliedCallbacks Mutex is poisoned")
-                    .handle_subscription_applied(new_state.clone());
-
-                let mut db_callbacks_lock = db_callbacks.lock().expect("DbCallbacks Mutex is poisoned");
-                new_state.invoke_row_callbacks(&mut callback_reminders, &mut db_callbacks_lock, None);
-            }
-            client_api_messages::Message {
-                r#type: Some(client_api_messages::message::Type::TransactionUpdate(transaction_update)),
-            } => {
-                log::info!("Message TransactionUpdate");
-
-                process_transaction_update(transaction_update, &client_cache, &db_callbacks, &reducer_callbacks);
+    // Whether the connection was re-established after dropping.
+    // Its `IdentityToken` and first `SubscriptionUpdate` then resume the dropped connection,
+    // so they don't invoke the on-connect and on-subscription-applied callbacks again.
+    let mut resumed_identity = false;
+    let mut resumed_subscription = false;
+    loop {
+        while let Some(msg) = recv.next().await {
+            match msg {
+                client_api_messages::Message { r#type: None } => (),
+                client_api_messages::Message {
+                    r#type: Some(client_api_messages::message::Type::SubscriptionUpdate(update)),
+                } => {
+                    log::info!("Message SubscriptionUpdate");
+                    let resumed = std::mem::take(&mut resumed_subscription);
+                    subscription
+                        .lock()
+                        .expect("SubscriptionState Mutex is poisoned")
+                        .resume_point = (update.history_id != 0).then_some(client_api_messages::ResumeFrom {
+                        history_id: update.history_id,
+                        tx_offset: update.tx_offset,
+                    });
+                    let mut callback_reminders = RowCallbackReminders::new_for_subscription_update(&update);
+                    let new_state = update_client_cache(&client_cache, |client_cache| {
+                        if update.resumed {
+                            // It only holds the changes since the rows the client cache already has.
+                            process_subscription_update_for_transaction_update(
+                                update,
+                                client_cache,
+                                &mut callback_reminders,
+                            );
+                        } else {
+                            client_cache.reinitialize_tables_missing_from(&mut callback_reminders, &update);
+                            process_subscription_update_for_new_subscribed_set(
+                                update,
+                                client_cache,
+                                &mut callback_reminders,
+                            );
+                        }
+                    });
+
+                    if !resumed {
+                        subscription_callbacks
+                            .lock()
+                            .expect("SubscriptionAppliedCallbacks Mutex is poisoned")
+                            .handle_subscription_applied(new_state.clone());
+                    }
+
+                    let mut db_callbacks_lock = db_callbacks.lock().expect("DbCallbacks Mutex is poisoned");
+                    new_state.invoke_row_callbacks(&mut callback_reminders, &mut db_callbacks_lock, None);
+                }
+                client_api_messages::Message {
+                    r#type: Some(client_api_messages::message::Type::TransactionUpdate(transaction_update)),
+                } => {
+                    log::info!("Message TransactionUpdate");
+                    if transaction_update.tx_offset != 0 {
+                        let mut subscription = subscription.lock().expect("SubscriptionState Mutex is poisoned");
+                        if let Some(resume_point) = subscription.resume_point.as_mut() {
+                            resume_point.tx_offset = transaction_update.tx_offset;
+                        }
+                    }
+
+                    process_transaction_update(
+                        transaction_update,
+                        &client_cache,
+                        &db_callbacks,
+                        &reducer_callbacks,
+                        &pending_calls,
+                    );
+                }
+                client_api_messages::Message {
+                    r#type: Some(client_api_messages::message::Type::QueryUpdate(query_update)),
+                } => {
+                    log::info!("Message QueryUpdate");
+                    // Unlike a `SubscriptionUpdate`, it only holds the rows added to or removed from the subscribed rows,
+                    // so it's applied like the updates of a `TransactionUpdate`.
+                    if let Some(update) = query_update.subscription_update {
+                        let mut callback_reminders = RowCallbackReminders::new_for_subscription_update(&update);
+                        let new_state = update_client_cache(&client_cache, |client_cache| {
+                            process_subscription_update_for_transaction_update(
+                                update,
+                                client_cache,
+                                &mut callback_reminders,
+                            );
+                        });
+
+                        let mut db_callbacks_lock = db_callbacks.lock().expect("DbCallbacks Mutex is poisoned");
+                        new_state.invoke_row_callbacks(&mut callback_reminders, &mut db_callbacks_lock, None);
+                    }
+                }
+                client_api_messages::Message {
+                    r#type: Some(client_api_messages::message::Type::IdentityToken(ident)),
+                } => {
+                    log::info!("Message IdentityToken");
+                    let state = Option::clone(&client_cache.lock().expect("ClientCache Mutex is poisoned")).unwrap();
+                    let mut credentials_lock = credentials.lock().expect("Credentials Mutex is poisoned");
+                    credentials_lock.handle_identity_token(ident, state, std::mem::take(&mut resumed_identity));
+                }
+                other => log::info!("Unknown message: {:?}", other),
             }
-            client_api_messages::Message {
-                r#type: Some(client_api_messages::message::Type::IdentityToken(ident)),
-            } => {
-                log::info!("Message IdentityToken");
-                let state = Option::clone(&client_cache.lock().expect("ClientCache Mutex is poisoned")).unwrap();
-                let mut credentials_lock = credentials.lock().expect("Credentials Mutex is poisoned");
-                credentials_lock.handle_identity_token(ident, state);
-            }
-            other => log::info!("Unknown message: {:?}", other),
         }
+        // Resolve the calls which will never receive their outcome as failed.
+        pending_calls.lock().expect("PendingCalls Mutex is poisoned").clear();
+        let Some(reconnect) = &reconnect else { break };
+        let Some((new_recv, resubscribed)) = reconnect.reconnect().await else {
+            break;
+        };
+        recv = new_recv;
+        resumed_identity = true;
+        resumed_subscription = resubscribed;
     }
     let final_state = client_cache.lock().expect("ClientCache Mutex is poisoned");
     let final_state = ClientCacheView::clone(final_state.as_ref().unwrap());
+    if let Some(path) = cache_file {
+        let subscribe = subscription
+            .lock()
+            .expect("SubscriptionState Mutex is poisoned")
+            .resuming_subscribe();
+        if let Err(e) = save_client_cache(&path, &final_state, subscribe) {
+            log::error!("Failed to save the client cache to {}: {:?}", path.display(), e);
+        }
+    }
     disconnect_callbacks
         .lock()
         .expect("DisconnectCallbacks Mutex is poisoned")
@@ -250,6 +542,15 @@ impl BackgroundDbConnection {
     ///
     /// The `BackgroundDbConnection` will be fully initialized upon calling `connect`.
     pub(crate) fn unconnected() -> Result<Self> {
+        Self::with_client_cache(Arc::clone(&CLIENT_CACHE))
+    }
+
+    /// Construct a partially-initialized `BackgroundDbConnection`
+    /// which publishes its most recent client cache state to `client_cache`.
+    ///
+    /// The global connection publishes to `CLIENT_CACHE`,
+    /// while each `DbConnection` has a cell of its own.
+    pub(crate) fn with_client_cache(client_cache: SharedCell<Option<ClientCacheView>>) -> Result<Self> {
         let (runtime, handle) = enter_or_create_runtime()?;
         let db_callbacks = Arc::new(Mutex::new(DbCallbacks::new(handle.clone())));
         let reducer_callbacks = Arc::new(Mutex::new(ReducerCallbacks::without_handle_event(handle.clone())));
@@ -260,22 +561,41 @@ impl BackgroundDbConnection {
         Ok(BackgroundDbConnection {
             runtime,
             handle,
-            send_chan: None,
+            send_chan: Arc::new(Mutex::new(None)),
             websocket_loop_handle: None,
             recv_handle: None,
             credentials,
-            client_cache: Arc::clone(&CLIENT_CACHE),
+            client_cache,
             db_callbacks,
             reducer_callbacks,
             subscription_callbacks,
             disconnect_callbacks,
+            next_query_id: AtomicU32::new(0),
+            pending_calls: Arc::new(Mutex::new(HashMap::new())),
+            next_request_id: AtomicU32::new(1),
+            subscription: Arc::new(Mutex::new(SubscriptionState::default())),
+            reconnect_policy: None,
+            cache_file: None,
         })
     }
 
+    /// Save the client cache to the file at `path` when a connection established after this call ends,
+    /// and load it from there when connecting, or don't if `path` is None.
+    pub(crate) fn set_cache_file(&mut self, path: Option<PathBuf>) {
+        self.cache_file = path;
+    }
+
+    /// Reconnect according to `policy` when a connection established after this call drops,
+    /// or don't if `policy` is None.
+    pub(crate) fn set_reconnect_policy(&mut self, policy: Option<ReconnectPolicy>) {
+        self.reconnect_policy = policy;
+    }
+
     fn spawn_receiver(
         &self,
         recv: mpsc::UnboundedReceiver<client_api_messages::Message>,
         client_cache: SharedCell<Option<ClientCacheView>>,
+        reconnect: Option<Reconnect>,
     ) -> JoinHandle<()> {
         self.handle.spawn(receiver_loop(
             recv,
@@ -285,6 +605,10 @@ impl BackgroundDbConnection {
             self.credentials.clone(),
             self.subscription_callbacks.clone(),
             self.disconnect_callbacks.clone(),
+            self.pending_calls.clone(),
+            self.subscription.clone(),
+            self.cache_file.clone(),
+            reconnect,
         ))
     }
 
@@ -313,6 +637,7 @@ impl BackgroundDbConnection {
         IntoUri: TryInto<http::Uri>,
         <IntoUri as TryInto<http::Uri>>::Error: std::error::Error + Send + Sync + 'static,
     {
+        let spacetimedb_uri: http::Uri = spacetimedb_uri.try_into()?;
         let client_address = {
             let mut lock = self.credentials.lock().expect("CredentialStore Mutex is poisoned");
             lock.get_or_init_address()
@@ -320,27 +645,60 @@ impl BackgroundDbConnection {
         // `block_in_place` is required here, as tokio won't allow us to call
         // `block_on` if it would block the current thread of an outer runtime
         let connection = tokio::task::block_in_place(|| {
-            self.handle.block_on(DbConnection::connect(
-                spacetimedb_uri,
+            self.handle.block_on(WsConnection::connect(
+                spacetimedb_uri.clone(),
                 db_name,
                 credentials.as_ref(),
                 client_address,
             ))
         })?;
 
-        let client_cache = Arc::new(ClientCache::new(module.clone()));
+        // Start from the client cache saved by a previous connection, if any,
+        // whose subscription can resume from the rows it holds.
+        let loaded = match &self.cache_file {
+            Some(path) => load_client_cache(path, module.clone()).unwrap_or_else(|e| {
+                log::warn!("Failed to load the client cache from {}: {:?}", path.display(), e);
+                None
+            }),
+            None => None,
+        };
+        let (client_cache, subscription) = match loaded {
+            Some((client_cache, mut subscribe)) => {
+                let resume_point = subscribe.resume_from.take();
+                let subscription = SubscriptionState {
+                    subscribe: Some(subscribe),
+                    resume_point,
+                    resume_next_subscribe: true,
+                };
+                (client_cache, subscription)
+            }
+            None => (ClientCache::new(module.clone()), SubscriptionState::default()),
+        };
+        let client_cache = Arc::new(client_cache);
 
         {
-            // Set the global `CLIENT_CACHE` to our newly-constructed cache.
+            // Publish our newly-constructed cache, in the global `CLIENT_CACHE`
+            // or in the cell of a `DbConnection`.
             // Do this inside a short scope to avoid holding the lock unnecessarily.
             let mut client_cache_lock = self.client_cache.lock().expect("ClientCache mutex is poisoned");
             *client_cache_lock = Some(client_cache);
         }
 
         let (websocket_loop_handle, recv_chan, send_chan) = connection.spawn_message_loop(&self.handle);
-        let recv_handle = self.spawn_receiver(recv_chan, self.client_cache.clone());
+        *self.send_chan.lock().expect("send_chan Mutex is poisoned") = Some(send_chan);
+        *self.subscription.lock().expect("SubscriptionState Mutex is poisoned") = subscription;
+
+        let reconnect = self.reconnect_policy.clone().map(|policy| Reconnect {
+            policy,
+            handle: self.handle.clone(),
+            spacetimedb_uri,
+            db_name: db_name.to_owned(),
+            credentials: self.credentials.clone(),
+            send_chan: self.send_chan.clone(),
+            subscription: self.subscription.clone(),
+        });
+        let recv_handle = self.spawn_receiver(recv_chan, self.client_cache.clone(), reconnect);
 
-        self.send_chan = Some(send_chan);
         self.websocket_loop_handle = Some(websocket_loop_handle);
         self.recv_handle = Some(recv_handle);
 
@@ -357,7 +715,8 @@ impl BackgroundDbConnection {
     }
 
     pub fn disconnect(&mut self) {
-        self.send_chan = None;
+        // Dropping the sender closes the WebSocket, and stops the `receiver_loop` from reconnecting.
+        *self.send_chan.lock().expect("send_chan Mutex is poisoned") = None;
         if let Some(h) = self.websocket_loop_handle.take() {
             let _ = self.handle.block_on(h);
         }
@@ -368,6 +727,8 @@ impl BackgroundDbConnection {
 
     fn send_message(&self, message: client_api_messages::Message) -> Result<()> {
         self.send_chan
+            .lock()
+            .expect("send_chan Mutex is poisoned")
             .as_ref()
             .context("Cannot send message before connecting")?
             .unbounded_send(message)
@@ -379,12 +740,87 @@ impl BackgroundDbConnection {
     }
 
     pub(crate) fn subscribe_owned(&self, queries: Vec<String>) -> Result<()> {
+        self.subscribe_parameterized(queries, Vec::new())
+    }
+
+    pub(crate) fn subscribe_parameterized(
+        &self,
+        queries: Vec<String>,
+        parameterized: Vec<ParameterizedQuery>,
+    ) -> Result<()> {
+        let parameterized_queries = parameterized
+            .into_iter()
+            .map(|query| client_api_messages::ParameterizedQuery {
+                template: query.template,
+                params: query
+                    .params
+                    .into_iter()
+                    .map(|(name, value)| client_api_messages::QueryParam { name, value })
+                    .collect(),
+            })
+            .collect();
+        let subscribe = client_api_messages::Subscribe {
+            query_strings: queries,
+            parameterized_queries,
+            added_queries: Vec::new(),
+            resume_from: None,
+        };
+        // Hold the lock while sending, so the `receiver_loop` doesn't resubscribe
+        // with a set of queries older than the one sent.
+        let mut subscription = self.subscription.lock().expect("SubscriptionState Mutex is poisoned");
+        // The first subscription after loading the client cache from a file resumes from it,
+        // if it's to the same queries.
+        let resume = std::mem::take(&mut subscription.resume_next_subscribe)
+            && subscription.subscribe.as_ref() == Some(&subscribe);
+        let message = if resume {
+            subscription.resuming_subscribe().unwrap()
+        } else {
+            subscribe.clone()
+        };
+        self.send_message(client_api_messages::Message {
+            r#type: Some(client_api_messages::message::Type::Subscribe(message)),
+        })
+        .with_context(|| "Subscribing to new queries")?;
+        if !resume {
+            subscription.resume_point = None;
+        }
+        subscription.subscribe = Some(subscribe);
+        Ok(())
+    }
+
+    pub(crate) fn subscribe_add(&self, query: &str) -> Result<QueryId> {
+        let query_id = self.next_query_id.fetch_add(1, Ordering::Relaxed);
+        let added = client_api_messages::SubscribeAdd {
+            query_id,
+            query_string: query.into(),
+        };
+        let mut subscription = self.subscription.lock().expect("SubscriptionState Mutex is poisoned");
+        self.send_message(client_api_messages::Message {
+            r#type: Some(client_api_messages::message::Type::SubscribeAdd(added.clone())),
+        })
+        .with_context(|| "Adding a subscribed query")?;
+        subscription.resume_point = None;
+        subscription
+            .subscribe
+            .get_or_insert_with(Default::default)
+            .added_queries
+            .push(added);
+        Ok(QueryId(query_id))
+    }
+
+    pub(crate) fn unsubscribe(&self, QueryId(query_id): QueryId) -> Result<()> {
+        let mut subscription = self.subscription.lock().expect("SubscriptionState Mutex is poisoned");
         self.send_message(client_api_messages::Message {
-            r#type: Some(client_api_messages::message::Type::Subscribe(
-                client_api_messages::Subscribe { query_strings: queries },
+            r#type: Some(client_api_messages::message::Type::Unsubscribe(
+                client_api_messages::Unsubscribe { query_id },
             )),
         })
-        .with_context(|| "Subscribing to new queries")
+        .with_context(|| "Removing a subscribed query")?;
+        subscription.resume_point = None;
+        if let Some(subscribe) = subscription.subscribe.as_mut() {
+            subscribe.added_queries.retain(|added| added.query_id != query_id);
+        }
+        Ok(())
     }
 
     pub(crate) fn invoke_reducer<R: Reducer>(&self, reducer: R) -> Result<()> {
@@ -393,9 +829,41 @@ impl BackgroundDbConnection {
                 client_api_messages::FunctionCall {
                     reducer: R::REDUCER_NAME.to_string(),
                     arg_bytes: bsatn::to_vec(&reducer).expect("Serializing reducer failed"),
+                    request_id: 0,
                 },
             )),
         })
         .with_context(|| format!("Invoking reducer {}", R::REDUCER_NAME))
     }
+
+    pub(crate) fn invoke_reducer_async<R: Reducer>(&self, reducer: R) -> InvokeFuture {
+        let request_id = self.next_request_id.fetch_add(1, Ordering::Relaxed);
+        let (send, recv) = oneshot::channel();
+        // Register the call before sending it, so its outcome can't be received first.
+        self.pending_calls
+            .lock()
+            .expect("PendingCalls Mutex is poisoned")
+            .insert(request_id, send);
+        let sent = self
+            .send_message(client_api_messages::Message {
+                r#type: Some(client_api_messages::message::Type::FunctionCall(
+                    client_api_messages::FunctionCall {
+                        reducer: R::REDUCER_NAME.to_string(),
+                        arg_bytes: bsatn::to_vec(&reducer).expect("Serializing reducer failed"),
+                        request_id,
+                    },
+                )),
+            })
+            .with_context(|| format!("Invoking reducer {}", R::REDUCER_NAME));
+        match sent {
+            Ok(()) => InvokeFuture::new(recv),
+            Err(e) => {
+                self.pending_calls
+                    .lock()
+                    .expect("PendingCalls Mutex is poisoned")
+                    .remove(&request_id);
+                InvokeFuture::failed(e)
+            }
+        }
+    }
 }
@@ -22,12 +22,12 @@ use crate::{
     identity::{Credentials, Identity, Token},
     reducer::{AnyReducerEvent, Reducer, Status},
     spacetime_module::SpacetimeModule,
-    table::TableType,
+    table::{TableEvent, TableType},
     Address,
 };
 use anyhow::Context;
 use anymap::{any::Any, Map};
-use futures::stream::StreamExt;
+use futures::{sink::SinkExt, stream::StreamExt};
 use futures_channel::mpsc;
 use spacetimedb_sats::bsatn;
 use std::{
@@ -488,6 +488,105 @@ where
     //       In fact, consider splitting all three fields into separate `Map`s in the `DbCallbacks`,
     //       In order to lazily allocate the `CallbackMap`s.
     on_update: CallbackMap<(Table, Table, Option<Arc<AnyReducerEvent>>)>,
+
+    /// The `TableType::events` streams, each fed every insert, delete and update
+    /// in the order they were applied to the `ClientCache`.
+    ///
+    /// Spawned on the first call to `register_event_stream`,
+    /// so that tables without streams don't clone their rows for it.
+    on_event: Option<EventStreams<Table>>,
+
+    /// A handle on the Tokio runtime, used to spawn `on_event` when it's first needed.
+    runtime: runtime::Handle,
+}
+
+/// The number of events buffered for a `TableEvent` stream before the worker feeding it
+/// waits for the stream to be polled.
+const TABLE_EVENT_BUFFER: usize = 64;
+
+/// A message sent by an `EventStreams` to its background worker.
+enum EventStreamMessage<Table: TableType> {
+    /// Feed the events sent from now on to a new stream.
+    Register(mpsc::Sender<TableEvent<Table>>),
+
+    /// Send `TableEvent` to each registered stream.
+    Event(TableEvent<Table>),
+}
+
+/// A handle on a background worker which feeds the `TableType::events` streams of a table.
+///
+/// Each stream has a bounded channel, and the worker awaits room in it for each event,
+/// so a stream which isn't polled holds up the delivery of events to the table's other streams.
+/// This bounds what each stream buffers, but not the events queued for the worker:
+/// the `ClientCache` doesn't wait for the streams, so those are buffered without bound,
+/// just like the invocations queued for a `CallbackMap`.
+pub(crate) struct EventStreams<Table: TableType> {
+    /// Channel for sending new streams and events to the background worker.
+    send: mpsc::UnboundedSender<EventStreamMessage<Table>>,
+
+    /// The Tokio handle for the worker.
+    #[allow(unused)]
+    handle: JoinHandle<()>,
+}
+
+impl<Table: TableType> EventStreams<Table> {
+    /// Sends each event received via `recv` to every registered stream, in order,
+    /// and drops the streams whose receiver has been dropped.
+    async fn event_stream_loop(mut recv: mpsc::UnboundedReceiver<EventStreamMessage<Table>>) {
+        let mut streams: Vec<mpsc::Sender<TableEvent<Table>>> = Vec::new();
+        while let Some(msg) = recv.next().await {
+            match msg {
+                EventStreamMessage::Register(stream) => streams.push(stream),
+                EventStreamMessage::Event(event) => {
+                    let mut closed = Vec::new();
+                    for (i, stream) in streams.iter_mut().enumerate() {
+                        if stream.send(event.clone()).await.is_err() {
+                            closed.push(i);
+                        }
+                    }
+                    for i in closed.into_iter().rev() {
+                        streams.swap_remove(i);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Construct a new `EventStreams` with a background worker running in `runtime`.
+    fn spawn(runtime: &runtime::Handle) -> Self {
+        let (send, recv) = mpsc::unbounded();
+        let handle = runtime.spawn(Self::event_stream_loop(recv));
+        EventStreams { send, handle }
+    }
+
+    /// Register a new stream, which receives the events sent after it.
+    fn register(&self) -> mpsc::Receiver<TableEvent<Table>> {
+        let (send, recv) = mpsc::channel(TABLE_EVENT_BUFFER);
+        self.send
+            .unbounded_send(EventStreamMessage::Register(send))
+            // TODO: properly handle this error somehow
+            .expect("EventStreams worker panicked");
+        recv
+    }
+
+    /// Send `event` to each registered stream.
+    fn send_event(&self, event: TableEvent<Table>) {
+        self.send
+            .unbounded_send(EventStreamMessage::Event(event))
+            // TODO: properly handle this error somehow
+            .expect("EventStreams worker panicked");
+    }
+}
+
+/// Downcast the `ReducerEvent` carried with a row callback to the concrete type of `Table`.
+fn downcast_reducer_event<Table: TableType>(
+    reducer_event: &Option<Arc<AnyReducerEvent>>,
+) -> Option<Arc<Table::ReducerEvent>> {
+    reducer_event.clone().map(|event| {
+        event
+            .downcast()
+            .expect("Found ReducerEvent of unexpected concrete type.")
+    })
 }
 
 impl<Table> TableCallbacks<Table>
@@ -521,6 +620,13 @@ where
         reducer_event: Option<Arc<AnyReducerEvent>>,
         db_state: ClientCacheView,
     ) {
+        if let Some(on_event) = &self.on_event {
+            let event = TableEvent::Insert {
+                row: inserted.clone(),
+                reducer_event: downcast_reducer_event::<Table>(&reducer_event),
+            };
+            on_event.send_event(event);
+        }
         self.on_insert.invoke((inserted, reducer_event), db_state);
     }
 
@@ -552,6 +658,13 @@ where
         reducer_event: Option<Arc<AnyReducerEvent>>,
         db_state: ClientCacheView,
     ) {
+        if let Some(on_event) = &self.on_event {
+            let event = TableEvent::Delete {
+                row: deleted.clone(),
+                reducer_event: downcast_reducer_event::<Table>(&reducer_event),
+            };
+            on_event.send_event(event);
+        }
         self.on_delete.invoke((deleted, reducer_event), db_state);
     }
 
@@ -560,6 +673,8 @@ where
             on_insert: CallbackMap::spawn(runtime),
             on_delete: CallbackMap::spawn(runtime),
             on_update: CallbackMap::spawn(runtime),
+            on_event: None,
+            runtime: runtime.clone(),
         }
     }
 
@@ -585,6 +700,17 @@ where
         self.on_update.insert(Box::new(uncurry_update_callback(on_update)))
     }
 
+    /// Register a stream of every insert, delete and update to the table.
+    ///
+    /// See `EventStreams` for how the events are buffered.
+    /// The stream is unregistered upon the first event after its receiver is dropped.
+    pub(crate) fn register_event_stream(&mut self) -> mpsc::Receiver<TableEvent<Table>> {
+        let runtime = &self.runtime;
+        self.on_event
+            .get_or_insert_with(|| EventStreams::spawn(runtime))
+            .register()
+    }
+
     /// Unregister an on-update callback with the given `id`.
     ///
     /// Update callbacks are only meaningful for tables with primary keys.
@@ -599,6 +725,14 @@ where
         reducer_event: Option<Arc<AnyReducerEvent>>,
         db_state: ClientCacheView,
     ) {
+        if let Some(on_event) = &self.on_event {
+            let event = TableEvent::Update {
+                old: old.clone(),
+                new: new.clone(),
+                reducer_event: downcast_reducer_event::<Table>(&reducer_event),
+            };
+            on_event.send_event(event);
+        }
         self.on_update.invoke((old, new, reducer_event), db_state);
     }
 }
@@ -659,7 +793,7 @@ pub struct ReducerCallbacks {
 // which we must then compare against the enum variants.
 // This helper function does that comparison.
 
-fn parse_status(status: i32, message: String) -> Option<Status> {
+pub(crate) fn parse_status(status: i32, message: String) -> Option<Status> {
     if status == client_api_messages::event::Status::Committed as i32 {
         debug_assert!(message.is_empty());
         Some(Status::Committed)
@@ -668,6 +802,8 @@ fn parse_status(status: i32, message: String) -> Option<Status> {
     } else if status == client_api_messages::event::Status::OutOfEnergy as i32 {
         debug_assert!(message.is_empty());
         Some(Status::OutOfEnergy)
+    } else if status == client_api_messages::event::Status::Unauthorized as i32 {
+        Some(Status::Unauthorized(message))
     } else {
         None
     }
@@ -902,8 +1038,15 @@ impl CredentialStore {
     /// If we connected with existing `Credentials`, compare them to the ones provided by the database,
     /// and log an error if they don't match.
     ///
-    /// Either way, invoke any on-connect callbacks with the received credentials.
-    pub(crate) fn handle_identity_token(&mut self, msg: client_api_messages::IdentityToken, state: ClientCacheView) {
+    /// Either way, invoke any on-connect callbacks with the received credentials,
+    /// unless the connection was `resumed` after dropping with the credentials already known,
+    /// in which case the callbacks were invoked for the first connection.
+    pub(crate) fn handle_identity_token(
+        &mut self,
+        msg: client_api_messages::IdentityToken,
+        state: ClientCacheView,
+        resumed: bool,
+    ) {
         let client_api_messages::IdentityToken {
             identity,
             token,
@@ -930,7 +1073,9 @@ impl CredentialStore {
             );
         }
 
-        self.callbacks.invoke((creds.clone(), address), state);
+        if !(resumed && self.credentials.is_some()) {
+            self.callbacks.invoke((creds.clone(), address), state);
+        }
 
         if let Some(existing_creds) = &self.credentials {
             // If we already have credentials, make sure that they match. Log an error if
@@ -522,6 +522,12 @@ pub struct ClientCache {
     /// "keyed" on the type `TableCache<T> where T: TableType`.
     tables: Map<dyn CloneAny + Send + Sync>,
 
+    /// The tables which have received rows, by name,
+    /// with a function returning the rows of each as a `TableUpdate` of inserts,
+    /// so that a table missing from a later `SubscriptionUpdate` can be emptied,
+    /// and the rows of every table can be saved to a file.
+    received_tables: HashMap<&'static str, fn(&ClientCache) -> client_api_messages::TableUpdate>,
+
     /// Contains functions autogenerated by the CLI,
     /// which handle dispatching on table names
     /// to select appropriate type parameters for various methods.
@@ -531,6 +537,7 @@ pub struct ClientCache {
 impl ClientCache {
     /// Look up a type-specific `TableCache` for `T`, creating it if it does not exist.
     pub(crate) fn find_table<T: TableType>(&mut self) -> &mut TableCache<T> {
+        self.received_tables.entry(T::TABLE_NAME).or_insert(table_rows::<T>);
         self.tables
             .entry::<TableCache<T>>()
             .or_insert_with(|| TableCache::new())
@@ -586,6 +593,7 @@ impl ClientCache {
     pub(crate) fn new(module: Arc<dyn SpacetimeModule>) -> ClientCache {
         ClientCache {
             tables: Map::new(),
+            received_tables: HashMap::new(),
             module,
         }
     }
@@ -616,6 +624,48 @@ impl ClientCache {
             .handle_resubscribe(new_subs, self, callback_reminders);
     }
 
+    /// Empty each table which has received rows but has no `TableUpdate` in `subs`,
+    /// as a `SubscriptionUpdate` only holds the tables with a row which matches the subscribed queries.
+    ///
+    /// Used when the subscribed rows are replaced, as the client cache may hold rows of tables
+    /// which the new queries don't match, or which were deleted since the rows were kept
+    /// across a reconnection or loaded from a file.
+    pub(crate) fn reinitialize_tables_missing_from(
+        &mut self,
+        callback_reminders: &mut RowCallbackReminders,
+        subs: &client_api_messages::SubscriptionUpdate,
+    ) {
+        let missing = self
+            .received_tables
+            .keys()
+            .filter(|&&name| !subs.table_updates.iter().any(|update| update.table_name == name))
+            .copied()
+            .collect::<Vec<_>>();
+        for table_name in missing {
+            self.handle_table_reinitialize_for_new_subscribed_set(
+                callback_reminders,
+                client_api_messages::TableUpdate {
+                    table_id: 0,
+                    table_name: table_name.to_owned(),
+                    table_row_operations: Vec::new(),
+                },
+            );
+        }
+    }
+
+    /// Returns the rows of every table as a `SubscriptionUpdate` of inserts,
+    /// which reinitializes an empty `ClientCache` to the state of `self`.
+    pub(crate) fn all_rows(&self) -> client_api_messages::SubscriptionUpdate {
+        client_api_messages::SubscriptionUpdate {
+            table_updates: self
+                .received_tables
+                .values()
+                .map(|table_rows| table_rows(self))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
     /// Invoke the autogenerated `invoke_row_callbacks` function
     /// to invoke all callbacks in `callback_reminders`
     /// in the state `self`.
@@ -630,5 +680,24 @@ impl ClientCache {
     }
 }
 
+/// Returns the rows of the table `T` in `client_cache` as a `TableUpdate` of inserts.
+fn table_rows<T: TableType>(client_cache: &ClientCache) -> client_api_messages::TableUpdate {
+    let table_row_operations = client_cache
+        .get_table::<T>()
+        .into_iter()
+        .flat_map(|table| &table.entries)
+        .map(|(row_pk, row)| client_api_messages::TableRowOperation {
+            op: client_api_messages::table_row_operation::OperationType::Insert.into(),
+            row_pk: row_pk.clone(),
+            row: bsatn::to_vec(row).expect("Serializing row failed"),
+        })
+        .collect();
+    client_api_messages::TableUpdate {
+        table_id: 0,
+        table_name: T::TABLE_NAME.to_owned(),
+        table_row_operations,
+    }
+}
+
 /// A shared view into a particular state of the `ClientCache`.
 pub(crate) type ClientCacheView = Arc<ClientCache>;
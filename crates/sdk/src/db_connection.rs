@@ -0,0 +1,421 @@
+//! Connections to databases independent from the global connection.
+//!
+//! The free functions of this crate, and the static methods of [`TableType`] and [`Reducer`],
+//! act on a single process-wide connection, established by the generated `connect` function.
+//! A [`DbConnection`], connected by the generated `connect_with` function,
+//! owns its connection, client cache and callbacks instead,
+//! so a process can hold several, with different identities or to different databases.
+
+use crate::background_connection::BackgroundDbConnection;
+use crate::client_cache::TableCache;
+use crate::identity::{ConnectCallbackId, Credentials, Identity};
+use crate::reducer::{Reducer, ReducerCallbackId, Status};
+use crate::spacetime_module::SpacetimeModule;
+use crate::table::{
+    DeleteCallbackId, InsertCallbackId, TableEventStream, TableIter, TableType, TableWithPrimaryKey, UpdateCallbackId,
+};
+use crate::{Address, DisconnectCallbackId, ParameterizedQuery, QueryId, ReconnectPolicy, SubscriptionCallbackId};
+use anyhow::{anyhow, Result};
+use std::future::Future;
+use std::sync::{Arc, Mutex, RwLock};
+
+/// A connection to a database, with its own client cache and callbacks.
+///
+/// A `DbConnection` is a cheap handle: its clones share the same connection.
+///
+/// The callbacks registered with a `DbConnection` run with the state of its client cache,
+/// which the static methods of [`TableType`] read while they run.
+/// Outside of callbacks, they read the global connection's client cache,
+/// so the rows of a `DbConnection` should be read through its own methods.
+#[derive(Clone)]
+pub struct DbConnection {
+    inner: Arc<RwLock<BackgroundDbConnection>>,
+}
+
+impl DbConnection {
+    /// Construct a `DbConnection` which is not yet connected to a database.
+    ///
+    /// Callbacks, notably [`DbConnection::on_connect`], can be registered
+    /// before connecting by passing the `DbConnection` to the `connect_with` function
+    /// generated by the SpaceTime CLI.
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            inner: Arc::new(RwLock::new(BackgroundDbConnection::with_client_cache(Arc::new(
+                Mutex::new(None),
+            ))?)),
+        })
+    }
+
+    /// Connect to a database named `db_name` accessible over the internet at the URI `spacetimedb_uri`.
+    ///
+    /// Users should not call `DbConnection::connect` directly;
+    /// instead, call the `connect_with` function generated by the SpaceTime CLI,
+    /// which passes the `module` of the bindings.
+    #[doc(hidden)]
+    pub fn connect<IntoUri>(
+        &self,
+        spacetimedb_uri: IntoUri,
+        db_name: &str,
+        credentials: Option<Credentials>,
+        module: Arc<dyn SpacetimeModule>,
+    ) -> Result<()>
+    where
+        IntoUri: TryInto<http::Uri>,
+        <IntoUri as TryInto<http::Uri>>::Error: std::error::Error + Send + Sync + 'static,
+    {
+        self.inner.write().expect("DbConnection RwLock is poisoned").connect(
+            spacetimedb_uri,
+            db_name,
+            credentials,
+            module,
+        )
+    }
+
+    fn with_connection<Res>(&self, f: impl FnOnce(&BackgroundDbConnection) -> Res) -> Res {
+        let connection = self.inner.read().expect("DbConnection RwLock is poisoned");
+        f(&connection)
+    }
+
+    fn with_table<T: TableType, Res>(&self, f: impl FnOnce(&TableCache<T>) -> Res) -> Option<Res> {
+        let state = self.with_connection(|connection| {
+            Option::clone(&connection.client_cache.lock().expect("ClientCache Mutex is poisoned"))
+        })?;
+        state.get_table::<T>().map(f)
+    }
+
+    /// Gracefully close the connection.
+    ///
+    /// The rows of the client cache remain readable afterwards.
+    pub fn disconnect(&self) {
+        self.inner
+            .write()
+            .expect("DbConnection RwLock is poisoned")
+            .disconnect();
+    }
+
+    /// Reconnect automatically when the connection drops, following `policy`,
+    /// or don't if `policy` is `None`, which is the default.
+    ///
+    /// See [`crate::set_reconnect_policy`], which does the same for the global connection.
+    pub fn set_reconnect_policy(&self, policy: Option<ReconnectPolicy>) {
+        self.inner
+            .write()
+            .expect("DbConnection RwLock is poisoned")
+            .set_reconnect_policy(policy);
+    }
+
+    /// Persist the client cache to the file at `path`, or don't if `path` is `None`, which is the default.
+    ///
+    /// See [`crate::persist_client_cache`], which does the same for the global connection.
+    pub fn persist_client_cache(&self, path: Option<std::path::PathBuf>) {
+        self.inner
+            .write()
+            .expect("DbConnection RwLock is poisoned")
+            .set_cache_file(path);
+    }
+
+    /// Subscribe to a set of queries, replacing the previous ones, like [`crate::subscribe`].
+    pub fn subscribe(&self, queries: &[&str]) -> Result<()> {
+        self.with_connection(|conn| conn.subscribe(queries))
+    }
+
+    /// Subscribe to a set of queries, replacing the previous ones, like [`crate::subscribe_owned`].
+    pub fn subscribe_owned(&self, queries: Vec<String>) -> Result<()> {
+        self.with_connection(|conn| conn.subscribe_owned(queries))
+    }
+
+    /// Subscribe to a set of queries and of parameterized queries, like [`crate::subscribe_parameterized`].
+    pub fn subscribe_parameterized(&self, queries: Vec<String>, parameterized: Vec<ParameterizedQuery>) -> Result<()> {
+        self.with_connection(|conn| conn.subscribe_parameterized(queries, parameterized))
+    }
+
+    /// Add a query to the subscribed queries, like [`crate::subscribe_add`].
+    pub fn subscribe_add(&self, query: &str) -> Result<QueryId> {
+        self.with_connection(|conn| conn.subscribe_add(query))
+    }
+
+    /// Remove a query added by [`DbConnection::subscribe_add`], like [`crate::unsubscribe`].
+    pub fn unsubscribe(&self, id: QueryId) -> Result<()> {
+        self.with_connection(|conn| conn.unsubscribe(id))
+    }
+
+    /// Request the database to run `reducer`, like [`Reducer::invoke`].
+    pub fn invoke<R: Reducer>(&self, reducer: R) -> Result<()> {
+        self.with_connection(|conn| conn.invoke_reducer(reducer))
+    }
+
+    /// Request the database to run `reducer`,
+    /// returning a future which resolves to the outcome of this particular call,
+    /// like [`Reducer::invoke_async`].
+    pub fn invoke_async<R: Reducer>(&self, reducer: R) -> impl Future<Output = Status> {
+        self.with_connection(|conn| conn.invoke_reducer_async(reducer))
+    }
+
+    /// Register a callback to run after the reducer `R` runs, like [`Reducer::on_reducer`].
+    pub fn on_reducer<R: Reducer>(
+        &self,
+        callback: impl FnMut(&Identity, Option<Address>, &Status, &R) + Send + 'static,
+    ) -> ReducerCallbackId<R> {
+        let id = self.with_connection(|conn| {
+            let mut callbacks = conn
+                .reducer_callbacks
+                .lock()
+                .expect("ReducerCallbacks Mutex is poisoned");
+            callbacks.register_on_reducer::<R>(callback)
+        });
+        ReducerCallbackId { id }
+    }
+
+    /// Register a callback to run once after the reducer `R` runs, like [`Reducer::once_on_reducer`].
+    pub fn once_on_reducer<R: Reducer>(
+        &self,
+        callback: impl FnOnce(&Identity, Option<Address>, &Status, &R) + Send + 'static,
+    ) -> ReducerCallbackId<R> {
+        let id = self.with_connection(|conn| {
+            let mut callbacks = conn
+                .reducer_callbacks
+                .lock()
+                .expect("ReducerCallbacks Mutex is poisoned");
+            callbacks.register_on_reducer_oneshot::<R>(callback)
+        });
+        ReducerCallbackId { id }
+    }
+
+    /// Unregister a previously-registered [`DbConnection::on_reducer`] callback.
+    pub fn remove_on_reducer<R: Reducer>(&self, id: ReducerCallbackId<R>) {
+        self.with_connection(|conn| {
+            let mut callbacks = conn
+                .reducer_callbacks
+                .lock()
+                .expect("ReducerCallbacks Mutex is poisoned");
+            callbacks.unregister_on_reducer::<R>(id.id)
+        })
+    }
+
+    /// Return the number of subscribed rows in the table `T`, like [`TableType::count`].
+    pub fn count<T: TableType>(&self) -> usize {
+        self.with_table::<T, _>(|table_cache| table_cache.count_subscribed_rows())
+            .unwrap_or(0)
+    }
+
+    /// Iterate over all the subscribed rows in the table `T`, like [`TableType::iter`].
+    pub fn iter<T: TableType>(&self) -> TableIter<T> {
+        TableIter {
+            iter: self
+                .with_table::<T, _>(|table_cache| table_cache.values())
+                .unwrap_or_default()
+                .into_iter(),
+        }
+    }
+
+    /// Iterate over the subscribed rows in the table `T` for which `predicate` returns `true`,
+    /// like [`TableType::filter`].
+    pub fn filter<T: TableType>(&self, predicate: impl FnMut(&T) -> bool) -> TableIter<T> {
+        TableIter {
+            iter: self
+                .with_table::<T, _>(|table_cache| table_cache.filter(predicate))
+                .unwrap_or_default()
+                .into_iter(),
+        }
+    }
+
+    /// Locate a subscribed row in the table `T` for which `predicate` returns `true`,
+    /// like [`TableType::find`].
+    pub fn find<T: TableType>(&self, predicate: impl FnMut(&T) -> bool) -> Option<T> {
+        self.with_table::<T, _>(|table_cache| table_cache.find(predicate))
+            .flatten()
+    }
+
+    /// Register an `on_insert` callback for the table `T`, like [`TableType::on_insert`].
+    pub fn on_insert<T: TableType>(
+        &self,
+        callback: impl FnMut(&T, Option<&T::ReducerEvent>) + Send + 'static,
+    ) -> InsertCallbackId<T> {
+        let id = self.with_connection(|conn| {
+            let mut db_callbacks = conn.db_callbacks.lock().expect("DbCallbacks Mutex is poisoned");
+            db_callbacks.find_table::<T>().register_on_insert(callback)
+        });
+        InsertCallbackId { id }
+    }
+
+    /// Unregister a previously-registered [`DbConnection::on_insert`] callback.
+    pub fn remove_on_insert<T: TableType>(&self, id: InsertCallbackId<T>) {
+        self.with_connection(|conn| {
+            let mut db_callbacks = conn.db_callbacks.lock().expect("DbCallbacks Mutex is poisoned");
+            db_callbacks.find_table::<T>().unregister_on_insert(id.id)
+        })
+    }
+
+    /// Register an `on_delete` callback for the table `T`, like [`TableType::on_delete`].
+    pub fn on_delete<T: TableType>(
+        &self,
+        callback: impl FnMut(&T, Option<&T::ReducerEvent>) + Send + 'static,
+    ) -> DeleteCallbackId<T> {
+        let id = self.with_connection(|conn| {
+            let mut db_callbacks = conn.db_callbacks.lock().expect("DbCallbacks Mutex is poisoned");
+            db_callbacks.find_table::<T>().register_on_delete(callback)
+        });
+        DeleteCallbackId { id }
+    }
+
+    /// Unregister a previously-registered [`DbConnection::on_delete`] callback.
+    pub fn remove_on_delete<T: TableType>(&self, id: DeleteCallbackId<T>) {
+        self.with_connection(|conn| {
+            let mut db_callbacks = conn.db_callbacks.lock().expect("DbCallbacks Mutex is poisoned");
+            db_callbacks.find_table::<T>().unregister_on_delete(id.id)
+        })
+    }
+
+    /// Register an `on_update` callback for the table `T`, like [`TableWithPrimaryKey::on_update`].
+    pub fn on_update<T: TableWithPrimaryKey>(
+        &self,
+        callback: impl FnMut(&T, &T, Option<&T::ReducerEvent>) + Send + 'static,
+    ) -> UpdateCallbackId<T> {
+        let id = self.with_connection(|conn| {
+            let mut db_callbacks = conn.db_callbacks.lock().expect("DbCallbacks Mutex is poisoned");
+            db_callbacks.find_table::<T>().register_on_update(callback)
+        });
+        UpdateCallbackId { id }
+    }
+
+    /// Unregister a previously-registered [`DbConnection::on_update`] callback.
+    pub fn remove_on_update<T: TableWithPrimaryKey>(&self, id: UpdateCallbackId<T>) {
+        self.with_connection(|conn| {
+            let mut db_callbacks = conn.db_callbacks.lock().expect("DbCallbacks Mutex is poisoned");
+            db_callbacks.find_table::<T>().unregister_on_update(id.id)
+        })
+    }
+
+    /// Returns a `Stream` of the changes to the table `T`, like [`TableType::events`].
+    pub fn events<T: TableType>(&self) -> TableEventStream<T> {
+        let recv = self.with_connection(|conn| {
+            let mut db_callbacks = conn.db_callbacks.lock().expect("DbCallbacks Mutex is poisoned");
+            db_callbacks.find_table::<T>().register_event_stream()
+        });
+        TableEventStream { recv }
+    }
+
+    /// Register a callback to be invoked upon a subscription's matching rows becoming available,
+    /// like [`crate::on_subscription_applied`].
+    pub fn on_subscription_applied(&self, callback: impl FnMut() + Send + 'static) -> SubscriptionCallbackId {
+        let id = self.with_connection(|conn| {
+            let mut callbacks = conn
+                .subscription_callbacks
+                .lock()
+                .expect("SubscriptionAppliedCallbacks Mutex is poisoned");
+            callbacks.register_on_subscription_applied(callback)
+        });
+        SubscriptionCallbackId { id }
+    }
+
+    /// Register a callback to be invoked once upon a subscription's matching rows becoming available,
+    /// like [`crate::once_on_subscription_applied`].
+    pub fn once_on_subscription_applied(&self, callback: impl FnOnce() + Send + 'static) -> SubscriptionCallbackId {
+        let id = self.with_connection(|conn| {
+            let mut callbacks = conn
+                .subscription_callbacks
+                .lock()
+                .expect("SubscriptionAppliedCallbacks Mutex is poisoned");
+            callbacks.register_on_subscription_applied_oneshot(callback)
+        });
+        SubscriptionCallbackId { id }
+    }
+
+    /// Unregister a previously-registered [`DbConnection::on_subscription_applied`] callback.
+    pub fn remove_on_subscription_applied(&self, id: SubscriptionCallbackId) {
+        self.with_connection(|conn| {
+            let mut callbacks = conn
+                .subscription_callbacks
+                .lock()
+                .expect("SubscriptionAppliedCallbacks Mutex is poisoned");
+            callbacks.unregister_on_subscription_applied(id.id)
+        })
+    }
+
+    /// Register a callback to be invoked upon the connection ending, like [`crate::on_disconnect`].
+    pub fn on_disconnect(&self, callback: impl FnMut() + Send + 'static) -> DisconnectCallbackId {
+        let id = self.with_connection(|conn| {
+            let mut callbacks = conn
+                .disconnect_callbacks
+                .lock()
+                .expect("DisconnectCallbacks Mutex is poisoned");
+            callbacks.register_on_disconnect(callback)
+        });
+        DisconnectCallbackId { id }
+    }
+
+    /// Register a callback to be invoked once upon the connection ending, like [`crate::once_on_disconnect`].
+    pub fn once_on_disconnect(&self, callback: impl FnOnce() + Send + 'static) -> DisconnectCallbackId {
+        let id = self.with_connection(|conn| {
+            let mut callbacks = conn
+                .disconnect_callbacks
+                .lock()
+                .expect("DisconnectCallbacks Mutex is poisoned");
+            callbacks.register_on_disconnect_oneshot(callback)
+        });
+        DisconnectCallbackId { id }
+    }
+
+    /// Unregister a previously-registered [`DbConnection::on_disconnect`] callback.
+    pub fn remove_on_disconnect(&self, id: DisconnectCallbackId) {
+        self.with_connection(|conn| {
+            let mut callbacks = conn
+                .disconnect_callbacks
+                .lock()
+                .expect("DisconnectCallbacks Mutex is poisoned");
+            callbacks.unregister_on_disconnect(id.id)
+        })
+    }
+
+    /// Register a callback to be invoked upon authentication with the database,
+    /// like [`crate::identity::on_connect`].
+    pub fn on_connect(&self, callback: impl FnMut(&Credentials, Address) + Send + 'static) -> ConnectCallbackId {
+        let id = self.with_connection(|conn| {
+            let mut credentials = conn.credentials.lock().expect("CredentialStore Mutex is poisoned");
+            credentials.register_on_connect(callback)
+        });
+        ConnectCallbackId { id }
+    }
+
+    /// Register a callback to be invoked once upon authentication with the database,
+    /// like [`crate::identity::once_on_connect`].
+    pub fn once_on_connect(&self, callback: impl FnOnce(&Credentials, Address) + Send + 'static) -> ConnectCallbackId {
+        let id = self.with_connection(|conn| {
+            let mut credentials = conn.credentials.lock().expect("CredentialStore Mutex is poisoned");
+            credentials.register_on_connect_oneshot(callback)
+        });
+        ConnectCallbackId { id }
+    }
+
+    /// Unregister a previously-registered [`DbConnection::on_connect`] callback.
+    pub fn remove_on_connect(&self, id: ConnectCallbackId) {
+        self.with_connection(|conn| {
+            let mut credentials = conn.credentials.lock().expect("CredentialStore Mutex is poisoned");
+            credentials.unregister_on_connect(id.id)
+        })
+    }
+
+    /// Read the `Credentials` of the connection, like [`crate::identity::credentials`].
+    pub fn credentials(&self) -> Result<Credentials> {
+        self.with_connection(|conn| {
+            let credentials = conn.credentials.lock().expect("CredentialStore Mutex is poisoned");
+            credentials.credentials().ok_or(anyhow!("Credentials not yet received"))
+        })
+    }
+
+    /// Read the public `Identity` of the connection, like [`crate::identity::identity`].
+    pub fn identity(&self) -> Result<Identity> {
+        self.with_connection(|conn| {
+            let credentials = conn.credentials.lock().expect("CredentialStore Mutex is poisoned");
+            credentials.identity().ok_or(anyhow!("Identity not yet received"))
+        })
+    }
+
+    /// Read the `Address` of the connection, like [`crate::identity::address`].
+    pub fn address(&self) -> Result<Address> {
+        self.with_connection(|conn| {
+            let credentials = conn.credentials.lock().expect("CredentialStore Mutex is poisoned");
+            credentials.address().ok_or(anyhow!("Address not yet generated"))
+        })
+    }
+}
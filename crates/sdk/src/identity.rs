@@ -76,7 +76,7 @@ pub struct Credentials {
 
 #[derive(Copy, Clone)]
 pub struct ConnectCallbackId {
-    id: CallbackId<(Credentials, Address)>,
+    pub(crate) id: CallbackId<(Credentials, Address)>,
 }
 
 /// Register a callback to be invoked upon authentication with the database.
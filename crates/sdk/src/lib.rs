@@ -3,6 +3,7 @@ pub mod callbacks;
 #[doc(hidden)]
 pub mod spacetime_module;
 
+pub mod db_connection;
 pub mod identity;
 pub mod reducer;
 pub mod table;
@@ -83,6 +84,73 @@ pub fn subscribe_owned(queries: Vec<String>) -> anyhow::Result<()> {
     with_connection(|conn| conn.subscribe_owned(queries))
 }
 
+/// A SQL query template and the values bound to its parameters, see [`subscribe_parameterized`].
+#[derive(Clone, Debug)]
+pub struct ParameterizedQuery {
+    template: String,
+    params: Vec<(String, Vec<u8>)>,
+}
+
+impl ParameterizedQuery {
+    /// A query `template` in which each `:name` parameter is compared for equality
+    /// with a column of the table it reads, as in `SELECT * FROM Message WHERE channel = :channel`.
+    pub fn new(template: impl Into<String>) -> Self {
+        Self {
+            template: template.into(),
+            params: Vec::new(),
+        }
+    }
+
+    /// Binds `value` to the parameter `:name` of the template.
+    ///
+    /// The `value` must have the type of the column the parameter is compared with.
+    pub fn bind(mut self, name: &str, value: &impl sats::ser::Serialize) -> Self {
+        let value = sats::bsatn::to_vec(value).expect("Serializing query parameter failed");
+        self.params.push((name.trim_start_matches(':').to_owned(), value));
+        self
+    }
+}
+
+/// Subscribe to a set of queries and of parameterized queries,
+/// to be notified when rows which match those queries are altered.
+///
+/// The clients subscribed to the same query template share its plan on the database,
+/// which makes them much cheaper to serve than queries with the values spliced into their text.
+///
+/// Like [`subscribe`], a new call to `subscribe_parameterized` replaces all previous subscriptions.
+pub fn subscribe_parameterized(queries: Vec<String>, parameterized: Vec<ParameterizedQuery>) -> anyhow::Result<()> {
+    with_connection(|conn| conn.subscribe_parameterized(queries, parameterized))
+}
+
+/// Identifies a query added by [`subscribe_add`], to remove it with [`unsubscribe`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct QueryId(u32);
+
+/// Add a query to the subscribed queries,
+/// to be notified when rows which match it are altered.
+///
+/// Unlike [`subscribe`], `subscribe_add` keeps the previously subscribed queries,
+/// and the database doesn't evaluate them again.
+/// The rows which match the new `query` but none of the other subscribed queries
+/// will be inserted into the client cache,
+/// and `TableType::on_insert` callbacks will be invoked for them.
+///
+/// A later call to [`subscribe`] replaces the queries added by `subscribe_add` too.
+///
+/// The returned `QueryId` can be passed to [`unsubscribe`] to remove the query.
+pub fn subscribe_add(query: &str) -> anyhow::Result<QueryId> {
+    with_connection(|conn| conn.subscribe_add(query))
+}
+
+/// Remove a query added by [`subscribe_add`] from the subscribed queries.
+///
+/// The rows which matched the query but match none of the remaining subscribed queries
+/// will be removed from the client cache,
+/// and `TableType::on_delete` callbacks will be invoked for them.
+pub fn unsubscribe(id: QueryId) -> anyhow::Result<()> {
+    with_connection(|conn| conn.unsubscribe(id))
+}
+
 #[derive(Copy, Clone)]
 pub struct SubscriptionCallbackId {
     id: CallbackId<()>,
@@ -168,3 +236,64 @@ pub fn once_on_disconnect(callback: impl FnOnce() + Send + 'static) -> Disconnec
 pub fn remove_on_disconnect(id: DisconnectCallbackId) {
     with_disconnect_callbacks(|dc_callbacks| dc_callbacks.unregister_on_disconnect(id.id));
 }
+
+/// How a connection is re-established after it drops, see [`set_reconnect_policy`].
+#[derive(Clone, Debug)]
+pub struct ReconnectPolicy {
+    /// How long to wait before the first attempt to reconnect.
+    pub initial_delay: std::time::Duration,
+    /// The delay doubles after each failed attempt, up to `max_delay`.
+    pub max_delay: std::time::Duration,
+    /// How many attempts to make before giving up, or `None` to never give up.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: std::time::Duration::from_millis(500),
+            max_delay: std::time::Duration::from_secs(30),
+            max_attempts: None,
+        }
+    }
+}
+
+/// Reconnect automatically when the connection drops, following `policy`,
+/// or don't if `policy` is `None`, which is the default.
+///
+/// The new connection reuses the `Credentials` and `Address` of the dropped one,
+/// and subscribes to the same queries, including those added by [`subscribe_add`].
+/// The client cache is reconciled with the rows the database sends,
+/// so row callbacks are invoked only for the rows which changed while disconnected,
+/// and the `on_connect` and `on_subscription_applied` callbacks are not invoked again.
+/// The `on_disconnect` callbacks are invoked only once the attempts to reconnect run out,
+/// or after a call to [`disconnect`].
+///
+/// Reducer calls made while disconnected fail, and the outcome of those
+/// in flight when the connection dropped is never received.
+///
+/// The policy applies to connections established after calling `set_reconnect_policy`.
+pub fn set_reconnect_policy(policy: Option<ReconnectPolicy>) {
+    with_connection_mut(|conn| conn.set_reconnect_policy(policy));
+}
+
+/// Persist the client cache to the file at `path`, or don't if `path` is `None`, which is the default.
+///
+/// The client cache is saved to the file when the connection ends,
+/// notably after a call to [`disconnect`], along with the subscribed queries
+/// and the last transaction whose changes it holds.
+/// When connecting, the client cache starts with the rows saved in the file,
+/// without invoking row callbacks for them.
+/// If the first call to [`subscribe`] is then to the same queries,
+/// the database only sends the changes made to the rows since the saved transaction,
+/// and row callbacks are invoked for those changes only.
+/// If it no longer holds those changes, as after many transactions or a restart,
+/// it sends every subscribed row, and the client cache is reconciled with them.
+///
+/// The client cache isn't saved if it doesn't hold the rows as of a known transaction,
+/// as after [`subscribe_add`] or [`unsubscribe`] until the next call to [`subscribe`].
+///
+/// The path applies to connections established after calling `persist_client_cache`.
+pub fn persist_client_cache(path: Option<std::path::PathBuf>) {
+    with_connection_mut(|conn| conn.set_cache_file(path));
+}
@@ -3,19 +3,57 @@ use crate::global_connection::{with_connection, with_reducer_callbacks};
 use crate::identity::Identity;
 use crate::Address;
 use anyhow::Result;
+use futures::FutureExt;
+use futures_channel::oneshot;
 use spacetimedb_sats::{de::DeserializeOwned, ser::Serialize};
 use std::any::Any;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum Status {
     Committed,
     Failed(String),
     OutOfEnergy,
+    /// The caller isn't allowed to call the reducer, so it didn't run.
+    Unauthorized(String),
+}
+
+/// The outcome of a single reducer call, returned by [`Reducer::invoke_async`].
+///
+/// Resolves to `Status::Failed` if the call could not be sent,
+/// or if the connection ended before the outcome was received.
+pub struct InvokeFuture {
+    recv: oneshot::Receiver<Status>,
+}
+
+impl InvokeFuture {
+    pub(crate) fn new(recv: oneshot::Receiver<Status>) -> Self {
+        InvokeFuture { recv }
+    }
+
+    /// An `InvokeFuture` for a call which could not be sent.
+    pub(crate) fn failed(error: anyhow::Error) -> Self {
+        let (send, recv) = oneshot::channel();
+        let _ = send.send(Status::Failed(format!("{:#}", error)));
+        InvokeFuture { recv }
+    }
+}
+
+impl Future for InvokeFuture {
+    type Output = Status;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Status> {
+        self.recv.poll_unpin(cx).map(|status| {
+            status.unwrap_or_else(|_| Status::Failed("Connection ended before the reducer call completed".to_string()))
+        })
+    }
 }
 
 #[derive(Copy, Clone)]
 pub struct ReducerCallbackId<R> {
-    id: CallbackId<(Identity, Option<Address>, Status, R)>,
+    pub(crate) id: CallbackId<(Identity, Option<Address>, Status, R)>,
 }
 
 // Any bound so these can be keys in an `AnyMap` to store callbacks.
@@ -30,6 +68,15 @@ pub trait Reducer: DeserializeOwned + Serialize + Any + Send + Sync + Clone {
         with_connection(|conn| conn.invoke_reducer(self))
     }
 
+    /// Request the database to run the reducer,
+    /// returning a future which resolves to the outcome of this particular call.
+    ///
+    /// Unlike `on_reducer` callbacks, which run for every call to the reducer by any client,
+    /// the future is resolved only by the `TransactionUpdate` the database sends in reply to this call.
+    fn invoke_async(self) -> InvokeFuture {
+        with_connection(|conn| conn.invoke_reducer_async(self))
+    }
+
     /// Register a callback to run after the reducer runs.
     ///
     // TODO: the cli should generate a more convenient function `on_{REDUCER_NAME}` for
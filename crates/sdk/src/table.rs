@@ -3,8 +3,15 @@ use crate::client_cache::TableCache;
 use crate::global_connection::{try_with_client_cache, with_db_callbacks};
 use crate::reducer::AnyReducerEvent;
 use anyhow::{anyhow, Result};
+use futures::stream::{Stream, StreamExt};
+use futures_channel::mpsc;
 use spacetimedb_sats::{de::DeserializeOwned, ser::Serialize};
-use std::{any::Any, sync::Arc};
+use std::{
+    any::Any,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
 
 /// A unique identifier for an `on_insert` callback registered with a table.
 ///
@@ -12,7 +19,7 @@ use std::{any::Any, sync::Arc};
 /// `TableType::remove_on_insert` to remove the callback.
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub struct InsertCallbackId<T: TableType> {
-    id: CallbackId<(T, Option<Arc<AnyReducerEvent>>)>,
+    pub(crate) id: CallbackId<(T, Option<Arc<AnyReducerEvent>>)>,
 }
 
 /// A unique identifier for an `on_delete` callback registered with a table.
@@ -21,7 +28,7 @@ pub struct InsertCallbackId<T: TableType> {
 /// `TableType::remove_on_delete` to remove the callback.
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub struct DeleteCallbackId<T: TableType> {
-    id: CallbackId<(T, Option<Arc<AnyReducerEvent>>)>,
+    pub(crate) id: CallbackId<(T, Option<Arc<AnyReducerEvent>>)>,
 }
 
 /// An iterator over all of the rows in `Table`.
@@ -30,7 +37,7 @@ pub struct DeleteCallbackId<T: TableType> {
 pub struct TableIter<Table> {
     // The specific iterator type here should remain opaque to users, so that we can
     // change the implementation of `TableCache`.
-    iter: std::vec::IntoIter<Table>,
+    pub(crate) iter: std::vec::IntoIter<Table>,
 }
 
 impl<Table> Iterator for TableIter<Table> {
@@ -45,6 +52,92 @@ impl<Table> Iterator for TableIter<Table> {
     }
 }
 
+/// A change to a row in a table, as yielded by the stream from `TableType::events`.
+///
+/// Like the arguments to the row callbacks, each event carries the `ReducerEvent`
+/// of the reducer which caused it, or `None` for changes from a subscription being applied.
+pub enum TableEvent<T: TableType> {
+    /// `row` was newly inserted into the table, as for `TableType::on_insert`.
+    Insert {
+        row: T,
+        reducer_event: Option<Arc<T::ReducerEvent>>,
+    },
+
+    /// `row` was removed from the table, as for `TableType::on_delete`.
+    Delete {
+        row: T,
+        reducer_event: Option<Arc<T::ReducerEvent>>,
+    },
+
+    /// `old` was replaced by `new`, as for `TableWithPrimaryKey::on_update`.
+    ///
+    /// Only tables with a column annotated `#[primarykey]` have update events;
+    /// for other tables, an update is a `Delete` and an `Insert`.
+    Update {
+        old: T,
+        new: T,
+        reducer_event: Option<Arc<T::ReducerEvent>>,
+    },
+}
+
+// Not derived, as the derives would require `T::ReducerEvent: Clone + Debug`.
+impl<T: TableType> Clone for TableEvent<T> {
+    fn clone(&self) -> Self {
+        match self {
+            TableEvent::Insert { row, reducer_event } => TableEvent::Insert {
+                row: row.clone(),
+                reducer_event: reducer_event.clone(),
+            },
+            TableEvent::Delete { row, reducer_event } => TableEvent::Delete {
+                row: row.clone(),
+                reducer_event: reducer_event.clone(),
+            },
+            TableEvent::Update {
+                old,
+                new,
+                reducer_event,
+            } => TableEvent::Update {
+                old: old.clone(),
+                new: new.clone(),
+                reducer_event: reducer_event.clone(),
+            },
+        }
+    }
+}
+
+impl<T: TableType> std::fmt::Debug for TableEvent<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TableEvent::Insert { row, .. } => f.debug_struct("Insert").field("row", row).finish_non_exhaustive(),
+            TableEvent::Delete { row, .. } => f.debug_struct("Delete").field("row", row).finish_non_exhaustive(),
+            TableEvent::Update { old, new, .. } => f
+                .debug_struct("Update")
+                .field("old", old)
+                .field("new", new)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+/// A `Stream` of the changes to the rows in `Table`, returned by `TableType::events`.
+// Defined here for the same reason as `TableIter`.
+pub struct TableEventStream<Table: TableType> {
+    // As with `TableIter`, the channel type should remain opaque to users.
+    pub(crate) recv: mpsc::Receiver<TableEvent<Table>>,
+}
+
+impl<Table: TableType> Stream for TableEventStream<Table> {
+    type Item = TableEvent<Table>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<TableEvent<Table>>> {
+        self.recv.poll_next_unpin(cx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.recv.size_hint()
+    }
+}
+
 /// Because `Result::flatten` is unstable as of Rust 1.70.
 fn flatten_result<T>(res: Result<Result<T>>) -> Result<T> {
     res.and_then(|x| x)
@@ -181,6 +274,25 @@ pub trait TableType: DeserializeOwned + Serialize + Any + Send + Sync + Clone +
     fn remove_on_delete(id: DeleteCallbackId<Self>) {
         with_callbacks::<Self, _>(|table_callbacks| table_callbacks.unregister_on_delete(id.id));
     }
+
+    /// Returns a `Stream` of every insert, delete and update to this table
+    /// from now on, in the order they are applied to the client cache.
+    ///
+    /// This is an async alternative to `on_insert`, `on_delete` and `on_update`,
+    /// dispatched alongside those callbacks,
+    /// so that the changes to a table can be awaited with e.g. `select!`
+    /// together with the results of `invoke_async` reducer calls.
+    ///
+    /// Each stream buffers a fixed number of events. If a stream isn't polled,
+    /// delivery of events to all of this table's streams waits for it,
+    /// and the events not yet delivered are queued without bound.
+    /// This doesn't slow down the client cache nor the row callbacks, which are dispatched separately.
+    ///
+    /// Dropping the stream unregisters it.
+    fn events() -> TableEventStream<Self> {
+        let recv = with_callbacks::<Self, _>(|table_callbacks| table_callbacks.register_event_stream());
+        TableEventStream { recv }
+    }
 }
 
 /// A unique identifier for an `on_update` callback registered with a table.
@@ -189,7 +301,7 @@ pub trait TableType: DeserializeOwned + Serialize + Any + Send + Sync + Clone +
 /// `TableWithPrimaryKey::remove_on_update` to remove the callback.
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub struct UpdateCallbackId<T: TableWithPrimaryKey> {
-    id: CallbackId<(T, T, Option<Arc<AnyReducerEvent>>)>,
+    pub(crate) id: CallbackId<(T, T, Option<Arc<AnyReducerEvent>>)>,
 }
 
 /// A `TableType` with a column annotated `#[primarykey]`, allowing `on_update` callbacks.
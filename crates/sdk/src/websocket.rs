@@ -15,7 +15,7 @@ use tokio_tungstenite::{
     MaybeTlsStream, WebSocketStream,
 };
 
-pub(crate) struct DbConnection {
+pub(crate) struct WsConnection {
     sock: WebSocketStream<MaybeTlsStream<TcpStream>>,
 }
 
@@ -122,7 +122,7 @@ fn request_insert_auth_header(req: &mut http::Request<()>, credentials: Option<&
     };
 }
 
-impl DbConnection {
+impl WsConnection {
     pub(crate) async fn connect<Host>(
         host: Host,
         db_name: &str,
@@ -147,7 +147,7 @@ impl DbConnection {
             false,
         )
         .await?;
-        Ok(DbConnection { sock })
+        Ok(WsConnection { sock })
     }
 
     pub(crate) fn parse_response(bytes: &[u8]) -> Result<Message> {
@@ -4,6 +4,7 @@
 use spacetimedb_sdk::callbacks::{DbCallbacks, ReducerCallbacks};
 use spacetimedb_sdk::client_api_messages::{Event, TableUpdate};
 use spacetimedb_sdk::client_cache::{ClientCache, RowCallbackReminders};
+use spacetimedb_sdk::db_connection::DbConnection;
 use spacetimedb_sdk::global_connection::with_connection_mut;
 use spacetimedb_sdk::identity::Credentials;
 use spacetimedb_sdk::reducer::AnyReducerEvent;
@@ -110,3 +111,26 @@ where
         Ok(())
     })
 }
+
+/// Connect `connection` to a database named `db_name` accessible over the internet at the URI `spacetimedb_uri`.
+///
+/// Unlike `connect`, this does not use the global connection,
+/// so several `DbConnection`s can be connected at once,
+/// to different databases or with different `credentials`.
+///
+/// If `credentials` are supplied, they will be passed to the new connection to
+/// identify and authenticate the user. Otherwise, a set of `Credentials` will be
+/// generated by the server.
+#[allow(unused)]
+pub fn connect_with<IntoUri>(
+    connection: &DbConnection,
+    spacetimedb_uri: IntoUri,
+    db_name: &str,
+    credentials: Option<Credentials>,
+) -> Result<()>
+where
+    IntoUri: TryInto<spacetimedb_sdk::http::Uri>,
+    <IntoUri as TryInto<spacetimedb_sdk::http::Uri>>::Error: std::error::Error + Send + Sync + 'static,
+{
+    connection.connect(spacetimedb_uri, db_name, credentials, Arc::new(Module))
+}
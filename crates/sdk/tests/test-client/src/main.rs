@@ -1,10 +1,11 @@
 use spacetimedb_sdk::{
+    db_connection::DbConnection,
     disconnect,
     identity::{address, identity, load_credentials, once_on_connect, save_credentials},
     once_on_disconnect, once_on_subscription_applied,
-    reducer::Status,
+    reducer::{Reducer, Status},
     subscribe,
-    table::TableType,
+    table::{TableEvent, TableEventStream, TableType},
 };
 
 #[allow(clippy::too_many_arguments)]
@@ -72,6 +73,11 @@ fn main() {
 
         "on_reducer" => exec_on_reducer(),
         "fail_reducer" => exec_fail_reducer(),
+        "invoke_async" => exec_invoke_async(),
+
+        "persist_client_cache" => exec_persist_client_cache(),
+
+        "table_event_stream" => exec_table_event_stream(),
 
         "insert_vec" => exec_insert_vec(),
 
@@ -661,6 +667,177 @@ fn exec_on_reducer() {
     test_counter.wait_for_all();
 }
 
+/// This tests that awaiting `invoke_async` yields the outcome of that particular call.
+fn exec_invoke_async() {
+    let test_counter = TestCounter::new();
+    let name = db_name_or_panic();
+
+    let conn_result = test_counter.add_test("connect");
+
+    let sub_result = test_counter.add_test("subscribe");
+
+    let sub_applied_nothing_result = test_counter.add_test("on_subscription_applied_nothing");
+
+    let reducer_success_result = test_counter.add_test("invoke-async-success");
+    let reducer_fail_result = test_counter.add_test("invoke-async-fail");
+
+    let key = 128;
+    let initial_data = 0xbeef;
+    let fail_data = 0xbabe;
+
+    once_on_subscription_applied(move || {
+        sub_applied_nothing_result(assert_all_tables_empty());
+
+        // Awaiting blocks, so it mustn't happen on the callback thread.
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+
+            let status = runtime.block_on(
+                InsertPkU8Args {
+                    n: key,
+                    data: initial_data,
+                }
+                .invoke_async(),
+            );
+            reducer_success_result(if !matches!(status, Status::Committed) {
+                Err(anyhow::anyhow!(
+                    "Unexpected status. Expected Committed but found {:?}",
+                    status
+                ))
+            } else if PkU8::count() != 1 {
+                Err(anyhow::anyhow!(
+                    "Expected 1 row in table PkU8, but found {}",
+                    PkU8::count()
+                ))
+            } else {
+                Ok(())
+            });
+
+            // Inserting the same key again violates the primary key constraint.
+            let status = runtime.block_on(
+                InsertPkU8Args {
+                    n: key,
+                    data: fail_data,
+                }
+                .invoke_async(),
+            );
+            reducer_fail_result(if !matches!(status, Status::Failed(_)) {
+                Err(anyhow::anyhow!(
+                    "Unexpected status. Expected Failed but found {:?}",
+                    status
+                ))
+            } else {
+                Ok(())
+            });
+        });
+    });
+
+    once_on_connect(move |_, _| sub_result(subscribe(SUBSCRIBE_ALL)));
+
+    conn_result(connect(LOCALHOST, &name, None));
+
+    test_counter.wait_for_all();
+}
+
+/// Invoke a reducer and wait for both its result and the next event on `events`,
+/// whichever arrives first.
+async fn invoke_and_next_event<R: Reducer>(
+    args: R,
+    events: &mut TableEventStream<PkU8>,
+) -> (Status, Option<TableEvent<PkU8>>) {
+    use futures::StreamExt;
+
+    let call = args.invoke_async();
+    tokio::pin!(call);
+    let (mut status, mut event) = (None, None);
+    while status.is_none() || event.is_none() {
+        tokio::select! {
+            res = &mut call, if status.is_none() => status = Some(res),
+            ev = events.next(), if event.is_none() => event = Some(ev),
+        }
+    }
+    (status.unwrap(), event.unwrap())
+}
+
+/// This tests that a stream from `TableType::events` yields the inserts, updates and deletes
+/// caused by a reducer, and can be awaited together with its result.
+fn exec_table_event_stream() {
+    let test_counter = TestCounter::new();
+    let name = db_name_or_panic();
+
+    let conn_result = test_counter.add_test("connect");
+
+    let sub_result = test_counter.add_test("subscribe");
+
+    let sub_applied_nothing_result = test_counter.add_test("on_subscription_applied_nothing");
+
+    let insert_result = test_counter.add_test("insert-event");
+    let update_result = test_counter.add_test("update-event");
+    let delete_result = test_counter.add_test("delete-event");
+
+    let key = 128;
+    let initial_data = 0xbeef;
+    let update_data = 0xbabe;
+
+    once_on_subscription_applied(move || {
+        sub_applied_nothing_result(assert_all_tables_empty());
+
+        let mut events = PkU8::events();
+
+        // Awaiting blocks, so it mustn't happen on the callback thread.
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+
+            let insert = InsertPkU8Args {
+                n: key,
+                data: initial_data,
+            };
+            insert_result(match runtime.block_on(invoke_and_next_event(insert, &mut events)) {
+                (Status::Committed, Some(TableEvent::Insert { row, reducer_event }))
+                    if row.n == key
+                        && row.data == initial_data
+                        && matches!(reducer_event.as_deref(), Some(ReducerEvent::InsertPkU8(_))) =>
+                {
+                    Ok(())
+                }
+                other => Err(anyhow::anyhow!("Unexpected result of insert: {:?}", other)),
+            });
+
+            let update = UpdatePkU8Args {
+                n: key,
+                data: update_data,
+            };
+            update_result(match runtime.block_on(invoke_and_next_event(update, &mut events)) {
+                (Status::Committed, Some(TableEvent::Update { old, new, .. }))
+                    if old.data == initial_data && new.n == key && new.data == update_data =>
+                {
+                    Ok(())
+                }
+                other => Err(anyhow::anyhow!("Unexpected result of update: {:?}", other)),
+            });
+
+            let delete = DeletePkU8Args { n: key };
+            delete_result(match runtime.block_on(invoke_and_next_event(delete, &mut events)) {
+                (Status::Committed, Some(TableEvent::Delete { row, .. }))
+                    if row.n == key && row.data == update_data =>
+                {
+                    Ok(())
+                }
+                other => Err(anyhow::anyhow!("Unexpected result of delete: {:?}", other)),
+            });
+        });
+    });
+
+    once_on_connect(move |_, _| sub_result(subscribe(SUBSCRIBE_ALL)));
+
+    conn_result(connect(LOCALHOST, &name, None));
+
+    test_counter.wait_for_all();
+}
+
 /// This tests that we can observe reducer callbacks for failed reducers.
 fn exec_fail_reducer() {
     let test_counter = TestCounter::new();
@@ -1289,6 +1466,56 @@ fn exec_reconnect() {
     todo!()
 }
 
+/// Saves the client cache of a connection to a file,
+/// then checks that a later connection starts from the saved rows,
+/// and is only sent the rows inserted since.
+fn exec_persist_client_cache() {
+    let name = db_name_or_panic();
+    let path = std::env::temp_dir().join(format!("{name}-client-cache"));
+    let _ = std::fs::remove_file(&path);
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    let insert = |conn: &DbConnection, n: u8| {
+        let status = runtime.block_on(conn.invoke_async(InsertPkU8Args { n, data: 0 }));
+        assert!(matches!(status, Status::Committed), "Inserting {n} failed: {status:?}");
+    };
+    let timeout = std::time::Duration::from_secs(10);
+    let subscribe_and_wait = |conn: &DbConnection| {
+        let (applied_send, applied_recv) = std::sync::mpsc::channel();
+        conn.once_on_subscription_applied(move || applied_send.send(()).unwrap());
+        conn.subscribe(&["SELECT * FROM PkU8"]).unwrap();
+        applied_recv
+            .recv_timeout(timeout)
+            .expect("Subscription was not applied");
+    };
+
+    let first = DbConnection::new().unwrap();
+    first.persist_client_cache(Some(path.clone()));
+    connect_with(&first, LOCALHOST, &name, None).unwrap();
+    subscribe_and_wait(&first);
+    insert(&first, 1);
+    first.disconnect();
+    assert!(path.exists(), "The client cache was not saved");
+
+    let writer = DbConnection::new().unwrap();
+    connect_with(&writer, LOCALHOST, &name, None).unwrap();
+    insert(&writer, 2);
+    writer.disconnect();
+
+    let second = DbConnection::new().unwrap();
+    second.persist_client_cache(Some(path.clone()));
+    let (inserted_send, inserted_recv) = std::sync::mpsc::channel();
+    second.on_insert(move |row: &PkU8, _| inserted_send.send(row.n).unwrap());
+    connect_with(&second, LOCALHOST, &name, None).unwrap();
+    assert_eq!(second.count::<PkU8>(), 1, "The saved rows were not loaded");
+    subscribe_and_wait(&second);
+    assert_eq!(second.count::<PkU8>(), 2);
+    assert_eq!(inserted_recv.recv_timeout(timeout), Ok(2));
+    // The row loaded from the file doesn't invoke the on-insert callbacks.
+    assert!(inserted_recv.recv_timeout(std::time::Duration::from_secs(1)).is_err());
+    second.disconnect();
+    let _ = std::fs::remove_file(&path);
+}
+
 /// Part of the `reauth` test, this connects to Spacetime to get new credentials,
 /// and saves them to a file.
 fn exec_reauth_part_1() {
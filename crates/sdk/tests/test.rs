@@ -70,6 +70,21 @@ fn fail_reducer() {
     make_test("fail_reducer").run();
 }
 
+#[test]
+fn invoke_async() {
+    make_test("invoke_async").run();
+}
+
+#[test]
+fn persist_client_cache() {
+    make_test("persist_client_cache").run();
+}
+
+#[test]
+fn table_event_stream() {
+    make_test("table_event_stream").run();
+}
+
 #[test]
 fn insert_vec() {
     make_test("insert_vec").run();
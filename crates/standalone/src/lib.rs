@@ -18,18 +18,24 @@ use spacetimedb::client::ClientActorIndex;
 use spacetimedb::control_db::{self, ControlDb};
 use spacetimedb::database_instance_context::DatabaseInstanceContext;
 use spacetimedb::database_instance_context_controller::DatabaseInstanceContextController;
+use spacetimedb::db::commit_log::RestorePoint;
 use spacetimedb::db::{db_metrics, Config};
+use spacetimedb::error::DBError;
 use spacetimedb::host::EnergyQuanta;
 use spacetimedb::host::UpdateDatabaseResult;
 use spacetimedb::host::UpdateOutcome;
-use spacetimedb::host::{scheduler::Scheduler, HostController};
+use spacetimedb::host::{
+    scheduler::{self, Scheduler},
+    HostController,
+};
 use spacetimedb::identity::Identity;
 use spacetimedb::messages::control_db::{Database, DatabaseInstance, HostType, IdentityEmail, Node};
 use spacetimedb::module_host_context::ModuleHostContext;
 use spacetimedb::object_db::ObjectDb;
 use spacetimedb::sendgrid_controller::SendGridController;
 use spacetimedb::{stdb_path, worker_metrics};
-use spacetimedb_lib::name::{DomainName, InsertDomainResult, RegisterTldResult, Tld};
+use spacetimedb_client_api::FsyncPolicyUpdate;
+use spacetimedb_lib::name::{DomainName, InsertDomainResult, RegisterTldResult, SchemaDiff, Tld};
 use spacetimedb_lib::recovery::RecoveryCode;
 use std::fs::File;
 use std::io::Write;
@@ -73,6 +79,16 @@ impl StandaloneEnv {
         energy_monitor.set_standalone_env(this.clone());
         Ok(this)
     }
+
+    /// The config of the instances of the database of id `database_id`,
+    /// i.e. that of the environment with the database's own [`FsyncPolicy`](spacetimedb::db::FsyncPolicy), if any.
+    fn database_config(&self, database_id: u64) -> spacetimedb::control_db::Result<Config> {
+        let fsync = self.control_db.get_fsync_policy(database_id)?;
+        Ok(Config {
+            fsync: fsync.unwrap_or(self.config.fsync),
+            ..self.config
+        })
+    }
 }
 
 fn get_or_create_keys() -> anyhow::Result<(DecodingKey, EncodingKey, Box<[u8]>)> {
@@ -300,18 +316,21 @@ impl spacetimedb_client_api::ControlStateWriteAccess for StandaloneEnv {
 
         if let Some(existing) = existing_db.as_ref() {
             if &existing.identity != identity {
-                return Err(anyhow!(
-                    "Permission denied: `{}` does not own database `{}`",
-                    identity.to_hex(),
-                    spec.address.to_abbreviated_hex()
-                )
-                .into());
+                return Err(spacetimedb::control_db::Error::PermissionDenied {
+                    identity: *identity,
+                    address: spec.address,
+                });
             }
             self.control_db.update_database(database.clone())?;
         } else {
             let id = self.control_db.insert_database(database.clone())?;
             database.id = id;
         }
+        match spec.fsync {
+            FsyncPolicyUpdate::Keep => {}
+            FsyncPolicyUpdate::HostDefault => self.control_db.set_fsync_policy(database.id, None)?,
+            FsyncPolicyUpdate::Set(policy) => self.control_db.set_fsync_policy(database.id, Some(policy))?,
+        }
 
         let database_id = database.id;
         let should_update_instances = existing_db.is_some();
@@ -323,12 +342,45 @@ impl spacetimedb_client_api::ControlStateWriteAccess for StandaloneEnv {
                 .control_db
                 .get_leader_database_instance_by_database(database_id)
                 .ok_or_else(|| anyhow!("Not found: leader instance for database {database_id}"))?;
+            // A running instance keeps the policy its commit log was opened with, unless told otherwise.
+            if !matches!(spec.fsync, FsyncPolicyUpdate::Keep) {
+                if let Some((dbic, _)) = self.db_inst_ctx_controller.get(leader.id) {
+                    let fsync = self.database_config(database_id)?.fsync;
+                    dbic.relational_db.set_fsync_policy(fsync);
+                }
+            }
             Ok(self.update_database_instance(leader).await?)
         } else {
             Ok(None)
         }
     }
 
+    async fn publish_database_dry_run(
+        &self,
+        identity: &Identity,
+        spec: spacetimedb_client_api::DatabaseDef,
+    ) -> spacetimedb::control_db::Result<Option<SchemaDiff>> {
+        let Some(database) = self.control_db.get_database_by_address(&spec.address)? else {
+            return Ok(None);
+        };
+        if &database.identity != identity {
+            return Err(spacetimedb::control_db::Error::PermissionDenied {
+                identity: *identity,
+                address: spec.address,
+            });
+        }
+
+        let database_id = database.id;
+        let leader = self
+            .control_db
+            .get_leader_database_instance_by_database(database_id)
+            .ok_or_else(|| anyhow!("Not found: leader instance for database {database_id}"))?;
+        // The proposed module runs against the live database instance.
+        let mut ctx = self.load_module_host_context(database, leader.id).await?;
+        ctx.program_bytes = spec.program_bytes.into();
+        Ok(Some(self.host_controller.schema_diff(ctx).await?))
+    }
+
     async fn delete_database(&self, identity: &Identity, address: &Address) -> spacetimedb::control_db::Result<()> {
         let Some(database) = self.control_db.get_database_by_address(address)? else {
             return Ok(());
@@ -351,6 +403,114 @@ impl spacetimedb_client_api::ControlStateWriteAccess for StandaloneEnv {
         Ok(())
     }
 
+    async fn restore_database(
+        &self,
+        identity: &Identity,
+        source: &Address,
+        point: RestorePoint,
+        address: &Address,
+        domain: Option<&DomainName>,
+    ) -> spacetimedb::control_db::Result<()> {
+        let source = self
+            .control_db
+            .get_database_by_address(source)?
+            .ok_or_else(|| anyhow!("Not found: database {}", source.to_abbreviated_hex()))?;
+        if &source.identity != identity {
+            return Err(spacetimedb::control_db::Error::PermissionDenied {
+                identity: *identity,
+                address: source.address,
+            });
+        }
+        if self.control_db.get_database_by_address(address)?.is_some() {
+            return Err(anyhow!("Database {} already exists", address.to_abbreviated_hex()).into());
+        }
+        let (source_dbic, _) = self
+            .control_db
+            .get_leader_database_instance_by_database(source.id)
+            .and_then(|leader| self.db_inst_ctx_controller.get(leader.id))
+            .ok_or_else(|| anyhow!("Not found: leader instance for database {}", source.id))?;
+
+        let mut database = Database {
+            id: 0,
+            address: *address,
+            num_replicas: 1,
+            ..source
+        };
+        database.id = self.control_db.insert_database(database.clone())?;
+        self.control_db
+            .set_fsync_policy(database.id, self.control_db.get_fsync_policy(source.id)?)?;
+        let mut instance = DatabaseInstance {
+            id: 0,
+            database_id: database.id,
+            node_id: 0,
+            leader: true,
+        };
+        instance.id = self.control_db.insert_database_instance(instance.clone())?;
+
+        let root_db_path = stdb_path("worker_node/database_instances");
+        let result = async {
+            let root_db_path = root_db_path.clone();
+            let (dbic, (scheduler, _)) = tokio::task::spawn_blocking({
+                let database = database.clone();
+                let config = self.database_config(database.id)?;
+                move || -> anyhow::Result<_> {
+                    let dbic = DatabaseInstanceContext::restore_from(
+                        config,
+                        &database,
+                        instance.id,
+                        root_db_path,
+                        &source_dbic.relational_db,
+                        point,
+                    )?;
+                    let sched = Scheduler::open(dbic.relational_db.clone());
+                    Ok((dbic, sched))
+                }
+            })
+            .await??;
+
+            // The fork runs the module it had as of the restore point.
+            let program_hash = dbic
+                .relational_db
+                .with_read_only(|tx| dbic.relational_db.program_hash(tx))?;
+            if let Some(program_hash) = program_hash.filter(|hash| *hash != database.program_bytes_address) {
+                database.program_bytes_address = program_hash;
+                self.control_db.update_database(database.clone())?;
+            }
+
+            self.db_inst_ctx_controller.insert(dbic, scheduler);
+            self.on_insert_database_instance(&instance).await
+        }
+        .await
+        .map_err(|e| match e.downcast_ref::<DBError>() {
+            Some(DBError::InvalidRestorePoint(msg)) => spacetimedb::control_db::Error::InvalidRestorePoint(msg.clone()),
+            _ => e.into(),
+        })
+        // The name is only registered once the restore succeeded, so a failed one leaves no record behind.
+        .and_then(|()| match domain {
+            Some(domain) => self
+                .control_db
+                .spacetime_insert_domain(address, domain.clone(), *identity, true)
+                .map(drop),
+            None => Ok(()),
+        });
+
+        if let Err(e) = result {
+            log::warn!("Failed to restore database {}: {e:#}", address.to_abbreviated_hex());
+            self.control_db.delete_database_instance(instance.id)?;
+            self.control_db.delete_database(database.id)?;
+            self.on_delete_database_instance(instance.id).await?;
+            // The address is fresh, so everything stored under it belongs to the failed restore.
+            let db_path = root_db_path.join(address.to_hex());
+            if let Err(err) = std::fs::remove_dir_all(&db_path) {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    log::error!("Failed to remove {}: {err}", db_path.display());
+                }
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+
     async fn create_identity(&self) -> spacetimedb::control_db::Result<Identity> {
         self.control_db.alloc_spacetime_identity()
     }
@@ -587,6 +747,18 @@ impl StandaloneEnv {
                             .host_controller
                             .update_module_host(lock.token() as u128, ctx)
                             .await?;
+                        if let Err(e) = &update_result {
+                            // The update was rolled back, so the database still
+                            // matches the old module, which is put back in place.
+                            log::warn!("Update rejected, restoring module {}: {}", hash, e);
+                            let old_database = Database {
+                                program_bytes_address: hash,
+                                ..database
+                            };
+                            self.control_db.update_database(old_database.clone())?;
+                            let ctx = self.load_module_host_context(old_database, instance.id).await?;
+                            self.host_controller.spawn_module_host(ctx).await?;
+                        }
                         Ok(Some(update_result))
                     }
                 }
@@ -604,14 +776,11 @@ impl StandaloneEnv {
         // database instances which have been deleted. This will just drop
         // them from memory, but will not remove them from disk.  We need
         // some kind of database lifecycle manager long term.
-        let (_, scheduler) = self.db_inst_ctx_controller.remove(instance_id).unzip();
+        self.db_inst_ctx_controller.remove(instance_id);
         self.host_controller
             .delete_module_host(lock.token() as u128, instance_id)
             .await
             .unwrap();
-        if let Some(scheduler) = scheduler {
-            scheduler.clear();
-        }
 
         Ok(())
     }
@@ -634,26 +803,28 @@ impl StandaloneEnv {
 
         let root_db_path = stdb_path("worker_node/database_instances");
 
-        let (dbic, (scheduler, scheduler_starter)) =
-            if let Some((dbic, scheduler)) = self.db_inst_ctx_controller.get(instance_id) {
-                (dbic, scheduler.new_with_same_db())
-            } else {
-                // `spawn_blocking` because we're accessing the filesystem
-                let (dbic, (scheduler, scheduler_starter)) = tokio::task::spawn_blocking({
-                    let database = database.clone();
-                    let path = root_db_path.clone();
-                    let config = self.config;
-                    move || -> anyhow::Result<_> {
-                        let dbic = DatabaseInstanceContext::from_database(config, &database, instance_id, path);
-                        let sched = Scheduler::open(dbic.scheduler_db_path(root_db_path))?;
-                        Ok((dbic, sched))
-                    }
-                })
-                .await??;
+        let (dbic, (scheduler, scheduler_starter)) = if let Some((dbic, scheduler)) =
+            self.db_inst_ctx_controller.get(instance_id)
+        {
+            (dbic, scheduler.new_with_same_db())
+        } else {
+            // `spawn_blocking` because we're accessing the filesystem
+            let (dbic, (scheduler, scheduler_starter)) = tokio::task::spawn_blocking({
+                let database = database.clone();
+                let config = self.database_config(database.id)?;
+                move || -> anyhow::Result<_> {
+                    let dbic =
+                        DatabaseInstanceContext::from_database(config, &database, instance_id, root_db_path.clone());
+                    scheduler::import_legacy_schedules(&dbic.relational_db, &dbic.scheduler_db_path(root_db_path))?;
+                    let sched = Scheduler::open(dbic.relational_db.clone());
+                    Ok((dbic, sched))
+                }
+            })
+            .await??;
 
-                self.db_inst_ctx_controller.insert(dbic.clone(), scheduler.clone());
-                (dbic, (scheduler, scheduler_starter))
-            };
+            self.db_inst_ctx_controller.insert(dbic.clone(), scheduler.clone());
+            (dbic, (scheduler, scheduler_starter))
+        };
 
         let mhc = ModuleHostContext {
             dbic,
@@ -4,9 +4,13 @@ use crate::StandaloneEnv;
 use clap::ArgAction::SetTrue;
 use clap::{Arg, ArgMatches};
 use spacetimedb::config::{FilesGlobal, FilesLocal, SpacetimeDbFiles};
-use spacetimedb::db::{db_metrics, Config, FsyncPolicy, Storage};
+use spacetimedb::db::{db_metrics, Config, FsyncPolicy, Storage, DEFAULT_GROUP_COMMIT_BYTES};
 use spacetimedb::{startup, worker_metrics};
 use std::net::TcpListener;
+use std::time::Duration;
+
+/// The default number of commits between two snapshots of a database.
+const DEFAULT_SNAPSHOT_INTERVAL: &str = "100000";
 
 #[cfg(feature = "string")]
 impl From<std::string::String> for OsStr {
@@ -76,6 +80,32 @@ pub fn cli(mode: ProgramMode) -> clap::Command {
         .action(SetTrue)
         .help("If specified the database will fsync on each commit.");
 
+    let wal_group_commit_arg = Arg::new("wal_group_commit")
+        .long("wal-group-commit")
+        .value_name("MILLIS")
+        .value_parser(clap::value_parser!(u64))
+        .conflicts_with("wal_fsync")
+        .help("If specified the database will batch the commits made within this many milliseconds, and fsync them once for the whole batch.");
+
+    let wal_group_commit_bytes_arg = Arg::new("wal_group_commit_bytes")
+        .long("wal-group-commit-bytes")
+        .value_name("BYTES")
+        .value_parser(clap::value_parser!(usize))
+        .requires("wal_group_commit")
+        .help("The size of a batch of commits at which it's fsync'd without waiting for its window to elapse. Defaults to 1 MiB.");
+
+    let snapshot_interval_arg = Arg::new("snapshot_interval")
+        .long("snapshot-interval")
+        .value_name("COMMITS")
+        .value_parser(clap::value_parser!(u64))
+        .default_value(DEFAULT_SNAPSHOT_INTERVAL)
+        .help("The number of commits after which a snapshot of the database is taken, so that restarting it doesn't replay its entire log. 0 disables the snapshots.");
+
+    let compact_log_arg = Arg::new("compact_log")
+        .long("compact-log")
+        .action(SetTrue)
+        .help("If specified, taking a snapshot removes the segments of the log it covers. The history before the retained snapshots can then no longer be restored, forked or inspected.");
+
     // the default root for files, this *should* be the home directory unless it cannot be determined.
     let default_root = if let Some(dir) = dirs::home_dir() {
         dir
@@ -140,6 +170,10 @@ pub fn cli(mode: ProgramMode) -> clap::Command {
         .arg(jwt_priv_key_path_arg)
         .arg(in_memory_arg)
         .arg(wal_fsync_arg)
+        .arg(wal_group_commit_arg)
+        .arg(wal_group_commit_bytes_arg)
+        .arg(snapshot_interval_arg)
+        .arg(compact_log_arg)
         .after_help(mode.after_help())
 }
 
@@ -182,10 +216,25 @@ pub async fn exec(args: &ArgMatches) -> anyhow::Result<()> {
     };
     let fsync = if args.get_flag("wal_fsync") {
         FsyncPolicy::EveryTx
+    } else if let Some(&window) = args.get_one::<u64>("wal_group_commit") {
+        FsyncPolicy::GroupCommit {
+            window: Duration::from_millis(window),
+            max_bytes: args
+                .get_one::<usize>("wal_group_commit_bytes")
+                .copied()
+                .unwrap_or(DEFAULT_GROUP_COMMIT_BYTES),
+        }
     } else {
         FsyncPolicy::Never
     };
-    let config = Config { storage, fsync };
+    let snapshot_interval = Some(*args.get_one::<u64>("snapshot_interval").unwrap()).filter(|&n| n > 0);
+    let compact_log = args.get_flag("compact_log");
+    let config = Config {
+        storage,
+        fsync,
+        snapshot_interval,
+        compact_log,
+    };
 
     banner();
     let exe_name = std::env::current_exe()?;
@@ -13,7 +13,9 @@ use spacetimedb::config::{FilesLocal, SpacetimeDbFiles};
 use spacetimedb::database_logger::DatabaseLogger;
 use spacetimedb::db::{Config, FsyncPolicy, Storage};
 use spacetimedb::protobuf::client_api;
-use spacetimedb_client_api::{ControlStateReadAccess, ControlStateWriteAccess, DatabaseDef, NodeDelegate};
+use spacetimedb_client_api::{
+    ControlStateReadAccess, ControlStateWriteAccess, DatabaseDef, FsyncPolicyUpdate, NodeDelegate,
+};
 use spacetimedb_lib::sats;
 
 use spacetimedb_standalone::StandaloneEnv;
@@ -56,6 +58,7 @@ impl ModuleHandle {
             r#type: Some(client_api::message::Type::FunctionCall(client_api::FunctionCall {
                 reducer: reducer.to_string(),
                 arg_bytes: sats::bsatn::to_vec(&args)?,
+                request_id: 0,
             })),
         };
         self.send(message.encode_to_vec()).await
@@ -146,6 +149,7 @@ impl CompiledModule {
                 address: db_address,
                 program_bytes,
                 num_replicas: 1,
+                fsync: FsyncPolicyUpdate::Keep,
             },
         )
         .await
@@ -179,4 +183,6 @@ impl CompiledModule {
 pub static DEFAULT_CONFIG: Config = Config {
     storage: Storage::Disk,
     fsync: FsyncPolicy::Never,
+    snapshot_interval: None,
+    compact_log: false,
 };
@@ -1,6 +1,10 @@
+use std::cmp::Ordering;
 use std::collections::HashMap;
 
-use spacetimedb_lib::relation::{FieldExpr, MemTable, RelIter, Relation, Table};
+use spacetimedb_lib::error::RelationError;
+use spacetimedb_lib::relation::{
+    Column, FieldExpr, FieldName, Header, MemTable, RelIter, RelValueRef, Relation, Table,
+};
 use spacetimedb_sats::algebraic_type::AlgebraicType;
 use spacetimedb_sats::algebraic_value::AlgebraicValue;
 use spacetimedb_sats::{product, ProductType, ProductValue};
@@ -8,12 +12,13 @@ use spacetimedb_sats::{product, ProductType, ProductValue};
 use crate::dsl::{bin_op, call_fn, if_, mem_table, scalar, var};
 use crate::errors::{ErrorKind, ErrorLang, ErrorType, ErrorVm};
 use crate::expr::{
-    Code, CrudCode, CrudExpr, CrudExprOpt, Expr, ExprOpt, FunctionOpt, QueryCode, QueryExpr, QueryExprOpt, SourceExpr,
-    SourceExprOpt, TyExpr,
+    option_type, Code, CrudCode, CrudExpr, CrudExprOpt, Expr, ExprOpt, FunctionOpt, GroupByExpr, LimitExpr, QueryCode,
+    QueryExpr, QueryExprOpt, SortKey, SourceExpr, SourceExprOpt, TyExpr,
 };
 use crate::expr::{Function, Query};
 use crate::functions::{Args, Param};
 use crate::operator::*;
+use crate::ops::aggregate::Accumulator;
 use crate::program::ProgramVm;
 use crate::rel_ops::RelOps;
 use crate::typecheck::check_types;
@@ -401,6 +406,100 @@ pub fn eval<P: ProgramVm>(p: &mut P, code: Code) -> Code {
 
 pub type IterRows<'a> = dyn RelOps + 'a;
 
+/// Builds a comparator that orders rows of `header` by the `keys`, in order of precedence.
+///
+/// Fails if any of the `keys` is not a field of the `header`.
+pub fn sort_cmp(header: &Header, keys: &[SortKey]) -> Result<impl Fn(RelValueRef, RelValueRef) -> Ordering, ErrorVm> {
+    let mut positions = Vec::with_capacity(keys.len());
+    for key in keys {
+        let pos = header
+            .column_pos(&key.field)
+            .ok_or_else(|| RelationError::FieldNotFound(header.clone(), key.field.clone()))?;
+        positions.push((pos, key.asc));
+    }
+
+    Ok(move |lhs: RelValueRef, rhs: RelValueRef| {
+        for &(pos, asc) in &positions {
+            let order = lhs.data.elements[pos].cmp(&rhs.data.elements[pos]);
+            let order = if asc { order } else { order.reverse() };
+            if order != Ordering::Equal {
+                return order;
+            }
+        }
+        Ordering::Equal
+    })
+}
+
+/// Applies a [LimitExpr] to the `result`.
+pub fn build_limit(result: Box<IterRows<'_>>, limit: LimitExpr) -> Box<IterRows<'_>> {
+    let to_usize = |x: u64| usize::try_from(x).unwrap_or(usize::MAX);
+    Box::new(result.limit(to_usize(limit.offset), limit.limit.map(to_usize)))
+}
+
+/// Groups the rows of `result` by the keys of a [GroupByExpr] and computes its aggregates for each group.
+///
+/// Fails if any of the fields is not in the `result`, or an aggregate doesn't support the type of its field.
+pub fn build_aggregate(result: Box<IterRows<'_>>, group: GroupByExpr) -> Result<Box<IterRows<'_>>, ErrorVm> {
+    let header = result.head().clone();
+    let column_pos = |field: &FieldName| {
+        header
+            .column_pos(field)
+            .ok_or_else(|| RelationError::FieldNotFound(header.clone(), field.clone()))
+    };
+    // Without keys, there is a single group even if there are no rows, so the aggregates can be `NULL`.
+    let nullable = group.keys.is_empty();
+
+    let mut keys = Vec::with_capacity(group.keys.len());
+    let mut fields = Vec::with_capacity(group.keys.len() + group.aggs.len());
+    for key in &group.keys {
+        let pos = column_pos(key)?;
+        keys.push(pos);
+        fields.push(header.fields[pos].clone());
+    }
+    let mut aggs = Vec::with_capacity(group.aggs.len());
+    for agg in group.aggs {
+        let pos = agg.field.as_ref().map(column_pos).transpose()?;
+        let arg_ty = pos.map(|pos| &header.fields[pos].algebraic_type);
+        let is_option = arg_ty.and_then(option_type).is_some();
+        let ty = agg.result_type(arg_ty, nullable)?;
+        fields.push(Column::new(agg.field_name(&header.table_name), ty.clone()));
+        aggs.push((agg, pos, is_option, ty));
+    }
+    let head = Header::new(header.table_name.clone(), fields);
+
+    let aggs_init = aggs
+        .iter()
+        .map(|(agg, _, _, _)| Accumulator::new(agg.func))
+        .collect::<Vec<_>>();
+    let aggs_fold = aggs
+        .iter()
+        .map(|(agg, pos, is_option, _)| (agg.clone(), *pos, *is_option))
+        .collect::<Vec<_>>();
+    let iter = result.group_by(
+        head,
+        move |row| Ok(keys.iter().map(|&pos| row.data.elements[pos].clone()).collect()),
+        move || aggs_init.clone(),
+        move |state: &mut Vec<Accumulator>, row| {
+            for (acc, (agg, pos, is_option)) in state.iter_mut().zip(&aggs_fold) {
+                acc.update(agg, pos.map(|pos| &row.data.elements[pos]), *is_option)?;
+            }
+            Ok(())
+        },
+        move |mut key, state| {
+            for (acc, (agg, _, _, ty)) in state.into_iter().zip(&aggs) {
+                key.elements.push(acc.finish(agg, ty)?);
+            }
+            Ok(key)
+        },
+    );
+
+    Ok(if nullable {
+        Box::new(iter.with_group(ProductValue::new(&[])))
+    } else {
+        Box::new(iter)
+    })
+}
+
 #[tracing::instrument(skip_all)]
 pub fn build_query(mut result: Box<IterRows>, query: Vec<Query>) -> Result<Box<IterRows<'_>>, ErrorVm> {
     for q in query {
@@ -474,6 +573,12 @@ pub fn build_query(mut result: Box<IterRows>, query: Vec<Query>) -> Result<Box<I
                 )?;
                 Box::new(iter)
             }
+            Query::Sort(keys) => {
+                let cmp = sort_cmp(result.head(), &keys)?;
+                Box::new(result.sort_by(cmp))
+            }
+            Query::Limit(limit) => build_limit(result, limit),
+            Query::Aggregate(group) => build_aggregate(result, group)?,
         };
     }
     Ok(result)
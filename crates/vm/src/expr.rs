@@ -6,10 +6,11 @@ use spacetimedb_lib::relation::{
 };
 use spacetimedb_lib::table::ProductTypeMeta;
 use spacetimedb_lib::Identity;
+use spacetimedb_sats::algebraic_type::fmt::fmt_algebraic_type;
 use spacetimedb_sats::algebraic_type::AlgebraicType;
 use spacetimedb_sats::algebraic_value::AlgebraicValue;
 use spacetimedb_sats::satn::Satn;
-use spacetimedb_sats::{ProductValue, Typespace, WithTypespace};
+use spacetimedb_sats::{BuiltinType, ProductValue, Typespace, WithTypespace};
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fmt;
@@ -141,13 +142,13 @@ impl ColumnOp {
                     OpCmp::GtEq => lhs >= rhs,
                 })
             }
+            // Short-circuits, so the conditions are evaluated in the order they were planned.
             OpQuery::Logic(op) => {
                 let lhs = self.reduce_bool(row, lhs, header)?;
-                let rhs = self.reduce_bool(row, rhs, header)?;
 
                 Ok(match op {
-                    OpLogic::And => lhs && rhs,
-                    OpLogic::Or => lhs || rhs,
+                    OpLogic::And => lhs && self.reduce_bool(row, rhs, header)?,
+                    OpLogic::Or => lhs || self.reduce_bool(row, rhs, header)?,
                 })
             }
         }
@@ -254,7 +255,8 @@ impl From<IndexScan> for ColumnOp {
                 lhs: field.into(),
                 rhs: value.into(),
             },
-            (Bound::Unbounded, Bound::Unbounded) => unreachable!(),
+            // A full index scan, used to stream rows in index order => true
+            (Bound::Unbounded, Bound::Unbounded) => ColumnOp::Field(AlgebraicValue::Bool(true).into()),
             (lower_bound, upper_bound) => {
                 let lhs = IndexScan {
                     table: table.clone(),
@@ -509,6 +511,147 @@ impl Ord for IndexScan {
     }
 }
 
+/// A sort key, as in `ORDER BY field [ASC | DESC]`.
+#[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash)]
+pub struct SortKey {
+    pub field: FieldName,
+    pub asc: bool,
+}
+
+impl SortKey {
+    pub fn new(field: FieldName, asc: bool) -> Self {
+        Self { field, asc }
+    }
+}
+
+/// The `LIMIT` and `OFFSET` of a query.
+///
+/// Skips the first `offset` rows and then returns at most `limit` rows.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, PartialOrd, Ord, Hash)]
+pub struct LimitExpr {
+    pub offset: u64,
+    pub limit: Option<u64>,
+}
+
+/// An aggregate function, as in `SELECT COUNT(*) ...`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash)]
+pub enum AggFn {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg,
+}
+
+impl AggFn {
+    /// Parses the (case-insensitive) name of an aggregate function.
+    pub fn parse(name: &str) -> Option<Self> {
+        Some(match name.to_uppercase().as_str() {
+            "COUNT" => Self::Count,
+            "SUM" => Self::Sum,
+            "MIN" => Self::Min,
+            "MAX" => Self::Max,
+            "AVG" => Self::Avg,
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for AggFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Count => "COUNT",
+            Self::Sum => "SUM",
+            Self::Min => "MIN",
+            Self::Max => "MAX",
+            Self::Avg => "AVG",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// An aggregate function over a field, or over the whole rows for `COUNT(*)`.
+#[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash)]
+pub struct AggExpr {
+    pub func: AggFn,
+    pub field: Option<FieldName>,
+}
+
+impl AggExpr {
+    pub fn new(func: AggFn, field: Option<FieldName>) -> Self {
+        Self { func, field }
+    }
+
+    /// The name of the column with the result of the aggregate, in the relation `table`.
+    pub fn field_name(&self, table: &str) -> FieldName {
+        FieldName::named(table, &self.to_string())
+    }
+
+    /// Infers the type of the result of the aggregate, given the type `ty` of its field.
+    ///
+    /// `COUNT` is always a `U64`.
+    /// `SUM` widens its integers to 64 bits (or keeps 128) and its floats to `F64`.
+    /// `AVG` is a `F64`, and `MIN` & `MAX` keep the type of the field.
+    ///
+    /// Except `COUNT`, they are `NULL` over an empty input, so the result is an `Option` if `nullable`.
+    /// The `NULL`s of an `Option<T>` field are skipped, so it is aggregated as a `T` into a nullable result.
+    pub fn result_type(&self, ty: Option<&AlgebraicType>, nullable: bool) -> Result<AlgebraicType, ErrorType> {
+        use BuiltinType::*;
+
+        let (ty, nullable) = match ty.and_then(option_type) {
+            Some(ty) => (Some(ty), true),
+            None => (ty, nullable),
+        };
+
+        let ty = match (self.func, ty) {
+            (AggFn::Count, _) => return Ok(AlgebraicType::U64),
+            (AggFn::Min | AggFn::Max, Some(ty)) => ty.clone(),
+            (AggFn::Sum, Some(AlgebraicType::Builtin(I8 | I16 | I32 | I64))) => AlgebraicType::I64,
+            (AggFn::Sum, Some(AlgebraicType::Builtin(U8 | U16 | U32 | U64))) => AlgebraicType::U64,
+            (AggFn::Sum, Some(AlgebraicType::Builtin(I128))) => AlgebraicType::I128,
+            (AggFn::Sum, Some(AlgebraicType::Builtin(U128))) => AlgebraicType::U128,
+            (AggFn::Sum, Some(AlgebraicType::Builtin(F32 | F64))) => AlgebraicType::F64,
+            (
+                AggFn::Avg,
+                Some(AlgebraicType::Builtin(I8 | U8 | I16 | U16 | I32 | U32 | I64 | U64 | I128 | U128 | F32 | F64)),
+            ) => AlgebraicType::F64,
+            (_, ty) => {
+                return Err(ErrorType::Aggregate {
+                    agg: self.to_string(),
+                    ty: ty.map(|ty| fmt_algebraic_type(ty).to_string()).unwrap_or_default(),
+                })
+            }
+        };
+        Ok(if nullable { AlgebraicType::option(ty) } else { ty })
+    }
+}
+
+impl fmt::Display for AggExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.field {
+            Some(field) => write!(f, "{}({field})", self.func),
+            None => write!(f, "{}(*)", self.func),
+        }
+    }
+}
+
+/// Returns `T` if `ty` is an `Option<T>`, i.e. a nullable type.
+pub(crate) fn option_type(ty: &AlgebraicType) -> Option<&AlgebraicType> {
+    match ty {
+        AlgebraicType::Sum(sum) => sum.as_option(),
+        _ => None,
+    }
+}
+
+/// The `GROUP BY keys` of a query, with the aggregates to compute for each group.
+///
+/// Without `keys`, all the rows are aggregated into a single group, even if there are none.
+#[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash)]
+pub struct GroupByExpr {
+    pub keys: Vec<FieldName>,
+    pub aggs: Vec<AggExpr>,
+}
+
 // An individual operation in a query.
 #[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord)]
 pub enum Query {
@@ -529,6 +672,16 @@ pub enum Query {
     // Equivalent to a Nested Loop Join.
     // Its operands my use indexes but the join itself does not.
     JoinInner(JoinExpr),
+    // Sorts an intermediate relation by a list of keys.
+    // It materializes its input.
+    // If the rows could be streamed in index order it would have been planned as an IndexScan.
+    Sort(Vec<SortKey>),
+    // Skips and truncates the rows of an intermediate relation.
+    Limit(LimitExpr),
+    // Groups the rows of an intermediate relation and computes the aggregates of each group.
+    // It materializes its input.
+    // The result has the columns of the keys followed by one for each aggregate.
+    Aggregate(GroupByExpr),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -934,6 +1087,52 @@ impl QueryExpr {
         x
     }
 
+    // Appends a sort operation to the query operator pipeline.
+    pub fn with_sort(self, keys: &[SortKey]) -> Self {
+        let mut x = self;
+        if !keys.is_empty() {
+            x.query.push(Query::Sort(keys.into()));
+        }
+        x
+    }
+
+    // Appends an aggregate operation to the query operator pipeline.
+    pub fn with_aggregate(self, keys: &[FieldName], aggs: &[AggExpr]) -> Self {
+        let mut x = self;
+        x.query.push(Query::Aggregate(GroupByExpr {
+            keys: keys.into(),
+            aggs: aggs.into(),
+        }));
+        x
+    }
+
+    // Appends a limit operation to the query operator pipeline,
+    // or tightens the preceding one.
+    pub fn with_limit(mut self, offset: u64, limit: Option<u64>) -> Self {
+        if offset == 0 && limit.is_none() {
+            return self;
+        }
+        match self.query.pop() {
+            Some(Query::Limit(prev)) => {
+                // `prev` is applied first, so skip into its window and clamp to what remains of it.
+                let remaining = prev.limit.map(|l| l.saturating_sub(offset));
+                let limit = match (remaining, limit) {
+                    (Some(a), Some(b)) => Some(a.min(b)),
+                    (a, b) => a.or(b),
+                };
+                self.query.push(Query::Limit(LimitExpr {
+                    offset: prev.offset.saturating_add(offset),
+                    limit,
+                }));
+            }
+            query => {
+                self.query.extend(query);
+                self.query.push(Query::Limit(LimitExpr { offset, limit }));
+            }
+        }
+        self
+    }
+
     fn bound(value: AlgebraicValue, inclusive: bool) -> Bound<AlgebraicValue> {
         if inclusive {
             Bound::Included(value)
@@ -1118,6 +1317,43 @@ impl fmt::Display for Query {
             Query::JoinInner(q) => {
                 write!(f, "&inner {:?} ON {} = {}", q.rhs, q.col_lhs, q.col_rhs)
             }
+            Query::Sort(keys) => {
+                write!(f, "sort ")?;
+                for (pos, x) in keys.iter().enumerate() {
+                    write!(f, "{} {}", x.field, if x.asc { "ASC" } else { "DESC" })?;
+                    if pos + 1 < keys.len() {
+                        write!(f, ", ")?;
+                    }
+                }
+                Ok(())
+            }
+            Query::Aggregate(GroupByExpr { keys, aggs }) => {
+                write!(f, "aggregate ")?;
+                for (pos, x) in aggs.iter().enumerate() {
+                    write!(f, "{x}")?;
+                    if pos + 1 < aggs.len() {
+                        write!(f, ", ")?;
+                    }
+                }
+                if !keys.is_empty() {
+                    write!(f, " group by ")?;
+                }
+                for (pos, x) in keys.iter().enumerate() {
+                    write!(f, "{x}")?;
+                    if pos + 1 < keys.len() {
+                        write!(f, ", ")?;
+                    }
+                }
+                Ok(())
+            }
+            Query::Limit(LimitExpr { offset, limit }) => {
+                write!(f, "limit ")?;
+                match limit {
+                    Some(limit) => write!(f, "{limit}")?,
+                    None => write!(f, "ALL")?,
+                }
+                write!(f, " offset {offset}")
+            }
         }
     }
 }
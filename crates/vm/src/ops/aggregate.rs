@@ -0,0 +1,169 @@
+//! The accumulators of the aggregate functions [AggFn]
+use crate::errors::{ErrorType, ErrorVm};
+use crate::expr::{option_type, AggExpr, AggFn};
+use spacetimedb_sats::algebraic_type::fmt::fmt_algebraic_type;
+use spacetimedb_sats::algebraic_type::AlgebraicType;
+use spacetimedb_sats::algebraic_value::{AlgebraicValue, F64};
+use spacetimedb_sats::SumValue;
+
+/// A number widened for accumulating a `SUM` or `AVG` without overflow of the field type.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Number {
+    Int(i128),
+    UInt(u128),
+    Float(f64),
+}
+
+impl Number {
+    fn new(value: &AlgebraicValue) -> Option<Self> {
+        use AlgebraicValue::*;
+        Some(match *value {
+            I8(x) => Self::Int(x.into()),
+            I16(x) => Self::Int(x.into()),
+            I32(x) => Self::Int(x.into()),
+            I64(x) => Self::Int(x.into()),
+            I128(x) => Self::Int(x),
+            U8(x) => Self::UInt(x.into()),
+            U16(x) => Self::UInt(x.into()),
+            U32(x) => Self::UInt(x.into()),
+            U64(x) => Self::UInt(x.into()),
+            U128(x) => Self::UInt(x),
+            F32(x) => Self::Float(x.into_inner().into()),
+            F64(x) => Self::Float(x.into_inner()),
+            _ => return None,
+        })
+    }
+
+    fn checked_add(self, rhs: Self) -> Option<Self> {
+        match (self, rhs) {
+            (Self::Int(a), Self::Int(b)) => a.checked_add(b).map(Self::Int),
+            (Self::UInt(a), Self::UInt(b)) => a.checked_add(b).map(Self::UInt),
+            (Self::Float(a), Self::Float(b)) => Some(Self::Float(a + b)),
+            _ => None,
+        }
+    }
+
+    fn as_f64(self) -> f64 {
+        match self {
+            Self::Int(x) => x as f64,
+            Self::UInt(x) => x as f64,
+            Self::Float(x) => x,
+        }
+    }
+
+    /// Converts the number to the type `ty` inferred by [AggExpr::result_type] for a `SUM`.
+    fn into_value(self, ty: &AlgebraicType) -> Option<AlgebraicValue> {
+        Some(match self {
+            Self::Int(x) if *ty == AlgebraicType::I64 => AlgebraicValue::I64(x.try_into().ok()?),
+            Self::Int(x) => AlgebraicValue::I128(x),
+            Self::UInt(x) if *ty == AlgebraicType::U64 => AlgebraicValue::U64(x.try_into().ok()?),
+            Self::UInt(x) => AlgebraicValue::U128(x),
+            Self::Float(x) => AlgebraicValue::F64(x.into()),
+        })
+    }
+}
+
+/// The state of an aggregate function over the rows of a group.
+#[derive(Debug, Clone)]
+pub(crate) enum Accumulator {
+    Count(u64),
+    Sum(Option<Number>),
+    Avg(Option<Number>, u64),
+    Min(Option<AlgebraicValue>),
+    Max(Option<AlgebraicValue>),
+}
+
+impl Accumulator {
+    pub(crate) fn new(func: AggFn) -> Self {
+        match func {
+            AggFn::Count => Self::Count(0),
+            AggFn::Sum => Self::Sum(None),
+            AggFn::Avg => Self::Avg(None, 0),
+            AggFn::Min => Self::Min(None),
+            AggFn::Max => Self::Max(None),
+        }
+    }
+
+    /// Accumulates the `value` of the field of a row, or `None` for `COUNT(*)`.
+    ///
+    /// When the field `is_option`, its `NULL` values are ignored, except by `COUNT(*)`,
+    /// and the others are accumulated without their `some`.
+    pub(crate) fn update(
+        &mut self,
+        agg: &AggExpr,
+        value: Option<&AlgebraicValue>,
+        is_option: bool,
+    ) -> Result<(), ErrorVm> {
+        let value = match value {
+            Some(AlgebraicValue::Sum(SumValue { tag, value })) if is_option => match tag {
+                0 => &**value,
+                _ => return Ok(()),
+            },
+            Some(x) => x,
+            None => {
+                if let Self::Count(count) = self {
+                    *count += 1;
+                }
+                return Ok(());
+            }
+        };
+        let overflow = || ErrorVm::Overflow(agg.to_string());
+        let add = |total: &mut Option<Number>| {
+            let x = Number::new(value).ok_or_else(|| ErrorType::Aggregate {
+                agg: agg.to_string(),
+                ty: fmt_algebraic_type(&value.type_of()).to_string(),
+            })?;
+            *total = Some(match *total {
+                Some(total) => total.checked_add(x).ok_or_else(overflow)?,
+                None => x,
+            });
+            Ok::<_, ErrorVm>(())
+        };
+
+        match self {
+            Self::Count(count) => *count += 1,
+            Self::Sum(total) => add(total)?,
+            Self::Avg(total, count) => {
+                add(total)?;
+                *count += 1;
+            }
+            Self::Min(min) => {
+                if min.as_ref().map_or(true, |min| value < min) {
+                    *min = Some(value.clone());
+                }
+            }
+            Self::Max(max) => {
+                if max.as_ref().map_or(true, |max| value > max) {
+                    *max = Some(value.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the result of the aggregate, of the type `ty` inferred by [AggExpr::result_type].
+    ///
+    /// The result is `NULL` if no value was accumulated, in which case `ty` is an `Option`.
+    pub(crate) fn finish(self, agg: &AggExpr, ty: &AlgebraicType) -> Result<AlgebraicValue, ErrorVm> {
+        let (ty, nullable) = match option_type(ty) {
+            Some(ty) => (ty, true),
+            None => (ty, false),
+        };
+        let value = match self {
+            Self::Count(count) => return Ok(count.into()),
+            Self::Sum(total) => total
+                .map(|total| total.into_value(ty).ok_or_else(|| ErrorVm::Overflow(agg.to_string())))
+                .transpose()?,
+            Self::Avg(total, count) => total.map(|total| AlgebraicValue::F64(F64::from(total.as_f64() / count as f64))),
+            Self::Min(x) | Self::Max(x) => x,
+        };
+        Ok(match (nullable, value) {
+            (true, Some(x)) => AlgebraicValue::OptionSome(x),
+            (true, None) => AlgebraicValue::OptionNone(),
+            (false, Some(x)) => x,
+            // Only the single group of an aggregate without `GROUP BY`, or a group of only `NULL`s, can lack values,
+            // and then the result is nullable.
+            (false, None) => unreachable!("Aggregate `{agg}` over a group without values"),
+        })
+    }
+}
@@ -1,4 +1,5 @@
 //! Implements the in-built operators & functions loaded by the `vm`
+pub(crate) mod aggregate;
 pub(crate) mod logic;
 pub(crate) mod math;
 pub mod parse;
@@ -1,7 +1,8 @@
 use crate::errors::ErrorVm;
 use spacetimedb_lib::relation::{FieldExpr, Header, RelValue, RelValueRef, RowCount};
 use spacetimedb_sats::product_value::ProductValue;
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{btree_map, BTreeMap, HashMap};
 
 pub(crate) trait ResultExt<T> {
     fn unpack_fold(self) -> Result<T, ErrorVm>;
@@ -100,6 +101,82 @@ pub trait RelOps {
         Ok(JoinInner::new(head, self, with, key_lhs, key_rhs, predicate, project))
     }
 
+    /// Creates an `Iterator` that yields the rows sorted with the comparator function `cmp`.
+    ///
+    /// The sort is stable and requires collecting all the rows of the input before yielding the first one.
+    ///
+    /// Note:
+    ///
+    /// It is the equivalent of a `ORDER BY` clause on SQL.
+    #[inline]
+    fn sort_by<C>(self, cmp: C) -> Sort<Self, C>
+    where
+        C: FnMut(RelValueRef, RelValueRef) -> Ordering,
+        Self: Sized,
+    {
+        let count = self.row_count();
+        let head = self.head().clone();
+        Sort::new(self, count, head, cmp)
+    }
+
+    /// Creates an `Iterator` that skips the first `offset` rows and yields at most `limit` of the rest.
+    ///
+    /// Note:
+    ///
+    /// It is the equivalent of a `LIMIT ... OFFSET ...` clause on SQL.
+    #[inline]
+    fn limit(self, offset: usize, limit: Option<usize>) -> Limit<Self>
+    where
+        Self: Sized,
+    {
+        let count = self.row_count();
+        let clamp = |rows: usize| {
+            let rows = rows.saturating_sub(offset);
+            limit.map_or(rows, |l| rows.min(l))
+        };
+        let count = RowCount {
+            min: clamp(count.min),
+            max: count.max.map(clamp).or(limit),
+        };
+        let head = self.head().clone();
+        Limit::new(self, count, head, offset, limit)
+    }
+
+    /// Creates an `Iterator` that yields a single row for each group of rows with the same key.
+    ///
+    /// The closure `key` extracts the key of the group of a [RelValueRef],
+    /// `fold` accumulates the row into the state of its group, that starts as `init()`,
+    /// and `finish` builds the row of each group from its key and final state.
+    ///
+    /// The groups are yielded in order of their keys, and `head` is the [Header] of the rows built by `finish`.
+    ///
+    /// Note:
+    ///
+    /// It is the equivalent of a `GROUP BY` clause on SQL.
+    #[inline]
+    fn group_by<K, S, Init, Fold, Fin>(
+        self,
+        head: Header,
+        key: K,
+        init: Init,
+        fold: Fold,
+        finish: Fin,
+    ) -> GroupBy<Self, K, S, Init, Fold, Fin>
+    where
+        K: FnMut(RelValueRef) -> Result<ProductValue, ErrorVm>,
+        Init: FnMut() -> S,
+        Fold: FnMut(&mut S, RelValueRef) -> Result<(), ErrorVm>,
+        Fin: FnMut(ProductValue, S) -> Result<ProductValue, ErrorVm>,
+        Self: Sized,
+    {
+        let count = self.row_count();
+        let count = RowCount {
+            min: count.min.min(1),
+            max: count.max,
+        };
+        GroupBy::new(self, count, head, key, init, fold, finish)
+    }
+
     /// Utility to collect the results into a [Vec]
     #[inline]
     fn collect_vec(mut self) -> Result<Vec<RelValue>, ErrorVm>
@@ -219,6 +296,183 @@ where
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct Sort<I, C> {
+    pub(crate) head: Header,
+    pub(crate) count: RowCount,
+    pub(crate) iter: I,
+    pub(crate) cmp: C,
+    sorted: Option<std::vec::IntoIter<RelValue>>,
+}
+
+impl<I, C> Sort<I, C> {
+    pub fn new(iter: I, count: RowCount, head: Header, cmp: C) -> Sort<I, C> {
+        Sort {
+            iter,
+            count,
+            cmp,
+            head,
+            sorted: None,
+        }
+    }
+}
+
+impl<I, C> RelOps for Sort<I, C>
+where
+    I: RelOps,
+    C: FnMut(RelValueRef, RelValueRef) -> Ordering,
+{
+    fn head(&self) -> &Header {
+        &self.head
+    }
+
+    fn row_count(&self) -> RowCount {
+        self.count
+    }
+
+    #[tracing::instrument(skip_all)]
+    fn next(&mut self) -> Result<Option<RelValue>, ErrorVm> {
+        if self.sorted.is_none() {
+            let mut rows = Vec::with_capacity(self.count.min);
+            while let Some(v) = self.iter.next()? {
+                rows.push(v);
+            }
+            let cmp = &mut self.cmp;
+            rows.sort_by(|a, b| cmp(a.as_val_ref(), b.as_val_ref()));
+            self.sorted = Some(rows.into_iter());
+        }
+        Ok(self.sorted.as_mut().and_then(|rows| rows.next()))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Limit<I> {
+    pub(crate) head: Header,
+    pub(crate) count: RowCount,
+    pub(crate) iter: I,
+    offset: usize,
+    remaining: Option<usize>,
+}
+
+impl<I> Limit<I> {
+    pub fn new(iter: I, count: RowCount, head: Header, offset: usize, limit: Option<usize>) -> Limit<I> {
+        Limit {
+            iter,
+            count,
+            head,
+            offset,
+            remaining: limit,
+        }
+    }
+}
+
+impl<I> RelOps for Limit<I>
+where
+    I: RelOps,
+{
+    fn head(&self) -> &Header {
+        &self.head
+    }
+
+    fn row_count(&self) -> RowCount {
+        self.count
+    }
+
+    #[tracing::instrument(skip_all)]
+    fn next(&mut self) -> Result<Option<RelValue>, ErrorVm> {
+        if self.remaining == Some(0) {
+            return Ok(None);
+        }
+        while self.offset > 0 {
+            if self.iter.next()?.is_none() {
+                return Ok(None);
+            }
+            self.offset -= 1;
+        }
+        let next = self.iter.next()?;
+        if next.is_some() {
+            if let Some(remaining) = &mut self.remaining {
+                *remaining -= 1;
+            }
+        }
+        Ok(next)
+    }
+}
+
+pub struct GroupBy<I, K, S, Init, Fold, Fin> {
+    pub(crate) head: Header,
+    pub(crate) count: RowCount,
+    pub(crate) iter: I,
+    pub(crate) key: K,
+    pub(crate) init: Init,
+    pub(crate) fold: Fold,
+    pub(crate) finish: Fin,
+    seed: Option<ProductValue>,
+    groups: Option<btree_map::IntoIter<ProductValue, S>>,
+}
+
+impl<I, K, S, Init, Fold, Fin> GroupBy<I, K, S, Init, Fold, Fin> {
+    pub fn new(iter: I, count: RowCount, head: Header, key: K, init: Init, fold: Fold, finish: Fin) -> Self {
+        GroupBy {
+            head,
+            count,
+            iter,
+            key,
+            init,
+            fold,
+            finish,
+            seed: None,
+            groups: None,
+        }
+    }
+
+    /// Yields the group of `key` even if no row falls into it,
+    /// like the single group of an aggregate without `GROUP BY`.
+    pub fn with_group(mut self, key: ProductValue) -> Self {
+        self.count.min = self.count.min.max(1);
+        self.count.max = self.count.max.map(|max| max.max(1));
+        self.seed = Some(key);
+        self
+    }
+}
+
+impl<I, K, S, Init, Fold, Fin> RelOps for GroupBy<I, K, S, Init, Fold, Fin>
+where
+    I: RelOps,
+    K: FnMut(RelValueRef) -> Result<ProductValue, ErrorVm>,
+    Init: FnMut() -> S,
+    Fold: FnMut(&mut S, RelValueRef) -> Result<(), ErrorVm>,
+    Fin: FnMut(ProductValue, S) -> Result<ProductValue, ErrorVm>,
+{
+    fn head(&self) -> &Header {
+        &self.head
+    }
+
+    fn row_count(&self) -> RowCount {
+        self.count
+    }
+
+    #[tracing::instrument(skip_all)]
+    fn next(&mut self) -> Result<Option<RelValue>, ErrorVm> {
+        if self.groups.is_none() {
+            let mut groups = BTreeMap::new();
+            if let Some(key) = self.seed.take() {
+                groups.insert(key, (self.init)());
+            }
+            while let Some(v) = self.iter.next()? {
+                let key = (self.key)(v.as_val_ref())?;
+                let state = groups.entry(key).or_insert_with(&mut self.init);
+                (self.fold)(state, v.as_val_ref())?;
+            }
+            self.groups = Some(groups.into_iter());
+        }
+        match self.groups.as_mut().and_then(|groups| groups.next()) {
+            Some((key, state)) => Ok(Some(RelValue::new((self.finish)(key, state)?, None))),
+            None => Ok(None),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct JoinInner<Lhs, Rhs, KeyLhs, KeyRhs, Pred, Proj> {
     pub(crate) head: Header,
@@ -1,6 +1,7 @@
 #![allow(clippy::disallowed_names)]
 use spacetimedb::{
-    delete_by_col_eq, query, spacetimedb, AlgebraicValue, Deserialize, ReducerContext, SpacetimeType, Timestamp,
+    delete_by_col_eq, query, spacetimedb, AlgebraicValue, Deserialize, Identity, MisfirePolicy, ReducerContext, Repeat,
+    SpacetimeType, Timestamp,
 };
 use spacetimedb_lib::bsatn;
 
@@ -27,6 +28,7 @@ pub enum TestC {
 
 #[spacetimedb(table)]
 pub struct TestD {
+    #[default(Some(TestC::Bar))]
     test_c: Option<TestC>,
 }
 
@@ -44,6 +46,12 @@ pub struct _Private {
     name: String,
 }
 
+#[spacetimedb(table, filter = "owner = :sender")]
+pub struct Note {
+    owner: Identity,
+    text: String,
+}
+
 pub type TestAlias = TestA;
 
 // #[spacetimedb(migrate)]
@@ -52,6 +60,11 @@ pub type TestAlias = TestA;
 #[spacetimedb(init)]
 pub fn init() {
     spacetimedb::schedule!("1000ms", repeating_test(_, Timestamp::now()));
+    spacetimedb::schedule_repeating!(
+        Repeat::cron("0 4 * * mon").unwrap(),
+        MisfirePolicy::Skip,
+        weekly_test(_)
+    );
 }
 
 #[spacetimedb(update)]
@@ -65,6 +78,16 @@ pub fn repeating_test(ctx: ReducerContext, prev_time: Timestamp) {
     log::trace!("Timestamp: {:?}, Delta time: {:?}", ctx.timestamp, delta_time);
 }
 
+#[spacetimedb(reducer, repeat = "cron 0 4 * * *", misfire = skip)]
+pub fn nightly_test(ctx: ReducerContext) {
+    log::info!("Nightly at {:?}", ctx.timestamp);
+}
+
+#[spacetimedb(reducer)]
+pub fn weekly_test(ctx: ReducerContext) {
+    log::info!("Weekly at {:?}", ctx.timestamp);
+}
+
 #[spacetimedb(reducer)]
 pub fn test(ctx: ReducerContext, arg: TestAlias, arg2: TestB, arg3: TestC) -> anyhow::Result<()> {
     log::info!("BEGIN");
@@ -120,6 +143,23 @@ pub fn add_player(name: String) -> Result<(), String> {
     Ok(())
 }
 
+#[spacetimedb(reducer, allow = owner)]
+pub fn clear_players() {
+    for player in TestE::iter() {
+        TestE::delete_by_id(&player.id);
+    }
+}
+
+fn has_address(ctx: &ReducerContext) -> bool {
+    ctx.address.is_some()
+}
+
+#[spacetimedb(reducer, allow = has_address)]
+pub fn rename_player(id: u64, name: String) -> Result<(), String> {
+    TestE::update_by_id(&id, TestE { id, name });
+    Ok(())
+}
+
 #[spacetimedb(connect)]
 fn on_connect(_ctx: ReducerContext) {}
 